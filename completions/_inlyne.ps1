@@ -21,18 +21,61 @@ Register-ArgumentCompleter -Native -CommandName 'inlyne' -ScriptBlock {
 
     $completions = @(switch ($command) {
         'inlyne' {
-            [CompletionResult]::new('-t', 't', [CompletionResultType]::ParameterName, 'Theme to use when rendering')
-            [CompletionResult]::new('--theme', 'theme', [CompletionResultType]::ParameterName, 'Theme to use when rendering')
+            [CompletionResult]::new('-t', 't', [CompletionResultType]::ParameterName, 'Theme to use when rendering, or the name of one from the config')
+            [CompletionResult]::new('--theme', 'theme', [CompletionResultType]::ParameterName, 'Theme to use when rendering, or the name of one from the config')
             [CompletionResult]::new('-s', 's', [CompletionResultType]::ParameterName, 'Factor to scale rendered file by [default: OS defined window scale factor]')
             [CompletionResult]::new('--scale', 'scale', [CompletionResultType]::ParameterName, 'Factor to scale rendered file by [default: OS defined window scale factor]')
             [CompletionResult]::new('-c', 'c', [CompletionResultType]::ParameterName, 'Configuration file to use')
             [CompletionResult]::new('--config', 'config', [CompletionResultType]::ParameterName, 'Configuration file to use')
             [CompletionResult]::new('-w', 'w', [CompletionResultType]::ParameterName, 'Maximum width of page in pixels')
             [CompletionResult]::new('--page-width', 'page-width', [CompletionResultType]::ParameterName, 'Maximum width of page in pixels')
+            [CompletionResult]::new('--clipboard', 'clipboard', [CompletionResultType]::ParameterName, 'Preview the clipboard''s contents as markdown instead of a file')
+            [CompletionResult]::new('--no-cache', 'no-cache', [CompletionResultType]::ParameterName, 'Download remote images every time instead of caching them on disk')
             [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', 'V ', [CompletionResultType]::ParameterName, 'Print version')
             [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('serve', 'serve', [CompletionResultType]::ParameterValue, 'Serve a directory of markdown files as themed HTML with live-reload')
+            [CompletionResult]::new('bench', 'bench', [CompletionResultType]::ParameterValue, 'Measure how long each stage of rendering a file takes')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
+            break
+        }
+        'inlyne;serve' {
+            [CompletionResult]::new('-p', 'p', [CompletionResultType]::ParameterName, 'Port to listen on')
+            [CompletionResult]::new('--port', 'port', [CompletionResultType]::ParameterName, 'Port to listen on')
+            [CompletionResult]::new('-t', 't', [CompletionResultType]::ParameterName, 'Theme to use when rendering, or the name of one from the config')
+            [CompletionResult]::new('--theme', 'theme', [CompletionResultType]::ParameterName, 'Theme to use when rendering, or the name of one from the config')
+            [CompletionResult]::new('-c', 'c', [CompletionResultType]::ParameterName, 'Configuration file to use')
+            [CompletionResult]::new('--config', 'config', [CompletionResultType]::ParameterName, 'Configuration file to use')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'inlyne;bench' {
+            [CompletionResult]::new('-f', 'f', [CompletionResultType]::ParameterName, 'Number of frames to render after the first when measuring frame times')
+            [CompletionResult]::new('--frames', 'frames', [CompletionResultType]::ParameterName, 'Number of frames to render after the first when measuring frame times')
+            [CompletionResult]::new('-t', 't', [CompletionResultType]::ParameterName, 'Theme to use when rendering, or the name of one from the config')
+            [CompletionResult]::new('--theme', 'theme', [CompletionResultType]::ParameterName, 'Theme to use when rendering, or the name of one from the config')
+            [CompletionResult]::new('-c', 'c', [CompletionResultType]::ParameterName, 'Configuration file to use')
+            [CompletionResult]::new('--config', 'config', [CompletionResultType]::ParameterName, 'Configuration file to use')
+            [CompletionResult]::new('--json', 'json', [CompletionResultType]::ParameterName, 'Output the results as JSON')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'inlyne;help' {
+            [CompletionResult]::new('serve', 'serve', [CompletionResultType]::ParameterValue, 'Serve a directory of markdown files as themed HTML with live-reload')
+            [CompletionResult]::new('bench', 'bench', [CompletionResultType]::ParameterValue, 'Measure how long each stage of rendering a file takes')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
+            break
+        }
+        'inlyne;help;serve' {
+            break
+        }
+        'inlyne;help;bench' {
+            break
+        }
+        'inlyne;help;help' {
             break
         }
     })
@@ -1,6 +1,23 @@
-complete -c inlyne -s t -l theme -d 'Theme to use when rendering' -r -f -a "{auto	'',dark	'',light	''}"
-complete -c inlyne -s s -l scale -d 'Factor to scale rendered file by [default: OS defined window scale factor]' -r
-complete -c inlyne -s c -l config -d 'Configuration file to use' -r -F
-complete -c inlyne -s w -l page-width -d 'Maximum width of page in pixels' -r
-complete -c inlyne -s h -l help -d 'Print help'
-complete -c inlyne -s V -l version -d 'Print version'
+complete -c inlyne -n "__fish_use_subcommand" -s t -l theme -d 'Theme to use when rendering, or the name of one from the config' -r -f -a "{auto	'',dark	'',light	''}"
+complete -c inlyne -n "__fish_use_subcommand" -s s -l scale -d 'Factor to scale rendered file by [default: OS defined window scale factor]' -r
+complete -c inlyne -n "__fish_use_subcommand" -s c -l config -d 'Configuration file to use' -r -F
+complete -c inlyne -n "__fish_use_subcommand" -s w -l page-width -d 'Maximum width of page in pixels' -r
+complete -c inlyne -n "__fish_use_subcommand" -l clipboard -d 'Preview the clipboard\'s contents as markdown instead of a file'
+complete -c inlyne -n "__fish_use_subcommand" -l no-cache -d 'Download remote images every time instead of caching them on disk'
+complete -c inlyne -n "__fish_use_subcommand" -s h -l help -d 'Print help'
+complete -c inlyne -n "__fish_use_subcommand" -s V -l version -d 'Print version'
+complete -c inlyne -n "__fish_use_subcommand" -f -a "serve" -d 'Serve a directory of markdown files as themed HTML with live-reload'
+complete -c inlyne -n "__fish_use_subcommand" -f -a "bench" -d 'Measure how long each stage of rendering a file takes'
+complete -c inlyne -n "__fish_use_subcommand" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c inlyne -n "__fish_seen_subcommand_from serve" -s p -l port -d 'Port to listen on' -r
+complete -c inlyne -n "__fish_seen_subcommand_from serve" -s t -l theme -d 'Theme to use when rendering, or the name of one from the config' -r -f -a "{auto	'',dark	'',light	''}"
+complete -c inlyne -n "__fish_seen_subcommand_from serve" -s c -l config -d 'Configuration file to use' -r -F
+complete -c inlyne -n "__fish_seen_subcommand_from serve" -s h -l help -d 'Print help'
+complete -c inlyne -n "__fish_seen_subcommand_from bench" -s f -l frames -d 'Number of frames to render after the first when measuring frame times' -r
+complete -c inlyne -n "__fish_seen_subcommand_from bench" -s t -l theme -d 'Theme to use when rendering, or the name of one from the config' -r -f -a "{auto	'',dark	'',light	''}"
+complete -c inlyne -n "__fish_seen_subcommand_from bench" -s c -l config -d 'Configuration file to use' -r -F
+complete -c inlyne -n "__fish_seen_subcommand_from bench" -l json -d 'Output the results as JSON'
+complete -c inlyne -n "__fish_seen_subcommand_from bench" -s h -l help -d 'Print help'
+complete -c inlyne -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from serve; and not __fish_seen_subcommand_from bench; and not __fish_seen_subcommand_from help" -f -a "serve" -d 'Serve a directory of markdown files as themed HTML with live-reload'
+complete -c inlyne -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from serve; and not __fish_seen_subcommand_from bench; and not __fish_seen_subcommand_from help" -f -a "bench" -d 'Measure how long each stage of rendering a file takes'
+complete -c inlyne -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from serve; and not __fish_seen_subcommand_from bench; and not __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
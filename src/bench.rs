@@ -0,0 +1,227 @@
+//! Measures how long each stage of getting a file onto the screen takes
+//!
+//! Everything runs headlessly, so this works in CI as long as some GPU adapter (including
+//! software ones like lavapipe or llvmpipe) is available.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::interpreter::HtmlInterpreter;
+use crate::opts::BenchOpts;
+use crate::renderer::Renderer;
+use crate::utils::{markdown_to_html, MarkdownOptions};
+use crate::Inlyne;
+
+use anyhow::Context;
+use serde::Serialize;
+
+const SCREEN_SIZE: (u32, u32) = (1280, 720);
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub file: PathBuf,
+    pub bytes: usize,
+    pub elements: usize,
+    pub parse_ms: f64,
+    pub interpret_ms: f64,
+    pub layout_ms: f64,
+    pub first_frame_ms: f64,
+    pub frames: FrameStats,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FrameStats {
+    pub count: usize,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl FrameStats {
+    fn from_durations(durations: &[Duration]) -> Self {
+        let mut millis: Vec<_> = durations.iter().copied().map(as_millis).collect();
+        millis.sort_by(f64::total_cmp);
+
+        let count = millis.len();
+        let percentile = |p: f64| {
+            if count == 0 {
+                0.0
+            } else {
+                let index = ((count - 1) as f64 * p).round() as usize;
+                millis[index]
+            }
+        };
+        let mean_ms = if count == 0 {
+            0.0
+        } else {
+            millis.iter().sum::<f64>() / count as f64
+        };
+
+        Self {
+            count,
+            mean_ms,
+            min_ms: percentile(0.0),
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            max_ms: percentile(1.0),
+        }
+    }
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000.0
+}
+
+pub fn run(opts: BenchOpts) -> anyhow::Result<()> {
+    let json = opts.json;
+    let report = measure(opts)?;
+    let mut stdout = io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut stdout, &report)?;
+        writeln!(stdout)?;
+    } else {
+        write_report(&mut stdout, &report)?;
+    }
+
+    Ok(())
+}
+
+fn write_report(w: &mut impl Write, report: &Report) -> io::Result<()> {
+    let Report {
+        file,
+        bytes,
+        elements,
+        parse_ms,
+        interpret_ms,
+        layout_ms,
+        first_frame_ms,
+        frames,
+    } = report;
+
+    writeln!(w, "{} ({bytes} bytes, {elements} elements)", file.display())?;
+    writeln!(w, "  parse        {parse_ms:>10.3} ms")?;
+    writeln!(w, "  interpret    {interpret_ms:>10.3} ms")?;
+    writeln!(w, "  layout       {layout_ms:>10.3} ms")?;
+    writeln!(w, "  first frame  {first_frame_ms:>10.3} ms")?;
+    writeln!(
+        w,
+        "  frames       {:>10.3} ms mean, {:.3} ms p50, {:.3} ms p95, {:.3} ms max ({} frames)",
+        frames.mean_ms, frames.p50_ms, frames.p95_ms, frames.max_ms, frames.count
+    )
+}
+
+fn measure(opts: BenchOpts) -> anyhow::Result<Report> {
+    let BenchOpts {
+        file_path,
+        frames,
+        json: _,
+        theme,
+        scale,
+        page_width,
+        font_opts,
+        color_scheme,
+    } = opts;
+
+    let md = fs::read_to_string(&file_path)
+        .with_context(|| format!("Could not read file at '{}'", file_path.display()))?;
+
+    let mut renderer = pollster::block_on(Renderer::new_headless(
+        SCREEN_SIZE,
+        theme.clone(),
+        scale.unwrap_or(1.0),
+        page_width.unwrap_or(std::f32::MAX),
+        font_opts.clone(),
+    ))?;
+
+    let start = Instant::now();
+    let htmlified = markdown_to_html(
+        &md,
+        theme.code_highlighter.clone(),
+        MarkdownOptions::default(),
+    );
+    let parse = start.elapsed();
+
+    let element_queue = Arc::new(Mutex::new(VecDeque::new()));
+    let mut interpreter = HtmlInterpreter::new_headless(
+        Arc::clone(&element_queue),
+        theme,
+        renderer.surface_format,
+        renderer.hidpi_scale,
+        file_path.clone(),
+        Arc::new(Mutex::new(HashMap::new())),
+        color_scheme,
+    );
+    interpreter.apply_font_options(&font_opts);
+    let start = Instant::now();
+    interpreter.interpret_html(&htmlified);
+    let interpret = start.elapsed();
+    let num_elements = element_queue.lock().unwrap().len();
+
+    let mut elements = Vec::new();
+    let start = Instant::now();
+    Inlyne::position_queued_elements(&element_queue, &mut renderer, &mut elements);
+    let layout = start.elapsed();
+
+    let start = Instant::now();
+    renderer.redraw(&mut elements)?;
+    renderer.device.poll(wgpu::Maintain::Wait);
+    let first_frame = start.elapsed();
+
+    // Scroll through the document while rendering so that the steady state frames aren't all
+    // drawing the same thing
+    let max_scroll = (renderer.positioner.reserved_height - renderer.screen_height()).max(0.);
+    let scroll_step = max_scroll / frames as f32;
+    let mut frame_times = Vec::with_capacity(frames as usize);
+    for i in 0..frames {
+        renderer.set_scroll_y(scroll_step * (i + 1) as f32);
+        let start = Instant::now();
+        renderer.redraw(&mut elements)?;
+        renderer.device.poll(wgpu::Maintain::Wait);
+        frame_times.push(start.elapsed());
+    }
+
+    Ok(Report {
+        file: file_path,
+        bytes: md.len(),
+        elements: num_elements,
+        parse_ms: as_millis(parse),
+        interpret_ms: as_millis(interpret),
+        layout_ms: as_millis(layout),
+        first_frame_ms: as_millis(first_frame),
+        frames: FrameStats::from_durations(&frame_times),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_stats() {
+        let durations: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        let stats = FrameStats::from_durations(&durations);
+        assert_eq!(stats.count, 100);
+        assert!((stats.mean_ms - 50.5).abs() < 1e-9);
+        assert_eq!(stats.min_ms, 1.0);
+        assert_eq!(stats.p95_ms, 95.0);
+        assert_eq!(stats.max_ms, 100.0);
+
+        assert_eq!(
+            FrameStats::from_durations(&[]),
+            FrameStats {
+                count: 0,
+                mean_ms: 0.0,
+                min_ms: 0.0,
+                p50_ms: 0.0,
+                p95_ms: 0.0,
+                max_ms: 0.0,
+            }
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Which way paragraphs of text run
+//!
+//! The text shaper already reorders mixed left-to-right and right-to-left runs within a line and
+//! mirrors paired punctuation like `(` in right-to-left runs. What's left up to us is the base
+//! direction of each paragraph, which comes from a `dir` attribute or else from its first strong
+//! character like `dir="auto"` in a browser, and which decides the side it's aligned to
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    /// Reads a `dir` attribute. `auto` (or anything else) leaves it up to the text
+    pub fn new(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ltr" => Some(Self::Ltr),
+            "rtl" => Some(Self::Rtl),
+            _ => None,
+        }
+    }
+
+    /// The direction of the first strongly directional character, if there is one
+    pub fn detect(text: &str) -> Option<Self> {
+        text.chars().find_map(|c| {
+            if is_rtl(c) {
+                Some(Self::Rtl)
+            } else if c.is_alphabetic() {
+                Some(Self::Ltr)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Whether `c` is a letter from a right-to-left script like Hebrew or Arabic
+fn is_rtl(c: char) -> bool {
+    match c {
+        // Arabic-Indic digits are numbers, which don't pick a direction
+        '\u{0660}'..='\u{0669}' | '\u{06F0}'..='\u{06F9}' => false,
+        // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, and Mandaic
+        '\u{0590}'..='\u{08FF}'
+        // Hebrew and Arabic presentation forms
+        | '\u{FB1D}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFE}'
+        // Historic scripts along with more Arabic
+        | '\u{10800}'..='\u{10FFF}'
+        | '\u{1E800}'..='\u{1EFFF}' => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detecting() {
+        assert_eq!(Direction::detect("Hello שלום"), Some(Direction::Ltr));
+        assert_eq!(Direction::detect("«שלום» world"), Some(Direction::Rtl));
+        assert_eq!(Direction::detect("1. مرحبا"), Some(Direction::Rtl));
+        assert_eq!(Direction::detect("٣٤ - 12"), None);
+        assert_eq!(Direction::detect(""), None);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(Direction::new("RTL"), Some(Direction::Rtl));
+        assert_eq!(Direction::new("ltr"), Some(Direction::Ltr));
+        assert_eq!(Direction::new("auto"), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! GitHub style `> [!NOTE]` callouts and `:::note` containers
+//!
+//! ```markdown
+//! > [!WARNING]
+//! > Here be dragons
+//!
+//! :::tip Optional title
+//! Containers hold markdown just like quotes do
+//! :::
+//! ```
+//!
+//! Containers get rewritten into callout quotes before parsing. Callout quotes then get a title
+//! paragraph marked with `data-callout` in place of their `[!NOTE]` marker, which the interpreter
+//! picks up to draw the quote in the callout's color
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use crate::color::Theme;
+use crate::utils::{closes_fence, opens_fence};
+
+use comrak::arena_tree::Node;
+use comrak::nodes::{Ast, AstNode, NodeHtmlBlock, NodeValue};
+use comrak::Arena;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Callout {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl Callout {
+    pub const ALL: [Self; 5] = [
+        Self::Note,
+        Self::Tip,
+        Self::Important,
+        Self::Warning,
+        Self::Caution,
+    ];
+
+    /// Case-insensitive, and also takes the `info` and `danger` names that other tools use
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        let callout = match name.as_str() {
+            "info" => Self::Note,
+            "danger" => Self::Caution,
+            name => *Self::ALL.iter().find(|callout| callout.name() == name)?,
+        };
+        Some(callout)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Note => "note",
+            Self::Tip => "tip",
+            Self::Important => "important",
+            Self::Warning => "warning",
+            Self::Caution => "caution",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Note => "Note",
+            Self::Tip => "Tip",
+            Self::Important => "Important",
+            Self::Warning => "Warning",
+            Self::Caution => "Caution",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            Self::Note => "ℹ",
+            Self::Tip => "💡",
+            Self::Important => "❗",
+            Self::Warning => "⚠",
+            Self::Caution => "⛔",
+        }
+    }
+
+    pub fn color(self, theme: &Theme) -> u32 {
+        match self {
+            Self::Note => theme.note_color,
+            Self::Tip => theme.tip_color,
+            Self::Important => theme.important_color,
+            Self::Warning => theme.warning_color,
+            Self::Caution => theme.caution_color,
+        }
+    }
+}
+
+/// Splits a `:::note Some title` line into its callout and title
+fn container_start(line: &str) -> Option<(Callout, &str)> {
+    let rest = line
+        .strip_prefix(":::")?
+        .trim_start_matches(':')
+        .trim_start();
+    let (name, title) = rest.split_once(' ').unwrap_or((rest, ""));
+    Some((Callout::from_name(name)?, title.trim()))
+}
+
+fn is_container_end(line: &str) -> bool {
+    line.len() >= 3 && line.chars().all(|c| c == ':')
+}
+
+/// Rewrites `:::note` containers into `> [!NOTE]` quotes
+///
+/// Line numbers are kept the same, so source positions still line up with the original
+pub fn mark_containers(md: &str) -> Cow<'_, str> {
+    if !md.contains(":::") {
+        return Cow::Borrowed(md);
+    }
+
+    let mut marked = String::with_capacity(md.len());
+    let mut code_fence = None;
+    let mut depth = 0;
+    for line in md.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[content.len()..];
+        let trimmed = content.trim();
+        let quote = "> ".repeat(depth);
+
+        if let Some((fence_char, fence_len)) = code_fence {
+            if closes_fence(trimmed, fence_char, fence_len) {
+                code_fence = None;
+            }
+            marked.push_str(&quote);
+            marked.push_str(line);
+        } else if let Some((callout, title)) = container_start(trimmed) {
+            depth += 1;
+            marked.push_str(&quote);
+            marked.push_str("> [!");
+            marked.push_str(&callout.name().to_ascii_uppercase());
+            marked.push(']');
+            if !title.is_empty() {
+                marked.push(' ');
+                marked.push_str(title);
+            }
+            marked.push_str(ending);
+        } else if depth > 0 && is_container_end(trimmed) {
+            depth -= 1;
+            marked.push_str("> ".repeat(depth).trim_end());
+            marked.push_str(ending);
+        } else {
+            code_fence = opens_fence(content.trim_start());
+            marked.push_str(&quote);
+            marked.push_str(line);
+        }
+    }
+
+    Cow::Owned(marked)
+}
+
+/// Splits the `[!NOTE] Some title` text that starts a callout into its callout and title
+fn marker(text: &str) -> Option<(Callout, &str)> {
+    let (name, title) = text.trim_start().strip_prefix("[!")?.split_once(']')?;
+    Some((Callout::from_name(name)?, title.trim()))
+}
+
+/// Swaps the marker in a callout quote for its title
+pub fn render<'a>(arena: &'a Arena<AstNode<'a>>, quote: &'a AstNode<'a>) {
+    if !matches!(quote.data.borrow().value, NodeValue::BlockQuote) {
+        return;
+    }
+    let Some(paragraph) = quote
+        .first_child()
+        .filter(|child| matches!(child.data.borrow().value, NodeValue::Paragraph))
+    else {
+        return;
+    };
+    let Some(first) = paragraph.first_child() else {
+        return;
+    };
+    if !matches!(first.data.borrow().value, NodeValue::Text(_)) {
+        return;
+    }
+    // The title can get split up into more than one inline, like around anything that looks like
+    // an HTML tag, so it's everything up to the end of the marker's line
+    let mut line = vec![first];
+    while let Some(next) = line[line.len() - 1].next_sibling() {
+        line.push(next);
+        if matches!(
+            next.data.borrow().value,
+            NodeValue::SoftBreak | NodeValue::LineBreak
+        ) {
+            break;
+        }
+    }
+    let text: String = line
+        .iter()
+        .flat_map(|node| node.descendants())
+        .filter_map(|node| match &node.data.borrow().value {
+            NodeValue::Text(text) | NodeValue::HtmlInline(text) => Some(text.clone()),
+            NodeValue::Code(code) => Some(code.literal.clone()),
+            _ => None,
+        })
+        .collect();
+    let (callout, title) = match marker(&text) {
+        Some((callout, "")) => (callout, callout.title().to_owned()),
+        Some((callout, title)) => (callout, title.to_owned()),
+        None => return,
+    };
+
+    for node in line {
+        node.detach();
+    }
+    if paragraph.first_child().is_none() {
+        paragraph.detach();
+    }
+
+    let literal = format!(
+        "<p data-callout=\"{}\"><strong>{} {}</strong></p>\n",
+        callout.name(),
+        callout.icon(),
+        html_escape::encode_text(&title),
+    );
+    let start = quote.data.borrow().sourcepos.start;
+    let value = NodeValue::HtmlBlock(NodeHtmlBlock {
+        block_type: 6,
+        literal,
+    });
+    quote.prepend(arena.alloc(Node::new(RefCell::new(Ast::new(value, start)))));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{markdown_to_html, MarkdownOptions};
+
+    use syntect::highlighting::Theme as SyntectTheme;
+
+    #[test]
+    fn names() {
+        assert_eq!(Callout::from_name("NOTE"), Some(Callout::Note));
+        assert_eq!(Callout::from_name("Warning"), Some(Callout::Warning));
+        assert_eq!(Callout::from_name("danger"), Some(Callout::Caution));
+        assert_eq!(Callout::from_name("quote"), None);
+    }
+
+    #[test]
+    fn marking_containers() {
+        let md = "\
+:::note
+Plain
+::::warning Be careful
+```
+:::
+```
+::::
+
+after
+:::
+:::unknown
+";
+        let expected = "\
+> [!NOTE]
+> Plain
+> > [!WARNING] Be careful
+> > ```
+> > :::
+> > ```
+>
+> \n\
+> after
+
+:::unknown
+";
+        assert_eq!(mark_containers(md), expected);
+        assert!(matches!(mark_containers("> [!TIP]"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn rendering() {
+        let html = |md| markdown_to_html(md, SyntectTheme::default(), MarkdownOptions::default());
+
+        let callout = html("> [!TIP]\n> Some *advice*\n");
+        assert!(callout.contains("<p data-callout=\"tip\"><strong>💡 Tip</strong></p>"));
+        assert!(callout.contains("Some <em data-sourcepos=\"2:8-2:15\">advice</em>"));
+        assert!(!callout.contains("[!TIP]"));
+
+        let titled = html(":::caution Hot <stove>\nStay back\n:::\n");
+        assert!(titled.contains("<strong>⛔ Hot &lt;stove&gt;</strong>"));
+        assert!(titled.contains("Stay back"));
+
+        let quote = html("> [!SOMETHING]\n> Else\n");
+        assert!(!quote.contains("data-callout"));
+    }
+}
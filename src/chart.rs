@@ -0,0 +1,446 @@
+//! Renders charts from `chart` and `vega-lite` code blocks
+//!
+//! Charts get drawn to an SVG in the cache directory named after a hash of the block, so they go
+//! through the regular image pipeline and only get redrawn when the block actually changes.
+
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use plotters::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+use twox_hash::XxHash64;
+
+/// Bump whenever the output changes so that stale charts don't get reused
+const RENDER_VERSION: u64 = 1;
+const SIZE: (u32, u32) = (640, 360);
+/// The generic `sans-serif` family maps to whatever the SVG renderer defaults to (Arial), so list
+/// some widely available fonts first
+const FONT: &str = "DejaVu Sans, Noto Sans, Liberation Sans, Helvetica, Arial, sans-serif";
+
+/// The info string languages that get rendered as charts
+pub fn is_chart_lang(lang: &str) -> bool {
+    matches!(lang, "chart" | "vega-lite")
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum Kind {
+    #[default]
+    Bar,
+    Line,
+    Point,
+}
+
+#[derive(Debug, PartialEq)]
+struct Series {
+    name: Option<String>,
+    /// One value per label. `None` when the data has a gap
+    values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Chart {
+    kind: Kind,
+    title: Option<String>,
+    labels: Vec<String>,
+    series: Vec<Series>,
+}
+
+impl Chart {
+    fn parse(lang: &str, code: &str) -> anyhow::Result<Self> {
+        match lang {
+            "chart" => Self::from_simple(code),
+            "vega-lite" => Self::from_vega_lite(code),
+            _ => bail!("Unknown chart language: {lang}"),
+        }
+    }
+
+    /// Our own simple TOML spec
+    ///
+    /// ```toml
+    /// type = "line"
+    /// title = "Downloads"
+    /// labels = ["Jan", "Feb", "Mar"]
+    ///
+    /// [[series]]
+    /// name = "2023"
+    /// values = [120, 180, 150]
+    /// ```
+    fn from_simple(code: &str) -> anyhow::Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Simple {
+            #[serde(default, rename = "type")]
+            kind: Kind,
+            title: Option<String>,
+            labels: Vec<String>,
+            series: Vec<SimpleSeries>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct SimpleSeries {
+            name: Option<String>,
+            values: Vec<f64>,
+        }
+
+        let Simple {
+            kind,
+            title,
+            labels,
+            series,
+        } = toml::from_str(code).context("Invalid chart spec")?;
+        let series = series
+            .into_iter()
+            .map(|SimpleSeries { name, values }| {
+                let mut values: Vec<_> = values.into_iter().map(Some).collect();
+                values.resize(labels.len(), None);
+                Series { name, values }
+            })
+            .collect();
+
+        Ok(Self {
+            kind,
+            title,
+            labels,
+            series,
+        })
+    }
+
+    /// The subset of vega-lite that covers simple bar, line, and point charts with inline data
+    fn from_vega_lite(code: &str) -> anyhow::Result<Self> {
+        let spec: Value = serde_json::from_str(code).context("Invalid vega-lite spec")?;
+
+        let mark = spec.get("mark").context("Missing `mark`")?;
+        let mark = mark.get("type").unwrap_or(mark).as_str();
+        let kind = match mark {
+            Some("bar") => Kind::Bar,
+            Some("line") => Kind::Line,
+            Some("point" | "circle" | "square") => Kind::Point,
+            other => bail!("Unsupported mark: {}", other.unwrap_or("<not a string>")),
+        };
+        let title = spec.get("title").and_then(|title| {
+            title
+                .as_str()
+                .or_else(|| title.get("text")?.as_str())
+                .map(ToOwned::to_owned)
+        });
+
+        let values = spec
+            .pointer("/data/values")
+            .and_then(Value::as_array)
+            .context("Only inline `data.values` are supported")?;
+        let field = |channel: &str| {
+            spec.pointer(&format!("/encoding/{channel}/field"))
+                .and_then(Value::as_str)
+        };
+        let x_field = field("x").context("Missing `encoding.x.field`")?;
+        let y_field = field("y").context("Missing `encoding.y.field`")?;
+        let color_field = field("color");
+
+        let mut labels = Vec::new();
+        let mut series: Vec<Series> = Vec::new();
+        for datum in values {
+            let Some(label) = datum.get(x_field).map(value_to_label) else {
+                continue;
+            };
+            let label_index = match labels.iter().position(|l| l == &label) {
+                Some(index) => index,
+                None => {
+                    labels.push(label);
+                    series
+                        .iter_mut()
+                        .for_each(|series| series.values.push(None));
+                    labels.len() - 1
+                }
+            };
+
+            let name = match color_field {
+                Some(color_field) => datum.get(color_field).map(value_to_label),
+                None => None,
+            };
+            let series_index = match series.iter().position(|s| s.name == name) {
+                Some(index) => index,
+                None => {
+                    series.push(Series {
+                        name,
+                        values: vec![None; labels.len()],
+                    });
+                    series.len() - 1
+                }
+            };
+            series[series_index].values[label_index] = datum.get(y_field).and_then(Value::as_f64);
+        }
+
+        Ok(Self {
+            kind,
+            title,
+            labels,
+            series,
+        })
+    }
+
+    fn to_svg(&self, text_color: RGBColor) -> anyhow::Result<String> {
+        if self.labels.is_empty() || self.series.is_empty() {
+            bail!("Chart has no data");
+        }
+
+        let values = || self.series.iter().flat_map(|s| s.values.iter().flatten());
+        let min = values().copied().fold(0.0, f64::min);
+        let mut max = values().copied().fold(0.0, f64::max);
+        if max <= min {
+            max = min + 1.0;
+        }
+        let headroom = (max - min) * 0.1;
+        let y_range = if min < 0.0 { min - headroom } else { min }..max + headroom;
+        let x_range = -0.5..self.labels.len() as f64 - 0.5;
+
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, SIZE).into_drawing_area();
+            let mut builder = ChartBuilder::on(&root);
+            builder
+                .margin(16)
+                .x_label_area_size(32)
+                .y_label_area_size(48);
+            if let Some(title) = &self.title {
+                builder.caption(title, (FONT, 22).into_font().color(&text_color));
+            }
+            let mut chart = builder.build_cartesian_2d(x_range, y_range)?;
+
+            let label_at = |x: &f64| {
+                let index = x.round();
+                if (x - index).abs() < 1e-6 && index >= 0.0 {
+                    self.labels.get(index as usize).cloned().unwrap_or_default()
+                } else {
+                    String::new()
+                }
+            };
+            chart
+                .configure_mesh()
+                .disable_x_mesh()
+                .x_labels(self.labels.len())
+                .x_label_formatter(&label_at)
+                .y_label_formatter(&format_value)
+                .label_style((FONT, 14).into_font().color(&text_color))
+                .axis_style(text_color.mix(0.6))
+                .bold_line_style(text_color.mix(0.15))
+                .light_line_style(TRANSPARENT)
+                .draw()?;
+
+            let num_series = self.series.len();
+            for (series_index, series) in self.series.iter().enumerate() {
+                let color = Palette99::pick(series_index).to_rgba();
+                let points = || {
+                    series
+                        .values
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, value)| Some((i as f64, (*value)?)))
+                };
+
+                let annotation = match self.kind {
+                    Kind::Bar => {
+                        let width = 0.8 / num_series as f64;
+                        chart.draw_series(points().map(|(x, y)| {
+                            let left = x - 0.4 + width * series_index as f64;
+                            Rectangle::new([(left, 0.0), (left + width, y)], color.filled())
+                        }))?
+                    }
+                    Kind::Line => {
+                        chart.draw_series(
+                            points().map(|point| Circle::new(point, 3, color.filled())),
+                        )?;
+                        chart.draw_series(LineSeries::new(points(), color.stroke_width(2)))?
+                    }
+                    Kind::Point => chart
+                        .draw_series(points().map(|point| Circle::new(point, 4, color.filled())))?,
+                };
+                if let Some(name) = &series.name {
+                    annotation.label(name).legend(move |(x, y)| {
+                        Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
+                    });
+                }
+            }
+
+            if self.series.iter().any(|series| series.name.is_some()) {
+                chart
+                    .configure_series_labels()
+                    .position(SeriesLabelPosition::UpperRight)
+                    .label_font((FONT, 14).into_font().color(&text_color))
+                    .border_style(text_color.mix(0.3))
+                    .draw()?;
+            }
+
+            root.present()?;
+        }
+
+        Ok(svg)
+    }
+}
+
+/// Formats an axis value without the float noise or trailing zeros
+fn format_value(value: &f64) -> String {
+    let formatted = format!("{value:.4}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_owned(),
+        other => other.to_owned(),
+    }
+}
+
+fn value_to_label(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_owned(),
+        other => other.to_string(),
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("inlyne")
+        .join("charts")
+}
+
+/// Renders the chart described by a code block returning the path to the rendered SVG
+pub fn render_cached(lang: &str, code: &str, text_color: u32) -> anyhow::Result<PathBuf> {
+    render_cached_in(&cache_dir(), lang, code, text_color)
+}
+
+fn render_cached_in(
+    dir: &Path,
+    lang: &str,
+    code: &str,
+    text_color: u32,
+) -> anyhow::Result<PathBuf> {
+    let mut hasher = XxHash64::default();
+    hasher.write_u64(RENDER_VERSION);
+    hasher.write(lang.as_bytes());
+    hasher.write(code.as_bytes());
+    hasher.write_u32(text_color);
+    let path = dir.join(format!("{:016x}.svg", hasher.finish()));
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    let [_, r, g, b] = text_color.to_be_bytes();
+    let svg = Chart::parse(lang, code)?.to_svg(RGBColor(r, g, b))?;
+    fs::create_dir_all(dir)?;
+    // Write then rename so that a half-written chart never gets picked up
+    let temp_path = path.with_extension("svg.tmp");
+    fs::write(&temp_path, svg)?;
+    fs::rename(&temp_path, &path)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_spec() {
+        let chart = Chart::parse(
+            "chart",
+            r#"
+type = "line"
+title = "Downloads"
+labels = ["Jan", "Feb", "Mar"]
+
+[[series]]
+name = "2023"
+values = [120, 180]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            chart,
+            Chart {
+                kind: Kind::Line,
+                title: Some("Downloads".to_owned()),
+                labels: vec!["Jan".to_owned(), "Feb".to_owned(), "Mar".to_owned()],
+                series: vec![Series {
+                    name: Some("2023".to_owned()),
+                    values: vec![Some(120.0), Some(180.0), None],
+                }],
+            }
+        );
+
+        assert!(Chart::parse("chart", "labels = []\nseries = []\nbogus = 1").is_err());
+    }
+
+    #[test]
+    fn vega_lite_spec() {
+        let chart = Chart::parse(
+            "vega-lite",
+            r#"{
+                "title": {"text": "Sales"},
+                "mark": {"type": "bar"},
+                "data": {"values": [
+                    {"quarter": "Q1", "region": "east", "sales": 10},
+                    {"quarter": "Q1", "region": "west", "sales": 7},
+                    {"quarter": "Q2", "region": "west", "sales": 12.5}
+                ]},
+                "encoding": {
+                    "x": {"field": "quarter", "type": "nominal"},
+                    "y": {"field": "sales", "type": "quantitative"},
+                    "color": {"field": "region"}
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            chart,
+            Chart {
+                kind: Kind::Bar,
+                title: Some("Sales".to_owned()),
+                labels: vec!["Q1".to_owned(), "Q2".to_owned()],
+                series: vec![
+                    Series {
+                        name: Some("east".to_owned()),
+                        values: vec![Some(10.0), None],
+                    },
+                    Series {
+                        name: Some("west".to_owned()),
+                        values: vec![Some(7.0), Some(12.5)],
+                    },
+                ],
+            }
+        );
+
+        let err = Chart::parse("vega-lite", r#"{"mark": "arc"}"#).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported mark: arc");
+    }
+
+    #[test]
+    fn axis_values() {
+        assert_eq!(format_value(&300.0), "300");
+        assert_eq!(format_value(&0.30000000000000004), "0.3");
+        assert_eq!(format_value(&-0.0), "0");
+        assert_eq!(format_value(&-2.5), "-2.5");
+    }
+
+    #[test]
+    fn renders_once_per_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let code = "title = \"Tiny\"\nlabels = [\"a\", \"b\"]\n[[series]]\nvalues = [1, -2]";
+
+        let path = render_cached_in(dir.path(), "chart", code, 0x333333).unwrap();
+        let svg = fs::read_to_string(&path).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Tiny"));
+
+        // The same block is reused while anything different gets its own chart
+        fs::write(&path, "cached").unwrap();
+        let same = render_cached_in(dir.path(), "chart", code, 0x333333).unwrap();
+        assert_eq!(fs::read_to_string(same).unwrap(), "cached");
+        let recolored = render_cached_in(dir.path(), "chart", code, 0xffffff).unwrap();
+        assert_ne!(recolored, path);
+
+        assert!(render_cached_in(dir.path(), "chart", "labels = []\nseries = []", 0).is_err());
+    }
+}
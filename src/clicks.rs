@@ -0,0 +1,50 @@
+//! Counts clicks that follow each other closely enough to be double and triple clicks
+
+use std::time::{Duration, Instant};
+
+use crate::utils::Point;
+
+/// The longest wait between clicks that still counts them together
+const MULTI_CLICK: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default)]
+pub struct ClickCounter {
+    last: Option<(Instant, Point)>,
+    count: u8,
+}
+
+impl ClickCounter {
+    /// Records a press at `pos`, returning how many clicks in a row it makes. Presses that are
+    /// more than `slop` away from the last one start over, and so does the click after a triple
+    /// click
+    pub fn press(&mut self, pos: Point, now: Instant, slop: f32) -> u8 {
+        let follows = self.last.map_or(false, |(at, last)| {
+            let distance = ((pos.0 - last.0).powi(2) + (pos.1 - last.1).powi(2)).sqrt();
+            now.saturating_duration_since(at) <= MULTI_CLICK && distance <= slop
+        });
+        self.count = if follows { self.count % 3 + 1 } else { 1 };
+        self.last = Some((now, pos));
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counting_clicks() {
+        let start = Instant::now();
+        let mut clicks = ClickCounter::default();
+        let after = |millis| start + Duration::from_millis(millis);
+
+        assert_eq!(clicks.press((10., 10.), start, 4.), 1);
+        assert_eq!(clicks.press((11., 10.), after(200), 4.), 2);
+        assert_eq!(clicks.press((11., 12.), after(400), 4.), 3);
+        assert_eq!(clicks.press((11., 12.), after(600), 4.), 1);
+        // Too slow
+        assert_eq!(clicks.press((11., 12.), after(1_200), 4.), 1);
+        // Too far away
+        assert_eq!(clicks.press((50., 12.), after(1_300), 4.), 1);
+    }
+}
@@ -7,6 +7,10 @@ use copypasta::wayland_clipboard;
 #[cfg(any(feature = "x11", target_os = "macos", windows))]
 use copypasta::ClipboardContext;
 
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
 use copypasta::ClipboardProvider;
 use raw_window_handle::RawDisplayHandle;
 
@@ -33,6 +37,15 @@ impl Clipboard {
         Self(clipboard)
     }
 
+    pub fn get_contents(&mut self) -> Option<String> {
+        self.0
+            .get_contents()
+            .map_err(|err| {
+                tracing::warn!("Unable to read text from clipboard: {}", err);
+            })
+            .ok()
+    }
+
     pub fn set_contents(&mut self, text: impl Into<String>) {
         self.0.set_contents(text.into()).unwrap_or_else(|err| {
             tracing::warn!("Unable to store text in clipboard: {}", err);
@@ -52,3 +65,63 @@ impl Default for Clipboard {
         return Self::new_nop();
     }
 }
+
+/// Puts a PNG on the clipboard. copypasta only handles text, so this goes through whatever tools
+/// each platform already has for images
+pub fn set_png(png: &[u8]) -> anyhow::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let path = std::env::temp_dir().join("inlyne-copied-image.png");
+        std::fs::write(&path, png)?;
+        let script = format!(
+            "set the clipboard to (read (POSIX file \"{}\") as «class PNGf»)",
+            path.display()
+        );
+        run(Command::new("osascript").args(["-e", &script]), None)
+    }
+
+    #[cfg(windows)]
+    {
+        let path = std::env::temp_dir().join("inlyne-copied-image.png");
+        std::fs::write(&path, png)?;
+        let script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms, System.Drawing; \
+            [System.Windows.Forms.Clipboard]::SetImage([System.Drawing.Image]::FromFile('{}'))",
+            path.display()
+        );
+        run(
+            Command::new("powershell").args(["-STA", "-NoProfile", "-Command", &script]),
+            None,
+        )
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            run(
+                Command::new("wl-copy").args(["--type", "image/png"]),
+                Some(png),
+            )
+        } else {
+            run(
+                Command::new("xclip").args(["-selection", "clipboard", "-t", "image/png", "-i"]),
+                Some(png),
+            )
+        }
+    }
+}
+
+fn run(command: &mut Command, input: Option<&[u8]>) -> anyhow::Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed running `{program}`"))?;
+    if let Some(input) = input {
+        child.stdin.take().unwrap().write_all(input)?;
+    }
+    let status = child.wait()?;
+    anyhow::ensure!(status.success(), "`{program}` exited with {status}");
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
 use anyhow::Context;
@@ -33,15 +33,54 @@ pub fn native_color(c: u32, format: &TextureFormat) -> [f32; 4] {
     }
 }
 
+/// Undoes [`native_color()`], giving back sRGB components for drawing somewhere besides `format`
+pub fn from_native_color(c: [f32; 4], format: &TextureFormat) -> [f32; 4] {
+    use wgpu::TextureFormat::*;
+    let f = |x: f32| {
+        if x > 0.0031308 {
+            1.055 * x.powf(1.0 / 2.4) - 0.055
+        } else {
+            x * 12.92
+        }
+    };
+
+    match format {
+        Rgba8UnormSrgb | Bgra8UnormSrgb => [f(c[0]), f(c[1]), f(c[2]), c[3]],
+        _ => c,
+    }
+}
+
+/// Blends `amount` of the way from one `0xRRGGBB` color to another
+pub fn mix(from: u32, to: u32, amount: f32) -> u32 {
+    let [_, from @ ..] = from.to_be_bytes();
+    let [_, to @ ..] = to.to_be_bytes();
+    let [r, g, b] = [0, 1, 2].map(|i| {
+        let (from, to) = (f32::from(from[i]), f32::from(to[i]));
+        (from + (to - from) * amount).round() as u8
+    });
+    u32::from_be_bytes([0, r, g, b])
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Theme {
     pub text_color: u32,
     pub background_color: u32,
+    pub heading_color: u32,
     pub code_color: u32,
     pub quote_block_color: u32,
+    pub quote_bar_color: u32,
     pub link_color: u32,
     pub select_color: u32,
+    pub table_border_color: u32,
     pub checkbox_color: u32,
+    pub mark_color: u32,
+    pub scrollbar_color: u32,
+    pub note_color: u32,
+    pub tip_color: u32,
+    pub important_color: u32,
+    pub warning_color: u32,
+    pub caution_color: u32,
+    pub image_backdrop: ImageBackdrop,
     pub code_highlighter: SyntectTheme,
 }
 
@@ -55,11 +94,22 @@ impl Theme {
         Self {
             text_color: 0x9DACBB,
             background_color: 0x1A1D22,
+            heading_color: 0x9DACBB,
             code_color: 0xB38FAC,
             quote_block_color: 0x1D2025,
+            quote_bar_color: 0x3675CB,
             link_color: 0x4182EB,
             select_color: 0x3675CB,
+            table_border_color: 0x9DACBB,
             checkbox_color: 0x0A5301,
+            mark_color: 0x4D4221,
+            scrollbar_color: 0x4B5263,
+            note_color: 0x4493F8,
+            tip_color: 0x3FB950,
+            important_color: 0xAB7DF8,
+            warning_color: 0xD29922,
+            caution_color: 0xF85149,
+            image_backdrop: ImageBackdrop::None,
             code_highlighter,
         }
     }
@@ -73,11 +123,22 @@ impl Theme {
         Self {
             text_color: 0x000000,
             background_color: 0xFFFFFF,
+            heading_color: 0x000000,
             code_color: 0x95114E,
             quote_block_color: 0xEEF9FE,
+            quote_bar_color: 0xCDE8F0,
             link_color: 0x5466FF,
             select_color: 0xCDE8F0,
+            table_border_color: 0x000000,
             checkbox_color: 0x96ECAE,
+            mark_color: 0xFFF3A3,
+            scrollbar_color: 0xAFB8C1,
+            note_color: 0x0969DA,
+            tip_color: 0x1A7F37,
+            important_color: 0x8250DF,
+            warning_color: 0x9A6700,
+            caution_color: 0xD1242F,
+            image_backdrop: ImageBackdrop::None,
             code_highlighter,
         }
     }
@@ -88,6 +149,105 @@ impl Theme {
     }
 }
 
+/// The 16 colors of a [base16](https://github.com/chriskempson/base16) color scheme
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Base16Scheme(pub [u32; 16]);
+
+impl Base16Scheme {
+    /// Loads a scheme's YAML file. Both the original format with `base00` through `base0F` at the
+    /// top level and the newer one with them under `palette` are supported
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed reading base16 scheme from {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("Invalid base16 scheme at {}", path.display()))
+    }
+
+    fn parse(contents: &str) -> anyhow::Result<Self> {
+        let scheme: serde_yaml::Mapping = serde_yaml::from_str(contents)?;
+        let palette = match scheme.get("palette") {
+            Some(serde_yaml::Value::Mapping(palette)) => palette,
+            _ => &scheme,
+        };
+
+        let mut colors = [0; 16];
+        for (i, color) in colors.iter_mut().enumerate() {
+            let name = format!("base{i:02X}");
+            let hex = palette
+                .get(name.as_str())
+                .and_then(serde_yaml::Value::as_str)
+                .with_context(|| format!("Missing `{name}`"))?;
+            *color = u32::from_str_radix(hex.trim_start_matches('#'), 16)
+                .with_context(|| format!("`{name}` isn't a hex color: {hex}"))?;
+        }
+        Ok(Self(colors))
+    }
+
+    /// Recolors `theme` following the usual base16 styling guidelines. The syntax highlighting and
+    /// image backdrop are left as they are
+    pub fn apply(self, theme: Theme) -> Theme {
+        let Self(base) = self;
+        Theme {
+            text_color: base[0x5],
+            background_color: base[0x0],
+            heading_color: base[0x5],
+            code_color: base[0xE],
+            quote_block_color: base[0x1],
+            quote_bar_color: base[0x3],
+            link_color: base[0xD],
+            select_color: base[0x2],
+            table_border_color: base[0x3],
+            checkbox_color: base[0xB],
+            mark_color: mix(base[0x0], base[0xA], 0.35),
+            scrollbar_color: base[0x3],
+            note_color: base[0xD],
+            tip_color: base[0xB],
+            important_color: base[0xE],
+            warning_color: base[0xA],
+            caution_color: base[0x8],
+            ..theme
+        }
+    }
+}
+
+/// What gets drawn behind images with see-through pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageBackdrop {
+    /// Let them blend into the page's background
+    #[default]
+    None,
+    /// Like image editors use
+    Checkerboard,
+    Solid(u32),
+}
+
+impl ImageBackdrop {
+    pub const CHECKER_LIGHT: u32 = 0xFFFFFF;
+    pub const CHECKER_DARK: u32 = 0xCCCCCC;
+}
+
+impl<'de> Deserialize<'de> for ImageBackdrop {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Untagged {
+            Named(String),
+            Solid(u32),
+        }
+
+        let expected = "Expects either \"none\", \"checkerboard\", or a color. E.g. 0xffffff";
+        match Untagged::deserialize(deserializer) {
+            Ok(Untagged::Named(name)) if name == "none" => Ok(Self::None),
+            Ok(Untagged::Named(name)) if name == "checkerboard" => Ok(Self::Checkerboard),
+            Ok(Untagged::Solid(color)) => Ok(Self::Solid(color)),
+            _ => Err(serde::de::Error::custom(expected)),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SyntaxTheme {
     Defaults(ThemeDefaults),
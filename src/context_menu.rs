@@ -0,0 +1,168 @@
+//! The menu that right-clicking or long pressing an image brings up
+
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::image::{Image, ImageData};
+use crate::panel::Panel;
+use crate::{clipboard, dialog};
+
+use anyhow::Context;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageAction {
+    Copy,
+    SaveAs,
+}
+
+impl ImageAction {
+    pub const ALL: [Self; 2] = [Self::Copy, Self::SaveAs];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Copy => "Copy image",
+            Self::SaveAs => "Save image as…",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ImageMenu {
+    image_data: Arc<Mutex<Option<ImageData>>>,
+    src: Option<String>,
+    selected: usize,
+}
+
+impl fmt::Debug for ImageMenu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageMenu")
+            .field("src", &self.src)
+            .field("selected", &self.selected)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for ImageMenu {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.image_data, &other.image_data)
+            && self.src == other.src
+            && self.selected == other.selected
+    }
+}
+
+impl ImageMenu {
+    pub fn new(image: &Image) -> Self {
+        Self {
+            image_data: Arc::clone(&image.image_data),
+            src: image.src.clone(),
+            selected: 0,
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(ImageAction::ALL.len() - 1);
+    }
+
+    pub fn selected(&self) -> ImageAction {
+        ImageAction::ALL[self.selected]
+    }
+
+    pub fn panel(&self) -> Panel {
+        let lines = ImageAction::ALL
+            .iter()
+            .map(|action| action.label().to_owned())
+            .collect();
+        Panel::new("Image", lines).with_selected(self.selected)
+    }
+
+    /// Runs `action` off of the main thread since it can sit waiting on a dialog or other programs
+    pub fn run(&self, action: ImageAction) {
+        let image_data = Arc::clone(&self.image_data);
+        let src = self.src.clone();
+        std::thread::spawn(move || {
+            let image_data = image_data.lock().unwrap();
+            let Some(image_data) = image_data.as_ref() else {
+                return;
+            };
+            let result = match action {
+                ImageAction::Copy => image_data
+                    .to_png()
+                    .and_then(|png| clipboard::set_png(&png))
+                    .context("Failed copying image"),
+                ImageAction::SaveAs => {
+                    save(image_data, src.as_deref()).context("Failed saving image")
+                }
+            };
+            if let Err(err) = result {
+                tracing::warn!("{err:#}");
+            }
+        });
+    }
+}
+
+fn save(image_data: &ImageData, src: Option<&str>) -> anyhow::Result<()> {
+    let (bytes, extension) = image_data.to_file()?;
+    let file_name = default_file_name(src, extension);
+    let Some(path) = dialog::save_file(&file_name)? else {
+        return Ok(());
+    };
+    std::fs::write(&path, bytes)?;
+    tracing::info!("Saved image to {}", path.display());
+    Ok(())
+}
+
+/// The last part of the image's path or URL, making sure that it ends in `extension`
+fn default_file_name(src: Option<&str>, extension: &str) -> String {
+    let name = src
+        .map(|src| src.split(['?', '#']).next().unwrap_or_default())
+        .and_then(|src| src.trim_end_matches('/').rsplit(['/', '\\']).next())
+        .map(|name| {
+            name.chars()
+                .filter(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | ' '))
+                .collect::<String>()
+        })
+        .filter(|name| !name.trim_matches('.').is_empty())
+        .unwrap_or_else(|| "image".to_owned());
+
+    let path = Path::new(&name);
+    match (path.file_stem(), path.extension()) {
+        (_, Some(ext)) if ext.eq_ignore_ascii_case(extension) => name,
+        (Some(stem), _) => format!("{}.{extension}", stem.to_string_lossy()),
+        (None, _) => format!("{name}.{extension}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_come_from_the_src() {
+        let name = |src| default_file_name(src, "png");
+        assert_eq!(name(Some("https://example.com/a/logo.png?v=2")), "logo.png");
+        assert_eq!(name(Some("assets\\Screenshot.PNG")), "Screenshot.PNG");
+        assert_eq!(name(Some("./badge.svg")), "badge.png");
+        assert_eq!(name(Some("https://example.com/render/")), "render.png");
+        assert_eq!(name(Some("https://example.com/\"quotes\"")), "quotes.png");
+        assert_eq!(name(Some("../")), "image.png");
+        assert_eq!(name(None), "image.png");
+    }
+
+    #[test]
+    fn selection_stays_on_the_menu() {
+        let image_data = ImageData::new(image::RgbaImage::new(1, 1), false);
+        let image = Image::from_image_data(Arc::new(Mutex::new(Some(image_data))), 1.);
+        let mut menu = ImageMenu::new(&image);
+        menu.select_prev();
+        assert_eq!(menu.selected(), ImageAction::Copy);
+        menu.select_next();
+        menu.select_next();
+        assert_eq!(menu.selected(), ImageAction::SaveAs);
+        assert_eq!(menu.panel().selected, Some(1));
+    }
+}
@@ -0,0 +1,471 @@
+//! Numbering for labeled figures, tables, and equations along with references to them
+//!
+//! This follows a subset of [pandoc-crossref](https://lierdakil.github.io/pandoc-crossref/)'s
+//! syntax
+//!
+//! ```markdown
+//! ![A cat](cat.png){#fig:cat}
+//!
+//! | Animal | Legs |
+//! | ------ | ---- |
+//! | Cat    | 4    |
+//!
+//! : Some animals {#tbl:animals}
+//!
+//! $$ E = mc^2 $$ {#eq:energy}
+//!
+//! See @fig:cat, [@tbl:animals], and equation [-@eq:energy]
+//! ```
+//!
+//! Equations get numbered and typeset like any other [math](crate::math)
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::math;
+use crate::utils::replace_with_html;
+
+use comrak::arena_tree::Node;
+use comrak::nodes::{Ast, AstNode, NodeLink, NodeValue};
+use comrak::{format_html, Arena, ComrakOptions};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Kind {
+    Figure,
+    Table,
+    Equation,
+}
+
+impl Kind {
+    fn new(prefix: &str) -> Option<Self> {
+        match prefix {
+            "fig" => Some(Self::Figure),
+            "tbl" => Some(Self::Table),
+            "eq" => Some(Self::Equation),
+            _ => None,
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Figure => "fig",
+            Self::Table => "tbl",
+            Self::Equation => "eq",
+        }
+    }
+
+    fn abbreviation(self, capitalized: bool) -> &'static str {
+        match (self, capitalized) {
+            (Self::Figure, false) => "fig.",
+            (Self::Figure, true) => "Fig.",
+            (Self::Table, false) => "tbl.",
+            (Self::Table, true) => "Tbl.",
+            (Self::Equation, false) => "eq.",
+            (Self::Equation, true) => "Eq.",
+        }
+    }
+}
+
+fn is_label_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Parses a label like `{#fig:cat}` into its kind and id
+fn parse_label(s: &str) -> Option<(Kind, &str)> {
+    let id = s.trim().strip_prefix("{#")?.strip_suffix('}')?;
+    let (prefix, name) = id.split_once(':')?;
+    let kind = Kind::new(prefix)?;
+    (!name.is_empty() && name.chars().all(is_label_char)).then_some((kind, id))
+}
+
+enum Labeled<'a> {
+    Figure { image: &'a AstNode<'a> },
+    Table { table: &'a AstNode<'a> },
+    Equation { body: String },
+}
+
+fn text_of<'a>(node: &'a AstNode<'a>) -> Option<String> {
+    match &node.data.borrow().value {
+        NodeValue::Text(text) => Some(text.to_owned()),
+        _ => None,
+    }
+}
+
+fn set_text<'a>(node: &'a AstNode<'a>, new_text: &str) {
+    if let NodeValue::Text(text) = &mut node.data.borrow_mut().value {
+        *text = new_text.to_owned();
+    }
+}
+
+/// Figures and equations are a paragraph on their own while tables get labeled by a caption
+/// paragraph right after them
+fn find_labeled<'a>(paragraph: &'a AstNode<'a>, md: &str) -> Option<(Kind, String, Labeled<'a>)> {
+    let children: Vec<_> = paragraph.children().collect();
+    match children.as_slice() {
+        [image, label] if matches!(image.data.borrow().value, NodeValue::Image(_)) => {
+            let (kind, id) = parse_label(&text_of(label)?).map(|(k, id)| (k, id.to_owned()))?;
+            (kind == Kind::Figure).then_some((kind, id, Labeled::Figure { image }))
+        }
+        [first, ..] => {
+            let last = children.last()?;
+            let last_text = text_of(last)?;
+            let label_start = last_text.rfind("{#")?;
+            let (kind, id) = parse_label(&last_text[label_start..])?;
+            let id = id.to_owned();
+            match kind {
+                Kind::Table => {
+                    let table = paragraph.previous_sibling()?;
+                    if !matches!(table.data.borrow().value, NodeValue::Table(_)) {
+                        return None;
+                    }
+                    let first_text = text_of(first)?;
+                    if !first_text.starts_with(':') && !first_text.starts_with("Table:") {
+                        return None;
+                    }
+                    // Trim the label first in case they're the same node
+                    set_text(last, last_text[..label_start].trim_end());
+                    let first_text = text_of(first)?;
+                    let caption = first_text
+                        .strip_prefix("Table:")
+                        .or_else(|| first_text.strip_prefix(':'))
+                        .unwrap_or(&first_text);
+                    set_text(first, caption.trim_start());
+                    Some((kind, id, Labeled::Table { table }))
+                }
+                Kind::Equation => {
+                    let sourcepos = paragraph.data.borrow().sourcepos;
+                    let source: Vec<_> = md
+                        .lines()
+                        .skip(sourcepos.start.line.saturating_sub(1))
+                        .take(sourcepos.end.line + 1 - sourcepos.start.line)
+                        .collect();
+                    let source = source.join("\n");
+                    let (math, label) = source.trim().strip_prefix("$$")?.rsplit_once("$$")?;
+                    parse_label(label)?;
+                    let body = math.trim().to_owned();
+                    Some((kind, id, Labeled::Equation { body }))
+                }
+                Kind::Figure => None,
+            }
+        }
+        [] => None,
+    }
+}
+
+fn render_html<'a>(
+    nodes: impl IntoIterator<Item = &'a AstNode<'a>>,
+    options: &ComrakOptions,
+) -> Option<String> {
+    let mut html = Vec::new();
+    for node in nodes {
+        format_html(node, options, &mut html).ok()?;
+    }
+    String::from_utf8(html).ok()
+}
+
+fn escape_attr(s: &str) -> String {
+    html_escape::encode_double_quoted_attribute(s).into_owned()
+}
+
+/// Numbers every labeled element and swaps in links in place of references to them
+pub fn resolve<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    md: &str,
+    options: &ComrakOptions,
+) {
+    let mut numbers = HashMap::new();
+    let mut counts: HashMap<Kind, usize> = HashMap::new();
+
+    let paragraphs: Vec<_> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Paragraph))
+        .collect();
+    for paragraph in paragraphs {
+        let Some((kind, id, labeled)) = find_labeled(paragraph, md) else {
+            continue;
+        };
+        let count = counts.entry(kind).or_default();
+        *count += 1;
+        let number = *count;
+        numbers.insert(id.clone(), (kind, number));
+
+        let anchor = format!("<a id=\"{}\"></a>", escape_attr(&id));
+        let html = match labeled {
+            Labeled::Figure { image } => {
+                let Some(image_html) = render_html([image], options) else {
+                    continue;
+                };
+                let Some(caption) = render_html(image.children(), options) else {
+                    continue;
+                };
+                format!(
+                    "<figure id=\"{}\">{image_html}<figcaption>Figure {number}: {caption}\
+                    </figcaption></figure>\n",
+                    escape_attr(&id),
+                )
+            }
+            Labeled::Table { table } => {
+                let Some(caption) = render_html(paragraph.children(), options) else {
+                    continue;
+                };
+                // Captions go above tables like everywhere else
+                table.insert_before(paragraph);
+                format!(
+                    "<p align=\"center\">{anchor}<small><em>Table {number}: {caption}</em></small>\
+                    </p>\n"
+                )
+            }
+            Labeled::Equation { body } => format!(
+                "<p align=\"center\">{anchor}{} ({number})</p>\n",
+                math::to_html(&body),
+            ),
+        };
+        replace_with_html(paragraph, html);
+    }
+
+    let texts: Vec<_> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Text(_)))
+        .filter(|node| {
+            !node.ancestors().any(|ancestor| {
+                matches!(
+                    ancestor.data.borrow().value,
+                    NodeValue::Link(_) | NodeValue::Image(_)
+                )
+            })
+        })
+        .collect();
+    for text_node in texts {
+        let Some(text) = text_of(text_node) else {
+            continue;
+        };
+        let pieces = split_references(&text);
+        if !pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Reference { .. }))
+        {
+            continue;
+        }
+
+        let start = text_node.data.borrow().sourcepos.start;
+        let new_node = |value| -> &'a AstNode<'a> {
+            arena.alloc(Node::new(RefCell::new(Ast::new(value, start))))
+        };
+        for piece in pieces {
+            let node = match piece {
+                Piece::Text(text) => new_node(NodeValue::Text(text.to_owned())),
+                Piece::Reference {
+                    id,
+                    capitalized,
+                    suppress_prefix,
+                } => match numbers.get(&id) {
+                    Some(&(kind, number)) => {
+                        let link = new_node(NodeValue::Link(NodeLink {
+                            url: format!("#{id}"),
+                            title: String::new(),
+                        }));
+                        let text = if suppress_prefix {
+                            number.to_string()
+                        } else {
+                            format!("{} {number}", kind.abbreviation(capitalized))
+                        };
+                        link.append(new_node(NodeValue::Text(text)));
+                        link
+                    }
+                    // Same marker pandoc-crossref uses for missing references
+                    None => new_node(NodeValue::Text(format!("¿{id}?"))),
+                },
+            };
+            text_node.insert_before(node);
+        }
+        text_node.detach();
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Piece<'text> {
+    Text(&'text str),
+    Reference {
+        id: String,
+        capitalized: bool,
+        suppress_prefix: bool,
+    },
+}
+
+/// Parses the part after the `@` in a reference like `@fig:cat` returning the id, whether it was
+/// capitalized, and how long it was
+fn parse_reference(s: &str) -> Option<(String, bool, usize)> {
+    let (prefix, rest) = s.split_once(':')?;
+    let capitalized = prefix.starts_with(char::is_uppercase);
+    let kind = Kind::new(&prefix.to_lowercase())?;
+    let name_len = rest.find(|c| !is_label_char(c)).unwrap_or(rest.len());
+    // Let trailing punctuation like in `see @fig:a-` be
+    let name = rest[..name_len].trim_end_matches(['-', '_']);
+    if name.is_empty() {
+        return None;
+    }
+    let id = format!("{}:{name}", kind.prefix());
+    Some((id, capitalized, prefix.len() + 1 + name.len()))
+}
+
+fn split_references(text: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest_start = 0;
+    let mut search_from = 0;
+    while let Some(offset) = text[search_from..].find('@') {
+        let at = search_from + offset;
+        search_from = at + 1;
+        let before = &text[rest_start..at];
+        // Some part of an email or handle
+        if before.ends_with(char::is_alphanumeric) {
+            continue;
+        }
+        let Some((id, capitalized, len)) = parse_reference(&text[at + 1..]) else {
+            continue;
+        };
+
+        let (mut start, mut end) = (at, at + 1 + len);
+        let mut suppress_prefix = false;
+        // The brackets in `[@fig:cat]` get dropped and `[-@fig:cat]` drops the "fig." too
+        if text[end..].starts_with(']') {
+            if before.ends_with("[-") {
+                start -= 2;
+                end += 1;
+                suppress_prefix = true;
+            } else if before.ends_with('[') {
+                start -= 1;
+                end += 1;
+            }
+        }
+
+        if rest_start < start {
+            pieces.push(Piece::Text(&text[rest_start..start]));
+        }
+        pieces.push(Piece::Reference {
+            id,
+            capitalized,
+            suppress_prefix,
+        });
+        rest_start = end;
+        search_from = end;
+    }
+    if rest_start < text.len() {
+        pieces.push(Piece::Text(&text[rest_start..]));
+    }
+
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use comrak::parse_document;
+
+    fn reference(id: &str) -> Piece<'static> {
+        Piece::Reference {
+            id: id.to_owned(),
+            capitalized: false,
+            suppress_prefix: false,
+        }
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(parse_label("{#fig:cat}"), Some((Kind::Figure, "fig:cat")));
+        assert_eq!(
+            parse_label(" {#tbl:a_b-2} "),
+            Some((Kind::Table, "tbl:a_b-2"))
+        );
+        assert_eq!(parse_label("{#eq:e}"), Some((Kind::Equation, "eq:e")));
+        assert_eq!(parse_label("{#sec:intro}"), None);
+        assert_eq!(parse_label("{#fig:}"), None);
+        assert_eq!(parse_label("{#fig:a b}"), None);
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(
+            split_references("See @fig:cat."),
+            [Piece::Text("See "), reference("fig:cat"), Piece::Text(".")],
+        );
+        assert_eq!(
+            split_references("[@tbl:x] and [-@eq:e]"),
+            [
+                reference("tbl:x"),
+                Piece::Text(" and "),
+                Piece::Reference {
+                    id: "eq:e".to_owned(),
+                    capitalized: false,
+                    suppress_prefix: true,
+                },
+            ],
+        );
+        assert_eq!(
+            split_references("@Fig:cat"),
+            [Piece::Reference {
+                id: "fig:cat".to_owned(),
+                capitalized: true,
+                suppress_prefix: false,
+            }],
+        );
+        // Emails, handles, and other things that happen to have an `@`
+        for text in ["me@fig:cat", "@someone: hi", "@fig: cat", "@"] {
+            assert!(
+                split_references(text)
+                    .iter()
+                    .all(|piece| matches!(piece, Piece::Text(_))),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn numbers_and_resolves() {
+        let md = "\
+See @fig:b, [@tbl:t], and [-@eq:e]. @fig:nope is missing
+
+![First](a.png)
+
+![Second *cat*](b.png){#fig:b}
+
+| A |
+| - |
+| 1 |
+
+: Some *numbers* {#tbl:t}
+
+$$ x < y $$ {#eq:e}
+";
+        let mut options = ComrakOptions::default();
+        options.extension.table = true;
+        options.render.unsafe_ = true;
+        let arena = Arena::new();
+        let root = parse_document(&arena, md, &options);
+        resolve(&arena, root, md, &options);
+        let mut html = Vec::new();
+        format_html(root, &options, &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+
+        insta::assert_snapshot!(html, @r###"
+        <p>See <a href="#fig:b">fig. 1</a>, <a href="#tbl:t">tbl. 1</a>, and <a href="#eq:e">1</a>. ¿fig:nope? is missing</p>
+        <p><img src="a.png" alt="First" /></p>
+        <figure id="fig:b"><img src="b.png" alt="Second cat" /><figcaption>Figure 1: Second <em>cat</em></figcaption></figure>
+        <p align="center"><a id="tbl:t"></a><small><em>Table 1: Some <em>numbers</em></em></small></p>
+        <table>
+        <thead>
+        <tr>
+        <th>A</th>
+        </tr>
+        </thead>
+        <tbody>
+        <tr>
+        <td>1</td>
+        </tr>
+        </tbody>
+        </table>
+        <p align="center"><a id="eq:e"></a><em>x &lt; y</em> (1)</p>
+        "###);
+    }
+}
@@ -79,6 +79,8 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         is_italic: bool,
         is_underlined: bool,
         is_striked: bool,
+        is_marked: bool,
+        is_key_cap: bool,
     }
 
     impl StyleWrapper {
@@ -88,9 +90,19 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 is_italic,
                 is_underlined,
                 is_striked,
+                is_marked,
+                is_key_cap,
             } = self;
 
-            ![is_bold, is_italic, is_underlined, is_striked].contains(&true)
+            ![
+                is_bold,
+                is_italic,
+                is_underlined,
+                is_striked,
+                is_marked,
+                is_key_cap,
+            ]
+            .contains(&true)
         }
     }
 
@@ -101,6 +113,8 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 is_italic,
                 is_underlined,
                 is_striked,
+                is_marked,
+                is_key_cap,
             } = *self;
 
             if self.is_regular() {
@@ -118,6 +132,12 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 if is_striked {
                     f.write_str("STRIKED ")?;
                 }
+                if is_marked {
+                    f.write_str("MARKED ")?;
+                }
+                if is_key_cap {
+                    f.write_str("KEY_CAP ")?;
+                }
             }
 
             Ok(())
@@ -132,6 +152,9 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         is_italic,
         is_underlined,
         is_striked,
+        is_marked,
+        is_key_cap,
+        script,
         font_family,
         // Globally consistent so avoid displaying as noise
         hidpi_scale: _,
@@ -158,10 +181,13 @@ pub fn text(text: &Text, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         is_italic: *is_italic,
         is_underlined: *is_underlined,
         is_striked: *is_striked,
+        is_marked: *is_marked,
+        is_key_cap: *is_key_cap,
     };
     if !style.is_regular() {
         debug.field("style", &style);
     }
+    debug_inline_some(&mut debug, "script", script);
     debug_inline_some(&mut debug, "link", link);
 
     debug.finish_non_exhaustive()
@@ -0,0 +1,34 @@
+//! Non-fatal problems noticed while rendering a document
+//!
+//! These would otherwise only show up in the logs, so they're collected to be displayed in the
+//! diagnostics panel instead
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+pub type Diagnostics = Arc<Mutex<Vec<Diagnostic>>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The line in the markdown source where the problem was found, if known
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
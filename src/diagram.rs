@@ -0,0 +1,224 @@
+//! Renders diagrams from `mermaid` and `dot` code blocks with the programs that draw them
+//!
+//! Each diagram language is drawn to an SVG by an external program, like
+//! [`mmdc`](https://github.com/mermaid-js/mermaid-cli) for mermaid. Just like charts the SVG is
+//! kept in the cache directory named after a hash of the block, so the (often slow) program only
+//! runs again when the block actually changes
+
+use std::ffi::OsString;
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context};
+use twox_hash::XxHash64;
+
+/// Bump whenever the output changes so that stale diagrams don't get reused
+const RENDER_VERSION: u64 = 1;
+
+/// A program that draws diagrams written in some language
+struct Tool {
+    /// The info string languages that it draws
+    langs: &'static [&'static str],
+    program: &'static str,
+    /// What the block's code gets saved as, since some programs go off of the extension
+    extension: &'static str,
+    /// `{input}` and `{output}` get swapped out for the paths of the block's code and the SVG
+    /// while `{text}` becomes the text color and `{theme}` either `default` or `dark`
+    args: &'static [&'static str],
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        langs: &["mermaid"],
+        program: "mmdc",
+        extension: "mmd",
+        args: &[
+            "--input",
+            "{input}",
+            "--output",
+            "{output}",
+            "--theme",
+            "{theme}",
+            "--backgroundColor",
+            "transparent",
+        ],
+    },
+    Tool {
+        langs: &["dot", "graphviz"],
+        program: "dot",
+        extension: "dot",
+        args: &[
+            "-Tsvg",
+            "-Gbgcolor=transparent",
+            "-Gcolor={text}",
+            "-Gfontcolor={text}",
+            "-Ncolor={text}",
+            "-Nfontcolor={text}",
+            "-Ecolor={text}",
+            "-Efontcolor={text}",
+            "-o",
+            "{output}",
+            "{input}",
+        ],
+    },
+];
+
+fn tool(lang: &str) -> Option<&'static Tool> {
+    TOOLS.iter().find(|tool| tool.langs.contains(&lang))
+}
+
+/// The info string languages that get rendered as diagrams
+pub fn is_diagram_lang(lang: &str) -> bool {
+    tool(lang).is_some()
+}
+
+impl Tool {
+    fn args(&self, input: &Path, output: &Path, text_color: u32) -> Vec<OsString> {
+        let text = format!("#{:06x}", text_color & 0xff_ff_ff);
+        // Light text means that it's going on a dark background
+        let [_, r, g, b] = text_color.to_be_bytes();
+        let luma = 0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b);
+        let theme = if luma > 127.5 { "dark" } else { "default" };
+
+        self.args
+            .iter()
+            .map(|&arg| -> OsString {
+                match arg {
+                    "{input}" => input.into(),
+                    "{output}" => output.into(),
+                    arg => arg
+                        .replace("{text}", &text)
+                        .replace("{theme}", theme)
+                        .into(),
+                }
+            })
+            .collect()
+    }
+
+    fn run(&self, input: &Path, output: &Path, text_color: u32) -> anyhow::Result<()> {
+        let result = Command::new(self.program)
+            .args(self.args(input, output, text_color))
+            .output();
+        let result = match result {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                bail!(
+                    "`{}` needs to be installed to draw {} diagrams",
+                    self.program,
+                    self.langs[0]
+                );
+            }
+            result => result.with_context(|| format!("Failed running `{}`", self.program))?,
+        };
+        if !result.status.success() {
+            bail!(
+                "`{}` failed: {}",
+                self.program,
+                String::from_utf8_lossy(&result.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("inlyne")
+        .join("diagrams")
+}
+
+fn cached_path(dir: &Path, lang: &str, code: &str, text_color: u32) -> PathBuf {
+    let mut hasher = XxHash64::default();
+    hasher.write_u64(RENDER_VERSION);
+    hasher.write(lang.as_bytes());
+    hasher.write(code.as_bytes());
+    hasher.write_u32(text_color);
+    dir.join(format!("{:016x}.svg", hasher.finish()))
+}
+
+/// Renders the diagram described by a code block returning the path to the rendered SVG
+pub fn render_cached(lang: &str, code: &str, text_color: u32) -> anyhow::Result<PathBuf> {
+    render_cached_in(&cache_dir(), lang, code, text_color)
+}
+
+fn render_cached_in(
+    dir: &Path,
+    lang: &str,
+    code: &str,
+    text_color: u32,
+) -> anyhow::Result<PathBuf> {
+    let tool = tool(lang).with_context(|| format!("Unknown diagram language: {lang}"))?;
+    let path = cached_path(dir, lang, code, text_color);
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    fs::create_dir_all(dir)?;
+    let input = path.with_extension(tool.extension);
+    fs::write(&input, code)?;
+    // Draw then rename so that a half-written diagram never gets picked up. The extension has to
+    // stay `.svg` for the same reason as above
+    let temp_path = path.with_extension("tmp.svg");
+    let drawn = tool.run(&input, &temp_path, text_color);
+    let _ = fs::remove_file(&input);
+    drawn?;
+    fs::rename(&temp_path, &path)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages() {
+        assert!(is_diagram_lang("mermaid"));
+        assert!(is_diagram_lang("dot"));
+        assert!(is_diagram_lang("graphviz"));
+        assert!(!is_diagram_lang("rust"));
+        assert!(!is_diagram_lang("chart"));
+    }
+
+    #[test]
+    fn filling_in_args() {
+        let mermaid = tool("mermaid").unwrap();
+        let args = mermaid.args(Path::new("in.mmd"), Path::new("out.svg"), 0xDDDDDD);
+        assert_eq!(
+            args,
+            [
+                "--input",
+                "in.mmd",
+                "--output",
+                "out.svg",
+                "--theme",
+                "dark",
+                "--backgroundColor",
+                "transparent"
+            ]
+        );
+        let args = mermaid.args(Path::new("in.mmd"), Path::new("out.svg"), 0x333333);
+        assert_eq!(args[5], "default");
+
+        let dot = tool("dot").unwrap();
+        let args = dot.args(Path::new("in.dot"), Path::new("out.svg"), 0x9DACBB);
+        assert!(args.contains(&OsString::from("-Nfontcolor=#9dacbb")));
+        assert_eq!(args.last().unwrap(), "in.dot");
+    }
+
+    #[test]
+    fn reuses_cached_diagrams() {
+        let dir = tempfile::tempdir().unwrap();
+        let code = "graph TD\n  A --> B";
+        let path = cached_path(dir.path(), "mermaid", code, 0x333333);
+        fs::write(&path, "<svg></svg>").unwrap();
+
+        // Already drawn, so `mmdc` doesn't even need to be installed
+        let rendered = render_cached_in(dir.path(), "mermaid", code, 0x333333).unwrap();
+        assert_eq!(rendered, path);
+        assert!(render_cached_in(dir.path(), "plantuml", code, 0x333333).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Native file and print dialogs, by way of the tools that each platform already has around
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use anyhow::Context;
+
+/// Asks where to save a file, suggesting `file_name`. Gives `None` when the dialog gets cancelled
+pub fn save_file(file_name: &str) -> anyhow::Result<Option<PathBuf>> {
+    #[cfg(target_os = "macos")]
+    let output = {
+        let script = format!("POSIX path of (choose file name default name \"{file_name}\")");
+        Command::new("osascript").args(["-e", &script]).output()
+    };
+
+    #[cfg(windows)]
+    let output = {
+        let script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms; \
+            $dialog = New-Object System.Windows.Forms.SaveFileDialog; \
+            $dialog.FileName = '{file_name}'; \
+            if ($dialog.ShowDialog() -eq 'OK') {{ $dialog.FileName }}"
+        );
+        Command::new("powershell")
+            .args(["-STA", "-NoProfile", "-Command", &script])
+            .output()
+    };
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let output = {
+        let zenity = Command::new("zenity")
+            .args(["--file-selection", "--save", "--confirm-overwrite"])
+            .arg(format!("--filename={file_name}"))
+            .output();
+        match zenity {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Command::new("kdialog")
+                .args(["--getsavefilename", file_name])
+                .output(),
+            output => output,
+        }
+    };
+
+    chosen_path(output)
+}
+
+/// Hands `pdf` off to the platform's printing. Windows and macOS bring up a print dialog for it
+/// straight away, while elsewhere it gets opened in the default viewer where printing is one
+/// shortcut away
+pub fn print_pdf(pdf: &Path) -> anyhow::Result<()> {
+    #[cfg(target_os = "macos")]
+    let status = {
+        let script = format!(
+            "tell application \"Preview\" to print POSIX file \"{}\" with print dialog",
+            pdf.display()
+        );
+        Command::new("osascript").args(["-e", &script]).status()
+    };
+
+    #[cfg(windows)]
+    let status = {
+        let script = format!("Start-Process -FilePath '{}' -Verb Print", pdf.display());
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command", &script])
+            .status()
+    };
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let status = Command::new("xdg-open").arg(pdf).status();
+
+    let status = status.context("Couldn't find anything to print with")?;
+    anyhow::ensure!(status.success(), "Printing exited with {status}");
+    Ok(())
+}
+
+/// Every dialog prints the chosen path, and exits unsuccessfully or prints nothing when cancelled
+fn chosen_path(output: io::Result<Output>) -> anyhow::Result<Option<PathBuf>> {
+    let output = output.context("Couldn't find anything to show a save dialog with")?;
+    let path = String::from_utf8_lossy(&output.stdout);
+    let path = path.trim_end_matches(['\r', '\n']);
+    if !output.status.success() || path.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(PathBuf::from(path)))
+}
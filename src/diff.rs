@@ -0,0 +1,115 @@
+//! Diffs in ```` ```diff ```` blocks
+//!
+//! Added and removed lines get a green or red background that spans the whole block. A language
+//! can come after the `diff` like ```` ```diff-rust ````, in which case the code is highlighted as
+//! that language with the `+`/`-`/` ` markers taken off beforehand and put back afterwards
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffLine {
+    Added,
+    Removed,
+}
+
+/// Whether a code block marked with `lang` holds a diff
+pub fn is_diff(lang: &str) -> bool {
+    lang == "diff" || lang.starts_with("diff-")
+}
+
+/// The language that the code in a ```` ```diff-lang ```` block gets highlighted as
+pub fn inner_language(lang: &str) -> Option<&str> {
+    lang.strip_prefix("diff-").filter(|inner| !inner.is_empty())
+}
+
+/// Which of the lines in `code` were added or removed. File headers like `+++ b/file` are neither
+pub fn line_kinds(code: &str) -> Vec<Option<DiffLine>> {
+    code.lines()
+        .map(|line| {
+            if line.starts_with("+++") || line.starts_with("---") {
+                None
+            } else if line.starts_with('+') {
+                Some(DiffLine::Added)
+            } else if line.starts_with('-') {
+                Some(DiffLine::Removed)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Takes the marker off the front of every line, giving back the markers and what's left
+pub fn strip_markers(code: &str) -> (Vec<&str>, String) {
+    let mut markers = Vec::new();
+    let mut stripped = String::with_capacity(code.len());
+    for line in code.split_inclusive('\n') {
+        let marker_len = match line.as_bytes().first() {
+            Some(b'+' | b'-' | b' ') => 1,
+            _ => 0,
+        };
+        markers.push(&line[..marker_len]);
+        stripped.push_str(&line[marker_len..]);
+    }
+    (markers, stripped)
+}
+
+/// Puts the markers from [`strip_markers`] back at the start of every line of the highlighted
+/// `html`. The markers don't need escaping and the highlighting keeps the newlines as they were
+pub fn restore_markers(html: &str, markers: &[&str]) -> String {
+    let mut markers = markers.iter();
+    let mut restored = String::with_capacity(html.len() + markers.len());
+    if let Some(marker) = markers.next() {
+        restored.push_str(marker);
+    }
+    for part in html.split_inclusive('\n') {
+        restored.push_str(part);
+        if part.ends_with('\n') {
+            if let Some(marker) = markers.next() {
+                restored.push_str(marker);
+            }
+        }
+    }
+    restored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages() {
+        assert!(is_diff("diff"));
+        assert!(is_diff("diff-rust"));
+        assert!(!is_diff("difficult"));
+        assert_eq!(inner_language("diff-rust"), Some("rust"));
+        assert_eq!(inner_language("diff"), None);
+        assert_eq!(inner_language("diff-"), None);
+    }
+
+    #[test]
+    fn kinds() {
+        let code = "--- a/main.rs\n+++ b/main.rs\n@@ -1 +1 @@\n-old\n+new\n same";
+        assert_eq!(
+            line_kinds(code),
+            [
+                None,
+                None,
+                None,
+                Some(DiffLine::Removed),
+                Some(DiffLine::Added),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn markers_round_trip() {
+        let code = "-let a = 1;\n+let a = 2;\n unchanged\nno marker\n";
+        let (markers, stripped) = strip_markers(code);
+        assert_eq!(stripped, "let a = 1;\nlet a = 2;\nunchanged\nno marker\n");
+        let html = "<span>let a = 1;\nlet a</span> = 2;\nunchanged\nno marker\n";
+        assert_eq!(
+            restore_markers(html, &markers),
+            "-<span>let a = 1;\n+let a</span> = 2;\n unchanged\nno marker\n"
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::callout::Callout;
+use crate::color::{mix, ImageBackdrop, Theme};
+use crate::image;
+use crate::opts::{ExportOpts, FontOptions};
+use crate::serve;
+use crate::title;
+use crate::utils::{markdown_to_html, MarkdownOptions};
+
+use anyhow::Context;
+use base64::prelude::*;
+
+/// Writes the document out as one HTML file that looks like it does in the viewer. Local images
+/// get embedded in it, so that it still works after getting moved somewhere else
+pub fn write(opts: &ExportOpts, out: &Path) -> anyhow::Result<()> {
+    let md = fs::read_to_string(&opts.file_path)
+        .with_context(|| format!("Could not read file at '{}'", opts.file_path.display()))?;
+    let title = title::first_heading(&md).unwrap_or_else(|| {
+        opts.file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let body = markdown_to_html(&md, opts.theme.code_highlighter.clone(), opts.markdown);
+    let body = inline_images(&body, &opts.file_path);
+    let style = format!(
+        "<style>\n{}</style>\n",
+        font_css(&opts.font_opts, opts.page_width)
+    );
+    let page = wrap_body(&body, &opts.theme, &title, &style);
+    fs::write(out, page)
+        .with_context(|| format!("Failed to write the HTML to '{}'", out.display()))?;
+    tracing::info!("Wrote HTML to '{}'", out.display());
+    Ok(())
+}
+
+/// Renders markdown into a standalone HTML document styled to match `theme` with some extra
+/// content injected into the `<head>`
+pub fn page_with_head(
+    md: &str,
+    theme: &Theme,
+    title: &str,
+    extra_head: &str,
+    markdown: MarkdownOptions,
+) -> String {
+    let body = markdown_to_html(md, theme.code_highlighter.clone(), markdown);
+    wrap_body(&body, theme, title, extra_head)
+}
+
+/// Wraps an already rendered HTML body in a full document
+pub fn wrap_body(body: &str, theme: &Theme, title: &str, extra_head: &str) -> String {
+    let mut page = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    page.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    page.push_str("<title>");
+    html_escape::encode_text_to_string(title, &mut page);
+    page.push_str("</title>\n<style>\n");
+    page.push_str(&theme_css(theme));
+    page.push_str("</style>\n");
+    page.push_str(extra_head);
+    page.push_str("</head>\n<body>\n<main>\n");
+    page.push_str(body);
+    page.push_str("</main>\n</body>\n</html>\n");
+    page
+}
+
+fn hex(color: u32) -> String {
+    format!("#{:06x}", color & 0xff_ff_ff)
+}
+
+/// Converts the colors from a theme into an equivalent stylesheet
+pub fn theme_css(theme: &Theme) -> String {
+    let text = hex(theme.text_color);
+    let background = hex(theme.background_color);
+    let heading = hex(theme.heading_color);
+    let code = hex(theme.code_color);
+    let quote = hex(theme.quote_block_color);
+    let quote_bar = hex(theme.quote_bar_color);
+    let link = hex(theme.link_color);
+    let select = hex(theme.select_color);
+    let table_border = hex(theme.table_border_color);
+    let checkbox = hex(theme.checkbox_color);
+    let mark = hex(theme.mark_color);
+    let scrollbar = hex(theme.scrollbar_color);
+
+    let mut css = String::new();
+    let _ = writeln!(
+        css,
+        "body {{ background: {background}; color: {text}; margin: 0; \
+        font-family: sans-serif; line-height: 1.5; }}"
+    );
+    let _ = writeln!(css, "html {{ scrollbar-color: {scrollbar} transparent; }}");
+    let _ = writeln!(
+        css,
+        "main {{ max-width: 960px; margin: 0 auto; padding: 2em 100px; }}"
+    );
+    let _ = writeln!(css, "h1, h2, h3, h4, h5, h6 {{ color: {heading}; }}");
+    let _ = writeln!(css, "a {{ color: {link}; }}");
+    let _ = writeln!(css, "::selection {{ background: {select}; }}");
+    let _ = writeln!(css, "code {{ color: {code}; font-family: monospace; }}");
+    let _ = writeln!(
+        css,
+        "pre {{ padding: 0.75em 1em; overflow-x: auto; }}\npre code {{ color: inherit; }}"
+    );
+    let _ = writeln!(
+        css,
+        "blockquote {{ background: {quote}; border-left: 5px solid {quote_bar}; \
+        margin: 0; padding: 0.25em 1em; }}"
+    );
+    let _ = writeln!(
+        css,
+        "table {{ border-collapse: collapse; }}\nth, td {{ padding: 0.25em 1em; }}\n\
+        thead {{ border-bottom: 2px solid {table_border}; }}\n\
+        tr {{ border-bottom: 1px solid {table_border}; }}"
+    );
+    let _ = writeln!(
+        css,
+        "hr {{ border: none; border-top: 2px solid {text}; }}\nimg {{ max-width: 100%; }}"
+    );
+    let _ = writeln!(
+        css,
+        "input[type=\"checkbox\"] {{ accent-color: {checkbox}; }}"
+    );
+    let _ = writeln!(css, "mark {{ background: {mark}; color: inherit; }}");
+    let _ = writeln!(
+        css,
+        "kbd {{ background: {quote}; border: 1px solid {text}; border-radius: 3px; \
+        padding: 0 0.3em; font-family: monospace; }}"
+    );
+    for callout in Callout::ALL {
+        let name = callout.name();
+        let color = callout.color(theme);
+        let tint = hex(mix(theme.background_color, color, 0.12));
+        let color = hex(color);
+        let _ = writeln!(
+            css,
+            "blockquote:has(> p[data-callout=\"{name}\"]) {{ background: {tint}; \
+            border-left-color: {color}; }}\np[data-callout=\"{name}\"] {{ color: {color}; }}"
+        );
+    }
+    match theme.image_backdrop {
+        ImageBackdrop::None => {}
+        ImageBackdrop::Checkerboard => {
+            let light = hex(ImageBackdrop::CHECKER_LIGHT);
+            let dark = hex(ImageBackdrop::CHECKER_DARK);
+            let _ = writeln!(
+                css,
+                "img {{ background: repeating-conic-gradient({dark} 0% 25%, {light} 0% 50%) \
+                0 0 / 16px 16px; }}"
+            );
+        }
+        ImageBackdrop::Solid(color) => {
+            let _ = writeln!(css, "img {{ background: {}; }}", hex(color));
+        }
+    }
+    css
+}
+
+/// The fonts and content width from the config, which win out over the defaults in
+/// [`theme_css()`]
+fn font_css(font_opts: &FontOptions, page_width: Option<f32>) -> String {
+    // Nothing that could close the quotes or the `<style>` that it ends up in
+    let family = |font: &str| font.replace(['"', '\\', '<'], "");
+    let mut css = String::new();
+    if let Some(font) = &font_opts.regular_font {
+        let _ = writeln!(
+            css,
+            "body {{ font-family: \"{}\", sans-serif; }}",
+            family(font)
+        );
+    }
+    if let Some(font) = &font_opts.monospace_font {
+        let _ = writeln!(
+            css,
+            "code, kbd {{ font-family: \"{}\", monospace; }}",
+            family(font)
+        );
+    }
+    if let Some(size) = font_opts.font_size {
+        let _ = writeln!(css, "body {{ font-size: {size}px; }}");
+    }
+    if let Some(scales) = font_opts.heading_scales {
+        for (level, scale) in (1..).zip(scales) {
+            let _ = writeln!(css, "h{level} {{ font-size: {scale}em; }}");
+        }
+    }
+    if let Some(width) = page_width {
+        let _ = writeln!(css, "main {{ max-width: {width}px; }}");
+    }
+    css
+}
+
+/// Swaps the `src` of each `<img>` that's a local file for a `data:` URI with the file's contents
+fn inline_images(body: &str, file_path: &Path) -> String {
+    const SRC: &str = " src=\"";
+
+    let mut inlined = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find("<img") {
+        let tag_end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |end| start + end);
+        let Some(src_start) = rest[start..tag_end]
+            .find(SRC)
+            .map(|i| start + i + SRC.len())
+        else {
+            inlined.push_str(&rest[..tag_end]);
+            rest = &rest[tag_end..];
+            continue;
+        };
+        let src_end = rest[src_start..]
+            .find('"')
+            .map_or(rest.len(), |end| src_start + end);
+        inlined.push_str(&rest[..src_start]);
+        let src = &rest[src_start..src_end];
+        match data_uri(&html_escape::decode_html_entities(src), file_path) {
+            Some(uri) => inlined.push_str(&uri),
+            None => inlined.push_str(src),
+        }
+        rest = &rest[src_end..];
+    }
+    inlined.push_str(rest);
+    inlined
+}
+
+fn data_uri(src: &str, file_path: &Path) -> Option<String> {
+    let is_remote = src.contains("://") && !src.starts_with("file://");
+    if is_remote || src.starts_with("data:") {
+        return None;
+    }
+    let path = image::resolve_path(src, file_path);
+    match fs::read(&path) {
+        Ok(bytes) => Some(format!(
+            "data:{};base64,{}",
+            serve::content_type(&path),
+            BASE64_STANDARD.encode(bytes)
+        )),
+        Err(err) => {
+            tracing::warn!("Failed embedding image at '{}': {err}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inlining_images() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a b.png"), b"inlyne").unwrap();
+        let file_path = dir.path().join("README.md");
+
+        let body = "<p><img src=\"a%20b.png\" alt=\"A\"> <img src=\"https://example.com/b.png\">\
+            <img alt=\"No src\"><img src=\"missing.png\"></p>";
+        assert_eq!(
+            inline_images(body, &file_path),
+            "<p><img src=\"data:image/png;base64,aW5seW5l\" alt=\"A\"> \
+            <img src=\"https://example.com/b.png\"><img alt=\"No src\"><img src=\"missing.png\"></p>"
+        );
+    }
+
+    #[test]
+    fn fonts_from_the_config() {
+        let font_opts = FontOptions {
+            regular_font: Some("Fira \"Sans\"".to_owned()),
+            font_size: Some(18.),
+            ..Default::default()
+        };
+        assert_eq!(
+            font_css(&font_opts, None),
+            "body { font-family: \"Fira Sans\", sans-serif; }\nbody { font-size: 18px; }\n"
+        );
+        assert_eq!(
+            font_css(&FontOptions::default(), Some(700.)),
+            "main { max-width: 700px; }\n"
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! Rendering documents to formats other than the live window
+
+pub mod html;
+pub mod pdf;
+pub mod png;
+pub mod slides;
+
+use crate::opts::ExportOpts;
+
+/// Writes the document out in each of the formats that were asked for
+pub fn run(opts: ExportOpts) -> anyhow::Result<()> {
+    if let Some(out) = &opts.png {
+        png::write(&opts, out)?;
+    }
+    if let Some(out) = &opts.pdf {
+        pdf::write(&opts, out)?;
+    }
+    if let Some(out) = &opts.html {
+        html::write(&opts, out)?;
+    }
+    if let Some(out) = &opts.slides {
+        slides::write(&opts, out)?;
+    }
+    Ok(())
+}
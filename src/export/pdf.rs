@@ -0,0 +1,748 @@
+//! Writing a document out as a PDF split into pages
+//!
+//! The document gets laid out headlessly at the width of the paper and cut into pages between its
+//! blocks. Each page is tessellated just like it would be for the screen, but the triangles get
+//! written out as paths instead of being drawn. Text is kept as text in subsets of the fonts that
+//! it was shaped with, and links stay clickable
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::png;
+use crate::color::from_native_color;
+use crate::image::Image;
+use crate::opts::ExportOpts;
+use crate::positioner::Positioned;
+use crate::renderer::{self, Renderer};
+use crate::text::CachedTextArea;
+use crate::title;
+use crate::utils::{Point, Rect};
+use crate::Element;
+
+use anyhow::Context;
+use glyphon::fontdb::ID;
+use glyphon::{CacheKey, Color, Font, FontSystem};
+use pdf_writer::types::{
+    ActionType, AnnotationType, CidFontType, FontFlags, SystemInfo, UnicodeCmap,
+};
+use pdf_writer::{Content, Filter, Finish, Name, PdfWriter, Rect as PdfRect, Ref, Str, TextStr};
+
+/// Pixels are 1/96th of an inch while points are 1/72nd
+pub(super) const PX_PER_PT: f32 = 96. / 72.;
+/// The space left above and below what's on each page, in points
+const MARGIN: f32 = 48.;
+
+const SYSTEM_INFO: SystemInfo = SystemInfo {
+    registry: Str(b"Adobe"),
+    ordering: Str(b"Identity"),
+    supplement: 0,
+};
+
+pub fn write(opts: &ExportOpts, out: &Path) -> anyhow::Result<()> {
+    let md = fs::read_to_string(&opts.file_path)
+        .with_context(|| format!("Could not read file at '{}'", opts.file_path.display()))?;
+    write_markdown(opts, &md, out)
+}
+
+/// Like [`write()`], but for markdown that doesn't have to come from `opts.file_path`. Relative
+/// links and images still get resolved from there
+pub fn write_markdown(opts: &ExportOpts, md: &str, out: &Path) -> anyhow::Result<()> {
+    let (page_width, page_height) = opts.page_size.points();
+    let width = (page_width * PX_PER_PT).round() as u32;
+    let (mut renderer, mut elements) = png::render_document(opts, md, width)?;
+
+    let content_height = (page_height - 2. * MARGIN) * PX_PER_PT;
+    let tops = page_breaks(&blocks(renderer.zoom, &elements), content_height);
+    let doc_height = renderer.positioner.reserved_height;
+    renderer.resize(width, content_height.ceil() as u32);
+
+    let mut pdf = PdfDocument::new((page_width, page_height));
+    let mut pages = Vec::with_capacity(tops.len());
+    for (i, &top) in tops.iter().enumerate() {
+        let bottom = tops.get(i + 1).copied().unwrap_or(doc_height);
+        pages.push(pdf.draw_page(&mut renderer, &mut elements, top, bottom)?);
+    }
+
+    let links = renderer.find_links(&elements);
+    let anchors = &renderer.positioner.anchors;
+    let page_of = |y: f32| tops.iter().rposition(|&top| top <= y).unwrap_or_default();
+    let mut annotations = vec![Vec::new(); tops.len()];
+    for (rect, link) in links {
+        let page = page_of(rect.pos.1);
+        let target = match anchors.get(&link) {
+            Some(&y) => {
+                let dest = page_of(y);
+                Target::Position(dest, pdf.to_page((0., y), tops[dest]).1)
+            }
+            None => Target::Uri(link),
+        };
+        annotations[page].push((pdf.to_page_rect(&rect, tops[page]), target));
+    }
+
+    let title = title::first_heading(md);
+    let bytes = pdf.finish(pages, annotations, title.as_deref())?;
+    fs::write(out, bytes)
+        .with_context(|| format!("Failed to write the PDF to '{}'", out.display()))?;
+    tracing::info!("Wrote {} page PDF to '{}'", tops.len(), out.display());
+    Ok(())
+}
+
+/// A stretch of the document that pages get broken around
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Block {
+    top: f32,
+    bottom: f32,
+    /// Text can be broken up between its lines
+    line_height: Option<f32>,
+    /// Moved onto the next page instead of being cut in two when it fits on one
+    keep_whole: bool,
+}
+
+fn blocks(zoom: f32, elements: &[Positioned<Element>]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    collect_blocks(zoom, elements, &mut blocks);
+    blocks
+}
+
+fn collect_blocks(zoom: f32, elements: &[Positioned<Element>], blocks: &mut Vec<Block>) {
+    for element in elements {
+        let (line_height, keep_whole) = match &element.inner {
+            Element::TextBox(text_box) => {
+                (Some(text_box.line_height(zoom)), text_box.is_code_block)
+            }
+            Element::Image(_) | Element::Table(_) | Element::Row(_) => (None, true),
+            Element::Section(section) => {
+                if let Some(summary) = &*section.summary {
+                    collect_blocks(zoom, std::slice::from_ref(summary), blocks);
+                }
+                if !*section.hidden.borrow() {
+                    collect_blocks(zoom, &section.elements, blocks);
+                }
+                continue;
+            }
+            Element::Spacer(_) => continue,
+        };
+        if let Some(bounds) = &element.bounds {
+            blocks.push(Block {
+                top: bounds.pos.1,
+                bottom: bounds.max().1,
+                line_height,
+                keep_whole,
+            });
+        }
+    }
+}
+
+/// Where each page starts in the document. Pages end before blocks that should be kept whole and
+/// between the lines of text, only cutting through things that are taller than a page
+fn page_breaks(blocks: &[Block], page_height: f32) -> Vec<f32> {
+    let mut tops = vec![0.];
+    let mut top = 0.;
+    for block in blocks {
+        while block.bottom > top + page_height {
+            let bottom = top + page_height;
+            let fits = block.bottom - block.top <= page_height;
+            top = if block.top >= bottom || (block.keep_whole && fits && block.top > top) {
+                block.top
+            } else {
+                block
+                    .line_height
+                    .map(|line_height| {
+                        let lines = ((bottom - block.top) / line_height).floor();
+                        block.top + lines * line_height
+                    })
+                    .filter(|&cut| cut > top)
+                    .unwrap_or(bottom)
+            };
+            tops.push(top);
+        }
+    }
+    tops
+}
+
+#[derive(Clone)]
+enum Target {
+    Uri(String),
+    /// A page and how far up it the spot is, in points
+    Position(usize, f32),
+}
+
+struct UsedFont {
+    /// What it's called in each page's resources
+    name: String,
+    type0_ref: Ref,
+    font: Arc<Font>,
+    index: u32,
+    post_script_name: String,
+    italic: bool,
+    monospaced: bool,
+    /// Every glyph that got shown along with the text that it came from
+    glyphs: BTreeMap<u16, String>,
+}
+
+struct PdfDocument {
+    writer: PdfWriter,
+    next_ref: i32,
+    /// Width and height of the paper in points
+    size: (f32, f32),
+    fonts: Vec<UsedFont>,
+    font_indices: HashMap<ID, usize>,
+    /// Keyed by the image's data, so that images cut in two across pages are only embedded once
+    images: HashMap<usize, (String, Ref)>,
+    /// The graphics states for drawing with each level of opacity
+    alphas: BTreeMap<u8, Ref>,
+}
+
+impl PdfDocument {
+    fn new(size: (f32, f32)) -> Self {
+        Self {
+            writer: PdfWriter::new(),
+            next_ref: 0,
+            size,
+            fonts: Vec::new(),
+            font_indices: HashMap::new(),
+            images: HashMap::new(),
+            alphas: BTreeMap::new(),
+        }
+    }
+
+    fn alloc(&mut self) -> Ref {
+        self.next_ref += 1;
+        Ref::new(self.next_ref)
+    }
+
+    /// Where a point on the page that starts at `top` in the document ends up on the paper
+    fn to_page(&self, (x, y): Point, top: f32) -> Point {
+        (x / PX_PER_PT, self.size.1 - MARGIN - (y - top) / PX_PER_PT)
+    }
+
+    fn to_page_rect(&self, rect: &Rect, top: f32) -> PdfRect {
+        let (x1, y1) = self.to_page(rect.pos, top);
+        let (x2, y2) = self.to_page(rect.max(), top);
+        PdfRect::new(x1, y2, x2, y1)
+    }
+
+    /// Draws the part of the document from `top` to `bottom`
+    fn draw_page(
+        &mut self,
+        renderer: &mut Renderer,
+        elements: &mut [Positioned<Element>],
+        top: f32,
+        bottom: f32,
+    ) -> anyhow::Result<Vec<u8>> {
+        // Set directly since the last page can't always be scrolled to
+        renderer.scroll_y = top;
+        let text_areas = renderer.tessellate(elements)?;
+
+        let mut content = Content::new();
+        let [_, r, g, b] = renderer.theme.background_color.to_be_bytes();
+        content.set_fill_rgb(
+            f32::from(r) / 255.,
+            f32::from(g) / 255.,
+            f32::from(b) / 255.,
+        );
+        content
+            .rect(0., 0., self.size.0, self.size.1)
+            .fill_nonzero();
+
+        // Everything after this is in the same pixels as the screen, only upside down
+        content.save_state();
+        content.transform([
+            1. / PX_PER_PT,
+            0.,
+            0.,
+            -1. / PX_PER_PT,
+            0.,
+            self.size.1 - MARGIN,
+        ]);
+        let screen_size = renderer.screen_size();
+        content.rect(0., 0., screen_size.0, bottom - top);
+        content.clip_nonzero().end_path();
+
+        self.draw_shapes(&mut content, renderer);
+
+        self.set_alpha(&mut content, u8::MAX);
+        let mut images = Vec::new();
+        renderer::collect_positioned_images(elements, &mut images);
+        for (bounds, image) in images {
+            if bounds.max().1 <= top || bounds.pos.1 >= bottom {
+                continue;
+            }
+            let Some(name) = self.image(image)? else {
+                continue;
+            };
+            let (x, y) = (bounds.pos.0, bounds.pos.1 - top);
+            let (width, height) = bounds.size;
+            content.save_state();
+            content.transform([width, 0., 0., -height, x, y + height]);
+            content.x_object(Name(name.as_bytes()));
+            content.restore_state();
+        }
+
+        self.draw_text(&mut content, renderer, &text_areas);
+        content.restore_state();
+
+        Ok(content.finish())
+    }
+
+    /// Fills in the triangles that the renderer tessellated, with each run of them that's the
+    /// same color making up one path
+    fn draw_shapes(&mut self, content: &mut Content, renderer: &Renderer) {
+        let (width, height) = renderer.screen_size();
+        let to_px = |[x, y, _]: [f32; 3]| ((x + 1.) / 2. * width, (1. - y) / 2. * height);
+        let vertices = &renderer.lyon_buffer.vertices;
+        let mut current_color = None;
+        for triangle in renderer.lyon_buffer.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| &vertices[usize::from(triangle[i])]);
+            if current_color != Some(a.color) {
+                if current_color.is_some() {
+                    content.fill_nonzero();
+                }
+                let [r, g, b, alpha] = from_native_color(a.color, &renderer.surface_format);
+                content.set_fill_rgb(r, g, b);
+                self.set_alpha(content, (alpha * 255.).round() as u8);
+                current_color = Some(a.color);
+            }
+            let [a, b, c] = [a, b, c].map(|vertex| to_px(vertex.pos));
+            content.move_to(a.0, a.1);
+            content.line_to(b.0, b.1);
+            content.line_to(c.0, c.1);
+            content.close_path();
+        }
+        if current_color.is_some() {
+            content.fill_nonzero();
+        }
+    }
+
+    fn draw_text(
+        &mut self,
+        content: &mut Content,
+        renderer: &Renderer,
+        text_areas: &[CachedTextArea],
+    ) {
+        let cache = renderer.text_system.text_cache.lock().unwrap();
+        let mut font_system = renderer.text_system.font_system.lock().unwrap();
+        let mut current_font = None;
+        let mut current_color = None;
+        content.begin_text();
+        for area in text_areas.iter().flat_map(|area| area.text_areas(&cache)) {
+            let (left, right) = (area.bounds.left as f32, area.bounds.right as f32);
+            for run in area.buffer.layout_runs() {
+                for glyph in run.glyphs {
+                    let x = area.left + glyph.x;
+                    // Scrolled out of view in a code block that doesn't wrap
+                    if x < left || x + glyph.w > right {
+                        continue;
+                    }
+                    // Holds the place of a superscript or subscript that gets drawn on its own
+                    if glyph.color_opt.is_some_and(|color| color.a() == 0) {
+                        continue;
+                    }
+                    let y = area.top + run.line_y + glyph.y;
+                    let (key, _, _) = CacheKey::new(
+                        glyph.font_id,
+                        glyph.glyph_id,
+                        glyph.font_size,
+                        (glyph.x, glyph.y),
+                    );
+                    let Some(font) = self.font(&mut font_system, key.font_id) else {
+                        continue;
+                    };
+                    let used = &mut self.fonts[font];
+                    let _ = used
+                        .glyphs
+                        .entry(key.glyph_id)
+                        .or_insert_with(|| run.text[glyph.start..glyph.end].to_owned());
+
+                    let size = f32::from_bits(key.font_size_bits);
+                    if current_font != Some((font, key.font_size_bits)) {
+                        content.set_font(Name(used.name.as_bytes()), size);
+                        current_font = Some((font, key.font_size_bits));
+                    }
+                    let color = glyph.color_opt.unwrap_or(area.default_color);
+                    if current_color != Some(color) {
+                        let [r, g, b] = glyph_rgb(color, renderer);
+                        content.set_fill_rgb(r, g, b);
+                        current_color = Some(color);
+                    }
+                    content.set_text_matrix([1., 0., 0., -1., x, y]);
+                    content.show(Str(&key.glyph_id.to_be_bytes()));
+                }
+            }
+        }
+        content.end_text();
+    }
+
+    /// Sets how opaque whatever gets filled in next is
+    fn set_alpha(&mut self, content: &mut Content, alpha: u8) {
+        if !self.alphas.contains_key(&alpha) {
+            let gs_ref = self.alloc();
+            self.writer
+                .ext_graphics(gs_ref)
+                .non_stroking_alpha(f32::from(alpha) / 255.);
+            let _ = self.alphas.insert(alpha, gs_ref);
+        }
+        content.set_parameters(Name(format!("Gs{alpha}").as_bytes()));
+    }
+
+    /// The index of the font in [`PdfDocument::fonts`], adding it the first time that it's used
+    fn font(&mut self, font_system: &mut FontSystem, id: ID) -> Option<usize> {
+        if let Some(&index) = self.font_indices.get(&id) {
+            return Some(index);
+        }
+        let font = font_system.get_font(id)?;
+        let face = font_system.db().face(id)?;
+        let (index, post_script_name) = (face.index, face.post_script_name.clone());
+        let (italic, monospaced) = (face.style != glyphon::Style::Normal, face.monospaced);
+        let type0_ref = self.alloc();
+        self.fonts.push(UsedFont {
+            name: format!("F{}", self.fonts.len()),
+            type0_ref,
+            font,
+            index,
+            post_script_name,
+            italic,
+            monospaced,
+            glyphs: BTreeMap::new(),
+        });
+        let _ = self.font_indices.insert(id, self.fonts.len() - 1);
+        Some(self.fonts.len() - 1)
+    }
+
+    /// The name of the image's embedded copy, embedding it the first time that it's drawn. Images
+    /// that haven't loaded get left out
+    fn image(&mut self, image: &Image) -> anyhow::Result<Option<String>> {
+        let key = Arc::as_ptr(&image.image_data) as usize;
+        if let Some((name, _)) = self.images.get(&key) {
+            return Ok(Some(name.clone()));
+        }
+        let pixels = {
+            let image_data = image.image_data.lock().unwrap();
+            let Some(image_data) = image_data.as_ref() else {
+                return Ok(None);
+            };
+            image
+                .transform
+                .unwrap_or_default()
+                .apply(image_data.to_image()?)
+        };
+
+        let (width, height) = pixels.dimensions();
+        let mut rgb = Vec::with_capacity(pixels.len() / 4 * 3);
+        let mut alpha = Vec::with_capacity(pixels.len() / 4);
+        for pixel in pixels.pixels() {
+            rgb.extend_from_slice(&pixel.0[..3]);
+            alpha.push(pixel.0[3]);
+        }
+        let mask_ref = alpha.iter().any(|&a| a != u8::MAX).then(|| self.alloc());
+
+        let image_ref = self.alloc();
+        let rgb = deflate(&rgb);
+        let mut xobject = self.writer.image_xobject(image_ref, &rgb);
+        xobject.filter(Filter::FlateDecode);
+        xobject.width(width as i32).height(height as i32);
+        xobject.color_space().device_rgb();
+        xobject.bits_per_component(8);
+        if let Some(mask_ref) = mask_ref {
+            xobject.s_mask(mask_ref);
+        }
+        xobject.finish();
+        if let Some(mask_ref) = mask_ref {
+            let alpha = deflate(&alpha);
+            let mut mask = self.writer.image_xobject(mask_ref, &alpha);
+            mask.filter(Filter::FlateDecode);
+            mask.width(width as i32).height(height as i32);
+            mask.color_space().device_gray();
+            mask.bits_per_component(8);
+        }
+
+        let name = format!("Im{}", self.images.len());
+        let _ = self.images.insert(key, (name.clone(), image_ref));
+        Ok(Some(name))
+    }
+
+    /// Embeds the fonts now that every glyph is known and writes out the pages around everything
+    fn finish(
+        mut self,
+        pages: Vec<Vec<u8>>,
+        annotations: Vec<Vec<(PdfRect, Target)>>,
+        title: Option<&str>,
+    ) -> anyhow::Result<Vec<u8>> {
+        for i in 0..self.fonts.len() {
+            self.write_font(i)?;
+        }
+
+        let catalog_ref = self.alloc();
+        let tree_ref = self.alloc();
+        let page_refs: Vec<_> = pages.iter().map(|_| self.alloc()).collect();
+        self.writer.catalog(catalog_ref).pages(tree_ref);
+        self.writer
+            .pages(tree_ref)
+            .kids(page_refs.iter().copied())
+            .count(page_refs.len() as i32);
+
+        for ((content, page_ref), annotations) in pages.iter().zip(&page_refs).zip(annotations) {
+            let content_ref = self.alloc();
+            self.writer
+                .stream(content_ref, &deflate(content))
+                .filter(Filter::FlateDecode);
+
+            let mut page_writer = self.writer.page(*page_ref);
+            page_writer
+                .media_box(PdfRect::new(0., 0., self.size.0, self.size.1))
+                .parent(tree_ref)
+                .contents(content_ref);
+
+            let mut resources = page_writer.resources();
+            let mut fonts = resources.fonts();
+            for font in &self.fonts {
+                fonts.pair(Name(font.name.as_bytes()), font.type0_ref);
+            }
+            fonts.finish();
+            let mut x_objects = resources.x_objects();
+            for (name, image_ref) in self.images.values() {
+                x_objects.pair(Name(name.as_bytes()), *image_ref);
+            }
+            x_objects.finish();
+            let mut ext_g_states = resources.ext_g_states();
+            for (alpha, gs_ref) in &self.alphas {
+                ext_g_states.pair(Name(format!("Gs{alpha}").as_bytes()), *gs_ref);
+            }
+            ext_g_states.finish();
+            resources.finish();
+
+            let mut page_annotations = page_writer.annotations();
+            for (rect, target) in annotations {
+                let mut annotation = page_annotations.push();
+                annotation.subtype(AnnotationType::Link).rect(rect);
+                annotation.border(0., 0., 0., None);
+                match target {
+                    Target::Uri(uri) => {
+                        annotation
+                            .action()
+                            .action_type(ActionType::Uri)
+                            .uri(Str(uri.as_bytes()));
+                    }
+                    Target::Position(page, y) => {
+                        annotation
+                            .action()
+                            .action_type(ActionType::GoTo)
+                            .destination()
+                            .page(page_refs[page])
+                            .xyz(0., y, None);
+                    }
+                }
+            }
+            page_annotations.finish();
+        }
+
+        let info_ref = self.alloc();
+        let mut info = self.writer.document_info(info_ref);
+        info.creator(TextStr("Inlyne"));
+        if let Some(title) = title {
+            info.title(TextStr(title));
+        }
+        info.finish();
+
+        Ok(self.writer.finish())
+    }
+
+    /// Embeds a subset of the font with only the glyphs that got shown
+    fn write_font(&mut self, index: usize) -> anyhow::Result<()> {
+        let cid_ref = self.alloc();
+        let descriptor_ref = self.alloc();
+        let cmap_ref = self.alloc();
+        let file_ref = self.alloc();
+        let font = &self.fonts[index];
+
+        let glyphs: Vec<u16> = font.glyphs.keys().copied().collect();
+        let subset = subsetter::subset(
+            font.font.data(),
+            font.index,
+            subsetter::Profile::pdf(&glyphs),
+        )
+        .map_err(|err| anyhow::anyhow!("{err:?}"))
+        .with_context(|| format!("Failed to subset the font '{}'", font.post_script_name))?;
+        // PostScript outlines instead of TrueType ones
+        let is_cff = subset.starts_with(b"OTTO");
+        let base_font = format!("{}+{}", subset_tag(index), font.post_script_name);
+
+        let swash = font.font.as_swash();
+        let metrics = swash.metrics(&[]);
+        let glyph_metrics = swash.glyph_metrics(&[]);
+        let to_pdf = |units: f32| units * 1000. / f32::from(metrics.units_per_em);
+
+        self.writer
+            .type0_font(font.type0_ref)
+            .base_font(Name(base_font.as_bytes()))
+            .encoding_predefined(Name(b"Identity-H"))
+            .descendant_font(cid_ref)
+            .to_unicode(cmap_ref);
+
+        let mut cid_font = self.writer.cid_font(cid_ref);
+        cid_font.subtype(if is_cff {
+            CidFontType::Type0
+        } else {
+            CidFontType::Type2
+        });
+        cid_font.base_font(Name(base_font.as_bytes()));
+        cid_font.system_info(SYSTEM_INFO);
+        cid_font.font_descriptor(descriptor_ref);
+        cid_font.default_width(0.);
+        if !is_cff {
+            cid_font.cid_to_gid_map_predefined(Name(b"Identity"));
+        }
+        let mut widths = cid_font.widths();
+        for &glyph in &glyphs {
+            widths.consecutive(glyph, [to_pdf(glyph_metrics.advance_width(glyph))]);
+        }
+        widths.finish();
+        cid_font.finish();
+
+        let mut flags = FontFlags::SYMBOLIC;
+        flags.set(FontFlags::ITALIC, font.italic);
+        flags.set(FontFlags::FIXED_PITCH, font.monospaced);
+        let (ascent, descent) = (to_pdf(metrics.ascent), to_pdf(-metrics.descent));
+        let mut descriptor = self.writer.font_descriptor(descriptor_ref);
+        descriptor
+            .name(Name(base_font.as_bytes()))
+            .flags(flags)
+            .bbox(PdfRect::new(0., descent, 1000., ascent))
+            // Viewers only use the slant as a hint for substitute fonts, so a typical one will do
+            .italic_angle(if font.italic { -12. } else { 0. })
+            .ascent(ascent)
+            .descent(descent)
+            .cap_height(to_pdf(metrics.cap_height))
+            .stem_v(80.);
+        if is_cff {
+            descriptor.font_file3(file_ref);
+        } else {
+            descriptor.font_file2(file_ref);
+        }
+        descriptor.finish();
+
+        let mut cmap = UnicodeCmap::new(Name(b"Custom"), SYSTEM_INFO);
+        for (&glyph, text) in &font.glyphs {
+            if !text.is_empty() {
+                cmap.pair_with_multiple(glyph, text.chars());
+            }
+        }
+        self.writer
+            .cmap(cmap_ref, &deflate(&cmap.finish()))
+            .filter(Filter::FlateDecode);
+
+        let subset = deflate(&subset);
+        let mut stream = self.writer.stream(file_ref, &subset);
+        stream.filter(Filter::FlateDecode);
+        if is_cff {
+            stream.pair(Name(b"Subtype"), Name(b"OpenType"));
+        }
+        stream.finish();
+        Ok(())
+    }
+}
+
+/// Glyph colors get stored as the renderer's native color, so they're turned back into sRGB
+fn glyph_rgb(color: Color, renderer: &Renderer) -> [f32; 3] {
+    let native = [color.r(), color.g(), color.b(), color.a()].map(|c| f32::from(c) / 255.);
+    let [r, g, b, _] = from_native_color(native, &renderer.surface_format);
+    [r, g, b]
+}
+
+/// Subsets of fonts get a made up six letter tag in front of their name
+fn subset_tag(index: usize) -> String {
+    let mut num = index;
+    (0..6)
+        .map(|_| {
+            let letter = char::from(b'A' + (num % 26) as u8);
+            num /= 26;
+            letter
+        })
+        .collect()
+}
+
+pub(super) fn deflate(data: &[u8]) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec_zlib(data, 6)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::*;
+    use crate::opts::{Cli, Config};
+
+    fn text(top: f32, bottom: f32, line_height: f32) -> Block {
+        Block {
+            top,
+            bottom,
+            line_height: Some(line_height),
+            keep_whole: false,
+        }
+    }
+
+    fn image(top: f32, bottom: f32) -> Block {
+        Block {
+            top,
+            bottom,
+            line_height: None,
+            keep_whole: true,
+        }
+    }
+
+    #[test]
+    fn breaking_pages() {
+        // Text breaks between its lines
+        assert_eq!(page_breaks(&[text(0., 250., 30.)], 100.), [0., 90., 180.]);
+        // Images get moved onto the next page whole
+        assert_eq!(
+            page_breaks(&[text(0., 80., 20.), image(90., 150.)], 100.),
+            [0., 90.]
+        );
+        // Unless they wouldn't fit on it anyways
+        assert_eq!(page_breaks(&[image(10., 250.)], 100.), [0., 100., 200.]);
+        // Pages skip over the space before whatever's next
+        assert_eq!(
+            page_breaks(&[text(0., 50., 10.), image(250., 300.)], 100.),
+            [0., 250.]
+        );
+        assert_eq!(page_breaks(&[], 100.), [0.]);
+    }
+
+    #[test]
+    fn writes_a_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let md_path = dir.path().join("doc.md");
+        let pdf_path = dir.path().join("doc.pdf");
+        let md =
+            "# Title\n\nSome text with [a link](https://example.com) and [one back up](#title)\n";
+        fs::write(&md_path, md).unwrap();
+        let args = ["inlyne", "export", "--pdf"]
+            .into_iter()
+            .map(OsString::from)
+            .chain([pdf_path.clone().into(), md_path.into()])
+            .collect();
+        let Cli::Export(args) = Cli::try_parse_from(args).unwrap() else {
+            unreachable!("Parsed the export subcommand");
+        };
+        let opts = ExportOpts::parse_and_load_from(args, Config::default()).unwrap();
+
+        write(&opts, &pdf_path).unwrap();
+        let pdf = fs::read(&pdf_path).unwrap();
+        let contains = |needle: &[u8]| pdf.windows(needle.len()).any(|window| window == needle);
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(contains(b"%%EOF"));
+        assert!(contains(b"/Title (Title)"));
+        assert!(contains(b"/URI (https://example.com)"));
+        assert!(contains(b"/S /GoTo"));
+        assert!(contains(b"/FontFile"));
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(subset_tag(0), "AAAAAA");
+        assert_eq!(subset_tag(27), "BBAAAA");
+    }
+}
@@ -0,0 +1,123 @@
+//! Rendering a whole document into one tall image
+//!
+//! Everything runs headlessly like `inlyne bench`. Documents that are taller than a texture can
+//! get drawn a tile at a time, with each tile copied into place in the final image
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::interpreter::HtmlInterpreter;
+use crate::opts::ExportOpts;
+use crate::positioner::Positioned;
+use crate::renderer::{self, Renderer};
+use crate::utils::markdown_to_html;
+use crate::{Element, Inlyne};
+
+use anyhow::Context;
+
+/// How tall each tile gets, which stays under the texture size limit of every GPU
+const TILE_HEIGHT: u32 = 4096;
+/// How long to wait on images before going ahead without the ones that haven't loaded
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn write(opts: &ExportOpts, out: &Path) -> anyhow::Result<()> {
+    let md = fs::read_to_string(&opts.file_path)
+        .with_context(|| format!("Could not read file at '{}'", opts.file_path.display()))?;
+    let (mut renderer, mut elements) = render_document(opts, &md, opts.width)?;
+    let page = capture(&mut renderer, &mut elements)?;
+    page.save(out)
+        .with_context(|| format!("Failed to write the image to '{}'", out.display()))?;
+    tracing::info!(
+        "Wrote {}x{} image to '{}'",
+        page.width(),
+        page.height(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Interprets and lays out the document at `width`, waiting on its images so that they take up the
+/// room that they need
+pub(super) fn render_document(
+    opts: &ExportOpts,
+    md: &str,
+    width: u32,
+) -> anyhow::Result<(Renderer, Vec<Positioned<Element>>)> {
+    let mut renderer = pollster::block_on(Renderer::new_headless(
+        (width, TILE_HEIGHT),
+        opts.theme.clone(),
+        opts.scale.unwrap_or(1.0),
+        opts.page_width.unwrap_or(f32::MAX),
+        opts.font_opts.clone(),
+    ))?;
+    renderer.scrollbar = false;
+    renderer.animate_images = false;
+
+    let htmlified = markdown_to_html(md, opts.theme.code_highlighter.clone(), opts.markdown);
+    let element_queue = Arc::new(Mutex::new(VecDeque::new()));
+    let mut interpreter = HtmlInterpreter::new_headless(
+        Arc::clone(&element_queue),
+        opts.theme.clone(),
+        renderer.surface_format,
+        renderer.hidpi_scale,
+        opts.file_path.clone(),
+        Arc::new(Mutex::new(HashMap::new())),
+        opts.color_scheme,
+    );
+    interpreter.apply_font_options(&opts.font_opts);
+    interpreter.markdown = opts.markdown;
+    interpreter.image_captions = opts.image_captions;
+    interpreter.color_swatches = opts.color_swatches;
+    interpreter.tables = opts.tables;
+    interpreter.interpret_html(&htmlified);
+
+    let mut elements = Vec::new();
+    Inlyne::position_queued_elements(&element_queue, &mut renderer, &mut elements);
+    wait_for_images(&mut elements);
+    renderer.reposition(&mut elements)?;
+    Ok((renderer, elements))
+}
+
+fn wait_for_images(elements: &mut [Positioned<Element>]) {
+    let start = Instant::now();
+    loop {
+        let mut images = Vec::new();
+        renderer::collect_images(elements, &mut images);
+        let pending = images
+            .iter()
+            .filter(|image| !image.is_loaded() && !image.load_failed())
+            .count();
+        if pending == 0 {
+            return;
+        }
+        if start.elapsed() > IMAGE_TIMEOUT {
+            tracing::warn!("Gave up waiting on {pending} images to load");
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Draws the document a tile at a time from top to bottom and stitches the tiles together
+fn capture(
+    renderer: &mut Renderer,
+    elements: &mut [Positioned<Element>],
+) -> anyhow::Result<image::RgbaImage> {
+    let width = renderer.config.width;
+    let height = renderer.positioner.reserved_height.ceil().max(1.) as u32;
+    let mut page = image::RgbaImage::new(width, height);
+    for top in (0..height).step_by(TILE_HEIGHT as usize) {
+        // The last tile is cut down to fit so that it can still be scrolled to
+        let tile_height = TILE_HEIGHT.min(height - top);
+        renderer.resize(width, tile_height);
+        renderer.set_scroll_y(top as f32);
+        renderer.redraw(elements)?;
+        let tile = renderer.read_frame()?;
+        image::imageops::replace(&mut page, &tile, 0, i64::from(top));
+    }
+    Ok(page)
+}
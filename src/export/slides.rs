@@ -0,0 +1,185 @@
+//! Rendering each slide of a slideshow on its own, for presenting it from other tools
+//!
+//! The document gets laid out at the width of the slides and split up on its horizontal rules the
+//! same way as in the window. Each slide is then drawn centered like it would be on screen, with
+//! the ones that are too tall getting cut off at the bottom
+
+use std::fs;
+use std::path::Path;
+
+use super::pdf::{self, PX_PER_PT};
+use super::png;
+use crate::opts::ExportOpts;
+use crate::slideshow::{self, Slideshow};
+use crate::title;
+
+use anyhow::Context;
+use image::RgbaImage;
+use pdf_writer::{Content, Filter, Finish, Name, PdfWriter, Rect, Ref, TextStr};
+
+pub fn write(opts: &ExportOpts, out: &Path) -> anyhow::Result<()> {
+    let md = fs::read_to_string(&opts.file_path)
+        .with_context(|| format!("Could not read file at '{}'", opts.file_path.display()))?;
+    let frames = render_slides(opts, &md)?;
+    let is_pdf = out
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("pdf"));
+    if is_pdf {
+        let title = title::first_heading(&md);
+        fs::write(out, to_pdf(&frames, title.as_deref()))
+            .with_context(|| format!("Failed to write the PDF to '{}'", out.display()))?;
+    } else {
+        fs::create_dir_all(out)
+            .with_context(|| format!("Failed to create the directory '{}'", out.display()))?;
+        for (i, frame) in frames.iter().enumerate() {
+            let path = out.join(file_name(i, frames.len()));
+            frame
+                .save(&path)
+                .with_context(|| format!("Failed to write the image to '{}'", path.display()))?;
+        }
+    }
+    tracing::info!("Wrote {} slides to '{}'", frames.len(), out.display());
+    Ok(())
+}
+
+fn render_slides(opts: &ExportOpts, md: &str) -> anyhow::Result<Vec<RgbaImage>> {
+    let (width, height) = opts.slide_size;
+    let (mut renderer, mut elements) = png::render_document(opts, md, width)?;
+    renderer.resize(width, height);
+    let mut slideshow = Slideshow::new(&elements, false);
+    anyhow::ensure!(!slideshow.slides.is_empty(), "The document has no slides");
+
+    let mut frames = Vec::with_capacity(slideshow.slides.len());
+    for current in 0..slideshow.slides.len() {
+        slideshow.current = current;
+        let top = slideshow.slide().map_or(0., |slide| slide.top);
+        renderer.scroll_y = slideshow.scroll_y(top, renderer.screen_height());
+        let shown = slideshow::shown(Some(&slideshow), elements.len());
+        renderer.redraw(&mut elements[shown])?;
+        frames.push(renderer.read_frame()?);
+    }
+    Ok(frames)
+}
+
+/// Numbered from one, with enough leading zeros that they sort in order
+fn file_name(index: usize, count: usize) -> String {
+    let digits = count.to_string().len().max(2);
+    format!("slide-{:0digits$}.png", index + 1)
+}
+
+/// One page per slide, each of them the size of the slide
+fn to_pdf(frames: &[RgbaImage], title: Option<&str>) -> Vec<u8> {
+    let mut writer = PdfWriter::new();
+    let mut next_ref = 0;
+    let mut alloc = || {
+        next_ref += 1;
+        Ref::new(next_ref)
+    };
+    let catalog_ref = alloc();
+    let tree_ref = alloc();
+    let page_refs: Vec<_> = frames.iter().map(|_| alloc()).collect();
+    writer.catalog(catalog_ref).pages(tree_ref);
+    writer
+        .pages(tree_ref)
+        .kids(page_refs.iter().copied())
+        .count(page_refs.len() as i32);
+
+    for (frame, page_ref) in frames.iter().zip(&page_refs) {
+        let (image_ref, content_ref) = (alloc(), alloc());
+        let (width, height) = frame.dimensions();
+        let rgb: Vec<u8> = frame
+            .pixels()
+            .flat_map(|pixel| &pixel.0[..3])
+            .copied()
+            .collect();
+        let rgb = pdf::deflate(&rgb);
+        let mut xobject = writer.image_xobject(image_ref, &rgb);
+        xobject.filter(Filter::FlateDecode);
+        xobject.width(width as i32).height(height as i32);
+        xobject.color_space().device_rgb();
+        xobject.bits_per_component(8);
+        xobject.finish();
+
+        let size = (width as f32 / PX_PER_PT, height as f32 / PX_PER_PT);
+        let mut content = Content::new();
+        content.save_state();
+        content.transform([size.0, 0., 0., size.1, 0., 0.]);
+        content.x_object(Name(b"Im0"));
+        content.restore_state();
+        writer
+            .stream(content_ref, &pdf::deflate(&content.finish()))
+            .filter(Filter::FlateDecode);
+
+        let mut page = writer.page(*page_ref);
+        page.media_box(Rect::new(0., 0., size.0, size.1))
+            .parent(tree_ref)
+            .contents(content_ref);
+        page.resources().x_objects().pair(Name(b"Im0"), image_ref);
+    }
+
+    let mut info = writer.document_info(alloc());
+    info.creator(TextStr("Inlyne"));
+    if let Some(title) = title {
+        info.title(TextStr(title));
+    }
+    info.finish();
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::*;
+    use crate::opts::{Cli, Config};
+
+    fn export(out: &Path) {
+        let dir = out.parent().unwrap();
+        let md_path = dir.join("deck.md");
+        fs::write(
+            &md_path,
+            "# Deck\n\nFirst\n\n---\n\nSecond\n\n---\n\nThird\n",
+        )
+        .unwrap();
+        let args = ["inlyne", "export", "--slide-size", "320x180", "--slides"]
+            .into_iter()
+            .map(OsString::from)
+            .chain([out.into(), md_path.into()])
+            .collect();
+        let Cli::Export(args) = Cli::try_parse_from(args).unwrap() else {
+            unreachable!("Parsed the export subcommand");
+        };
+        let opts = ExportOpts::parse_and_load_from(args, Config::default()).unwrap();
+        write(&opts, out).unwrap();
+    }
+
+    #[test]
+    fn numbered_pngs() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("slides");
+        export(&out);
+        let mut names: Vec<_> = fs::read_dir(&out)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["slide-01.png", "slide-02.png", "slide-03.png"]);
+        let slide = image::open(out.join("slide-02.png")).unwrap();
+        assert_eq!((slide.width(), slide.height()), (320, 180));
+
+        assert_eq!(file_name(9, 120), "slide-010.png");
+    }
+
+    #[test]
+    fn one_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("deck.pdf");
+        export(&out);
+        let pdf = fs::read(&out).unwrap();
+        let count = |needle: &[u8]| pdf.windows(needle.len()).filter(|w| *w == needle).count();
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert_eq!(count(b"/Type /Page\n"), 3);
+        assert_eq!(count(b"/Title (Deck)"), 1);
+    }
+}
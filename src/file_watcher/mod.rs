@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests;
 
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::time::Duration;
@@ -17,6 +18,8 @@ use winit::event_loop::EventLoopProxy;
 trait Callback: Send + 'static {
     fn file_reload(&self);
     fn file_change(&self, contents: String);
+    fn image_change(&self, path: PathBuf);
+    fn config_change(&self);
 }
 
 impl Callback for EventLoopProxy<InlyneEvent> {
@@ -27,6 +30,14 @@ impl Callback for EventLoopProxy<InlyneEvent> {
     fn file_change(&self, contents: String) {
         let _ = self.send_event(InlyneEvent::FileChange { contents });
     }
+
+    fn image_change(&self, path: PathBuf) {
+        let _ = self.send_event(InlyneEvent::ImageChange(path));
+    }
+
+    fn config_change(&self) {
+        let _ = self.send_event(InlyneEvent::ConfigChange);
+    }
 }
 
 struct FileChange {
@@ -42,8 +53,18 @@ enum DebouncerAction {
 enum WatcherMsg {
     // Sent by the file watcher debouncer
     Action(DebouncerAction),
+    // Sent by the image debouncer
+    ImageChange {
+        paths: Vec<PathBuf>,
+        reregister: bool,
+    },
+    // Sent by the config debouncer
+    ConfigChange(Vec<PathBuf>),
     // Sent by the event loop
     FileChange(FileChange),
+    Unwatch,
+    WatchImages(BTreeSet<PathBuf>),
+    WatchConfig(PathBuf),
 }
 
 impl WatcherMsg {
@@ -92,22 +113,100 @@ impl DebounceEventHandler for MsgHandler {
     }
 }
 
+/// Passes along changes to the images that the document shows
+struct ImageMsgHandler(mpsc::Sender<WatcherMsg>);
+
+impl DebounceEventHandler for ImageMsgHandler {
+    fn handle_event(&mut self, debounced_event: DebounceEventResult) {
+        match debounced_event {
+            Ok(events) => {
+                let mut paths = BTreeSet::new();
+                let mut reregister = false;
+                for ev in events {
+                    match ev.event.kind {
+                        EventKind::Modify(ModifyKind::Name(_)) | EventKind::Remove(_) => {
+                            reregister = true;
+                        }
+                        EventKind::Create(_) | EventKind::Modify(_) => {}
+                        _ => continue,
+                    }
+                    paths.extend(ev.event.paths);
+                }
+
+                if !paths.is_empty() {
+                    let paths = paths.into_iter().collect();
+                    let _ = self.0.send(WatcherMsg::ImageChange { paths, reregister });
+                }
+            }
+            Err(errs) => {
+                for err in errs {
+                    tracing::warn!("Image watcher error: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Passes along anything that happens in the config file's directory. Editors often save by
+/// swapping in a new file, so the directory gets watched instead of the file itself
+struct ConfigMsgHandler(mpsc::Sender<WatcherMsg>);
+
+impl DebounceEventHandler for ConfigMsgHandler {
+    fn handle_event(&mut self, debounced_event: DebounceEventResult) {
+        match debounced_event {
+            Ok(events) => {
+                let paths: Vec<_> = events
+                    .into_iter()
+                    .filter(|ev| {
+                        matches!(ev.event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    })
+                    .flat_map(|ev| ev.event.paths)
+                    .collect();
+                if !paths.is_empty() {
+                    let _ = self.0.send(WatcherMsg::ConfigChange(paths));
+                }
+            }
+            Err(errs) => {
+                for err in errs {
+                    tracing::warn!("Config watcher error: {err}");
+                }
+            }
+        }
+    }
+}
+
 pub struct Watcher(mpsc::Sender<WatcherMsg>);
 
 impl Watcher {
-    pub fn spawn(event_proxy: EventLoopProxy<InlyneEvent>, file_path: PathBuf) -> Self {
+    /// Watches `file_path` or starts off watching nothing when there's no file on disk
+    pub fn spawn(event_proxy: EventLoopProxy<InlyneEvent>, file_path: Option<PathBuf>) -> Self {
         Self::spawn_inner(event_proxy, file_path)
     }
 
-    fn spawn_inner<C: Callback>(reload_callback: C, file_path: PathBuf) -> Self {
+    fn spawn_inner<C: Callback>(reload_callback: C, file_path: Option<PathBuf>) -> Self {
         let (msg_tx, msg_rx) = mpsc::channel();
         let watcher = Self(msg_tx.clone());
 
         let notify_watcher =
-            new_debouncer(Duration::from_millis(10), None, MsgHandler(msg_tx)).unwrap();
+            new_debouncer(Duration::from_millis(10), None, MsgHandler(msg_tx.clone())).unwrap();
+        let image_watcher = new_debouncer(
+            Duration::from_millis(10),
+            None,
+            ImageMsgHandler(msg_tx.clone()),
+        )
+        .unwrap();
+        let config_watcher =
+            new_debouncer(Duration::from_millis(10), None, ConfigMsgHandler(msg_tx)).unwrap();
 
         std::thread::spawn(move || {
-            endlessly_handle_messages(notify_watcher, msg_rx, reload_callback, file_path);
+            endlessly_handle_messages(
+                notify_watcher,
+                image_watcher,
+                config_watcher,
+                msg_rx,
+                reload_callback,
+                file_path,
+            );
         });
 
         watcher
@@ -117,18 +216,45 @@ impl Watcher {
         let msg = WatcherMsg::file_change(new_path.to_owned(), contents);
         let _ = self.0.send(msg);
     }
+
+    /// Stops watching the current file. Used when the document no longer comes from a file
+    pub fn unwatch(&self) {
+        let _ = self.0.send(WatcherMsg::Unwatch);
+    }
+
+    /// Watches just these images on disk, replacing whatever images were watched before
+    pub fn watch_images(&self, paths: BTreeSet<PathBuf>) {
+        let _ = self.0.send(WatcherMsg::WatchImages(paths));
+    }
+
+    /// Reports changes to the config file at `path`, including it getting created later on
+    pub fn watch_config(&self, path: PathBuf) {
+        let _ = self.0.send(WatcherMsg::WatchConfig(path));
+    }
 }
 
+/// How many times to try watching an image again after it gets renamed or removed. Unlike the
+/// document, it may be gone for good
+const IMAGE_REREGISTER_ATTEMPTS: u32 = 20;
+
 fn endlessly_handle_messages<C: Callback>(
     mut watcher: Debouncer<RecommendedWatcher, FileIdMap>,
+    mut image_watcher: Debouncer<RecommendedWatcher, FileIdMap>,
+    mut config_watcher: Debouncer<RecommendedWatcher, FileIdMap>,
     msg_rx: mpsc::Receiver<WatcherMsg>,
     reload_callback: C,
-    mut file_path: PathBuf,
+    mut file_path: Option<PathBuf>,
 ) {
     let watcher = watcher.watcher();
-    watcher
-        .watch(&file_path, RecursiveMode::NonRecursive)
-        .unwrap();
+    let image_watcher = image_watcher.watcher();
+    let config_watcher = config_watcher.watcher();
+    let mut image_paths = BTreeSet::new();
+    let mut config_name = None;
+    if let Some(file_path) = &file_path {
+        watcher
+            .watch(file_path, RecursiveMode::NonRecursive)
+            .unwrap();
+    }
 
     let poll_registering_watcher = |watcher: &mut RecommendedWatcher, file_path: &Path| loop {
         std::thread::sleep(Duration::from_millis(15));
@@ -145,8 +271,11 @@ fn endlessly_handle_messages<C: Callback>(
     while let Ok(msg) = msg_rx.recv() {
         match msg {
             WatcherMsg::Action(DebouncerAction::ReregisterWatcher) => {
+                let Some(file_path) = &file_path else {
+                    continue;
+                };
                 tracing::debug!("File may have been renamed/removed. Falling back to polling");
-                poll_registering_watcher(watcher, &file_path);
+                poll_registering_watcher(watcher, file_path);
                 tracing::debug!("Successfully re-registered file watcher");
                 reload_callback.file_reload();
             }
@@ -156,11 +285,71 @@ fn endlessly_handle_messages<C: Callback>(
             }
             WatcherMsg::FileChange(FileChange { new_path, contents }) => {
                 tracing::info!("Updating file watcher path: {}", new_path.display());
-                let _ = watcher.unwatch(&file_path);
+                if let Some(file_path) = &file_path {
+                    let _ = watcher.unwatch(file_path);
+                }
                 poll_registering_watcher(watcher, &new_path);
-                file_path = new_path;
+                file_path = Some(new_path);
                 reload_callback.file_change(contents);
             }
+            WatcherMsg::Unwatch => {
+                if let Some(file_path) = file_path.take() {
+                    tracing::info!("No longer watching: {}", file_path.display());
+                    let _ = watcher.unwatch(&file_path);
+                }
+            }
+            WatcherMsg::ImageChange { paths, reregister } => {
+                for path in paths {
+                    if !image_paths.contains(&path) {
+                        continue;
+                    }
+                    if reregister {
+                        let _ = image_watcher.unwatch(&path);
+                        let rewatched = (0..IMAGE_REREGISTER_ATTEMPTS).any(|_| {
+                            std::thread::sleep(Duration::from_millis(15));
+                            image_watcher
+                                .watch(&path, RecursiveMode::NonRecursive)
+                                .is_ok()
+                        });
+                        if !rewatched {
+                            tracing::debug!("Image went away: {}", path.display());
+                            continue;
+                        }
+                    }
+                    tracing::debug!("Reloading image: {}", path.display());
+                    reload_callback.image_change(path);
+                }
+            }
+            WatcherMsg::WatchImages(paths) => {
+                for old in image_paths.difference(&paths) {
+                    let _ = image_watcher.unwatch(old);
+                }
+                for new in paths.difference(&image_paths) {
+                    if let Err(err) = image_watcher.watch(new, RecursiveMode::NonRecursive) {
+                        tracing::debug!("Can't watch image {}: {err}", new.display());
+                    }
+                }
+                image_paths = paths;
+            }
+            WatcherMsg::ConfigChange(paths) => {
+                if paths
+                    .iter()
+                    .any(|path| path.file_name() == config_name.as_deref())
+                {
+                    tracing::debug!("Reloading config");
+                    reload_callback.config_change();
+                }
+            }
+            WatcherMsg::WatchConfig(path) => {
+                let dir = match path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                match config_watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => config_name = path.file_name().map(ToOwned::to_owned),
+                    Err(err) => tracing::debug!("Can't watch config {}: {err}", path.display()),
+                }
+            }
         }
     }
 
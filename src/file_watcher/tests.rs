@@ -15,6 +15,14 @@ impl Callback for mpsc::Sender<()> {
     fn file_change(&self, _: String) {
         self.send(()).unwrap();
     }
+
+    fn image_change(&self, _: PathBuf) {
+        self.send(()).unwrap();
+    }
+
+    fn config_change(&self) {
+        self.send(()).unwrap();
+    }
 }
 
 fn touch(file: &Path) {
@@ -74,7 +82,7 @@ fn init_test_env() -> (TestEnv, TempDir) {
 
     // Setup our watcher
     let (callback_tx, callback_rx) = mpsc::channel();
-    let watcher = Watcher::spawn_inner(callback_tx, main_file.clone());
+    let watcher = Watcher::spawn_inner(callback_tx, Some(main_file.clone()));
 
     let test_env = TestEnv {
         base_dir: temp_dir.path().to_owned(),
@@ -135,6 +143,9 @@ gen_watcher_test!(
     (sanity, sanity_fn),
     (update_moves_watcher, update_moves_watcher_fn),
     (slowly_swap_file, slowly_swap_file_fn),
+    (unwatch_then_update, unwatch_then_update_fn),
+    (watch_images, watch_images_fn),
+    (watch_config, watch_config_fn),
 );
 
 fn sanity_fn(
@@ -169,6 +180,28 @@ fn update_moves_watcher_fn(
     delays.assert_at_least_one_message(&callback_rx);
 }
 
+fn unwatch_then_update_fn(
+    TestEnv {
+        main_file,
+        rel_file,
+        watcher,
+        callback_rx,
+        ..
+    }: TestEnv,
+    delays: Delays,
+) {
+    // Nothing gets reported while unwatched
+    watcher.unwatch();
+    delays.delay();
+    touch(&main_file);
+    delays.assert_no_message(&callback_rx);
+    // but opening a file picks things back up again
+    watcher.update_file(&rel_file, fs::read_to_string(&rel_file).unwrap());
+    delays.assert_at_least_one_message(&callback_rx);
+    touch(&rel_file);
+    delays.assert_at_least_one_message(&callback_rx);
+}
+
 fn slowly_swap_file_fn(
     TestEnv {
         base_dir,
@@ -195,3 +228,60 @@ fn slowly_swap_file_fn(
     touch(&main_file);
     delays.assert_at_least_one_message(&callback_rx);
 }
+
+fn watch_images_fn(
+    TestEnv {
+        base_dir,
+        watcher,
+        callback_rx,
+        ..
+    }: TestEnv,
+    delays: Delays,
+) {
+    let image = base_dir.join("diagram.png");
+    let other = base_dir.join("other.png");
+    fs::write(&image, "png").unwrap();
+    fs::write(&other, "png").unwrap();
+
+    watcher.watch_images([image.clone()].into());
+    delays.delay();
+    touch(&other);
+    delays.assert_no_message(&callback_rx);
+    touch(&image);
+    delays.assert_at_least_one_message(&callback_rx);
+
+    // Images that the document stops showing aren't followed anymore
+    watcher.watch_images(Default::default());
+    delays.delay();
+    touch(&image);
+    delays.assert_no_message(&callback_rx);
+}
+
+fn watch_config_fn(
+    TestEnv {
+        base_dir,
+        rel_file,
+        watcher,
+        callback_rx,
+        ..
+    }: TestEnv,
+    delays: Delays,
+) {
+    let config = base_dir.join("inlyne.toml");
+    fs::write(&config, "theme = \"Dark\"").unwrap();
+
+    watcher.watch_config(config.clone());
+    delays.delay();
+    // Only the config matters out of everything in its directory
+    touch(&rel_file);
+    delays.assert_no_message(&callback_rx);
+    touch(&config);
+    delays.assert_at_least_one_message(&callback_rx);
+
+    // Like an editor saving by swapping in a new file
+    let swap = base_dir.join("inlyne.toml.swp");
+    fs::write(&swap, "theme = \"Light\"").unwrap();
+    delays.assert_no_message(&callback_rx);
+    fs::rename(&swap, &config).unwrap();
+    delays.assert_at_least_one_message(&callback_rx);
+}
@@ -1,17 +1,249 @@
-use glyphon::FontSystem;
+use std::ops::Range;
 
 use crate::opts::FontOptions;
 
+use fxhash::FxHashMap;
+use glyphon::{Family, FontSystem};
+
 pub fn get_fonts(font_opts: &FontOptions) -> FontSystem {
     let mut font_system = FontSystem::new();
+    // Asking for a family that isn't installed leaves text without any font at all, so stick with
+    // the defaults instead
+    let installed = |font_system: &FontSystem, name: &str| {
+        let found = font_system.db().faces().any(|face| {
+            face.families
+                .iter()
+                .any(|(family, _)| family.eq_ignore_ascii_case(name))
+        });
+        if !found {
+            tracing::warn!("The `{name}` font family isn't installed. Using the default instead");
+        }
+        found
+    };
 
     if let Some(regular_name) = &font_opts.regular_font {
-        font_system.db_mut().set_sans_serif_family(regular_name)
+        if installed(&font_system, regular_name) {
+            font_system.db_mut().set_sans_serif_family(regular_name)
+        }
     }
 
     if let Some(monospace_name) = &font_opts.monospace_font {
-        font_system.db_mut().set_monospace_family(monospace_name)
+        if installed(&font_system, monospace_name) {
+            font_system.db_mut().set_monospace_family(monospace_name)
+        }
     }
 
     font_system
 }
+
+/// Installed fonts that emoji get drawn in, in order of preference
+const COLOR_EMOJI_FAMILIES: &[&str] = &[
+    "Noto Color Emoji",
+    "Apple Color Emoji",
+    "Segoe UI Emoji",
+    "Twemoji Mozilla",
+    "JoyPixels",
+];
+
+/// Fonts that get tried in order for characters that the requested font doesn't have, like CJK
+/// or Indic text in a Latin-only font. Anything they don't cover is left to the text shaper's
+/// own fallback
+///
+/// Emoji get drawn with a color emoji font instead whenever one is installed, even if the
+/// requested font has a plain outline for them
+#[derive(Debug, Default)]
+pub struct FontFallbacks {
+    pub families: Vec<String>,
+    /// Whether a family (by name) has a glyph for a character
+    coverage: FxHashMap<(String, char), bool>,
+    /// Which of the families is the color emoji font, once it's been looked for
+    emoji: Option<Option<usize>>,
+}
+
+impl FontFallbacks {
+    pub fn new(families: Vec<String>) -> Self {
+        Self {
+            families,
+            coverage: FxHashMap::default(),
+            emoji: None,
+        }
+    }
+
+    /// Splits `text` into runs that each get drawn in one font, where `None` is the requested
+    /// `family` and `Some(i)` is the `i`th fallback
+    pub fn runs(
+        &mut self,
+        font_system: &mut FontSystem,
+        family: Family<'_>,
+        text: &str,
+    ) -> Vec<(Range<usize>, Option<usize>)> {
+        if text.is_ascii() {
+            return vec![(0..text.len(), None)];
+        }
+        let emoji = self.color_emoji(font_system);
+        if self.families.is_empty() {
+            return vec![(0..text.len(), None)];
+        }
+
+        let requested = font_system.db().family_name(&family).to_owned();
+        // `split_runs()` goes over the characters in order, so this stays one ahead of it
+        let mut next_chars = text.chars().skip(1);
+        let mut in_emoji = false;
+        split_runs(text, |c| {
+            let next = next_chars.next();
+            if let Some(emoji) = emoji {
+                let emoji_family = self.families[emoji].clone();
+                in_emoji = (in_emoji && is_emoji_component(c))
+                    || (is_emoji(c, next) && self.covers(font_system, &emoji_family, c));
+                if in_emoji {
+                    return Some(emoji);
+                }
+            }
+            if c.is_ascii() || c.is_whitespace() || self.covers(font_system, &requested, c) {
+                return None;
+            }
+            (0..self.families.len()).find(|&i| {
+                let fallback = self.families[i].clone();
+                self.covers(font_system, &fallback, c)
+            })
+        })
+    }
+
+    /// The first installed font with color glyphs from [`COLOR_EMOJI_FAMILIES`], which gets
+    /// added on after the other fallbacks
+    fn color_emoji(&mut self, font_system: &mut FontSystem) -> Option<usize> {
+        if let Some(emoji) = self.emoji {
+            return emoji;
+        }
+
+        // Embedded bitmaps, Apple's bitmaps, and layered outlines respectively
+        let color_tables = [*b"CBDT", *b"sbix", *b"COLR"].map(u32::from_be_bytes);
+        let family = COLOR_EMOJI_FAMILIES.iter().find(|&&family| {
+            let faces: Vec<_> = font_system
+                .db()
+                .faces()
+                .filter(|face| face.families.iter().any(|(name, _)| name == family))
+                .map(|face| face.id)
+                .collect();
+            faces.into_iter().any(|id| {
+                font_system.get_font(id).map_or(false, |font| {
+                    let font = font.as_swash();
+                    color_tables.iter().any(|&tag| font.table(tag).is_some())
+                })
+            })
+        });
+        let emoji = family.map(|family| {
+            tracing::debug!("Drawing emoji with {family}");
+            self.families.push((*family).to_owned());
+            self.families.len() - 1
+        });
+        self.emoji = Some(emoji);
+        emoji
+    }
+
+    fn covers(&mut self, font_system: &mut FontSystem, family: &str, c: char) -> bool {
+        let key = (family.to_owned(), c);
+        if let Some(&covered) = self.coverage.get(&key) {
+            return covered;
+        }
+
+        let faces: Vec<_> = font_system
+            .db()
+            .faces()
+            .filter(|face| face.families.iter().any(|(name, _)| name == family))
+            .map(|face| face.id)
+            .collect();
+        let covered = faces.into_iter().any(|id| {
+            font_system
+                .get_font(id)
+                .map_or(false, |font| font.as_swash().charmap().map(c) != 0)
+        });
+        let _ = self.coverage.insert(key, covered);
+        covered
+    }
+}
+
+/// Whether `c` gets shown as an emoji, either by default or because the character after it asks
+/// for it to be
+fn is_emoji(c: char, next: Option<char>) -> bool {
+    const EMOJI_PRESENTATION: char = '\u{fe0f}';
+    let pictographic = matches!(c, '\u{1f000}'..='\u{1faff}');
+    pictographic || next == Some(EMOJI_PRESENTATION)
+}
+
+/// Parts of an emoji sequence that have to stay in the same font as the emoji before them, like
+/// the joiners in 👩‍💻 or the tags in a subdivision flag
+fn is_emoji_component(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200d}' | '\u{fe0f}' | '\u{20e3}' | '\u{e0020}'..='\u{e007f}'
+    )
+}
+
+/// Groups neighboring characters that `pick` picks the same font for
+fn split_runs(
+    text: &str,
+    mut pick: impl FnMut(char) -> Option<usize>,
+) -> Vec<(Range<usize>, Option<usize>)> {
+    let mut runs: Vec<(Range<usize>, Option<usize>)> = Vec::new();
+    for (i, c) in text.char_indices() {
+        let font = pick(c);
+        let end = i + c.len_utf8();
+        match runs.last_mut() {
+            Some((range, last)) if *last == font => range.end = end,
+            _ => runs.push((i..end, font)),
+        }
+    }
+    if runs.is_empty() {
+        runs.push((0..0, None));
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitting_runs() {
+        // Pretend the first fallback only has kana and the second has everything else
+        let pick = |c: char| match c {
+            'ぁ'..='ヿ' => Some(0),
+            c if c.is_ascii() => None,
+            _ => Some(1),
+        };
+        let text = "Hi こんにちは 世界!";
+        let runs = split_runs(text, pick);
+        let runs: Vec<_> = runs
+            .into_iter()
+            .map(|(range, font)| (&text[range], font))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                ("Hi ", None),
+                ("こんにちは", Some(0)),
+                (" ", None),
+                ("世界", Some(1)),
+                ("!", None),
+            ]
+        );
+        assert_eq!(split_runs("", pick), [(0..0, None)]);
+    }
+
+    #[test]
+    fn spotting_emoji() {
+        assert!(is_emoji('🚀', None));
+        assert!(is_emoji('🏽', Some('x')));
+        // Text presentation unless asked otherwise
+        assert!(!is_emoji('❤', None));
+        assert!(is_emoji('❤', Some('\u{fe0f}')));
+        assert!(is_emoji('1', Some('\u{fe0f}')));
+        assert!(!is_emoji('é', None));
+
+        assert!("\u{200d}\u{fe0f}\u{20e3}\u{e0067}"
+            .chars()
+            .all(is_emoji_component));
+        assert!(!is_emoji_component('a'));
+    }
+}
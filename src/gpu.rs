@@ -0,0 +1,139 @@
+//! Getting a working device out of the GPU, and getting another one when it goes away
+
+use std::borrow::Cow;
+
+use crate::image::ImageRenderer;
+use crate::renderer::Vertex;
+
+use anyhow::Context;
+use glyphon::{TextAtlas, TextRenderer};
+use wgpu::{MultisampleState, TextureFormat};
+
+/// Everything that belongs to a device, so it all has to be recreated along with it
+pub struct Gpu {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub render_pipeline: wgpu::RenderPipeline,
+    pub image_renderer: ImageRenderer,
+    pub text_atlas: TextAtlas,
+    pub text_renderer: TextRenderer,
+    pub overlay_text_renderer: TextRenderer,
+}
+
+impl Gpu {
+    /// Sets everything up on `adapter`, falling back to a software adapter if that doesn't work
+    pub async fn new(
+        instance: &wgpu::Instance,
+        adapter: wgpu::Adapter,
+        compatible_surface: Option<&wgpu::Surface>,
+        format: TextureFormat,
+    ) -> anyhow::Result<Self> {
+        match Self::with_adapter(&adapter, format).await {
+            Ok(gpu) => Ok(gpu),
+            Err(err) => {
+                tracing::warn!("{err:#}\nFalling back to a software renderer");
+                let fallback = request_adapter(instance, compatible_surface, true)
+                    .await
+                    .context("Failed to find a software adapter to fall back to")?;
+                Self::with_adapter(&fallback, format).await
+            }
+        }
+    }
+
+    async fn with_adapter(adapter: &wgpu::Adapter, format: TextureFormat) -> anyhow::Result<Self> {
+        let name = adapter.get_info().name;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+                },
+                None,
+            )
+            .await
+            .with_context(|| format!("Failed to get a device from {name}"))?;
+
+        // Frames catch their own errors with error scopes, so these are only logged instead of
+        // taking down the whole program like wgpu's default handler would
+        device.on_uncaptured_error(Box::new(|err| tracing::error!("GPU error: {err}")));
+
+        // Some drivers choke on perfectly fine shaders, so check that everything compiled instead
+        // of finding out from a panic partway through the first frame
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let render_pipeline = shape_pipeline(&device, format);
+        let image_renderer = ImageRenderer::new(&device, &format);
+        let mut text_atlas = TextAtlas::new(&device, &queue, format);
+        let text_renderer =
+            TextRenderer::new(&mut text_atlas, &device, MultisampleState::default(), None);
+        let overlay_text_renderer =
+            TextRenderer::new(&mut text_atlas, &device, MultisampleState::default(), None);
+        if let Some(err) = device.pop_error_scope().await {
+            anyhow::bail!("Failed to create render pipelines on {name}: {err}");
+        }
+
+        Ok(Self {
+            device,
+            queue,
+            render_pipeline,
+            image_renderer,
+            text_atlas,
+            text_renderer,
+            overlay_text_renderer,
+        })
+    }
+}
+
+pub async fn request_adapter(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface>,
+    force_fallback_adapter: bool,
+) -> anyhow::Result<wgpu::Adapter> {
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter,
+            compatible_surface,
+        })
+        .await
+        .context("Failed to find an appropriate adapter")
+}
+
+/// The pipeline for all the shapes drawn with lyon
+fn shape_pipeline(device: &wgpu::Device, format: TextureFormat) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/shader.wgsl"))),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[],
+        push_constant_ranges: &[],
+    });
+
+    let vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+    }];
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &vertex_buffers,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,94 @@
+//! Going back and forth between the documents that were opened in the window, like a browser
+
+use std::path::PathBuf;
+
+use winit::event::MouseButton;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    /// Where the document was scrolled to when it was left
+    pub scroll_y: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Back,
+    Forward,
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    back: Vec<Entry>,
+    forward: Vec<Entry>,
+}
+
+impl History {
+    /// Records leaving `current` for a newly opened document. Whatever could have been gone
+    /// forward to is dropped
+    pub fn visit(&mut self, current: Entry) {
+        self.back.push(current);
+        self.forward.clear();
+    }
+
+    /// Steps away from `current` in `direction`, giving the entry to go to if there is one
+    pub fn step(&mut self, direction: Direction, current: Entry) -> Option<Entry> {
+        let (from, to) = match direction {
+            Direction::Back => (&mut self.back, &mut self.forward),
+            Direction::Forward => (&mut self.forward, &mut self.back),
+        };
+        let next = from.pop()?;
+        to.push(current);
+        Some(next)
+    }
+}
+
+/// Which way the extra side buttons that some mice have go. Each platform numbers them differently
+pub fn mouse_button_direction(button: MouseButton) -> Option<Direction> {
+    let (back, forward) = if cfg!(target_os = "windows") {
+        (1, 2)
+    } else if cfg!(target_os = "macos") {
+        (3, 4)
+    } else {
+        (8, 9)
+    };
+    match button {
+        MouseButton::Other(num) if num == back => Some(Direction::Back),
+        MouseButton::Other(num) if num == forward => Some(Direction::Forward),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, scroll_y: f32) -> Entry {
+        Entry {
+            path: path.into(),
+            scroll_y,
+        }
+    }
+
+    #[test]
+    fn back_and_forth() {
+        let mut history = History::default();
+        assert_eq!(history.step(Direction::Back, entry("README.md", 0.)), None);
+
+        history.visit(entry("README.md", 120.));
+        history.visit(entry("CONTRIBUTING.md", 40.));
+        let back = history.step(Direction::Back, entry("docs/usage.md", 10.));
+        assert_eq!(back, Some(entry("CONTRIBUTING.md", 40.)));
+        let back = history.step(Direction::Back, entry("CONTRIBUTING.md", 50.));
+        assert_eq!(back, Some(entry("README.md", 120.)));
+        let forward = history.step(Direction::Forward, entry("README.md", 120.));
+        assert_eq!(forward, Some(entry("CONTRIBUTING.md", 50.)));
+
+        // Opening something new drops what was ahead
+        history.visit(entry("CONTRIBUTING.md", 50.));
+        assert_eq!(
+            history.step(Direction::Forward, entry("LICENSE.md", 0.)),
+            None
+        );
+    }
+}
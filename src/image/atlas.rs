@@ -0,0 +1,224 @@
+//! Packs small images into one shared texture so that they can all be drawn together
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::utils::Rect;
+
+use image::RgbaImage;
+use wgpu::BindGroup;
+
+/// Images with both sides at most this long go in the atlas, like badges and icons
+pub const MAX_SIDE: u32 = 128;
+const SIZE: u32 = 1_024;
+/// Every image gets its edge pixels repeated around it, so that filtering along the edges doesn't
+/// pick up whatever its neighbors are
+const PADDING: u32 = 1;
+
+/// Tells apart the atlases getting emptied out or recreated on a new device, so that slots from an
+/// old one don't get drawn from the new one
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Where an image ended up in the atlas
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasSlot {
+    generation: u64,
+    /// The image's texture coordinates within the atlas
+    pub uv: Rect,
+}
+
+pub struct ImageAtlas {
+    generation: u64,
+    packer: ShelfPacker,
+    texture: wgpu::Texture,
+    pub bind_group: Arc<BindGroup>,
+    /// Set once something didn't fit, so that the atlas gets emptied out before the next frame
+    /// instead of partway through one that's already drawing from it
+    full: bool,
+}
+
+impl ImageAtlas {
+    pub fn new(
+        device: &wgpu::Device,
+        sampler: &wgpu::Sampler,
+        bindgroup_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let size = SIZE.min(device.limits().max_texture_dimension_2d);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("Image Atlas"),
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: bindgroup_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("Image Atlas Bind Group"),
+        });
+
+        Self {
+            generation: next_generation(),
+            packer: ShelfPacker::new(size),
+            texture,
+            bind_group: Arc::new(bind_group),
+            full: false,
+        }
+    }
+
+    /// Empties the atlas out if it filled up last frame. Images that were in it get packed in
+    /// again the next time they're drawn
+    pub fn next_frame(&mut self) {
+        if self.full {
+            tracing::debug!("Image atlas filled up. Emptying it out");
+            self.generation = next_generation();
+            self.packer = ShelfPacker::new(self.packer.size);
+            self.full = false;
+        }
+    }
+
+    /// Whether `slot` still holds the image that was put there
+    pub fn contains(&self, slot: &AtlasSlot) -> bool {
+        slot.generation == self.generation
+    }
+
+    /// Uploads `image` into the atlas, giving `None` if there's no room left for it
+    pub fn insert(&mut self, queue: &wgpu::Queue, image: &RgbaImage) -> Option<AtlasSlot> {
+        let (width, height) = image.dimensions();
+        let Some((x, y)) = self
+            .packer
+            .allocate(width + 2 * PADDING, height + 2 * PADDING)
+        else {
+            self.full = true;
+            return None;
+        };
+
+        let padded = RgbaImage::from_fn(width + 2 * PADDING, height + 2 * PADDING, |x, y| {
+            let x = x.saturating_sub(PADDING).min(width - 1);
+            let y = y.saturating_sub(PADDING).min(height - 1);
+            *image.get_pixel(x, y)
+        });
+        super::write_texture(
+            queue,
+            &self.texture,
+            0,
+            (x, y),
+            padded.as_raw(),
+            padded.dimensions(),
+        );
+
+        let size = self.packer.size as f32;
+        let uv = Rect::new(
+            ((x + PADDING) as f32 / size, (y + PADDING) as f32 / size),
+            (width as f32 / size, height as f32 / size),
+        );
+        Some(AtlasSlot {
+            generation: self.generation,
+            uv,
+        })
+    }
+}
+
+/// Fills the atlas row by row, where each row is as tall as the first image that went in it
+#[derive(Debug)]
+struct ShelfPacker {
+    size: u32,
+    shelves: Vec<Shelf>,
+}
+
+#[derive(Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// How much of the row is already taken
+    width: u32,
+}
+
+impl ShelfPacker {
+    fn new(size: u32) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Finds room for a `width` by `height` rectangle, giving its top left corner
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.size || height > self.size {
+            return None;
+        }
+
+        // The snuggest row that it fits in wastes the least space
+        let size = self.size;
+        let best = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && shelf.width + width <= size)
+            .min_by_key(|shelf| shelf.height);
+        if let Some(shelf) = best {
+            let pos = (shelf.width, shelf.y);
+            shelf.width += width;
+            return Some(pos);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        if y + height > self.size {
+            return None;
+        }
+        self.shelves.push(Shelf { y, height, width });
+        Some((0, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_rows_before_starting_new_ones() {
+        let mut packer = ShelfPacker::new(100);
+        assert_eq!(packer.allocate(40, 20), Some((0, 0)));
+        assert_eq!(packer.allocate(40, 10), Some((40, 0)));
+        // Too wide for what's left of the first row
+        assert_eq!(packer.allocate(30, 16), Some((0, 20)));
+        // Both rows have room, but the shorter one wastes less of it
+        assert_eq!(packer.allocate(10, 15), Some((30, 20)));
+        assert_eq!(packer.allocate(20, 5), Some((40, 20)));
+        assert_eq!(packer.allocate(20, 18), Some((80, 0)));
+    }
+
+    #[test]
+    fn gives_up_once_full() {
+        let mut packer = ShelfPacker::new(100);
+        assert_eq!(packer.allocate(101, 1), None);
+        assert_eq!(packer.allocate(100, 60), Some((0, 0)));
+        assert_eq!(packer.allocate(50, 50), None);
+        assert_eq!(packer.allocate(50, 40), Some((0, 60)));
+        assert_eq!(packer.allocate(50, 40), Some((50, 60)));
+        assert_eq!(packer.allocate(1, 1), None);
+    }
+}
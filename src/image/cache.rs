@@ -0,0 +1,279 @@
+//! Keeps remote images around on disk, so that documents full of badges don't have to download
+//! everything again on every launch (or at all when offline)
+
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context;
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use twox_hash::XxHash64;
+
+/// The least recently used images get evicted once everything adds up to more than this
+pub const MAX_CACHE_SIZE: u64 = 64 * 1_024 * 1_024;
+
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+/// What gets stored alongside each image to check whether it's still fresh
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Entry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Entry {
+    fn from_headers(url: &str, headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        Self {
+            url: url.to_owned(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+}
+
+impl DiskCache {
+    /// The cache in the platform's cache directory
+    pub fn load() -> Option<Self> {
+        let Some(dir) = dirs::cache_dir() else {
+            tracing::warn!("Failed to find a directory to cache images in");
+            return None;
+        };
+        Some(Self::new(dir.join("inlyne").join("images"), MAX_CACHE_SIZE))
+    }
+
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self { dir, max_size }
+    }
+
+    /// Gets the image at `url`, only downloading it again if the server says that it changed
+    ///
+    /// Falls back to the cached copy when the server can't be reached
+    pub fn fetch(&self, client: &Client, url: &str, headers: HeaderMap) -> anyhow::Result<Vec<u8>> {
+        let (entry_path, body_path) = self.paths(url);
+        let cached = read_entry(&entry_path, url).and_then(|entry| {
+            let body = fs::read(&body_path).ok()?;
+            Some((entry, body))
+        });
+
+        let mut request = client.get(url).headers(headers);
+        if let Some((entry, _)) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = match request.send() {
+            Ok(response) => response,
+            Err(err) => {
+                let (_, body) = cached.with_context(|| format!("Failed fetching {url}: {err}"))?;
+                tracing::debug!("Using cached image for {url} since fetching failed: {err}");
+                return Ok(body);
+            }
+        };
+
+        let status = response.status();
+        if let Some((entry, body)) = cached {
+            if status == StatusCode::NOT_MODIFIED || status.is_server_error() {
+                tracing::debug!("Using cached image for {url} ({status})");
+                // Rewriting the entry marks it as recently used
+                write_entry(&entry_path, &entry)?;
+                return Ok(body);
+            }
+        }
+
+        anyhow::ensure!(status.is_success(), "Failed fetching {url}: {status}");
+        let entry = Entry::from_headers(url, response.headers());
+        let body = response.bytes()?.to_vec();
+        if let Err(err) = self.store(&entry_path, &body_path, &entry, &body) {
+            tracing::warn!("Failed caching image from {url}: {err}");
+        }
+
+        Ok(body)
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let mut hasher = XxHash64::default();
+        hasher.write(url.as_bytes());
+        let name = format!("{:016x}", hasher.finish());
+        (
+            self.dir.join(format!("{name}.json")),
+            self.dir.join(format!("{name}.bin")),
+        )
+    }
+
+    fn store(
+        &self,
+        entry_path: &Path,
+        body_path: &Path,
+        entry: &Entry,
+        body: &[u8],
+    ) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // Write then rename so that a half-written image never gets picked up
+        let temp_path = body_path.with_extension("bin.tmp");
+        fs::write(&temp_path, body)?;
+        fs::rename(&temp_path, body_path)?;
+        write_entry(entry_path, entry)?;
+
+        self.evict()
+    }
+
+    /// Removes the least recently used images until everything fits in the max size
+    fn evict(&self) -> anyhow::Result<()> {
+        let mut images = Vec::new();
+        let mut total_size = 0;
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let entry_path = dir_entry?.path();
+            if entry_path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            let body_path = entry_path.with_extension("bin");
+            let Ok(size) = fs::metadata(&body_path).map(|meta| meta.len()) else {
+                continue;
+            };
+            let last_used = fs::metadata(&entry_path)
+                .and_then(|meta| meta.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            total_size += size;
+            images.push((last_used, size, entry_path, body_path));
+        }
+
+        images.sort_by_key(|(last_used, ..)| *last_used);
+        for (_, size, entry_path, body_path) in images {
+            if total_size <= self.max_size {
+                break;
+            }
+            tracing::debug!("Evicting cached image {}", body_path.display());
+            fs::remove_file(&entry_path)?;
+            fs::remove_file(&body_path)?;
+            total_size -= size;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_entry(path: &Path, url: &str) -> Option<Entry> {
+    let contents = fs::read_to_string(path).ok()?;
+    let entry: Entry = serde_json::from_str(&contents).ok()?;
+    // Just in case two urls manage to hash to the same thing
+    (entry.url == url).then_some(entry)
+}
+
+fn write_entry(path: &Path, entry: &Entry) -> anyhow::Result<()> {
+    fs::write(path, serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    use filetime::FileTime;
+    use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+    fn fetch(cache: &DiskCache, url: &str) -> anyhow::Result<Vec<u8>> {
+        // The blocking client can't be used from within the server's runtime
+        let (cache, url) = (cache.clone(), url.to_owned());
+        std::thread::spawn(move || cache.fetch(&utils::client(), &url, HeaderMap::new()))
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn revalidates_cached_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_owned(), MAX_CACHE_SIZE);
+
+        let server = pollster::block_on(MockServer::start());
+        let url = format!("{}/badge.svg", server.uri());
+        pollster::block_on(
+            Mock::given(matchers::header("If-None-Match", "\"v1\""))
+                .respond_with(ResponseTemplate::new(304))
+                .expect(1)
+                .mount(&server),
+        );
+        pollster::block_on(
+            Mock::given(matchers::path("/badge.svg"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("ETag", "\"v1\"")
+                        .set_body_bytes(b"<svg/>".as_slice()),
+                )
+                .expect(1)
+                .mount(&server),
+        );
+
+        assert_eq!(fetch(&cache, &url).unwrap(), b"<svg/>");
+        // The second time around only gets a "Not Modified" from the server
+        assert_eq!(fetch(&cache, &url).unwrap(), b"<svg/>");
+    }
+
+    #[test]
+    fn works_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_owned(), MAX_CACHE_SIZE);
+
+        // Nothing listens on port 1, so connecting fails right away
+        let url = "http://127.0.0.1:1/badge.svg";
+        let (entry_path, body_path) = cache.paths(url);
+        let entry = Entry {
+            url: url.to_owned(),
+            etag: Some("\"v1\"".to_owned()),
+            last_modified: None,
+        };
+        cache
+            .store(&entry_path, &body_path, &entry, b"<svg/>")
+            .unwrap();
+
+        assert_eq!(fetch(&cache, url).unwrap(), b"<svg/>");
+        assert!(fetch(&cache, "http://127.0.0.1:1/other.svg").is_err());
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_owned(), 10);
+
+        let entry = |url: &str| Entry {
+            url: url.to_owned(),
+            etag: None,
+            last_modified: None,
+        };
+        for (url, last_used) in [("old", 2), ("older", 1), ("new", 3)] {
+            let (entry_path, body_path) = cache.paths(url);
+            cache
+                .store(&entry_path, &body_path, &entry(url), &[0; 4])
+                .unwrap();
+            filetime::set_file_mtime(&entry_path, FileTime::from_unix_time(last_used, 0)).unwrap();
+        }
+        cache.evict().unwrap();
+
+        let cached = |url| {
+            let (entry_path, body_path) = cache.paths(url);
+            read_entry(&entry_path, url).is_some() && body_path.is_file()
+        };
+        assert!(!cached("older"));
+        assert!(cached("old"));
+        assert!(cached("new"));
+    }
+}
@@ -1,13 +1,19 @@
 use std::cmp;
+use std::fmt;
 use std::io;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use crate::image::icc::Profile;
 use crate::utils::usize_in_mib;
 
+use anyhow::Context;
 use image::codecs::{
     gif::GifDecoder, jpeg::JpegDecoder, png::PngDecoder, tiff::TiffDecoder, webp::WebPDecoder,
 };
-use image::{ColorType, GenericImageView, ImageDecoder, ImageFormat, ImageResult};
+use image::{
+    AnimationDecoder, ColorType, DynamicImage, Frames, GenericImageView, ImageDecoder, ImageFormat,
+    ImageResult, RgbaImage,
+};
 use lz4_flex::frame::{BlockSize, FrameDecoder, FrameEncoder, FrameInfo};
 
 pub fn lz4_compress<R: io::Read>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
@@ -30,9 +36,191 @@ pub fn lz4_decompress(blob: &[u8], size: usize) -> anyhow::Result<Vec<u8>> {
     Ok(decompressed)
 }
 
-pub type ImageParts = (Vec<u8>, (u32, u32));
+pub struct ImageParts {
+    pub lz4_blob: Vec<u8>,
+    pub dimensions: (u32, u32),
+    /// Whether any of the pixels are see-through
+    pub has_alpha: bool,
+}
+
+/// Whether any of the RGBA8 pixels in `rgba` aren't fully opaque
+pub fn has_alpha(rgba: &[u8]) -> bool {
+    rgba.chunks_exact(4).any(|pixel| pixel[3] < u8::MAX)
+}
+
+/// Formats that turn up in docs, but that only get decoded when inlyne is built with the feature
+/// for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionalFormat {
+    Avif,
+    JpegXl,
+}
+
+impl OptionalFormat {
+    pub fn sniff(contents: &[u8]) -> Option<Self> {
+        const JXL_CODESTREAM: &[u8] = &[0xff, 0x0a];
+        const JXL_CONTAINER: &[u8] = b"\0\0\0\x0cJXL \r\n\x87\n";
+        if contents.starts_with(JXL_CODESTREAM) || contents.starts_with(JXL_CONTAINER) {
+            return Some(Self::JpegXl);
+        }
+
+        // AVIFs start with an ISOBMFF `ftyp` box that lists `avif` or `avis` among its brands
+        let box_size = u32::from_be_bytes(contents.get(..4)?.try_into().ok()?);
+        let ftyp = contents.get(..usize::try_from(box_size).ok()?)?;
+        if ftyp.len() < 16 || &ftyp[4..8] != b"ftyp" {
+            return None;
+        }
+        let major_brand = &ftyp[8..12];
+        let mut brands = std::iter::once(major_brand).chain(ftyp[16..].chunks_exact(4));
+        brands
+            .any(|brand| brand == b"avif" || brand == b"avis")
+            .then_some(Self::Avif)
+    }
+
+    /// The cargo feature that brings in a decoder for the format
+    pub fn feature(self) -> &'static str {
+        match self {
+            Self::Avif => "avif",
+            Self::JpegXl => "jxl",
+        }
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Avif => cfg!(feature = "avif"),
+            Self::JpegXl => cfg!(feature = "jxl"),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Avif => "avif",
+            Self::JpegXl => "jxl",
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "avif", feature = "jxl")), allow(unused_variables))]
+    fn decode(self, contents: &[u8]) -> anyhow::Result<DynamicImage> {
+        match self {
+            // `image` only guesses AVIFs with a few specific brands, so skip the guessing
+            #[cfg(feature = "avif")]
+            Self::Avif => Ok(image::load_from_memory_with_format(
+                contents,
+                ImageFormat::Avif,
+            )?),
+            #[cfg(feature = "jxl")]
+            Self::JpegXl => decode_jxl(contents),
+            #[allow(unreachable_patterns)]
+            _ => anyhow::bail!("Built without the `{}` feature", self.feature()),
+        }
+    }
+}
+
+impl fmt::Display for OptionalFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Avif => "AVIF",
+            Self::JpegXl => "JPEG XL",
+        })
+    }
+}
+
+/// Renders the first frame of a JPEG XL in sRGB
+#[cfg(feature = "jxl")]
+fn decode_jxl(contents: &[u8]) -> anyhow::Result<DynamicImage> {
+    let image = jxl_oxide::JxlImage::builder().read(io::Cursor::new(contents))?;
+    let render = image.render_frame(0)?;
+    let mut stream = render.stream();
+    let (width, height, channels) = (stream.width(), stream.height(), stream.channels());
+    let mut samples = vec![0f32; width as usize * height as usize * channels as usize];
+    stream.write_to_buffer(&mut samples);
+
+    let to_u8 = |sample: f32| (sample.clamp(0., 1.) * 255. + 0.5) as u8;
+    let rgba = samples
+        .chunks_exact(channels as usize)
+        .flat_map(|pixel| match *pixel {
+            [gray] => [gray, gray, gray, 1.],
+            [gray, alpha] => [gray, gray, gray, alpha],
+            [r, g, b] => [r, g, b, 1.],
+            [r, g, b, alpha, ..] => [r, g, b, alpha],
+            [] => unreachable!("Chunks are never empty"),
+        })
+        .map(to_u8)
+        .collect();
+    let image =
+        RgbaImage::from_raw(width, height, rgba).context("Decoded JPEG XL is the wrong size")?;
+    Ok(DynamicImage::ImageRgba8(image))
+}
+
+/// Browsers bump up tiny delays since so many GIFs in the wild set them to 0 or 10ms while
+/// expecting something much slower
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const BUMPED_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// Decodes every frame of an animated GIF, PNG, or WebP along with how long each is shown
+///
+/// Still images, including animation formats with a single frame, give `None`
+pub fn decode_animation(contents: &[u8]) -> anyhow::Result<Option<Vec<(RgbaImage, Duration)>>> {
+    if OptionalFormat::sniff(contents).is_some() {
+        return Ok(None);
+    }
+    let frames: Frames<'_> = match image::guess_format(contents)? {
+        ImageFormat::Gif => GifDecoder::new(io::Cursor::new(contents))?.into_frames(),
+        ImageFormat::Png => {
+            let dec = PngDecoder::new(io::Cursor::new(contents))?;
+            if !dec.is_apng() {
+                return Ok(None);
+            }
+            dec.apng().into_frames()
+        }
+        ImageFormat::WebP => {
+            let dec = WebPDecoder::new(io::Cursor::new(contents))?;
+            if !dec.has_animation() {
+                return Ok(None);
+            }
+            dec.into_frames()
+        }
+        _ => return Ok(None),
+    };
+
+    let start = Instant::now();
+    let frames = frames.collect_frames()?;
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+    tracing::debug!(
+        "Decoded {} animation frames: Time {:.2?}",
+        frames.len(),
+        start.elapsed()
+    );
+
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let delay = Duration::from(frame.delay());
+            let delay = if delay < MIN_FRAME_DELAY {
+                BUMPED_FRAME_DELAY
+            } else {
+                delay
+            };
+            (frame.into_buffer(), delay)
+        })
+        .collect();
+    Ok(Some(frames))
+}
 
 pub fn decode_and_compress(contents: &[u8]) -> anyhow::Result<ImageParts> {
+    if let Some(format) = OptionalFormat::sniff(contents) {
+        return compress_decoded(format.decode(contents)?);
+    }
+
+    // Turning the image upright or converting its colors needs all of it at once
+    let orientation = Orientation::from_exif(contents);
+    let profile = icc_profile(contents).and_then(|icc| Profile::parse(&icc));
+    if orientation != Orientation::Normal || profile.is_some() {
+        return fallback_decode_and_compress(contents, orientation, profile.as_ref());
+    }
+
     // We can stream decoding some formats although decoding may still load everything into memory
     // at once depending on how the decoder behaves
     let maybe_streamed = match image::guess_format(contents)? {
@@ -46,7 +234,19 @@ pub fn decode_and_compress(contents: &[u8]) -> anyhow::Result<ImageParts> {
 
     match maybe_streamed {
         Some(streamed) => Ok(streamed),
-        None => fallback_decode_and_compress(contents),
+        None => fallback_decode_and_compress(contents, Orientation::Normal, None),
+    }
+}
+
+/// The color profile embedded in the image, if its format has a place for one
+fn icc_profile(contents: &[u8]) -> Option<Vec<u8>> {
+    let cursor = io::Cursor::new(contents);
+    match image::guess_format(contents).ok()? {
+        ImageFormat::Png => PngDecoder::new(cursor).ok()?.icc_profile(),
+        ImageFormat::Jpeg => JpegDecoder::new(cursor).ok()?.icc_profile(),
+        ImageFormat::Tiff => TiffDecoder::new(cursor).ok()?.icc_profile(),
+        ImageFormat::WebP => WebPDecoder::new(cursor).ok()?.icc_profile(),
+        _ => None,
     }
 }
 
@@ -63,9 +263,10 @@ where
     let dimensions = dec.dimensions();
     let start = Instant::now();
 
-    let Some(mut adapter) = Rgba8Adapter::new(dec) else {
+    let Some(adapter) = Rgba8Adapter::new(dec) else {
         return Ok(None);
     };
+    let mut adapter = AlphaScan::new(adapter);
 
     let maybe_image_parts = lz4_compress(&mut adapter).ok().map(|lz4_blob| {
         tracing::debug!(
@@ -78,11 +279,49 @@ where
             start.elapsed(),
         );
 
-        (lz4_blob, dimensions)
+        ImageParts {
+            lz4_blob,
+            dimensions,
+            has_alpha: adapter.has_alpha,
+        }
     });
     Ok(maybe_image_parts)
 }
 
+/// Notes whether any of the RGBA8 pixels read through it aren't fully opaque
+pub struct AlphaScan<R> {
+    inner: R,
+    bytes_read: usize,
+    pub has_alpha: bool,
+}
+
+impl<R> AlphaScan<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            bytes_read: 0,
+            has_alpha: false,
+        }
+    }
+}
+
+impl<R: io::Read> io::Read for AlphaScan<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if !self.has_alpha {
+            // Reads can end partway through a pixel
+            let first_alpha = (3 + 4 - self.bytes_read % 4) % 4;
+            self.has_alpha = buf[..n]
+                .iter()
+                .skip(first_alpha)
+                .step_by(4)
+                .any(|&alpha| alpha < u8::MAX);
+        }
+        self.bytes_read += n;
+        Ok(n)
+    }
+}
+
 /// An adapter that can do a streaming transformation from some pixel formats to RGBA8
 enum Rgba8Adapter<'img> {
     Rgba8(Box<dyn io::Read + 'img>),
@@ -179,13 +418,199 @@ impl<'img> io::Read for Rgba8Adapter<'img> {
     }
 }
 
-fn fallback_decode_and_compress(contents: &[u8]) -> anyhow::Result<(Vec<u8>, (u32, u32))> {
-    let image = image::load_from_memory(contents)?;
+fn fallback_decode_and_compress(
+    contents: &[u8],
+    orientation: Orientation,
+    profile: Option<&Profile>,
+) -> anyhow::Result<ImageParts> {
+    let image = match profile {
+        Some(profile) if profile.channels() == 4 => decode_cmyk(contents, profile)?,
+        Some(profile) => {
+            let mut image = image::load_from_memory(contents)?.into_rgba8();
+            profile.rgba_to_srgb(&mut image);
+            DynamicImage::ImageRgba8(image)
+        }
+        None => image::load_from_memory(contents)?,
+    };
+    compress_decoded(orientation.apply(image))
+}
+
+fn compress_decoded(image: DynamicImage) -> anyhow::Result<ImageParts> {
     let dimensions = image.dimensions();
     let image_data = image.into_rgba8().into_raw();
     tracing::debug!(
         "Decoded full image in memory {:.3} MiB",
         usize_in_mib(image_data.len()),
     );
-    lz4_compress(&mut io::Cursor::new(image_data)).map(|lz4_blob| (lz4_blob, dimensions))
+    let has_alpha = has_alpha(&image_data);
+    lz4_compress(&mut io::Cursor::new(image_data)).map(|lz4_blob| ImageParts {
+        lz4_blob,
+        dimensions,
+        has_alpha,
+    })
+}
+
+/// Decodes a CMYK JPEG through its profile, since `image` converts CMYK to RGB as if the inks
+/// were perfect
+fn decode_cmyk(contents: &[u8], profile: &Profile) -> anyhow::Result<DynamicImage> {
+    let mut dec = jpeg_decoder::Decoder::new(io::Cursor::new(contents));
+    let cmyk = dec.decode()?;
+    let info = dec.info().context("Missing JPEG info")?;
+    if info.pixel_format != jpeg_decoder::PixelFormat::CMYK32 {
+        return Ok(image::load_from_memory(contents)?);
+    }
+    let rgba = profile
+        .cmyk_to_srgb(&cmyk)
+        .context("Profile can't convert CMYK")?;
+    let image = RgbaImage::from_raw(info.width.into(), info.height.into(), rgba)
+        .context("Decoded JPEG is the wrong size")?;
+    Ok(DynamicImage::ImageRgba8(image))
+}
+
+/// How an image's pixels have to be transformed to show it upright, from its EXIF orientation tag
+///
+/// Mostly comes up with photos from phones, which store everything the way the sensor saw it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Normal,
+    FlipHorizontal,
+    Rotate180,
+    FlipVertical,
+    /// Flipped across the top-left to bottom-right diagonal
+    Transpose,
+    Rotate90,
+    /// Flipped across the top-right to bottom-left diagonal
+    Transverse,
+    Rotate270,
+}
+
+impl Orientation {
+    const TAG: u16 = 0x0112;
+
+    pub fn from_exif(contents: &[u8]) -> Self {
+        let tag = find_exif(contents).and_then(|tiff| tiff_tag(tiff, Self::TAG));
+        match tag {
+            Some(2) => Self::FlipHorizontal,
+            Some(3) => Self::Rotate180,
+            Some(4) => Self::FlipVertical,
+            Some(5) => Self::Transpose,
+            Some(6) => Self::Rotate90,
+            Some(7) => Self::Transverse,
+            Some(8) => Self::Rotate270,
+            _ => Self::Normal,
+        }
+    }
+
+    /// Rotations by a quarter turn swap the image's width and height
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Self::Normal => image,
+            Self::FlipHorizontal => image.fliph(),
+            Self::Rotate180 => image.rotate180(),
+            Self::FlipVertical => image.flipv(),
+            Self::Transpose => image.rotate90().fliph(),
+            Self::Rotate90 => image.rotate90(),
+            Self::Transverse => image.rotate270().fliph(),
+            Self::Rotate270 => image.rotate270(),
+        }
+    }
+}
+
+/// The TIFF structure holding the EXIF data in JPEGs, PNGs, WebPs, and TIFFs themselves
+fn find_exif(contents: &[u8]) -> Option<&[u8]> {
+    const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+    let be_u16 =
+        |bytes: &[u8], at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let be_u32 =
+        |bytes: &[u8], at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let le_u32 =
+        |bytes: &[u8], at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+
+    match image::guess_format(contents).ok()? {
+        ImageFormat::Jpeg => {
+            // Segments up until the image data starts
+            let mut pos = 2;
+            while *contents.get(pos)? == 0xFF {
+                let marker = *contents.get(pos + 1)?;
+                if marker == 0xDA {
+                    break;
+                }
+                let len = be_u16(contents, pos + 2)? as usize;
+                let segment = contents.get(pos + 4..pos + 2 + len)?;
+                if marker == 0xE1 {
+                    if let Some(tiff) = segment.strip_prefix(EXIF_HEADER) {
+                        return Some(tiff);
+                    }
+                }
+                pos += 2 + len;
+            }
+            None
+        }
+        ImageFormat::Png => {
+            // Chunks after the signature up until the image data starts
+            let mut pos = 8;
+            loop {
+                let len = be_u32(contents, pos)? as usize;
+                let kind = contents.get(pos + 4..pos + 8)?;
+                let data = contents.get(pos + 8..pos + 8 + len)?;
+                match kind {
+                    b"eXIf" => return Some(data),
+                    b"IDAT" => return None,
+                    _ => pos += 12 + len,
+                }
+            }
+        }
+        ImageFormat::WebP => {
+            // Chunks after the RIFF header, which are padded to an even length
+            let mut pos = 12;
+            loop {
+                let kind = contents.get(pos..pos + 4)?;
+                let len = le_u32(contents, pos + 4)? as usize;
+                let data = contents.get(pos + 8..pos + 8 + len)?;
+                if kind == b"EXIF" {
+                    return Some(data.strip_prefix(EXIF_HEADER).unwrap_or(data));
+                }
+                pos += 8 + len + len % 2;
+            }
+        }
+        ImageFormat::Tiff => Some(contents),
+        _ => None,
+    }
+}
+
+/// Looks up a short-valued tag in the first IFD of a TIFF structure
+fn tiff_tag(tiff: &[u8], tag: u16) -> Option<u16> {
+    const SHORT: u16 = 3;
+
+    let big_endian = match tiff.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let bytes = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |at: usize| {
+        let bytes = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let num_entries = u16_at(ifd)? as usize;
+    (0..num_entries).find_map(|i| {
+        let entry = ifd + 2 + i * 12;
+        (u16_at(entry)? == tag && u16_at(entry + 2)? == SHORT)
+            .then(|| u16_at(entry + 8))
+            .flatten()
+    })
 }
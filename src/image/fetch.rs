@@ -0,0 +1,391 @@
+//! Downloading remote images, with a little patience for slow or flaky servers
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::thread;
+use std::time::Duration;
+
+use crate::image::cache::DiskCache;
+use crate::image::netrc::Netrc;
+use crate::opts::ImageFetchOptions;
+use crate::utils;
+
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{redirect, NoProxy, Proxy, Url};
+
+/// How long to wait before the first retry. Each one after that waits twice as long
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The same limit as reqwest's default redirect policy
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct Fetcher {
+    pub disk_cache: Option<DiskCache>,
+    pub timeout: Duration,
+    pub retries: u32,
+    /// Extra headers for each host, with the most specific hosts last so that they win
+    pub headers: Vec<(String, HeaderMap)>,
+    pub netrc: Option<Netrc>,
+    pub proxy: Option<Proxy>,
+}
+
+impl Default for Fetcher {
+    fn default() -> Self {
+        Self::new(ImageFetchOptions::default())
+    }
+}
+
+impl Fetcher {
+    pub fn new(opts: ImageFetchOptions) -> Self {
+        let ImageFetchOptions {
+            timeout,
+            retries,
+            headers,
+            netrc,
+            proxy,
+            ..
+        } = opts;
+        let mut headers: Vec<_> = headers
+            .into_iter()
+            .map(|(host, headers)| (host.to_ascii_lowercase(), header_map(&host, headers)))
+            .collect();
+        headers.sort_by_key(|(host, _)| host.len());
+        let proxy = proxy.and_then(|proxy| match Proxy::all(&proxy) {
+            Ok(proxy) => Some(proxy.no_proxy(NoProxy::from_env())),
+            Err(err) => {
+                tracing::warn!("Invalid image fetch proxy {proxy}: {err}");
+                None
+            }
+        });
+        Self {
+            disk_cache: None,
+            timeout: Duration::try_from_secs_f32(timeout).unwrap_or_else(|err| {
+                tracing::warn!("Invalid image fetch timeout of {timeout}: {err}");
+                DEFAULT_TIMEOUT
+            }),
+            retries,
+            headers,
+            netrc: if netrc { Netrc::load() } else { None },
+            proxy,
+        }
+    }
+
+    /// Gets the image at `url`, trying again after a growing delay when the request fails
+    pub fn fetch(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        let client = self.client(url);
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            match self.fetch_once(&client, url) {
+                Ok(bytes) => return Ok(bytes),
+                Err(err) if attempt < self.retries => {
+                    tracing::debug!("Retrying {url} in {backoff:?} after error: {err}");
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn fetch_once(&self, client: &Client, url: &str) -> anyhow::Result<Vec<u8>> {
+        let headers = self.headers_for(url);
+        match &self.disk_cache {
+            Some(disk_cache) => disk_cache.fetch(client, url, headers),
+            None => Ok(client
+                .get(url)
+                .headers(headers)
+                .send()?
+                .error_for_status()?
+                .bytes()?
+                .to_vec()),
+        }
+    }
+
+    /// Everything configured for the host that `url` points to
+    ///
+    /// These are usually secrets, so they only get sent over https, or to this machine
+    fn headers_for(&self, url: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let Ok(url) = Url::parse(url) else {
+            return headers;
+        };
+        let Some(host) = url.host_str().map(str::to_ascii_lowercase) else {
+            return headers;
+        };
+        if url.scheme() != "https" && !is_loopback(&host) {
+            return headers;
+        }
+
+        for (pattern, extra) in &self.headers {
+            let is_subdomain = host
+                .strip_suffix(pattern.as_str())
+                .map_or(false, |sub| sub.is_empty() || sub.ends_with('.'));
+            if is_subdomain {
+                headers.extend(extra.clone());
+            }
+        }
+        if !headers.contains_key(AUTHORIZATION) {
+            if let Some(auth) = self
+                .netrc
+                .as_ref()
+                .and_then(|netrc| netrc.authorization(&host))
+            {
+                headers.insert(AUTHORIZATION, auth);
+            }
+        }
+        headers
+    }
+
+    /// reqwest only drops `Authorization` and cookies when a redirect goes to another host, so
+    /// requests with anything configured for them aren't allowed to leave where they started
+    fn client(&self, url: &str) -> Client {
+        let mut builder = utils::client_builder().timeout(self.timeout);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        if let (false, Ok(url)) = (self.headers_for(url).is_empty(), Url::parse(url)) {
+            let origin = url.origin();
+            builder = builder.redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() > MAX_REDIRECTS {
+                    attempt.error("Too many redirects")
+                } else if attempt.url().origin() != origin {
+                    let error = format!(
+                        "Refusing to send the headers for {} on to {}",
+                        origin.ascii_serialization(),
+                        attempt.url().origin().ascii_serialization()
+                    );
+                    attempt.error(error)
+                } else {
+                    attempt.follow()
+                }
+            }));
+        }
+        builder.build().unwrap()
+    }
+}
+
+/// `host` as it is in a URL, so IPv6 addresses are in brackets
+fn is_loopback(host: &str) -> bool {
+    let ip = host.trim_start_matches('[').trim_end_matches(']');
+    host == "localhost" || ip.parse::<IpAddr>().map_or(false, |ip| ip.is_loopback())
+}
+
+fn header_map(host: &str, headers: BTreeMap<String, String>) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .map_err(anyhow::Error::from)
+            .and_then(|name| Ok((name, HeaderValue::from_str(&value)?)));
+        match header {
+            Ok((name, mut value)) => {
+                // These are usually secrets
+                value.set_sensitive(true);
+                map.insert(name, value);
+            }
+            Err(err) => tracing::warn!("Skipping invalid header {name} for {host}: {err}"),
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
+
+    fn fetch(fetcher: &Fetcher, url: &str) -> anyhow::Result<Vec<u8>> {
+        // The blocking client can't be used from within the server's runtime
+        let (fetcher, url) = (fetcher.clone(), url.to_owned());
+        thread::spawn(move || fetcher.fetch(&url)).join().unwrap()
+    }
+
+    #[test]
+    fn retries_failed_requests() {
+        let server = pollster::block_on(MockServer::start());
+        let url = format!("{}/flaky.png", server.uri());
+        pollster::block_on(
+            Mock::given(matchers::path("/flaky.png"))
+                .respond_with(ResponseTemplate::new(503))
+                .up_to_n_times(1)
+                .expect(1)
+                .mount(&server),
+        );
+        pollster::block_on(
+            Mock::given(matchers::path("/flaky.png"))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(b"png".as_slice()))
+                .expect(1)
+                .mount(&server),
+        );
+
+        let fetcher = Fetcher {
+            retries: 1,
+            ..Fetcher::default()
+        };
+        assert_eq!(fetch(&fetcher, &url).unwrap(), b"png");
+    }
+
+    #[test]
+    fn sends_headers_for_the_host() {
+        let server = pollster::block_on(MockServer::start());
+        let url = format!("{}/private.png", server.uri());
+        pollster::block_on(
+            Mock::given(matchers::path("/private.png"))
+                .and(matchers::header("private-token", "secret"))
+                .and(matchers::header(
+                    "authorization",
+                    "Basic YWxpY2U6aHVudGVyMg==",
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(b"png".as_slice()))
+                .mount(&server),
+        );
+        pollster::block_on(
+            Mock::given(matchers::path("/private.png"))
+                .respond_with(ResponseTemplate::new(404))
+                .mount(&server),
+        );
+
+        let opts = ImageFetchOptions {
+            retries: 0,
+            headers: [
+                (
+                    "127.0.0.1".to_owned(),
+                    [("PRIVATE-TOKEN".to_owned(), "secret".to_owned())].into(),
+                ),
+                (
+                    "other.example.com".to_owned(),
+                    [("PRIVATE-TOKEN".to_owned(), "wrong".to_owned())].into(),
+                ),
+            ]
+            .into(),
+            ..ImageFetchOptions::default()
+        };
+        let without_login = Fetcher::new(opts.clone());
+        assert!(fetch(&without_login, &url).is_err());
+
+        let with_login = Fetcher {
+            netrc: Some(Netrc::parse(
+                "machine 127.0.0.1 login alice password hunter2",
+            )),
+            ..Fetcher::new(opts)
+        };
+        assert_eq!(fetch(&with_login, &url).unwrap(), b"png");
+    }
+
+    #[test]
+    fn matches_subdomains() {
+        let opts = ImageFetchOptions {
+            headers: [
+                (
+                    "Example.com".to_owned(),
+                    [("X-Token".to_owned(), "outer".to_owned())].into(),
+                ),
+                (
+                    "img.example.com".to_owned(),
+                    [("X-Token".to_owned(), "inner".to_owned())].into(),
+                ),
+            ]
+            .into(),
+            ..ImageFetchOptions::default()
+        };
+        let fetcher = Fetcher::new(opts);
+        let token = |url: &str| {
+            let headers = fetcher.headers_for(url);
+            headers
+                .get("x-token")
+                .map(|value| value.to_str().unwrap().to_owned())
+        };
+        assert_eq!(token("https://example.com/a.png").as_deref(), Some("outer"));
+        assert_eq!(
+            token("https://cdn.example.com/a.png").as_deref(),
+            Some("outer")
+        );
+        // The more specific host wins
+        assert_eq!(
+            token("https://img.example.com/a.png").as_deref(),
+            Some("inner")
+        );
+        assert_eq!(token("https://notexample.com/a.png"), None);
+        // Secrets don't go out in the clear
+        assert_eq!(token("http://example.com/a.png"), None);
+    }
+
+    #[test]
+    fn keeps_headers_from_other_hosts_on_redirect() {
+        let server = pollster::block_on(MockServer::start());
+        let other = pollster::block_on(MockServer::start());
+        let url = format!("{}/private.png", server.uri());
+        let moved = format!("{}/moved.png", other.uri());
+        pollster::block_on(
+            Mock::given(matchers::path("/private.png"))
+                .respond_with(ResponseTemplate::new(302).insert_header("location", moved.as_str()))
+                .mount(&server),
+        );
+        pollster::block_on(
+            Mock::given(matchers::path("/same-origin.png"))
+                .respond_with(ResponseTemplate::new(302).insert_header("location", "/moved.png"))
+                .mount(&server),
+        );
+        for server in [&server, &other] {
+            pollster::block_on(
+                Mock::given(matchers::path("/moved.png"))
+                    .respond_with(ResponseTemplate::new(200).set_body_bytes(b"png".as_slice()))
+                    .mount(server),
+            );
+        }
+
+        let opts = ImageFetchOptions {
+            retries: 0,
+            headers: [(
+                "127.0.0.1".to_owned(),
+                [("PRIVATE-TOKEN".to_owned(), "secret".to_owned())].into(),
+            )]
+            .into(),
+            ..ImageFetchOptions::default()
+        };
+        let fetcher = Fetcher::new(opts);
+        let err = fetch(&fetcher, &url).unwrap_err();
+        assert!(format!("{err:#}").contains("Refusing to send the headers"));
+        let same_origin = format!("{}/same-origin.png", server.uri());
+        assert_eq!(fetch(&fetcher, &same_origin).unwrap(), b"png");
+
+        let requests = pollster::block_on(other.received_requests()).unwrap();
+        assert!(requests.iter().all(|request| request
+            .headers
+            .keys()
+            .all(|name| name.as_str() != "private-token")));
+
+        // Redirects are followed like usual when there's nothing to leak
+        let fetcher = Fetcher {
+            retries: 0,
+            ..Fetcher::default()
+        };
+        assert_eq!(fetch(&fetcher, &url).unwrap(), b"png");
+    }
+
+    #[test]
+    fn gives_up_on_slow_servers() {
+        let server = pollster::block_on(MockServer::start());
+        let url = format!("{}/slow.png", server.uri());
+        pollster::block_on(
+            Mock::given(matchers::path("/slow.png"))
+                .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(10)))
+                .mount(&server),
+        );
+
+        let fetcher = Fetcher {
+            timeout: Duration::from_millis(100),
+            retries: 0,
+            ..Fetcher::default()
+        };
+        let start = Instant::now();
+        assert!(fetch(&fetcher, &url).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,708 @@
+//! Just enough of ICC color management to show images tagged with other color spaces as sRGB
+//!
+//! Covers RGB profiles made of tone curves and a matrix, like Display P3 and Adobe RGB, along with
+//! the lookup table profiles that CMYK JPEGs usually carry. Anything else is shown as is
+
+/// The D50 white point that ICC profiles connect through
+const D50: [f32; 3] = [0.9642, 1.0, 0.8249];
+
+/// From D50 XYZ to linear sRGB, with the white point adapted using Bradford
+const XYZ_D50_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+    [3.133_856, -1.616_867, -0.490_615],
+    [-0.978_768, 1.916_142, 0.033_454],
+    [0.071_945, -0.228_991, 1.405_243],
+];
+
+/// How close a profile has to be to sRGB to skip converting
+const SRGB_TOLERANCE: f32 = 0.01;
+
+/// How finely linear light gets quantized on its way to 8-bit sRGB
+const ENCODE_STEPS: usize = 4096;
+
+#[derive(Debug)]
+pub struct Profile {
+    transform: Transform,
+}
+
+#[derive(Debug)]
+enum Transform {
+    /// A tone curve for each channel followed by a matrix to linear sRGB
+    Matrix {
+        curves: [Curve; 3],
+        to_srgb: [[f32; 3]; 3],
+    },
+    Lut {
+        lut: Lut,
+        pcs: Pcs,
+    },
+}
+
+/// The color space that the profile's lookup table gives colors in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pcs {
+    Xyz,
+    /// Lab with the 8-bit encoding, or the legacy 16-bit one
+    Lab {
+        bits: u8,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Curve {
+    Gamma(f32),
+    Table(Vec<f32>),
+    /// The `para` curves, which are all a special case of
+    /// `y = (a * x + b) ^ g + e` when `x >= d` and `y = c * x + f` otherwise
+    Parametric {
+        g: f32,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    },
+}
+
+impl Curve {
+    fn eval(&self, x: f32) -> f32 {
+        let x = x.clamp(0., 1.);
+        match self {
+            Self::Gamma(gamma) => x.powf(*gamma),
+            Self::Table(table) => interpolate(table, x),
+            &Self::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if x >= d {
+                    (a * x + b).max(0.).powf(g) + e
+                } else {
+                    c * x + f
+                }
+            }
+        }
+    }
+}
+
+/// Samples `table` at `x` between 0 and 1, interpolating between entries
+fn interpolate(table: &[f32], x: f32) -> f32 {
+    match table {
+        [] => x,
+        [only] => *only,
+        _ => {
+            let pos = x.clamp(0., 1.) * (table.len() - 1) as f32;
+            let index = (pos as usize).min(table.len() - 2);
+            let frac = pos - index as f32;
+            table[index] + (table[index + 1] - table[index]) * frac
+        }
+    }
+}
+
+/// The `mft1` and `mft2` lookup tables of version 2 profiles: curves going in, a grid of colors
+/// that gets interpolated between, and curves coming out
+#[derive(Debug)]
+struct Lut {
+    inputs: Vec<Vec<f32>>,
+    grid_points: usize,
+    /// `grid_points ^ inputs.len()` colors, with the first input changing slowest
+    clut: Vec<[f32; 3]>,
+    outputs: [Vec<f32>; 3],
+}
+
+impl Lut {
+    fn eval(&self, input: &[f32]) -> [f32; 3] {
+        let channels = self.inputs.len();
+        let max = (self.grid_points - 1) as f32;
+        let mut base = 0;
+        let mut fracs = [0.; 4];
+        let mut strides = [0; 4];
+        let mut stride = 1;
+        for channel in (0..channels).rev() {
+            let pos = interpolate(&self.inputs[channel], input[channel]).clamp(0., 1.) * max;
+            let index = (pos as usize).min(self.grid_points.saturating_sub(2));
+            fracs[channel] = pos - index as f32;
+            base += index * stride;
+            strides[channel] = stride;
+            stride *= self.grid_points;
+        }
+
+        // Blends all of the grid points around the color, weighting each by how close it is
+        let mut color = [0.; 3];
+        for corner in 0..1usize << channels {
+            let mut weight = 1.;
+            let mut index = base;
+            for channel in 0..channels {
+                if corner & (1 << channel) == 0 {
+                    weight *= 1. - fracs[channel];
+                } else {
+                    weight *= fracs[channel];
+                    index += strides[channel];
+                }
+            }
+            if weight == 0. {
+                continue;
+            }
+            let Some(point) = self.clut.get(index) else {
+                continue;
+            };
+            for (out, value) in color.iter_mut().zip(point) {
+                *out += weight * value;
+            }
+        }
+
+        let mut output = [0.; 3];
+        for (out, (value, curve)) in output.iter_mut().zip(color.iter().zip(&self.outputs)) {
+            *out = interpolate(curve, *value);
+        }
+        output
+    }
+}
+
+impl Profile {
+    /// Reads the parts of the profile needed to convert to sRGB
+    ///
+    /// Gives `None` for profiles that can't be handled, and for ones that are already close enough
+    /// to sRGB that converting wouldn't change anything
+    pub fn parse(icc: &[u8]) -> Option<Self> {
+        let color_space = icc.get(16..20)?;
+        let pcs = match icc.get(20..24)? {
+            b"XYZ " => Pcs::Xyz,
+            b"Lab " => Pcs::Lab { bits: 16 },
+            _ => return None,
+        };
+        let tags = Tags::parse(icc)?;
+
+        let matrix = || {
+            let colorants = [b"rXYZ", b"gXYZ", b"bXYZ"].map(|sig| tags.xyz(sig));
+            let curves = [b"rTRC", b"gTRC", b"bTRC"].map(|sig| tags.curve(sig));
+            let [Some(r), Some(g), Some(b)] = colorants else {
+                return None;
+            };
+            let [Some(r_curve), Some(g_curve), Some(b_curve)] = curves else {
+                return None;
+            };
+            let to_xyz = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+            Some(Transform::Matrix {
+                curves: [r_curve, g_curve, b_curve],
+                to_srgb: multiply(&XYZ_D50_TO_LINEAR_SRGB, &to_xyz),
+            })
+        };
+        let lut = |channels| {
+            let (lut, bits) = tags.lut(b"A2B0", channels)?;
+            let pcs = match pcs {
+                Pcs::Lab { .. } => Pcs::Lab { bits },
+                Pcs::Xyz => Pcs::Xyz,
+            };
+            Some(Transform::Lut { lut, pcs })
+        };
+        let transform = match color_space {
+            b"RGB " => matrix().or_else(|| lut(3))?,
+            b"CMYK" => lut(4)?,
+            _ => return None,
+        };
+
+        let profile = Self { transform };
+        (!profile.is_srgb()).then_some(profile)
+    }
+
+    /// 3 for RGB profiles and 4 for CMYK ones
+    pub fn channels(&self) -> usize {
+        match &self.transform {
+            Transform::Matrix { .. } => 3,
+            Transform::Lut { lut, .. } => lut.inputs.len(),
+        }
+    }
+
+    fn is_srgb(&self) -> bool {
+        let Transform::Matrix { curves, to_srgb } = &self.transform else {
+            return false;
+        };
+        let identity = to_srgb.iter().enumerate().all(|(row, values)| {
+            values.iter().enumerate().all(|(col, value)| {
+                let expected = if row == col { 1. } else { 0. };
+                (value - expected).abs() < SRGB_TOLERANCE
+            })
+        });
+        let srgb_curves = (0..=16).all(|step| {
+            let x = step as f32 / 16.;
+            curves
+                .iter()
+                .all(|curve| (curve.eval(x) - srgb_to_linear(x)).abs() < SRGB_TOLERANCE)
+        });
+        identity && srgb_curves
+    }
+
+    /// Converts RGBA pixels from an RGB profile to sRGB in place, leaving alpha alone
+    pub fn rgba_to_srgb(&self, rgba: &mut [u8]) {
+        let encode = encode_table();
+        match &self.transform {
+            Transform::Matrix { curves, to_srgb } => {
+                let linear: Vec<[f32; 256]> = curves
+                    .iter()
+                    .map(|curve| std::array::from_fn(|value| curve.eval(value as f32 / 255.)))
+                    .collect();
+                for pixel in rgba.chunks_exact_mut(4) {
+                    let rgb = [
+                        linear[0][usize::from(pixel[0])],
+                        linear[1][usize::from(pixel[1])],
+                        linear[2][usize::from(pixel[2])],
+                    ];
+                    let srgb = apply(to_srgb, rgb);
+                    for (out, value) in pixel.iter_mut().zip(srgb) {
+                        *out = encode_linear(&encode, value);
+                    }
+                }
+            }
+            Transform::Lut { lut, pcs } => {
+                for pixel in rgba.chunks_exact_mut(4) {
+                    let input = [pixel[0], pixel[1], pixel[2]].map(|value| value as f32 / 255.);
+                    let srgb = pcs_to_linear_srgb(*pcs, lut.eval(&input));
+                    for (out, value) in pixel.iter_mut().zip(srgb) {
+                        *out = encode_linear(&encode, value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Converts CMYK pixels with 0 being no ink to opaque sRGB ones
+    pub fn cmyk_to_srgb(&self, cmyk: &[u8]) -> Option<Vec<u8>> {
+        let Transform::Lut { lut, pcs } = &self.transform else {
+            return None;
+        };
+        if lut.inputs.len() != 4 {
+            return None;
+        }
+
+        let encode = encode_table();
+        let mut rgba = Vec::with_capacity(cmyk.len());
+        // Neighboring pixels are often the same color
+        let mut last: Option<(&[u8], [u8; 3])> = None;
+        for pixel in cmyk.chunks_exact(4) {
+            let rgb = match last {
+                Some((last_pixel, rgb)) if last_pixel == pixel => rgb,
+                _ => {
+                    let input =
+                        [pixel[0], pixel[1], pixel[2], pixel[3]].map(|value| value as f32 / 255.);
+                    let srgb = pcs_to_linear_srgb(*pcs, lut.eval(&input));
+                    let rgb = srgb.map(|value| encode_linear(&encode, value));
+                    last = Some((pixel, rgb));
+                    rgb
+                }
+            };
+            rgba.extend_from_slice(&rgb);
+            rgba.push(u8::MAX);
+        }
+        Some(rgba)
+    }
+}
+
+fn pcs_to_linear_srgb(pcs: Pcs, value: [f32; 3]) -> [f32; 3] {
+    let xyz = match pcs {
+        // Encoded as `u1Fixed15`, where 1.0 is 0x8000
+        Pcs::Xyz => value.map(|v| v * 65_535. / 32_768.),
+        Pcs::Lab { bits } => {
+            // The legacy 16-bit encoding tops out at 0xff00 instead of 0xffff
+            let scale = if bits == 8 { 1. } else { 65_535. / 65_280. };
+            let l = value[0] * scale * 100.;
+            let a = value[1] * scale * 255. - 128.;
+            let b = value[2] * scale * 255. - 128.;
+            lab_to_xyz([l, a, b])
+        }
+    };
+    apply(&XYZ_D50_TO_LINEAR_SRGB, xyz)
+}
+
+fn lab_to_xyz([l, a, b]: [f32; 3]) -> [f32; 3] {
+    const EPSILON: f32 = 216. / 24_389.;
+    const KAPPA: f32 = 24_389. / 27.;
+    let fy = (l + 16.) / 116.;
+    let fx = fy + a / 500.;
+    let fz = fy - b / 200.;
+    let f_inv = |f: f32| {
+        let cubed = f * f * f;
+        if cubed > EPSILON {
+            cubed
+        } else {
+            (116. * f - 16.) / KAPPA
+        }
+    };
+    let y = if l > KAPPA * EPSILON {
+        fy * fy * fy
+    } else {
+        l / KAPPA
+    };
+    [f_inv(fx) * D50[0], y * D50[1], f_inv(fz) * D50[2]]
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn encode_table() -> Vec<u8> {
+    (0..ENCODE_STEPS)
+        .map(|step| {
+            let linear = step as f32 / (ENCODE_STEPS - 1) as f32;
+            (linear_to_srgb(linear) * 255.).round() as u8
+        })
+        .collect()
+}
+
+fn encode_linear(table: &[u8], value: f32) -> u8 {
+    let step = (value.clamp(0., 1.) * (ENCODE_STEPS - 1) as f32).round() as usize;
+    table[step]
+}
+
+fn multiply(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    std::array::from_fn(|row| {
+        std::array::from_fn(|col| (0..3).map(|k| a[row][k] * b[k][col]).sum())
+    })
+}
+
+fn apply(matrix: &[[f32; 3]; 3], value: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|row| (0..3).map(|col| matrix[row][col] * value[col]).sum())
+}
+
+/// The profile's tag table, pointing at where each tag's data is
+struct Tags<'icc> {
+    entries: Vec<([u8; 4], &'icc [u8])>,
+}
+
+impl<'icc> Tags<'icc> {
+    fn parse(icc: &'icc [u8]) -> Option<Self> {
+        let count = read_u32(icc, 128)? as usize;
+        let mut entries = Vec::new();
+        for index in 0..count {
+            let entry = 132 + index * 12;
+            let sig = icc.get(entry..entry + 4)?.try_into().ok()?;
+            let offset = read_u32(icc, entry + 4)? as usize;
+            let size = read_u32(icc, entry + 8)? as usize;
+            if let Some(data) = icc.get(offset..offset.checked_add(size)?) {
+                entries.push((sig, data));
+            }
+        }
+        Some(Self { entries })
+    }
+
+    fn get(&self, sig: &[u8; 4]) -> Option<&'icc [u8]> {
+        self.entries
+            .iter()
+            .find(|(entry_sig, _)| entry_sig == sig)
+            .map(|(_, data)| *data)
+    }
+
+    fn xyz(&self, sig: &[u8; 4]) -> Option<[f32; 3]> {
+        let data = self.get(sig)?;
+        if data.get(..4)? != b"XYZ " {
+            return None;
+        }
+        Some([
+            read_s15f16(data, 8)?,
+            read_s15f16(data, 12)?,
+            read_s15f16(data, 16)?,
+        ])
+    }
+
+    fn curve(&self, sig: &[u8; 4]) -> Option<Curve> {
+        let data = self.get(sig)?;
+        match data.get(..4)? {
+            b"curv" => {
+                let count = read_u32(data, 8)? as usize;
+                match count {
+                    0 => Some(Curve::Gamma(1.)),
+                    // Stored as `u8Fixed8`
+                    1 => Some(Curve::Gamma(f32::from(read_u16(data, 12)?) / 256.)),
+                    _ => {
+                        let table = (0..count)
+                            .map(|index| read_u16(data, 12 + index * 2).map(unorm16))
+                            .collect::<Option<_>>()?;
+                        Some(Curve::Table(table))
+                    }
+                }
+            }
+            b"para" => {
+                let function = read_u16(data, 8)?;
+                let param = |index: usize| read_s15f16(data, 12 + index * 4);
+                let g = param(0)?;
+                let curve = match function {
+                    0 => Curve::Gamma(g),
+                    1 => {
+                        let (a, b) = (param(1)?, param(2)?);
+                        let d = if a == 0. { 0. } else { -b / a };
+                        Curve::Parametric {
+                            g,
+                            a,
+                            b,
+                            c: 0.,
+                            d,
+                            e: 0.,
+                            f: 0.,
+                        }
+                    }
+                    2 => {
+                        let (a, b, c) = (param(1)?, param(2)?, param(3)?);
+                        let d = if a == 0. { 0. } else { -b / a };
+                        Curve::Parametric {
+                            g,
+                            a,
+                            b,
+                            c: 0.,
+                            d,
+                            e: c,
+                            f: c,
+                        }
+                    }
+                    3 => Curve::Parametric {
+                        g,
+                        a: param(1)?,
+                        b: param(2)?,
+                        c: param(3)?,
+                        d: param(4)?,
+                        e: 0.,
+                        f: 0.,
+                    },
+                    4 => Curve::Parametric {
+                        g,
+                        a: param(1)?,
+                        b: param(2)?,
+                        c: param(3)?,
+                        d: param(4)?,
+                        e: param(5)?,
+                        f: param(6)?,
+                    },
+                    _ => return None,
+                };
+                Some(curve)
+            }
+            _ => None,
+        }
+    }
+
+    /// The lookup table along with how many bits its values have
+    fn lut(&self, sig: &[u8; 4], channels: usize) -> Option<(Lut, u8)> {
+        let data = self.get(sig)?;
+        let bits = match data.get(..4)? {
+            b"mft1" => 8,
+            b"mft2" => 16,
+            _ => return None,
+        };
+        let inputs = usize::from(*data.get(8)?);
+        let outputs = usize::from(*data.get(9)?);
+        let grid_points = usize::from(*data.get(10)?);
+        if inputs != channels || outputs != 3 || grid_points < 2 {
+            return None;
+        }
+
+        let (input_entries, output_entries, mut offset) = match bits {
+            8 => (256, 256, 48),
+            _ => (
+                usize::from(read_u16(data, 48)?),
+                usize::from(read_u16(data, 50)?),
+                52,
+            ),
+        };
+        let mut read_values = |count: usize| -> Option<Vec<f32>> {
+            let values = (0..count)
+                .map(|index| match bits {
+                    8 => data
+                        .get(offset + index)
+                        .map(|&value| f32::from(value) / 255.),
+                    _ => read_u16(data, offset + index * 2).map(unorm16),
+                })
+                .collect();
+            offset += count * usize::from(bits / 8);
+            values
+        };
+
+        let input_curves = (0..inputs)
+            .map(|_| read_values(input_entries))
+            .collect::<Option<Vec<_>>>()?;
+        let points = grid_points.checked_pow(inputs as u32)?;
+        let clut = read_values(points * 3)?
+            .chunks_exact(3)
+            .map(|color| [color[0], color[1], color[2]])
+            .collect();
+        let output_curves = [
+            read_values(output_entries)?,
+            read_values(output_entries)?,
+            read_values(output_entries)?,
+        ];
+        let lut = Lut {
+            inputs: input_curves,
+            grid_points,
+            clut,
+            outputs: output_curves,
+        };
+        Some((lut, bits))
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_s15f16(data: &[u8], offset: usize) -> Option<f32> {
+    let raw = i32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?);
+    Some(raw as f32 / 65_536.)
+}
+
+fn unorm16(value: u16) -> f32 {
+    f32::from(value) / 65_535.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The D50-adapted colorants of sRGB
+    const SRGB_COLORANTS: [[f32; 3]; 3] = [
+        [0.436_07, 0.222_49, 0.013_93],
+        [0.385_15, 0.716_87, 0.097_10],
+        [0.143_07, 0.060_61, 0.714_19],
+    ];
+
+    fn s15f16(value: f32) -> [u8; 4] {
+        ((value * 65_536.).round() as i32).to_be_bytes()
+    }
+
+    fn profile(color_space: &[u8; 4], pcs: &[u8; 4], tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut header = vec![0; 128];
+        header[16..20].copy_from_slice(color_space);
+        header[20..24].copy_from_slice(pcs);
+        header.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+
+        let mut offset = header.len() + tags.len() * 12;
+        let mut data = Vec::new();
+        for &(sig, ref tag) in tags {
+            header.extend_from_slice(sig);
+            header.extend_from_slice(&(offset as u32).to_be_bytes());
+            header.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+            data.extend_from_slice(tag);
+        }
+        header.extend(data);
+        header
+    }
+
+    fn xyz(value: [f32; 3]) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for component in value {
+            tag.extend_from_slice(&s15f16(component));
+        }
+        tag
+    }
+
+    /// The `para` curve that sRGB uses
+    fn srgb_curve() -> Vec<u8> {
+        let mut tag = b"para\0\0\0\0\0\x03\0\0".to_vec();
+        for param in [2.4, 1. / 1.055, 0.055 / 1.055, 1. / 12.92, 0.040_45] {
+            tag.extend_from_slice(&s15f16(param));
+        }
+        tag
+    }
+
+    fn gamma_curve(gamma: f32) -> Vec<u8> {
+        let mut tag = b"curv\0\0\0\0\0\0\0\x01".to_vec();
+        tag.extend_from_slice(&((gamma * 256.) as u16).to_be_bytes());
+        tag
+    }
+
+    fn matrix_profile(curve: Vec<u8>) -> Vec<u8> {
+        let [r, g, b] = SRGB_COLORANTS;
+        profile(
+            b"RGB ",
+            b"XYZ ",
+            &[
+                (b"rXYZ", xyz(r)),
+                (b"gXYZ", xyz(g)),
+                (b"bXYZ", xyz(b)),
+                (b"rTRC", curve.clone()),
+                (b"gTRC", curve.clone()),
+                (b"bTRC", curve),
+            ],
+        )
+    }
+
+    #[test]
+    fn srgb_is_left_alone() {
+        assert!(Profile::parse(&matrix_profile(srgb_curve())).is_none());
+        assert!(Profile::parse(b"not a profile").is_none());
+    }
+
+    #[test]
+    fn linear_rgb() {
+        let profile = Profile::parse(&matrix_profile(gamma_curve(1.))).unwrap();
+        assert_eq!(profile.channels(), 3);
+
+        let mut pixels = [0, 0x80, 0xff, 0x40];
+        profile.rgba_to_srgb(&mut pixels);
+        let [r, g, b, a] = pixels;
+        assert!(r <= 1, "{pixels:?}");
+        assert!(g.abs_diff(188) <= 1, "{pixels:?}");
+        assert!(b >= 254, "{pixels:?}");
+        assert_eq!(a, 0x40);
+    }
+
+    #[test]
+    fn cmyk_lut() {
+        // The smallest possible grid where black ink takes the color from white to black, and the
+        // other inks do nothing
+        let mut tag = b"mft2\0\0\0\0\x04\x03\x02\0".to_vec();
+        for value in [1., 0., 0., 0., 1., 0., 0., 0., 1.] {
+            tag.extend_from_slice(&s15f16(value));
+        }
+        tag.extend_from_slice(&2u16.to_be_bytes());
+        tag.extend_from_slice(&2u16.to_be_bytes());
+        for _ in 0..4 {
+            tag.extend_from_slice(&[0, 0, 0xff, 0xff]);
+        }
+        // 16-bit Lab for white and black, which have neutral a/b
+        let white = [0xff00u16, 0x8000, 0x8000];
+        let black = [0, 0x8000, 0x8000];
+        for point in 0..16 {
+            let color = if point % 2 == 0 { white } else { black };
+            for value in color {
+                tag.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        for _ in 0..3 {
+            tag.extend_from_slice(&[0, 0, 0xff, 0xff]);
+        }
+        let profile = Profile::parse(&profile(b"CMYK", b"Lab ", &[(b"A2B0", tag)])).unwrap();
+        assert_eq!(profile.channels(), 4);
+
+        let rgba = profile
+            .cmyk_to_srgb(&[0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0])
+            .unwrap();
+        let [white, black, inked] = [&rgba[..4], &rgba[4..8], &rgba[8..]];
+        assert!(white.iter().all(|&value| value >= 254), "{white:?}");
+        assert!(black[..3].iter().all(|&value| value <= 1), "{black:?}");
+        assert_eq!(black[3], u8::MAX);
+        assert_eq!(inked, white);
+    }
+}
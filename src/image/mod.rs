@@ -1,20 +1,34 @@
+pub mod atlas;
+pub mod cache;
 mod decode;
+pub mod fetch;
+mod icc;
+mod netrc;
+pub mod pool;
 #[cfg(test)]
 mod tests;
+pub mod textures;
 
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use std::{fs, io};
 
 use crate::debug_impls::{DebugBytesPrefix, DebugInline};
+use crate::image::atlas::{AtlasSlot, ImageAtlas};
+use crate::image::decode::OptionalFormat;
+use crate::image::fetch::Fetcher;
+use crate::image::pool::Priority;
 use crate::interpreter::ImageCallback;
 use crate::positioner::DEFAULT_MARGIN;
-use crate::utils::{self, usize_in_mib, Align, Point, Size};
+use crate::text::{Text, TextBox, TextSystem};
+use crate::utils::{usize_in_mib, Align, Float, Point, Rect, Size};
 
 use anyhow::Context;
+use base64::prelude::*;
 use bytemuck::{Pod, Zeroable};
 use image::{ImageBuffer, RgbaImage};
 use smart_debug::SmartDebug;
@@ -40,10 +54,44 @@ impl From<u32> for Px {
     }
 }
 
+impl From<Px> for f32 {
+    fn from(px: Px) -> Self {
+        px.0 as f32
+    }
+}
+
+/// A share of the width that the document's content gets, like `50%`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percent(f32);
+
+impl FromStr for Percent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let percent: f32 = s
+            .strip_suffix('%')
+            .context("Missing a `%` suffix")?
+            .trim()
+            .parse()?;
+        anyhow::ensure!(
+            percent.is_finite() && percent >= 0.,
+            "Invalid percentage {percent}"
+        );
+        Ok(Self(percent))
+    }
+}
+
+impl Percent {
+    pub fn of(self, width: f32) -> f32 {
+        width * self.0 / 100.
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum ImageSize {
     PxWidth(Px),
     PxHeight(Px),
+    PercentWidth(Percent),
 }
 
 impl ImageSize {
@@ -54,6 +102,150 @@ impl ImageSize {
     pub fn height<P: Into<Px>>(px: P) -> Self {
         Self::PxHeight(px.into())
     }
+
+    pub fn percent_width(percent: Percent) -> Self {
+        Self::PercentWidth(percent)
+    }
+}
+
+/// Caps how wide an image gets, like CSS's `max-width`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaxWidth {
+    Px(Px),
+    Percent(Percent),
+}
+
+impl FromStr for MaxWidth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.ends_with('%') {
+            s.parse().map(Self::Percent)
+        } else {
+            s.parse().map(Self::Px)
+        }
+    }
+}
+
+/// Quarter turns and mirroring, from CSS like `transform: rotate(90deg) scaleX(-1)`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImageTransform {
+    /// Clockwise, applied after mirroring
+    quarter_turns: u8,
+    /// Flipped left to right
+    mirrored: bool,
+}
+
+impl ImageTransform {
+    fn rotate(&mut self, quarter_turns: i32) {
+        // Rotating a mirrored image one way looks like rotating the original the other way
+        let quarter_turns = if self.mirrored {
+            -quarter_turns
+        } else {
+            quarter_turns
+        };
+        self.quarter_turns = (i32::from(self.quarter_turns) + quarter_turns).rem_euclid(4) as u8;
+    }
+
+    fn mirror(&mut self) {
+        self.mirrored = !self.mirrored;
+    }
+
+    fn flip_vertically(&mut self) {
+        self.rotate(2);
+        self.mirror();
+    }
+
+    /// Whether the image ends up on its side, trading its width for its height
+    pub fn swaps_sides(self) -> bool {
+        self.quarter_turns % 2 == 1
+    }
+
+    /// Which of the texture's corners shows up at each corner of the quad, going top left,
+    /// bottom left, bottom right, then top right
+    fn corners(self) -> [usize; 4] {
+        let turns = usize::from(self.quarter_turns);
+        std::array::from_fn(|corner| {
+            if self.mirrored {
+                (3 + 4 * 2 - corner - turns) % 4
+            } else {
+                (corner + turns) % 4
+            }
+        })
+    }
+
+    /// Where `shown`, a part of the image as it looks on screen, lies in the texture. Both go from
+    /// 0 to 1 across the image
+    pub fn texture_rect(self, shown: &Rect) -> Rect {
+        const UNIT: [Point; 4] = [(0., 0.), (0., 1.), (1., 1.), (1., 0.)];
+        let [top_left, bottom_left, _, top_right] = self.corners().map(|corner| UNIT[corner]);
+        let to_texture = |(x, y): Point| {
+            (
+                top_left.0 + x * (top_right.0 - top_left.0) + y * (bottom_left.0 - top_left.0),
+                top_left.1 + x * (top_right.1 - top_left.1) + y * (bottom_left.1 - top_left.1),
+            )
+        };
+        let (a, b) = (to_texture(shown.pos), to_texture(shown.max()));
+        Rect::from_min_max((a.0.min(b.0), a.1.min(b.1)), (a.0.max(b.0), a.1.max(b.1)))
+    }
+
+    /// Turns the pixels themselves, for when they're shown by something besides a textured quad
+    pub fn apply(self, image: RgbaImage) -> RgbaImage {
+        let image = if self.mirrored {
+            image::imageops::flip_horizontal(&image)
+        } else {
+            image
+        };
+        match self.quarter_turns {
+            1 => image::imageops::rotate90(&image),
+            2 => image::imageops::rotate180(&image),
+            3 => image::imageops::rotate270(&image),
+            _ => image,
+        }
+    }
+}
+
+impl FromStr for ImageTransform {
+    type Err = anyhow::Error;
+
+    /// Only the parts that keep the image rectangular are understood, and anything else is skipped
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut transform = Self::default();
+        let mut rest = s.trim();
+        while let Some((function, after)) = rest.split_once('(') {
+            let (args, after) = after
+                .split_once(')')
+                .context("Unclosed transform function")?;
+            rest = after.trim_start();
+            let args: Vec<_> = args.split(',').map(str::trim).collect();
+            match (function.trim(), args.as_slice()) {
+                ("rotate" | "rotateZ", [angle]) => {
+                    if let Some(quarter_turns) = quarter_turns(angle) {
+                        transform.rotate(quarter_turns);
+                    }
+                }
+                ("scaleX", ["-1"]) | ("scale", ["-1", "1"]) => transform.mirror(),
+                ("scaleY", ["-1"]) | ("scale", ["1", "-1"]) => transform.flip_vertically(),
+                ("scale", ["-1"] | ["-1", "-1"]) => transform.rotate(2),
+                _ => {}
+            }
+        }
+        Ok(transform)
+    }
+}
+
+/// An angle like `90deg` or `-0.25turn` in quarter turns, if it's a whole number of them
+fn quarter_turns(angle: &str) -> Option<i32> {
+    let (value, per_quarter) = if let Some(degrees) = angle.strip_suffix("deg") {
+        (degrees, 90.)
+    } else if let Some(turns) = angle.strip_suffix("turn") {
+        (turns, 0.25)
+    } else {
+        return (angle == "0").then_some(0);
+    };
+    let quarters = value.trim().parse::<f32>().ok()? / per_quarter;
+    ((quarters - quarters.round()).abs() < 1e-3).then(|| quarters.round() as i32)
 }
 
 #[derive(SmartDebug, Default, Clone)]
@@ -63,24 +255,159 @@ pub struct ImageData {
     scale: bool,
     #[debug(wrapper = DebugInline)]
     dimensions: (u32, u32),
+    #[debug(skip_fn = Option::is_none)]
+    animation: Option<Arc<Animation>>,
+    #[debug(skip_fn = Option::is_none)]
+    svg: Option<SvgSource>,
+    /// The image just as it was read or fetched, so that it can be saved without any losses
+    #[debug(skip)]
+    encoded: Option<Arc<Vec<u8>>>,
+    /// Whether any of the pixels are see-through, so that they need a backdrop to stay readable
+    #[debug(skip)]
+    has_alpha: bool,
+}
+
+/// Kept around for SVGs so that they can be rasterized again at the current zoom
+#[derive(SmartDebug, Clone)]
+struct SvgSource {
+    #[debug(skip)]
+    bytes: Arc<Vec<u8>>,
+    /// The zoom that the current pixels were rasterized for
+    zoom: f32,
+    /// The zoom that they actually have after applying the size limit
+    rasterized_zoom: f32,
+}
+
+const PLACEHOLDER_FONT_SIZE: f32 = 14.;
+const PLACEHOLDER_PADDING: f32 = 6.;
+
+/// Anything bigger gets rasterized at a lower resolution instead of making an absurd texture
+const MAX_SVG_SIDE: f32 = 8192.;
+
+/// Textures are uploaded at up to this much bigger than they're shown, so that zooming in a little
+/// doesn't mean uploading them all over again
+const UPLOAD_HEADROOM: f32 = 1.25;
+
+/// Loading system fonts is slow, so all SVGs share the same database
+fn svg_fonts() -> &'static usvg::fontdb::Database {
+    static FONTS: OnceLock<usvg::fontdb::Database> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let mut fontdb = usvg::fontdb::Database::new();
+        fontdb.load_system_fonts();
+        fontdb
+    })
 }
 
 impl ImageData {
     fn load(bytes: &[u8], scale: bool) -> anyhow::Result<Self> {
-        let (lz4_blob, dimensions) = decode::decode_and_compress(bytes)?;
+        let frames = decode::decode_animation(bytes).unwrap_or_else(|err| {
+            tracing::warn!("Failed decoding animation frames, showing a still image: {err}");
+            None
+        });
+        let has_alpha = frames.as_ref().map_or(false, |frames| {
+            frames
+                .iter()
+                .any(|(frame, _)| decode::has_alpha(frame.as_raw()))
+        });
+        if let Some(animation) = frames.and_then(Animation::new) {
+            let first = &animation.frames[0];
+            return Ok(Self {
+                lz4_blob: first.lz4_blob.clone(),
+                scale,
+                dimensions: animation.dimensions,
+                animation: Some(Arc::new(animation)),
+                svg: None,
+                encoded: Some(Arc::new(bytes.to_vec())),
+                has_alpha,
+            });
+        }
+
+        let decode::ImageParts {
+            lz4_blob,
+            dimensions,
+            has_alpha,
+        } = decode::decode_and_compress(bytes)?;
         Ok(Self {
             lz4_blob,
             scale,
             dimensions,
+            animation: None,
+            svg: None,
+            encoded: Some(Arc::new(bytes.to_vec())),
+            has_alpha,
         })
     }
 
+    /// Rasterizes an SVG to cover as many pixels as it would take up on screen at `zoom`
+    fn load_svg(bytes: Arc<Vec<u8>>, hidpi_scale: f32, zoom: f32) -> anyhow::Result<Self> {
+        let mut tree = usvg::Tree::from_data(&bytes, &usvg::Options::default())?;
+        let (width, height) = (tree.size.width(), tree.size.height());
+        let mut scale = hidpi_scale * zoom;
+        if width.max(height) * scale > MAX_SVG_SIDE {
+            scale = MAX_SVG_SIDE / width.max(height);
+        }
+        tree.size = tree.size.scale_to(
+            tiny_skia::Size::from_wh(width * scale, height * scale)
+                .context("Svg has invalid dimensions")?,
+        );
+        tree.convert_text(svg_fonts());
+        let rtree = resvg::Tree::from_usvg(&tree);
+        let mut pixmap =
+            tiny_skia::Pixmap::new(rtree.size.width() as u32, rtree.size.height() as u32)
+                .context("Couldn't create svg pixmap")?;
+        rtree.render(tiny_skia::Transform::default(), &mut pixmap.as_mut());
+        let image = ImageBuffer::from_raw(pixmap.width(), pixmap.height(), pixmap.take())
+            .context("Svg buffer has invalid dimensions")?;
+
+        let mut image_data = Self::new(image, false);
+        image_data.encoded = Some(Arc::clone(&bytes));
+        image_data.svg = Some(SvgSource {
+            bytes,
+            zoom,
+            rasterized_zoom: scale / hidpi_scale,
+        });
+        Ok(image_data)
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         decode::lz4_decompress(&self.lz4_blob, self.rgba_image_byte_size())
             .expect("Size matches and I/O is in memory")
     }
 
-    fn new(image: RgbaImage, scale: bool) -> Self {
+    /// The shown pixels. Animations just give whichever frame shows first
+    pub fn to_image(&self) -> anyhow::Result<RgbaImage> {
+        let (width, height) = self.dimensions;
+        RgbaImage::from_raw(width, height, self.to_bytes())
+            .context("Image buffer has invalid dimensions")
+    }
+
+    /// The shown pixels encoded as a PNG
+    pub fn to_png(&self) -> anyhow::Result<Vec<u8>> {
+        let image = self.to_image()?;
+        let mut png = Vec::new();
+        image.write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        Ok(png)
+    }
+
+    /// The original bytes along with the file extension that suits them, falling back to
+    /// [`ImageData::to_png()`] when they're not around
+    pub fn to_file(&self) -> anyhow::Result<(Cow<'_, [u8]>, &'static str)> {
+        let Some(encoded) = &self.encoded else {
+            return Ok((Cow::Owned(self.to_png()?), "png"));
+        };
+        let extension = if self.svg.is_some() {
+            "svg"
+        } else {
+            image::guess_format(encoded)
+                .ok()
+                .and_then(|format| format.extensions_str().first().copied())
+                .or_else(|| OptionalFormat::sniff(encoded).map(OptionalFormat::extension))
+                .unwrap_or("png")
+        };
+        Ok((Cow::Borrowed(encoded.as_slice()), extension))
+    }
+
+    pub fn new(image: RgbaImage, scale: bool) -> Self {
         let dimensions = image.dimensions();
 
         let start = Instant::now();
@@ -97,6 +424,10 @@ impl ImageData {
             dimensions,
             lz4_blob,
             scale,
+            animation: None,
+            svg: None,
+            encoded: None,
+            has_alpha: decode::has_alpha(image.as_raw()),
         }
     }
 
@@ -106,6 +437,78 @@ impl ImageData {
     }
 }
 
+/// The frames of an animated image, compressed the same way as still images
+#[derive(SmartDebug)]
+pub struct Animation {
+    #[debug(wrapper = DebugInline)]
+    dimensions: (u32, u32),
+    #[debug(skip)]
+    frames: Vec<AnimationFrame>,
+    /// How long it takes to play through every frame once
+    loop_duration: Duration,
+}
+
+struct AnimationFrame {
+    lz4_blob: Vec<u8>,
+    delay: Duration,
+}
+
+impl Animation {
+    /// Returns `None` unless there are multiple frames that all share the same dimensions
+    fn new(frames: Vec<(RgbaImage, Duration)>) -> Option<Self> {
+        let dimensions = frames.first()?.0.dimensions();
+        if frames.len() < 2 || frames.iter().any(|(img, _)| img.dimensions() != dimensions) {
+            return None;
+        }
+
+        let start = Instant::now();
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(|(image, delay)| AnimationFrame {
+                lz4_blob: decode::lz4_compress(&mut io::Cursor::new(image.as_raw()))
+                    .expect("I/O is in memory"),
+                delay,
+            })
+            .collect();
+        tracing::debug!(
+            "Compressing {} animation frames:\n- Compressed {:.2} MiB\n- Time {:.2?}",
+            frames.len(),
+            usize_in_mib(frames.iter().map(|frame| frame.lz4_blob.len()).sum()),
+            start.elapsed(),
+        );
+
+        let loop_duration = frames.iter().map(|frame| frame.delay).sum();
+        Some(Self {
+            dimensions,
+            frames,
+            loop_duration,
+        })
+    }
+
+    /// The frame that's showing `elapsed` into the (endlessly looping) animation along with how
+    /// long until the next one
+    pub fn frame_at(&self, elapsed: Duration) -> (usize, Duration) {
+        let mut offset = if self.loop_duration.is_zero() {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((elapsed.as_nanos() % self.loop_duration.as_nanos()) as u64)
+        };
+        for (index, frame) in self.frames.iter().enumerate() {
+            match offset.checked_sub(frame.delay) {
+                Some(rest) => offset = rest,
+                None => return (index, frame.delay - offset),
+            }
+        }
+        (0, self.frames[0].delay)
+    }
+
+    fn frame_bytes(&self, index: usize) -> Vec<u8> {
+        let (x, y) = self.dimensions;
+        decode::lz4_decompress(&self.frames[index].lz4_blob, x as usize * y as usize * 4)
+            .expect("Size matches and I/O is in memory")
+    }
+}
+
 #[derive(SmartDebug, Default)]
 pub struct Image {
     #[debug(skip_fn = debug_ignore_image_data)]
@@ -113,15 +516,63 @@ pub struct Image {
     #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
     pub is_aligned: Option<Align>,
     #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
+    pub float: Option<Float>,
+    #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
     pub size: Option<ImageSize>,
+    #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
+    pub max_width: Option<MaxWidth>,
+    #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
+    pub transform: Option<ImageTransform>,
     #[debug(skip)]
     pub bind_group: Option<Arc<wgpu::BindGroup>>,
+    /// Kept around so that animations can upload new frames to it
+    #[debug(skip)]
+    pub texture: Option<Arc<wgpu::Texture>>,
+    #[debug(skip)]
+    shown_frame: usize,
+    /// Small images get drawn from a shared atlas instead of their own texture
+    #[debug(skip)]
+    pub atlas_slot: Option<AtlasSlot>,
+    /// The frame this was last drawn in, for dropping textures that haven't been seen in a while
+    #[debug(skip)]
+    pub last_drawn: u64,
     #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
     pub is_link: Option<String>,
+    #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
+    pub is_anchor: Option<String>,
+    /// Shown in a placeholder box until the image loads, or instead of it if it fails to
+    #[debug(skip_fn = Option::is_none, wrapper = DebugInline)]
+    pub alt: Option<String>,
+    /// Set from the loading thread when nothing could be read or fetched
+    #[debug(skip)]
+    pub load_failed: Arc<AtomicBool>,
+    /// Set along with `load_failed` when the image is in a format that can't be decoded
+    #[debug(skip)]
+    unsupported_format: Arc<OnceLock<OptionalFormat>>,
+    /// Remote images wait here until they get close to being seen
+    #[debug(skip)]
+    pending_load: Option<PendingLoad>,
+    /// Where the image came from, like a path or URL
+    #[debug(skip)]
+    pub src: Option<String>,
+    /// Where the image would be on disk, so that it can be loaded again when it changes
+    #[debug(skip)]
+    pub path: Option<PathBuf>,
+    /// The one-indexed line of the markdown source that the image comes from
+    #[debug(skip)]
+    pub source_line: Option<usize>,
     #[debug(skip)]
     pub hidpi_scale: f32,
 }
 
+/// Everything needed to start loading an image that's been put off
+struct PendingLoad {
+    src: String,
+    file_path: PathBuf,
+    fetcher: Fetcher,
+    image_callback: Box<dyn ImageCallback + Send>,
+}
+
 fn debug_ignore_image_data(mutex: &Mutex<Option<ImageData>>) -> bool {
     match mutex.lock() {
         Ok(data) => data.is_none(),
@@ -136,23 +587,58 @@ impl Image {
         queue: &wgpu::Queue,
         sampler: &wgpu::Sampler,
         bindgroup_layout: &wgpu::BindGroupLayout,
+        display_size: Size,
     ) -> Option<Arc<BindGroup>> {
-        let dimensions = self.buffer_dimensions()?;
-        if dimensions.0 == 0 || dimensions.1 == 0 {
+        let buffer_dimensions = self.buffer_dimensions()?;
+        let display_size = self.texture_side_up(display_size);
+        if buffer_dimensions.0 == 0 || buffer_dimensions.1 == 0 {
             tracing::warn!("Invalid buffer dimensions");
             return None;
         }
 
         let start = Instant::now();
-        let rgba_image = self
+        let (rgba_image, is_animated) = self
             .image_data
             .lock()
             .unwrap()
             .as_ref()
-            .map(|image| image.to_bytes())?;
+            .map(|image| (image.to_bytes(), image.animation.is_some()))?;
 
         tracing::debug!("Decompressing image: Time {:.2?}", start.elapsed());
 
+        // Every frame of an animation gets written to the full size first level only, so those
+        // are left alone unless they're too big for the GPU. Then they're stuck on their first
+        // frame
+        let max_side = device.limits().max_texture_dimension_2d;
+        let fits = buffer_dimensions.0 <= max_side && buffer_dimensions.1 <= max_side;
+        let (dimensions, mip_levels) = if is_animated && fits {
+            (buffer_dimensions, vec![rgba_image])
+        } else {
+            let mut image =
+                RgbaImage::from_raw(buffer_dimensions.0, buffer_dimensions.1, rgba_image)?;
+            let dimensions = upload_dimensions(buffer_dimensions, display_size, max_side);
+            if dimensions != buffer_dimensions {
+                let start = Instant::now();
+                image = image::imageops::thumbnail(&image, dimensions.0, dimensions.1);
+                tracing::debug!(
+                    "Downscaling image from {:?} to {:?}: Time {:.2?}",
+                    buffer_dimensions,
+                    dimensions,
+                    start.elapsed()
+                );
+            }
+
+            let start = Instant::now();
+            let mip_levels = mip_chain(image);
+            tracing::debug!(
+                "Generating {} mip levels: Time {:.2?}",
+                mip_levels.len(),
+                start.elapsed()
+            );
+            let mip_levels = mip_levels.into_iter().map(RgbaImage::into_raw).collect();
+            (dimensions, mip_levels)
+        };
+
         let texture_size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
@@ -160,7 +646,7 @@ impl Image {
         };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             size: texture_size,
-            mip_level_count: 1,
+            mip_level_count: mip_levels.len() as u32,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -168,24 +654,14 @@ impl Image {
             label: Some("Image Texture"),
             view_formats: &[],
         });
-        queue.write_texture(
-            // Tells wgpu where to copy the pixel data
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            // The actual pixel data
-            &rgba_image,
-            // The layout of the texture
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            texture_size,
-        );
+        for (level, rgba) in mip_levels.iter().enumerate() {
+            let level = level as u32;
+            let level_dimensions = (
+                (dimensions.0 >> level).max(1),
+                (dimensions.1 >> level).max(1),
+            );
+            write_texture(queue, &texture, level, (0, 0), rgba, level_dimensions);
+        }
 
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -204,92 +680,300 @@ impl Image {
         });
         let bind_group = Arc::new(bind_group);
         self.bind_group = Some(bind_group.clone());
+        self.texture = Some(Arc::new(texture));
+        self.shown_frame = 0;
         Some(bind_group)
     }
 
+    /// Packs still images that are small enough into `atlas`, giving where they ended up
+    ///
+    /// Gives `None` for everything else, and for images that didn't fit since they get their own
+    /// texture instead
+    pub fn atlas_slot(&mut self, atlas: &mut ImageAtlas, queue: &wgpu::Queue) -> Option<AtlasSlot> {
+        if let Some(slot) = self.atlas_slot.as_ref().filter(|slot| atlas.contains(slot)) {
+            return Some(slot.clone());
+        }
+        if self.bind_group.is_some() {
+            return None;
+        }
+
+        let image_data = self.image_data.lock().unwrap();
+        let image = image_data.as_ref()?;
+        let (width, height) = image.dimensions;
+        let fits =
+            (1..=atlas::MAX_SIDE).contains(&width) && (1..=atlas::MAX_SIDE).contains(&height);
+        if !fits || image.animation.is_some() {
+            return None;
+        }
+        let rgba_image = RgbaImage::from_raw(width, height, image.to_bytes())?;
+        drop(image_data);
+
+        self.atlas_slot = atlas.insert(queue, &rgba_image);
+        self.atlas_slot.clone()
+    }
+
+    /// Uploads whichever frame should be showing `elapsed` into the animation, returning how long
+    /// until the next one is due
+    ///
+    /// Still images and images that haven't been uploaded yet give `None`
+    pub fn animate(&mut self, queue: &wgpu::Queue, elapsed: Duration) -> Option<Duration> {
+        let texture = self.texture.as_ref()?;
+        let image_data = self.image_data.lock().unwrap();
+        let animation = image_data.as_ref()?.animation.as_ref()?;
+        if (texture.width(), texture.height()) != animation.dimensions {
+            return None;
+        }
+        let (index, until_next) = animation.frame_at(elapsed);
+        if index != self.shown_frame {
+            write_texture(
+                queue,
+                texture,
+                0,
+                (0, 0),
+                &animation.frame_bytes(index),
+                animation.dimensions,
+            );
+            self.shown_frame = index;
+        }
+        Some(until_next)
+    }
+
+    /// Rasterizes SVGs again when the zoom has changed since they were last rasterized, so that
+    /// they stay crisp instead of getting stretched
+    pub fn refresh_svg(&mut self, zoom: f32) {
+        let mut image_data = self.image_data.lock().unwrap();
+        let Some(svg) = image_data.as_ref().and_then(|data| data.svg.as_ref()) else {
+            return;
+        };
+        if svg.zoom == zoom {
+            return;
+        }
+
+        let start = Instant::now();
+        match ImageData::load_svg(Arc::clone(&svg.bytes), self.hidpi_scale, zoom) {
+            Ok(rasterized) => {
+                tracing::debug!("Rasterizing SVG at {zoom}x: Time {:.2?}", start.elapsed());
+                *image_data = Some(rasterized);
+                drop(image_data);
+                self.forget_texture();
+            }
+            Err(err) => {
+                tracing::warn!("Failed rasterizing SVG again: {err}");
+                // Keep the stretched version instead of trying again every frame
+                if let Some(svg) = image_data.as_mut().and_then(|data| data.svg.as_mut()) {
+                    svg.zoom = zoom;
+                }
+            }
+        }
+    }
+
+    /// Drops a scaled down texture once it's shown bigger than it was uploaded at, so that it gets
+    /// uploaded again at a resolution that stays sharp
+    pub fn refresh_resolution(&mut self, display_size: Size, max_side: u32) {
+        let (Some(texture), Some(buffer_dimensions)) = (&self.texture, self.buffer_dimensions())
+        else {
+            return;
+        };
+        let display_size = self.texture_side_up(display_size);
+        let uploaded = (texture.width(), texture.height());
+        let sharper = upload_dimensions(buffer_dimensions, display_size, max_side);
+        if display_size.0 > uploaded.0 as f32 && sharper.0 > uploaded.0 {
+            self.forget_texture();
+        }
+    }
+
+    /// Turns the size it's shown at back around to match the texture
+    fn texture_side_up(&self, (width, height): Size) -> Size {
+        if self.transform.unwrap_or_default().swaps_sides() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// How much memory the texture takes up on the GPU, if it's been uploaded
+    pub fn texture_bytes(&self) -> u64 {
+        self.texture.as_ref().map_or(0, |texture| {
+            (0..texture.mip_level_count())
+                .map(|level| {
+                    let width = (texture.width() >> level).max(1);
+                    let height = (texture.height() >> level).max(1);
+                    u64::from(width) * u64::from(height) * 4
+                })
+                .sum()
+        })
+    }
+
+    /// Drops everything on the GPU, so that it gets uploaded again next time it's drawn
+    pub fn forget_texture(&mut self) {
+        self.bind_group = None;
+        self.texture = None;
+        self.atlas_slot = None;
+    }
+
     pub fn from_src(
         src: String,
         file_path: PathBuf,
         hidpi_scale: f32,
+        fetcher: Fetcher,
         image_callback: Box<dyn ImageCallback + Send>,
-    ) -> anyhow::Result<Image> {
-        let image_data = Arc::new(Mutex::new(None));
-        let image_data_clone = image_data.clone();
-
-        std::thread::spawn(move || {
-            let mut src_path = PathBuf::from(&src);
-            if src_path.is_relative() {
-                if let Some(parent_dir) = file_path.parent() {
-                    src_path = parent_dir.join(src_path.strip_prefix("./").unwrap_or(&src_path));
-                }
+    ) -> Image {
+        let mut image = Self::deferred(src, file_path, hidpi_scale, fetcher, image_callback);
+        image.start_loading(Priority::Normal);
+        image
+    }
+
+    /// Like [`Image::from_src`], except that nothing gets read or fetched until
+    /// [`Image::start_loading`] gets called
+    pub fn deferred(
+        src: String,
+        file_path: PathBuf,
+        hidpi_scale: f32,
+        fetcher: Fetcher,
+        image_callback: Box<dyn ImageCallback + Send>,
+    ) -> Image {
+        let is_local = !["http://", "https://", "data:"]
+            .iter()
+            .any(|scheme| src.starts_with(scheme));
+        Image {
+            path: is_local.then(|| resolve_path(&src, &file_path)),
+            src: Some(src.clone()),
+            pending_load: Some(PendingLoad {
+                src,
+                file_path,
+                fetcher,
+                image_callback,
+            }),
+            hidpi_scale,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the image is still waiting on [`Image::start_loading`]
+    pub fn is_deferred(&self) -> bool {
+        self.pending_load.is_some()
+    }
+
+    /// Loads a deferred image in the background. Does nothing for images that already started
+    pub fn start_loading(&mut self, priority: Priority) {
+        let Some(PendingLoad {
+            src,
+            file_path,
+            fetcher,
+            image_callback,
+        }) = self.pending_load.take()
+        else {
+            return;
+        };
+        let image_data_clone = self.image_data.clone();
+        let load_failed_clone = Arc::clone(&self.load_failed);
+        let unsupported_format_clone = Arc::clone(&self.unsupported_format);
+        let hidpi_scale = self.hidpi_scale;
+
+        pool::pool().spawn(priority, move || {
+            if src.starts_with("data:") {
+                let image = match decode_data_uri(&src) {
+                    Ok(bytes) => ImageData::load(&bytes, true)
+                        .or_else(|_| ImageData::load_svg(Arc::new(bytes), hidpi_scale, 1.)),
+                    Err(err) => Err(err),
+                };
+                let image = image.unwrap_or_else(|err| {
+                    tracing::warn!("Failed loading image from a data URI: {err}");
+                    image_callback
+                        .failed_image(src.clone(), format!("is an invalid data URI: {err}"));
+                    ImageData::load(include_bytes!("../../assets/img/broken.png"), false).unwrap()
+                });
+                *image_data_clone.lock().unwrap() = Some(image);
+                image_callback.loaded_image(src, image_data_clone);
+                return;
             }
 
+            let src_path = resolve_path(&src, &file_path);
+
             let image_data = if let Ok(img_file) = fs::read(&src_path) {
                 img_file
-            } else if let Ok(bytes) = utils::client()
-                .get(&src)
-                .send()
-                .and_then(|resp| resp.bytes())
-            {
-                bytes.to_vec()
             } else {
-                tracing::warn!("Request for image from {} failed", src_path.display());
-                return;
+                match fetcher.fetch(&src) {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        tracing::warn!(
+                            "Request for image from {} failed: {err}",
+                            src_path.display()
+                        );
+                        load_failed_clone.store(true, Ordering::Relaxed);
+                        image_callback.failed_image(src, "couldn't be read or fetched".to_owned());
+                        return;
+                    }
+                }
             };
 
+            let unsupported = OptionalFormat::sniff(&image_data).filter(|f| !f.is_supported());
+            if let Some(format) = unsupported {
+                tracing::warn!("Can't decode {format} image from {}", src_path.display());
+                let _ = unsupported_format_clone.set(format);
+                load_failed_clone.store(true, Ordering::Relaxed);
+                let feature = format.feature();
+                image_callback.failed_image(
+                    src,
+                    format!("is {format}, which needs inlyne built with the `{feature}` feature"),
+                );
+                return;
+            }
+
             let image = if let Ok(image) = ImageData::load(&image_data, true) {
                 image
             } else {
-                let opt = usvg::Options::default();
-                let mut fontdb = usvg::fontdb::Database::new();
-                fontdb.load_system_fonts();
-                // TODO: yes all of this image loading is very messy and could use a refactor
-                let Ok(mut tree) = usvg::Tree::from_data(&image_data, &opt) else {
-                    tracing::warn!(
-                        "Failed loading image:\n- src: {}\n- src_path: {}",
-                        src,
-                        src_path.display()
-                    );
-                    let image =
+                match ImageData::load_svg(Arc::new(image_data), hidpi_scale, 1.) {
+                    Ok(image) => image,
+                    Err(err) => {
+                        tracing::warn!(
+                            "Failed loading image:\n- src: {}\n- src_path: {}\n- err: {}",
+                            src,
+                            src_path.display(),
+                            err,
+                        );
+                        image_callback
+                            .failed_image(src.clone(), "isn't a supported format".to_owned());
                         ImageData::load(include_bytes!("../../assets/img/broken.png"), false)
-                            .unwrap();
-                    *image_data_clone.lock().unwrap() = Some(image);
-                    image_callback.loaded_image(src, image_data_clone);
-                    return;
-                };
-                tree.size = tree.size.scale_to(
-                    tiny_skia::Size::from_wh(
-                        tree.size.width() * hidpi_scale,
-                        tree.size.height() * hidpi_scale,
-                    )
-                    .unwrap(),
-                );
-                tree.convert_text(&fontdb);
-                let rtree = resvg::Tree::from_usvg(&tree);
-                let mut pixmap =
-                    tiny_skia::Pixmap::new(rtree.size.width() as u32, rtree.size.height() as u32)
-                        .context("Couldn't create svg pixmap")
-                        .unwrap();
-                rtree.render(tiny_skia::Transform::default(), &mut pixmap.as_mut());
-                ImageData::new(
-                    ImageBuffer::from_raw(pixmap.width(), pixmap.height(), pixmap.data().into())
-                        .context("Svg buffer has invalid dimensions")
-                        .unwrap(),
-                    false,
-                )
+                            .unwrap()
+                    }
+                }
             };
 
             *image_data_clone.lock().unwrap() = Some(image);
             image_callback.loaded_image(src, image_data_clone);
         });
+    }
 
-        let image = Image {
-            image_data,
-            hidpi_scale,
-            ..Default::default()
+    /// Reads the image from disk again after it's changed, swapping out the old image once the
+    /// new one is decoded
+    ///
+    /// The old image stays when the new one can't be read or decoded, like when it's only partway
+    /// written
+    pub fn reload(&self, image_callback: Box<dyn ImageCallback + Send>) {
+        let (Some(src), Some(path)) = (self.src.clone(), self.path.clone()) else {
+            return;
         };
+        let image_data = Arc::clone(&self.image_data);
+        let load_failed = Arc::clone(&self.load_failed);
+        let hidpi_scale = self.hidpi_scale;
 
-        Ok(image)
+        pool::pool().spawn(Priority::Visible, move || {
+            let image = fs::read(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| {
+                    ImageData::load(&bytes, true)
+                        .or_else(|_| ImageData::load_svg(Arc::new(bytes), hidpi_scale, 1.))
+                });
+            match image {
+                Ok(image) => {
+                    *image_data.lock().unwrap() = Some(image);
+                    load_failed.store(false, Ordering::Relaxed);
+                    image_callback.loaded_image(src, image_data);
+                }
+                Err(err) => tracing::debug!("Keeping the old {}: {err}", path.display()),
+            }
+        });
     }
 
     pub fn from_image_data(image_data: Arc<Mutex<Option<ImageData>>>, hidpi_scale: f32) -> Image {
@@ -304,18 +988,118 @@ impl Image {
         self.is_link = Some(link);
     }
 
+    pub fn set_anchor(&mut self, anchor: String) {
+        self.is_anchor = Some(anchor);
+    }
+
+    pub fn set_alt(&mut self, alt: String) {
+        self.alt = Some(alt);
+    }
+
+    /// Whether the loaded image has any see-through pixels
+    pub fn has_alpha(&self) -> bool {
+        let image_data = self.image_data.lock().unwrap();
+        image_data.as_ref().map_or(false, |data| data.has_alpha)
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.image_data.lock().unwrap().is_some()
+    }
+
+    pub fn load_failed(&self) -> bool {
+        self.load_failed.load(Ordering::Relaxed)
+    }
+
+    /// The alt text that gets shown in place of the image while there's nothing to show, or a
+    /// note that it's broken if loading it failed
+    pub fn placeholder(&self, color: [f32; 4]) -> Option<TextBox> {
+        let alt = self
+            .alt
+            .as_deref()
+            .map(str::trim)
+            .filter(|alt| !alt.is_empty());
+        let failure = match self.unsupported_format.get() {
+            Some(format) => format!(
+                "{format} images need inlyne built with the `{}` feature",
+                format.feature()
+            ),
+            None => "Failed to load image".to_owned(),
+        };
+        let text = match (alt, self.load_failed()) {
+            (Some(alt), false) => alt.to_owned(),
+            (Some(alt), true) => format!("{failure}: {alt}"),
+            (None, true) => failure,
+            (None, false) => return None,
+        };
+        let mut text_box = TextBox::new(
+            vec![Text::new(text, self.hidpi_scale, color)],
+            self.hidpi_scale,
+        );
+        text_box.font_size = PLACEHOLDER_FONT_SIZE;
+        Some(text_box)
+    }
+
+    /// How far the placeholder's text is inset from its border
+    pub fn placeholder_padding(&self, zoom: f32) -> f32 {
+        PLACEHOLDER_PADDING * self.hidpi_scale * zoom
+    }
+
+    /// Room for the alt text, or for whichever dimension the image was given
+    pub fn placeholder_size(
+        &self,
+        text_system: &mut TextSystem,
+        screen_size: Size,
+        zoom: f32,
+    ) -> Option<Size> {
+        let text_box = self.placeholder([0.; 4])?;
+        let padding = self.placeholder_padding(zoom);
+        let max_width = screen_size.0 - 2. * DEFAULT_MARGIN;
+        let hinted = |px: Px| px.0 as f32 * self.hidpi_scale * zoom;
+
+        let width = match self.size {
+            Some(ImageSize::PxWidth(width)) => hinted(width).min(max_width),
+            _ => {
+                let bounds = ((max_width - 2. * padding).max(0.), f32::INFINITY);
+                text_box.size(text_system, bounds, zoom).0 + 2. * padding
+            }
+        };
+        let height = match self.size {
+            Some(ImageSize::PxHeight(height)) => hinted(height),
+            _ => {
+                let bounds = ((width - 2. * padding).max(0.), f32::INFINITY);
+                text_box.size(text_system, bounds, zoom).1 + 2. * padding
+            }
+        };
+        Some((width, height))
+    }
+
     pub fn with_align(mut self, align: Align) -> Self {
         self.is_aligned = Some(align);
         self
     }
 
+    pub fn with_float(mut self, float: Float) -> Self {
+        self.float = Some(float);
+        self
+    }
+
     pub fn with_size(mut self, size: ImageSize) -> Self {
         self.size = Some(size);
         self
     }
 
+    pub fn with_max_width(mut self, max_width: MaxWidth) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_transform(mut self, transform: ImageTransform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
     pub fn dimensions_from_image_size(&mut self, size: &ImageSize) -> Option<(u32, u32)> {
-        let image_dimensions = self.buffer_dimensions()?;
+        let image_dimensions = self.shown_dimensions()?;
         match size {
             ImageSize::PxWidth(px_width) => Some((
                 px_width.0,
@@ -327,24 +1111,52 @@ impl Image {
                     as u32,
                 px_height.0,
             )),
+            // Depends on the content width, so it only gets worked out along with the layout
+            ImageSize::PercentWidth(_) => None,
         }
     }
 
-    fn buffer_dimensions(&self) -> Option<(u32, u32)> {
+    pub fn buffer_dimensions(&self) -> Option<(u32, u32)> {
         Some(self.image_data.lock().unwrap().as_ref()?.dimensions)
     }
 
+    /// The size of the image once it's been turned however it's transformed
+    pub fn shown_dimensions(&self) -> Option<(u32, u32)> {
+        let (width, height) = self.buffer_dimensions()?;
+        if self.transform.unwrap_or_default().swaps_sides() {
+            Some((height, width))
+        } else {
+            Some((width, height))
+        }
+    }
+
     fn dimensions(&mut self, screen_size: Size, zoom: f32) -> Option<(u32, u32)> {
-        let buffer_size = self.buffer_dimensions()?;
+        let buffer_size = self.shown_dimensions()?;
         let mut buffer_size = (buffer_size.0 as f32 * zoom, buffer_size.1 as f32 * zoom);
         if let Some(image) = self.image_data.lock().as_deref().unwrap() {
             if image.scale {
                 buffer_size.0 *= self.hidpi_scale;
                 buffer_size.1 *= self.hidpi_scale;
             }
+            // Already rasterized at some zoom, so that shouldn't get applied twice
+            if let Some(svg) = &image.svg {
+                buffer_size.0 /= svg.rasterized_zoom;
+                buffer_size.1 /= svg.rasterized_zoom;
+            }
         }
-        let max_width = screen_size.0 - 2. * DEFAULT_MARGIN;
-        let dimensions = if let Some(size) = self.size {
+        let content_width = screen_size.0 - 2. * DEFAULT_MARGIN;
+        let max_width = match self.max_width {
+            Some(MaxWidth::Px(px)) => (px.0 as f32 * self.hidpi_scale * zoom).min(content_width),
+            Some(MaxWidth::Percent(percent)) => percent.of(content_width).min(content_width),
+            None => content_width,
+        };
+        let dimensions = if let Some(ImageSize::PercentWidth(percent)) = self.size {
+            let width = percent.of(content_width).min(max_width);
+            (
+                width as u32,
+                ((width / buffer_size.0) * buffer_size.1) as u32,
+            )
+        } else if let Some(size) = self.size {
             let dimensions = self.dimensions_from_image_size(&size)?;
             let target_dimensions = (
                 (dimensions.0 as f32 * self.hidpi_scale * zoom) as u32,
@@ -375,6 +1187,156 @@ impl Image {
     }
 }
 
+/// The order to start loading the images at `bounds` in, leaving out the ones that are more than
+/// `preload_screens` screens away from the view between `view_top` and `view_bottom`
+///
+/// Whatever's already in view goes first, and then everything else from the closest on out
+pub fn load_order(
+    bounds: &[&Rect],
+    view_top: f32,
+    view_bottom: f32,
+    preload_screens: f32,
+) -> Vec<usize> {
+    let margin = (view_bottom - view_top) * preload_screens;
+    let distance = |bounds: &Rect| {
+        let (top, bottom) = (bounds.pos.1, bounds.pos.1 + bounds.size.1);
+        (view_top - bottom).max(top - view_bottom).max(0.)
+    };
+    let mut nearby: Vec<_> = (0..bounds.len())
+        .filter(|&index| distance(bounds[index]) <= margin)
+        .collect();
+    nearby.sort_by(|&a, &b| distance(bounds[a]).total_cmp(&distance(bounds[b])));
+    nearby
+}
+
+/// Where `src` would be on disk, with relative paths resolved from the document at `file_path`
+pub fn resolve_path(src: &str, file_path: &Path) -> PathBuf {
+    let path = src.strip_prefix("file://").unwrap_or(src);
+    // Drop anything tacked on like `?raw=true` or `#gh-dark-mode-only`
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let path = percent_encoding::percent_decode_str(path).decode_utf8_lossy();
+    let path = Path::new(path.as_ref());
+    match file_path.parent() {
+        Some(parent_dir) if path.is_relative() => {
+            parent_dir.join(path.strip_prefix("./").unwrap_or(path))
+        }
+        _ => path.to_owned(),
+    }
+}
+
+/// Decodes the contents of a `data:` URI like `data:image/png;base64,iVBORw0KGgo...`
+fn decode_data_uri(uri: &str) -> anyhow::Result<Vec<u8>> {
+    let rest = uri
+        .strip_prefix("data:")
+        .context("Missing the `data:` scheme")?;
+    let (media_type, data) = rest
+        .split_once(',')
+        .context("Missing a comma before the data")?;
+    let data: Vec<u8> = percent_encoding::percent_decode_str(data).collect();
+    if media_type
+        .split(';')
+        .any(|param| param.trim().eq_ignore_ascii_case("base64"))
+    {
+        // Long URIs tend to get wrapped across lines
+        let data: Vec<u8> = data
+            .into_iter()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+        Ok(BASE64_STANDARD.decode(data)?)
+    } else {
+        Ok(data)
+    }
+}
+
+/// How big a texture to upload for an image that's shown at `display_size`. Images that are a lot
+/// bigger than that get scaled down to save on memory and upload time, and so do images with a
+/// side longer than the GPU's `max_side` since they can't be uploaded at all otherwise
+fn upload_dimensions(
+    buffer_dimensions: (u32, u32),
+    display_size: Size,
+    max_side: u32,
+) -> (u32, u32) {
+    let (width, height) = (buffer_dimensions.0 as f32, buffer_dimensions.1 as f32);
+    let mut scale =
+        (display_size.0 * UPLOAD_HEADROOM / width).max(display_size.1 * UPLOAD_HEADROOM / height);
+    // Not being shown at all doesn't say anything about how big it should be
+    if scale >= 1. || scale <= 0. || !scale.is_finite() {
+        scale = 1.;
+    }
+    scale = scale.min(max_side as f32 / width.max(height));
+    if scale >= 1. {
+        return buffer_dimensions;
+    }
+    (
+        ((width * scale).ceil() as u32).clamp(1, max_side),
+        ((height * scale).ceil() as u32).clamp(1, max_side),
+    )
+}
+
+/// Each level is half the size of the last, down to a single pixel
+///
+/// Sampling from these keeps images that are drawn smaller than their texture from shimmering
+fn mip_chain(image: RgbaImage) -> Vec<RgbaImage> {
+    let mut levels = vec![image];
+    loop {
+        let (width, height) = levels.last().unwrap().dimensions();
+        if width == 1 && height == 1 {
+            break levels;
+        }
+        let last = levels.last().unwrap();
+        // Averages each 2x2 block, with odd rows and columns folded into their neighbors
+        let next = RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+            let xs = [(2 * x).min(width - 1), (2 * x + 1).min(width - 1)];
+            let ys = [(2 * y).min(height - 1), (2 * y + 1).min(height - 1)];
+            let mut sum = [0u16; 4];
+            for (x, y) in xs.into_iter().flat_map(|x| ys.map(|y| (x, y))) {
+                let pixel = last.get_pixel(x, y);
+                for (total, channel) in sum.iter_mut().zip(pixel.0) {
+                    *total += u16::from(channel);
+                }
+            }
+            image::Rgba(sum.map(|total| (total / 4) as u8))
+        });
+        levels.push(next);
+    }
+}
+
+fn write_texture(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    mip_level: u32,
+    origin: (u32, u32),
+    rgba: &[u8],
+    dimensions: (u32, u32),
+) {
+    queue.write_texture(
+        // Tells wgpu where to copy the pixel data
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level,
+            origin: wgpu::Origin3d {
+                x: origin.0,
+                y: origin.1,
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        // The actual pixel data
+        rgba,
+        // The layout of the texture
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * dimensions.0),
+            rows_per_image: Some(dimensions.1),
+        },
+        wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable, Debug)]
 pub struct ImageVertex {
@@ -382,11 +1344,20 @@ pub struct ImageVertex {
     tex_coords: [f32; 2],
 }
 
+/// Quads that all get drawn from the same bind group with a single draw call
+pub struct ImageBatch {
+    pub bind_group: Arc<BindGroup>,
+    pub vertex_buf: wgpu::Buffer,
+    pub quads: u32,
+}
+
 pub struct ImageRenderer {
     pub render_pipeline: wgpu::RenderPipeline,
+    /// Covers as many quads as fit in a batch
     pub index_buf: wgpu::Buffer,
     pub bindgroup_layout: wgpu::BindGroupLayout,
     pub sampler: wgpu::Sampler,
+    pub atlas: ImageAtlas,
 }
 
 pub fn point(x: f32, y: f32, position: Point, size: Size, screen: Size) -> [f32; 3] {
@@ -469,10 +1440,12 @@ impl ImageRenderer {
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
         });
-        const INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
+        let indices: Vec<u16> = (0..Self::MAX_BATCH_QUADS as u16)
+            .flat_map(|quad| [0, 1, 2, 2, 3, 0].map(|index| quad * 4 + index))
+            .collect();
         let index_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(INDICES),
+            contents: bytemuck::cast_slice(&indices),
             usage: wgpu::BufferUsages::INDEX,
         });
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -484,41 +1457,114 @@ impl ImageRenderer {
             mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
+        let atlas = ImageAtlas::new(device, &sampler, &texture_bind_group_layout);
         Self {
             render_pipeline: image_pipeline,
             index_buf,
             bindgroup_layout: texture_bind_group_layout,
             sampler,
+            atlas,
         }
     }
 
-    pub fn vertex_buf(device: &Device, pos: Point, size: Size, screen_size: Size) -> wgpu::Buffer {
-        let vertices: &[ImageVertex] = &[
+    /// Keeps every index within what fits in a `u16`
+    pub const MAX_BATCH_QUADS: usize = 4_096;
+
+    /// The corners of an image at `pos` showing the `uv` part of its texture, turned by `transform`
+    pub fn quad(
+        pos: Point,
+        size: Size,
+        screen_size: Size,
+        uv: Rect,
+        transform: ImageTransform,
+    ) -> [ImageVertex; 4] {
+        let (left, top) = uv.pos;
+        let (right, bottom) = uv.max();
+        let tex_coords = [[left, top], [left, bottom], [right, bottom], [right, top]];
+        let [top_left, bottom_left, bottom_right, top_right] =
+            transform.corners().map(|corner| tex_coords[corner]);
+        [
             // TOP LEFT
             ImageVertex {
                 pos: point(-1.0, 1.0, pos, size, screen_size),
-                tex_coords: [0.0, 0.0],
+                tex_coords: top_left,
             },
             // BOTTOM LEFT
             ImageVertex {
                 pos: point(-1.0, -1.0, pos, size, screen_size),
-                tex_coords: [0.0, 1.0],
+                tex_coords: bottom_left,
             },
             // BOTTOM RIGHT
             ImageVertex {
                 pos: point(1.0, -1.0, pos, size, screen_size),
-                tex_coords: [1.0, 1.0],
+                tex_coords: bottom_right,
             },
             // TOP RIGHT
             ImageVertex {
                 pos: point(1.0, 1.0, pos, size, screen_size),
-                tex_coords: [1.0, 0.0],
+                tex_coords: top_right,
             },
-        ];
+        ]
+    }
+
+    pub fn vertex_buf(
+        device: &Device,
+        pos: Point,
+        size: Size,
+        screen_size: Size,
+        transform: ImageTransform,
+    ) -> wgpu::Buffer {
+        let whole = Rect::new((0., 0.), (1., 1.));
+        let quad = Self::quad(pos, size, screen_size, whole, transform);
+        Self::batch_vertex_buf(device, &[quad])
+    }
+
+    pub fn batch_vertex_buf(device: &Device, quads: &[[ImageVertex; 4]]) -> wgpu::Buffer {
         device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(vertices),
+            contents: bytemuck::cast_slice(quads),
             usage: wgpu::BufferUsages::VERTEX,
         })
     }
+
+    /// A single pixel of `rgba`, for filling quads with translucent colors that the shape
+    /// pipeline can't blend
+    pub fn color_bind_group(
+        &self,
+        device: &Device,
+        queue: &wgpu::Queue,
+        rgba: [u8; 4],
+    ) -> BindGroup {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("Color Texture"),
+            view_formats: &[],
+        });
+        write_texture(queue, &texture, 0, (0, 0), &rgba, (1, 1));
+
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bindgroup_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("Color Bind Group"),
+        })
+    }
 }
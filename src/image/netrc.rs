@@ -0,0 +1,166 @@
+//! Logins from a `.netrc` file, the same one that curl and git read
+
+use std::path::PathBuf;
+use std::{env, fs, io};
+
+use base64::prelude::*;
+use reqwest::header::HeaderValue;
+
+#[derive(Clone, Debug, Default)]
+pub struct Netrc {
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone)]
+struct Entry {
+    /// `None` for the `default` entry, which covers every machine not listed
+    machine: Option<String>,
+    login: String,
+    password: Option<String>,
+}
+
+// Keeps passwords out of the logs
+impl std::fmt::Debug for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Entry")
+            .field("machine", &self.machine)
+            .field("login", &self.login)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Netrc {
+    /// Reads the file at `$NETRC`, falling back to the one in the home directory
+    pub fn load() -> Option<Self> {
+        let path = match env::var_os("NETRC") {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let file_name = if cfg!(windows) { "_netrc" } else { ".netrc" };
+                dirs::home_dir()?.join(file_name)
+            }
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => Some(Self::parse(&contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                tracing::warn!("Failed reading {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut lines = contents.lines();
+        while let Some(line) = lines.next() {
+            for word in line.split_whitespace() {
+                if word.starts_with('#') {
+                    break;
+                }
+                if word == "macdef" {
+                    // Macro definitions run until the next blank line
+                    for line in lines.by_ref() {
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                    }
+                    break;
+                }
+                tokens.push(word);
+            }
+        }
+
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut tokens = tokens.into_iter();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" | "default" => {
+                    let machine = match token {
+                        "machine" => Some(tokens.next().unwrap_or_default().to_owned()),
+                        _ => None,
+                    };
+                    entries.push(Entry {
+                        machine,
+                        login: String::new(),
+                        password: None,
+                    });
+                }
+                "login" | "password" | "account" => {
+                    let value = tokens.next().unwrap_or_default().to_owned();
+                    let Some(entry) = entries.last_mut() else {
+                        continue;
+                    };
+                    match token {
+                        "login" => entry.login = value,
+                        "password" => entry.password = Some(value),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self { entries }
+    }
+
+    /// The `Authorization` header that logs in to `host`, if there's a password for it
+    pub fn authorization(&self, host: &str) -> Option<HeaderValue> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| {
+                entry
+                    .machine
+                    .as_deref()
+                    .map_or(false, |machine| machine.eq_ignore_ascii_case(host))
+            })
+            .or_else(|| self.entries.iter().find(|entry| entry.machine.is_none()))?;
+        let password = entry.password.as_ref()?;
+        let credentials = BASE64_STANDARD.encode(format!("{}:{password}", entry.login));
+        let mut value = HeaderValue::from_str(&format!("Basic {credentials}")).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(credentials: &str) -> Option<HeaderValue> {
+        let encoded = BASE64_STANDARD.encode(credentials);
+        Some(HeaderValue::from_str(&format!("Basic {encoded}")).unwrap())
+    }
+
+    #[test]
+    fn picks_the_matching_machine() {
+        let netrc = Netrc::parse(
+            "# Work stuff\n\
+            machine git.example.com login alice password hunter2\n\
+            machine images.example.com\n    login bob\n    account ignored\n    password swordfish\n\
+            \n\
+            macdef init\nmachine evil.example.com login mallory password oops\n\
+            \n\
+            default login anonymous password guest\n",
+        );
+        assert_eq!(
+            netrc.authorization("git.example.com"),
+            basic("alice:hunter2")
+        );
+        assert_eq!(
+            netrc.authorization("IMAGES.example.com"),
+            basic("bob:swordfish")
+        );
+        // Lines in a macro aren't entries
+        assert_eq!(
+            netrc.authorization("evil.example.com"),
+            basic("anonymous:guest")
+        );
+    }
+
+    #[test]
+    fn needs_a_password() {
+        let netrc = Netrc::parse("machine example.com login alice");
+        assert_eq!(netrc.authorization("example.com"), None);
+        assert_eq!(Netrc::parse("").authorization("example.com"), None);
+    }
+}
@@ -0,0 +1,192 @@
+//! A few worker threads that images get read, fetched, and decoded on, instead of a thread each
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+
+/// Most of the time goes to waiting on downloads, so there are a few workers even on small
+/// machines
+const MIN_WORKERS: usize = 4;
+const MAX_WORKERS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Normal,
+    /// Already on screen, so it goes ahead of everything else
+    Visible,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Queued {
+    priority: Priority,
+    /// Keeps jobs with the same priority in the order they came in
+    seq: u64,
+    job: Job,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: BinaryHeap<Queued>,
+    next_seq: u64,
+}
+
+pub struct Pool {
+    shared: Arc<(Mutex<Queue>, Condvar)>,
+}
+
+impl Pool {
+    pub fn new(workers: usize) -> Self {
+        let shared = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        for index in 0..workers {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name(format!("image-loader-{index}"))
+                .spawn(move || work(&shared))
+                .expect("Failed spawning an image loading thread");
+        }
+        Self { shared }
+    }
+
+    pub fn spawn<F: FnOnce() + Send + 'static>(&self, priority: Priority, job: F) {
+        let (queue, ready) = &*self.shared;
+        let mut queue = queue.lock().unwrap();
+        let seq = queue.next_seq;
+        queue.next_seq += 1;
+        queue.jobs.push(Queued {
+            priority,
+            seq,
+            job: Box::new(job),
+        });
+        ready.notify_one();
+    }
+
+    /// Drops everything that hasn't started yet. Anything that's already running finishes
+    pub fn cancel_pending(&self) {
+        let mut queue = self.shared.0.lock().unwrap();
+        let cancelled = queue.jobs.len();
+        queue.jobs.clear();
+        if cancelled > 0 {
+            tracing::debug!("Cancelled loading {cancelled} images");
+        }
+    }
+}
+
+fn work(shared: &(Mutex<Queue>, Condvar)) {
+    let (queue, ready) = shared;
+    loop {
+        let job = {
+            let mut queue = queue.lock().unwrap();
+            loop {
+                match queue.jobs.pop() {
+                    Some(queued) => break queued.job,
+                    None => queue = ready.wait(queue).unwrap(),
+                }
+            }
+        };
+        // A panic only takes out that one image instead of a whole worker
+        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+            tracing::warn!("Loading an image panicked");
+        }
+    }
+}
+
+/// The pool that every image shares
+pub fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let workers = thread::available_parallelism()
+            .map_or(MIN_WORKERS, |count| count.get())
+            .clamp(MIN_WORKERS, MAX_WORKERS);
+        Pool::new(workers)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// Keeps the only worker busy until the returned sender gets dropped
+    fn block(pool: &Pool) -> mpsc::Sender<()> {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (unblock_tx, unblock_rx) = mpsc::channel::<()>();
+        pool.spawn(Priority::Normal, move || {
+            started_tx.send(()).unwrap();
+            let _ = unblock_rx.recv();
+        });
+        started_rx.recv().unwrap();
+        unblock_tx
+    }
+
+    #[test]
+    fn visible_images_go_first() {
+        let pool = Pool::new(1);
+        let unblock = block(&pool);
+
+        let (tx, rx) = mpsc::channel();
+        for (priority, name) in [
+            (Priority::Normal, "first offscreen"),
+            (Priority::Visible, "first visible"),
+            (Priority::Normal, "second offscreen"),
+            (Priority::Visible, "second visible"),
+        ] {
+            let tx = tx.clone();
+            pool.spawn(priority, move || tx.send(name).unwrap());
+        }
+        drop(unblock);
+
+        let order: Vec<_> = (0..4).map(|_| rx.recv().unwrap()).collect();
+        assert_eq!(
+            order,
+            [
+                "first visible",
+                "second visible",
+                "first offscreen",
+                "second offscreen"
+            ]
+        );
+    }
+
+    #[test]
+    fn cancelling_drops_what_is_queued() {
+        let pool = Pool::new(1);
+        let unblock = block(&pool);
+
+        let (tx, rx) = mpsc::channel();
+        let stale = tx.clone();
+        pool.spawn(Priority::Visible, move || stale.send("stale").unwrap());
+        pool.cancel_pending();
+        pool.spawn(Priority::Normal, move || tx.send("fresh").unwrap());
+        drop(unblock);
+
+        assert_eq!(rx.recv().unwrap(), "fresh");
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+}
@@ -1,8 +1,17 @@
 use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
 use std::{fmt, fs};
 
-use super::{ImageData, Px};
+use super::decode::{self, OptionalFormat};
+use super::{
+    decode_data_uri, load_order, mip_chain, resolve_path, upload_dimensions, Image, ImageData,
+    ImageSize, ImageTransform, MaxWidth, Percent, Px,
+};
+use crate::interpreter::ImageCallback;
 use crate::test_utils::init_test_log;
+use crate::utils::Rect;
 
 #[test]
 fn px_parsing() {
@@ -10,6 +19,21 @@ fn px_parsing() {
     assert_eq!("500px".parse::<Px>().unwrap(), Px(500));
 }
 
+#[test]
+fn relative_size_parsing() {
+    assert_eq!("50%".parse::<Percent>().unwrap(), Percent(50.));
+    assert_eq!("12.5 %".parse::<Percent>().unwrap(), Percent(12.5));
+    assert!("50".parse::<Percent>().is_err());
+    assert!("-5%".parse::<Percent>().is_err());
+
+    assert_eq!(
+        " 100% ".parse::<MaxWidth>().unwrap(),
+        MaxWidth::Percent(Percent(100.))
+    );
+    assert_eq!("300px".parse::<MaxWidth>().unwrap(), MaxWidth::Px(Px(300)));
+    assert!("none".parse::<MaxWidth>().is_err());
+}
+
 // Checks that the image crate converting to RGBA8 is the same as our technique
 fn check(input_path: &Path) {
     let bytes = fs::read(input_path).unwrap();
@@ -46,6 +70,190 @@ fn source_image_variety() {
     }
 }
 
+fn animated_gif(delays_ms: &[u32]) -> Vec<u8> {
+    let mut gif = Vec::new();
+    {
+        let mut encoder = image::codecs::gif::GifEncoder::new(&mut gif);
+        for (i, &delay) in delays_ms.iter().enumerate() {
+            let shade = (i * 100) as u8;
+            let pixels =
+                image::RgbaImage::from_pixel(2, 2, image::Rgba([shade, shade, shade, 255]));
+            let delay = image::Delay::from_numer_denom_ms(delay, 1);
+            encoder
+                .encode_frame(image::Frame::from_parts(pixels, 0, 0, delay))
+                .unwrap();
+        }
+    }
+    gif
+}
+
+#[test]
+fn animation_frames() {
+    init_test_log();
+
+    let image = ImageData::load(&animated_gif(&[100, 50, 0]), false).unwrap();
+    let animation = image.animation.as_ref().unwrap();
+    assert_eq!(animation.frames.len(), 3);
+    // The first frame doubles as the still image
+    assert_eq!(image.to_bytes(), animation.frame_bytes(0));
+    assert_ne!(animation.frame_bytes(0), animation.frame_bytes(1));
+
+    let ms = Duration::from_millis;
+    // The 0ms delay gets bumped up like it would in a browser
+    assert_eq!(animation.loop_duration, ms(250));
+    assert_eq!(animation.frame_at(ms(0)), (0, ms(100)));
+    assert_eq!(animation.frame_at(ms(99)), (0, ms(1)));
+    assert_eq!(animation.frame_at(ms(100)), (1, ms(50)));
+    assert_eq!(animation.frame_at(ms(200)), (2, ms(50)));
+    // And it loops back around
+    assert_eq!(animation.frame_at(ms(260)), (0, ms(90)));
+}
+
+#[test]
+fn still_gif_is_not_animated() {
+    let image = ImageData::load(&animated_gif(&[100]), false).unwrap();
+    assert!(image.animation.is_none());
+}
+
+#[test]
+fn svgs_rasterize_at_the_current_zoom() {
+    let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+        <rect width="20" height="10" fill="red"/>
+    </svg>"#;
+    let svg = Arc::new(svg.to_vec());
+
+    let image = ImageData::load_svg(Arc::clone(&svg), 1.5, 1.).unwrap();
+    assert_eq!(image.dimensions, (30, 15));
+    let image = ImageData::load_svg(Arc::clone(&svg), 1.5, 2.).unwrap();
+    assert_eq!(image.dimensions, (60, 30));
+    assert_eq!(image.svg.as_ref().unwrap().rasterized_zoom, 2.);
+
+    // Ludicrous zoom levels get capped instead
+    let image = ImageData::load_svg(svg, 1., 1_000.).unwrap();
+    assert_eq!(image.dimensions, (8192, 4096));
+    let svg = image.svg.unwrap();
+    assert_eq!((svg.zoom, svg.rasterized_zoom), (1_000., 8192. / 20.));
+}
+
+#[test]
+fn resolving_paths() {
+    let doc = Path::new("docs").join("guide").join("README.md");
+    let resolve = |src| resolve_path(src, &doc);
+    let guide = Path::new("docs").join("guide");
+    assert_eq!(resolve("./img/cat.png"), guide.join("img").join("cat.png"));
+    assert_eq!(
+        resolve("../assets/cat.png"),
+        guide.join("..").join("assets").join("cat.png")
+    );
+    assert_eq!(resolve("my%20cat.png?raw=true"), guide.join("my cat.png"));
+    assert_eq!(resolve("cat.png#gh-dark-mode-only"), guide.join("cat.png"));
+
+    let absolute = std::env::current_dir().unwrap().join("cat.png");
+    let absolute_src = absolute.to_str().unwrap();
+    assert_eq!(resolve(absolute_src), absolute);
+    assert_eq!(resolve(&format!("file://{absolute_src}")), absolute);
+}
+
+#[test]
+fn data_uris() {
+    assert_eq!(
+        decode_data_uri("data:image/png;base64,aW5s\neW5l").unwrap(),
+        b"inlyne"
+    );
+    assert_eq!(
+        decode_data_uri("data:image/svg+xml;charset=utf-8,%3Csvg%2F%3E").unwrap(),
+        b"<svg/>"
+    );
+    assert_eq!(decode_data_uri("data:,").unwrap(), b"");
+    assert!(decode_data_uri("data:image/png;base64").is_err());
+    assert!(decode_data_uri("data:image/png;base64,not base64!").is_err());
+}
+
+/// A JPEG that's red on the left and blue on the right, with an EXIF orientation tag
+fn oriented_jpeg(orientation: u16, big_endian: bool) -> Vec<u8> {
+    let pixels = image::RgbImage::from_fn(32, 16, |x, _| {
+        if x < 16 {
+            image::Rgb([255, 0, 0])
+        } else {
+            image::Rgb([0, 0, 255])
+        }
+    });
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+        .encode_image(&pixels)
+        .unwrap();
+
+    let u16_bytes = |n: u16| {
+        if big_endian {
+            n.to_be_bytes()
+        } else {
+            n.to_le_bytes()
+        }
+    };
+    let u32_bytes = |n: u32| {
+        if big_endian {
+            n.to_be_bytes()
+        } else {
+            n.to_le_bytes()
+        }
+    };
+    let mut tiff = if big_endian {
+        b"MM\0*".to_vec()
+    } else {
+        b"II*\0".to_vec()
+    };
+    tiff.extend(u32_bytes(8));
+    tiff.extend(u16_bytes(1));
+    // The orientation tag holding a single short
+    tiff.extend(u16_bytes(0x0112));
+    tiff.extend(u16_bytes(3));
+    tiff.extend(u32_bytes(1));
+    tiff.extend(u16_bytes(orientation));
+    tiff.extend([0, 0]);
+    // No more IFDs
+    tiff.extend(u32_bytes(0));
+
+    let mut app1 = vec![0xFF, 0xE1];
+    app1.extend((2 + 6 + tiff.len() as u16).to_be_bytes());
+    app1.extend(b"Exif\0\0");
+    app1.extend(tiff);
+    jpeg.splice(2..2, app1);
+    jpeg
+}
+
+#[test]
+fn exif_orientation() {
+    let pixel_at = |image: &ImageData, x: u32, y: u32| {
+        let bytes = image.to_bytes();
+        let i = 4 * (y * image.dimensions.0 + x) as usize;
+        let [r, _, b, _] = bytes[i..i + 4] else {
+            unreachable!()
+        };
+        if r > b {
+            "red"
+        } else {
+            "blue"
+        }
+    };
+
+    let upright = ImageData::load(&oriented_jpeg(1, true), false).unwrap();
+    assert_eq!(upright.dimensions, (32, 16));
+    assert_eq!(pixel_at(&upright, 4, 8), "red");
+
+    // Turning it a quarter turn clockwise puts the left side on top
+    let rotated = ImageData::load(&oriented_jpeg(6, true), false).unwrap();
+    assert_eq!(rotated.dimensions, (16, 32));
+    assert_eq!(pixel_at(&rotated, 8, 4), "red");
+    assert_eq!(pixel_at(&rotated, 8, 28), "blue");
+
+    let upside_down = ImageData::load(&oriented_jpeg(3, false), false).unwrap();
+    assert_eq!(upside_down.dimensions, (32, 16));
+    assert_eq!(pixel_at(&upside_down, 4, 8), "blue");
+
+    let mirrored = ImageData::load(&oriented_jpeg(2, false), false).unwrap();
+    assert_eq!(pixel_at(&mirrored, 4, 8), "blue");
+}
+
 #[derive(PartialEq)]
 struct Rgba8Data(Vec<[u8; 4]>);
 
@@ -74,3 +282,338 @@ impl fmt::Debug for Rgba8Data {
             .finish()
     }
 }
+
+#[test]
+fn broken_image_placeholder() {
+    let placeholder_text = |image: &Image| {
+        let text_box = image.placeholder([0.; 4])?;
+        Some(text_box.texts[0].text.clone())
+    };
+
+    let mut image = Image::default();
+    assert_eq!(placeholder_text(&image), None);
+    image.set_alt("A cat".to_owned());
+    assert_eq!(placeholder_text(&image).unwrap(), "A cat");
+
+    image.load_failed.store(true, Ordering::Relaxed);
+    assert_eq!(
+        placeholder_text(&image).unwrap(),
+        "Failed to load image: A cat"
+    );
+    image.alt = None;
+    assert_eq!(placeholder_text(&image).unwrap(), "Failed to load image");
+
+    image.unsupported_format.set(OptionalFormat::Avif).unwrap();
+    assert_eq!(
+        placeholder_text(&image).unwrap(),
+        "AVIF images need inlyne built with the `avif` feature"
+    );
+}
+
+struct ReloadCallback(mpsc::Sender<String>);
+
+impl ImageCallback for ReloadCallback {
+    fn loaded_image(&self, src: String, _: Arc<Mutex<Option<ImageData>>>) {
+        self.0.send(src).unwrap();
+    }
+}
+
+#[test]
+fn reloading_from_disk() {
+    init_test_log();
+
+    let png = |width, height| {
+        let mut bytes = Vec::new();
+        image::RgbaImage::new(width, height)
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        bytes
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("diagram.png");
+    fs::write(&path, png(2, 1)).unwrap();
+
+    let image_data = ImageData::load(&png(2, 1), true).unwrap();
+    let image = Image {
+        src: Some("diagram.png".to_owned()),
+        path: Some(path.clone()),
+        ..Image::from_image_data(Arc::new(Mutex::new(Some(image_data))), 1.)
+    };
+    let dimensions = || {
+        image
+            .image_data
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .dimensions
+    };
+    let (tx, rx) = mpsc::channel();
+
+    fs::write(&path, png(3, 4)).unwrap();
+    image.reload(Box::new(ReloadCallback(tx.clone())));
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+        "diagram.png"
+    );
+    assert_eq!(dimensions(), (3, 4));
+
+    // Something partway written keeps the last good image around
+    fs::write(&path, &png(5, 5)[..20]).unwrap();
+    image.reload(Box::new(ReloadCallback(tx)));
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+    assert_eq!(dimensions(), (3, 4));
+}
+
+#[test]
+fn sniffing_optional_formats() {
+    let mut avif = b"\0\0\0\x1cftypmif1\0\0\0\0mif1avifmiaf".to_vec();
+    assert_eq!(OptionalFormat::sniff(&avif), Some(OptionalFormat::Avif));
+    // Only the brands count, not the rest of the file
+    avif[20..24].copy_from_slice(b"heic");
+    avif.extend_from_slice(b"avif");
+    assert_eq!(OptionalFormat::sniff(&avif), None);
+
+    let jxl_codestream = [0xff, 0x0a, 0xfa, 0x1f];
+    let jxl_container = b"\0\0\0\x0cJXL \r\n\x87\n\0\0\0\x14ftypjxl ";
+    assert_eq!(
+        OptionalFormat::sniff(&jxl_codestream),
+        Some(OptionalFormat::JpegXl)
+    );
+    assert_eq!(
+        OptionalFormat::sniff(jxl_container),
+        Some(OptionalFormat::JpegXl)
+    );
+
+    let png = include_bytes!("../../assets/img/broken.png");
+    assert_eq!(OptionalFormat::sniff(png), None);
+    assert_eq!(OptionalFormat::sniff(&[]), None);
+}
+
+#[test]
+fn oversized_images_are_scaled_down() {
+    const MAX_SIDE: u32 = 8192;
+
+    // A big photo shown at a fraction of its size
+    assert_eq!(
+        upload_dimensions((8000, 6000), (800., 600.), MAX_SIDE),
+        (1000, 750)
+    );
+    // Anything shown at close to its actual size stays as is
+    assert_eq!(
+        upload_dimensions((1000, 750), (900., 675.), MAX_SIDE),
+        (1000, 750)
+    );
+    assert_eq!(
+        upload_dimensions((100, 100), (400., 400.), MAX_SIDE),
+        (100, 100)
+    );
+    // Squashed images keep enough pixels for their longest side
+    assert_eq!(
+        upload_dimensions((4000, 4000), (100., 400.), MAX_SIDE),
+        (500, 500)
+    );
+    assert_eq!(
+        upload_dimensions((4000, 4000), (0., 0.), MAX_SIDE),
+        (4000, 4000)
+    );
+
+    // Giant screenshots get shrunk to what the GPU can handle no matter how big they're shown
+    assert_eq!(
+        upload_dimensions((20000, 1000), (0., 0.), MAX_SIDE),
+        (8192, 410)
+    );
+    assert_eq!(
+        upload_dimensions((1000, 30000), (2000., 60000.), MAX_SIDE),
+        (274, 8192)
+    );
+}
+
+#[test]
+fn mip_levels_halve_down_to_a_pixel() {
+    let image = image::RgbaImage::from_pixel(10, 3, image::Rgba([255, 0, 0, 255]));
+    let sizes: Vec<_> = mip_chain(image)
+        .iter()
+        .map(|level| level.dimensions())
+        .collect();
+    assert_eq!(sizes, [(10, 3), (5, 1), (2, 1), (1, 1)]);
+
+    let checkerboard = image::RgbaImage::from_fn(2, 2, |x, y| {
+        let value = if (x + y) % 2 == 0 { 255 } else { 1 };
+        image::Rgba([value, value, value, 255])
+    });
+    assert_eq!(
+        mip_chain(checkerboard)[1].get_pixel(0, 0).0,
+        [128, 128, 128, 255]
+    );
+}
+
+#[test]
+fn images_near_the_view_load_closest_first() {
+    let bounds = [
+        Rect::new((0., 1200.), (100., 100.)),
+        Rect::new((0., 0.), (100., 100.)),
+        Rect::new((0., 1600.), (100., 100.)),
+        Rect::new((0., 600.), (100., 100.)),
+        Rect::new((0., 2100.), (100., 100.)),
+        Rect::new((0., 1450.), (100., 100.)),
+    ];
+    let bounds: Vec<_> = bounds.iter().collect();
+
+    // Everything in view goes first, in the order it's in the document
+    assert_eq!(load_order(&bounds, 1000., 1500., 1.), [0, 5, 2, 3]);
+    assert_eq!(load_order(&bounds, 1000., 1500., 0.), [0, 5]);
+}
+
+#[test]
+fn relative_sizes_follow_the_content_width() {
+    // Leaves 1000px for the content between the margins
+    const SCREEN: (f32, f32) = (1200., 800.);
+    let image = |size: Option<ImageSize>, max_width: Option<MaxWidth>| {
+        let image_data = ImageData::new(image::RgbaImage::new(400, 200), false);
+        let mut image = Image::from_image_data(Arc::new(Mutex::new(Some(image_data))), 1.);
+        image.size = size;
+        image.max_width = max_width;
+        image.size(SCREEN, 1.).unwrap()
+    };
+    let half = Some(ImageSize::PercentWidth(Percent(50.)));
+
+    assert_eq!(image(half, None), (500., 250.));
+    assert_eq!(
+        image(Some(ImageSize::PercentWidth(Percent(150.))), None),
+        (1000., 500.)
+    );
+    assert_eq!(image(None, Some(MaxWidth::Px(Px(300)))), (300., 150.));
+    assert_eq!(
+        image(
+            Some(ImageSize::PxWidth(Px(800))),
+            Some(MaxWidth::Percent(Percent(20.)))
+        ),
+        (200., 100.)
+    );
+    assert_eq!(
+        image(half, Some(MaxWidth::Percent(Percent(40.)))),
+        (400., 200.)
+    );
+}
+
+#[test]
+fn transform_parsing() {
+    // The texture corner shown at the top left, bottom left, bottom right, and top right
+    let corners = |css: &str| css.parse::<ImageTransform>().unwrap().corners();
+
+    assert_eq!(corners("none"), [0, 1, 2, 3]);
+    assert_eq!(corners("rotate(90deg)"), [1, 2, 3, 0]);
+    assert_eq!(corners("rotate(-0.25turn)"), corners("rotate(270deg)"));
+    assert_eq!(corners("rotate(45deg)"), [0, 1, 2, 3]);
+    assert_eq!(corners("scaleX(-1)"), [3, 2, 1, 0]);
+    assert_eq!(corners("scaleY(-1)"), [1, 0, 3, 2]);
+    assert_eq!(corners("scale(-1, -1)"), corners("rotate(180deg)"));
+    // Applied from right to left, so this mirrors then rotates
+    assert_eq!(corners("rotate(90deg) scaleX(-1)"), [2, 1, 0, 3]);
+    assert_eq!(corners("scaleX(-1) rotate(90deg)"), [0, 3, 2, 1]);
+    assert!("rotate(90deg".parse::<ImageTransform>().is_err());
+}
+
+#[test]
+fn quarter_turns_swap_sides() {
+    let image_data = ImageData::new(image::RgbaImage::new(400, 200), false);
+    let mut image = Image::from_image_data(Arc::new(Mutex::new(Some(image_data))), 1.);
+    assert_eq!(image.size((1200., 800.), 1.), Some((400., 200.)));
+
+    image.transform = Some("rotate(90deg)".parse().unwrap());
+    assert_eq!(image.size((1200., 800.), 1.), Some((200., 400.)));
+    image.size = Some(ImageSize::PxWidth(Px(100)));
+    assert_eq!(image.size((1200., 800.), 1.), Some((100., 200.)));
+    image.transform = Some("scaleX(-1)".parse().unwrap());
+    assert_eq!(image.size((1200., 800.), 1.), Some((100., 50.)));
+}
+
+#[test]
+fn shown_parts_map_back_to_the_texture() {
+    let texture_rect = |css: &str, min, max| {
+        let transform = css.parse::<ImageTransform>().unwrap();
+        transform.texture_rect(&Rect::from_min_max(min, max))
+    };
+
+    assert_eq!(
+        texture_rect("none", (0.25, 0.5), (0.5, 0.75)),
+        Rect::from_min_max((0.25, 0.5), (0.5, 0.75))
+    );
+    // The shown top left is the texture's bottom left after turning it clockwise
+    assert_eq!(
+        texture_rect("rotate(90deg)", (0., 0.), (0.25, 0.5)),
+        Rect::from_min_max((0., 0.75), (0.5, 1.))
+    );
+    assert_eq!(
+        texture_rect("rotate(90deg)", (0.25, 0.5), (0.5, 0.75)),
+        Rect::from_min_max((0.5, 0.5), (0.75, 0.75))
+    );
+    assert_eq!(
+        texture_rect("scaleY(-1)", (0.25, 0.5), (0.5, 0.75)),
+        Rect::from_min_max((0.25, 0.25), (0.5, 0.5))
+    );
+}
+
+#[test]
+fn detecting_alpha() {
+    // Reads that end partway through a pixel still only look at the alpha channel
+    struct Trickle<'a>(&'a [u8]);
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(3).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    let has_alpha = |image: &image::RgbaImage| {
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        ImageData::load(&png, false).unwrap().has_alpha
+    };
+
+    let mut image = image::RgbaImage::from_pixel(3, 3, image::Rgba([0, 0, 0, 255]));
+    assert!(!has_alpha(&image));
+    assert!(!decode::has_alpha(image.as_raw()));
+    image.put_pixel(2, 1, image::Rgba([255, 255, 255, 254]));
+    assert!(has_alpha(&image));
+    assert!(decode::has_alpha(image.as_raw()));
+
+    let opaque_but_many_255s = [0, 0, 0, 255, 255, 255, 255, 255, 0, 255, 0, 255];
+    let mut scan = decode::AlphaScan::new(Trickle(&opaque_but_many_255s));
+    std::io::copy(&mut scan, &mut std::io::sink()).unwrap();
+    assert!(!scan.has_alpha);
+    let mut scan = decode::AlphaScan::new(Trickle(&[9, 9, 9, 255, 0, 0, 0, 0]));
+    std::io::copy(&mut scan, &mut std::io::sink()).unwrap();
+    assert!(scan.has_alpha);
+}
+
+#[cfg(feature = "jxl")]
+#[test]
+fn decoding_jxl() {
+    // A lossless 8x8 codestream that's mid gray all over
+    let jxl = include_bytes!("../../assets/img/gray.jxl");
+    assert!(OptionalFormat::JpegXl.is_supported());
+    let parts = decode::decode_and_compress(jxl).unwrap();
+    assert_eq!(parts.dimensions, (8, 8));
+    assert!(!parts.has_alpha);
+    let rgba = decode::lz4_decompress(&parts.lz4_blob, 8 * 8 * 4).unwrap();
+    assert!(rgba
+        .chunks_exact(4)
+        .all(|pixel| pixel == [128, 128, 128, 255]));
+}
+
+#[cfg(not(feature = "jxl"))]
+#[test]
+fn optional_format_without_its_feature() {
+    let jxl_codestream = [0xff, 0x0a, 0xfa, 0x1f];
+    assert!(!OptionalFormat::JpegXl.is_supported());
+    let err = decode::decode_and_compress(&jxl_codestream).err().unwrap();
+    assert_eq!(err.to_string(), "Built without the `jxl` feature");
+}
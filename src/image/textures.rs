@@ -0,0 +1,109 @@
+//! Keeps image textures on the GPU within a memory budget
+
+use crate::image::Image;
+
+/// Tracks which frame each image was last drawn in, so that the ones that have been scrolled out
+/// of view the longest get dropped first once their textures take up too much memory
+///
+/// Dropped textures get uploaded again from the image's decoded data the next time it's drawn
+#[derive(Debug)]
+pub struct TextureManager {
+    max_bytes: u64,
+    frame: u64,
+}
+
+impl TextureManager {
+    pub fn new(budget_mib: u32) -> Self {
+        Self {
+            max_bytes: u64::from(budget_mib) * 1_024 * 1_024,
+            frame: 0,
+        }
+    }
+
+    /// Never drops anything
+    pub fn unlimited() -> Self {
+        Self {
+            max_bytes: u64::MAX,
+            frame: 0,
+        }
+    }
+
+    /// Starts a new frame, giving the number that images drawn in it get marked with
+    pub fn next_frame(&mut self) -> u64 {
+        self.frame += 1;
+        self.frame
+    }
+
+    /// Drops the textures of images that weren't drawn this frame until everything fits
+    pub fn evict(&self, mut images: Vec<&mut Image>) {
+        let resident: Vec<_> = images
+            .iter()
+            .map(|image| (image.last_drawn, image.texture_bytes()))
+            .collect();
+        let evicted = self.to_evict(&resident);
+        if evicted.is_empty() {
+            return;
+        }
+
+        tracing::debug!(
+            "Dropping {} image textures to stay within {:.2} MiB",
+            evicted.len(),
+            crate::utils::usize_in_mib(self.max_bytes as usize),
+        );
+        for index in evicted {
+            images[index].forget_texture();
+        }
+    }
+
+    /// Which of the `(last_drawn, bytes)` textures should go, least recently drawn first
+    fn to_evict(&self, resident: &[(u64, u64)]) -> Vec<usize> {
+        let mut total: u64 = resident.iter().map(|(_, bytes)| bytes).sum();
+        let mut by_last_drawn: Vec<_> = (0..resident.len())
+            .filter(|&index| resident[index].1 > 0)
+            .collect();
+        by_last_drawn.sort_by_key(|&index| resident[index].0);
+
+        let mut evicted = Vec::new();
+        for index in by_last_drawn {
+            let (last_drawn, bytes) = resident[index];
+            // Whatever's on screen has to stay, even if that alone is over budget
+            if total <= self.max_bytes || last_drawn == self.frame {
+                break;
+            }
+            total -= bytes;
+            evicted.push(index);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1_024 * 1_024;
+
+    #[test]
+    fn least_recently_drawn_go_first() {
+        let mut textures = TextureManager::new(3);
+        for _ in 0..5 {
+            textures.next_frame();
+        }
+
+        let resident = [(3, MIB), (1, MIB), (5, MIB), (2, MIB), (4, 0)];
+        assert_eq!(textures.to_evict(&resident), [1]);
+        let resident = [(3, 2 * MIB), (1, MIB), (5, MIB), (2, MIB)];
+        assert_eq!(textures.to_evict(&resident), [1, 3]);
+        assert!(textures.to_evict(&resident[..2]).is_empty());
+    }
+
+    #[test]
+    fn visible_images_stay() {
+        let mut textures = TextureManager::new(1);
+        let frame = textures.next_frame();
+
+        let resident = [(frame, 2 * MIB), (frame - 1, MIB), (frame, MIB)];
+        assert_eq!(textures.to_evict(&resident), [1]);
+        assert!(TextureManager::unlimited().to_evict(&resident).is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! Keeps to a single instance that files opened later get handed off to
+//!
+//! The first instance listens on a local socket, which is a unix socket or a named pipe on
+//! Windows. Later ones send it the path of the file that they were asked to open and then exit
+//! instead of opening a window of their own. Other programs can make requests over it too, see
+//! [`crate::ipc`]
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::thread;
+
+use crate::ipc::{Request, Response};
+use crate::InlyneEvent;
+
+use anyhow::Context;
+use interprocess::local_socket::{LocalSocketListener, LocalSocketStream, NameTypeSupport};
+use winit::event_loop::EventLoopProxy;
+
+/// Where instances find each other. Each user gets their own, so that nobody's files end up in
+/// someone else's window
+fn socket_name() -> String {
+    match NameTypeSupport::query() {
+        NameTypeSupport::OnlyPaths | NameTypeSupport::Both => match dirs::runtime_dir() {
+            Some(dir) => dir.join("inlyne.sock"),
+            // The temp dir is shared between everyone on the machine
+            None => env::temp_dir().join(format!("inlyne-{}.sock", user_id())),
+        }
+        .to_string_lossy()
+        .into_owned(),
+        NameTypeSupport::OnlyNamespaced => {
+            let user = env::var("USERNAME").unwrap_or_default();
+            format!("@inlyne-{user}")
+        }
+    }
+}
+
+#[cfg(unix)]
+fn user_id() -> String {
+    // SAFETY: `getuid()` always succeeds
+    unsafe { libc::getuid() }.to_string()
+}
+
+#[cfg(not(unix))]
+fn user_id() -> String {
+    env::var("USERNAME").unwrap_or_default()
+}
+
+/// Whether the socket at `name` can be trusted with our files. Somebody else could have put one
+/// wherever ours would go in a shared directory
+#[cfg(unix)]
+fn is_ours(name: &str) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match fs::symlink_metadata(name) {
+        // SAFETY: `getuid()` always succeeds
+        Ok(meta) => meta.uid() == unsafe { libc::getuid() },
+        // Nothing's there yet, or it's a namespaced socket that doesn't live on the filesystem
+        Err(_) => true,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_ours(_name: &str) -> bool {
+    true
+}
+
+/// Sends `path` over to the running instance, along with the `line` to scroll to. Gives whether
+/// there was one to take it
+pub fn forward(path: &Path, line: Option<usize>) -> bool {
+    // The other instance was most likely started from a different directory
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    let name = socket_name();
+    if !is_ours(&name) {
+        tracing::warn!("Not sending files over '{name}' since it belongs to another user");
+        return false;
+    }
+    let Ok(mut stream) = LocalSocketStream::connect(name) else {
+        return false;
+    };
+    let request = Request::Open {
+        path: path.clone(),
+        line,
+    };
+    let request = serde_json::to_string(&request).expect("Requests serialize to JSON");
+    match writeln!(stream, "{request}").and_then(|()| stream.flush()) {
+        Ok(()) => {
+            tracing::info!("Opening '{}' in the running instance", path.display());
+            true
+        }
+        Err(err) => {
+            tracing::warn!(
+                "Failed sending '{}' to the running instance: {err}",
+                path.display()
+            );
+            false
+        }
+    }
+}
+
+/// Takes in the files that later instances send over for as long as the event loop is around.
+/// Does nothing when another instance is already doing that
+pub fn listen(event_proxy: EventLoopProxy<InlyneEvent>) {
+    if let Err(err) = try_listen(event_proxy) {
+        tracing::warn!("Failed listening for files from other instances: {err:#}");
+    }
+}
+
+fn try_listen(event_proxy: EventLoopProxy<InlyneEvent>) -> anyhow::Result<()> {
+    let name = socket_name();
+    anyhow::ensure!(is_ours(&name), "'{name}' belongs to another user");
+    if LocalSocketStream::connect(name.as_str()).is_ok() {
+        tracing::debug!("Another instance is already taking files");
+        return Ok(());
+    }
+    // Unix sockets stick around after an instance that didn't exit cleanly
+    if !name.starts_with('@') {
+        let _ = fs::remove_file(&name);
+    }
+    let listener = LocalSocketListener::bind(name.as_str())
+        .with_context(|| format!("Couldn't bind the socket at '{name}'"))?;
+
+    thread::spawn(move || {
+        for conn in listener.incoming().filter_map(Result::ok) {
+            // An editor can keep its connection open for as long as it likes, so each gets a
+            // thread of its own
+            let event_proxy = event_proxy.clone();
+            thread::spawn(move || handle(conn, &event_proxy));
+        }
+    });
+
+    Ok(())
+}
+
+/// Answers each request coming in over `conn` until it closes or there's no event loop left to
+/// carry them out
+fn handle(conn: LocalSocketStream, event_proxy: &EventLoopProxy<InlyneEvent>) {
+    let mut conn = BufReader::new(conn);
+    let mut line = String::new();
+    while matches!(conn.read_line(&mut line), Ok(len) if len > 0) {
+        let send = |request| event_proxy.send_event(InlyneEvent::Request(request));
+        let Some(response) = Response::answer(line.trim(), send) else {
+            return;
+        };
+        let response = serde_json::to_string(&response).expect("Responses serialize to JSON");
+        // Whoever sent the request doesn't have to stick around for the answer
+        let _ = writeln!(conn.get_mut(), "{response}");
+        line.clear();
+    }
+}
@@ -0,0 +1,74 @@
+//! Line numbers down the left side of code blocks
+
+use crate::text::Text;
+
+use glyphon::FamilyOwned;
+
+/// Puts a right-aligned line number in front of every line of a code block
+pub fn insert(texts: &mut Vec<Text>, color: [f32; 4]) {
+    let Some(hidpi_scale) = texts.first().map(|text| text.hidpi_scale) else {
+        return;
+    };
+    let num_lines = count_lines(texts);
+    let width = num_lines.to_string().len();
+
+    let mut numbered = Vec::with_capacity(texts.len() + num_lines);
+    let mut line = 0;
+    let mut line_start = true;
+    for text in texts.drain(..) {
+        for part in text.text.split_inclusive('\n') {
+            if line_start {
+                line += 1;
+                let number = Text::new(format!("{line:>width$}  "), hidpi_scale, color)
+                    .with_color(color)
+                    .with_family(FamilyOwned::Monospace);
+                numbered.push(number);
+            }
+            line_start = part.ends_with('\n');
+            let mut part_text = text.clone();
+            part_text.text = part.to_owned();
+            numbered.push(part_text);
+        }
+    }
+    *texts = numbered;
+}
+
+/// Doesn't count the nothing after a trailing newline as a line
+fn count_lines(texts: &[Text]) -> usize {
+    let mut count = 0;
+    let mut line_start = true;
+    for part in texts
+        .iter()
+        .flat_map(|text| text.text.split_inclusive('\n'))
+    {
+        if line_start {
+            count += 1;
+        }
+        line_start = part.ends_with('\n');
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbering() {
+        let color = [0.5; 4];
+        let mut texts: Vec<_> = ["fn main() {\n    ", "println!(\"hi\");\n}\n", "\n", "// 10"]
+            .iter()
+            .map(|text| Text::new(text.to_string(), 1.0, [1.0; 4]))
+            .collect();
+        for _ in 0..6 {
+            texts.push(Text::new("\n".to_owned(), 1.0, [1.0; 4]));
+        }
+        insert(&mut texts, color);
+
+        let joined: String = texts.iter().map(|text| text.text.as_str()).collect();
+        assert_eq!(
+            joined,
+            " 1  fn main() {\n 2      println!(\"hi\");\n 3  }\n 4  \n 5  // 10\n 6  \n 7  \n 8  \n 9  \n10  \n"
+        );
+    }
+}
@@ -1,6 +1,14 @@
 use std::slice;
 
-use crate::{image::Px, opts::ResolvedTheme, utils::Align};
+use crate::{
+    bidi::Direction,
+    callout::Callout,
+    image::{Percent, Px},
+    opts::ResolvedTheme,
+    utils::Align,
+};
+
+use super::picture::SrcSet;
 
 use html5ever::{local_name, Attribute};
 
@@ -20,9 +28,13 @@ impl<'attrs> Iterator for Iter<'attrs> {
             let Attribute { name, value } = self.0.next()?;
             let attr = match name.local {
                 local_name!("align") => Align::new(value).map(Attr::Align),
+                local_name!("dir") => Direction::new(value).map(Attr::Dir),
                 local_name!("href") => Some(Attr::Href(value.to_string())),
                 local_name!("id") => Some(Attr::Anchor(format!("#{value}"))),
-                local_name!("width") => value.parse().ok().map(Attr::Width),
+                local_name!("width") => match value.parse() {
+                    Ok(px) => Some(Attr::Width(px)),
+                    Err(_) => value.parse().ok().map(Attr::PercentWidth),
+                },
                 local_name!("height") => value.parse().ok().map(Attr::Height),
                 local_name!("src") => Some(Attr::Src(value.to_string())),
                 local_name!("start") => value.parse().ok().map(Attr::Start),
@@ -31,8 +43,27 @@ impl<'attrs> Iterator for Iter<'attrs> {
                     (value.to_string() == "checkbox").then_some(Attr::IsCheckbox)
                 }
                 local_name!("checked") => Some(Attr::IsChecked),
-                local_name!("media") => PrefersColorScheme::new(value).map(Attr::Media),
-                local_name!("srcset") => Some(Attr::SrcSet(value.to_string())),
+                // Comrak marks fenced code with its language e.g. `class="language-rust"`
+                local_name!("class") => value
+                    .split_whitespace()
+                    .find_map(|class| class.strip_prefix("language-"))
+                    .map(|lang| Attr::Language(lang.to_owned())),
+                local_name!("open") => Some(Attr::IsOpen),
+                local_name!("media") => Some(Attr::Media(PrefersColorScheme::new(value))),
+                local_name!("srcset") => Some(Attr::SrcSet(SrcSet::parse(value))),
+                local_name!("sizes") => Some(Attr::Sizes(value.to_string())),
+                local_name!("alt") => Some(Attr::Alt(value.to_string())),
+                local_name!("title") => Some(Attr::Title(value.to_string())),
+                // Comrak marks block elements with where they're from in the markdown source
+                // e.g. `data-sourcepos="3:1-4:10"`
+                ref local if &**local == "data-footnotes" => Some(Attr::Footnotes),
+                ref local if &**local == "data-callout" => {
+                    Callout::from_name(value).map(Attr::Callout)
+                }
+                ref local if &**local == "data-sourcepos" => value
+                    .split_once(':')
+                    .and_then(|(line, _)| line.parse().ok())
+                    .map(Attr::SourceLine),
                 _ => continue,
             };
 
@@ -45,17 +76,31 @@ impl<'attrs> Iterator for Iter<'attrs> {
 
 pub enum Attr {
     Align(Align),
+    Dir(Direction),
+    Language(String),
     Href(String),
     Anchor(String),
     Width(Px),
+    PercentWidth(Percent),
     Height(Px),
     Src(String),
     Start(usize),
     Style(String),
     IsCheckbox,
     IsChecked,
-    Media(PrefersColorScheme),
-    SrcSet(String),
+    /// A `<details>` that starts out expanded
+    IsOpen,
+    /// `None` for media queries other than the color scheme ones
+    Media(Option<PrefersColorScheme>),
+    SrcSet(SrcSet),
+    Sizes(String),
+    Alt(String),
+    Title(String),
+    SourceLine(usize),
+    /// Marks the section that comrak collects footnotes into
+    Footnotes,
+    /// Marks the title of a callout quote
+    Callout(Callout),
 }
 
 impl Attr {
@@ -1,4 +1,5 @@
 use super::{picture, Header, List, ListType};
+use crate::opts::TableOptions;
 use crate::utils::Align;
 use crate::{Section, Table, TextBox};
 
@@ -10,6 +11,8 @@ pub enum Element {
     Header(Header),
     Paragraph(Option<Align>),
     Div(Option<Align>),
+    /// Holds onto the figure's anchor until its image comes along
+    Figure(Option<String>),
     Details(Section),
     Summary,
     Picture(picture::Builder),
@@ -22,8 +25,8 @@ impl From<picture::Builder> for Element {
 }
 
 impl Element {
-    pub fn table() -> Self {
-        Self::Table(Table::new())
+    pub fn table(style: TableOptions) -> Self {
+        Self::Table(Table::new(style))
     }
 
     pub fn table_row() -> Self {
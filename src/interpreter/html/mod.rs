@@ -9,6 +9,9 @@ pub use element::Element;
 pub use picture::Picture;
 pub use tag_name::TagName;
 
+use std::borrow::Cow;
+
+use crate::bidi::Direction;
 use crate::utils::Align;
 
 use html5ever::Attribute;
@@ -23,6 +26,26 @@ pub fn find_align(attrs: &[Attribute]) -> Option<Align> {
     })
 }
 
+pub fn find_dir(attrs: &[Attribute]) -> Option<Direction> {
+    attr::Iter::new(attrs).find_map(|attr| {
+        if let Attr::Dir(dir) = attr {
+            Some(dir)
+        } else {
+            None
+        }
+    })
+}
+
+pub fn find_language(attrs: &[Attribute]) -> Option<String> {
+    attr::Iter::new(attrs).find_map(|attr| {
+        if let Attr::Language(lang) = attr {
+            Some(lang)
+        } else {
+            None
+        }
+    })
+}
+
 pub fn find_style(attrs: &[Attribute]) -> Option<String> {
     attr::Iter::new(attrs).find_map(|attr| {
         if let Attr::Style(style) = attr {
@@ -32,6 +55,17 @@ pub fn find_style(attrs: &[Attribute]) -> Option<String> {
         }
     })
 }
+
+pub fn find_source_line(attrs: &[Attribute]) -> Option<usize> {
+    attr::Iter::new(attrs).find_map(|attr| {
+        if let Attr::SourceLine(line) = attr {
+            Some(line)
+        } else {
+            None
+        }
+    })
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HeaderType {
     H1,
@@ -43,14 +77,17 @@ pub enum HeaderType {
 }
 
 impl HeaderType {
-    pub fn text_size(&self) -> f32 {
+    /// How large each level is compared to body text by default, matching what browsers use
+    pub const DEFAULT_SCALES: [f32; 6] = [2., 1.5, 1.17, 1., 0.83, 0.67];
+
+    pub fn level(&self) -> u8 {
         match &self {
-            Self::H1 => 32.,
-            Self::H2 => 24.,
-            Self::H3 => 18.72,
-            Self::H4 => 16.,
-            Self::H5 => 13.28,
-            Self::H6 => 10.72,
+            Self::H1 => 1,
+            Self::H2 => 2,
+            Self::H3 => 3,
+            Self::H4 => 4,
+            Self::H5 => 5,
+            Self::H6 => 6,
         }
     }
 }
@@ -76,6 +113,226 @@ pub struct List {
     pub ty: ListType,
 }
 
+impl List {
+    /// The bullet or number for the next item. Like in a browser these change with how many
+    /// lists deep the list is, with bullets going `·`, `◦`, `▪` and numbers going `1.`, `a.`, `i.`
+    pub fn next_marker(&mut self, depth: usize) -> String {
+        let level = depth.saturating_sub(1) % 3;
+        match &mut self.ty {
+            ListType::Ordered(index) => {
+                let number = *index;
+                *index += 1;
+                match level {
+                    0 => format!("{number}. "),
+                    1 => format!("{}. ", alphabetic(number)),
+                    _ => format!("{}. ", roman(number)),
+                }
+            }
+            ListType::Unordered => ["· ", "◦ ", "▪ "][level].to_owned(),
+        }
+    }
+}
+
+/// `a` through `z` followed by `aa`, `ab`, and so on
+fn alphabetic(mut number: usize) -> String {
+    if number == 0 {
+        return number.to_string();
+    }
+    let mut letters = Vec::new();
+    while number > 0 {
+        number -= 1;
+        letters.push(char::from(b'a' + (number % 26) as u8));
+        number /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Lowercase roman numerals, which only go up to 3999
+fn roman(mut number: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    if number == 0 || number >= 4000 {
+        return number.to_string();
+    }
+    let mut numeral = String::new();
+    for (value, letters) in NUMERALS {
+        while number >= value {
+            numeral.push_str(letters);
+            number -= value;
+        }
+    }
+    numeral
+}
+
+/// Swaps out emoji shortcodes like `:rocket:` for their emoji. Comrak already does this for
+/// markdown, but leaves text in HTML alone
+pub fn expand_shortcodes(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        let after = &rest[start + 1..];
+        let name_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-')))
+            .unwrap_or(after.len());
+        let emoji = after[name_len..]
+            .starts_with(':')
+            .then(|| emojis::get_by_shortcode(&after[..name_len]))
+            .flatten();
+        match emoji {
+            Some(emoji) => {
+                expanded.push_str(&rest[..start]);
+                expanded.push_str(emoji.as_str());
+                rest = &after[name_len + 1..];
+            }
+            None => {
+                expanded.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+
+    Cow::Owned(expanded)
+}
+
+/// The superscript version of `c`, for the few characters that have one
+pub fn superscript(c: char) -> Option<char> {
+    let superscript = match c {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '+' => '⁺',
+        '-' | '−' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'a' => 'ᵃ',
+        'b' => 'ᵇ',
+        'c' => 'ᶜ',
+        'd' => 'ᵈ',
+        'e' => 'ᵉ',
+        'f' => 'ᶠ',
+        'g' => 'ᵍ',
+        'h' => 'ʰ',
+        'i' => 'ⁱ',
+        'j' => 'ʲ',
+        'k' => 'ᵏ',
+        'l' => 'ˡ',
+        'm' => 'ᵐ',
+        'n' => 'ⁿ',
+        'o' => 'ᵒ',
+        'p' => 'ᵖ',
+        'r' => 'ʳ',
+        's' => 'ˢ',
+        't' => 'ᵗ',
+        'u' => 'ᵘ',
+        'v' => 'ᵛ',
+        'w' => 'ʷ',
+        'x' => 'ˣ',
+        'y' => 'ʸ',
+        'z' => 'ᶻ',
+        'A' => 'ᴬ',
+        'B' => 'ᴮ',
+        'D' => 'ᴰ',
+        'E' => 'ᴱ',
+        'G' => 'ᴳ',
+        'H' => 'ᴴ',
+        'I' => 'ᴵ',
+        'J' => 'ᴶ',
+        'K' => 'ᴷ',
+        'L' => 'ᴸ',
+        'M' => 'ᴹ',
+        'N' => 'ᴺ',
+        'O' => 'ᴼ',
+        'P' => 'ᴾ',
+        'R' => 'ᴿ',
+        'T' => 'ᵀ',
+        'U' => 'ᵁ',
+        'V' => 'ⱽ',
+        'W' => 'ᵂ',
+        'α' => 'ᵅ',
+        'β' => 'ᵝ',
+        'γ' => 'ᵞ',
+        'δ' => 'ᵟ',
+        'ε' => 'ᵋ',
+        'θ' => 'ᶿ',
+        'ι' => 'ᶥ',
+        'φ' => 'ᵠ',
+        'χ' => 'ᵡ',
+        _ => return None,
+    };
+    Some(superscript)
+}
+
+/// The subscript version of `c`, for the few characters that have one
+pub fn subscript(c: char) -> Option<char> {
+    let subscript = match c {
+        '0' => '₀',
+        '1' => '₁',
+        '2' => '₂',
+        '3' => '₃',
+        '4' => '₄',
+        '5' => '₅',
+        '6' => '₆',
+        '7' => '₇',
+        '8' => '₈',
+        '9' => '₉',
+        '+' => '₊',
+        '-' | '−' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'h' => 'ₕ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'l' => 'ₗ',
+        'm' => 'ₘ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'p' => 'ₚ',
+        'r' => 'ᵣ',
+        's' => 'ₛ',
+        't' => 'ₜ',
+        'u' => 'ᵤ',
+        'v' => 'ᵥ',
+        'x' => 'ₓ',
+        'β' => 'ᵦ',
+        'γ' => 'ᵧ',
+        'ρ' => 'ᵨ',
+        'φ' => 'ᵩ',
+        'χ' => 'ᵪ',
+        _ => return None,
+    };
+    Some(subscript)
+}
+
 // Represents the number of parent text option tags the current element is a child of
 #[derive(Default)]
 pub struct TextOptions {
@@ -84,6 +341,10 @@ pub struct TextOptions {
     pub italic: usize,
     pub strike_through: usize,
     pub small: usize,
+    pub superscript: usize,
+    pub subscript: usize,
+    pub mark: usize,
+    pub keyboard: usize,
     pub code: usize,
     pub pre_formatted: usize,
     pub block_quote: usize,
@@ -1,15 +1,27 @@
-use crate::image::ImageSize;
+use crate::image::{ImageSize, ImageTransform, MaxWidth, Px};
 use crate::opts::ResolvedTheme;
-use crate::utils::Align;
+use crate::utils::{Align, Float};
 
 use anyhow::Context;
 
 #[derive(Debug, Default)]
 pub struct Inner {
     pub align: Option<Align>,
-    pub dark_variant: Option<String>,
-    pub light_variant: Option<String>,
+    /// Only when the `<img>` itself was aligned to a side, instead of whatever it's in
+    pub float: Option<Float>,
+    pub dark_variant: Option<SrcSet>,
+    pub light_variant: Option<SrcSet>,
+    /// From a `<source>` that applies no matter the color scheme
+    pub source: Option<SrcSet>,
+    /// From the `<img>`'s own `srcset`
+    pub src_set: Option<SrcSet>,
+    /// How wide the `sizes` attribute says the image gets shown
+    pub sizes: Option<Px>,
     pub size: Option<ImageSize>,
+    pub max_width: Option<MaxWidth>,
+    pub transform: Option<ImageTransform>,
+    pub alt: Option<String>,
+    pub title: Option<String>,
 }
 
 #[derive(Debug, Default)]
@@ -23,25 +35,68 @@ impl Builder {
         self.inner.align = Some(align);
     }
 
-    pub fn set_dark_variant(&mut self, dark: String) {
+    pub fn set_float(&mut self, float: Float) {
+        self.inner.float = Some(float);
+    }
+
+    pub fn set_dark_variant(&mut self, dark: SrcSet) {
         self.inner.dark_variant = Some(dark);
     }
 
-    pub fn set_light_variant(&mut self, light: String) {
+    pub fn set_light_variant(&mut self, light: SrcSet) {
         self.inner.light_variant = Some(light);
     }
 
+    /// Only the first one counts, just like how browsers go with the first `<source>` that matches
+    pub fn set_source(&mut self, source: SrcSet) {
+        self.inner.source.get_or_insert(source);
+    }
+
+    pub fn set_src_set(&mut self, src_set: SrcSet) {
+        self.inner.src_set = Some(src_set);
+    }
+
+    pub fn set_sizes(&mut self, sizes: &str) {
+        // Only the fallback at the end is understood, since there's no media to match the rest
+        // against. Something like `(max-width: 600px) 480px, 800px`
+        self.inner.sizes = sizes
+            .rsplit(',')
+            .next()
+            .and_then(|size| size.trim().parse().ok());
+    }
+
     pub fn set_size(&mut self, size: ImageSize) {
         self.inner.size = Some(size);
     }
 
+    pub fn set_max_width(&mut self, max_width: MaxWidth) {
+        self.inner.max_width = Some(max_width);
+    }
+
+    pub fn set_transform(&mut self, transform: ImageTransform) {
+        self.inner.transform = Some(transform);
+    }
+
+    pub fn set_alt(&mut self, alt: String) {
+        self.inner.alt = Some(alt);
+    }
+
+    pub fn set_title(&mut self, title: String) {
+        self.inner.title = Some(title);
+    }
+
     pub fn set_src(&mut self, src: String) {
         self.src = Some(src);
     }
 
     pub fn try_finish(self) -> anyhow::Result<Picture> {
         let Self { inner, src } = self;
-        let src = src.context("Missing `src` link for <picture>")?;
+        let src = src
+            .or_else(|| {
+                let src_set = inner.src_set.as_ref()?;
+                src_set.pick(1., None).map(str::to_owned)
+            })
+            .context("Missing `src` link for <picture>")?;
         Ok(Picture { inner, src })
     }
 }
@@ -57,12 +112,193 @@ impl Picture {
         Builder::default()
     }
 
-    pub fn resolve_src(&self, scheme: Option<ResolvedTheme>) -> &str {
-        scheme
-            .and_then(|scheme| match scheme {
-                ResolvedTheme::Dark => self.inner.dark_variant.as_ref(),
-                ResolvedTheme::Light => self.inner.light_variant.as_ref(),
-            })
-            .unwrap_or(&self.src)
+    /// The best source for the color scheme and a `hidpi_scale` screen, falling back to the plain
+    /// `src`
+    pub fn resolve_src(&self, scheme: Option<ResolvedTheme>, hidpi_scale: f32) -> &str {
+        let variant = scheme.and_then(|scheme| match scheme {
+            ResolvedTheme::Dark => self.inner.dark_variant.as_ref(),
+            ResolvedTheme::Light => self.inner.light_variant.as_ref(),
+        });
+        let display_width = match self.inner.size {
+            Some(ImageSize::PxWidth(width)) => Some(f32::from(width)),
+            _ => self.inner.sizes.map(f32::from),
+        };
+        [
+            variant,
+            self.inner.source.as_ref(),
+            self.inner.src_set.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(|src_set| src_set.pick(hidpi_scale, display_width))
+        .unwrap_or(&self.src)
+    }
+}
+
+/// The images offered up by a `srcset`, like `logo.png, logo@2x.png 2x` or
+/// `small.png 480w, large.png 1200w`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SrcSet(Vec<Candidate>);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Candidate {
+    url: String,
+    descriptor: Descriptor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Descriptor {
+    /// Meant for screens with this many pixels per CSS pixel
+    Density(f32),
+    /// How many pixels wide the image is
+    Width(u32),
+}
+
+impl SrcSet {
+    pub fn parse(s: &str) -> Self {
+        let mut candidates = Vec::new();
+        let mut rest = s;
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+            if rest.is_empty() {
+                break Self(candidates);
+            }
+
+            // URLs can have commas in them, but not at the end
+            let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let (url, after) = rest.split_at(url_end);
+            let (url, descriptor) = if let Some(url) = url.strip_suffix(',') {
+                rest = after;
+                (url.trim_end_matches(','), "")
+            } else {
+                let descriptor_end = after.find(',').unwrap_or(after.len());
+                rest = &after[descriptor_end..];
+                (url, after[..descriptor_end].trim())
+            };
+
+            let parsed = if descriptor.is_empty() {
+                Some(Descriptor::Density(1.))
+            } else if let Some(density) = descriptor.strip_suffix('x') {
+                density.parse().ok().map(Descriptor::Density)
+            } else if let Some(width) = descriptor.strip_suffix('w') {
+                width.parse().ok().map(Descriptor::Width)
+            } else {
+                None
+            };
+            match parsed {
+                Some(descriptor) if !url.is_empty() => candidates.push(Candidate {
+                    url: url.to_owned(),
+                    descriptor,
+                }),
+                _ => tracing::info!("Skipping invalid srcset candidate: {url} {descriptor}"),
+            }
+        }
+    }
+
+    /// The candidate that's sharp enough for a `hidpi_scale` screen with the least to spare, or
+    /// the sharpest one when none of them are
+    ///
+    /// Widths only say how sharp an image is once it's known how wide it's shown, so the widest
+    /// one gets picked when `display_width` isn't known
+    pub fn pick(&self, hidpi_scale: f32, display_width: Option<f32>) -> Option<&str> {
+        let density = |candidate: &Candidate| match candidate.descriptor {
+            Descriptor::Density(density) => density,
+            Descriptor::Width(width) => match display_width {
+                Some(display_width) if display_width > 0. => width as f32 / display_width,
+                _ => f32::INFINITY,
+            },
+        };
+        let sharp_enough = self
+            .0
+            .iter()
+            .filter(|candidate| density(candidate) >= hidpi_scale)
+            .min_by(|a, b| density(a).total_cmp(&density(b)));
+        let widest = || {
+            self.0
+                .iter()
+                .max_by_key(|candidate| match candidate.descriptor {
+                    Descriptor::Width(width) => width,
+                    Descriptor::Density(_) => 0,
+                })
+        };
+        let sharpest = || {
+            self.0
+                .iter()
+                .max_by(|a, b| density(a).total_cmp(&density(b)))
+        };
+        let picked = match sharp_enough {
+            Some(candidate) if density(candidate).is_infinite() => widest(),
+            Some(candidate) => Some(candidate),
+            None => sharpest(),
+        };
+        picked.map(|candidate| candidate.url.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_candidates() {
+        let src_set =
+            SrcSet::parse(" logo.png, logo@2x.png 2x,data:image/png,abc 3x ,, bad.png 2q");
+        let urls: Vec<_> = src_set.0.iter().map(|c| (&*c.url, c.descriptor)).collect();
+        assert_eq!(
+            urls,
+            [
+                ("logo.png", Descriptor::Density(1.)),
+                ("logo@2x.png", Descriptor::Density(2.)),
+                ("data:image/png,abc", Descriptor::Density(3.)),
+            ]
+        );
+        assert_eq!(SrcSet::parse(""), SrcSet::default());
+    }
+
+    #[test]
+    fn picking_by_density() {
+        let src_set = SrcSet::parse("one.png, two.png 2x, three.png 3x");
+        assert_eq!(src_set.pick(1., None), Some("one.png"));
+        assert_eq!(src_set.pick(1.5, None), Some("two.png"));
+        assert_eq!(src_set.pick(2., None), Some("two.png"));
+        assert_eq!(src_set.pick(4., None), Some("three.png"));
+        assert_eq!(SrcSet::default().pick(1., None), None);
+    }
+
+    #[test]
+    fn picking_by_width() {
+        let src_set = SrcSet::parse("small.png 400w, medium.png 800w, large.png 1600w");
+        assert_eq!(src_set.pick(1., Some(400.)), Some("small.png"));
+        assert_eq!(src_set.pick(2., Some(400.)), Some("medium.png"));
+        assert_eq!(src_set.pick(1., Some(1000.)), Some("large.png"));
+        assert_eq!(src_set.pick(2., Some(1000.)), Some("large.png"));
+        assert_eq!(src_set.pick(1., None), Some("large.png"));
+    }
+
+    #[test]
+    fn resolving_sources() {
+        let mut builder = Picture::builder();
+        builder.set_src_set(SrcSet::parse("small.png 400w, large.png 1200w"));
+        builder.set_sizes("(max-width: 600px) 200px, 400px");
+        let picture = builder.try_finish().unwrap();
+        // Needs a `src` without one
+        assert_eq!(picture.src, "large.png");
+        assert_eq!(picture.resolve_src(None, 1.), "small.png");
+        assert_eq!(picture.resolve_src(None, 2.), "large.png");
+
+        let mut builder = Picture::builder();
+        builder.set_dark_variant(SrcSet::parse("dark.png, dark@2x.png 2x"));
+        builder.set_source(SrcSet::parse("any.png"));
+        builder.set_source(SrcSet::parse("ignored.png"));
+        builder.set_src("fallback.png".to_owned());
+        let picture = builder.try_finish().unwrap();
+        assert_eq!(
+            picture.resolve_src(Some(ResolvedTheme::Dark), 2.),
+            "dark@2x.png"
+        );
+        assert_eq!(
+            picture.resolve_src(Some(ResolvedTheme::Light), 1.),
+            "any.png"
+        );
     }
 }
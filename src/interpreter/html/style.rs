@@ -1,3 +1,5 @@
+use crate::image::{ImageTransform, MaxWidth};
+
 pub struct Iter<'style>(std::str::Split<'style, char>);
 
 impl<'style> Iter<'style> {
@@ -32,6 +34,18 @@ impl<'style> Iterator for Iter<'style> {
                 .and_then(TextDecoration::new)
             {
                 return Some(Style::TextDecoration(d));
+            } else if let Some(w) = part
+                .trim()
+                .strip_prefix("max-width:")
+                .and_then(|w| w.parse().ok())
+            {
+                return Some(Style::MaxWidth(w));
+            } else if let Some(t) = part
+                .trim()
+                .strip_prefix("transform:")
+                .and_then(|t| t.parse().ok())
+            {
+                return Some(Style::Transform(t));
             }
         }
     }
@@ -43,6 +57,8 @@ pub enum Style {
     FontWeight(FontWeight),
     FontStyle(FontStyle),
     TextDecoration(TextDecoration),
+    MaxWidth(MaxWidth),
+    Transform(ImageTransform),
 }
 
 #[derive(Default, PartialEq, Eq)]
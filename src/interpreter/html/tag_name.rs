@@ -10,16 +10,23 @@ pub enum TagName {
     BoldOrStrong,
     Break,
     Code,
+    DescriptionDetails,
+    DescriptionList,
+    DescriptionTerm,
     Details,
     Div,
     EmphasisOrItalic,
+    Figure,
+    FigureCaption,
     Header(HeaderType),
     HorizontalRuler,
     Picture,
     Source,
     Image,
     Input,
+    Keyboard,
     ListItem,
+    Mark,
     OrderedList,
     Paragraph,
     PreformattedText,
@@ -27,7 +34,9 @@ pub enum TagName {
     Small,
     Span,
     Strikethrough,
+    Subscript,
     Summary,
+    Superscript,
     Table,
     TableBody,
     TableDataCell,
@@ -47,10 +56,15 @@ impl TryFrom<&Atom<LocalNameStaticSet>> for TagName {
             &local_name!("blockquote") => Self::BlockQuote,
             &local_name!("b") | &local_name!("strong") => Self::BoldOrStrong,
             &local_name!("br") => Self::Break,
-            &local_name!("code") | &local_name!("kbd") => Self::Code,
+            &local_name!("code") => Self::Code,
+            &local_name!("dd") => Self::DescriptionDetails,
+            &local_name!("dl") => Self::DescriptionList,
+            &local_name!("dt") => Self::DescriptionTerm,
             &local_name!("details") => Self::Details,
             &local_name!("div") => Self::Div,
             &local_name!("em") | &local_name!("i") => Self::EmphasisOrItalic,
+            &local_name!("figure") => Self::Figure,
+            &local_name!("figcaption") => Self::FigureCaption,
             &local_name!("h1") => Self::Header(HeaderType::H1),
             &local_name!("h2") => Self::Header(HeaderType::H2),
             &local_name!("h3") => Self::Header(HeaderType::H3),
@@ -62,7 +76,9 @@ impl TryFrom<&Atom<LocalNameStaticSet>> for TagName {
             &local_name!("source") => Self::Source,
             &local_name!("img") => Self::Image,
             &local_name!("input") => Self::Input,
+            &local_name!("kbd") => Self::Keyboard,
             &local_name!("li") => Self::ListItem,
+            &local_name!("mark") => Self::Mark,
             &local_name!("ol") => Self::OrderedList,
             &local_name!("p") => Self::Paragraph,
             &local_name!("pre") => Self::PreformattedText,
@@ -70,7 +86,9 @@ impl TryFrom<&Atom<LocalNameStaticSet>> for TagName {
             &local_name!("small") => Self::Small,
             &local_name!("span") => Self::Span,
             &local_name!("s") | &local_name!("del") => Self::Strikethrough,
+            &local_name!("sub") => Self::Subscript,
             &local_name!("summary") => Self::Summary,
+            &local_name!("sup") => Self::Superscript,
             &local_name!("table") => Self::Table,
             &local_name!("tbody") => Self::TableBody,
             &local_name!("td") => Self::TableDataCell,
@@ -1,20 +1,31 @@
-mod html;
+mod gutter;
+pub(crate) mod html;
+mod swatch;
 #[cfg(test)]
 mod tests;
 
-use std::collections::VecDeque;
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
 use std::path::PathBuf;
 use std::slice;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use std::sync::{mpsc, Arc, Mutex};
 
-use crate::color::{native_color, Theme};
+use crate::bidi::Direction;
+use crate::callout::Callout;
+use crate::color::{mix, native_color, Theme};
+use crate::diagnostics::{Diagnostic, Diagnostics};
+use crate::diff;
+use crate::image::fetch::Fetcher;
 use crate::image::{Image, ImageData, ImageSize};
-use crate::opts::ResolvedTheme;
+use crate::opts::{CodeBlockOptions, FontOptions, ImageCaptions, ResolvedTheme, TableOptions};
 use crate::positioner::{Positioned, Row, Section, Spacer, DEFAULT_MARGIN};
-use crate::text::{Text, TextBox};
-use crate::utils::{markdown_to_html, Align};
+use crate::slideshow::{parse_note, Note, SpeakerNotes};
+use crate::text::{Script, Text, TextBox};
+use crate::utils::{
+    markdown_to_html_chunked, parse_source_line_marker, Align, Float, MarkdownOptions,
+};
 use crate::{Element, ImageCache, InlyneEvent};
 use html::{
     attr::{self, PrefersColorScheme},
@@ -30,10 +41,13 @@ use html5ever::tokenizer::{
 };
 use wgpu::TextureFormat;
 use winit::event_loop::EventLoopProxy;
-use winit::window::Window;
 
 use self::html::{picture, HeaderType, Picture};
 
+const DEFAULT_FONT_SIZE: f32 = 16.;
+/// How large `<small>` text and captions are compared to body text
+const SMALL_FONT_SCALE: f32 = 0.75;
+
 struct State {
     global_indent: f32,
     element_stack: Vec<InterpreterElement>,
@@ -41,9 +55,27 @@ struct State {
     span: Span,
     // Stores the row and a counter of newlines after each image
     inline_images: Option<(Row, usize)>,
+    // The caption for the first image in the row. Only used when it's the only image
+    inline_caption: Option<String>,
     pending_anchor: Option<String>,
     pending_list_prefix: Option<String>,
+    // The callouts of the quotes that we're in along with their nesting levels
+    callouts: Vec<(Callout, usize)>,
+    // Whether we're in the title of a callout
+    in_callout_title: bool,
+    // The elements with a `dir` attribute that we're in
+    directions: Vec<(String, Direction)>,
     anchorizer: Anchorizer,
+    // How many elements have been queued, which is where speaker notes get placed
+    queued: usize,
+    // The rest is only tracked to report diagnostics
+    // The last known (markdown line, HTML line) pair along with the current HTML line
+    source_anchor: Option<(usize, u64)>,
+    html_line: u64,
+    open_tags: Vec<(String, Option<usize>)>,
+    anchors: HashSet<String>,
+    internal_links: Vec<(String, Option<usize>)>,
+    unsupported_tags: HashSet<String>,
 }
 
 impl State {
@@ -54,9 +86,20 @@ impl State {
             text_options: Default::default(),
             span: Span::with_color(span_color),
             inline_images: None,
+            inline_caption: None,
             pending_anchor: None,
             pending_list_prefix: None,
+            callouts: Vec::new(),
+            in_callout_title: false,
+            directions: Vec::new(),
             anchorizer: Default::default(),
+            queued: 0,
+            source_anchor: None,
+            html_line: 0,
+            open_tags: Vec::new(),
+            anchors: HashSet::new(),
+            internal_links: Vec::new(),
+            unsupported_tags: HashSet::new(),
         }
     }
 
@@ -86,33 +129,65 @@ impl Span {
 // Images are loaded in a separate thread and use a callback to indicate when they're finished
 pub trait ImageCallback {
     fn loaded_image(&self, src: String, image_data: Arc<Mutex<Option<ImageData>>>);
+    /// Called when an image couldn't be loaded. `loaded_image()` may still be called afterwards
+    /// with a placeholder
+    fn failed_image(&self, _src: String, _reason: String) {}
+}
+
+// Records failed images as diagnostics before passing everything along
+struct DiagnosticCallback {
+    inner: Box<dyn ImageCallback + Send>,
+    diagnostics: Diagnostics,
+    line: Option<usize>,
+}
+
+impl ImageCallback for DiagnosticCallback {
+    fn loaded_image(&self, src: String, image_data: Arc<Mutex<Option<ImageData>>>) {
+        self.inner.loaded_image(src, image_data);
+    }
+
+    fn failed_image(&self, src: String, reason: String) {
+        let message = format!("Image `{src}` {reason}");
+        self.diagnostics
+            .lock()
+            .unwrap()
+            .push(Diagnostic::new(self.line, message));
+        self.inner.failed_image(src, reason);
+    }
 }
 
 // External state from the interpreter that we want to stub out for testing
 trait WindowInteractor {
     fn finished_single_doc(&self);
-    fn request_redraw(&self);
+    /// Another chunk of the current document was queued up
+    fn finished_chunk(&self);
     fn image_callback(&self) -> Box<dyn ImageCallback + Send>;
 }
 
-struct EventLoopCallback(EventLoopProxy<InlyneEvent>);
+pub struct EventLoopCallback(pub EventLoopProxy<InlyneEvent>);
 
 impl ImageCallback for EventLoopCallback {
     fn loaded_image(&self, src: String, image_data: Arc<Mutex<Option<ImageData>>>) {
         let event = InlyneEvent::LoadedImage(src, image_data);
         self.0.send_event(event).unwrap();
     }
+
+    fn failed_image(&self, src: String, reason: String) {
+        let event = InlyneEvent::ImageError { src, reason };
+        self.0.send_event(event).unwrap();
+    }
 }
 
 // A real interactive window that is being used with `HtmlInterpreter`
 struct LiveWindow {
-    window: Arc<Window>,
     event_proxy: EventLoopProxy<InlyneEvent>,
 }
 
 impl WindowInteractor for LiveWindow {
-    fn request_redraw(&self) {
-        self.window.request_redraw();
+    fn finished_chunk(&self) {
+        self.event_proxy
+            .send_event(InlyneEvent::PositionQueue)
+            .unwrap();
     }
 
     fn image_callback(&self) -> Box<dyn ImageCallback + Send> {
@@ -126,6 +201,40 @@ impl WindowInteractor for LiveWindow {
     }
 }
 
+// Used when interpreting without a window, so there's nobody to notify
+struct Headless;
+
+struct NoopCallback;
+
+impl ImageCallback for NoopCallback {
+    fn loaded_image(&self, _: String, _: Arc<Mutex<Option<ImageData>>>) {}
+}
+
+impl WindowInteractor for Headless {
+    fn finished_single_doc(&self) {}
+
+    fn finished_chunk(&self) {}
+
+    fn image_callback(&self) -> Box<dyn ImageCallback + Send> {
+        Box::new(NoopCallback)
+    }
+}
+
+/// Markdown to interpret along with the file that it's from
+pub struct Document {
+    /// What relative paths, like those of images, get resolved against
+    pub file_path: PathBuf,
+    pub contents: String,
+    /// Switches to a new theme and color scheme first, like when the theme gets toggled
+    pub theme: Option<(Theme, Option<ResolvedTheme>)>,
+    /// Before the document's front matter gets a say
+    pub code_blocks: CodeBlockOptions,
+    /// Scales how far apart lines are, like when zen mode spreads them out
+    pub line_spacing: f32,
+    /// Picked up again for every document since they can change when the config gets reloaded
+    pub font_opts: FontOptions,
+}
+
 pub struct HtmlInterpreter {
     element_queue: Arc<Mutex<VecDeque<Element>>>,
     current_textbox: TextBox,
@@ -138,10 +247,33 @@ pub struct HtmlInterpreter {
     pub should_queue: Arc<AtomicBool>,
     // Whether interpreter should stop queuing till next received file
     stopped: bool,
-    first_pass: bool,
+    /// Whether the next document gets shown chunk by chunk as it's interpreted. It's only
+    /// worth the jumpiness when there's nothing on screen to keep in place, like for the first
+    /// document or a newly opened one
+    pub progressive: Arc<AtomicBool>,
     image_cache: ImageCache,
     window: Box<dyn WindowInteractor + Send>,
     color_scheme: Option<ResolvedTheme>,
+    pub diagnostics: Diagnostics,
+    pub notes: SpeakerNotes,
+    /// Whether color literals in code get a swatch of their color shown next to them
+    pub color_swatches: bool,
+    /// Which attribute, if any, images outside of a `<figure>` get captioned with
+    pub image_captions: ImageCaptions,
+    /// Which of the optional markdown extensions get applied
+    pub markdown: MarkdownOptions,
+    pub code_blocks: CodeBlockOptions,
+    /// The padding and row separators that tables get drawn with
+    pub tables: TableOptions,
+    /// How remote images get downloaded, and where they get cached between runs
+    pub fetcher: Fetcher,
+    /// Remote images don't get fetched until the renderer sees them getting close to the view
+    pub lazy_images: bool,
+    /// Size of body text in pixels, which every other font size is relative to
+    font_size: f32,
+    /// How large each heading level is compared to body text, from `h1` to `h6`
+    heading_scales: [f32; 6],
+    line_spacing: f32,
 }
 
 impl HtmlInterpreter {
@@ -149,7 +281,6 @@ impl HtmlInterpreter {
     // rest of the repo just because of here
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        window: Arc<Window>,
         element_queue: Arc<Mutex<VecDeque<Element>>>,
         theme: Theme,
         surface_format: TextureFormat,
@@ -159,10 +290,7 @@ impl HtmlInterpreter {
         event_proxy: EventLoopProxy<InlyneEvent>,
         color_scheme: Option<ResolvedTheme>,
     ) -> Self {
-        let live_window = LiveWindow {
-            window,
-            event_proxy,
-        };
+        let live_window = LiveWindow { event_proxy };
         Self::new_with_interactor(
             element_queue,
             theme,
@@ -175,6 +303,27 @@ impl HtmlInterpreter {
         )
     }
 
+    pub fn new_headless(
+        element_queue: Arc<Mutex<VecDeque<Element>>>,
+        theme: Theme,
+        surface_format: TextureFormat,
+        hidpi_scale: f32,
+        file_path: PathBuf,
+        image_cache: ImageCache,
+        color_scheme: Option<ResolvedTheme>,
+    ) -> Self {
+        Self::new_with_interactor(
+            element_queue,
+            theme,
+            surface_format,
+            hidpi_scale,
+            file_path,
+            image_cache,
+            Box::new(Headless),
+            color_scheme,
+        )
+    }
+
     // TODO: fix in a later refactor (consolidate a lot of junk)
     #[allow(clippy::too_many_arguments)]
     fn new_with_interactor(
@@ -198,45 +347,97 @@ impl HtmlInterpreter {
             file_path,
             should_queue: Arc::new(AtomicBool::new(true)),
             stopped: false,
-            first_pass: true,
+            progressive: Arc::new(AtomicBool::new(true)),
             image_cache,
             color_scheme,
+            diagnostics: Diagnostics::default(),
+            notes: SpeakerNotes::default(),
+            color_swatches: false,
+            image_captions: ImageCaptions::Off,
+            markdown: MarkdownOptions::default(),
+            code_blocks: CodeBlockOptions::default(),
+            tables: TableOptions::default(),
+            fetcher: Fetcher::default(),
+            lazy_images: false,
+            font_size: DEFAULT_FONT_SIZE,
+            heading_scales: HeaderType::DEFAULT_SCALES,
+            line_spacing: 1.0,
         }
     }
 
-    pub fn interpret_md(self, receiver: mpsc::Receiver<String>) {
+    pub fn interpret_md(self, receiver: mpsc::Receiver<Document>) {
         let mut input = BufferQueue::new();
 
-        let span_color = self.native_color(self.theme.text_color);
-        let code_highlighter = self.theme.code_highlighter.clone();
         let mut tok = Tokenizer::new(self, TokenizerOpts::default());
 
-        for md_string in receiver {
+        for Document {
+            file_path,
+            contents: md_string,
+            theme,
+            code_blocks,
+            line_spacing,
+            font_opts,
+        } in receiver
+        {
             tracing::debug!(
                 "Received markdown for interpretation: {} bytes",
                 md_string.len()
             );
 
+            if let Some((theme, color_scheme)) = theme {
+                tok.sink.theme = theme;
+                tok.sink.color_scheme = color_scheme;
+            }
             if tok.sink.should_queue.load(AtomicOrdering::Relaxed) {
+                let span_color = tok.sink.native_color(tok.sink.theme.text_color);
+                let code_highlighter = tok.sink.theme.code_highlighter.clone();
                 tok.sink.state = State::with_span_color(span_color);
-                tok.sink.current_textbox = TextBox::new(Vec::new(), tok.sink.hidpi_scale);
+                tok.sink.current_textbox = tok.sink.new_textbox(Vec::new());
                 tok.sink.stopped = false;
-                let htmlified = markdown_to_html(&md_string, code_highlighter.clone());
-
-                input.push_back(
-                    Tendril::from_str(&htmlified)
-                        .unwrap()
-                        .try_reinterpret::<fmt::UTF8>()
-                        .unwrap(),
-                );
+                tok.sink.file_path = file_path;
+                tok.sink.code_blocks = code_blocks.with_front_matter(&md_string);
+                tok.sink.line_spacing = line_spacing;
+                tok.sink.apply_font_options(&font_opts);
+                tok.sink.diagnostics.lock().unwrap().clear();
+                tok.sink.notes.lock().unwrap().clear();
+                let progressive = tok.sink.progressive.swap(false, AtomicOrdering::Relaxed);
+                let markdown = tok.sink.markdown;
+                markdown_to_html_chunked(&md_string, code_highlighter, markdown, |htmlified| {
+                    input.push_back(
+                        Tendril::from_str(htmlified)
+                            .unwrap()
+                            .try_reinterpret::<fmt::UTF8>()
+                            .unwrap(),
+                    );
 
-                let _ = tok.feed(&mut input);
-                assert!(input.is_empty());
+                    let _ = tok.feed(&mut input);
+                    assert!(input.is_empty());
+                    if progressive && !tok.sink.stopped {
+                        tok.sink.window.finished_chunk();
+                    }
+                });
                 tok.end();
             }
         }
     }
 
+    /// Interprets a single document that was already converted to HTML on the current thread
+    pub fn interpret_html(mut self, htmlified: &str) {
+        self.state = State::with_span_color(self.native_color(self.theme.text_color));
+        let mut input = BufferQueue::new();
+        input.push_back(
+            Tendril::from_str(htmlified)
+                .unwrap()
+                .try_reinterpret::<fmt::UTF8>()
+                .unwrap(),
+        );
+
+        let mut tok = Tokenizer::new(self, TokenizerOpts::default());
+        let _ = tok.feed(&mut input);
+        assert!(input.is_empty());
+        tok.end();
+    }
+
     fn align_or_inherit(&self, maybe_align: Option<Align>) -> Option<Align> {
         maybe_align.or_else(|| self.find_current_align())
     }
@@ -244,6 +445,9 @@ impl HtmlInterpreter {
     // Searches the currently nested elements for align attribute
     fn find_current_align(&self) -> Option<Align> {
         for element in self.state.element_stack.iter().rev() {
+            if let InterpreterElement::Figure(_) = element {
+                return Some(Align::Center);
+            }
             if let InterpreterElement::Div(Some(elem_align))
             | InterpreterElement::Paragraph(Some(elem_align))
             | InterpreterElement::Header(html::Header {
@@ -262,12 +466,25 @@ impl HtmlInterpreter {
         native_color(color, &self.surface_format)
     }
 
+    pub fn apply_font_options(&mut self, font_opts: &FontOptions) {
+        self.font_size = font_opts.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+        self.heading_scales = font_opts
+            .heading_scales
+            .unwrap_or(HeaderType::DEFAULT_SCALES);
+    }
+
+    fn new_textbox(&self, texts: Vec<Text>) -> TextBox {
+        let mut textbox = TextBox::new(texts, self.hidpi_scale);
+        textbox.font_size = self.font_size;
+        textbox.line_spacing = self.line_spacing;
+        textbox
+    }
+
     fn push_current_textbox(&mut self) {
         // Push any inline images
         if let Some((row, count)) = self.state.inline_images.take() {
             if count == 0 {
-                self.push_element(row);
-                self.push_spacer();
+                self.push_inline_images(row);
             } else {
                 self.state.inline_images = Some((row, count))
             }
@@ -282,7 +499,40 @@ impl HtmlInterpreter {
                 }
             }
             if !empty {
+                if self.current_textbox.is_code_block {
+                    let source: String = self
+                        .current_textbox
+                        .texts
+                        .iter()
+                        .map(|text| &*text.text)
+                        .collect();
+                    let source = source.trim_end_matches('\n');
+                    if self
+                        .current_textbox
+                        .code_language
+                        .as_deref()
+                        .map_or(false, diff::is_diff)
+                    {
+                        self.current_textbox.diff_lines = diff::line_kinds(source);
+                    }
+                    self.current_textbox.code_source = Some(source.to_owned());
+                }
+                if self.color_swatches {
+                    let surface_format = self.surface_format;
+                    swatch::insert(&mut self.current_textbox.texts, |color| {
+                        native_color(color, &surface_format)
+                    });
+                }
+                if self.current_textbox.is_code_block {
+                    self.current_textbox.no_wrap = !self.code_blocks.wrap;
+                    if self.code_blocks.line_numbers {
+                        let faded = mix(self.theme.background_color, self.theme.text_color, 0.5);
+                        let color = self.native_color(faded);
+                        gutter::insert(&mut self.current_textbox.texts, color);
+                    }
+                }
                 self.current_textbox.indent = self.state.global_indent;
+                self.align_to_direction();
                 let section = self.state.element_iter_mut().rev().find_map(|e| {
                     if let InterpreterElement::Details(section) = e {
                         Some(section)
@@ -299,55 +549,272 @@ impl HtmlInterpreter {
                 }
             }
         }
-        self.current_textbox = TextBox::new(Vec::new(), self.hidpi_scale);
+        self.current_textbox = self.new_textbox(Vec::new());
         self.current_textbox.indent = self.state.global_indent;
     }
+    // Right-to-left paragraphs start from the right unless something else aligns them
+    fn align_to_direction(&mut self) {
+        let textbox = &self.current_textbox;
+        if textbox.is_code_block || textbox.align != Align::Left {
+            return;
+        }
+        let direction = match self.state.directions.last() {
+            Some(&(_, dir)) => Some(dir),
+            None => textbox
+                .texts
+                .iter()
+                .find_map(|text| Direction::detect(&text.text)),
+        };
+        if direction == Some(Direction::Rtl) && self.find_current_align().is_none() {
+            self.current_textbox.set_align(Align::Right);
+        }
+    }
+
+    fn enter_direction(&mut self, tag: &Tag) {
+        if let Some(dir) = html::find_dir(&tag.attrs).filter(|_| !tag.self_closing) {
+            self.state.directions.push((tag.name.to_string(), dir));
+        }
+    }
+
+    // Only left once the element's end tag is processed, so its last text still gets its `dir`
+    fn leave_direction(&mut self, name: &str) {
+        if let Some(index) = self
+            .state
+            .directions
+            .iter()
+            .rposition(|(open, _)| open == name)
+        {
+            self.state.directions.truncate(index);
+        }
+    }
+
+    fn set_anchor(&mut self, anchor: String) {
+        self.state.anchors.insert(anchor.clone());
+        self.current_textbox.set_anchor(anchor);
+    }
+
+    // Blocks map their lines one-to-one between the markdown source and the rendered HTML, so we
+    // can work out a line from the last block's position
+    fn source_line(&self) -> Option<usize> {
+        let (md_line, html_line) = self.state.source_anchor?;
+        let offset = self.state.html_line.saturating_sub(html_line);
+        Some(md_line + usize::try_from(offset).ok()?)
+    }
+
+    fn diagnose(&self, message: impl Into<String>) {
+        let diagnostic = Diagnostic::new(self.source_line(), message);
+        self.diagnostics.lock().unwrap().push(diagnostic);
+    }
+
+    // Keeps track of which elements are still open, so that we can point out any mismatched tags
+    fn track_open_tags(&mut self, tag: &Tag) {
+        const VOID_ELEMENTS: &[&str] = &[
+            "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source",
+            "track", "wbr",
+        ];
+
+        let name = tag.name.to_string();
+        match tag.kind {
+            TagKind::StartTag => {
+                if let Some(line) = html::find_source_line(&tag.attrs) {
+                    self.state.source_anchor = Some((line, self.state.html_line));
+                }
+                if !tag.self_closing && !VOID_ELEMENTS.contains(&name.as_str()) {
+                    self.state.open_tags.push((name, self.source_line()));
+                }
+            }
+            TagKind::EndTag => {
+                match self
+                    .state
+                    .open_tags
+                    .iter()
+                    .rposition(|(open, _)| *open == name)
+                {
+                    Some(index) => {
+                        for (unclosed, line) in self.state.open_tags.split_off(index).drain(1..) {
+                            let message = format!("Element `<{unclosed}>` is never closed");
+                            self.diagnostics
+                                .lock()
+                                .unwrap()
+                                .push(Diagnostic::new(line, message));
+                        }
+                    }
+                    None if VOID_ELEMENTS.contains(&name.as_str()) => {}
+                    None => self.diagnose(format!("Closing tag `</{name}>` was never opened")),
+                }
+            }
+        }
+    }
+
+    // Reports anything that can only be detected after seeing the whole document
+    fn finish_diagnostics(&mut self) {
+        let mut diagnostics = self.diagnostics.lock().unwrap();
+        for (unclosed, line) in self.state.open_tags.drain(..) {
+            let message = format!("Element `<{unclosed}>` is never closed");
+            diagnostics.push(Diagnostic::new(line, message));
+        }
+        for (link, line) in &self.state.internal_links {
+            if !self.state.anchors.contains(link) {
+                let message = format!("Link to `{link}` doesn't match any heading or anchor");
+                diagnostics.push(Diagnostic::new(*line, message));
+            }
+        }
+    }
+
     fn push_spacer(&mut self) {
         self.push_element(Spacer::invisible());
     }
     fn push_element<I: Into<Element>>(&mut self, element: I) {
+        self.state.queued += 1;
         self.element_queue.lock().unwrap().push_back(element.into());
-        if self.first_pass {
-            self.window.request_redraw()
+    }
+
+    fn push_inline_images(&mut self, mut row: Row) {
+        match self.state.inline_caption.take() {
+            // A lone captioned image gets shown as a centered figure instead
+            Some(caption) if row.elements.len() == 1 => {
+                let Some(Positioned {
+                    inner: Element::Image(image),
+                    ..
+                }) = row.elements.pop()
+                else {
+                    unreachable!("Rows only hold images");
+                };
+                self.push_element(image.with_align(Align::Center));
+                self.push_caption(caption);
+            }
+            _ => self.push_element(row),
+        }
+        self.push_spacer();
+    }
+
+    fn push_caption(&mut self, caption: String) {
+        let color = self.native_color(self.theme.text_color);
+        let text = Text::new(caption, self.hidpi_scale, color).make_italic(true);
+        let mut textbox = self.new_textbox(vec![text]);
+        textbox.set_align(Align::Center);
+        // Sized like `<small>` text
+        textbox.font_size = self.font_size * SMALL_FONT_SCALE;
+        textbox.indent = self.state.global_indent;
+        textbox.source_line = self.source_line();
+        self.push_element(textbox);
+    }
+
+    fn image_caption(&self, pic: &Picture) -> Option<String> {
+        // Figures bring their own `<figcaption>`
+        let in_figure = self
+            .state
+            .element_stack
+            .iter()
+            .any(|elem| matches!(elem, InterpreterElement::Figure(_)));
+        if in_figure {
+            return None;
         }
+
+        let caption = match self.image_captions {
+            ImageCaptions::Title => pic.inner.title.as_ref(),
+            ImageCaptions::Alt => pic.inner.alt.as_ref(),
+            ImageCaptions::Off => None,
+        }?;
+        let caption = caption.trim();
+        (!caption.is_empty()).then(|| caption.to_owned())
     }
 
     fn push_image_from_picture(&mut self, pic: Picture) {
+        let caption = self.image_caption(&pic);
         let align = pic.inner.align;
-        let src = pic.resolve_src(self.color_scheme).to_owned();
+        let src = pic
+            .resolve_src(self.color_scheme, self.hidpi_scale)
+            .to_owned();
         let align = align.unwrap_or_default();
         let is_url = src.starts_with("http://") || src.starts_with("https://");
         let mut image = match self.image_cache.lock().unwrap().get(&src) {
             Some(image_data) if is_url => {
-                Image::from_image_data(image_data.clone(), self.hidpi_scale)
+                let mut image = Image::from_image_data(image_data.clone(), self.hidpi_scale);
+                image.src = Some(src.clone());
+                image
+            }
+            _ => {
+                let callback = Box::new(DiagnosticCallback {
+                    inner: self.window.image_callback(),
+                    diagnostics: Arc::clone(&self.diagnostics),
+                    line: self.source_line(),
+                });
+                let (src, file_path) = (src.clone(), self.file_path.clone());
+                if is_url && self.lazy_images {
+                    Image::deferred(
+                        src,
+                        file_path,
+                        self.hidpi_scale,
+                        self.fetcher.clone(),
+                        callback,
+                    )
+                } else {
+                    Image::from_src(
+                        src,
+                        file_path,
+                        self.hidpi_scale,
+                        self.fetcher.clone(),
+                        callback,
+                    )
+                }
             }
-            _ => Image::from_src(
-                src.clone(),
-                self.file_path.clone(),
-                self.hidpi_scale,
-                self.window.image_callback(),
-            )
-            .unwrap(),
         }
         .with_align(align);
 
         if let Some(link) = self.state.text_options.link.last() {
             image.set_link(link.clone())
         }
+        let figure_anchor = self
+            .state
+            .element_stack
+            .iter_mut()
+            .rev()
+            .find_map(|elem| match elem {
+                InterpreterElement::Figure(anchor) => anchor.take(),
+                _ => None,
+            });
+        if let Some(anchor) = figure_anchor {
+            image.set_anchor(anchor);
+        }
         if let Some(size) = pic.inner.size {
             image = image.with_size(size);
         }
+        if let Some(max_width) = pic.inner.max_width {
+            image = image.with_max_width(max_width);
+        }
+        if let Some(transform) = pic.inner.transform {
+            image = image.with_transform(transform);
+        }
+        if let Some(alt) = &pic.inner.alt {
+            image.set_alt(alt.clone());
+        }
+        image.source_line = self.source_line();
+
+        // Floats get pushed on their own so that whatever comes after them can go beside them
+        if let Some(float) = pic.inner.float {
+            if let Some((row, _)) = self.state.inline_images.take() {
+                self.push_inline_images(row);
+            }
+            self.push_element(image.with_float(float));
+            return;
+        }
 
         if align == Align::Left {
             if let Some((row, count)) = &mut self.state.inline_images {
                 row.elements.push(Positioned::new(image));
                 // Restart newline count
                 *count = 1;
+                self.state.inline_caption = None;
             } else {
                 self.state.inline_images = Some((Row::with_image(image, self.hidpi_scale), 1));
+                self.state.inline_caption = caption;
             }
         } else {
             self.push_element(image);
+            if let Some(caption) = caption {
+                self.push_caption(caption);
+            }
             self.push_spacer();
         }
     }
@@ -357,6 +824,11 @@ impl HtmlInterpreter {
             Ok(name) => name,
             Err(name) => {
                 tracing::info!("Missing implementation for start tag: {name}");
+                if self.state.unsupported_tags.insert(name.to_string()) {
+                    self.diagnose(format!(
+                        "Unsupported element `<{name}>`. Only its text is shown"
+                    ));
+                }
                 return;
             }
         };
@@ -372,7 +844,9 @@ impl HtmlInterpreter {
             TagName::TableHead | TagName::TableBody => {}
             TagName::Table => {
                 self.push_spacer();
-                self.state.element_stack.push(InterpreterElement::table());
+                self.state
+                    .element_stack
+                    .push(InterpreterElement::table(self.tables));
             }
             TagName::TableHeader => {
                 self.state.text_options.bold += 1;
@@ -390,8 +864,14 @@ impl HtmlInterpreter {
             TagName::Anchor => {
                 for attr in attr::Iter::new(&tag.attrs) {
                     match attr {
-                        Attr::Href(link) => self.state.text_options.link.push(link),
-                        Attr::Anchor(a) => self.current_textbox.set_anchor(a),
+                        Attr::Href(link) => {
+                            if link.starts_with('#') {
+                                let line = self.source_line();
+                                self.state.internal_links.push((link.clone(), line));
+                            }
+                            self.state.text_options.link.push(link);
+                        }
+                        Attr::Anchor(a) => self.set_anchor(a),
                         _ => {}
                     }
                 }
@@ -424,24 +904,49 @@ impl HtmlInterpreter {
                     }
                 }
 
-                let Some((media, src_set)) = media.zip(src_set) else {
-                    tracing::info!("Skipping <source> tag. Missing either srcset or known media");
+                let Some(src_set) = src_set else {
+                    tracing::info!("Skipping <source> tag. Missing srcset");
                     return;
                 };
 
                 match media {
-                    PrefersColorScheme(ResolvedTheme::Dark) => builder.set_dark_variant(src_set),
-                    PrefersColorScheme(ResolvedTheme::Light) => builder.set_light_variant(src_set),
+                    Some(Some(PrefersColorScheme(ResolvedTheme::Dark))) => {
+                        builder.set_dark_variant(src_set)
+                    }
+                    Some(Some(PrefersColorScheme(ResolvedTheme::Light))) => {
+                        builder.set_light_variant(src_set)
+                    }
+                    Some(None) => tracing::info!("Skipping <source> tag. Unknown media"),
+                    None => builder.set_source(src_set),
                 }
             }
             TagName::Image => {
                 let apply_attrs = |builder: &mut picture::Builder, attr_iter: attr::Iter<'_>| {
                     for attr in attr_iter {
                         match attr {
-                            Attr::Align(a) => builder.set_align(a),
+                            Attr::Align(a) => {
+                                builder.set_align(a);
+                                if let Some(float) = Float::from_align(a) {
+                                    builder.set_float(float);
+                                }
+                            }
                             Attr::Width(w) => builder.set_size(ImageSize::width(w)),
+                            Attr::PercentWidth(p) => builder.set_size(ImageSize::percent_width(p)),
                             Attr::Height(h) => builder.set_size(ImageSize::height(h)),
                             Attr::Src(s) => builder.set_src(s),
+                            Attr::SrcSet(s) => builder.set_src_set(s),
+                            Attr::Sizes(s) => builder.set_sizes(&s),
+                            Attr::Alt(a) => builder.set_alt(a),
+                            Attr::Title(t) => builder.set_title(t),
+                            Attr::Style(style) => {
+                                for style in style::Iter::new(&style) {
+                                    match style {
+                                        Style::MaxWidth(w) => builder.set_max_width(w),
+                                        Style::Transform(t) => builder.set_transform(t),
+                                        _ => {}
+                                    }
+                                }
+                            }
                             _ => {}
                         }
                     }
@@ -471,40 +976,71 @@ impl HtmlInterpreter {
                 let anchor_name = self.state.pending_anchor.take();
                 if let Some(anchor) = anchor_name {
                     let anchorized = self.state.anchorizer.anchorize(anchor);
-                    self.current_textbox.set_anchor(format!("#{anchorized}"));
+                    self.set_anchor(format!("#{anchorized}"));
                 }
 
                 let align = html::find_align(&tag.attrs);
                 if let Some(align) = self.align_or_inherit(align) {
                     self.current_textbox.set_align(align);
                 }
+                let callout = attr::Iter::new(&tag.attrs).find_map(|attr| match attr {
+                    Attr::Callout(callout) => Some(callout),
+                    _ => None,
+                });
+                let block_quote = self.state.text_options.block_quote;
+                if let Some(callout) = callout.filter(|_| block_quote >= 1) {
+                    self.state.callouts.push((callout, block_quote));
+                    self.state.in_callout_title = true;
+                }
                 self.state.element_stack.push(match tag_name {
                     TagName::Div => InterpreterElement::Div(align),
                     TagName::Paragraph => InterpreterElement::Paragraph(align),
                     _ => unreachable!("Arm matches on Div and Paragraph"),
                 });
             }
+            TagName::Figure => {
+                self.push_current_textbox();
+                let anchor = attr::Iter::new(&tag.attrs).find_map(|attr| match attr {
+                    Attr::Anchor(anchor) => Some(anchor),
+                    _ => None,
+                });
+                if let Some(anchor) = &anchor {
+                    self.state.anchors.insert(anchor.clone());
+                }
+                self.state
+                    .element_stack
+                    .push(InterpreterElement::Figure(anchor));
+            }
+            TagName::FigureCaption => {
+                self.push_current_textbox();
+                self.current_textbox.set_align(Align::Center);
+                self.state.text_options.italic += 1;
+                self.state.text_options.small += 1;
+            }
             TagName::EmphasisOrItalic => self.state.text_options.italic += 1,
             TagName::BoldOrStrong => self.state.text_options.bold += 1,
-            TagName::Code => self.state.text_options.code += 1,
-            TagName::ListItem => {
-                for attr in attr::Iter::new(&tag.attrs) {
-                    self.state.pending_anchor = attr.to_anchor();
+            TagName::Code => {
+                self.state.text_options.code += 1;
+                if self.current_textbox.is_code_block {
+                    // Info strings like ```rust,ignore only have the language before the comma
+                    self.current_textbox.code_language = html::find_language(&tag.attrs)
+                        .map(|lang| lang.split(',').next().unwrap_or_default().to_owned());
                 }
+            }
+            TagName::ListItem => {
+                self.state.pending_anchor =
+                    attr::Iter::new(&tag.attrs).find_map(|attr| attr.to_anchor());
 
-                // Push a pending list prefix based on the list type
-                let iter = self.state.element_iter_mut();
-                let list = iter.rev().find_map(|elem| elem.as_mut_list()).unwrap();
+                // Push a pending list prefix based on the list type and how deeply it's nested
+                let mut lists = self
+                    .state
+                    .element_iter_mut()
+                    .rev()
+                    .filter_map(|elem| elem.as_mut_list());
+                let list = lists.next().unwrap();
+                let depth = 1 + lists.count();
                 if self.current_textbox.texts.is_empty() {
-                    let prefix = match &mut list.ty {
-                        html::ListType::Ordered(index) => {
-                            *index += 1;
-                            format!("{}. ", *index - 1)
-                        }
-                        html::ListType::Unordered => "· ".to_owned(),
-                    };
-
-                    self.state.pending_list_prefix = Some(prefix);
+                    self.state.pending_list_prefix = Some(list.next_marker(depth));
                 }
             }
             TagName::UnorderedList => {
@@ -527,6 +1063,18 @@ impl HtmlInterpreter {
                     .element_stack
                     .push(InterpreterElement::ordered_list(start_index));
             }
+            TagName::DescriptionList => {
+                self.push_current_textbox();
+                self.push_spacer();
+            }
+            TagName::DescriptionTerm => {
+                self.push_current_textbox();
+                self.state.text_options.bold += 1;
+            }
+            TagName::DescriptionDetails => {
+                self.push_current_textbox();
+                self.state.global_indent += DEFAULT_MARGIN / 2.;
+            }
             TagName::Header(header_type) => {
                 let mut align = html::find_align(&tag.attrs);
                 align = self.align_or_inherit(align);
@@ -592,7 +1140,8 @@ impl HtmlInterpreter {
                 self.push_current_textbox();
                 self.push_spacer();
                 let section = Section::bare(self.hidpi_scale);
-                *section.hidden.borrow_mut() = true;
+                let is_open = attr::Iter::new(&tag.attrs).any(|attr| matches!(attr, Attr::IsOpen));
+                *section.hidden.borrow_mut() = !is_open;
                 self.state
                     .element_stack
                     .push(InterpreterElement::Details(section));
@@ -604,7 +1153,22 @@ impl HtmlInterpreter {
             TagName::HorizontalRuler => {
                 self.push_element(Spacer::visible());
             }
-            TagName::Section => {}
+            TagName::Section => {
+                let is_footnotes =
+                    attr::Iter::new(&tag.attrs).any(|attr| matches!(attr, Attr::Footnotes));
+                if is_footnotes {
+                    // Set apart from the rest of the document like GitHub does
+                    self.push_current_textbox();
+                    self.push_element(Spacer::visible());
+                }
+            }
+            TagName::Superscript => self.state.text_options.superscript += 1,
+            TagName::Subscript => self.state.text_options.subscript += 1,
+            TagName::Mark => self.state.text_options.mark += 1,
+            TagName::Keyboard => {
+                self.state.text_options.code += 1;
+                self.state.text_options.keyboard += 1;
+            }
         }
     }
 
@@ -620,6 +1184,13 @@ impl HtmlInterpreter {
             TagName::Underline => self.state.text_options.underline -= 1,
             TagName::Strikethrough => self.state.text_options.strike_through -= 1,
             TagName::Small => self.state.text_options.small -= 1,
+            TagName::Superscript => self.state.text_options.superscript -= 1,
+            TagName::Subscript => self.state.text_options.subscript -= 1,
+            TagName::Mark => self.state.text_options.mark -= 1,
+            TagName::Keyboard => {
+                self.state.text_options.code -= 1;
+                self.state.text_options.keyboard -= 1;
+            }
             TagName::TableHead | TagName::TableBody => {}
             TagName::TableHeader => {
                 let iter = self.state.element_iter_mut();
@@ -661,11 +1232,22 @@ impl HtmlInterpreter {
             TagName::Code => self.state.text_options.code -= 1,
             TagName::Div | TagName::Paragraph => {
                 self.push_current_textbox();
+                self.state.in_callout_title = false;
                 if tag_name == TagName::Paragraph {
                     self.push_spacer();
                 }
                 self.state.element_stack.pop();
             }
+            TagName::Figure => {
+                self.push_current_textbox();
+                self.state.element_stack.pop();
+                self.push_spacer();
+            }
+            TagName::FigureCaption => {
+                self.push_current_textbox();
+                self.state.text_options.italic -= 1;
+                self.state.text_options.small -= 1;
+            }
             TagName::EmphasisOrItalic => self.state.text_options.italic -= 1,
             TagName::BoldOrStrong => self.state.text_options.bold -= 1,
             TagName::Header(header_type) => {
@@ -679,7 +1261,8 @@ impl HtmlInterpreter {
                     .flat_map(|t| t.text.chars())
                     .collect();
                 let anchorized = self.state.anchorizer.anchorize(anchor_name);
-                self.current_textbox.set_anchor(format!("#{anchorized}"));
+                self.set_anchor(format!("#{anchorized}"));
+                self.current_textbox.heading_level = Some(header_type.level());
                 self.push_current_textbox();
                 self.push_spacer();
                 self.state.element_stack.pop();
@@ -703,6 +1286,18 @@ impl HtmlInterpreter {
                     self.push_spacer();
                 }
             }
+            TagName::DescriptionList => {
+                self.push_current_textbox();
+                self.push_spacer();
+            }
+            TagName::DescriptionTerm => {
+                self.push_current_textbox();
+                self.state.text_options.bold -= 1;
+            }
+            TagName::DescriptionDetails => {
+                self.push_current_textbox();
+                self.state.global_indent -= DEFAULT_MARGIN / 2.;
+            }
             TagName::PreformattedText => {
                 self.push_current_textbox();
                 self.push_spacer();
@@ -713,6 +1308,8 @@ impl HtmlInterpreter {
                 self.push_current_textbox();
                 self.state.text_options.block_quote -= 1;
                 self.state.global_indent -= DEFAULT_MARGIN / 2.;
+                let block_quote = self.state.text_options.block_quote;
+                self.state.callouts.retain(|&(_, nest)| nest <= block_quote);
                 self.current_textbox.clear_quote_block();
                 if self.state.global_indent == 0. {
                     self.push_spacer();
@@ -724,7 +1321,17 @@ impl HtmlInterpreter {
             }
             TagName::Details => {
                 self.push_current_textbox();
-                if let Some(InterpreterElement::Details(section)) = self.state.element_stack.pop() {
+                if let Some(InterpreterElement::Details(mut section)) =
+                    self.state.element_stack.pop()
+                {
+                    // Browsers fall back to a summary of "Details", which keeps the section from
+                    // getting stuck closed
+                    if section.summary.is_none() {
+                        let text_native_color = self.native_color(self.theme.text_color);
+                        let text =
+                            Text::new("Details".to_owned(), self.hidpi_scale, text_native_color);
+                        *section.summary = Some(Positioned::new(self.new_textbox(vec![text])));
+                    }
                     self.push_element(section);
                 }
                 self.push_spacer();
@@ -769,6 +1376,9 @@ impl HtmlInterpreter {
     }
 
     fn process_character_tokens(&mut self, mut str: String) {
+        if self.current_textbox.source_line.is_none() && !str.trim().is_empty() {
+            self.current_textbox.source_line = self.source_line();
+        }
         let text_native_color = self.native_color(self.theme.text_color);
         if str == "\n" {
             if self.state.text_options.pre_formatted >= 1 {
@@ -791,8 +1401,7 @@ impl HtmlInterpreter {
             }
             if let Some((row, newline_counter)) = self.state.inline_images.take() {
                 if newline_counter == 0 {
-                    self.push_element(row);
-                    self.push_spacer();
+                    self.push_inline_images(row);
                 } else {
                     self.state.inline_images = Some((row, newline_counter - 1));
                 }
@@ -813,8 +1422,30 @@ impl HtmlInterpreter {
             if self.current_textbox.texts.is_empty() && self.state.text_options.pre_formatted == 0 {
                 str = str.trim_start().to_owned();
             }
+            if self.state.text_options.code == 0 {
+                if let Cow::Owned(expanded) = html::expand_shortcodes(&str) {
+                    str = expanded;
+                }
+            }
+            let mut script = None;
+            if self.state.text_options.superscript >= 1 {
+                // Characters with a form of their own fit in better than scaled down ones, but that
+                // only works out when they all have one
+                match str.chars().map(html::superscript).collect() {
+                    Some(raised) => str = raised,
+                    None => script = Some(Script::Super),
+                }
+            } else if self.state.text_options.subscript >= 1 {
+                match str.chars().map(html::subscript).collect() {
+                    Some(lowered) => str = lowered,
+                    None => script = Some(Script::Sub),
+                }
+            }
 
             let mut text = Text::new(str, self.hidpi_scale, text_native_color);
+            if let Some(script) = script {
+                text = text.with_script(script);
+            }
             if let Some(prefix) = self.state.pending_list_prefix.take() {
                 if self.current_textbox.texts.is_empty() {
                     self.current_textbox.texts.push(
@@ -825,6 +1456,12 @@ impl HtmlInterpreter {
             if self.state.text_options.block_quote >= 1 {
                 self.current_textbox
                     .set_quote_block(self.state.text_options.block_quote);
+                self.current_textbox.callout = self.state.callouts.last().copied();
+            }
+            if self.state.in_callout_title {
+                if let Some(&(callout, _)) = self.state.callouts.last() {
+                    text = text.with_color(self.native_color(callout.color(&self.theme)));
+                }
             }
             if self.state.text_options.code >= 1 {
                 text = text
@@ -842,7 +1479,11 @@ impl HtmlInterpreter {
             }
             for elem in self.state.element_stack.iter().rev() {
                 if let InterpreterElement::Header(header) = elem {
-                    self.current_textbox.font_size = header.ty.text_size();
+                    let scale = self.heading_scales[usize::from(header.ty.level()) - 1];
+                    self.current_textbox.font_size = self.font_size * scale;
+                    if self.state.text_options.code == 0 {
+                        text = text.with_color(self.native_color(self.theme.heading_color));
+                    }
                     text = text.make_bold(true);
                     break;
                 }
@@ -863,8 +1504,14 @@ impl HtmlInterpreter {
             if self.state.text_options.strike_through >= 1 {
                 text = text.make_striked(true);
             }
+            if self.state.text_options.mark >= 1 {
+                text = text.make_marked(true);
+            }
+            if self.state.text_options.keyboard >= 1 {
+                text = text.make_key_cap(true);
+            }
             if self.state.text_options.small >= 1 {
-                self.current_textbox.font_size = 12.;
+                self.current_textbox.font_size = self.font_size * SMALL_FONT_SCALE;
             }
             self.current_textbox.texts.push(text);
         }
@@ -874,27 +1521,49 @@ impl HtmlInterpreter {
 impl TokenSink for HtmlInterpreter {
     type Handle = ();
 
-    fn process_token(&mut self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+    fn process_token(&mut self, token: Token, line_number: u64) -> TokenSinkResult<()> {
         if !self.should_queue.load(AtomicOrdering::Relaxed) {
             self.stopped = true;
         }
         if self.stopped {
             return TokenSinkResult::Continue;
         }
+        self.state.html_line = line_number;
         match token {
-            Token::TagToken(tag) => match tag.kind {
-                TagKind::StartTag => self.process_start_tag(tag),
-                TagKind::EndTag => self.process_end_tag(tag),
-            },
+            Token::TagToken(tag) => {
+                self.track_open_tags(&tag);
+                match tag.kind {
+                    TagKind::StartTag => {
+                        self.enter_direction(&tag);
+                        self.process_start_tag(tag);
+                    }
+                    TagKind::EndTag => {
+                        let name = tag.name.to_string();
+                        self.process_end_tag(tag);
+                        self.leave_direction(&name);
+                    }
+                }
+            }
             Token::CharacterTokens(str) => self.process_character_tokens(str.to_string()),
             Token::EOFToken => {
                 self.push_current_textbox();
+                self.finish_diagnostics();
                 self.should_queue.store(false, AtomicOrdering::Relaxed);
-                self.first_pass = false;
                 self.window.finished_single_doc();
             }
-            Token::ParseError(err) => tracing::warn!("HTML parser emitted error: {err}"),
-            Token::DoctypeToken(_) | Token::CommentToken(_) | Token::NullCharacterToken => {}
+            Token::ParseError(err) => {
+                tracing::warn!("HTML parser emitted error: {err}");
+                self.diagnose(format!("Malformed HTML: {err}"));
+            }
+            Token::CommentToken(comment) => {
+                if let Some(line) = parse_source_line_marker(&comment) {
+                    self.state.source_anchor = Some((line, line_number));
+                } else if let Some(text) = parse_note(&comment) {
+                    let position = self.state.queued;
+                    self.notes.lock().unwrap().push(Note { position, text });
+                }
+            }
+            Token::DoctypeToken(_) | Token::NullCharacterToken => {}
         }
         TokenSinkResult::Continue
     }
@@ -0,0 +1,215 @@
+//! Little squares of color that get shown before color literals in code
+
+use crate::text::Text;
+
+use glyphon::FamilyOwned;
+
+const SWATCH: &str = "\u{25a0} ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ColorLiteral {
+    /// Byte offset of where the literal starts
+    start: usize,
+    rgb: u32,
+    alpha: f32,
+}
+
+/// Puts a swatch in front of every color literal within monospace text
+///
+/// Literals can be split across several texts (syntax highlighting loves to do that), so the texts
+/// get searched as a whole and split up wherever a swatch needs to go
+pub fn insert(texts: &mut Vec<Text>, to_native: impl Fn(u32) -> [f32; 4]) {
+    let mut joined = String::new();
+    let mut starts = Vec::with_capacity(texts.len());
+    for text in texts.iter() {
+        starts.push(joined.len());
+        joined.push_str(&text.text);
+    }
+
+    // Work backwards, so that splitting texts doesn't shift around any of the earlier literals
+    for literal in find_colors(&joined).into_iter().rev() {
+        let index = starts.partition_point(|&start| start <= literal.start) - 1;
+        let text = &mut texts[index];
+        if text.font_family != FamilyOwned::Monospace {
+            continue;
+        }
+
+        let mut color = to_native(literal.rgb);
+        color[3] = literal.alpha;
+        let swatch = Text::new(SWATCH.to_owned(), text.hidpi_scale, color)
+            .with_color(color)
+            .with_family(FamilyOwned::Monospace);
+
+        let split_at = literal.start - starts[index];
+        if split_at == 0 {
+            texts.insert(index, swatch);
+        } else {
+            let mut tail = text.clone();
+            tail.text = text.text.split_off(split_at);
+            texts.insert(index + 1, tail);
+            texts.insert(index + 1, swatch);
+        }
+    }
+}
+
+/// Finds CSS-style `#rgb[a]`, `#rrggbb[aa]`, and `rgb[a](...)` color literals
+fn find_colors(s: &str) -> Vec<ColorLiteral> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut colors = Vec::new();
+    let mut prev = None;
+    for (start, c) in s.char_indices() {
+        let at_boundary = prev.map_or(true, |prev: char| !is_word_char(prev) && prev != '#');
+        prev = Some(c);
+        if !at_boundary {
+            continue;
+        }
+
+        let rest = &s[start..];
+        let parsed = if c == '#' {
+            parse_hex(&rest[1..])
+        } else if c == 'r' || c == 'R' {
+            parse_rgb(rest)
+        } else {
+            None
+        };
+        let Some((len, rgb, alpha)) = parsed else {
+            continue;
+        };
+        let ends_at_boundary = s[start + len..]
+            .chars()
+            .next()
+            .map_or(true, |next| !is_word_char(next));
+        if ends_at_boundary {
+            colors.push(ColorLiteral { start, rgb, alpha });
+        }
+    }
+
+    colors
+}
+
+/// Returns the literal's length (including the `#`), its color, and its alpha
+fn parse_hex(s: &str) -> Option<(usize, u32, f32)> {
+    let digits = s.bytes().take_while(u8::is_ascii_hexdigit).count();
+    let hex = &s[..digits];
+    let channels: Vec<u8> = match digits {
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8 * 0x11))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..digits)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    let rgb = u32::from_be_bytes([0, channels[0], channels[1], channels[2]]);
+    let alpha = channels.get(3).map_or(1., |&a| f32::from(a) / 255.);
+    Some((digits + 1, rgb, alpha))
+}
+
+/// Returns the literal's length, its color, and its alpha
+fn parse_rgb(s: &str) -> Option<(usize, u32, f32)> {
+    let lower = s.get(..5)?.to_ascii_lowercase();
+    let args_start = if lower.starts_with("rgba(") {
+        5
+    } else if lower.starts_with("rgb(") {
+        4
+    } else {
+        return None;
+    };
+    let args_len = s[args_start..].find(')')?;
+    let args = &s[args_start..args_start + args_len];
+
+    let parts: Vec<_> = args
+        .split([',', ' ', '/'])
+        .filter(|part| !part.is_empty())
+        .collect();
+    let (channels, alpha) = match parts.as_slice() {
+        [r, g, b] => ([r, g, b], None),
+        [r, g, b, a] => ([r, g, b], Some(a)),
+        _ => return None,
+    };
+
+    let mut rgb = 0;
+    for channel in channels {
+        let value = match channel.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? * 2.55,
+            None => channel.parse::<f32>().ok()?,
+        };
+        rgb = rgb << 8 | value.round().clamp(0., 255.) as u32;
+    }
+    let alpha = match alpha {
+        Some(alpha) => match alpha.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? / 100.,
+            None => alpha.parse::<f32>().ok()?,
+        }
+        .clamp(0., 1.),
+        None => 1.,
+    };
+
+    Some((args_start + args_len + 1, rgb, alpha))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors(s: &str) -> Vec<(usize, u32, f32)> {
+        find_colors(s)
+            .into_iter()
+            .map(|ColorLiteral { start, rgb, alpha }| (start, rgb, alpha))
+            .collect()
+    }
+
+    #[test]
+    fn hex_literals() {
+        assert_eq!(colors("#ff8800"), [(0, 0xff8800, 1.)]);
+        assert_eq!(colors("color: #F80;"), [(7, 0xff8800, 1.)]);
+        assert_eq!(colors("#ff880080"), [(0, 0xff8800, 128. / 255.)]);
+        assert_eq!(colors("#f808"), [(0, 0xff8800, 0x88 as f32 / 255.)]);
+        // Not colors
+        assert_eq!(colors("#define"), []);
+        assert_eq!(colors("#ff88001"), []);
+        assert_eq!(colors("a#fff"), []);
+        assert_eq!(colors("##fff"), []);
+    }
+
+    #[test]
+    fn rgb_literals() {
+        assert_eq!(colors("rgb(255, 136, 0)"), [(0, 0xff8800, 1.)]);
+        assert_eq!(colors("RGBA(255 136 0 / 50%)"), [(0, 0xff8800, 0.5)]);
+        assert_eq!(colors("x: rgba(100%,0,0,0.25)"), [(3, 0xff0000, 0.25)]);
+        assert_eq!(colors("rgb(1, 2)"), []);
+        assert_eq!(colors("torgb(1, 2, 3)"), []);
+        assert_eq!(colors("rgb(1, 2, 3"), []);
+    }
+
+    #[test]
+    fn split_across_texts() {
+        let code =
+            |s: &str| Text::new(s.to_owned(), 1., [0.; 4]).with_family(FamilyOwned::Monospace);
+        let mut texts = vec![code("let c = rg"), code("b(0, 0, 255); // #fff")];
+        insert(&mut texts, |rgb| [rgb as f32, 0., 0., 1.]);
+
+        let pieces: Vec<_> = texts.iter().map(|text| text.text.as_str()).collect();
+        assert_eq!(
+            pieces,
+            [
+                "let c = ",
+                SWATCH,
+                "rg",
+                "b(0, 0, 255); // ",
+                SWATCH,
+                "#fff"
+            ]
+        );
+        assert_eq!(texts[1].color, Some([255., 0., 0., 1.]));
+
+        // Regular text is left alone
+        let mut texts = vec![Text::new("#fff".to_owned(), 1., [0.; 4])];
+        insert(&mut texts, |_| [0.; 4]);
+        assert_eq!(texts.len(), 1);
+    }
+}
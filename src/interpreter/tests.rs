@@ -7,12 +7,15 @@ use std::sync::{
 use std::time::{Duration, Instant};
 use std::{env, thread};
 
-use super::{HtmlInterpreter, ImageCallback, WindowInteractor};
+use super::{Document, HtmlInterpreter, ImageCallback, WindowInteractor};
+use crate::callout::Callout;
 use crate::color::{Theme, ThemeDefaults};
+use crate::diagnostics::Diagnostic;
+use crate::diff::DiffLine;
 use crate::image::{Image, ImageData};
-use crate::opts::ResolvedTheme;
+use crate::opts::{CodeBlockOptions, ImageCaptions, ResolvedTheme};
 use crate::test_utils::init_test_log;
-use crate::utils::Align;
+use crate::utils::{Align, Float, MarkdownOptions};
 use crate::{Element, ImageCache};
 
 use base64::prelude::*;
@@ -51,7 +54,7 @@ impl WindowInteractor for DummyWindow {
         self.0.dec();
     }
 
-    fn request_redraw(&self) {}
+    fn finished_chunk(&self) {}
 
     // The counter is inc'd for each callback we create and internally dec's when it's called
     fn image_callback(&self) -> Box<dyn ImageCallback + Send> {
@@ -72,6 +75,11 @@ struct InterpreterOpts {
     theme: Theme,
     fail_after: Duration,
     color_scheme: Option<ResolvedTheme>,
+    color_swatches: bool,
+    image_captions: ImageCaptions,
+    markdown: MarkdownOptions,
+    code_blocks: CodeBlockOptions,
+    file_path: PathBuf,
 }
 
 impl Default for InterpreterOpts {
@@ -80,6 +88,11 @@ impl Default for InterpreterOpts {
             theme: Theme::light_default(),
             fail_after: Duration::from_secs(8),
             color_scheme: None,
+            color_swatches: false,
+            image_captions: ImageCaptions::Off,
+            markdown: MarkdownOptions::default(),
+            code_blocks: CodeBlockOptions::default(),
+            file_path: PathBuf::from("does_not_exist"),
         }
     }
 }
@@ -94,6 +107,41 @@ impl InterpreterOpts {
         self
     }
 
+    fn color_swatches(mut self, color_swatches: bool) -> Self {
+        self.color_swatches = color_swatches;
+        self
+    }
+
+    fn image_captions(mut self, image_captions: ImageCaptions) -> Self {
+        self.image_captions = image_captions;
+        self
+    }
+
+    fn cross_references(mut self, cross_references: bool) -> Self {
+        self.markdown.cross_references = cross_references;
+        self
+    }
+
+    fn smart_punctuation(mut self, smart_punctuation: bool) -> Self {
+        self.markdown.smart_punctuation = smart_punctuation;
+        self
+    }
+
+    fn front_matter_table(mut self, front_matter_table: bool) -> Self {
+        self.markdown.front_matter_table = front_matter_table;
+        self
+    }
+
+    fn code_blocks(mut self, code_blocks: CodeBlockOptions) -> Self {
+        self.code_blocks = code_blocks;
+        self
+    }
+
+    fn file_path<P: Into<PathBuf>>(mut self, file_path: P) -> Self {
+        self.file_path = file_path.into();
+        self
+    }
+
     fn set_color_scheme(&mut self, color_scheme: ResolvedTheme) {
         self.color_scheme = Some(color_scheme);
     }
@@ -103,14 +151,18 @@ impl InterpreterOpts {
             theme,
             fail_after: _,
             color_scheme,
+            color_swatches,
+            image_captions,
+            markdown,
+            code_blocks: _,
+            file_path,
         } = self;
         let element_queue = Arc::default();
         let surface_format = TextureFormat::Bgra8UnormSrgb;
         let hidpi_scale = 1.0;
-        let file_path = PathBuf::from("does_not_exist");
         let image_cache = ImageCache::default();
         let window = Box::new(DummyWindow(counter));
-        let interpreter = HtmlInterpreter::new_with_interactor(
+        let mut interpreter = HtmlInterpreter::new_with_interactor(
             Arc::clone(&element_queue),
             theme,
             surface_format,
@@ -120,6 +172,9 @@ impl InterpreterOpts {
             window,
             color_scheme,
         );
+        interpreter.color_swatches = color_swatches;
+        interpreter.image_captions = image_captions;
+        interpreter.markdown = markdown;
 
         (interpreter, element_queue)
     }
@@ -160,12 +215,31 @@ fn interpret_md(text: &str) -> VecDeque<Element> {
 }
 
 fn interpret_md_with_opts(text: &str, opts: InterpreterOpts) -> VecDeque<Element> {
+    interpret_md_with_diagnostics(text, opts).0
+}
+
+fn interpret_md_with_diagnostics(
+    text: &str,
+    opts: InterpreterOpts,
+) -> (VecDeque<Element>, Vec<Diagnostic>) {
     let fail_after = opts.fail_after;
+    let file_path = opts.file_path.clone();
+    let code_blocks = opts.code_blocks;
 
     let counter = AtomicCounter::new();
     let (interpreter, element_queue) = opts.finish(counter.clone());
+    let diagnostics = Arc::clone(&interpreter.diagnostics);
     let (md_tx, md_rx) = mpsc::channel();
-    md_tx.send(text.to_owned()).unwrap();
+    md_tx
+        .send(Document {
+            file_path,
+            contents: text.to_owned(),
+            theme: None,
+            code_blocks,
+            line_spacing: 1.0,
+            font_opts: Default::default(),
+        })
+        .unwrap();
     let interpreter_handle = std::thread::spawn(|| {
         interpreter.interpret_md(md_rx);
     });
@@ -181,7 +255,8 @@ fn interpret_md_with_opts(text: &str, opts: InterpreterOpts) -> VecDeque<Element
     }
 
     let mut elements_queue = element_queue.lock().unwrap();
-    std::mem::take(&mut *elements_queue)
+    let diagnostics = std::mem::take(&mut *diagnostics.lock().unwrap());
+    (std::mem::take(&mut *elements_queue), diagnostics)
 }
 
 #[macro_export]
@@ -206,6 +281,7 @@ macro_rules! snapshot_interpreted_elements {
                 let htmlified = $crate::utils::markdown_to_html(
                     text,
                     opts.theme.code_highlighter.clone(),
+                    opts.markdown,
                 );
                 let description = format!(" --- md\n\n{text}\n\n --- html\n\n{htmlified}");
 
@@ -219,7 +295,6 @@ macro_rules! snapshot_interpreted_elements {
     }
 }
 
-#[allow(unused)]
 const FOOTNOTES_LIST_PREFIX: &str = "\
 This sentence[^1] has two footnotes[^2]
 
@@ -341,6 +416,12 @@ _italic_
 ~~strikethrough~~
 
 <u>underline</u>
+
+<mark>mark</mark>
+
+H<sub>2</sub>O and C<sub>n</sub>H<sub>2n+2</sub> at T<sub>crit</sub>
+
+x<sup>2</sup> from <sup>Fig. 1</sup>
 ";
 
 // TODO: this still has all sorts of issues (the anchor and extra whitespace)
@@ -358,7 +439,7 @@ const HEADER_INHERIT_ALIGN: &str = r##"
 </div>"##;
 
 snapshot_interpreted_elements!(
-    // (footnotes_list_prefix, FOOTNOTES_LIST_PREFIX),
+    (footnotes_list_prefix, FOOTNOTES_LIST_PREFIX),
     (checklist_has_no_text_prefix, CHECKLIST_HAS_NO_TEXT_PREFIX),
     (code_block_bg_color, CODE_BLOCK_BG_COLOR),
     (bare_link_gets_autolinked, BARE_LINK_GETS_AUTOLINKED),
@@ -369,7 +450,6 @@ snapshot_interpreted_elements!(
     (ordered_list_in_unordered, ORDERED_LIST_IN_UNORDERED),
     (para_in_ordered_list, PARA_IN_ORDERED_LIST),
     (code_in_ordered_list, CODE_IN_ORDERED_LIST),
-    (yaml_frontmatter, YAML_FRONTMATTER),
     (aligned_table, ALIGNED_TABLE),
     (unique_anchors, UNIQUE_ANCHORS),
     (kbd_tag, KBD_TAG),
@@ -406,6 +486,23 @@ snapshot_interpreted_elements!(
     (num_is_bold, NUM_IS_BOLD),
 );
 
+const COLOR_SWATCHES: &str = "\
+Brand orange is `#ff8800` and not `text #80`
+
+```css
+a { color: rgba(0, 0, 255, 50%); }
+```";
+
+snapshot_interpreted_elements!(
+    InterpreterOpts::new().color_swatches(true),
+    (color_swatches, COLOR_SWATCHES),
+);
+
+snapshot_interpreted_elements!(
+    InterpreterOpts::new().front_matter_table(true),
+    (yaml_frontmatter, YAML_FRONTMATTER),
+);
+
 struct File {
     url_path: String,
     mime: String,
@@ -473,6 +570,49 @@ fn centered_image_with_size_align_and_link() {
     });
 }
 
+#[test]
+fn relative_image_sizes() {
+    init_test_log();
+
+    let logo = include_bytes!("../../assets/test_data/bun_logo.png");
+    let logo_path = "/bun_logo.png";
+    let (_server, server_url) = mock_file_server(&[File::new(logo_path, "image/png", logo)]);
+    let logo_url = server_url + logo_path;
+
+    let text = format!(
+        r#"<p align="center"><img src="{logo_url}" width="50%" style="border: 0; max-width: 300px;"></p>"#
+    );
+    let elements = interpret_md(&text);
+    let Some(Element::Image(image)) = elements.front() else {
+        panic!("Expected an image: {elements:#?}");
+    };
+    assert_eq!(
+        format!("{:?} {:?}", image.size, image.max_width),
+        "Some(PercentWidth(Percent(50.0))) Some(Px(Px(300)))"
+    );
+}
+
+#[test]
+fn transformed_images() {
+    init_test_log();
+
+    let logo = include_bytes!("../../assets/test_data/bun_logo.png");
+    let logo_path = "/bun_logo.png";
+    let (_server, server_url) = mock_file_server(&[File::new(logo_path, "image/png", logo)]);
+    let logo_url = server_url + logo_path;
+
+    let text = format!(
+        r#"<p align="center"><img src="{logo_url}" style="transform: rotate(90deg) scaleX(-1);"></p>"#
+    );
+    let elements = interpret_md(&text);
+    let Some(Element::Image(image)) = elements.front() else {
+        panic!("Expected an image: {elements:#?}");
+    };
+    let transform = image.transform.expect("Missing transform");
+    assert!(transform.swaps_sides());
+    assert_eq!(transform, "rotate(90deg) scaleX(-1)".parse().unwrap());
+}
+
 #[test]
 fn image_loading_fails_gracefully() {
     init_test_log();
@@ -504,12 +644,201 @@ fn image_loading_fails_gracefully() {
     });
 }
 
+#[test]
+fn image_captions() {
+    // Boils the elements down to just what matters for captions
+    fn summarize(elements: &VecDeque<Element>) -> Vec<String> {
+        elements
+            .iter()
+            .filter_map(|element| match element {
+                Element::Image(image) => Some(format!("image {:?}", image.is_aligned)),
+                Element::Row(row) => Some(format!("row of {}", row.elements.len())),
+                Element::TextBox(textbox) => {
+                    let text: String = textbox.texts.iter().map(|t| t.text.as_str()).collect();
+                    let italic = textbox.texts.iter().all(|t| t.is_italic);
+                    Some(format!("text {text:?} {:?} italic={italic}", textbox.align))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    init_test_log();
+
+    let logo = include_bytes!("../../assets/test_data/bun_logo.png");
+    let logo_path = "/bun_logo.png";
+    let (_server, server_url) = mock_file_server(&[File::new(logo_path, "image/png", logo)]);
+    let logo_url = server_url + logo_path;
+
+    let lone_image = format!(r#"![The logo]({logo_url} "Bun's logo")"#);
+    let with_title = InterpreterOpts::new().image_captions(ImageCaptions::Title);
+    assert_eq!(
+        summarize(&interpret_md_with_opts(&lone_image, with_title)),
+        [
+            "image Some(Center)",
+            r#"text "Bun's logo" Center italic=true"#
+        ],
+    );
+    let with_alt = InterpreterOpts::new().image_captions(ImageCaptions::Alt);
+    assert_eq!(
+        summarize(&interpret_md_with_opts(&lone_image, with_alt)),
+        [
+            "image Some(Center)",
+            r#"text "The logo" Center italic=true"#
+        ],
+    );
+    let without = InterpreterOpts::new();
+    assert_eq!(
+        summarize(&interpret_md_with_opts(&lone_image, without)),
+        ["row of 1"],
+    );
+
+    // Images sharing a line, like a row of badges, don't get captions
+    let badges = format!(r#"![one]({logo_url} "One") ![two]({logo_url} "Two")"#);
+    let with_title = InterpreterOpts::new().image_captions(ImageCaptions::Title);
+    assert_eq!(
+        summarize(&interpret_md_with_opts(&badges, with_title)),
+        ["row of 2"],
+    );
+
+    // An emphasized line right after an image is its caption, and it wins over the title
+    let figure = format!("![The logo]({logo_url} \"Bun's logo\")\n*Figure 1: **Bun***");
+    let with_title = InterpreterOpts::new().image_captions(ImageCaptions::Title);
+    assert_eq!(
+        summarize(&interpret_md_with_opts(&figure, with_title)),
+        [
+            "image Some(Center)",
+            r#"text "Figure 1: Bun" Center italic=true"#
+        ],
+    );
+
+    // Hand written figures use their `<figcaption>` instead of the title
+    let html_figure = format!(
+        "<figure>\n<img src=\"{logo_url}\" title=\"Bun's logo\">\n\
+        <figcaption>The <b>Bun</b> logo</figcaption>\n</figure>"
+    );
+    let with_title = InterpreterOpts::new().image_captions(ImageCaptions::Title);
+    assert_eq!(
+        summarize(&interpret_md_with_opts(&html_figure, with_title)),
+        [
+            "image Some(Center)",
+            r#"text "The Bun logo" Center italic=true"#
+        ],
+    );
+}
+
+#[test]
+fn floating_images() {
+    init_test_log();
+
+    let logo = include_bytes!("../../assets/test_data/bun_logo.png");
+    let logo_path = "/bun_logo.png";
+    let (_server, server_url) = mock_file_server(&[File::new(logo_path, "image/png", logo)]);
+    let logo_url = server_url + logo_path;
+
+    let floats = |html: &str| -> Vec<_> {
+        interpret_md(html)
+            .iter()
+            .filter_map(|element| match element {
+                Element::Image(image) => Some(image.float),
+                Element::Row(_) => Some(None),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let left = format!("<img src=\"{logo_url}\" align=\"left\">\n\nBeside the logo");
+    assert_eq!(floats(&left), [Some(Float::Left)]);
+    let right = format!("<img src=\"{logo_url}\" align=\"right\">");
+    assert_eq!(floats(&right), [Some(Float::Right)]);
+    let center = format!("<img src=\"{logo_url}\" align=\"center\">");
+    assert_eq!(floats(&center), [None]);
+    // Only the image's own alignment floats it, not its paragraph's
+    let inherited = format!("<p align=\"left\"><img src=\"{logo_url}\"></p>");
+    assert_eq!(floats(&inherited), [None]);
+}
+
+#[test]
+fn linked_images() {
+    init_test_log();
+
+    let logo = include_bytes!("../../assets/test_data/bun_logo.png");
+    let logo_path = "/bun_logo.png";
+    let (_server, server_url) = mock_file_server(&[File::new(logo_path, "image/png", logo)]);
+    let logo_url = server_url + logo_path;
+
+    fn image_link(element: &Element) -> Vec<Option<String>> {
+        match element {
+            Element::Image(image) => vec![image.is_link.clone()],
+            Element::Row(row) => row
+                .elements
+                .iter()
+                .flat_map(|element| image_link(&element.inner))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    let md = format!(
+        "[![ci]({logo_url})](https://example.com/ci) ![plain]({logo_url}) \
+        [![docs]({logo_url})](https://example.com/docs)"
+    );
+    assert_eq!(
+        interpret_md(&md)
+            .iter()
+            .flat_map(image_link)
+            .collect::<Vec<_>>(),
+        [
+            Some("https://example.com/ci".to_owned()),
+            None,
+            Some("https://example.com/docs".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn cross_references() {
+    init_test_log();
+
+    let logo = include_bytes!("../../assets/test_data/bun_logo.png");
+    let logo_path = "/bun_logo.png";
+    let (_server, server_url) = mock_file_server(&[File::new(logo_path, "image/png", logo)]);
+    let logo_url = server_url + logo_path;
+
+    let md = format!("See [@fig:logo]\n\n![The logo]({logo_url}){{#fig:logo}}");
+    let opts = InterpreterOpts::new().cross_references(true);
+    let elements = interpret_md_with_opts(&md, opts);
+
+    let reference = elements.iter().find_map(|element| match element {
+        Element::TextBox(textbox) => textbox.texts.iter().find(|text| text.link.is_some()),
+        _ => None,
+    });
+    let reference = reference.expect("Reference turns into a link");
+    assert_eq!(reference.text, "fig. 1");
+    assert_eq!(reference.link.as_deref(), Some("#fig:logo"));
+
+    let image = elements
+        .iter()
+        .find_map(|element| match element {
+            Element::Image(image) => Some(image),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(image.is_anchor.as_deref(), Some("#fig:logo"));
+    let caption = elements.iter().any(|element| {
+        matches!(element, Element::TextBox(textbox)
+            if textbox.texts.iter().map(|t| t.text.as_str()).collect::<String>()
+                == "Figure 1: The logo")
+    });
+    assert!(caption);
+}
+
 // Check to see that each paths are used for their respective color-schemes
 #[test]
 fn picture_dark_light() {
     fn find_image(elements: &VecDeque<Element>) -> Option<&Image> {
         elements.iter().find_map(|element| match element {
-            crate::Element::Image(image) => Some(image),
+            crate::Element::Image(image) => Some(image.as_ref()),
             _ => None,
         })
     }
@@ -581,3 +910,471 @@ fn picture_dark_light() {
         );
     }
 }
+
+#[test]
+fn relative_image_paths() {
+    init_test_log();
+
+    let dir = tempfile::tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    let assets = dir.path().join("assets");
+    std::fs::create_dir_all(&docs).unwrap();
+    std::fs::create_dir_all(&assets).unwrap();
+    let logo = include_bytes!("../../assets/test_data/bun_logo.png");
+    std::fs::write(assets.join("bun logo.png"), logo).unwrap();
+
+    let text = "![Logo](../assets/bun%20logo.png?raw=true)";
+    let opts = InterpreterOpts::new().file_path(docs.join("README.md"));
+    let elements = interpret_md_with_opts(text, opts);
+    let image = elements
+        .iter()
+        .find_map(|element| match element {
+            Element::Row(row) => row
+                .elements
+                .iter()
+                .find_map(|element| match &element.inner {
+                    Element::Image(image) => Some(image),
+                    _ => None,
+                }),
+            _ => None,
+        })
+        .unwrap();
+    let byte_len = image
+        .image_data
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|image_data| image_data.to_bytes().len());
+    // The logo rather than the broken image placeholder
+    assert_eq!(byte_len, Some(396 * 347 * 4));
+}
+
+#[test]
+fn data_uri_image() {
+    init_test_log();
+
+    let text = "![Gray](data:image/webp;base64,UklGRhoAAABXRUJQVlA4TA4AAAAvAAAAAM1VICICzYyIBA==)";
+    insta::assert_debug_snapshot!(interpret_md(text));
+}
+
+#[test]
+fn shortcodes_in_html() {
+    init_test_log();
+
+    let text = "<p>Ship it :rocket: at 10:30:00 :not_an_emoji:</p>\n\n<p><code>:rocket:</code></p>";
+    let texts: Vec<_> = interpret_md(text)
+        .iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some(
+                text_box
+                    .texts
+                    .iter()
+                    .map(|text| text.text.as_str())
+                    .collect::<String>(),
+            ),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(texts, ["Ship it 🚀 at 10:30:00 :not_an_emoji:", ":rocket:"]);
+}
+
+#[test]
+fn callouts() {
+    init_test_log();
+
+    let text = "\
+> [!WARNING]
+> Mind the gap
+>
+> > Nested quote
+
+> Plain quote
+";
+    let quotes: Vec<_> = interpret_md(text)
+        .iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some((
+                text_box
+                    .texts
+                    .iter()
+                    .map(|text| text.text.as_str())
+                    .collect::<String>(),
+                text_box.callout,
+            )),
+            _ => None,
+        })
+        .collect();
+    let warning = Some((Callout::Warning, 1));
+    assert_eq!(
+        quotes,
+        [
+            ("⚠ Warning".to_owned(), warning),
+            ("Mind the gap".to_owned(), warning),
+            ("Nested quote".to_owned(), warning),
+            ("Plain quote".to_owned(), None),
+        ]
+    );
+}
+
+#[test]
+fn nesting_depth() {
+    init_test_log();
+
+    let text = "\
+> outer
+> > inner
+
+- one
+  - two
+    - three
+      - four
+
+1. one
+   1. two
+      1. three
+      2. three again
+";
+    let boxes: Vec<_> = interpret_md(text)
+        .into_iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some(text_box),
+            _ => None,
+        })
+        .collect();
+
+    let quotes: Vec<_> = boxes
+        .iter()
+        .filter_map(|text_box| Some((text_box.is_quote_block?, text_box.indent)))
+        .collect();
+    assert_eq!(quotes.len(), 2);
+    assert_eq!((quotes[0].0, quotes[1].0), (1, 2));
+    assert!(
+        quotes[0].1 < quotes[1].1,
+        "Inner quotes get indented further"
+    );
+
+    let markers: Vec<_> = boxes
+        .iter()
+        .filter(|text_box| text_box.is_quote_block.is_none())
+        .map(|text_box| text_box.texts[0].text.as_str())
+        .collect();
+    assert_eq!(
+        markers,
+        ["· ", "◦ ", "▪ ", "· ", "1. ", "a. ", "i. ", "ii. "]
+    );
+}
+
+#[test]
+fn definition_lists() {
+    init_test_log();
+
+    let md = "\
+Apple
+: A red fruit
+";
+    // Comrak only picks these up on its own with a blank line after the term
+    let spaced = "Plum\n\n: A purple fruit\n";
+    let html = "<dl><dt>Pear</dt><dd>A green fruit</dd></dl>";
+    for text in [md, spaced, html] {
+        let boxes: Vec<_> = interpret_md(text)
+            .into_iter()
+            .filter_map(|element| match element {
+                Element::TextBox(text_box) => Some(text_box),
+                _ => None,
+            })
+            .collect();
+        let [term, definition] = &boxes[..] else {
+            panic!("Expected a term and its definition: {boxes:#?}");
+        };
+        assert!(term.texts.iter().all(|text| text.is_bold));
+        assert_eq!(term.indent, 0.);
+        assert!(definition.texts.iter().all(|text| !text.is_bold));
+        assert!(definition.indent > 0.);
+    }
+}
+
+#[test]
+fn smart_punctuation() {
+    init_test_log();
+
+    let text = "\"Wait\" -- it's 1--2... --- done";
+    let shown = |opts| -> String {
+        interpret_md_with_opts(text, opts)
+            .into_iter()
+            .filter_map(|element| match element {
+                Element::TextBox(text_box) => Some(text_box.texts),
+                _ => None,
+            })
+            .flatten()
+            .map(|text| text.text)
+            .collect()
+    };
+    assert_eq!(shown(InterpreterOpts::new()), "“Wait” – it’s 1–2… — done");
+    assert_eq!(shown(InterpreterOpts::new().smart_punctuation(false)), text);
+}
+
+#[test]
+fn front_matter_is_hidden_by_default() {
+    init_test_log();
+
+    let elements = interpret_md(YAML_FRONTMATTER);
+    assert!(!elements
+        .iter()
+        .any(|element| matches!(element, Element::Table(_))));
+    let shown: String = elements
+        .iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some(&text_box.texts),
+            _ => None,
+        })
+        .flatten()
+        .map(|text| text.text.as_str())
+        .collect();
+    assert_eq!(shown, "Markdown h1 header");
+}
+
+#[test]
+fn code_block_language_and_source() {
+    init_test_log();
+
+    let text = "\
+```rust,ignore
+let color = \"#ff8800\";
+```
+
+```
+plain
+```
+";
+    let opts = InterpreterOpts::new().color_swatches(true);
+    let blocks: Vec<_> = interpret_md_with_opts(text, opts)
+        .into_iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) if text_box.is_code_block => {
+                Some((text_box.code_language, text_box.code_source))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        blocks,
+        [
+            (
+                Some("rust".to_owned()),
+                Some("let color = \"#ff8800\";".to_owned())
+            ),
+            (None, Some("plain".to_owned())),
+        ]
+    );
+}
+
+#[test]
+fn diff_code_blocks() {
+    init_test_log();
+
+    let text = "\
+```diff-rust
+ fn main() {
+-    let answer = 41;
++    let answer = 42;
+ }
+```
+";
+    let text_box = interpret_md(text)
+        .into_iter()
+        .find_map(|element| match element {
+            Element::TextBox(text_box) if text_box.is_code_block => Some(text_box),
+            _ => None,
+        })
+        .unwrap();
+    let shown: String = text_box.texts.iter().map(|text| &*text.text).collect();
+    assert_eq!(
+        shown.trim_end(),
+        " fn main() {\n-    let answer = 41;\n+    let answer = 42;\n }"
+    );
+    assert!(text_box.texts.len() > 1, "Should be highlighted as Rust");
+    assert_eq!(
+        text_box.diff_lines,
+        [None, Some(DiffLine::Removed), Some(DiffLine::Added), None]
+    );
+}
+
+#[test]
+fn code_block_options() {
+    init_test_log();
+
+    fn code_blocks(elements: VecDeque<Element>) -> Vec<(String, bool)> {
+        elements
+            .into_iter()
+            .filter_map(|element| match element {
+                Element::TextBox(text_box) if text_box.is_code_block => {
+                    let text = text_box.texts.iter().map(|text| &*text.text).collect();
+                    Some((text, text_box.no_wrap))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    let text = "```\nfirst\nsecond\n```\n";
+    assert_eq!(
+        code_blocks(interpret_md(text)),
+        [("first\nsecond\n".to_owned(), false)]
+    );
+
+    let opts = InterpreterOpts::new().code_blocks(CodeBlockOptions {
+        line_numbers: true,
+        wrap: false,
+    });
+    assert_eq!(
+        code_blocks(interpret_md_with_opts(text, opts)),
+        [("1  first\n2  second\n".to_owned(), true)]
+    );
+
+    // The document's front matter gets the last say
+    let text = "---\nline-numbers: true\ncode-wrap: false\n---\n```\nfirst\n```\n";
+    assert_eq!(
+        code_blocks(interpret_md(text)),
+        [("1  first\n".to_owned(), true)]
+    );
+}
+
+#[test]
+fn right_to_left_text() {
+    init_test_log();
+
+    let text = "\
+שלום, עולם (hello)
+
+Hello (שלום)
+
+<p dir=\"rtl\">Starts with English</p>
+
+<p align=\"center\">مرحبا</p>
+
+<div dir=\"ltr\">
+
+مرحبا
+
+</div>
+
+```
+שלום
+```
+";
+    let aligns: Vec<_> = interpret_md(text)
+        .iter()
+        .filter_map(|element| match element {
+            Element::TextBox(text_box) => Some(text_box.align),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        aligns,
+        [
+            Align::Right,
+            Align::Left,
+            Align::Right,
+            Align::Center,
+            Align::Left,
+            Align::Left,
+        ]
+    );
+}
+
+#[test]
+fn details_sections() {
+    init_test_log();
+
+    let text = "\
+<details open>
+<summary>Starts open</summary>
+
+Shown
+</details>
+
+<details>
+
+No summary
+</details>
+";
+    let sections: Vec<_> = interpret_md(text)
+        .into_iter()
+        .filter_map(|element| match element {
+            Element::Section(section) => {
+                let Some(Element::TextBox(summary)) =
+                    section.summary.as_ref().as_ref().map(|s| &s.inner)
+                else {
+                    panic!("Sections always have a summary");
+                };
+                let summary: String = summary
+                    .texts
+                    .iter()
+                    .map(|text| text.text.as_str())
+                    .collect();
+                Some((summary, *section.hidden.borrow()))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        sections,
+        [
+            ("Starts open".to_owned(), false),
+            ("Details".to_owned(), true),
+        ]
+    );
+}
+
+#[test]
+fn diagnostics() {
+    init_test_log();
+
+    let text = "\
+# Heading
+
+[Fine](#heading) and [broken](#nowhere)
+
+<div>
+<blink>Old school</blink>
+</div>
+
+<details>
+<summary>Never closed</summary>
+";
+    let (_, diagnostics) = interpret_md_with_diagnostics(text, InterpreterOpts::new());
+    let diagnostics: Vec<_> = diagnostics.iter().map(ToString::to_string).collect();
+    insta::assert_debug_snapshot!(diagnostics, @r###"
+    [
+        "line 6: Unsupported element `<blink>`. Only its text is shown",
+        "line 9: Element `<details>` is never closed",
+        "line 3: Link to `#nowhere` doesn't match any heading or anchor",
+    ]
+    "###);
+
+    // Nothing to complain about for regular markdown
+    let text = "\
+# Checklist
+
+- [x] Done
+- [ ] Not done
+
+| a | b |
+|---|---|
+| 1 | 2 |
+
+```rust
+fn main() {}
+```
+
+Line<br>break and <hr>
+
+<details>
+<summary>Raw <em>HTML</em></summary>
+
+Hidden<br>
+</details>
+";
+    let (_, diagnostics) = interpret_md_with_diagnostics(text, InterpreterOpts::new());
+    assert_eq!(diagnostics, Vec::new());
+}
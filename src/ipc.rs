@@ -0,0 +1,140 @@
+//! Requests that editors and other programs can make of the running instance through its socket,
+//! see [`crate::instance`]
+//!
+//! Each request is a line of JSON like `{"command": "scroll-to-line", "line": 42}`, and gets
+//! answered with a line of either `{"ok": true}` or `{"ok": false, "error": "..."}`
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::opts::ResolvedTheme;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Switches over to the file at `path`, which has to be absolute. Scrolls to where `line` of
+    /// its source shows up when there is one
+    Open {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+    },
+    /// Reads the current file from disk again
+    Reload,
+    /// Scrolls the document to where the one-indexed `line` of its source shows up
+    ScrollToLine {
+        line: usize,
+    },
+    /// Scrolls `percent` of the way down the document
+    ScrollToPercent {
+        percent: f32,
+    },
+    SetTheme {
+        theme: ResolvedTheme,
+    },
+    Quit,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Response {
+    /// Answers a single line of JSON, handing the request off with `send` when it's a valid one.
+    /// `send` fails once there's nothing left to carry out requests
+    pub fn answer<E>(line: &str, send: impl FnOnce(Request) -> Result<(), E>) -> Option<Self> {
+        match serde_json::from_str(line) {
+            // There's no telling what directory a relative path was meant to be relative to
+            Ok(Request::Open { path, .. }) if !path.is_absolute() => Some(Self {
+                ok: false,
+                error: Some(format!("Path must be absolute: '{}'", path.display())),
+            }),
+            Ok(request) => send(request).ok().map(|()| Self {
+                ok: true,
+                error: None,
+            }),
+            Err(err) => Some(Self {
+                ok: false,
+                error: Some(format!("Invalid request: {err}")),
+            }),
+        }
+    }
+}
+
+/// Where the one-indexed source `line` starts in the document. Lines that don't map to any text,
+/// like blank ones, get interpolated between the known lines around them
+///
+/// `source_lines` is [`crate::positioner::Positioner::source_lines`]
+pub fn line_y(source_lines: &BTreeMap<usize, f32>, line: usize) -> Option<f32> {
+    let before = source_lines.range(..=line).next_back();
+    let after = source_lines.range(line + 1..).next();
+    match (before, after) {
+        (Some((&line_a, &y_a)), Some((&line_b, &y_b))) => {
+            let progress = (line - line_a) as f32 / (line_b - line_a) as f32;
+            Some(y_a + progress * (y_b - y_a))
+        }
+        (Some((_, &y)), None) | (None, Some((_, &y))) => Some(y),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_requests() {
+        let answer = |line: &str| {
+            let mut sent = None;
+            let response = Response::answer(line, |request| {
+                sent = Some(request);
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+            (serde_json::to_string(&response).unwrap(), sent)
+        };
+
+        assert_eq!(
+            answer(r#"{"command": "scroll-to-line", "line": 42}"#),
+            (
+                r#"{"ok":true}"#.to_owned(),
+                Some(Request::ScrollToLine { line: 42 })
+            )
+        );
+        assert_eq!(
+            answer(r#"{"command": "set-theme", "theme": "Dark"}"#).1,
+            Some(Request::SetTheme {
+                theme: ResolvedTheme::Dark
+            })
+        );
+        // What counts as absolute differs between platforms
+        let path = std::env::temp_dir().join("todo.md");
+        let open = serde_json::json!({"command": "open", "path": path}).to_string();
+        assert_eq!(answer(&open).1, Some(Request::Open { path, line: None }));
+        assert_eq!(
+            answer(r#"{"command": "open", "path": "notes/todo.md"}"#),
+            (
+                r#"{"ok":false,"error":"Path must be absolute: 'notes/todo.md'"}"#.to_owned(),
+                None
+            )
+        );
+        let (response, sent) = answer(r#"{"command": "explode"}"#);
+        assert!(response.starts_with(r#"{"ok":false,"error":"Invalid request: "#));
+        assert_eq!(sent, None);
+    }
+
+    #[test]
+    fn interpolating_lines() {
+        let source_lines = BTreeMap::from([(3, 100.), (7, 300.)]);
+        assert_eq!(line_y(&source_lines, 1), Some(100.));
+        assert_eq!(line_y(&source_lines, 3), Some(100.));
+        assert_eq!(line_y(&source_lines, 5), Some(200.));
+        assert_eq!(line_y(&source_lines, 20), Some(300.));
+        assert_eq!(line_y(&BTreeMap::new(), 1), None);
+    }
+}
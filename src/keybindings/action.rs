@@ -1,10 +1,36 @@
+use crate::history::Direction;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Action {
     ToEdge(VertDirection),
     Scroll(VertDirection),
     Page(VertDirection),
+    HalfPage(VertDirection),
     Zoom(Zoom),
+    History(Direction),
     Copy,
+    ToggleDiagnostics,
+    ToggleRecentFiles,
+    ToggleQuickOpen,
+    ToggleSearch,
+    NextMatch,
+    PrevMatch,
+    ToggleToc,
+    ToggleSource,
+    ToggleStatusBar,
+    ToggleMinimap,
+    ToggleTheme,
+    ToggleLineNumbers,
+    ToggleCodeWrap,
+    Reload,
+    PreviewClipboard,
+    Print,
+    ToggleSlideshow,
+    TogglePresenter,
+    NextSlide,
+    PrevSlide,
+    ToggleFullscreen,
+    ToggleZen,
     Quit,
 }
 
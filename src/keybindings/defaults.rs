@@ -1,5 +1,6 @@
 use super::action::{Action, VertDirection, Zoom};
 use super::{Key, KeyCombo, ModifiedKey};
+use crate::history::Direction;
 
 use winit::event::{ModifiersState, VirtualKeyCode as VirtKey};
 
@@ -34,6 +35,16 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
                 ctrl_or_command,
             )]),
         ),
+        // Zoom in: Ctrl++ / Command++
+        (
+            Action::Zoom(Zoom::In),
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::Plus), ctrl_or_command)]),
+        ),
+        // Reset the zoom: Ctrl+0 / Command+0
+        (
+            Action::Zoom(Zoom::Reset),
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::Key0), ctrl_or_command)]),
+        ),
         // Scroll up: Up-arrow
         (
             Action::Scroll(VertDirection::Up),
@@ -64,6 +75,139 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
             Action::ToEdge(VertDirection::Down),
             KeyCombo::from(VirtKey::End),
         ),
+        // Go back to the previously opened file: Alt+Left
+        (
+            Action::History(Direction::Back),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Left),
+                ModifiersState::ALT,
+            )]),
+        ),
+        // Go forward to the file that was gone back from: Alt+Right
+        (
+            Action::History(Direction::Forward),
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Right),
+                ModifiersState::ALT,
+            )]),
+        ),
+        // Toggle the diagnostics panel: F12
+        (Action::ToggleDiagnostics, KeyCombo::from(VirtKey::F12)),
+        // Toggle the recent files list: Ctrl+R / Command+R
+        (
+            Action::ToggleRecentFiles,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::R), ctrl_or_command)]),
+        ),
+        // Quickly open a file from the workspace: Ctrl+O / Command+O
+        (
+            Action::ToggleQuickOpen,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::O), ctrl_or_command)]),
+        ),
+        // Search the document: Ctrl+F / Command+F
+        (
+            Action::ToggleSearch,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::F), ctrl_or_command)]),
+        ),
+        // Jump to the next search match: F3
+        (Action::NextMatch, KeyCombo::from(VirtKey::F3)),
+        // Jump to the previous search match: Shift+F3
+        (
+            Action::PrevMatch,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::F3),
+                ModifiersState::SHIFT,
+            )]),
+        ),
+        // List the document's headings beside it: Ctrl+T / Command+T
+        (
+            Action::ToggleToc,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::T), ctrl_or_command)]),
+        ),
+        // Show the markdown source beside the document: Ctrl+U / Command+U
+        (
+            Action::ToggleSource,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::U), ctrl_or_command)]),
+        ),
+        // Show the scroll position, word count, and reading time along the bottom: Ctrl+B /
+        // Command+B
+        (
+            Action::ToggleStatusBar,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::B), ctrl_or_command)]),
+        ),
+        // Show an outline of the document in its right margin: Ctrl+M / Command+M
+        (
+            Action::ToggleMinimap,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::M), ctrl_or_command)]),
+        ),
+        // Switch between the light and dark themes: Ctrl+Shift+T / Command+Shift+T
+        (
+            Action::ToggleTheme,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::T),
+                ctrl_or_command | ModifiersState::SHIFT,
+            )]),
+        ),
+        // Show line numbers beside code blocks: Alt+L
+        (
+            Action::ToggleLineNumbers,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::L),
+                ModifiersState::ALT,
+            )]),
+        ),
+        // Switch code blocks between wrapping and scrolling sideways: Alt+Z
+        (
+            Action::ToggleCodeWrap,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::Z),
+                ModifiersState::ALT,
+            )]),
+        ),
+        // Read the file from disk again: F5
+        (Action::Reload, KeyCombo::from(VirtKey::F5)),
+        // Preview the clipboard's contents: Ctrl+Shift+V / Command+Shift+V
+        (
+            Action::PreviewClipboard,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::V),
+                ctrl_or_command | ModifiersState::SHIFT,
+            )]),
+        ),
+        // Print the document: Ctrl+P / Command+P
+        (
+            Action::Print,
+            KeyCombo(vec![ModifiedKey(Key::from(VirtKey::P), ctrl_or_command)]),
+        ),
+        // Present the document as slides split up by its horizontal rules: Shift+F5
+        (
+            Action::ToggleSlideshow,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::F5),
+                ModifiersState::SHIFT,
+            )]),
+        ),
+        // Show the speaker notes, the next slide, and a timer in a second window: Alt+F5
+        (
+            Action::TogglePresenter,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::F5),
+                ModifiersState::ALT,
+            )]),
+        ),
+        // Go to the next slide: Right-arrow
+        (Action::NextSlide, KeyCombo::from(VirtKey::Right)),
+        // Go to the previous slide: Left-arrow
+        (Action::PrevSlide, KeyCombo::from(VirtKey::Left)),
+        // Toggle fullscreen: F11
+        (Action::ToggleFullscreen, KeyCombo::from(VirtKey::F11)),
+        // Hide everything but the document for distraction-free reading: Shift+F11
+        (
+            Action::ToggleZen,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::F11),
+                ModifiersState::SHIFT,
+            )]),
+        ),
         // Quit: Esc
         (Action::Quit, KeyCombo::from(VirtKey::Escape)),
         // vim-like bindings
@@ -113,3 +257,39 @@ pub fn defaults() -> Vec<(Action, KeyCombo)> {
         ),
     ]
 }
+
+/// Added on top of the other keybindings in vim mode, taking over any that they collide with
+pub fn vim() -> Vec<(Action, KeyCombo)> {
+    vec![
+        // Copy: yy
+        (
+            Action::Copy,
+            KeyCombo(vec![
+                ModifiedKey::from(VirtKey::Y),
+                ModifiedKey::from(VirtKey::Y),
+            ]),
+        ),
+        // Half a page down: d
+        (
+            Action::HalfPage(VertDirection::Down),
+            KeyCombo::from(VirtKey::D),
+        ),
+        // Half a page up: u
+        (
+            Action::HalfPage(VertDirection::Up),
+            KeyCombo::from(VirtKey::U),
+        ),
+        // Search the document: /
+        (Action::ToggleSearch, KeyCombo::from(VirtKey::Slash)),
+        // Jump to the next search match: n
+        (Action::NextMatch, KeyCombo::from(VirtKey::N)),
+        // Jump to the previous search match: N
+        (
+            Action::PrevMatch,
+            KeyCombo(vec![ModifiedKey(
+                Key::from(VirtKey::N),
+                ModifiersState::SHIFT,
+            )]),
+        ),
+    ]
+}
@@ -13,6 +13,17 @@ impl Keybindings {
     pub fn iter(&self) -> std::slice::Iter<'_, (Action, KeyCombo)> {
         self.0.iter()
     }
+
+    /// Adds the vim mode keybindings, dropping any existing ones that would collide with them
+    pub fn with_vim_mode(mut self) -> Self {
+        let vim = super::defaults::vim();
+        self.0.retain(|(_, combo)| {
+            !vim.iter()
+                .any(|(_, vim_combo)| combo.starts_with(vim_combo) || vim_combo.starts_with(combo))
+        });
+        self.0.extend(vim);
+        self
+    }
 }
 
 impl Extend<(Action, KeyCombo)> for Keybindings {
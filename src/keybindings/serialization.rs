@@ -2,6 +2,7 @@ use std::str::FromStr;
 
 use super::action::{Action, VertDirection, Zoom};
 use super::{Key, KeyCombo, ModifiedKey};
+use crate::history::Direction;
 
 use serde::{de, Deserialize, Deserializer};
 use winit::event::ModifiersState;
@@ -19,10 +20,36 @@ impl<'de> Deserialize<'de> for Action {
             ScrollDown,
             PageUp,
             PageDown,
+            HalfPageUp,
+            HalfPageDown,
             ZoomIn,
             ZoomOut,
             ZoomReset,
+            HistoryBack,
+            HistoryForward,
             Copy,
+            ToggleDiagnostics,
+            ToggleRecentFiles,
+            ToggleQuickOpen,
+            ToggleSearch,
+            NextMatch,
+            PrevMatch,
+            ToggleToc,
+            ToggleSource,
+            ToggleStatusBar,
+            ToggleMinimap,
+            ToggleTheme,
+            ToggleLineNumbers,
+            ToggleCodeWrap,
+            Reload,
+            PreviewClipboard,
+            Print,
+            ToggleSlideshow,
+            TogglePresenter,
+            NextSlide,
+            PrevSlide,
+            ToggleFullscreen,
+            ToggleZen,
             Quit,
         }
 
@@ -33,10 +60,36 @@ impl<'de> Deserialize<'de> for Action {
             FlatAction::ScrollDown => Action::Scroll(VertDirection::Down),
             FlatAction::PageUp => Action::Page(VertDirection::Up),
             FlatAction::PageDown => Action::Page(VertDirection::Down),
+            FlatAction::HalfPageUp => Action::HalfPage(VertDirection::Up),
+            FlatAction::HalfPageDown => Action::HalfPage(VertDirection::Down),
             FlatAction::ZoomIn => Action::Zoom(Zoom::In),
             FlatAction::ZoomOut => Action::Zoom(Zoom::Out),
             FlatAction::ZoomReset => Action::Zoom(Zoom::Reset),
+            FlatAction::HistoryBack => Action::History(Direction::Back),
+            FlatAction::HistoryForward => Action::History(Direction::Forward),
             FlatAction::Copy => Action::Copy,
+            FlatAction::ToggleDiagnostics => Action::ToggleDiagnostics,
+            FlatAction::ToggleRecentFiles => Action::ToggleRecentFiles,
+            FlatAction::ToggleQuickOpen => Action::ToggleQuickOpen,
+            FlatAction::ToggleSearch => Action::ToggleSearch,
+            FlatAction::NextMatch => Action::NextMatch,
+            FlatAction::PrevMatch => Action::PrevMatch,
+            FlatAction::ToggleToc => Action::ToggleToc,
+            FlatAction::ToggleSource => Action::ToggleSource,
+            FlatAction::ToggleStatusBar => Action::ToggleStatusBar,
+            FlatAction::ToggleMinimap => Action::ToggleMinimap,
+            FlatAction::ToggleTheme => Action::ToggleTheme,
+            FlatAction::ToggleLineNumbers => Action::ToggleLineNumbers,
+            FlatAction::ToggleCodeWrap => Action::ToggleCodeWrap,
+            FlatAction::Reload => Action::Reload,
+            FlatAction::PreviewClipboard => Action::PreviewClipboard,
+            FlatAction::Print => Action::Print,
+            FlatAction::ToggleSlideshow => Action::ToggleSlideshow,
+            FlatAction::TogglePresenter => Action::TogglePresenter,
+            FlatAction::NextSlide => Action::NextSlide,
+            FlatAction::PrevSlide => Action::PrevSlide,
+            FlatAction::ToggleFullscreen => Action::ToggleFullscreen,
+            FlatAction::ToggleZen => Action::ToggleZen,
             FlatAction::Quit => Action::Quit,
         };
 
@@ -1,6 +1,6 @@
-use super::action::{Action, VertDirection};
-use super::{KeyCombos, ModifiedKey};
-use crate::opts::Config;
+use super::action::{Action, VertDirection, Zoom};
+use super::{KeyCombos, Keybindings, ModifiedKey};
+use crate::opts::{Config, KeybindingsSection};
 use crate::test_utils::init_test_log;
 
 use winit::event::{ModifiersState, VirtualKeyCode as VirtKey};
@@ -44,3 +44,70 @@ base = [
         assert_eq!(key_combos.munch(key), maybe_action);
     }
 }
+
+#[test]
+fn extra_bindings_with_modifiers() {
+    init_test_log();
+
+    let config = r#"
+[keybindings]
+extra = [
+    ["Reload", { key = "r", mod = ["Ctrl", "Shift"] }],
+    ["ZoomIn", { key = "Up", mod = ["Alt"] }],
+]
+"#;
+
+    let Config { keybindings, .. } = Config::load_from_str(config).unwrap();
+    let mut key_combos = KeyCombos::new(keybindings).unwrap();
+
+    let ctrl_shift_r = ModifiedKey(
+        VirtKey::R.into(),
+        ModifiersState::CTRL | ModifiersState::SHIFT,
+    );
+    let alt_up = ModifiedKey(VirtKey::Up.into(), ModifiersState::ALT);
+
+    let test_vectors = [
+        (ctrl_shift_r, Some(Action::Reload)),
+        (alt_up, Some(Action::Zoom(Zoom::In))),
+        // The defaults are still around
+        (VirtKey::F5.into(), Some(Action::Reload)),
+        (VirtKey::Up.into(), Some(Action::Scroll(VertDirection::Up))),
+    ];
+
+    for (key, maybe_action) in test_vectors {
+        assert_eq!(key_combos.munch(key), maybe_action);
+    }
+}
+
+#[test]
+fn vim_mode() {
+    init_test_log();
+
+    let keybindings = KeybindingsSection {
+        base: Keybindings::default().with_vim_mode(),
+        extra: None,
+    };
+    let mut key_combos = KeyCombos::new(keybindings).unwrap();
+
+    let y: ModifiedKey = VirtKey::Y.into();
+    let cap_n = ModifiedKey(VirtKey::N.into(), ModifiersState::SHIFT);
+
+    let test_vectors = [
+        // `y` turns into `yy` instead of colliding with it
+        (y, None),
+        (y, Some(Action::Copy)),
+        (
+            VirtKey::D.into(),
+            Some(Action::HalfPage(VertDirection::Down)),
+        ),
+        (VirtKey::Slash.into(), Some(Action::ToggleSearch)),
+        (VirtKey::N.into(), Some(Action::NextMatch)),
+        (cap_n, Some(Action::PrevMatch)),
+        // Along with all of the usual bindings
+        (VirtKey::J.into(), Some(Action::Scroll(VertDirection::Down))),
+    ];
+
+    for (key, maybe_action) in test_vectors {
+        assert_eq!(key_combos.munch(key), maybe_action);
+    }
+}
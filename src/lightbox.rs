@@ -0,0 +1,181 @@
+//! A single image blown up to fit the window over the dimmed document
+
+use crate::image::Image;
+use crate::utils::{Point, Rect, Size};
+
+/// Room left between the image and the edges of the window
+pub const MARGIN: f32 = 32.;
+pub const MIN_ZOOM: f32 = 1.;
+pub const MAX_ZOOM: f32 = 20.;
+/// How much each notch of the scroll wheel zooms by
+pub const ZOOM_STEP: f32 = 1.1;
+/// How far touchpads scroll to count as a notch of the scroll wheel
+pub const PIXELS_PER_NOTCH: f32 = 40.;
+
+#[derive(Debug)]
+pub struct Lightbox {
+    /// Shares its data with the image in the document, but gets its own texture
+    pub image: Image,
+    /// On top of whatever it takes to fit the image to the window
+    pub zoom: f32,
+    /// How far the image has been dragged away from the center of the window
+    pub pan: Point,
+    /// Where the cursor was last seen while dragging, if the image is being dragged
+    pub grabbed_at: Option<Point>,
+    /// Whether the current grab has moved anything, so that it doesn't count as a click
+    pub dragged: bool,
+}
+
+impl Lightbox {
+    pub fn new(image: &Image) -> Self {
+        let mut shown = Image::from_image_data(image.image_data.clone(), image.hidpi_scale);
+        shown.transform = image.transform;
+        Self {
+            image: shown,
+            zoom: 1.,
+            pan: (0., 0.),
+            grabbed_at: None,
+            dragged: false,
+        }
+    }
+
+    /// Where the image gets drawn on the screen
+    pub fn bounds(&self, screen_size: Size) -> Option<Rect> {
+        let (width, height) = self.image.shown_dimensions()?;
+        let (width, height) = (width as f32, height as f32);
+        let margin = MARGIN * self.image.hidpi_scale;
+        let fit = ((screen_size.0 - 2. * margin) / width)
+            .min((screen_size.1 - 2. * margin) / height)
+            .max(0.);
+        let size = (width * fit * self.zoom, height * fit * self.zoom);
+        let pos = (
+            (screen_size.0 - size.0) / 2. + self.pan.0,
+            (screen_size.1 - size.1) / 2. + self.pan.1,
+        );
+        Some(Rect::new(pos, size))
+    }
+
+    /// Zooms by `factor`, keeping whatever is under `focal` in place
+    pub fn zoom_by(&mut self, factor: f32, focal: Point, screen_size: Size) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let ratio = zoom / self.zoom;
+        let center = (
+            screen_size.0 / 2. + self.pan.0,
+            screen_size.1 / 2. + self.pan.1,
+        );
+        let new_center = (
+            focal.0 - (focal.0 - center.0) * ratio,
+            focal.1 - (focal.1 - center.1) * ratio,
+        );
+        self.zoom = zoom;
+        self.pan = (
+            new_center.0 - screen_size.0 / 2.,
+            new_center.1 - screen_size.1 / 2.,
+        );
+        if self.zoom == MIN_ZOOM {
+            // Nothing is left to pan around at the original size
+            self.pan = (0., 0.);
+        }
+    }
+
+    pub fn grab(&mut self, pos: Point) {
+        self.grabbed_at = Some(pos);
+        self.dragged = false;
+    }
+
+    /// Moves the image along with the cursor while it's grabbed
+    ///
+    /// Returns whether anything moved
+    pub fn drag_to(&mut self, pos: Point) -> bool {
+        let Some(grabbed_at) = self.grabbed_at.replace(pos) else {
+            return false;
+        };
+        self.pan.0 += pos.0 - grabbed_at.0;
+        self.pan.1 += pos.1 - grabbed_at.1;
+        self.dragged |= pos != grabbed_at;
+        pos != grabbed_at
+    }
+
+    /// Lets go of the image. Returns whether the lightbox should close, which is when the press
+    /// was a click on the background instead of a drag
+    pub fn release(&mut self, pos: Point, screen_size: Size) -> bool {
+        let was_grabbed = self.grabbed_at.take().is_some();
+        let on_image = self
+            .bounds(screen_size)
+            .map_or(false, |bounds| bounds.contains(pos));
+        was_grabbed && !self.dragged && !on_image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::ImageData;
+
+    use std::sync::{Arc, Mutex};
+
+    const SCREEN: Size = (864., 464.);
+
+    fn lightbox(dimensions: (u32, u32)) -> Lightbox {
+        let (width, height) = dimensions;
+        let image_data = ImageData::new(image::RgbaImage::new(width, height), false);
+        let image = Image::from_image_data(Arc::new(Mutex::new(Some(image_data))), 1.);
+        Lightbox::new(&image)
+    }
+
+    #[test]
+    fn fits_the_window() {
+        let wide = lightbox((200, 50));
+        assert_eq!(
+            wide.bounds(SCREEN).unwrap(),
+            Rect::new((32., 132.), (800., 200.))
+        );
+
+        let tall = lightbox((50, 100));
+        assert_eq!(
+            tall.bounds(SCREEN).unwrap(),
+            Rect::new((332., 32.), (200., 400.))
+        );
+    }
+
+    #[test]
+    fn zooming_keeps_the_focal_point_in_place() {
+        let mut lightbox = lightbox((200, 50));
+        let focal = (232., 182.);
+        let before = lightbox.bounds(SCREEN).unwrap();
+        lightbox.zoom_by(2., focal, SCREEN);
+        let after = lightbox.bounds(SCREEN).unwrap();
+
+        assert_eq!(after.size, (1600., 400.));
+        // The point a quarter of the way across the image is still under the cursor
+        let relative = |bounds: &Rect| {
+            (
+                (focal.0 - bounds.pos.0) / bounds.size.0,
+                (focal.1 - bounds.pos.1) / bounds.size.1,
+            )
+        };
+        assert_eq!(relative(&before), relative(&after));
+
+        lightbox.zoom_by(0.1, focal, SCREEN);
+        assert_eq!(lightbox.zoom, MIN_ZOOM);
+        assert_eq!(lightbox.bounds(SCREEN).unwrap(), before);
+    }
+
+    #[test]
+    fn clicking_the_background_closes() {
+        let mut lightbox = lightbox((200, 50));
+
+        // A click on the image itself
+        lightbox.grab((400., 200.));
+        assert!(!lightbox.release((400., 200.), SCREEN));
+
+        // Dragging the image and letting go over the background
+        lightbox.grab((400., 200.));
+        assert!(lightbox.drag_to((400., 20.)));
+        assert_eq!(lightbox.pan, (0., -180.));
+        assert!(!lightbox.release((400., 20.), SCREEN));
+
+        lightbox.grab((10., 10.));
+        assert!(lightbox.release((10., 10.), SCREEN));
+    }
+}
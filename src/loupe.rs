@@ -0,0 +1,164 @@
+//! A 1:1 crop of a scaled down image that follows the cursor around while it hovers the image
+
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::utils::{Point, Rect, Size};
+
+/// How big the crop gets on each side
+pub const SIZE: f32 = 200.;
+/// Framing the crop so that it stands out from the image under it
+pub const BORDER: f32 = 2.;
+
+#[derive(Debug)]
+pub struct Loupe {
+    /// Shares its data with the hovered image, but gets its own full resolution texture
+    pub image: Image,
+    /// Where the hovered image is in the document
+    pub image_bounds: Rect,
+    /// Where the cursor is in the document
+    pub cursor: Point,
+}
+
+impl Loupe {
+    /// Gives `None` unless `image` is shown smaller than its full resolution, since there would be
+    /// nothing to magnify otherwise
+    pub fn new(image: &Image, image_bounds: &Rect, cursor: Point) -> Option<Self> {
+        let (width, height) = image.shown_dimensions()?;
+        let Rect { size, .. } = image_bounds;
+        let downscaled = width as f32 >= size.0 + 1. || height as f32 >= size.1 + 1.;
+        if !downscaled || size.0 <= 0. || size.1 <= 0. {
+            return None;
+        }
+
+        let mut shown = Image::from_image_data(image.image_data.clone(), image.hidpi_scale);
+        shown.transform = image.transform;
+        Some(Self {
+            image: shown,
+            image_bounds: image_bounds.clone(),
+            cursor,
+        })
+    }
+
+    /// Whether this is magnifying `image` where it currently is in the document
+    pub fn is_over(&self, image: &Image, image_bounds: &Rect) -> bool {
+        Arc::ptr_eq(&self.image.image_data, &image.image_data) && self.image_bounds == *image_bounds
+    }
+
+    /// Where the crop gets drawn on the screen, along with which part of the texture it shows
+    ///
+    /// The crop is centered on the same spot of the image as the cursor, but both are kept from
+    /// hanging off the edges of the image and the screen respectively
+    pub fn view(&self, scroll_y: f32, screen_size: Size) -> Option<(Rect, Rect)> {
+        let (width, height) = self.image.shown_dimensions()?;
+        let full = (width as f32, height as f32);
+        let side = SIZE * self.image.hidpi_scale;
+        let crop_size = (side.min(full.0), side.min(full.1));
+
+        let Rect { pos, size } = &self.image_bounds;
+        let relative = (
+            ((self.cursor.0 - pos.0) / size.0).clamp(0., 1.),
+            ((self.cursor.1 - pos.1) / size.1).clamp(0., 1.),
+        );
+        let crop_pos = (
+            (relative.0 * full.0 - crop_size.0 / 2.).clamp(0., full.0 - crop_size.0),
+            (relative.1 * full.1 - crop_size.1 / 2.).clamp(0., full.1 - crop_size.1),
+        );
+        let shown = Rect::new(
+            (crop_pos.0 / full.0, crop_pos.1 / full.1),
+            (crop_size.0 / full.0, crop_size.1 / full.1),
+        );
+        let uv = self
+            .image
+            .transform
+            .unwrap_or_default()
+            .texture_rect(&shown);
+
+        let screen_pos = (
+            (self.cursor.0 - crop_size.0 / 2.).clamp(0., (screen_size.0 - crop_size.0).max(0.)),
+            (self.cursor.1 - scroll_y - crop_size.1 / 2.)
+                .clamp(0., (screen_size.1 - crop_size.1).max(0.)),
+        );
+        Some((Rect::new(screen_pos, crop_size), uv))
+    }
+}
+
+/// Keeps `loupe` over the image that's `hovered`, if any, with the cursor at `cursor`
+///
+/// Returns whether anything changed that needs to be drawn
+pub fn follow(loupe: &mut Option<Loupe>, hovered: Option<(&Image, &Rect)>, cursor: Point) -> bool {
+    let was_shown = loupe.is_some();
+    match (hovered, loupe.as_mut()) {
+        (Some((image, bounds)), Some(current)) if current.is_over(image, bounds) => {
+            current.cursor = cursor;
+        }
+        (Some((image, bounds)), _) => *loupe = Loupe::new(image, bounds, cursor),
+        (None, _) => *loupe = None,
+    }
+    was_shown || loupe.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::ImageData;
+
+    use std::sync::Mutex;
+
+    const SCREEN: Size = (800., 600.);
+
+    fn image(dimensions: (u32, u32)) -> Image {
+        let (width, height) = dimensions;
+        let image_data = ImageData::new(image::RgbaImage::new(width, height), false);
+        Image::from_image_data(Arc::new(Mutex::new(Some(image_data))), 1.)
+    }
+
+    #[test]
+    fn only_scaled_down_images() {
+        let image = image((1600, 800));
+        let full_size = Rect::new((0., 0.), (1600., 800.));
+        assert!(Loupe::new(&image, &full_size, (10., 10.)).is_none());
+
+        let half_size = Rect::new((0., 0.), (800., 400.));
+        let loupe = Loupe::new(&image, &half_size, (10., 10.)).unwrap();
+        assert!(loupe.is_over(&image, &half_size));
+        assert!(!loupe.is_over(&image, &full_size));
+    }
+
+    #[test]
+    fn following_the_cursor() {
+        let image = image((1600, 800));
+        let bounds = Rect::new((0., 0.), (800., 400.));
+        let mut loupe = None;
+
+        assert!(!follow(&mut loupe, None, (10., 10.)));
+        assert!(follow(&mut loupe, Some((&image, &bounds)), (10., 10.)));
+        assert!(follow(&mut loupe, Some((&image, &bounds)), (20., 10.)));
+        assert_eq!(loupe.as_ref().unwrap().cursor, (20., 10.));
+        // Moving off of the image hides it
+        assert!(follow(&mut loupe, None, (900., 10.)));
+        assert!(loupe.is_none());
+    }
+
+    #[test]
+    fn crops_around_the_cursor() {
+        let image = image((1600, 800));
+        let bounds = Rect::new((0., 100.), (800., 400.));
+        let mut loupe = Loupe::new(&image, &bounds, (400., 300.)).unwrap();
+
+        // Right in the middle of the image
+        let (screen, uv) = loupe.view(0., SCREEN).unwrap();
+        assert_eq!(screen, Rect::new((300., 200.), (200., 200.)));
+        assert_eq!(uv, Rect::new((0.4375, 0.375), (0.125, 0.25)));
+
+        // Scrolling moves it along with the document
+        let (screen, _) = loupe.view(100., SCREEN).unwrap();
+        assert_eq!(screen.pos, (300., 100.));
+
+        // The top left corner stays within both the image and the screen
+        loupe.cursor = (0., 100.);
+        let (screen, uv) = loupe.view(0., SCREEN).unwrap();
+        assert_eq!(screen, Rect::new((0., 0.), (200., 200.)));
+        assert_eq!(uv, Rect::new((0., 0.), (0.125, 0.25)));
+    }
+}
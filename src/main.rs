@@ -9,23 +9,58 @@
     clippy::print_stdout, clippy::print_stderr,
 )]
 
+mod bench;
+mod bidi;
+mod callout;
+mod chart;
+mod clicks;
 mod clipboard;
 pub mod color;
+mod context_menu;
+mod crossref;
 mod debug_impls;
+mod diagnostics;
+mod diagram;
+mod dialog;
+mod diff;
+mod export;
 mod file_watcher;
 pub mod fonts;
+mod gpu;
+mod history;
 pub mod image;
+mod instance;
 pub mod interpreter;
+mod ipc;
 mod keybindings;
+mod lightbox;
+mod loupe;
+mod math;
+mod minimap;
 pub mod opts;
+mod panel;
 pub mod positioner;
+mod presenter;
+mod print;
+mod quick_open;
+mod recent;
 pub mod renderer;
+mod scroll;
+mod search;
+mod serve;
+mod slideshow;
+mod status_bar;
+mod stdin;
 pub mod table;
 pub mod test_utils;
 pub mod text;
+mod title;
+mod toc;
+mod touch;
 pub mod utils;
+mod zen;
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::fmt::Debug;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
@@ -34,36 +69,94 @@ use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{self, channel};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use clicks::ClickCounter;
+use color::Theme;
+use context_menu::{ImageAction, ImageMenu};
+use diagnostics::Diagnostics;
 use file_watcher::Watcher;
+use history::History;
+use image::cache::DiskCache;
+use image::fetch::Fetcher;
+use image::textures::TextureManager;
 use image::{Image, ImageData};
-use interpreter::HtmlInterpreter;
+use interpreter::{Document, EventLoopCallback, HtmlInterpreter};
 use keybindings::action::{Action, VertDirection, Zoom};
 use keybindings::{Key, KeyCombos, ModifiedKey};
-use opts::{Args, Config, Opts};
-use positioner::{Positioned, Row, Section, Spacer, DEFAULT_MARGIN, DEFAULT_PADDING};
+use lightbox::Lightbox;
+use opts::{
+    Args, BenchOpts, Cli, Config, ExportOpts, Opts, ResolvedTheme, ScrollOptions, ServeOpts,
+};
+use panel::{Panel, SourcePane};
+use positioner::{centering, Positioned, Row, Section, Spacer, DEFAULT_MARGIN};
+use presenter::Presenter;
+use quick_open::QuickOpen;
 use raw_window_handle::HasRawDisplayHandle;
-use renderer::Renderer;
+use recent::{RecentFiles, WindowGeometry};
+use renderer::{Frame, HoveredLink, Renderer};
+use scroll::{Momentum, SmoothScroll};
+use search::Search;
+use slideshow::{Slideshow, SpeakerNotes};
+use status_bar::StatusBar;
 use table::Table;
 use text::{Text, TextBox, TextSystem};
+use toc::Toc;
+use touch::{Gesture, Gestures, PinchUpdate};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::util::SubscriberInitExt;
-use utils::{ImageCache, Point, Rect, Size};
+use utils::{ImageCache, Point, Rect, Selection, Size};
+use zen::Zen;
 
 use anyhow::Context;
 use taffy::Taffy;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::{
-    ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, WindowEvent,
+    ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, TouchPhase,
+    VirtualKeyCode, WindowEvent,
+};
+use winit::event_loop::{
+    ControlFlow, EventLoop, EventLoopBuilder, EventLoopProxy, EventLoopWindowTarget,
 };
-use winit::event_loop::{ControlFlow, EventLoop, EventLoopBuilder};
-use winit::window::{CursorIcon, Window};
+use winit::window::{CursorIcon, Fullscreen, Window, WindowBuilder};
+
+/// Keeps zooming from shrinking the document into a speck or blowing it up into a blur
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 5.0;
+/// How much each notch of the mouse wheel zooms by while holding Ctrl
+const WHEEL_ZOOM_STEP: f32 = 1.1;
+/// How far (in logical pixels) a finger can drift before a tap turns into scrolling
+const TOUCH_SLOP: f32 = 10.0;
+/// How far (in logical pixels) around a tap to look for a link
+const TOUCH_TARGET_RADIUS: f32 = 12.0;
+/// How far (in logical pixels) the mouse can move between the clicks of a double click
+const CLICK_SLOP: f32 = 4.0;
 
 pub enum InlyneEvent {
     LoadedImage(String, Arc<Mutex<Option<ImageData>>>),
+    /// An image couldn't be loaded, so it gets drawn as broken instead
+    ImageError {
+        src: String,
+        reason: String,
+    },
     FileReload,
-    FileChange { contents: String },
+    FileChange {
+        contents: String,
+    },
+    /// More of the document came in through stdin
+    StdinChange {
+        contents: String,
+    },
     Reposition,
     PositionQueue,
+    /// An image that the document shows changed on disk
+    ImageChange(PathBuf),
+    /// The config file changed on disk
+    ConfigChange,
+    /// Another instance or program asked for something over the socket, like opening a file
+    Request(ipc::Request),
+    /// The quick open palette's search found more files
+    QuickOpenFound,
 }
 
 impl Debug for InlyneEvent {
@@ -73,16 +166,27 @@ impl Debug for InlyneEvent {
 }
 
 pub enum Hoverable<'a> {
-    Image(&'a Image),
+    /// Along with where the image is in the document
+    Image(&'a Image, &'a Rect),
     Text(&'a Text),
     Summary(&'a Section),
 }
 
+impl Hoverable<'_> {
+    fn link(&self) -> Option<&str> {
+        match self {
+            Self::Image(image, _) => image.is_link.as_deref(),
+            Self::Text(text) => text.link.as_deref(),
+            Self::Summary(_) => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Element {
     TextBox(TextBox),
     Spacer(Spacer),
-    Image(Image),
+    Image(Box<Image>),
     Table(Table),
     Row(Row),
     Section(Section),
@@ -102,7 +206,7 @@ impl From<Row> for Element {
 
 impl From<Image> for Element {
     fn from(image: Image) -> Self {
-        Element::Image(image)
+        Element::Image(Box::new(image))
     }
 }
 
@@ -124,6 +228,16 @@ impl From<Table> for Element {
     }
 }
 
+/// What gets shown in the panel drawn over the document
+#[derive(Debug)]
+enum Overlay {
+    Diagnostics,
+    RecentFiles { selected: usize },
+    QuickOpen(QuickOpen),
+    ImageMenu(ImageMenu),
+    Search(Search),
+}
+
 pub struct Inlyne {
     opts: Opts,
     window: Arc<Window>,
@@ -134,12 +248,70 @@ pub struct Inlyne {
     element_queue: Arc<Mutex<VecDeque<Element>>>,
     elements: Vec<Positioned<Element>>,
     lines_to_scroll: f32,
+    scroll: ScrollOptions,
+    momentum: Momentum,
+    smooth_scroll: SmoothScroll,
+    gestures: Gestures,
     image_cache: ImageCache,
-    interpreter_sender: mpsc::Sender<String>,
+    interpreter_sender: mpsc::Sender<Document>,
     interpreter_should_queue: Arc<AtomicBool>,
+    interpreter_progressive: Arc<AtomicBool>,
     keycombos: KeyCombos,
     need_repositioning: bool,
+    /// Where the document was scrolled to when it got reloaded. The new one starts off empty, so
+    /// this gets restored once it's been positioned
+    restore_scroll_y: Option<f32>,
+    /// A line of the source to scroll to once the document has been positioned
+    restore_line: Option<usize>,
     watcher: Watcher,
+    /// The images that are on disk, as their canonical paths
+    watched_images: BTreeSet<PathBuf>,
+    /// Hands out callbacks for images that get loaded again after they change
+    event_proxy: EventLoopProxy<InlyneEvent>,
+    diagnostics: Diagnostics,
+    notes: SpeakerNotes,
+    /// The second window opened for presenting the slideshow from
+    presenter: Option<Presenter>,
+    overlay: Option<Overlay>,
+    recent_files: RecentFiles,
+    /// The documents opened in this window before (and after) the current one
+    history: History,
+    /// The markdown for the current file
+    source: String,
+    /// Showing a document that isn't backed by any file, like the clipboard's contents or stdin
+    transient: bool,
+    /// Showing what was read from stdin, which keeps getting updated in follow mode
+    from_stdin: bool,
+    /// In zen mode, along with how things were before it
+    zen: Option<Zen>,
+    /// The arguments that the config file at the path gets combined with again when it changes
+    live_config: Option<(Args, PathBuf)>,
+}
+
+/// What the window is called while showing `contents`, filled in from the `format` in the config
+fn window_title(
+    format: &str,
+    file_path: &Path,
+    clipboard: bool,
+    stdin: bool,
+    contents: &str,
+) -> String {
+    let heading = utils::front_matter_title(contents).or_else(|| title::first_heading(contents));
+    let (file, path) = if clipboard {
+        ("Clipboard".to_owned(), "Clipboard".to_owned())
+    } else if stdin {
+        ("stdin".to_owned(), "stdin".to_owned())
+    } else {
+        let file = file_path
+            .file_name()
+            .unwrap_or(file_path.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+        let path = root_filepath_to_vcs_dir(file_path)
+            .map_or_else(|| file.clone(), |path| path.to_string_lossy().into_owned());
+        (file, path)
+    };
+    title::format(format, heading.as_deref(), &file, &path)
 }
 
 /// Gets a relative path extending from the repo root falling back to the full path
@@ -176,16 +348,22 @@ fn root_filepath_to_vcs_dir(path: &Path) -> Option<PathBuf> {
 }
 
 impl Inlyne {
-    pub fn new(opts: Opts) -> anyhow::Result<Self> {
+    pub fn new(opts: Opts, mut recent_files: RecentFiles) -> anyhow::Result<Self> {
         let keycombos = KeyCombos::new(opts.keybindings.clone())?;
 
         let event_loop = EventLoopBuilder::<InlyneEvent>::with_user_event().build();
-        let window = Arc::new(Window::new(&event_loop).unwrap());
-        match root_filepath_to_vcs_dir(&opts.file_path) {
-            Some(path) => window.set_title(&format!("Inlyne - {}", path.to_string_lossy())),
-            None => window.set_title("Inlyne"),
+        let mut window_builder = WindowBuilder::new();
+        if let Some(geometry) = recent_files.window() {
+            let (width, height) = geometry.size;
+            window_builder = window_builder
+                .with_inner_size(PhysicalSize::new(width, height))
+                .with_maximized(geometry.maximized);
+            if let Some((x, y)) = geometry.position {
+                window_builder = window_builder.with_position(PhysicalPosition::new(x, y));
+            }
         }
-        let renderer = pollster::block_on(Renderer::new(
+        let window = Arc::new(window_builder.build(&event_loop).unwrap());
+        let mut renderer = pollster::block_on(Renderer::new(
             &window,
             opts.theme.clone(),
             opts.scale.unwrap_or(window.scale_factor() as f32),
@@ -195,11 +373,32 @@ impl Inlyne {
 
         let element_queue = Arc::new(Mutex::new(VecDeque::new()));
         let image_cache = Arc::new(Mutex::new(HashMap::new()));
-        let md_string = read_to_string(&opts.file_path)
-            .with_context(|| format!("Could not read file at '{}'", opts.file_path.display()))?;
+        let md_string = if opts.clipboard {
+            // SAFETY: The clipboard is dropped at the end of this block while the event loop is
+            // still alive
+            let mut clipboard =
+                unsafe { clipboard::Clipboard::new(event_loop.raw_display_handle()) };
+            clipboard
+                .get_contents()
+                .context("Could not read the clipboard's contents")?
+        } else if opts.follow {
+            // The rest shows up as it gets read
+            String::new()
+        } else if opts.stdin {
+            stdin::read_all().context("Could not read stdin")?
+        } else {
+            read_to_string(&opts.file_path)
+                .with_context(|| format!("Could not read file at '{}'", opts.file_path.display()))?
+        };
+        window.set_title(&window_title(
+            &opts.window_title,
+            &opts.file_path,
+            opts.clipboard,
+            opts.stdin,
+            &md_string,
+        ));
 
-        let interpreter = HtmlInterpreter::new(
-            window.clone(),
+        let mut interpreter = HtmlInterpreter::new(
             element_queue.clone(),
             renderer.theme.clone(),
             renderer.surface_format,
@@ -209,16 +408,71 @@ impl Inlyne {
             event_loop.create_proxy(),
             opts.color_scheme,
         );
+        renderer.animate_images = opts.animate_images;
+        renderer.status_bar = opts.status_bar.then(|| StatusBar::new(&md_string));
+        renderer.sticky_headings = opts.sticky_headings.then(Toc::default);
+        renderer.minimap = opts.minimap;
+        renderer.textures = TextureManager::new(opts.texture_budget);
+        interpreter.color_swatches = opts.color_swatches;
+        interpreter.tables = opts.tables;
+        interpreter.image_captions = opts.image_captions;
+        interpreter.markdown = opts.markdown();
+        interpreter.apply_font_options(&opts.font_opts);
+        renderer.preload_screens = opts.image_fetch.preload_screens;
+        interpreter.fetcher = Fetcher::new(opts.image_fetch.clone());
+        interpreter.lazy_images = true;
+        if !opts.no_cache {
+            interpreter.fetcher.disk_cache = DiskCache::load();
+        }
 
         let (interpreter_sender, interpreter_receiver) = channel();
         let interpreter_should_queue = interpreter.should_queue.clone();
+        let interpreter_progressive = Arc::clone(&interpreter.progressive);
+        let restore_line = opts.line;
+        let restore_scroll_y = if opts.clipboard || opts.stdin || restore_line.is_some() {
+            None
+        } else {
+            recent_files.scroll_y(&opts.file_path)
+        };
+        // Picking up where we left off needs all of the document to be there
+        if restore_scroll_y.is_some() || restore_line.is_some() {
+            interpreter_progressive.store(false, Ordering::Relaxed);
+        }
+        let diagnostics = Arc::clone(&interpreter.diagnostics);
+        let notes = Arc::clone(&interpreter.notes);
         std::thread::spawn(move || interpreter.interpret_md(interpreter_receiver));
 
-        interpreter_sender.send(md_string)?;
+        interpreter_sender.send(Document {
+            file_path: opts.file_path.clone(),
+            contents: md_string.clone(),
+            theme: None,
+            code_blocks: opts.code_blocks,
+            line_spacing: 1.0,
+            font_opts: opts.font_opts.clone(),
+        })?;
 
         let lines_to_scroll = opts.lines_to_scroll;
+        let scroll = opts.scroll;
 
-        let watcher = Watcher::spawn(event_loop.create_proxy(), opts.file_path.clone());
+        let from_stdin = opts.stdin;
+        let transient = opts.clipboard || from_stdin;
+        if opts.follow {
+            stdin::follow(event_loop.create_proxy());
+        }
+        let event_proxy = event_loop.create_proxy();
+        let watcher = if transient {
+            Watcher::spawn(event_loop.create_proxy(), None)
+        } else {
+            recent_files.record(&opts.file_path);
+            Watcher::spawn(event_loop.create_proxy(), Some(opts.file_path.clone()))
+        };
+        if !transient {
+            renderer.zoom = opts
+                .zoom
+                .or_else(|| recent_files.zoom(&opts.file_path))
+                .unwrap_or(1.)
+                .clamp(MIN_ZOOM, MAX_ZOOM);
+        }
 
         Ok(Self {
             opts,
@@ -228,15 +482,82 @@ impl Inlyne {
             element_queue,
             elements: Vec::new(),
             lines_to_scroll,
+            scroll,
+            momentum: Momentum::default(),
+            smooth_scroll: SmoothScroll::default(),
+            gestures: Gestures::default(),
             interpreter_sender,
             interpreter_should_queue,
+            interpreter_progressive,
             image_cache,
             keycombos,
             need_repositioning: false,
+            restore_scroll_y,
+            restore_line,
             watcher,
+            watched_images: BTreeSet::new(),
+            event_proxy,
+            diagnostics,
+            notes,
+            presenter: None,
+            overlay: None,
+            recent_files,
+            history: History::default(),
+            source: md_string,
+            transient,
+            from_stdin,
+            zen: None,
+            live_config: None,
         })
     }
 
+    /// Picks up changes to the config file at `config_path` while running
+    pub fn watch_config(&mut self, args: Args, config_path: PathBuf) {
+        self.watcher.watch_config(config_path.clone());
+        self.live_config = Some((args, config_path));
+    }
+
+    /// Applies the theme, fonts, and keybindings from the config again after it changed. When
+    /// something's wrong with it, the current ones are kept and the problem gets logged
+    fn reload_config(&mut self) {
+        let Some((args, config_path)) = &self.live_config else {
+            return;
+        };
+        // Removing the config goes back to the defaults
+        let config = if config_path.is_file() {
+            Config::load_from_file(config_path)
+        } else {
+            Ok(Config::default())
+        };
+        let opts = match config.and_then(|config| Opts::parse_and_load_from(args.clone(), config)) {
+            Ok(opts) => opts,
+            Err(err) => {
+                tracing::warn!("Keeping the current settings. Error: {err:#}");
+                return;
+            }
+        };
+        tracing::info!("Reloading the config from {}", config_path.display());
+
+        match KeyCombos::new(opts.keybindings.clone()) {
+            Ok(keycombos) => {
+                self.keycombos = keycombos;
+                self.opts.keybindings = opts.keybindings;
+            }
+            Err(err) => tracing::warn!("Keeping the current keybindings. Error: {err:#}"),
+        }
+        if opts.font_opts != self.opts.font_opts {
+            self.renderer.set_font_options(&opts.font_opts);
+            self.opts.font_opts = opts.font_opts;
+        }
+        self.opts.theme = opts.theme;
+        self.opts.toggled_theme = opts.toggled_theme;
+        self.opts.color_scheme = opts.color_scheme;
+        self.renderer.theme = self.opts.theme.clone();
+        let theme = (self.opts.theme.clone(), self.opts.color_scheme);
+        self.reinterpret(self.source.clone(), Some(theme));
+        self.window.request_redraw();
+    }
+
     pub fn position_queued_elements(
         element_queue: &Arc<Mutex<VecDeque<Element>>>,
         renderer: &mut Renderer,
@@ -259,23 +580,278 @@ impl Inlyne {
                         renderer.zoom,
                     )
                     .unwrap();
-                renderer.positioner.reserved_height +=
-                    DEFAULT_PADDING * renderer.hidpi_scale * renderer.zoom
-                        + positioned_element.bounds.as_ref().unwrap().size.1;
+                renderer
+                    .positioner
+                    .reserve(&positioned_element, renderer.zoom)
+                    .unwrap();
                 elements.push(positioned_element);
             }
         }
     }
 
+    /// Keeps the watcher following whichever images on disk the document shows
+    fn watch_images(&mut self) {
+        let mut images = Vec::new();
+        renderer::collect_images(&mut self.elements, &mut images);
+        let paths: BTreeSet<_> = images
+            .iter()
+            .filter_map(|image| image.path.as_ref()?.canonicalize().ok())
+            .collect();
+        if paths != self.watched_images {
+            self.watched_images = paths.clone();
+            self.watcher.watch_images(paths);
+        }
+    }
+
     fn load_file(&mut self, contents: String) {
+        self.reinterpret(contents, None);
+    }
+
+    /// Swaps in the other of the light and dark themes, which means reinterpreting the document
+    /// since colors get picked while interpreting
+    fn toggle_theme(&mut self) {
+        std::mem::swap(&mut self.opts.theme, &mut self.opts.toggled_theme);
+        self.opts.color_scheme = Some(match self.opts.color_scheme {
+            Some(ResolvedTheme::Dark) => ResolvedTheme::Light,
+            None | Some(ResolvedTheme::Light) => ResolvedTheme::Dark,
+        });
+        self.renderer.theme = self.opts.theme.clone();
+        let theme = (self.opts.theme.clone(), self.opts.color_scheme);
+        self.reinterpret(self.source.clone(), Some(theme));
+        self.window.request_redraw();
+    }
+
+    fn set_fullscreen(&self, fullscreen: bool) {
+        self.window
+            .set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+    }
+
+    /// Goes fullscreen with only the document showing, narrowed and with its lines spaced out a bit
+    /// more. Leaving puts everything back the way that it was
+    fn toggle_zen(&mut self) {
+        let page_width = match self.zen.take() {
+            Some(zen) => {
+                self.set_fullscreen(zen.fullscreen);
+                self.renderer.status_bar = zen.status_bar.then(|| StatusBar::new(&self.source));
+                self.renderer.minimap = zen.minimap;
+                self.renderer.scrollbar = zen.scrollbar;
+                zen.page_width
+            }
+            None => {
+                let zen = Zen {
+                    fullscreen: self.window.fullscreen().is_some(),
+                    status_bar: self.renderer.status_bar.take().is_some(),
+                    minimap: std::mem::take(&mut self.renderer.minimap),
+                    scrollbar: std::mem::take(&mut self.renderer.scrollbar),
+                    page_width: self.renderer.page_width,
+                };
+                self.set_fullscreen(true);
+                let page_width = zen::page_width(zen.page_width, self.renderer.hidpi_scale);
+                self.zen = Some(zen);
+                page_width
+            }
+        };
+        self.renderer.page_width = page_width;
+        self.renderer.positioner.page_width = page_width;
+        // The spacing between lines gets picked while interpreting
+        self.reinterpret(self.source.clone(), None);
+        self.window.request_redraw();
+    }
+
+    /// Starts presenting the document from the slide that's in view, or goes back to scrolling
+    /// through it from wherever the slideshow left off
+    fn toggle_slideshow(&mut self) {
+        self.momentum.stop();
+        self.smooth_scroll.stop();
+        match self.renderer.slideshow.take() {
+            Some(slideshow) => {
+                self.presenter = None;
+                let top = slideshow.slide().map_or(0., |slide| slide.top);
+                self.renderer.set_scroll_y(top);
+            }
+            None => {
+                let mut slideshow = Slideshow::new(&self.elements, self.opts.slide_counter);
+                slideshow.current = slideshow
+                    .slides
+                    .iter()
+                    .rposition(|slide| slide.top <= self.renderer.scroll_y)
+                    .unwrap_or_default();
+                self.renderer.slideshow = Some(slideshow);
+                self.renderer.set_scroll_y(self.renderer.scroll_y);
+            }
+        }
+        self.renderer.hovered_link = None;
+        self.renderer.selection = None;
+        self.window.request_redraw();
+    }
+
+    /// Opens a second window to present from, starting the slideshow when it isn't going yet, or
+    /// closes it again
+    fn toggle_presenter(&mut self, window_target: &EventLoopWindowTarget<InlyneEvent>) {
+        if self.presenter.take().is_some() {
+            return;
+        }
+        if self.renderer.slideshow.is_none() {
+            self.toggle_slideshow();
+        }
+        match Presenter::open(
+            window_target,
+            &self.window,
+            self.renderer.theme.clone(),
+            self.opts.scale,
+            self.opts.font_opts.clone(),
+        ) {
+            Ok(presenter) => self.presenter = Some(presenter),
+            Err(err) => tracing::warn!("{err:?}"),
+        }
+        self.window.request_redraw();
+    }
+
+    /// Fills the presenter window in again once the slideshow has moved on from what it shows
+    fn refresh_presenter(&mut self) {
+        let (Some(presenter), Some(slideshow)) = (&self.presenter, &self.renderer.slideshow) else {
+            return;
+        };
+        let view = (
+            slideshow.current,
+            slideshow.slides.len(),
+            self.renderer.screen_size(),
+            self.renderer.zoom,
+        );
+        if presenter.is_showing(view) {
+            return;
+        }
+        let counter = slideshow.counter_text();
+        let notes: Vec<_> = {
+            let notes = self.notes.lock().unwrap();
+            slideshow
+                .notes(&notes)
+                .into_iter()
+                .map(str::to_owned)
+                .collect()
+        };
+        let preview = self.next_slide_preview();
+        if let Some(presenter) = &mut self.presenter {
+            presenter.show(view, counter, &notes, preview);
+        }
+    }
+
+    /// Draws the slide after the current one offscreen, leaving the view where it was
+    fn next_slide_preview(&mut self) -> Option<::image::RgbaImage> {
+        let screen_height = self.renderer.screen_height();
+        let slideshow = self.renderer.slideshow.as_mut()?;
+        if !slideshow.step(true) {
+            return None;
+        }
+        let top = slideshow.slide().map_or(0., |slide| slide.top);
+        let next_scroll_y = slideshow.scroll_y(top, screen_height);
+        let scroll_y = std::mem::replace(&mut self.renderer.scroll_y, next_scroll_y);
+        let shown = slideshow::shown(self.renderer.slideshow.as_ref(), self.elements.len());
+        let preview = self.renderer.draw_offscreen(&mut self.elements[shown]);
+        self.renderer.scroll_y = scroll_y;
+        if let Some(slideshow) = &mut self.renderer.slideshow {
+            slideshow.step(false);
+        }
+        preview
+            .map_err(|err| tracing::warn!("Failed to preview the next slide: {err:?}"))
+            .ok()
+    }
+
+    fn handle_presenter_event(&mut self, event: WindowEvent<'_>) {
+        let Some(presenter) = &mut self.presenter else {
+            return;
+        };
+        match event {
+            WindowEvent::Resized(size) => presenter.resize(size.width, size.height),
+            WindowEvent::CloseRequested => self.presenter = None,
+            WindowEvent::MouseWheel { delta, .. } => {
+                let num_pixels = match delta {
+                    MouseScrollDelta::LineDelta(_, y_delta) => {
+                        y_delta * 16.0 * self.renderer.hidpi_scale * self.lines_to_scroll
+                    }
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32,
+                };
+                presenter.scroll(num_pixels);
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(keycode),
+                        ..
+                    },
+                ..
+            } => {
+                if keycode == VirtualKeyCode::Escape {
+                    self.presenter = None;
+                } else if let Some(forward) = presenter::step_for(keycode) {
+                    self.step_slide(forward);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn step_slide(&mut self, forward: bool) {
+        let Some(slideshow) = &mut self.renderer.slideshow else {
+            return;
+        };
+        if slideshow.step(forward) {
+            self.momentum.stop();
+            self.smooth_scroll.stop();
+            // Tall slides start from their top
+            let top = slideshow.slide().map_or(0., |slide| slide.top);
+            self.renderer.set_scroll_y(top);
+            self.renderer.hovered_link = None;
+            self.renderer.selection = None;
+            self.window.request_redraw();
+        }
+    }
+
+    fn reinterpret(&mut self, contents: String, theme: Option<(Theme, Option<ResolvedTheme>)>) {
         self.interpreter_should_queue
             .store(false, Ordering::Relaxed);
         self.element_queue.lock().unwrap().clear();
         self.elements.clear();
-        self.renderer.positioner.reserved_height = DEFAULT_PADDING * self.renderer.hidpi_scale;
+        // Nothing from the old document is going to be shown anymore
+        image::pool::pool().cancel_pending();
+        self.renderer.positioner.reset(1.);
         self.renderer.positioner.anchors.clear();
+        self.renderer.positioner.source_lines.clear();
+        self.renderer.hovered_link = None;
+        self.renderer.loupe = None;
+        // Opening a file from history may have already picked somewhere else to go back to
+        let _ = self.restore_scroll_y.get_or_insert(self.renderer.scroll_y);
+        self.refresh_toc();
+        if self.renderer.source_pane.is_some() {
+            self.renderer.source_pane = Some(SourcePane::new(&contents));
+        }
+        if self.renderer.status_bar.is_some() {
+            self.renderer.status_bar = Some(StatusBar::new(&contents));
+        }
+        self.window.set_title(&window_title(
+            &self.opts.window_title,
+            &self.opts.file_path,
+            self.transient && !self.from_stdin,
+            self.from_stdin,
+            &contents,
+        ));
+        self.source = contents.clone();
         self.interpreter_should_queue.store(true, Ordering::Relaxed);
-        self.interpreter_sender.send(contents).unwrap();
+        self.interpreter_sender
+            .send(Document {
+                file_path: self.opts.file_path.clone(),
+                contents,
+                theme,
+                code_blocks: self.opts.code_blocks,
+                line_spacing: if self.zen.is_some() {
+                    zen::LINE_SPACING
+                } else {
+                    1.0
+                },
+                font_opts: self.opts.font_opts.clone(),
+            })
+            .unwrap();
     }
 
     pub fn run(mut self) {
@@ -286,21 +862,71 @@ impl Inlyne {
         let mut last_loc = (0.0, 0.0);
         let mut selection_cache = String::new();
         let mut selecting = false;
+        let mut clicks = ClickCounter::default();
+        // Keeps the character from a key that triggered a keybinding from also getting typed
+        let mut key_used = false;
 
         let event_loop = self.event_loop.take().unwrap();
-        let event_loop_proxy = event_loop.create_proxy();
         // SAFETY: Since this takes a pointer to the winit event loop, it MUST be dropped first,
         // which is done by `move` into event loop.
         let mut clipboard = unsafe { clipboard::Clipboard::new(event_loop.raw_display_handle()) };
-        event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Wait;
+        event_loop.run(move |event, window_target, control_flow| {
+            let deadline = [
+                self.gestures.long_press_deadline(),
+                self.presenter.as_ref().map(Presenter::next_tick),
+                self.renderer.next_animation_frame,
+                self.renderer.scrollbar_hide_deadline(),
+                self.renderer.copied_flash_deadline(),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            *control_flow = match deadline {
+                Some(deadline) => ControlFlow::WaitUntil(deadline),
+                None => ControlFlow::Wait,
+            };
 
+            let presenter_id = self
+                .presenter
+                .as_ref()
+                .map(|presenter| presenter.window.id());
             match event {
+                Event::RedrawRequested(window_id) if Some(window_id) == presenter_id => {
+                    if let Some(presenter) = &mut self.presenter {
+                        if let Err(err) = presenter.redraw() {
+                            tracing::warn!("Failed to draw the presenter window: {err:?}");
+                        }
+                    }
+                }
+                Event::WindowEvent { window_id, event } if Some(window_id) == presenter_id => {
+                    self.handle_presenter_event(event)
+                }
                 Event::UserEvent(inlyne_event) => match inlyne_event {
                     InlyneEvent::LoadedImage(src, image_data) => {
+                        // Whatever was uploaded before an image got loaded again is out of date
+                        let mut images = Vec::new();
+                        renderer::collect_images(&mut self.elements, &mut images);
+                        if let Some(lightbox) = &mut self.renderer.lightbox {
+                            images.push(&mut lightbox.image);
+                        }
+                        if let Some(loupe) = &mut self.renderer.loupe {
+                            images.push(&mut loupe.image);
+                        }
+                        for image in images {
+                            if Arc::ptr_eq(&image.image_data, &image_data) {
+                                image.forget_texture();
+                            }
+                        }
                         self.image_cache.lock().unwrap().insert(src, image_data);
                         self.need_repositioning = true;
                     }
+                    InlyneEvent::ImageError { src, reason } => {
+                        tracing::debug!("Showing image `{src}` as broken since it {reason}");
+                        // The placeholder grows to fit the message
+                        self.need_repositioning = true;
+                    }
+                    // A stray reload from before we switched over to the clipboard
+                    InlyneEvent::FileReload if self.transient => {}
                     InlyneEvent::FileReload => match read_to_string(&self.opts.file_path) {
                         Ok(contents) => self.load_file(contents),
                         Err(err) => {
@@ -312,6 +938,11 @@ impl Inlyne {
                         }
                     },
                     InlyneEvent::FileChange { contents } => self.load_file(contents),
+                    InlyneEvent::StdinChange { contents } if self.from_stdin => {
+                        self.load_file(contents)
+                    }
+                    // Something else is being shown now
+                    InlyneEvent::StdinChange { .. } => {}
                     InlyneEvent::Reposition => {
                         self.need_repositioning = true;
                     }
@@ -321,73 +952,309 @@ impl Inlyne {
                             &mut self.renderer,
                             &mut self.elements,
                         );
+                        if let Some(scroll_y) = self.restore_scroll_y.take() {
+                            self.renderer.set_scroll_y(scroll_y);
+                        }
+                        if let Some(line) = self.restore_line.take() {
+                            self.scroll_to_line(line);
+                        }
+                        if let Some(presenter) = &mut self.presenter {
+                            presenter.invalidate();
+                        }
+                        self.refresh_toc();
+                        self.refresh_search(false);
+                        self.watch_images();
                         self.window.request_redraw()
                     }
+                    InlyneEvent::ImageChange(path) => {
+                        let mut images = Vec::new();
+                        renderer::collect_images(&mut self.elements, &mut images);
+                        for image in images {
+                            let image_path =
+                                image.path.as_ref().and_then(|p| p.canonicalize().ok());
+                            if image_path.as_ref() == Some(&path) {
+                                image.reload(Box::new(EventLoopCallback(self.event_proxy.clone())));
+                            }
+                        }
+                    }
+                    InlyneEvent::ConfigChange => self.reload_config(),
+                    InlyneEvent::Request(request) => self.handle_request(request, control_flow),
+                    InlyneEvent::QuickOpenFound => {
+                        if let Some(Overlay::QuickOpen(quick_open)) = &mut self.overlay {
+                            if quick_open.receive() {
+                                self.window.request_redraw();
+                            }
+                        }
+                    }
                 },
                 Event::RedrawRequested(_) => {
+                    let num_elements = self.elements.len();
                     Self::position_queued_elements(
                         &self.element_queue,
                         &mut self.renderer,
                         &mut self.elements,
                     );
+                    if self.elements.len() != num_elements {
+                        self.refresh_toc();
+                        self.refresh_search(false);
+                    }
+                    if let Some(slideshow) = &mut self.renderer.slideshow {
+                        slideshow.update(&self.elements);
+                    }
+                    if let Some(num_pixels) = self.momentum.tick(Instant::now()) {
+                        let prev_scroll = self.renderer.scroll_y;
+                        self.renderer.set_scroll_y(prev_scroll - num_pixels);
+                        // Ran into the top or bottom
+                        if self.renderer.scroll_y == prev_scroll {
+                            self.momentum.stop();
+                        }
+                        if self.momentum.is_coasting() {
+                            self.window.request_redraw();
+                        }
+                    }
+                    let now = Instant::now();
+                    if let Some(scroll_y) = self.smooth_scroll.tick(self.renderer.scroll_y, now) {
+                        let prev_scroll = self.renderer.scroll_y;
+                        self.renderer.set_scroll_y(scroll_y);
+                        // The target got clamped out of reach, like when the document shrank
+                        if self.renderer.scroll_y == prev_scroll && scroll_y != prev_scroll {
+                            self.smooth_scroll.stop();
+                        }
+                        if self.smooth_scroll.is_animating() {
+                            self.window.request_redraw();
+                        }
+                    }
                     self.renderer.set_scroll_y(self.renderer.scroll_y);
-                    self.renderer
-                        .redraw(&mut self.elements)
+                    self.renderer.panel = self.overlay.as_ref().map(|overlay| match overlay {
+                        Overlay::Diagnostics => {
+                            Panel::diagnostics(&self.diagnostics.lock().unwrap())
+                        }
+                        Overlay::RecentFiles { selected } => {
+                            Panel::recent_files(self.recent_files.files(), *selected)
+                        }
+                        Overlay::QuickOpen(quick_open) => quick_open.panel(),
+                        Overlay::ImageMenu(menu) => menu.panel(),
+                        Overlay::Search(search) => search.panel(),
+                    });
+                    self.renderer.search = match &self.overlay {
+                        Some(Overlay::Search(search)) => Some(search.clone()),
+                        _ => None,
+                    };
+                    let shown =
+                        slideshow::shown(self.renderer.slideshow.as_ref(), self.elements.len());
+                    let frame = self
+                        .renderer
+                        .redraw(&mut self.elements[shown])
                         .context("Renderer failed to redraw the screen")
                         .unwrap();
+                    if frame == Frame::Skipped {
+                        self.window.request_redraw();
+                    }
                     if selecting {
                         selection_cache = self.renderer.selection_text.clone();
                     }
+                    self.refresh_presenter();
                 }
                 Event::WindowEvent { event, .. } => match event {
                     WindowEvent::Resized(size) => pending_resize = Some(size),
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::MouseWheel { delta, .. } => match delta {
-                        MouseScrollDelta::PixelDelta(pos) => {
-                            Self::scroll_pixels(&mut self.renderer, &self.window, pos.y as f32)
+                    WindowEvent::MouseWheel { delta, .. } if self.renderer.lightbox.is_some() => {
+                        let notches = match delta {
+                            MouseScrollDelta::LineDelta(_, y_delta) => y_delta,
+                            MouseScrollDelta::PixelDelta(pos) => {
+                                pos.y as f32
+                                    / (lightbox::PIXELS_PER_NOTCH * self.renderer.hidpi_scale)
+                            }
+                        };
+                        let screen_size = self.renderer.screen_size();
+                        let focal = (last_loc.0, last_loc.1 - self.renderer.scroll_y);
+                        if let Some(lightbox) = &mut self.renderer.lightbox {
+                            lightbox.zoom_by(lightbox::ZOOM_STEP.powf(notches), focal, screen_size);
                         }
-                        MouseScrollDelta::LineDelta(_, y_delta) => Self::scroll_lines(
-                            &mut self.renderer,
-                            &self.window,
-                            self.lines_to_scroll,
-                            y_delta,
-                        ),
-                    },
+                        self.window.request_redraw();
+                    }
+                    WindowEvent::MouseWheel { delta, .. } if modifiers.ctrl() => {
+                        let notches = match delta {
+                            MouseScrollDelta::LineDelta(_, y_delta) => y_delta,
+                            MouseScrollDelta::PixelDelta(pos) => {
+                                pos.y as f32
+                                    / (lightbox::PIXELS_PER_NOTCH * self.renderer.hidpi_scale)
+                            }
+                        };
+                        let zoom = self.renderer.zoom * WHEEL_ZOOM_STEP.powf(notches);
+                        let focal_y = last_loc.1 - self.renderer.scroll_y;
+                        self.set_zoom(zoom, focal_y);
+                    }
+                    WindowEvent::MouseWheel { delta, phase, .. } => {
+                        let direction = if self.scroll.natural { -1.0 } else { 1.0 };
+                        let multiplier = self.scroll.speed * direction;
+                        // Code blocks that don't wrap scroll sideways with a sideways scroll or
+                        // while holding shift
+                        let sideways = match delta {
+                            MouseScrollDelta::LineDelta(x_delta, y_delta) => {
+                                let num_lines = if modifiers.shift() { y_delta } else { x_delta };
+                                num_lines * 16.0 * self.renderer.hidpi_scale * self.renderer.zoom
+                            }
+                            MouseScrollDelta::PixelDelta(pos) if modifiers.shift() => pos.y as f32,
+                            MouseScrollDelta::PixelDelta(pos) => pos.x as f32,
+                        };
+                        if sideways != 0.0
+                            && self.scroll_code_block(last_loc, -sideways * multiplier)
+                        {
+                            return;
+                        }
+                        match delta {
+                            // Touchpads and other precise devices
+                            MouseScrollDelta::PixelDelta(pos) => {
+                                let num_pixels = pos.y as f32 * multiplier;
+                                let now = Instant::now();
+                                match phase {
+                                    TouchPhase::Started => {
+                                        self.momentum.stop();
+                                        self.momentum.track(num_pixels, now);
+                                    }
+                                    TouchPhase::Moved => self.momentum.track(num_pixels, now),
+                                    TouchPhase::Ended if self.scroll.momentum => {
+                                        if self.momentum.release(now) {
+                                            self.window.request_redraw();
+                                        }
+                                    }
+                                    TouchPhase::Ended | TouchPhase::Cancelled => {
+                                        self.momentum.stop()
+                                    }
+                                }
+                                self.scroll_pixels(num_pixels)
+                            }
+                            MouseScrollDelta::LineDelta(_, y_delta) => {
+                                self.momentum.stop();
+                                self.scroll_lines(y_delta * multiplier)
+                            }
+                        }
+                    }
+                    WindowEvent::TouchpadMagnify { delta, .. } => {
+                        let zoom = self.renderer.zoom * (1.0 + delta as f32);
+                        let focal_y = last_loc.1 - self.renderer.scroll_y;
+                        self.set_zoom(zoom, focal_y);
+                    }
+                    WindowEvent::Touch(touch) => {
+                        if touch.phase == TouchPhase::Started {
+                            self.momentum.stop();
+                        }
+                        let location = (touch.location.x as f32, touch.location.y as f32);
+                        let slop = TOUCH_SLOP * self.renderer.hidpi_scale;
+                        let gesture = self.gestures.touch(
+                            touch.id,
+                            touch.phase,
+                            location,
+                            Instant::now(),
+                            slop,
+                        );
+                        if let Some(gesture) = gesture {
+                            self.handle_gesture(gesture);
+                        }
+                        selecting = self.gestures.is_selecting();
+                    }
+                    WindowEvent::CursorLeft { .. } => {
+                        let had_loupe = self.renderer.loupe.take().is_some();
+                        if self.renderer.hovered_link.take().is_some() || had_loupe {
+                            self.window.request_redraw();
+                        }
+                    }
                     WindowEvent::CursorMoved { position, .. } => {
-                        let screen_size = self.renderer.screen_size();
+                        let screen_size = self.renderer.doc_size();
                         let loc = (
                             position.x as f32,
                             position.y as f32 + self.renderer.scroll_y,
                         );
+                        last_loc = loc;
 
-                        let cursor_icon = if let Some(hoverable) = Self::find_hoverable(
+                        if let Some(lightbox) = &mut self.renderer.lightbox {
+                            if lightbox.drag_to(position.into()) {
+                                self.window.request_redraw();
+                            }
+                            return;
+                        }
+
+                        let shown =
+                            slideshow::shown(self.renderer.slideshow.as_ref(), self.elements.len());
+                        let hoverable = Self::find_hoverable(
                             &mut self.renderer.text_system,
                             &mut self.renderer.positioner.taffy,
-                            &self.elements,
+                            &self.elements[shown],
                             loc,
                             screen_size,
+                            self.renderer.page_width,
                             self.renderer.zoom,
-                        ) {
-                            match hoverable {
-                                Hoverable::Image(Image { is_link: None, .. }) => {
-                                    CursorIcon::Default
-                                }
-                                Hoverable::Text(Text { link: None, .. }) => CursorIcon::Text,
-                                _some_link => CursorIcon::Hand,
+                        );
+                        let over_toc_entry = self
+                            .renderer
+                            .toc_entry_at((position.x as f32, position.y as f32))
+                            .is_some();
+                        let over_minimap = self
+                            .renderer
+                            .minimap_scroll_at((position.x as f32, position.y as f32))
+                            .is_some();
+                        let over_checkbox = self.opts.toggle_checkboxes
+                            && !self.transient
+                            && self.renderer.checkbox_at(&self.elements, loc).is_some();
+                        let over_copy_button = self.renderer.code_copy_button_at(loc).is_some();
+                        let cursor_icon = match &hoverable {
+                            _ if over_toc_entry
+                                || over_minimap
+                                || over_checkbox
+                                || over_copy_button =>
+                            {
+                                CursorIcon::Hand
                             }
-                        } else {
-                            CursorIcon::Default
+                            Some(hoverable) if hoverable.link().is_some() => CursorIcon::Hand,
+                            Some(Hoverable::Image(image, _)) if image.is_loaded() => {
+                                CursorIcon::ZoomIn
+                            }
+                            Some(Hoverable::Text(_)) => CursorIcon::Text,
+                            _ => CursorIcon::Default,
                         };
                         self.window.set_cursor_icon(cursor_icon);
 
-                        if scrollbar_held.is_some()
-                            || (Rect::new(
-                                (screen_size.0 - DEFAULT_MARGIN / 4., 0.),
-                                (DEFAULT_MARGIN / 4., screen_size.1),
-                            )
-                            .contains(position.into())
-                                && mouse_down)
+                        if self.opts.image_loupe {
+                            let hovered_image = match &hoverable {
+                                Some(Hoverable::Image(image, bounds)) if image.is_loaded() => {
+                                    Some((*image, *bounds))
+                                }
+                                _ => None,
+                            };
+                            if loupe::follow(&mut self.renderer.loupe, hovered_image, loc) {
+                                self.window.request_redraw();
+                            }
+                        }
+
+                        let hovered_link = hoverable.and_then(|hoverable| {
+                            let target = hoverable.link()?.to_owned();
+                            let image_bounds = match hoverable {
+                                Hoverable::Image(_, bounds) => Some(bounds.clone()),
+                                _ => None,
+                            };
+                            Some(HoveredLink {
+                                target,
+                                image_bounds,
+                            })
+                        });
+                        if hovered_link != self.renderer.hovered_link {
+                            self.renderer.hovered_link = hovered_link;
+                            self.window.request_redraw();
+                        }
+
+                        let on_scrollbar = Rect::new(
+                            (screen_size.0 - DEFAULT_MARGIN / 4., 0.),
+                            (DEFAULT_MARGIN / 4., screen_size.1),
+                        )
+                        .contains(position.into());
+                        if (on_scrollbar || scrollbar_held.is_some())
+                            && self.renderer.show_scrollbar()
                         {
+                            self.window.request_redraw();
+                        }
+
+                        if scrollbar_held.is_some() || (on_scrollbar && mouse_down) {
                             let scrollbar_height = (screen_size.1
                                 / self.renderer.positioner.reserved_height)
                                 * screen_size.1;
@@ -424,6 +1291,7 @@ impl Inlyne {
                             };
                             let target_scroll = ((pos_y - scrollbar_height / 2.) / screen_size.1)
                                 * self.renderer.positioner.reserved_height;
+                            self.smooth_scroll.stop();
                             self.renderer.set_scroll_y(target_scroll);
                             self.window.request_redraw();
                         } else if let Some(selection) = &mut self.renderer.selection {
@@ -433,7 +1301,89 @@ impl Inlyne {
                                 self.window.request_redraw();
                             }
                         }
-                        last_loc = loc;
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if matches!(self.overlay, Some(Overlay::ImageMenu(_))) => {
+                        let pos = (last_loc.0, last_loc.1 - self.renderer.scroll_y);
+                        self.click_image_menu(pos);
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Other(num),
+                        ..
+                    } => {
+                        let button = MouseButton::Other(num);
+                        if let Some(direction) = history::mouse_button_direction(button) {
+                            self.step_history(direction);
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Right,
+                        ..
+                    } if self.renderer.lightbox.is_none() => {
+                        self.open_image_menu(last_loc, 0.0);
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } if self.renderer.lightbox.is_some() => {
+                        let screen_size = self.renderer.screen_size();
+                        let pos = (last_loc.0, last_loc.1 - self.renderer.scroll_y);
+                        let Some(lightbox) = &mut self.renderer.lightbox else {
+                            return;
+                        };
+                        match state {
+                            ElementState::Pressed => lightbox.grab(pos),
+                            ElementState::Released => {
+                                if lightbox.release(pos, screen_size) {
+                                    self.renderer.lightbox = None;
+                                    self.window.request_redraw();
+                                }
+                            }
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if self
+                        .renderer
+                        .is_over_toc((last_loc.0, last_loc.1 - self.renderer.scroll_y)) =>
+                    {
+                        let pos = (last_loc.0, last_loc.1 - self.renderer.scroll_y);
+                        if let Some(index) = self.renderer.toc_entry_at(pos) {
+                            self.jump_to_heading(index);
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if self
+                        .renderer
+                        .minimap_scroll_at((last_loc.0, last_loc.1 - self.renderer.scroll_y))
+                        .is_some() =>
+                    {
+                        let pos = (last_loc.0, last_loc.1 - self.renderer.scroll_y);
+                        if let Some(scroll_y) = self.renderer.minimap_scroll_at(pos) {
+                            self.glide_to(scroll_y);
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if self.renderer.code_copy_button_at(last_loc).is_some() => {
+                        if let Some((button, code)) = self.renderer.code_copy_button_at(last_loc) {
+                            clipboard.set_contents(code.to_owned());
+                            self.renderer.flash_copied(button);
+                            self.window.request_redraw();
+                        }
                     }
                     WindowEvent::MouseInput {
                         state,
@@ -441,112 +1391,38 @@ impl Inlyne {
                         ..
                     } => match state {
                         ElementState::Pressed => {
+                            // Grabbing the document stops it from gliding any further
+                            self.momentum.stop();
+                            self.smooth_scroll.stop();
+                            let slop = CLICK_SLOP * self.renderer.hidpi_scale;
+                            let click_count = clicks.press(last_loc, Instant::now(), slop);
+
                             // Reset selection
                             if self.renderer.selection.is_some() {
                                 self.renderer.selection = None;
                                 self.window.request_redraw();
                             }
 
-                            // Try to click a link
-                            let screen_size = self.renderer.screen_size();
-                            if let Some(hoverable) = Self::find_hoverable(
-                                &mut self.renderer.text_system,
-                                &mut self.renderer.positioner.taffy,
-                                &self.elements,
-                                last_loc,
-                                screen_size,
-                                self.renderer.zoom,
-                            ) {
-                                if let Hoverable::Summary(summary) = hoverable {
-                                    let mut hidden = summary.hidden.borrow_mut();
-                                    *hidden = !*hidden;
-                                    event_loop_proxy
-                                        .send_event(InlyneEvent::Reposition)
-                                        .unwrap();
+                            // Only set selection when not over link
+                            let clicked = self.click(last_loc, 0.0, modifiers.shift());
+                            let presenting = self.renderer.slideshow.is_some();
+                            if !clicked && presenting {
+                                // Clicking anywhere else on a slide moves on to the next one
+                                if click_count == 1 {
+                                    self.step_slide(true);
                                 }
-
-                                let maybe_link = match hoverable {
-                                    Hoverable::Image(Image { is_link, .. }) => is_link,
-                                    Hoverable::Text(Text { link, .. }) => link,
-                                    Hoverable::Summary(_) => &None,
+                            } else if !clicked && self.renderer.selection.is_none() {
+                                // Double clicks select a word and triple clicks a paragraph
+                                let selection = match click_count {
+                                    1 => None,
+                                    count => self.select_text(last_loc, count == 3),
                                 };
-
-                                if let Some(link) = maybe_link {
-                                    let maybe_path = PathBuf::from_str(link).ok();
-                                    let is_local_md = maybe_path.as_ref().map_or(false, |p| {
-                                        p.extension().map_or(false, |ext| ext == "md")
-                                            && !p.to_str().map_or(false, |s| s.starts_with("http"))
-                                    });
-                                    if is_local_md {
-                                        // Open markdown files ourselves
-                                        let path = maybe_path.expect("not a path");
-                                        // Handle relative paths and make them
-                                        // absolute by prepending current
-                                        // parent
-                                        let path = if path.is_relative() {
-                                            // Simply canonicalizing it doesn't suffice and leads to "no such file or directory"
-                                            let current_parent = self
-                                                .opts
-                                                .file_path
-                                                .parent()
-                                                .expect("no current parent");
-                                            let mut normalized_link = path.as_path();
-                                            if let Ok(stripped) = normalized_link
-                                                .strip_prefix(std::path::Component::CurDir)
-                                            {
-                                                normalized_link = stripped;
-                                            }
-                                            let mut link = current_parent.to_path_buf();
-                                            link.push(normalized_link);
-                                            link
-                                        } else {
-                                            path
-                                        };
-                                        // Open them in a new window, akin to what a browser does
-                                        if modifiers.shift() {
-                                            Command::new(
-                                                std::env::current_exe()
-                                                    .unwrap_or_else(|_| "inlyne".into()),
-                                            )
-                                            .args(Opts::program_args(&path))
-                                            .spawn()
-                                            .expect("Could not spawn new inlyne instance");
-                                        } else {
-                                            match read_to_string(&path) {
-                                                Ok(contents) => {
-                                                    self.opts.file_path = path;
-                                                    self.watcher.update_file(
-                                                        &self.opts.file_path,
-                                                        contents,
-                                                    );
-                                                    // TODO: Once and if history is implemented,
-                                                    // old scroll_y might be stored there
-                                                    self.renderer.set_scroll_y(0.);
-                                                }
-                                                Err(err) => {
-                                                    tracing::warn!(
-                                                        "Failed loading markdown file at {}\nError: {}",
-                                                        path.display(),
-                                                        err,
-                                                    );
-                                                }
-                                            }
-                                        }
-                                    } else if let Some(anchor_pos) =
-                                        self.renderer.positioner.anchors.get(link)
-                                    {
-                                        self.renderer.set_scroll_y(*anchor_pos);
-                                        self.window.request_redraw();
-                                        self.window.set_cursor_icon(CursorIcon::Default);
-                                    } else {
-                                        open::that(link).unwrap();
-                                    }
-                                } else if self.renderer.selection.is_none() {
-                                    // Only set selection when not over link
-                                    self.renderer.selection = Some((last_loc, last_loc));
+                                if selection.is_some() {
+                                    selecting = true;
+                                    self.window.request_redraw();
                                 }
-                            } else if self.renderer.selection.is_none() {
-                                self.renderer.selection = Some((last_loc, last_loc));
+                                self.renderer.selection =
+                                    Some(selection.unwrap_or((last_loc, last_loc)));
                             }
 
                             mouse_down = true;
@@ -558,6 +1434,20 @@ impl Inlyne {
                         }
                     },
                     WindowEvent::ModifiersChanged(new_state) => modifiers = new_state,
+                    WindowEvent::ReceivedCharacter(c) => {
+                        let is_shortcut = modifiers.ctrl() || modifiers.logo() || modifiers.alt();
+                        if std::mem::take(&mut key_used) || c.is_control() || is_shortcut {
+                            // Nothing to type
+                        } else if let Some(Overlay::QuickOpen(quick_open)) = &mut self.overlay {
+                            quick_open.push_char(c);
+                            self.window.request_redraw();
+                        } else if let Some(Overlay::Search(search)) = &mut self.overlay {
+                            if !search.confirmed {
+                                search.push_char(c);
+                                self.refresh_search(true);
+                            }
+                        }
+                    }
                     WindowEvent::KeyboardInput {
                         input:
                             KeyboardInput {
@@ -568,17 +1458,37 @@ impl Inlyne {
                             },
                         ..
                     } => {
+                        key_used = false;
+                        if let Some(keycode) = virtual_keycode {
+                            if keycode == VirtualKeyCode::Escape
+                                && self.renderer.lightbox.take().is_some()
+                            {
+                                self.window.request_redraw();
+                                return;
+                            }
+                            if self.handle_overlay_key(keycode, modifiers) {
+                                return;
+                            }
+                            // Leaves the slideshow instead of quitting
+                            if keycode == VirtualKeyCode::Escape
+                                && self.renderer.slideshow.is_some()
+                            {
+                                self.toggle_slideshow();
+                                return;
+                            }
+                        }
+
                         let key = Key::new(virtual_keycode, scancode);
                         let modified_key = ModifiedKey(key, modifiers);
                         if let Some(action) = self.keycombos.munch(modified_key) {
+                            key_used = true;
                             match action {
                                 Action::ToEdge(direction) => {
                                     let scroll = match direction {
                                         VertDirection::Up => 0.0,
-                                        VertDirection::Down => f32::INFINITY,
+                                        VertDirection::Down => self.renderer.max_scroll_y(),
                                     };
-                                    self.renderer.set_scroll_y(scroll);
-                                    self.window.request_redraw();
+                                    self.glide_to(scroll);
                                 }
                                 Action::Scroll(direction) => {
                                     let lines = match direction {
@@ -586,12 +1496,11 @@ impl Inlyne {
                                         VertDirection::Down => -1.0,
                                     };
 
-                                    Self::scroll_lines(
-                                        &mut self.renderer,
-                                        &self.window,
-                                        self.lines_to_scroll,
-                                        lines,
-                                    )
+                                    self.scroll_lines(lines)
+                                }
+                                // Presentation remotes page up and down to change slides
+                                Action::Page(direction) if self.renderer.slideshow.is_some() => {
+                                    self.step_slide(direction == VertDirection::Down)
                                 }
                                 Action::Page(direction) => {
                                     // Move 90% of current page height
@@ -601,11 +1510,29 @@ impl Inlyne {
                                         VertDirection::Down => -scroll_amount,
                                     };
 
-                                    Self::scroll_pixels(
-                                        &mut self.renderer,
-                                        &self.window,
-                                        scroll_with_direction,
-                                    );
+                                    self.glide_by(scroll_with_direction);
+                                }
+                                Action::HalfPage(direction) => {
+                                    let scroll_amount = self.renderer.config.height as f32 * 0.5;
+                                    let scroll_with_direction = match direction {
+                                        VertDirection::Up => scroll_amount,
+                                        VertDirection::Down => -scroll_amount,
+                                    };
+
+                                    self.glide_by(scroll_with_direction);
+                                }
+                                Action::Zoom(zoom_action) if self.renderer.lightbox.is_some() => {
+                                    let screen_size = self.renderer.screen_size();
+                                    let center = (screen_size.0 / 2., screen_size.1 / 2.);
+                                    if let Some(lightbox) = &mut self.renderer.lightbox {
+                                        let factor = match zoom_action {
+                                            Zoom::In => lightbox::ZOOM_STEP,
+                                            Zoom::Out => lightbox::ZOOM_STEP.recip(),
+                                            Zoom::Reset => lightbox.zoom.recip(),
+                                        };
+                                        lightbox.zoom_by(factor, center, screen_size);
+                                    }
+                                    self.window.request_redraw();
                                 }
                                 Action::Zoom(zoom_action) => {
                                     let zoom = match zoom_action {
@@ -613,18 +1540,121 @@ impl Inlyne {
                                         Zoom::Out => self.renderer.zoom * 0.9,
                                         Zoom::Reset => 1.0,
                                     };
-
-                                    self.renderer.zoom = zoom;
+                                    self.set_zoom(zoom, 0.0);
+                                }
+                                Action::Copy => {
+                                    clipboard.set_contents(selection_cache.trim().to_owned())
+                                }
+                                Action::ToggleDiagnostics => {
+                                    self.toggle_overlay(Overlay::Diagnostics)
+                                }
+                                Action::ToggleRecentFiles => {
+                                    self.toggle_overlay(Overlay::RecentFiles { selected: 0 })
+                                }
+                                Action::ToggleSource => {
+                                    let show = self.renderer.source_pane.is_none();
+                                    self.renderer.source_pane =
+                                        show.then(|| SourcePane::new(&self.source));
+                                    self.renderer.positioner.side_pane = show;
+                                    let old_reserved = self.renderer.positioner.reserved_height;
+                                    self.renderer.reposition(&mut self.elements).unwrap();
+                                    let new_reserved = self.renderer.positioner.reserved_height;
+                                    self.renderer.set_scroll_y(
+                                        self.renderer.scroll_y * (new_reserved / old_reserved),
+                                    );
+                                    self.refresh_search(false);
+                                    self.window.request_redraw();
+                                }
+                                Action::ToggleToc => {
+                                    let show = self.renderer.toc.is_none();
+                                    self.renderer.toc = show.then(|| Toc::new(&self.elements));
+                                    self.renderer.positioner.toc_pane = show;
                                     let old_reserved = self.renderer.positioner.reserved_height;
                                     self.renderer.reposition(&mut self.elements).unwrap();
                                     let new_reserved = self.renderer.positioner.reserved_height;
                                     self.renderer.set_scroll_y(
                                         self.renderer.scroll_y * (new_reserved / old_reserved),
                                     );
+                                    self.refresh_search(false);
+                                    self.window.request_redraw();
+                                }
+                                Action::ToggleStatusBar => {
+                                    let show = self.renderer.status_bar.is_none();
+                                    self.renderer.status_bar =
+                                        show.then(|| StatusBar::new(&self.source));
+                                    // Showing it leaves more room to scroll
+                                    self.renderer.set_scroll_y(self.renderer.scroll_y);
+                                    self.window.request_redraw();
+                                }
+                                Action::ToggleMinimap => {
+                                    self.renderer.minimap ^= true;
+                                    self.window.request_redraw();
+                                }
+                                Action::History(direction) => self.step_history(direction),
+                                Action::ToggleSearch => match &mut self.overlay {
+                                    // Start on a new query instead of closing the confirmed one
+                                    Some(Overlay::Search(search)) if search.confirmed => {
+                                        *search = Search::default();
+                                        self.refresh_search(true);
+                                    }
+                                    _ => self.toggle_overlay(Overlay::Search(Search::default())),
+                                },
+                                Action::NextMatch => {
+                                    if let Some(Overlay::Search(search)) = &mut self.overlay {
+                                        search.select_next();
+                                        self.scroll_to_match();
+                                    }
+                                }
+                                Action::PrevMatch => {
+                                    if let Some(Overlay::Search(search)) = &mut self.overlay {
+                                        search.select_prev();
+                                        self.scroll_to_match();
+                                    }
+                                }
+                                Action::ToggleQuickOpen => {
+                                    // Avoid searching for files when we're just closing it
+                                    self.overlay = match self.overlay {
+                                        Some(Overlay::QuickOpen(_)) => None,
+                                        _ => {
+                                            let event_proxy = self.event_proxy.clone();
+                                            let quick_open =
+                                                QuickOpen::new(&self.opts.file_path, move || {
+                                                    let _ = event_proxy
+                                                        .send_event(InlyneEvent::QuickOpenFound);
+                                                });
+                                            Some(Overlay::QuickOpen(quick_open))
+                                        }
+                                    };
+                                    self.window.request_redraw();
+                                }
+                                Action::PreviewClipboard => {
+                                    if let Some(contents) = clipboard.get_contents() {
+                                        self.preview_clipboard(contents);
+                                    }
+                                }
+                                Action::Print => {
+                                    print::print(self.opts.export_opts(), self.source.clone())
+                                }
+                                Action::ToggleTheme => self.toggle_theme(),
+                                Action::ToggleLineNumbers => {
+                                    self.opts.code_blocks.line_numbers ^= true;
+                                    self.reinterpret(self.source.clone(), None);
                                     self.window.request_redraw();
                                 }
-                                Action::Copy => clipboard
-                                    .set_contents(selection_cache.trim().to_owned()),
+                                Action::ToggleCodeWrap => {
+                                    self.opts.code_blocks.wrap ^= true;
+                                    self.reinterpret(self.source.clone(), None);
+                                    self.window.request_redraw();
+                                }
+                                Action::ToggleFullscreen => {
+                                    self.set_fullscreen(self.window.fullscreen().is_none())
+                                }
+                                Action::ToggleZen => self.toggle_zen(),
+                                Action::ToggleSlideshow => self.toggle_slideshow(),
+                                Action::TogglePresenter => self.toggle_presenter(window_target),
+                                Action::NextSlide => self.step_slide(true),
+                                Action::PrevSlide => self.step_slide(false),
+                                Action::Reload => self.reload(),
                                 Action::Quit => *control_flow = ControlFlow::Exit,
                             }
                         }
@@ -637,46 +1667,796 @@ impl Inlyne {
                     // with a bunch of costly resizes. (https://github.com/trimental/inlyne/issues/25)
                     if let Some(size) = pending_resize.take() {
                         if size.width > 0 && size.height > 0 {
-                            self.renderer.config.width = size.width;
-                            self.renderer.config.height = size.height;
-                            self.renderer.positioner.screen_size = size.into();
-                            self.renderer
-                                .surface
-                                .configure(&self.renderer.device, &self.renderer.config);
+                            self.renderer.resize(size.width, size.height);
                             let old_reserved = self.renderer.positioner.reserved_height;
                             self.renderer.reposition(&mut self.elements).unwrap();
                             let new_reserved = self.renderer.positioner.reserved_height;
                             self.renderer.set_scroll_y(
                                 self.renderer.scroll_y * (new_reserved / old_reserved),
                             );
+                            self.refresh_search(false);
                             self.window.request_redraw();
                         }
                     }
 
+                    if let Some(gesture) = self.gestures.poll(Instant::now()) {
+                        self.handle_gesture(gesture);
+                        selecting = self.gestures.is_selecting();
+                    }
+
+                    if self
+                        .renderer
+                        .next_animation_frame
+                        .is_some_and(|frame| frame <= Instant::now())
+                    {
+                        // Cleared so that we don't keep asking for redraws until it's drawn
+                        self.renderer.next_animation_frame = None;
+                        self.window.request_redraw();
+                    }
+                    if self
+                        .renderer
+                        .scrollbar_hide_deadline()
+                        .is_some_and(|hide| hide <= Instant::now())
+                    {
+                        self.window.request_redraw();
+                    }
+                    if self
+                        .renderer
+                        .copied_flash_deadline()
+                        .is_some_and(|done| done <= Instant::now())
+                    {
+                        self.renderer.copied_code = None;
+                        self.window.request_redraw();
+                    }
+
+                    if let Some(presenter) = &self.presenter {
+                        if presenter.next_tick() <= Instant::now() {
+                            presenter.window.request_redraw();
+                        }
+                    }
+
                     if self.need_repositioning {
+                        if let Some(presenter) = &mut self.presenter {
+                            presenter.invalidate();
+                        }
                         self.renderer.reposition(&mut self.elements).unwrap();
+                        self.refresh_toc();
+                        self.refresh_search(false);
                         self.window.request_redraw();
                         self.need_repositioning = false;
                     }
                 }
+                Event::LoopDestroyed => {
+                    self.remember_view();
+                    self.remember_window();
+                }
                 _ => {}
             }
         });
     }
 
-    fn scroll_lines(
-        renderer: &mut Renderer,
-        window: &Window,
-        lines_to_scroll: f32,
-        num_lines: f32,
-    ) {
-        let num_pixels = num_lines * 16.0 * lines_to_scroll * renderer.hidpi_scale * renderer.zoom;
-        Self::scroll_pixels(renderer, window, num_pixels);
+    /// Saves the current zoom level and scroll position for the next time that the file is opened
+    fn remember_view(&mut self) {
+        if !self.transient {
+            // A zoom that was asked for explicitly isn't the one to go back to
+            let zoom = self.opts.zoom.is_none().then_some(self.renderer.zoom);
+            self.recent_files
+                .record_view(&self.opts.file_path, zoom, self.renderer.scroll_y);
+        }
+    }
+
+    /// Saves the window's size and position for the next time that inlyne is started
+    fn remember_window(&mut self) {
+        // Coming back fullscreen, including from zen mode, would be more surprising than helpful
+        if self.window.fullscreen().is_some() {
+            return;
+        }
+        let maximized = self.window.is_maximized();
+        // Keep the size from before being maximized for when it gets restored
+        let geometry = match self.recent_files.window() {
+            Some(geometry) if maximized => WindowGeometry {
+                maximized,
+                ..geometry
+            },
+            _ => {
+                let size = self.window.inner_size();
+                WindowGeometry {
+                    position: self.window.outer_position().ok().map(|pos| (pos.x, pos.y)),
+                    size: (size.width, size.height),
+                    maximized,
+                }
+            }
+        };
+        self.recent_files.record_window(geometry);
+    }
+
+    /// Scrolls to where the one-indexed `line` of the source shows up, or waits until the document
+    /// has been positioned to do so
+    fn scroll_to_line(&mut self, line: usize) {
+        let source_lines = &self.renderer.positioner.source_lines;
+        let Some(scroll_y) = ipc::line_y(source_lines, line) else {
+            self.restore_line = Some(line);
+            return;
+        };
+        self.momentum.stop();
+        self.smooth_scroll.stop();
+        self.renderer.set_scroll_y(scroll_y);
+        self.window.request_redraw();
+    }
+
+    /// Carries out a request that came in over the socket, see [`ipc`]
+    fn handle_request(&mut self, request: ipc::Request, control_flow: &mut ControlFlow) {
+        match request {
+            ipc::Request::Open { path, line } => {
+                self.open_file(path);
+                if line.is_some() {
+                    self.restore_line = line;
+                    self.interpreter_progressive.store(false, Ordering::Relaxed);
+                }
+                self.window.focus_window();
+            }
+            ipc::Request::Reload => self.reload(),
+            ipc::Request::ScrollToLine { line } => self.scroll_to_line(line),
+            ipc::Request::ScrollToPercent { percent } => {
+                self.momentum.stop();
+                self.smooth_scroll.stop();
+                let scroll_y = self.renderer.max_scroll_y() * percent.clamp(0., 100.) / 100.;
+                self.renderer.set_scroll_y(scroll_y);
+                self.window.request_redraw();
+            }
+            ipc::Request::SetTheme { theme } => {
+                if self.opts.color_scheme.unwrap_or_default() != theme {
+                    self.toggle_theme();
+                }
+            }
+            ipc::Request::Quit => *control_flow = ControlFlow::Exit,
+        }
+    }
+
+    fn open_file(&mut self, path: PathBuf) {
+        let current = self.history_entry();
+        if self.load_path(path, 0.) {
+            if let Some(current) = current {
+                self.history.visit(current);
+            }
+        }
+    }
+
+    /// Goes back or forward to the document that was open before or after the current one
+    fn step_history(&mut self, direction: history::Direction) {
+        // The clipboard can't be gone back to, so it acts like a file that was never opened
+        let current = self.history_entry().unwrap_or_else(|| history::Entry {
+            path: self.opts.file_path.clone(),
+            scroll_y: 0.,
+        });
+        if let Some(entry) = self.history.step(direction, current) {
+            self.load_path(entry.path, entry.scroll_y);
+        }
+    }
+
+    /// Reads the current file from disk again, for when a change didn't get picked up
+    fn reload(&mut self) {
+        if self.transient {
+            return;
+        }
+        match read_to_string(&self.opts.file_path) {
+            Ok(contents) => self.load_file(contents),
+            Err(err) => tracing::warn!(
+                "Failed reloading markdown file at {}\nError: {}",
+                self.opts.file_path.display(),
+                err,
+            ),
+        }
+    }
+
+    /// Where the current document is, or `None` when it's the clipboard's contents
+    fn history_entry(&self) -> Option<history::Entry> {
+        (!self.transient).then(|| history::Entry {
+            path: self.opts.file_path.clone(),
+            scroll_y: self.renderer.scroll_y,
+        })
+    }
+
+    /// Switches over to the file at `path`, scrolled down to `scroll_y` once it's loaded
+    ///
+    /// Returns whether the file could be read
+    fn load_path(&mut self, path: PathBuf, scroll_y: f32) -> bool {
+        match read_to_string(&path) {
+            Ok(contents) => {
+                self.remember_view();
+                self.renderer.zoom = self
+                    .opts
+                    .zoom
+                    .or_else(|| self.recent_files.zoom(&path))
+                    .unwrap_or(1.)
+                    .clamp(MIN_ZOOM, MAX_ZOOM);
+                self.opts.file_path = path;
+                self.transient = false;
+                self.from_stdin = false;
+                // Landing in the middle of a document needs all of it to be there
+                self.interpreter_progressive
+                    .store(scroll_y == 0., Ordering::Relaxed);
+                self.watcher.update_file(&self.opts.file_path, contents);
+                self.recent_files.record(&self.opts.file_path);
+                self.renderer.lightbox = None;
+                self.smooth_scroll.stop();
+                self.renderer.set_scroll_y(0.);
+                self.restore_scroll_y = Some(scroll_y);
+                true
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed loading markdown file at {}\nError: {}",
+                    path.display(),
+                    err,
+                );
+                false
+            }
+        }
+    }
+
+    /// Shows `contents` as a transient document that isn't backed by any file
+    ///
+    /// The current file's path is kept around, so relative links still resolve from there
+    fn preview_clipboard(&mut self, contents: String) {
+        self.remember_view();
+        self.transient = true;
+        self.from_stdin = false;
+        self.renderer.lightbox = None;
+        self.watcher.unwatch();
+        self.interpreter_progressive.store(true, Ordering::Relaxed);
+        self.renderer.set_scroll_y(0.);
+        self.load_file(contents);
+    }
+
+    /// Opens a file in a new window, akin to what a browser does
+    fn spawn_new_window(path: &Path) {
+        Command::new(std::env::current_exe().unwrap_or_else(|_| "inlyne".into()))
+            .args(Opts::program_args(path))
+            .spawn()
+            .expect("Could not spawn new inlyne instance");
+    }
+
+    fn toggle_overlay(&mut self, overlay: Overlay) {
+        let is_same_kind =
+            |current: &Overlay| std::mem::discriminant(current) == std::mem::discriminant(&overlay);
+        self.overlay = match &self.overlay {
+            Some(current) if is_same_kind(current) => None,
+            _ => Some(overlay),
+        };
+        self.window.request_redraw();
+    }
+
+    /// Lets overlays that can be picked from take keys before they're used as keybindings
+    ///
+    /// Returns whether the key was handled
+    fn handle_overlay_key(&mut self, key: VirtualKeyCode, modifiers: ModifiersState) -> bool {
+        match &mut self.overlay {
+            Some(Overlay::RecentFiles { selected }) => {
+                let selected = *selected;
+                self.handle_recent_files_key(key, selected)
+            }
+            Some(Overlay::QuickOpen(quick_open)) => {
+                match key {
+                    VirtualKeyCode::Up => quick_open.select_prev(),
+                    VirtualKeyCode::Down => quick_open.select_next(),
+                    VirtualKeyCode::Back => quick_open.pop_char(),
+                    VirtualKeyCode::Return => {
+                        let maybe_path = quick_open.selected_path();
+                        self.overlay = None;
+                        if let Some(path) = maybe_path {
+                            if modifiers.shift() {
+                                Self::spawn_new_window(&path);
+                            } else {
+                                self.open_file(path);
+                            }
+                        }
+                    }
+                    VirtualKeyCode::Escape => self.overlay = None,
+                    // Let shortcuts through, but keep everything else from triggering keybindings
+                    // while typing
+                    _ if modifiers.ctrl() || modifiers.logo() || modifiers.alt() => return false,
+                    _ => return true,
+                }
+
+                self.window.request_redraw();
+                true
+            }
+            Some(Overlay::Search(search)) if search.confirmed => {
+                if key != VirtualKeyCode::Escape {
+                    return false;
+                }
+                self.overlay = None;
+                self.window.request_redraw();
+                true
+            }
+            Some(Overlay::Search(search)) => {
+                match key {
+                    VirtualKeyCode::Return if self.opts.vim_mode => search.confirmed = true,
+                    VirtualKeyCode::Return if modifiers.shift() => search.select_prev(),
+                    VirtualKeyCode::Return | VirtualKeyCode::Down => search.select_next(),
+                    VirtualKeyCode::Up => search.select_prev(),
+                    VirtualKeyCode::Back => {
+                        search.pop_char();
+                        self.refresh_search(true);
+                        return true;
+                    }
+                    VirtualKeyCode::Escape => {
+                        self.overlay = None;
+                        self.window.request_redraw();
+                        return true;
+                    }
+                    // Let shortcuts through, but keep everything else from triggering keybindings
+                    // while typing
+                    _ if modifiers.ctrl() || modifiers.logo() || modifiers.alt() => return false,
+                    _ => return true,
+                }
+
+                self.scroll_to_match();
+                true
+            }
+            Some(Overlay::ImageMenu(menu)) => {
+                match key {
+                    VirtualKeyCode::Up => menu.select_prev(),
+                    VirtualKeyCode::Down => menu.select_next(),
+                    VirtualKeyCode::Return => {
+                        menu.run(menu.selected());
+                        self.overlay = None;
+                    }
+                    VirtualKeyCode::Escape => self.overlay = None,
+                    _ => return false,
+                }
+
+                self.window.request_redraw();
+                true
+            }
+            Some(Overlay::Diagnostics) | None => false,
+        }
+    }
+
+    fn handle_recent_files_key(&mut self, key: VirtualKeyCode, selected: usize) -> bool {
+        let last = self.recent_files.files().len().saturating_sub(1);
+        match key {
+            VirtualKeyCode::Up => {
+                self.overlay = Some(Overlay::RecentFiles {
+                    selected: selected.saturating_sub(1),
+                });
+            }
+            VirtualKeyCode::Down => {
+                self.overlay = Some(Overlay::RecentFiles {
+                    selected: (selected + 1).min(last),
+                });
+            }
+            VirtualKeyCode::Return => {
+                self.overlay = None;
+                if let Some(path) = self.recent_files.files().get(selected).cloned() {
+                    self.open_file(path);
+                }
+            }
+            VirtualKeyCode::Escape => self.overlay = None,
+            _ => return false,
+        }
+
+        self.window.request_redraw();
+        true
+    }
+
+    /// Flips the task list checkbox at `loc` in the file, which then gets reloaded with the change.
+    /// Looks up to `radius` pixels around `loc`
+    ///
+    /// Returns if there was a checkbox to toggle
+    fn toggle_checkbox(&mut self, loc: Point, radius: f32) -> bool {
+        if !self.opts.toggle_checkboxes || self.transient {
+            return false;
+        }
+        let Some(line) = touch::probe_points(loc, radius)
+            .find_map(|point| self.renderer.checkbox_at(&self.elements, point))
+        else {
+            return false;
+        };
+        let Some(task) = utils::source_line(&self.source, line) else {
+            return false;
+        };
+        if utils::toggle_task(&self.source, line).is_none() {
+            tracing::debug!("Line {line} doesn't hold a task to toggle");
+            return false;
+        }
+
+        // The file may have been edited since it was last loaded, so only write over it when the
+        // task is still where it was rendered from
+        let on_disk = match std::fs::read_to_string(&self.opts.file_path) {
+            Ok(on_disk) => on_disk,
+            Err(err) => {
+                tracing::warn!(
+                    "Failed reading {} to toggle a task\nError: {}",
+                    self.opts.file_path.display(),
+                    err
+                );
+                return true;
+            }
+        };
+        if utils::source_line(&on_disk, line) != Some(task) {
+            tracing::warn!(
+                "Not toggling the task on line {line} since {} changed since it was loaded",
+                self.opts.file_path.display()
+            );
+            return true;
+        }
+        let Some(toggled) = utils::toggle_task(&on_disk, line) else {
+            return true;
+        };
+        if let Err(err) = std::fs::write(&self.opts.file_path, toggled) {
+            tracing::warn!(
+                "Failed toggling task in {}\nError: {}",
+                self.opts.file_path.display(),
+                err
+            );
+        }
+        true
+    }
+
+    /// Opens the image menu for whatever image is at `loc`, looking up to `radius` pixels around it
+    ///
+    /// Returns if there was an image to open it for
+    fn open_image_menu(&mut self, loc: Point, radius: f32) -> bool {
+        let screen_size = self.renderer.doc_size();
+        let menu = touch::probe_points(loc, radius).find_map(|point| {
+            match Self::find_hoverable(
+                &mut self.renderer.text_system,
+                &mut self.renderer.positioner.taffy,
+                &self.elements,
+                point,
+                screen_size,
+                self.renderer.page_width,
+                self.renderer.zoom,
+            )? {
+                Hoverable::Image(image, _) if image.is_loaded() => Some(ImageMenu::new(image)),
+                _ => None,
+            }
+        });
+        let Some(menu) = menu else {
+            return false;
+        };
+
+        self.overlay = Some(Overlay::ImageMenu(menu));
+        self.renderer.selection = None;
+        self.window.request_redraw();
+        true
+    }
+
+    /// Runs the image menu's line at `pos` on the screen, closing the menu either way
+    fn click_image_menu(&mut self, pos: Point) {
+        let Some(Overlay::ImageMenu(menu)) = self.overlay.take() else {
+            return;
+        };
+        let line = self
+            .renderer
+            .panel_lines
+            .iter()
+            .position(|line| line.contains(pos));
+        if let Some(&action) = line.and_then(|line| ImageAction::ALL.get(line)) {
+            menu.run(action);
+        }
+        self.window.request_redraw();
+    }
+
+    /// Clicks on whatever is at `loc`, looking up to `radius` pixels around it
+    ///
+    /// Returns if a link was followed, a checkbox was toggled, or an image was opened in the
+    /// lightbox
+    fn click(&mut self, loc: Point, radius: f32, open_in_new_window: bool) -> bool {
+        if self.toggle_checkbox(loc, radius) {
+            return true;
+        }
+
+        let screen_size = self.renderer.doc_size();
+        let shown = slideshow::shown(self.renderer.slideshow.as_ref(), self.elements.len());
+        let Some(hoverable) = touch::probe_points(loc, radius).find_map(|point| {
+            Self::find_hoverable(
+                &mut self.renderer.text_system,
+                &mut self.renderer.positioner.taffy,
+                &self.elements[shown.clone()],
+                point,
+                screen_size,
+                self.renderer.page_width,
+                self.renderer.zoom,
+            )
+        }) else {
+            return false;
+        };
+
+        if let Hoverable::Summary(summary) = hoverable {
+            let mut hidden = summary.hidden.borrow_mut();
+            *hidden = !*hidden;
+            self.need_repositioning = true;
+        }
+
+        if let Hoverable::Image(image, _) = hoverable {
+            if image.is_link.is_none() && image.is_loaded() {
+                self.renderer.lightbox = Some(Lightbox::new(image));
+                self.renderer.hovered_link = None;
+                self.renderer.loupe = None;
+                self.window.set_cursor_icon(CursorIcon::Default);
+                self.window.request_redraw();
+                return true;
+            }
+        }
+
+        let Some(link) = hoverable.link().map(str::to_owned) else {
+            return false;
+        };
+
+        let maybe_path = PathBuf::from_str(&link).ok();
+        let is_local_md = maybe_path.as_ref().map_or(false, |p| {
+            p.extension().map_or(false, |ext| ext == "md")
+                && !p.to_str().map_or(false, |s| s.starts_with("http"))
+        });
+        if is_local_md {
+            // Open markdown files ourselves
+            let path = maybe_path.expect("not a path");
+            // Handle relative paths and make them
+            // absolute by prepending current
+            // parent
+            let path = if path.is_relative() {
+                // Simply canonicalizing it doesn't suffice and leads to "no such file or directory"
+                let current_parent = self.opts.file_path.parent().expect("no current parent");
+                let mut normalized_link = path.as_path();
+                if let Ok(stripped) = normalized_link.strip_prefix(std::path::Component::CurDir) {
+                    normalized_link = stripped;
+                }
+                let mut link = current_parent.to_path_buf();
+                link.push(normalized_link);
+                link
+            } else {
+                path
+            };
+            // Open them in a new window, akin to what a browser does
+            if open_in_new_window {
+                Self::spawn_new_window(&path);
+            } else {
+                self.open_file(path);
+            }
+        } else if let Some(anchor_pos) = self.renderer.positioner.anchors.get(&link) {
+            self.glide_to(*anchor_pos);
+            self.window.set_cursor_icon(CursorIcon::Default);
+        } else if let Err(err) = open::that(&link) {
+            tracing::warn!("Failed opening link `{link}` with the system's handler\nError: {err}");
+        }
+
+        true
+    }
+
+    fn handle_gesture(&mut self, gesture: Gesture) {
+        let now = Instant::now();
+        let to_doc = |(x, y): Point, scroll_y: f32| (x, y + scroll_y);
+        match gesture {
+            Gesture::Scroll(num_pixels) => {
+                self.momentum.track(num_pixels, now);
+                self.scroll_pixels(num_pixels);
+            }
+            Gesture::Fling => {
+                if self.scroll.momentum && self.momentum.release(now) {
+                    self.window.request_redraw();
+                } else {
+                    self.momentum.stop();
+                }
+            }
+            Gesture::Tap(point) if matches!(self.overlay, Some(Overlay::ImageMenu(_))) => {
+                self.click_image_menu(point);
+            }
+            Gesture::Tap(point) => {
+                self.renderer.selection = None;
+                let loc = to_doc(point, self.renderer.scroll_y);
+                let radius = TOUCH_TARGET_RADIUS * self.renderer.hidpi_scale;
+                self.click(loc, radius, false);
+                self.window.request_redraw();
+            }
+            Gesture::LongPress(point) => {
+                let loc = to_doc(point, self.renderer.scroll_y);
+                let radius = TOUCH_TARGET_RADIUS * self.renderer.hidpi_scale;
+                if !self.open_image_menu(loc, radius) {
+                    // Selecting is the next best thing away from images
+                    self.renderer.selection = Some((loc, loc));
+                    self.window.request_redraw();
+                }
+            }
+            Gesture::Select(point) => {
+                let loc = to_doc(point, self.renderer.scroll_y);
+                if let Some(selection) = &mut self.renderer.selection {
+                    selection.1 = loc;
+                    self.window.request_redraw();
+                }
+            }
+            Gesture::Pinch(PinchUpdate { scale, focal_point }) => {
+                let zoom = self.renderer.zoom * scale;
+                self.set_zoom(zoom, focal_point.1);
+            }
+        }
+    }
+
+    /// Zooms while keeping whatever is `focal_y` pixels from the top of the screen in place
+    fn set_zoom(&mut self, zoom: f32, focal_y: f32) {
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        if zoom == self.renderer.zoom {
+            return;
+        }
+
+        self.renderer.zoom = zoom;
+        self.smooth_scroll.stop();
+        let old_reserved = self.renderer.positioner.reserved_height;
+        self.renderer.reposition(&mut self.elements).unwrap();
+        let new_reserved = self.renderer.positioner.reserved_height;
+        let focal_doc_y = self.renderer.scroll_y + focal_y;
+        self.renderer
+            .set_scroll_y(focal_doc_y * (new_reserved / old_reserved) - focal_y);
+        self.refresh_search(false);
+        self.window.request_redraw();
+    }
+
+    /// Lists the document's headings again when the table of contents is shown
+    fn refresh_toc(&mut self) {
+        if self.renderer.toc.is_some() {
+            self.renderer.toc = Some(Toc::new(&self.elements));
+        }
+        if self.renderer.sticky_headings.is_some() {
+            self.renderer.sticky_headings = Some(Toc::new(&self.elements));
+        }
+    }
+
+    /// Scrolls the heading at `index` in the table of contents to the top of the screen
+    fn jump_to_heading(&mut self, index: usize) {
+        let Some(heading) = self
+            .renderer
+            .toc
+            .as_ref()
+            .and_then(|toc| toc.headings.get(index))
+        else {
+            return;
+        };
+        if let Some(&y) = self.renderer.positioner.anchors.get(&heading.anchor) {
+            self.glide_to(y);
+        }
+    }
+
+    /// Finds the matches for the search query again, jumping to the first one from the top of the
+    /// screen when the query is `new`, or staying on the current one otherwise
+    fn refresh_search(&mut self, new: bool) {
+        let Some(Overlay::Search(search)) = &self.overlay else {
+            return;
+        };
+        let matches = self.renderer.find_matches(&self.elements, &search.query);
+        let Some(Overlay::Search(search)) = &mut self.overlay else {
+            return;
+        };
+        if new {
+            search.set_matches(matches, self.renderer.scroll_y);
+            self.scroll_to_match();
+        } else {
+            search.relocate(matches);
+        }
+        self.window.request_redraw();
+    }
+
+    /// Scrolls the current search match into view unless it's already comfortably on screen
+    fn scroll_to_match(&mut self) {
+        let Some(Overlay::Search(search)) = &self.overlay else {
+            return;
+        };
+        let Some(rect) = search.current().and_then(<[Rect]>::first) else {
+            return;
+        };
+        let screen_height = self.renderer.screen_height();
+        let scroll_y = self.renderer.scroll_y;
+        if rect.pos.1 < scroll_y || rect.max().1 > scroll_y + screen_height * 0.75 {
+            self.glide_to(rect.pos.1 - screen_height / 3.);
+        }
+        self.window.request_redraw();
     }
 
-    fn scroll_pixels(renderer: &mut Renderer, window: &Window, num_pixels: f32) {
-        renderer.set_scroll_y(renderer.scroll_y - num_pixels);
-        window.request_redraw();
+    fn scroll_lines(&mut self, num_lines: f32) {
+        let num_pixels = num_lines
+            * 16.0
+            * self.lines_to_scroll
+            * self.renderer.hidpi_scale
+            * self.renderer.zoom;
+        self.glide_by(num_pixels);
+    }
+
+    /// Scrolls right away for input that already moves smoothly, like touchpads
+    fn scroll_pixels(&mut self, num_pixels: f32) {
+        self.smooth_scroll.stop();
+        self.renderer
+            .set_scroll_y(self.renderer.scroll_y - num_pixels);
+        self.window.request_redraw();
+    }
+
+    /// Like [`Self::glide_to`], but relative to wherever the document is headed already
+    fn glide_by(&mut self, num_pixels: f32) {
+        let from = self
+            .smooth_scroll
+            .target()
+            .unwrap_or(self.renderer.scroll_y);
+        self.glide_to(from - num_pixels);
+    }
+
+    /// Scrolls to `scroll_y`, easing the way there unless smooth scrolling is turned off
+    fn glide_to(&mut self, scroll_y: f32) {
+        let scroll_y = scroll_y.clamp(0., self.renderer.max_scroll_y());
+        if self.scroll.smooth {
+            self.momentum.stop();
+            self.smooth_scroll.scroll_to(scroll_y, Instant::now());
+        } else {
+            self.renderer.set_scroll_y(scroll_y);
+        }
+        self.window.request_redraw();
+    }
+
+    /// Selects the word at `loc`, or the whole paragraph around it with `paragraph`
+    fn select_text(&mut self, loc: Point, paragraph: bool) -> Option<Selection> {
+        let (text_box, bounds) = Self::find_text_box(&self.elements, loc)?;
+        let (pos, text_bounds) = self.renderer.text_box_bounds(text_box, bounds.pos);
+        let zoom = self.renderer.zoom;
+        let text_system = &mut self.renderer.text_system;
+        if paragraph {
+            Some(text_box.paragraph_selection(text_system, pos, text_bounds, zoom))
+        } else {
+            text_box.word_selection(text_system, loc, pos, text_bounds, zoom)
+        }
+    }
+
+    fn find_text_box(elements: &[Positioned<Element>], loc: Point) -> Option<(&TextBox, &Rect)> {
+        let element = elements
+            .iter()
+            .find(|&e| e.contains(loc) && !matches!(e.inner, Element::Spacer(_)))?;
+        match &element.inner {
+            Element::TextBox(text_box) => Some((text_box, element.bounds.as_ref()?)),
+            Element::Row(row) => Self::find_text_box(&row.elements, loc),
+            Element::Section(section) if !*section.hidden.borrow() => {
+                Self::find_text_box(&section.elements, loc)
+            }
+            _ => None,
+        }
+    }
+
+    fn find_text_box_mut(
+        elements: &mut [Positioned<Element>],
+        loc: Point,
+    ) -> Option<(&mut TextBox, Rect)> {
+        let element = elements
+            .iter_mut()
+            .find(|e| e.contains(loc) && !matches!(e.inner, Element::Spacer(_)))?;
+        let bounds = element.bounds.clone()?;
+        match &mut element.inner {
+            Element::TextBox(text_box) => Some((text_box, bounds)),
+            Element::Row(row) => Self::find_text_box_mut(&mut row.elements, loc),
+            Element::Section(section) if !*section.hidden.borrow() => {
+                Self::find_text_box_mut(&mut section.elements, loc)
+            }
+            _ => None,
+        }
+    }
+
+    /// Scrolls the code block under `loc` sideways if it doesn't wrap
+    ///
+    /// Returns if there was one to scroll
+    fn scroll_code_block(&mut self, loc: Point, num_pixels: f32) -> bool {
+        let Some((text_box, bounds)) = Self::find_text_box_mut(&mut self.elements, loc) else {
+            return false;
+        };
+        if !text_box.no_wrap {
+            return false;
+        }
+        let (_, text_bounds) = self.renderer.text_box_bounds(text_box, bounds.pos);
+        if text_box.scroll_x_by(
+            &mut self.renderer.text_system,
+            text_bounds,
+            self.renderer.zoom,
+            num_pixels,
+        ) {
+            self.window.request_redraw();
+        }
+        // Even at the end of the line, so the document doesn't start scrolling instead
+        true
     }
 
     fn find_hoverable<'a>(
@@ -685,11 +2465,15 @@ impl Inlyne {
         elements: &'a [Positioned<Element>],
         loc: Point,
         screen_size: Size,
+        page_width: f32,
         zoom: f32,
     ) -> Option<Hoverable<'a>> {
         let screen_pos = |screen_size: Size, bounds_offset: f32| {
             (
-                screen_size.0 - bounds_offset - DEFAULT_MARGIN,
+                screen_size.0
+                    - bounds_offset
+                    - DEFAULT_MARGIN
+                    - centering(screen_size.0, page_width),
                 screen_size.1,
             )
         };
@@ -705,7 +2489,7 @@ impl Inlyne {
                             text_system,
                             loc,
                             bounds.pos,
-                            screen_pos(screen_size, bounds.pos.0),
+                            screen_pos(screen_size, bounds.pos.0 + text_box.float_inset),
                             zoom,
                         )
                         .map(Hoverable::Text)
@@ -723,11 +2507,19 @@ impl Inlyne {
                         )
                         .map(Hoverable::Text)
                 }
-                Element::Image(image) => Some(Hoverable::Image(image)),
-                Element::Spacer(_) => unreachable!("Spacers are filtered"),
-                Element::Row(row) => {
-                    Self::find_hoverable(text_system, taffy, &row.elements, loc, screen_size, zoom)
+                Element::Image(image) => {
+                    Some(Hoverable::Image(image, element.bounds.as_ref().unwrap()))
                 }
+                Element::Spacer(_) => unreachable!("Spacers are filtered"),
+                Element::Row(row) => Self::find_hoverable(
+                    text_system,
+                    taffy,
+                    &row.elements,
+                    loc,
+                    screen_size,
+                    page_width,
+                    zoom,
+                ),
                 Element::Section(section) => {
                     if let Some(ref summary) = *section.summary {
                         if let Some(ref bounds) = summary.bounds {
@@ -743,6 +2535,7 @@ impl Inlyne {
                             &section.elements,
                             loc,
                             screen_size,
+                            page_width,
                             zoom,
                         )
                     } else {
@@ -753,6 +2546,16 @@ impl Inlyne {
     }
 }
 
+fn load_config(config_path: Option<&Path>) -> anyhow::Result<Config> {
+    match config_path {
+        Some(config_path) => Config::load_from_file(config_path),
+        None => Ok(Config::load_from_system().unwrap_or_else(|err| {
+            tracing::warn!("Failed reading config file. Falling back to defaults. Error: {err:#}");
+            Config::default()
+        })),
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     human_panic::setup_panic!();
 
@@ -762,24 +2565,55 @@ fn main() -> anyhow::Result<()> {
         .from_env()?;
     tracing_subscriber::registry()
         .with(env_filter)
-        .with(tracing_subscriber::fmt::layer().compact())
+        // Keep stdout clean for subcommands that output results
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .compact(),
+        )
         .init();
 
-    let args = Args::new();
-    let config = match &args.config {
-        Some(config_path) => Config::load_from_file(config_path)?,
-        None => Config::load_from_system().unwrap_or_else(|err| {
-            tracing::warn!(
-                "Failed reading config file. Falling back to defaults. Error: {}",
-                err
-            );
-            Config::default()
-        }),
-    };
-    let opts = Opts::parse_and_load_from(args, config)?;
+    match Cli::new() {
+        Cli::View(mut args) => {
+            let forwardable = !args.new_window && !args.clipboard;
+            if let Some(file_path) = args.file_path.as_deref().filter(|_| forwardable) {
+                if file_path != Path::new("-") && instance::forward(file_path, args.line) {
+                    return Ok(());
+                }
+            }
+            let recent_files = RecentFiles::load();
+            if args.file_path.is_none() && !args.clipboard {
+                args.file_path = recent_files.most_recent().map(Path::to_owned);
+            }
+            let config = load_config(args.config.as_deref())?;
+            let config_path = args.config.clone().or_else(|| Config::system_path().ok());
+            let opts = Opts::parse_and_load_from(args.clone(), config)?;
 
-    let inlyne = Inlyne::new(opts)?;
-    inlyne.run();
+            let mut inlyne = Inlyne::new(opts, recent_files)?;
+            if !args.new_window {
+                instance::listen(inlyne.event_proxy.clone());
+            }
+            if let Some(config_path) = config_path {
+                inlyne.watch_config(args, config_path);
+            }
+            inlyne.run();
+        }
+        Cli::Serve(args) => {
+            let config = load_config(args.config.as_deref())?;
+            let opts = ServeOpts::parse_and_load_from(args, config)?;
+            serve::run(opts)?;
+        }
+        Cli::Bench(args) => {
+            let config = load_config(args.config.as_deref())?;
+            let opts = BenchOpts::parse_and_load_from(args, config)?;
+            bench::run(opts)?;
+        }
+        Cli::Export(args) => {
+            let config = load_config(args.config.as_deref())?;
+            let opts = ExportOpts::parse_and_load_from(args, config)?;
+            export::run(opts)?;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,757 @@
+//! Typesetting LaTeX math like `$e^{i\pi} + 1 = 0$` with Unicode symbols
+//!
+//! Inline math goes between single dollar signs and display math between double dollar signs
+//! (or in a ```` ```math ```` block like GitHub supports). Only the parts of LaTeX that come out
+//! readable as a line of text are supported: symbols, scripts, fractions, roots, accents, and
+//! matrices laid out row by row. Anything else is shown as written
+//!
+//! Comrak doesn't know about math, so it gets turned into code spans and blocks that are marked
+//! as math before parsing. That keeps emphasis and escapes in the markdown from mangling it
+
+use std::borrow::Cow;
+
+use crate::interpreter::html::{subscript, superscript};
+use crate::utils::{closes_fence, opens_fence, replace_with_html};
+
+use comrak::nodes::{AstNode, NodeValue};
+
+/// Starts code spans that hold inline math. Private use characters can't show up in real code
+const INLINE_MARKER: char = '\u{E000}';
+/// Starts code spans that hold display math written on a single line
+const DISPLAY_MARKER: char = '\u{E001}';
+
+/// Swaps out any math in `md` for marked code spans and ```` ```math ```` blocks
+///
+/// Line numbers are kept the same, so source positions still line up with the original
+pub fn mark_math(md: &str) -> Cow<'_, str> {
+    if !md.contains('$') {
+        return Cow::Borrowed(md);
+    }
+
+    let lines: Vec<_> = md.split_inclusive('\n').collect();
+    let mut marked = String::with_capacity(md.len());
+    let mut code_fence = None;
+    let mut math_block_end = None;
+    for (i, line) in lines.iter().enumerate() {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[content.len()..];
+        let indent = &content[..content.len() - content.trim_start().len()];
+
+        if let Some((fence_char, fence_len)) = code_fence {
+            if closes_fence(content.trim(), fence_char, fence_len) {
+                code_fence = None;
+            }
+            marked.push_str(line);
+        } else if let Some(end) = math_block_end {
+            if i == end {
+                math_block_end = None;
+                marked.push_str(indent);
+                marked.push_str("```");
+                marked.push_str(ending);
+            } else {
+                marked.push_str(line);
+            }
+        } else if let Some(fence) = opens_fence(content.trim_start()) {
+            code_fence = Some(fence);
+            marked.push_str(line);
+        } else if let Some(end) = math_block_end_from(&lines, i) {
+            math_block_end = Some(end);
+            marked.push_str(indent);
+            marked.push_str("```math");
+            marked.push_str(ending);
+        } else {
+            mark_inline_math(content, &mut marked);
+            marked.push_str(ending);
+        }
+    }
+
+    Cow::Owned(marked)
+}
+
+/// Where the display math block starting at `lines[start]` ends, if it does start there
+fn math_block_end_from(lines: &[&str], start: usize) -> Option<usize> {
+    let is_delimiter = |line: &&str| line.trim() == "$$";
+    if !is_delimiter(&lines[start]) {
+        return None;
+    }
+    let len = lines[start + 1..].iter().position(is_delimiter)?;
+    Some(start + 1 + len)
+}
+
+fn mark_inline_math(line: &str, marked: &mut String) {
+    let bytes = line.as_bytes();
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'`' => {
+                // Code spans are left alone
+                let run = count_run(&bytes[i..], b'`');
+                let closing = (i + run..bytes.len())
+                    .find(|&j| count_run(&bytes[j..], b'`') == run && bytes[j - 1] != b'`');
+                i = closing.map_or(i + run, |j| j + run);
+            }
+            b'$' => match find_math(line, i) {
+                Some((math, is_display, end)) => {
+                    marked.push_str(&line[copied..i]);
+                    let marker = if is_display {
+                        DISPLAY_MARKER
+                    } else {
+                        INLINE_MARKER
+                    };
+                    push_code_span(marked, marker, math);
+                    copied = end;
+                    i = end;
+                }
+                None => i += count_run(&bytes[i..], b'$'),
+            },
+            _ => i += 1,
+        }
+    }
+    marked.push_str(&line[copied..]);
+}
+
+fn count_run(bytes: &[u8], byte: u8) -> usize {
+    bytes.iter().take_while(|&&b| b == byte).count()
+}
+
+/// Finds the math that starts at the `$` at `start`, giving it along with whether it's display
+/// math and where it ends
+///
+/// Like pandoc, inline math can't start with a space or end with one, and the closing `$` can't
+/// be followed by a digit. That keeps prices like $5 and $10 from turning into math
+fn find_math(line: &str, start: usize) -> Option<(&str, bool, usize)> {
+    let bytes = line.as_bytes();
+    let is_escaped = |i: usize| i > 0 && bytes[i - 1] == b'\\';
+    if line[start..].starts_with("$$") {
+        let from = start + 2;
+        let close = (from..bytes.len().saturating_sub(1))
+            .find(|&i| bytes[i] == b'$' && bytes[i + 1] == b'$' && !is_escaped(i))?;
+        let math = line[from..close].trim();
+        return (!math.is_empty()).then_some((math, true, close + 2));
+    }
+
+    let first = line[start + 1..].chars().next()?;
+    if first.is_whitespace() || first == '$' {
+        return None;
+    }
+    let close = (start + 2..bytes.len()).find(|&i| {
+        bytes[i] == b'$'
+            && !is_escaped(i)
+            && !bytes[i - 1].is_ascii_whitespace()
+            && !bytes.get(i + 1).map_or(false, u8::is_ascii_digit)
+    })?;
+    Some((&line[start + 1..close], false, close + 1))
+}
+
+fn push_code_span(marked: &mut String, marker: char, math: &str) {
+    let longest_run = math
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run + 1);
+    marked.push_str(&fence);
+    marked.push(marker);
+    marked.push_str(math);
+    if math.ends_with('`') {
+        marked.push(' ');
+    }
+    marked.push_str(&fence);
+}
+
+/// Typesets the math that was marked by [`mark_math()`] in place
+pub fn render<'a>(node: &'a AstNode<'a>) {
+    let mut ast = node.data.borrow_mut();
+    match &ast.value {
+        NodeValue::Code(code) => {
+            let literal = code.literal.trim_end();
+            if let Some(tex) = literal
+                .strip_prefix(INLINE_MARKER)
+                .or_else(|| literal.strip_prefix(DISPLAY_MARKER))
+            {
+                ast.value = NodeValue::HtmlInline(to_html(tex));
+            }
+        }
+        NodeValue::CodeBlock(block) if block.fenced && block.info.trim() == "math" => {
+            let html = display_html(&block.literal);
+            drop(ast);
+            replace_with_html(node, html);
+        }
+        NodeValue::Paragraph => {
+            let children: Vec<_> = node.children().collect();
+            let [child] = children.as_slice() else {
+                return;
+            };
+            let tex = match &child.data.borrow().value {
+                NodeValue::Code(code) => match code.literal.strip_prefix(DISPLAY_MARKER) {
+                    Some(tex) => tex.to_owned(),
+                    None => return,
+                },
+                _ => return,
+            };
+            let html = display_html(&tex);
+            drop(ast);
+            replace_with_html(node, html);
+        }
+        _ => {}
+    }
+}
+
+/// Typesets `tex` as inline HTML
+pub fn to_html(tex: &str) -> String {
+    format!("<em>{}</em>", typeset(tex))
+}
+
+fn typeset(tex: &str) -> String {
+    let mut parser = Parser { tex, pos: 0 };
+    let mut html = String::new();
+    // An unmatched `}` would stop the group short, so it's skipped over
+    loop {
+        html.push_str(&parser.group());
+        if parser.bump().is_none() {
+            break;
+        }
+    }
+    html.trim().to_owned()
+}
+
+/// Typesets `tex` as its own centered paragraph
+pub fn display_html(tex: &str) -> String {
+    format!("<p align=\"center\">{}</p>\n", to_html(tex.trim()))
+}
+
+struct Parser<'tex> {
+    tex: &'tex str,
+    pos: usize,
+}
+
+impl<'tex> Parser<'tex> {
+    fn peek(&self) -> Option<char> {
+        self.tex[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    /// Typesets everything up to the end of the current group
+    fn group(&mut self) -> String {
+        let mut html = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '}' => break,
+                c if c.is_whitespace() => {
+                    self.skip_whitespace();
+                    if !html.is_empty() && !html.ends_with(' ') {
+                        html.push(' ');
+                    }
+                }
+                '^' | '_' => {
+                    self.bump();
+                    let body = self.argument();
+                    html.push_str(&script(&body, c == '^'));
+                }
+                _ => html.push_str(&self.atom()),
+            }
+        }
+        html
+    }
+
+    /// A single symbol, command, or braced group
+    fn atom(&mut self) -> String {
+        let Some(c) = self.bump() else {
+            return String::new();
+        };
+        match c {
+            '{' => {
+                let html = self.group();
+                self.bump();
+                html
+            }
+            '\\' => self.command(),
+            // Alignment points don't line up with anything in a line of text
+            '&' => String::new(),
+            '~' => "\u{A0}".to_owned(),
+            '\'' => "′".to_owned(),
+            '-' => "−".to_owned(),
+            '*' => "∗".to_owned(),
+            c => escape(c.encode_utf8(&mut [0; 4])),
+        }
+    }
+
+    /// The argument to a command or script, which is either a braced group or a single atom
+    fn argument(&mut self) -> String {
+        self.skip_whitespace();
+        self.atom()
+    }
+
+    /// The contents of a braced group without typesetting them, like for `\text{...}`
+    fn raw_argument(&mut self) -> &'tex str {
+        let tex = self.tex;
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            let start = self.pos;
+            self.bump();
+            return &tex[start..self.pos];
+        }
+        self.bump();
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            self.bump();
+        }
+        let raw = &tex[start..self.pos];
+        self.bump();
+        raw
+    }
+
+    fn optional_argument(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.peek() != Some('[') {
+            return None;
+        }
+        self.bump();
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c != ']') {
+            self.bump();
+        }
+        let raw = &self.tex[start..self.pos];
+        self.bump();
+        Some(typeset(raw))
+    }
+
+    fn command(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_ascii_alphabetic()) {
+            self.bump();
+        }
+        let name = &self.tex[start..self.pos];
+        if name.is_empty() {
+            return match self.bump() {
+                Some('\\') => "<br>".to_owned(),
+                Some(',' | ':' | ';' | '>') => "\u{2009}".to_owned(),
+                Some(' ') => " ".to_owned(),
+                Some('!') | None => String::new(),
+                Some('|') => "‖".to_owned(),
+                Some(c) => escape(c.encode_utf8(&mut [0; 4])),
+            };
+        }
+
+        if let Some(symbol) = symbol(name) {
+            return symbol.to_owned();
+        }
+        if FUNCTIONS.contains(&name) {
+            // Keeps `\sin\theta` from running together
+            let spaced = self
+                .peek()
+                .map_or(false, |c| c == '\\' || c.is_alphanumeric());
+            return if spaced {
+                format!("{name}\u{2009}")
+            } else {
+                name.to_owned()
+            };
+        }
+        match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.argument();
+                let denominator = self.argument();
+                format!(
+                    "{}/{}",
+                    parenthesize(&numerator),
+                    parenthesize(&denominator)
+                )
+            }
+            "binom" => {
+                let n = self.argument();
+                let k = self.argument();
+                format!("C({n}, {k})")
+            }
+            "sqrt" => {
+                let root = match self.optional_argument().as_deref() {
+                    None | Some("2") => "√".to_owned(),
+                    Some("3") => "∛".to_owned(),
+                    Some("4") => "∜".to_owned(),
+                    Some(degree) => format!("{}√", script(degree, true)),
+                };
+                let radicand = self.argument();
+                format!("{root}{}", parenthesize(&radicand))
+            }
+            "text" | "textrm" | "textnormal" | "textit" | "textbf" | "textsf" | "texttt"
+            | "mbox" => escape(self.raw_argument()),
+            "mathrm" | "mathit" | "mathbf" | "mathsf" | "mathtt" | "mathnormal" | "boldsymbol"
+            | "bm" | "operatorname" => self.argument(),
+            "mathbb" => self.argument().chars().map(double_struck).collect(),
+            "mathcal" | "mathscr" => self.argument().chars().map(script_letter).collect(),
+            "hat" | "widehat" => accent(&self.argument(), '\u{302}'),
+            "bar" | "overline" => accent(&self.argument(), '\u{305}'),
+            "vec" => accent(&self.argument(), '\u{20D7}'),
+            "dot" => accent(&self.argument(), '\u{307}'),
+            "ddot" => accent(&self.argument(), '\u{308}'),
+            "tilde" | "widetilde" => accent(&self.argument(), '\u{303}'),
+            "underline" => accent(&self.argument(), '\u{332}'),
+            "not" => accent(&self.argument(), '\u{338}'),
+            "pmod" => format!(" (mod {})", self.argument()),
+            "bmod" | "mod" => " mod ".to_owned(),
+            "left" | "right" | "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl"
+            | "Bigr" | "biggl" | "biggr" | "middle" => {
+                self.skip_whitespace();
+                // `\left.` is an invisible delimiter
+                if self.peek() == Some('.') {
+                    self.bump();
+                }
+                String::new()
+            }
+            "displaystyle" | "textstyle" | "scriptstyle" | "limits" | "nolimits" | "nonumber"
+            | "notag" => String::new(),
+            "begin" | "end" => {
+                let is_begin = name == "begin";
+                let delimiter = match (self.raw_argument(), is_begin) {
+                    ("pmatrix", true) => "(",
+                    ("pmatrix", false) => ")",
+                    ("bmatrix", true) => "[",
+                    ("bmatrix", false) => "]",
+                    ("Bmatrix" | "cases", true) => "{",
+                    ("Bmatrix", false) => "}",
+                    ("vmatrix", _) => "|",
+                    ("Vmatrix", _) => "‖",
+                    _ => "",
+                };
+                delimiter.to_owned()
+            }
+            _ => escape(&format!("\\{name}")),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    html_escape::encode_text(text).into_owned()
+}
+
+/// Wraps anything more complicated than a single number or name in parentheses, since there's no
+/// vinculum to group it
+fn parenthesize(html: &str) -> String {
+    let html = html.trim();
+    if html.chars().count() <= 1 || html.chars().all(char::is_alphanumeric) {
+        html.to_owned()
+    } else {
+        format!("({html})")
+    }
+}
+
+/// Symbols that already sit up high, so they don't need a superscript
+fn raised(c: char) -> Option<char> {
+    match c {
+        '′' | '″' | '‴' | '°' | '∗' => Some(c),
+        '∘' => Some('°'),
+        _ => None,
+    }
+}
+
+/// Raises or lowers `html`. Scripts that don't all have Unicode versions are written out with
+/// their `^` or `_` instead, since they'd be mistaken for regular text otherwise
+fn script(html: &str, is_superscript: bool) -> String {
+    let compact: String = html.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.is_empty() {
+        return String::new();
+    }
+    if is_superscript && compact.chars().all(|c| raised(c).is_some()) {
+        return compact.chars().filter_map(raised).collect();
+    }
+
+    let (map, tag, symbol): (fn(char) -> Option<char>, _, _) = if is_superscript {
+        (superscript, "sup", '^')
+    } else {
+        (subscript, "sub", '_')
+    };
+    let is_plain = !compact.contains(['<', '&']);
+    if is_plain && compact.chars().all(|c| map(c).is_some()) {
+        format!("<{tag}>{compact}</{tag}>")
+    } else if compact.chars().count() == 1 {
+        format!("{symbol}{compact}")
+    } else {
+        format!("{symbol}({})", html.trim())
+    }
+}
+
+/// Puts a combining accent over (or through) each character
+fn accent(html: &str, combining: char) -> String {
+    if html.contains(['<', '&']) {
+        return html.to_owned();
+    }
+    html.chars()
+        .flat_map(|c| [Some(c), (!c.is_whitespace()).then_some(combining)])
+        .flatten()
+        .collect()
+}
+
+fn double_struck(c: char) -> char {
+    let offset = |base: u32, start: char| char::from_u32(base + c as u32 - start as u32);
+    let styled = match c {
+        'C' => Some('ℂ'),
+        'H' => Some('ℍ'),
+        'N' => Some('ℕ'),
+        'P' => Some('ℙ'),
+        'Q' => Some('ℚ'),
+        'R' => Some('ℝ'),
+        'Z' => Some('ℤ'),
+        'A'..='Z' => offset(0x1D538, 'A'),
+        'a'..='z' => offset(0x1D552, 'a'),
+        '0'..='9' => offset(0x1D7D8, '0'),
+        _ => None,
+    };
+    styled.unwrap_or(c)
+}
+
+fn script_letter(c: char) -> char {
+    let styled = match c {
+        'B' => Some('ℬ'),
+        'E' => Some('ℰ'),
+        'F' => Some('ℱ'),
+        'H' => Some('ℋ'),
+        'I' => Some('ℐ'),
+        'L' => Some('ℒ'),
+        'M' => Some('ℳ'),
+        'R' => Some('ℛ'),
+        'A'..='Z' => char::from_u32(0x1D49C + c as u32 - 'A' as u32),
+        _ => None,
+    };
+    styled.unwrap_or(c)
+}
+
+fn symbol(name: &str) -> Option<&'static str> {
+    let symbol = match name {
+        // Greek letters
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "varpi" => "ϖ",
+        "rho" => "ρ",
+        "varrho" => "ϱ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        // Binary operators
+        "times" => "×",
+        "div" => "÷",
+        "cdot" => "⋅",
+        "pm" => "±",
+        "mp" => "∓",
+        "ast" => "∗",
+        "star" => "⋆",
+        "circ" => "∘",
+        "bullet" => "∙",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "wedge" | "land" => "∧",
+        "vee" | "lor" => "∨",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        // Relations
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "neq" | "ne" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "cong" => "≅",
+        "propto" => "∝",
+        "ll" => "≪",
+        "gg" => "≫",
+        "subset" => "⊂",
+        "supset" => "⊃",
+        "subseteq" => "⊆",
+        "supseteq" => "⊇",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "perp" => "⊥",
+        "parallel" => "∥",
+        "mid" => "∣",
+        // Arrows
+        "to" | "rightarrow" => "→",
+        "gets" | "leftarrow" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "implies" => "⟹",
+        "impliedby" => "⟸",
+        "mapsto" => "↦",
+        "longrightarrow" => "⟶",
+        "longleftarrow" => "⟵",
+        "uparrow" => "↑",
+        "downarrow" => "↓",
+        // Big operators
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "int" => "∫",
+        "iint" => "∬",
+        "iiint" => "∭",
+        "oint" => "∮",
+        "bigcup" => "⋃",
+        "bigcap" => "⋂",
+        // Delimiters
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "lvert" | "rvert" | "vert" => "|",
+        "lVert" | "rVert" | "Vert" => "‖",
+        "lbrace" => "{",
+        "rbrace" => "}",
+        // Everything else
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "forall" => "∀",
+        "exists" => "∃",
+        "nexists" => "∄",
+        "neg" | "lnot" => "¬",
+        "emptyset" | "varnothing" => "∅",
+        "angle" => "∠",
+        "triangle" => "△",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        "aleph" => "ℵ",
+        "prime" => "′",
+        "degree" => "°",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "therefore" => "∴",
+        "because" => "∵",
+        "quad" => "\u{2003}",
+        "qquad" => "\u{2003}\u{2003}",
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+/// Written out by name
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "lg", "exp", "lim", "max", "min", "sup", "inf", "det", "gcd", "deg", "dim", "ker",
+    "arg", "Pr",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marking_math() {
+        let md = "\
+Euler: $e^{i\\pi} + 1 = 0$ costs $5 and $10
+`$not math$` and \\$5 and $$x_1$$
+
+$$
+a_b * c_d
+$$
+
+```sh
+echo $HOME $PATH
+```
+";
+        let marked = mark_math(md);
+        assert_eq!(marked.lines().count(), md.lines().count());
+        assert_eq!(
+            marked,
+            "\
+Euler: `\u{E000}e^{i\\pi} + 1 = 0` costs $5 and $10
+`$not math$` and \\$5 and `\u{E001}x_1`
+
+```math
+a_b * c_d
+```
+
+```sh
+echo $HOME $PATH
+```
+"
+        );
+
+        assert!(matches!(mark_math("No math here"), Cow::Borrowed(_)));
+        // Unclosed blocks and spans are left as is
+        assert_eq!(mark_math("$$\nx\n"), "$$\nx\n");
+        assert_eq!(mark_math("$ x$ and $x $"), "$ x$ and $x $");
+    }
+
+    #[test]
+    fn typesetting() {
+        assert_eq!(typeset("e^{i\\pi} + 1 = 0"), "e^(iπ) + 1 = 0");
+        assert_eq!(
+            typeset("x^2 + y_i^2"),
+            "x<sup>2</sup> + y<sub>i</sub><sup>2</sup>"
+        );
+        assert_eq!(typeset("\\alpha \\leq \\beta"), "α ≤ β");
+        assert_eq!(typeset("\\frac{a+b}{2}"), "(a+b)/2");
+        assert_eq!(typeset("\\frac{dy}{dx}"), "dy/dx");
+        assert_eq!(typeset("\\sqrt{x} \\sqrt[3]{8}"), "√x ∛8");
+        assert_eq!(typeset("f'(x) = x^\\circ"), "f′(x) = x°");
+        assert_eq!(typeset("x \\in \\mathbb{R}"), "x ∈ ℝ");
+        assert_eq!(typeset("\\vec{v}"), "v\u{20D7}");
+        assert_eq!(typeset("\\text{if } x < 0"), "if x &lt; 0");
+        assert_eq!(typeset("a^{Q}"), "a^Q");
+        assert_eq!(typeset("\\sin\\theta"), "sin\u{2009}θ");
+        assert_eq!(typeset("\\unknown"), "\\unknown");
+        assert_eq!(
+            typeset("\\begin{pmatrix} a & b \\\\ c & d \\end{pmatrix}"),
+            "( a b <br> c d )"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! A shrunken outline of the document down its right edge for jumping around in it
+
+use crate::positioner::Positioned;
+use crate::utils::Rect;
+use crate::Element;
+
+/// How wide the minimap gets before it's capped to fit in the document's margin
+pub const WIDTH: f32 = 60.;
+/// Short documents are drawn at this scale instead of getting stretched to fill the window
+const MAX_SCALE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Block {
+    /// 1 for `<h1>` through 6 for `<h6>`
+    Heading(u8),
+    Text,
+    Code,
+    Image,
+    Table,
+}
+
+/// How much a document that's `doc_height` tall gets shrunk by to fit in `height`
+pub fn scale(doc_height: f32, height: f32) -> f32 {
+    if doc_height <= 0. {
+        return MAX_SCALE;
+    }
+    (height / doc_height).min(MAX_SCALE)
+}
+
+/// Where every block in `elements` that isn't tucked away in a collapsed section is in the
+/// document
+pub fn blocks(elements: &[Positioned<Element>]) -> Vec<(Rect, Block)> {
+    let mut blocks = Vec::new();
+    collect_blocks(elements, &mut blocks);
+    blocks
+}
+
+fn collect_blocks(elements: &[Positioned<Element>], blocks: &mut Vec<(Rect, Block)>) {
+    for element in elements {
+        let block = match &element.inner {
+            Element::TextBox(text_box) => match text_box.heading_level {
+                Some(level) => Block::Heading(level),
+                None if text_box.is_code_block => Block::Code,
+                None => Block::Text,
+            },
+            Element::Image(_) => Block::Image,
+            Element::Table(_) => Block::Table,
+            Element::Row(row) => {
+                collect_blocks(&row.elements, blocks);
+                continue;
+            }
+            Element::Section(section) => {
+                if let Some(summary) = &*section.summary {
+                    collect_blocks(std::slice::from_ref(summary), blocks);
+                }
+                if !*section.hidden.borrow() {
+                    collect_blocks(&section.elements, blocks);
+                }
+                continue;
+            }
+            Element::Spacer(_) => continue,
+        };
+        if let Some(bounds) = &element.bounds {
+            blocks.push((bounds.clone(), block));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{Text, TextBox};
+
+    fn text_box(y: f32, heading_level: Option<u8>, is_code_block: bool) -> Positioned<Element> {
+        let mut text_box = TextBox::new(vec![Text::new("Text".to_owned(), 1., [0.; 4])], 1.);
+        text_box.heading_level = heading_level;
+        text_box.is_code_block = is_code_block;
+        let mut element = Positioned::new(text_box);
+        element.bounds = Some(Rect::new((100., y), (500., 20.)));
+        element
+    }
+
+    #[test]
+    fn kinds_of_blocks() {
+        let kinds: Vec<_> = blocks(&[
+            text_box(0., Some(1), false),
+            text_box(30., None, false),
+            text_box(60., None, true),
+            Positioned::new(TextBox::new(Vec::new(), 1.)),
+        ])
+        .into_iter()
+        .map(|(bounds, block)| (bounds.pos.1, block))
+        .collect();
+        assert_eq!(
+            kinds,
+            [
+                (0., Block::Heading(1)),
+                (30., Block::Text),
+                (60., Block::Code)
+            ]
+        );
+    }
+
+    #[test]
+    fn scaling() {
+        assert_eq!(scale(1_000., 600.), MAX_SCALE);
+        assert_eq!(scale(60_000., 600.), 0.01);
+        assert_eq!(scale(0., 600.), MAX_SCALE);
+    }
+}
@@ -1,35 +1,94 @@
+use std::convert::Infallible;
 use std::env;
 use std::ffi::OsString;
 use std::path::PathBuf;
 
 use clap::builder::PossibleValue;
-use clap::{command, value_parser, Arg, Command, ValueEnum, ValueHint};
+use clap::{
+    command, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command, ValueEnum, ValueHint,
+};
 use serde::Deserialize;
 
 const SCALE_HELP: &str =
     "Factor to scale rendered file by [default: OS defined window scale factor]";
 
-#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(from = "String")]
 pub enum ThemeType {
     #[default]
     Auto,
     Dark,
     Light,
+    /// One of the config's `[themes.<name>]` sections
+    Named(String),
 }
 
 impl ThemeType {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Self::Auto => "auto",
             Self::Dark => "dark",
             Self::Light => "light",
+            Self::Named(name) => name,
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, Infallible> {
+        Ok(s.to_owned().into())
+    }
+}
+
+impl From<String> for ThemeType {
+    fn from(s: String) -> Self {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Self::Auto,
+            "dark" => Self::Dark,
+            "light" => Self::Light,
+            _ => Self::Named(s),
+        }
+    }
+}
+
+/// Parses a resolution like `1920x1080`
+fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| format!("Expected WIDTHxHEIGHT, like 1920x1080, but got `{s}`"))?;
+    let parse = |side: &str| match side.trim().parse() {
+        Ok(0) | Err(_) => Err(format!("Invalid size `{side}`")),
+        Ok(side) => Ok(side),
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+/// The paper that PDF exports and printouts are laid out on
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::A4 => "a4",
+            Self::Letter => "letter",
+        }
+    }
+
+    /// Width and height in points
+    pub fn points(self) -> (f32, f32) {
+        match self {
+            Self::A4 => (595.28, 841.89),
+            Self::Letter => (612., 792.),
         }
     }
 }
 
-impl ValueEnum for ThemeType {
+impl ValueEnum for PageSize {
     fn value_variants<'a>() -> &'a [Self] {
-        &[Self::Auto, Self::Dark, Self::Light]
+        &[Self::A4, Self::Letter]
     }
 
     fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
@@ -37,30 +96,81 @@ impl ValueEnum for ThemeType {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum Cli {
+    View(Args),
+    Serve(ServeArgs),
+    Bench(BenchArgs),
+    Export(ExportArgs),
+}
+
 #[derive(Debug, PartialEq, Clone, Default)]
 pub struct Args {
-    pub file_path: PathBuf,
+    pub file_path: Option<PathBuf>,
     pub theme: Option<ThemeType>,
     pub scale: Option<f32>,
     pub config: Option<PathBuf>,
     pub page_width: Option<f32>,
+    /// The one-indexed line of the source to start scrolled to
+    pub line: Option<usize>,
+    pub font: Option<String>,
+    pub monospace_font: Option<String>,
+    pub font_size: Option<f32>,
+    pub clipboard: bool,
+    pub no_cache: bool,
+    pub vim: bool,
+    pub follow: bool,
+    /// Open in a window of its own instead of sending the file to an already running instance
+    pub new_window: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ServeArgs {
+    pub dir: PathBuf,
+    pub port: u16,
+    pub theme: Option<ThemeType>,
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BenchArgs {
+    pub file_path: PathBuf,
+    pub frames: u32,
+    pub json: bool,
+    pub theme: Option<ThemeType>,
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExportArgs {
+    pub file_path: PathBuf,
+    pub png: Option<PathBuf>,
+    pub pdf: Option<PathBuf>,
+    pub html: Option<PathBuf>,
+    pub slides: Option<PathBuf>,
+    pub width: u32,
+    pub page_size: Option<PageSize>,
+    pub slide_size: (u32, u32),
+    pub theme: Option<ThemeType>,
+    pub config: Option<PathBuf>,
 }
 
 pub fn command() -> Command {
     let file_arg = Arg::new("file")
-        .required(true)
         .number_of_values(1)
         .value_name("FILE")
         .value_parser(value_parser!(PathBuf))
         .value_hint(ValueHint::AnyPath)
-        .help("Path to the markdown file");
+        .help("Path to the markdown file, or `-` to read it from stdin");
 
     let theme_arg = Arg::new("theme")
         .short('t')
         .long("theme")
         .number_of_values(1)
-        .value_parser(value_parser!(ThemeType))
-        .help("Theme to use when rendering");
+        .value_name("THEME")
+        .value_parser(ThemeType::parse)
+        .global(true)
+        .help("Theme to use when rendering, or the name of one from the config");
 
     let scale_arg = Arg::new("scale")
         .short('s')
@@ -74,21 +184,238 @@ pub fn command() -> Command {
         .long("config")
         .number_of_values(1)
         .value_parser(value_parser!(PathBuf))
+        .global(true)
         .help("Configuration file to use");
 
     let page_width_arg = Arg::new("page_width")
         .short('w')
         .long("page-width")
+        .visible_alias("max-content-width")
+        .number_of_values(1)
+        .value_parser(value_parser!(f32))
+        .help("Maximum width of page in pixels. Wider windows center the page in a column");
+
+    let line_arg = Arg::new("line")
+        .short('l')
+        .long("line")
+        .number_of_values(1)
+        .value_name("LINE")
+        .value_parser(value_parser!(usize))
+        .help("Start scrolled to where this line of the markdown source shows up");
+
+    let font_arg = Arg::new("font")
+        .long("font")
+        .number_of_values(1)
+        .value_name("FAMILY")
+        .help("Font family for regular text");
+
+    let monospace_font_arg = Arg::new("monospace_font")
+        .long("monospace-font")
+        .number_of_values(1)
+        .value_name("FAMILY")
+        .help("Font family for code");
+
+    let font_size_arg = Arg::new("font_size")
+        .long("font-size")
         .number_of_values(1)
         .value_parser(value_parser!(f32))
-        .help("Maximum width of page in pixels");
+        .help("Size of regular text in pixels");
+
+    let clipboard_arg = Arg::new("clipboard")
+        .long("clipboard")
+        .action(ArgAction::SetTrue)
+        .conflicts_with("file")
+        .help("Preview the clipboard's contents as markdown instead of a file");
+
+    let no_cache_arg = Arg::new("no_cache")
+        .long("no-cache")
+        .action(ArgAction::SetTrue)
+        .help("Download remote images every time instead of caching them on disk");
+
+    let vim_arg = Arg::new("vim")
+        .long("vim")
+        .action(ArgAction::SetTrue)
+        .help("Add vim-style keybindings for scrolling, searching, and copying");
+
+    let follow_arg = Arg::new("follow")
+        .long("follow")
+        .action(ArgAction::SetTrue)
+        .help("Keep reading stdin, adding onto the document as more comes in");
+
+    let new_window_arg = Arg::new("new_window")
+        .long("new-window")
+        .action(ArgAction::SetTrue)
+        .help("Open in a new window even when inlyne is already running");
+
+    let serve_cmd = Command::new("serve")
+        .about("Serve a directory of markdown files as themed HTML with live-reload")
+        .arg(
+            Arg::new("dir")
+                .number_of_values(1)
+                .value_name("DIR")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(ValueHint::DirPath)
+                .default_value(".")
+                .help("Directory to serve"),
+        )
+        .arg(
+            Arg::new("port")
+                .short('p')
+                .long("port")
+                .number_of_values(1)
+                .value_parser(value_parser!(u16))
+                .default_value("8000")
+                .help("Port to listen on"),
+        );
+
+    let bench_cmd = Command::new("bench")
+        .about("Measure how long each stage of rendering a file takes")
+        .arg(file_arg.clone().required(true))
+        .arg(
+            Arg::new("frames")
+                .short('f')
+                .long("frames")
+                .number_of_values(1)
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value("120")
+                .help("Number of frames to render after the first when measuring frame times"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Output the results as JSON"),
+        );
+
+    let export_cmd = Command::new("export")
+        .about("Render a file without opening a window and write it out")
+        .arg(file_arg.clone().required(true))
+        .arg(
+            Arg::new("png")
+                .long("png")
+                .number_of_values(1)
+                .value_name("OUT")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .help("Write the whole document out as one tall PNG image"),
+        )
+        .arg(
+            Arg::new("pdf")
+                .long("pdf")
+                .number_of_values(1)
+                .value_name("OUT")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .help("Write the document out as a PDF split into pages"),
+        )
+        .arg(
+            Arg::new("html")
+                .long("html")
+                .number_of_values(1)
+                .value_name("OUT")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath)
+                .help("Write the document out as a standalone HTML file styled like the viewer"),
+        )
+        .arg(
+            Arg::new("slides")
+                .long("slides")
+                .number_of_values(1)
+                .value_name("OUT")
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(ValueHint::AnyPath)
+                .help(
+                    "Write each slide out as a numbered PNG in the OUT directory, or all of them \
+                    as one PDF when OUT ends in `.pdf`",
+                ),
+        )
+        .group(
+            ArgGroup::new("format")
+                .args(["png", "pdf", "html", "slides"])
+                .required(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::new("width")
+                .long("width")
+                .number_of_values(1)
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value("1280")
+                .help("Width of the rendered PNG in pixels"),
+        )
+        .arg(
+            Arg::new("page_size")
+                .long("page-size")
+                .number_of_values(1)
+                .value_parser(value_parser!(PageSize))
+                .help("Size of the pages of a PDF [default: the config's `page-size`]"),
+        )
+        .arg(
+            Arg::new("slide_size")
+                .long("slide-size")
+                .number_of_values(1)
+                .value_name("WIDTHxHEIGHT")
+                .value_parser(parse_resolution)
+                .default_value("1920x1080")
+                .help("Resolution that each slide gets rendered at"),
+        );
 
     command!()
-        .arg(file_arg)
+        .arg(file_arg.help("Path to the markdown file [default: the most recently opened file]"))
         .arg(theme_arg)
         .arg(scale_arg)
         .arg(config_arg)
         .arg(page_width_arg)
+        .arg(line_arg)
+        .arg(font_arg)
+        .arg(monospace_font_arg)
+        .arg(font_size_arg)
+        .arg(clipboard_arg)
+        .arg(no_cache_arg)
+        .arg(vim_arg)
+        .arg(follow_arg)
+        .arg(new_window_arg)
+        .subcommand(serve_cmd)
+        .subcommand(bench_cmd)
+        .subcommand(export_cmd)
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+}
+
+impl Cli {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let program_args = std::env::args_os().collect();
+        Self::parse_from(program_args)
+    }
+
+    pub fn parse_from(args: Vec<OsString>) -> Self {
+        #[cfg(test)]
+        {
+            let _ = args;
+            panic!("Use `Cli::try_parse_from()` in tests");
+        }
+        #[cfg(not(test))]
+        match Self::try_parse_from(args) {
+            Ok(cli) => cli,
+            // Expose clap error normally
+            Err(clap_err) => clap_err.exit(),
+        }
+    }
+
+    pub fn try_parse_from(args: Vec<OsString>) -> Result<Self, clap::Error> {
+        let c = command();
+        let matches = c.try_get_matches_from(args)?;
+
+        let cli = match matches.subcommand() {
+            Some(("serve", matches)) => Self::Serve(ServeArgs::from_matches(matches)),
+            Some(("bench", matches)) => Self::Bench(BenchArgs::from_matches(matches)),
+            Some(("export", matches)) => Self::Export(ExportArgs::from_matches(matches)),
+            _ => Self::View(Args::from_matches(&matches)),
+        };
+
+        Ok(cli)
+    }
 }
 
 impl Args {
@@ -112,21 +439,108 @@ impl Args {
     }
 
     pub fn try_parse_from(args: Vec<OsString>) -> Result<Self, clap::Error> {
-        let c = command();
-        let matches = c.try_get_matches_from(args)?;
+        match Cli::try_parse_from(args)? {
+            Cli::View(args) => Ok(args),
+            _ => Err(command().error(
+                clap::error::ErrorKind::InvalidSubcommand,
+                "Expected arguments for viewing a file",
+            )),
+        }
+    }
 
-        let file_path = matches.get_one("file").cloned().unwrap();
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let file_path = matches.get_one("file").cloned();
         let theme = matches.get_one("theme").cloned();
         let scale = matches.get_one("scale").cloned();
         let config = matches.get_one("config").cloned();
         let page_width = matches.get_one("page_width").cloned();
+        let line = matches.get_one("line").cloned();
+        let font = matches.get_one("font").cloned();
+        let monospace_font = matches.get_one("monospace_font").cloned();
+        let font_size = matches.get_one("font_size").cloned();
+        let clipboard = matches.get_flag("clipboard");
+        let no_cache = matches.get_flag("no_cache");
+        let vim = matches.get_flag("vim");
+        let follow = matches.get_flag("follow");
+        let new_window = matches.get_flag("new_window");
 
-        Ok(Self {
+        Self {
             file_path,
             theme,
             scale,
             config,
             page_width,
-        })
+            line,
+            font,
+            monospace_font,
+            font_size,
+            clipboard,
+            no_cache,
+            vim,
+            follow,
+            new_window,
+        }
+    }
+}
+
+impl ServeArgs {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let dir = matches.get_one("dir").cloned().unwrap();
+        let port = matches.get_one("port").cloned().unwrap();
+        let theme = matches.get_one("theme").cloned();
+        let config = matches.get_one("config").cloned();
+
+        Self {
+            dir,
+            port,
+            theme,
+            config,
+        }
+    }
+}
+
+impl BenchArgs {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let file_path = matches.get_one("file").cloned().unwrap();
+        let frames = matches.get_one("frames").cloned().unwrap();
+        let json = matches.get_flag("json");
+        let theme = matches.get_one("theme").cloned();
+        let config = matches.get_one("config").cloned();
+
+        Self {
+            file_path,
+            frames,
+            json,
+            theme,
+            config,
+        }
+    }
+}
+
+impl ExportArgs {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let file_path = matches.get_one("file").cloned().unwrap();
+        let png = matches.get_one("png").cloned();
+        let pdf = matches.get_one("pdf").cloned();
+        let html = matches.get_one("html").cloned();
+        let slides = matches.get_one("slides").cloned();
+        let width = matches.get_one("width").cloned().unwrap();
+        let page_size = matches.get_one("page_size").cloned();
+        let slide_size = matches.get_one("slide_size").cloned().unwrap();
+        let theme = matches.get_one("theme").cloned();
+        let config = matches.get_one("config").cloned();
+
+        Self {
+            file_path,
+            png,
+            pdf,
+            html,
+            slides,
+            width,
+            page_size,
+            slide_size,
+            theme,
+            config,
+        }
     }
 }
@@ -1,38 +1,77 @@
+use std::collections::BTreeMap;
 use std::fs::read_to_string;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use super::ThemeType;
+use super::{PageSize, ResolvedTheme, ThemeType};
 use crate::color;
 use crate::keybindings::Keybindings;
+use crate::utils;
 
 use anyhow::Context;
 use serde::Deserialize;
 use syntect::highlighting::Theme as SyntectTheme;
 
-#[derive(Deserialize, Debug, PartialEq, Eq, Default, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Default, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct FontOptions {
     #[serde(default)]
     pub regular_font: Option<String>,
     #[serde(default)]
     pub monospace_font: Option<String>,
+    /// Size of body text in pixels before any scaling or zoom
+    #[serde(default)]
+    pub font_size: Option<f32>,
+    /// How large each heading level is compared to body text, from `h1` to `h6`
+    #[serde(default)]
+    pub heading_scales: Option<[f32; 6]>,
+    /// Tried in order for characters that the regular or monospace font doesn't have
+    #[serde(default)]
+    pub fallback_fonts: Vec<String>,
 }
 
 #[derive(Deserialize, Debug, Default, PartialEq)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct OptionalTheme {
+    /// A base16 scheme to take the colors from before applying any other settings
+    pub base16: Option<PathBuf>,
     pub text_color: Option<u32>,
     pub background_color: Option<u32>,
+    pub heading_color: Option<u32>,
     pub code_color: Option<u32>,
     pub quote_block_color: Option<u32>,
+    pub quote_bar_color: Option<u32>,
     pub link_color: Option<u32>,
     pub select_color: Option<u32>,
+    pub table_border_color: Option<u32>,
     pub checkbox_color: Option<u32>,
+    pub mark_color: Option<u32>,
+    pub scrollbar_color: Option<u32>,
+    pub note_color: Option<u32>,
+    pub tip_color: Option<u32>,
+    pub important_color: Option<u32>,
+    pub warning_color: Option<u32>,
+    pub caution_color: Option<u32>,
+    pub image_backdrop: Option<color::ImageBackdrop>,
     pub code_highlighter: Option<color::SyntaxTheme>,
 }
 
+/// A theme of its own that can be picked by name, like with `--theme <name>`
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct NamedTheme {
+    /// Which theme this one builds off of, including any changes to it from this config
+    #[serde(default)]
+    pub base: ResolvedTheme,
+    #[serde(flatten)]
+    pub theme: OptionalTheme,
+}
+
 impl OptionalTheme {
     pub fn merge(self, other: color::Theme) -> anyhow::Result<color::Theme> {
+        let other = match &self.base16 {
+            Some(path) => color::Base16Scheme::load(path)?.apply(other),
+            None => other,
+        };
         let code_highlighter = match self.code_highlighter {
             Some(theme) => SyntectTheme::try_from(theme)?,
             None => other.code_highlighter,
@@ -41,11 +80,22 @@ impl OptionalTheme {
         Ok(color::Theme {
             text_color: self.text_color.unwrap_or(other.text_color),
             background_color: self.background_color.unwrap_or(other.background_color),
+            heading_color: self.heading_color.unwrap_or(other.heading_color),
             code_color: self.code_color.unwrap_or(other.code_color),
             quote_block_color: self.quote_block_color.unwrap_or(other.quote_block_color),
+            quote_bar_color: self.quote_bar_color.unwrap_or(other.quote_bar_color),
             link_color: self.link_color.unwrap_or(other.link_color),
             select_color: self.select_color.unwrap_or(other.select_color),
+            table_border_color: self.table_border_color.unwrap_or(other.table_border_color),
             checkbox_color: self.checkbox_color.unwrap_or(other.checkbox_color),
+            mark_color: self.mark_color.unwrap_or(other.mark_color),
+            scrollbar_color: self.scrollbar_color.unwrap_or(other.scrollbar_color),
+            note_color: self.note_color.unwrap_or(other.note_color),
+            tip_color: self.tip_color.unwrap_or(other.tip_color),
+            important_color: self.important_color.unwrap_or(other.important_color),
+            warning_color: self.warning_color.unwrap_or(other.warning_color),
+            caution_color: self.caution_color.unwrap_or(other.caution_color),
+            image_backdrop: self.image_backdrop.unwrap_or(other.image_backdrop),
             code_highlighter,
         })
     }
@@ -66,6 +116,275 @@ impl Default for LinesToScroll {
     }
 }
 
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ColorSwatches(pub bool);
+
+impl From<ColorSwatches> for bool {
+    fn from(value: ColorSwatches) -> Self {
+        value.0
+    }
+}
+
+impl Default for ColorSwatches {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct SmartPunctuation(pub bool);
+
+impl From<SmartPunctuation> for bool {
+    fn from(value: SmartPunctuation) -> Self {
+        value.0
+    }
+}
+
+impl Default for SmartPunctuation {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// What the window gets called, filled in from `{heading}`, `{file}`, and `{path}`
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct WindowTitle(pub String);
+
+impl From<WindowTitle> for String {
+    fn from(value: WindowTitle) -> Self {
+        value.0
+    }
+}
+
+impl Default for WindowTitle {
+    fn default() -> Self {
+        Self(crate::title::DEFAULT_FORMAT.to_owned())
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct StickyHeadings(pub bool);
+
+impl From<StickyHeadings> for bool {
+    fn from(value: StickyHeadings) -> Self {
+        value.0
+    }
+}
+
+impl Default for StickyHeadings {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct SlideCounter(pub bool);
+
+impl From<SlideCounter> for bool {
+    fn from(value: SlideCounter) -> Self {
+        value.0
+    }
+}
+
+impl Default for SlideCounter {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct AnimateImages(pub bool);
+
+impl From<AnimateImages> for bool {
+    fn from(value: AnimateImages) -> Self {
+        value.0
+    }
+}
+
+impl Default for AnimateImages {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ImageLoupe(pub bool);
+
+impl From<ImageLoupe> for bool {
+    fn from(value: ImageLoupe) -> Self {
+        value.0
+    }
+}
+
+impl Default for ImageLoupe {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// In MiB
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct TextureBudget(pub u32);
+
+impl From<TextureBudget> for u32 {
+    fn from(value: TextureBudget) -> Self {
+        value.0
+    }
+}
+
+impl Default for TextureBudget {
+    fn default() -> Self {
+        Self(256)
+    }
+}
+
+/// What gets shown as a caption below images
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageCaptions {
+    #[default]
+    Title,
+    Alt,
+    Off,
+}
+
+/// What happens to the YAML front matter at the top of a document
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FrontMatter {
+    #[default]
+    Hide,
+    Table,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ScrollOptions {
+    pub speed: f32,
+    pub natural: bool,
+    pub momentum: bool,
+    pub smooth: bool,
+}
+
+impl Default for ScrollOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            natural: false,
+            momentum: true,
+            smooth: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CodeBlockOptions {
+    /// Number the lines down the left side of code blocks
+    pub line_numbers: bool,
+    /// Wrap long lines instead of letting them scroll sideways
+    pub wrap: bool,
+}
+
+impl Default for CodeBlockOptions {
+    fn default() -> Self {
+        Self {
+            line_numbers: false,
+            wrap: true,
+        }
+    }
+}
+
+impl CodeBlockOptions {
+    /// Lets a document pick for itself with `line-numbers` and `code-wrap` in its front matter
+    pub fn with_front_matter(self, md: &str) -> Self {
+        #[derive(Deserialize, Default)]
+        #[serde(default, rename_all = "kebab-case")]
+        struct Overrides {
+            line_numbers: Option<bool>,
+            code_wrap: Option<bool>,
+        }
+
+        let overrides = utils::front_matter(md)
+            .and_then(|front_matter| serde_yaml::from_str::<Overrides>(front_matter).ok())
+            .unwrap_or_default();
+        Self {
+            line_numbers: overrides.line_numbers.unwrap_or(self.line_numbers),
+            wrap: overrides.code_wrap.unwrap_or(self.wrap),
+        }
+    }
+}
+
+/// What goes between the rows of a table's body
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RowSeparators {
+    #[default]
+    Both,
+    Lines,
+    Stripes,
+    Off,
+}
+
+impl RowSeparators {
+    pub fn lines(self) -> bool {
+        matches!(self, Self::Both | Self::Lines)
+    }
+
+    pub fn stripes(self) -> bool {
+        matches!(self, Self::Both | Self::Stripes)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TableOptions {
+    /// Pixels of space around the contents of every cell
+    pub cell_padding: f32,
+    /// Shade in the header row
+    pub header_background: bool,
+    pub row_separators: RowSeparators,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            cell_padding: 10.0,
+            header_background: false,
+            row_separators: RowSeparators::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ImageFetchOptions {
+    /// Seconds to wait on a remote image before giving up on that attempt
+    pub timeout: f32,
+    pub retries: u32,
+    /// How many screens ahead of the view remote images start downloading
+    pub preload_screens: f32,
+    /// Extra headers sent to each host and its subdomains, like access tokens
+    pub headers: BTreeMap<String, BTreeMap<String, String>>,
+    /// Logs in to hosts listed in `~/.netrc`
+    pub netrc: bool,
+    /// Overrides the proxy from the `HTTP_PROXY` and `HTTPS_PROXY` environment variables
+    pub proxy: Option<String>,
+}
+
+impl Default for ImageFetchOptions {
+    fn default() -> Self {
+        Self {
+            timeout: 10.0,
+            retries: 2,
+            preload_screens: 2.0,
+            headers: BTreeMap::new(),
+            netrc: false,
+            proxy: None,
+        }
+    }
+}
+
 #[derive(Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct KeybindingsSection {
     #[serde(default)]
@@ -78,20 +397,56 @@ pub struct KeybindingsSection {
 pub struct Config {
     pub theme: Option<ThemeType>,
     pub scale: Option<f32>,
+    #[serde(alias = "max-content-width")]
     pub page_width: Option<f32>,
+    pub zoom: Option<f32>,
     pub lines_to_scroll: LinesToScroll,
+    pub color_swatches: ColorSwatches,
+    pub image_captions: ImageCaptions,
+    pub cross_references: bool,
+    pub smart_punctuation: SmartPunctuation,
+    pub front_matter: FrontMatter,
+    pub window_title: WindowTitle,
+    pub status_bar: bool,
+    pub sticky_headings: StickyHeadings,
+    pub minimap: bool,
+    pub page_size: PageSize,
+    pub slide_counter: SlideCounter,
+    pub animate_images: AnimateImages,
+    pub image_loupe: ImageLoupe,
+    pub toggle_checkboxes: bool,
+    pub vim_mode: bool,
+    pub texture_budget: TextureBudget,
+    pub scroll: ScrollOptions,
+    pub code_blocks: CodeBlockOptions,
+    pub tables: TableOptions,
+    pub image_fetch: ImageFetchOptions,
     pub light_theme: Option<OptionalTheme>,
     pub dark_theme: Option<OptionalTheme>,
+    pub themes: BTreeMap<String, NamedTheme>,
     pub font_options: Option<FontOptions>,
     pub keybindings: KeybindingsSection,
 }
 
 impl Config {
     pub fn load_from_str(s: &str) -> anyhow::Result<Self> {
-        let config = toml::from_str(s)?;
+        let (config, unknown_keys) = Self::parse(s)?;
+        for key in unknown_keys {
+            tracing::warn!("Ignoring unknown key in the configuration file: `{key}`");
+        }
         Ok(config)
     }
 
+    /// Along with every key that doesn't mean anything, which are most likely typos that would
+    /// otherwise get silently ignored
+    fn parse(s: &str) -> anyhow::Result<(Self, Vec<String>)> {
+        let mut unknown_keys = Vec::new();
+        let config = serde_ignored::deserialize(toml::Deserializer::new(s), |path| {
+            unknown_keys.push(key_name(&path));
+        })?;
+        Ok((config, unknown_keys))
+    }
+
     pub fn load_from_file(path: &Path) -> anyhow::Result<Self> {
         let config_content = read_to_string(path).context(format!(
             "Failed to read configuration file at '{}'",
@@ -99,13 +454,18 @@ impl Config {
         ))?;
 
         Self::load_from_str(&config_content)
+            .with_context(|| format!("Invalid configuration file at '{}'", path.display()))
     }
 
-    pub fn load_from_system() -> anyhow::Result<Self> {
+    /// Where the config lives when it isn't passed with `--config`
+    pub fn system_path() -> anyhow::Result<PathBuf> {
         let config_dir =
             dirs::config_dir().context("Failed to find the configuration directory")?;
+        Ok(config_dir.join("inlyne").join("inlyne.toml"))
+    }
 
-        let config_path = config_dir.join("inlyne").join("inlyne.toml");
+    pub fn load_from_system() -> anyhow::Result<Self> {
+        let config_path = Self::system_path()?;
 
         if !config_path.is_file() {
             return Ok(Self::default());
@@ -115,6 +475,30 @@ impl Config {
     }
 }
 
+/// The dotted key, like `scroll.speed`, that `path` leads to in the config
+fn key_name(path: &serde_ignored::Path) -> String {
+    let mut parts = Vec::new();
+    let mut path = path;
+    loop {
+        path = match path {
+            serde_ignored::Path::Root => break,
+            serde_ignored::Path::Map { parent, key } => {
+                parts.push(key.to_owned());
+                parent
+            }
+            serde_ignored::Path::Seq { parent, index } => {
+                parts.push(index.to_string());
+                parent
+            }
+            serde_ignored::Path::Some { parent }
+            | serde_ignored::Path::NewtypeStruct { parent }
+            | serde_ignored::Path::NewtypeVariant { parent } => parent,
+        };
+    }
+    parts.reverse();
+    parts.join(".")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,4 +526,59 @@ mod tests {
             color::Theme::light_default()
         );
     }
+
+    #[test]
+    fn unknown_keys() {
+        let (config, unknown_keys) = Config::parse(
+            r#"
+            scale = 1.5
+            scroll.speeed = 2.0
+
+            [font-options]
+            font-sise = 12
+            monospace-font = "Fira Code"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.scale, Some(1.5));
+        assert_eq!(unknown_keys, ["scroll.speeed", "font-options.font-sise"]);
+    }
+
+    #[test]
+    fn base16_scheme() {
+        let palette: String = (0..16)
+            .map(|i| format!("  base{i:02X}: \"#{i:x}{i:x}{i:x}{i:x}{i:x}{i:x}\"\n"))
+            .collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scheme.yaml");
+        std::fs::write(&path, format!("name: Test\npalette:\n{palette}")).unwrap();
+
+        let theme = OptionalTheme {
+            base16: Some(path.clone()),
+            link_color: Some(0x123456),
+            ..Default::default()
+        };
+        let theme = theme.merge(color::Theme::dark_default()).unwrap();
+        assert_eq!(theme.background_color, 0x000000);
+        assert_eq!(theme.text_color, 0x555555);
+        assert_eq!(theme.caution_color, 0x888888);
+        // Explicit colors win over the scheme's
+        assert_eq!(theme.link_color, 0x123456);
+
+        // The original format has the colors at the top level without a `#`
+        std::fs::write(&path, palette.replace("  ", "").replace('#', "")).unwrap();
+        let theme = OptionalTheme {
+            base16: Some(path.clone()),
+            ..Default::default()
+        };
+        let theme = theme.merge(color::Theme::light_default()).unwrap();
+        assert_eq!(theme.background_color, 0x000000);
+
+        std::fs::write(&path, "base00: \"000000\"\n").unwrap();
+        let theme = OptionalTheme {
+            base16: Some(path),
+            ..Default::default()
+        };
+        assert!(theme.merge(color::Theme::light_default()).is_err());
+    }
 }
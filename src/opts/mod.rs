@@ -3,17 +3,22 @@ mod config;
 #[cfg(test)]
 mod tests;
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 use crate::color;
-pub use cli::{Args, ThemeType};
-pub use config::{Config, FontOptions, KeybindingsSection};
+use crate::utils::MarkdownOptions;
+pub use cli::{Args, BenchArgs, Cli, ExportArgs, PageSize, ServeArgs, ThemeType};
+pub use config::{
+    CodeBlockOptions, Config, FontOptions, FrontMatter, ImageCaptions, ImageFetchOptions,
+    KeybindingsSection, NamedTheme, OptionalTheme, ScrollOptions, TableOptions,
+};
 
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use smart_debug::SmartDebug;
 
-#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ResolvedTheme {
     Dark,
     #[default]
@@ -21,11 +26,12 @@ pub enum ResolvedTheme {
 }
 
 impl ResolvedTheme {
-    fn new(theme_ty: ThemeType) -> Option<Self> {
+    fn new(theme_ty: &ThemeType, named: Option<&NamedTheme>) -> Option<Self> {
         match theme_ty {
             ThemeType::Auto => Self::try_detect(),
             ThemeType::Dark => Some(Self::Dark),
             ThemeType::Light => Some(Self::Light),
+            ThemeType::Named(_) => named.map(|named| named.base),
         }
     }
 
@@ -40,12 +46,63 @@ impl ResolvedTheme {
 
 #[derive(SmartDebug, PartialEq)]
 pub struct Opts {
+    /// The file being viewed. A placeholder in the current directory when previewing the clipboard
+    /// or stdin
     pub file_path: PathBuf,
+    /// Preview what's in the clipboard instead of reading `file_path`
+    pub clipboard: bool,
+    /// Read the document from stdin instead of `file_path`
+    pub stdin: bool,
+    /// Keep reading stdin after the window opens, updating the document as more comes in
+    pub follow: bool,
     #[debug(skip)]
     pub theme: color::Theme,
+    /// What toggling the theme switches to. The dark theme when `theme` is light and vice versa
+    #[debug(skip)]
+    pub toggled_theme: color::Theme,
     pub scale: Option<f32>,
     pub page_width: Option<f32>,
+    /// Starts every document at this zoom instead of the one that it was last viewed at
+    pub zoom: Option<f32>,
+    /// Start scrolled to where this one-indexed line of the source shows up
+    pub line: Option<usize>,
     pub lines_to_scroll: f32,
+    pub color_swatches: bool,
+    pub image_captions: ImageCaptions,
+    /// Number labeled figures, tables, and equations and resolve references to them
+    pub cross_references: bool,
+    /// Curly quotes, en and em dashes, and ellipses in place of their plain text versions
+    pub smart_punctuation: bool,
+    pub front_matter: FrontMatter,
+    /// Format for the window's title, see [`crate::title::format`]
+    pub window_title: String,
+    /// Start with the status bar shown along the bottom of the document
+    pub status_bar: bool,
+    /// Pin the heading of the section at the top of the view above the document
+    pub sticky_headings: bool,
+    /// Start with the minimap shown in the document's right margin
+    pub minimap: bool,
+    /// The paper that the document gets laid out on for printing
+    pub page_size: PageSize,
+    /// Show which slide is up out of how many while presenting
+    pub slide_counter: bool,
+    /// Download remote images every time instead of going through the disk cache
+    pub no_cache: bool,
+    /// Play animated GIFs, PNGs, and WebPs instead of only showing their first frame
+    pub animate_images: bool,
+    /// Magnify scaled down images under the cursor
+    pub image_loupe: bool,
+    /// Clicking a task list checkbox checks it off in the file
+    pub toggle_checkboxes: bool,
+    /// Vim-style keybindings are added and confirming a search frees up keys to step through it
+    pub vim_mode: bool,
+    /// How many MiB of image textures can stay on the GPU
+    pub texture_budget: u32,
+    pub scroll: ScrollOptions,
+    /// Line numbers and wrapping for code blocks, which can get toggled while viewing
+    pub code_blocks: CodeBlockOptions,
+    pub tables: TableOptions,
+    pub image_fetch: ImageFetchOptions,
     pub font_opts: FontOptions,
     pub keybindings: KeybindingsSection,
     pub color_scheme: Option<ResolvedTheme>,
@@ -84,11 +141,33 @@ impl Opts {
             theme: config_theme,
             scale: config_scale,
             page_width: config_page_width,
+            zoom,
             lines_to_scroll,
+            color_swatches,
+            image_captions,
+            cross_references,
+            smart_punctuation,
+            front_matter,
+            window_title,
+            status_bar,
+            sticky_headings,
+            minimap,
+            page_size,
+            slide_counter,
+            animate_images,
+            image_loupe,
+            toggle_checkboxes,
+            vim_mode,
+            texture_budget,
+            scroll,
+            code_blocks,
+            tables,
+            image_fetch,
             light_theme,
             dark_theme,
+            themes,
             font_options,
-            keybindings,
+            mut keybindings,
         } = config;
 
         let Args {
@@ -97,47 +176,141 @@ impl Opts {
             scale: args_scale,
             config: _,
             page_width: args_page_width,
+            line,
+            font: args_font,
+            monospace_font: args_monospace_font,
+            font_size: args_font_size,
+            clipboard,
+            no_cache,
+            vim,
+            follow,
+            new_window: _,
         } = args;
 
-        let resolved_theme = args_theme
-            .or(config_theme)
-            .and_then(ResolvedTheme::new)
-            .or(fallback_theme);
-        let theme = {
-            let (maybe_theme, fallback_values) = match resolved_theme {
-                Some(ResolvedTheme::Dark) => (dark_theme, color::Theme::dark_default()),
-                None | Some(ResolvedTheme::Light) => (light_theme, color::Theme::light_default()),
-            };
-
-            match maybe_theme {
-                Some(theme) => theme.merge(fallback_values)?,
-                None => fallback_values,
-            }
+        let stdin = file_path.as_deref() == Some(Path::new("-"));
+        anyhow::ensure!(
+            !follow || stdin,
+            "`--follow` only works when reading from stdin with `-`"
+        );
+        let file_path = if clipboard || stdin {
+            // Nothing is on disk, so relative links get resolved from where we were launched
+            let placeholder = if stdin { "stdin.md" } else { "clipboard.md" };
+            std::env::current_dir()
+                .context("Failed to get the current directory")?
+                .join(placeholder)
+        } else {
+            file_path
+                .context("No file was given and there isn't a recently opened file to reopen")?
         };
 
+        let (theme, resolved_theme, toggled_theme) = resolve_theme(
+            args_theme.or(config_theme),
+            light_theme,
+            dark_theme,
+            themes,
+            fallback_theme,
+        )?;
+
         let scale = args_scale.or(config_scale);
-        let font_opts = font_options.unwrap_or_default();
+        let mut font_opts = font_options.unwrap_or_default();
+        font_opts.regular_font = args_font.or(font_opts.regular_font);
+        font_opts.monospace_font = args_monospace_font.or(font_opts.monospace_font);
+        font_opts.font_size = args_font_size.or(font_opts.font_size);
         let page_width = args_page_width.or(config_page_width);
         let lines_to_scroll = lines_to_scroll.into();
+        let color_swatches = color_swatches.into();
+        let smart_punctuation = smart_punctuation.into();
+        let window_title = window_title.into();
+        let sticky_headings = sticky_headings.into();
+        let slide_counter = slide_counter.into();
+        let animate_images = animate_images.into();
+        let image_loupe = image_loupe.into();
+        let texture_budget = texture_budget.into();
+        let vim_mode = vim || vim_mode;
+        if vim_mode {
+            keybindings.base = keybindings.base.with_vim_mode();
+        }
 
         Ok(Self {
             file_path,
+            clipboard,
+            stdin,
+            follow,
             theme,
+            toggled_theme,
             scale,
             page_width,
+            zoom,
+            line,
             lines_to_scroll,
+            color_swatches,
+            image_captions,
+            cross_references,
+            smart_punctuation,
+            front_matter,
+            window_title,
+            status_bar,
+            sticky_headings,
+            minimap,
+            page_size,
+            slide_counter,
+            no_cache,
+            animate_images,
+            image_loupe,
+            toggle_checkboxes,
+            vim_mode,
+            texture_budget,
+            scroll,
+            code_blocks,
+            tables,
+            image_fetch,
             font_opts,
             keybindings,
             color_scheme: resolved_theme,
         })
     }
 
+    /// The markdown extensions that documents get rendered with
+    pub fn markdown(&self) -> MarkdownOptions {
+        MarkdownOptions {
+            cross_references: self.cross_references,
+            smart_punctuation: self.smart_punctuation,
+            front_matter_table: self.front_matter == FrontMatter::Table,
+        }
+    }
+
+    /// Options for exporting the document that's being viewed, like for printing it
+    pub fn export_opts(&self) -> ExportOpts {
+        ExportOpts {
+            file_path: self.file_path.clone(),
+            png: None,
+            pdf: None,
+            html: None,
+            slides: None,
+            // Only PNGs go by this, which printing doesn't use
+            width: 1280,
+            page_size: self.page_size,
+            slide_size: (1920, 1080),
+            theme: self.theme.clone(),
+            scale: self.scale,
+            page_width: self.page_width,
+            font_opts: self.font_opts.clone(),
+            color_scheme: self.color_scheme,
+            markdown: self.markdown(),
+            image_captions: self.image_captions,
+            color_swatches: self.color_swatches,
+            tables: self.tables,
+        }
+    }
+
     /// Arguments to supply to program that are opened externally.
     pub fn program_args(file_path: &Path) -> Vec<String> {
         let current_args = Args::new();
         let mut args = Vec::new();
 
         args.push(file_path.display().to_string());
+        // Otherwise it would just get sent right back to us
+        args.push("--new-window".to_owned());
 
         if let Some(theme) = current_args.theme {
             args.push("--theme".to_owned());
@@ -159,6 +332,280 @@ impl Opts {
             args.push(page_width.to_string());
         }
 
+        if let Some(font) = current_args.font {
+            args.push("--font".to_owned());
+            args.push(font);
+        }
+
+        if let Some(monospace_font) = current_args.monospace_font {
+            args.push("--monospace-font".to_owned());
+            args.push(monospace_font);
+        }
+
+        if let Some(font_size) = current_args.font_size {
+            args.push("--font-size".to_owned());
+            args.push(font_size.to_string());
+        }
+
+        if current_args.no_cache {
+            args.push("--no-cache".to_owned());
+        }
+
+        if current_args.vim {
+            args.push("--vim".to_owned());
+        }
+
         args
     }
 }
+
+#[derive(SmartDebug, PartialEq)]
+pub struct ServeOpts {
+    pub dir: PathBuf,
+    pub port: u16,
+    #[debug(skip)]
+    pub theme: color::Theme,
+    pub markdown: MarkdownOptions,
+}
+
+impl ServeOpts {
+    pub fn parse_and_load_from(args: ServeArgs, config: Config) -> Result<Self> {
+        let ServeArgs {
+            dir,
+            port,
+            theme: args_theme,
+            config: _,
+        } = args;
+        let Config {
+            theme: config_theme,
+            light_theme,
+            dark_theme,
+            themes,
+            cross_references,
+            smart_punctuation,
+            front_matter,
+            ..
+        } = config;
+
+        let (theme, _, _) = resolve_theme(
+            args_theme.or(config_theme),
+            light_theme,
+            dark_theme,
+            themes,
+            ResolvedTheme::try_detect(),
+        )?;
+
+        Ok(Self {
+            dir,
+            port,
+            theme,
+            markdown: MarkdownOptions {
+                cross_references,
+                smart_punctuation: smart_punctuation.into(),
+                front_matter_table: front_matter == FrontMatter::Table,
+            },
+        })
+    }
+}
+
+#[derive(SmartDebug, PartialEq)]
+pub struct BenchOpts {
+    pub file_path: PathBuf,
+    pub frames: u32,
+    pub json: bool,
+    #[debug(skip)]
+    pub theme: color::Theme,
+    pub scale: Option<f32>,
+    pub page_width: Option<f32>,
+    pub font_opts: FontOptions,
+    pub color_scheme: Option<ResolvedTheme>,
+}
+
+impl BenchOpts {
+    pub fn parse_and_load_from(args: BenchArgs, config: Config) -> Result<Self> {
+        let BenchArgs {
+            file_path,
+            frames,
+            json,
+            theme: args_theme,
+            config: _,
+        } = args;
+        let Config {
+            theme: config_theme,
+            scale,
+            page_width,
+            light_theme,
+            dark_theme,
+            themes,
+            font_options,
+            ..
+        } = config;
+
+        let (theme, color_scheme, _) = resolve_theme(
+            args_theme.or(config_theme),
+            light_theme,
+            dark_theme,
+            themes,
+            ResolvedTheme::try_detect(),
+        )?;
+        let font_opts = font_options.unwrap_or_default();
+
+        Ok(Self {
+            file_path,
+            frames,
+            json,
+            theme,
+            scale,
+            page_width,
+            font_opts,
+            color_scheme,
+        })
+    }
+}
+
+#[derive(SmartDebug, PartialEq)]
+pub struct ExportOpts {
+    pub file_path: PathBuf,
+    /// Where the rendered document gets written as a PNG
+    pub png: Option<PathBuf>,
+    /// Where the rendered document gets written as a PDF
+    pub pdf: Option<PathBuf>,
+    /// Where the document gets written as a standalone HTML file
+    pub html: Option<PathBuf>,
+    /// Where each slide gets written, as numbered PNGs in a directory or as one PDF
+    pub slides: Option<PathBuf>,
+    pub width: u32,
+    pub page_size: PageSize,
+    /// The resolution that each slide gets rendered at
+    pub slide_size: (u32, u32),
+    #[debug(skip)]
+    pub theme: color::Theme,
+    pub scale: Option<f32>,
+    pub page_width: Option<f32>,
+    pub font_opts: FontOptions,
+    pub color_scheme: Option<ResolvedTheme>,
+    pub markdown: MarkdownOptions,
+    pub image_captions: ImageCaptions,
+    pub color_swatches: bool,
+    pub tables: TableOptions,
+}
+
+impl ExportOpts {
+    pub fn parse_and_load_from(args: ExportArgs, config: Config) -> Result<Self> {
+        let ExportArgs {
+            file_path,
+            png,
+            pdf,
+            html,
+            slides,
+            width,
+            page_size: args_page_size,
+            slide_size,
+            theme: args_theme,
+            config: _,
+        } = args;
+        let Config {
+            theme: config_theme,
+            scale,
+            page_width,
+            page_size: config_page_size,
+            image_captions,
+            color_swatches,
+            cross_references,
+            smart_punctuation,
+            front_matter,
+            tables,
+            light_theme,
+            dark_theme,
+            themes,
+            font_options,
+            ..
+        } = config;
+
+        let (theme, color_scheme, _) = resolve_theme(
+            args_theme.or(config_theme),
+            light_theme,
+            dark_theme,
+            themes,
+            ResolvedTheme::try_detect(),
+        )?;
+        let font_opts = font_options.unwrap_or_default();
+
+        Ok(Self {
+            file_path,
+            png,
+            pdf,
+            html,
+            slides,
+            width,
+            page_size: args_page_size.unwrap_or(config_page_size),
+            slide_size,
+            theme,
+            scale,
+            page_width,
+            font_opts,
+            color_scheme,
+            markdown: MarkdownOptions {
+                cross_references,
+                smart_punctuation: smart_punctuation.into(),
+                front_matter_table: front_matter == FrontMatter::Table,
+            },
+            image_captions,
+            color_swatches: color_swatches.into(),
+            tables,
+        })
+    }
+}
+
+/// Picks the light or dark theme based on the requested theme type and merges in any user
+/// customizations. A named theme gets layered on top of the one that it's based on. The other
+/// theme is returned too, for switching to at runtime
+fn resolve_theme(
+    theme_ty: Option<ThemeType>,
+    light_theme: Option<OptionalTheme>,
+    dark_theme: Option<OptionalTheme>,
+    mut themes: BTreeMap<String, NamedTheme>,
+    fallback_theme: Option<ResolvedTheme>,
+) -> Result<(color::Theme, Option<ResolvedTheme>, color::Theme)> {
+    let named = match &theme_ty {
+        Some(ThemeType::Named(name)) => Some(themes.remove(name).with_context(|| {
+            format!("There's no theme named '{name}' in the configuration's `[themes]`")
+        })?),
+        _ => None,
+    };
+    let resolved_theme = theme_ty
+        .and_then(|theme_ty| ResolvedTheme::new(&theme_ty, named.as_ref()))
+        .or(fallback_theme);
+    let ((maybe_theme, fallback_values), (maybe_other, other_fallback_values)) =
+        match resolved_theme {
+            Some(ResolvedTheme::Dark) => (
+                (dark_theme, color::Theme::dark_default()),
+                (light_theme, color::Theme::light_default()),
+            ),
+            None | Some(ResolvedTheme::Light) => (
+                (light_theme, color::Theme::light_default()),
+                (dark_theme, color::Theme::dark_default()),
+            ),
+        };
+
+    let theme = match maybe_theme {
+        Some(theme) => theme.merge(fallback_values)?,
+        None => fallback_values,
+    };
+    let theme = match named {
+        Some(named) => named.theme.merge(theme)?,
+        None => theme,
+    };
+    // Not being able to toggle to a customized theme shouldn't stop us from starting up
+    let other_theme = match maybe_other {
+        Some(other) => other
+            .merge(other_fallback_values.clone())
+            .unwrap_or_else(|err| {
+                tracing::warn!("Failed loading the theme to toggle to: {err:#}");
+                other_fallback_values
+            }),
+        None => other_fallback_values,
+    };
+
+    Ok((theme, resolved_theme, other_theme))
+}
@@ -22,6 +22,7 @@ macro_rules! snapshot_config_parse_error {
 
 const UNKNOWN_THEME: &str = r#"light-theme.code-highlighter = "doesnt-exist""#;
 const INVALID_THEME_TY: &str = "light-theme.code-highlighter = []";
+const UNKNOWN_IMAGE_BACKDROP: &str = r#"dark-theme.image-backdrop = "stripes""#;
 
 const FIX_THIS_SUCKY_ERROR_MESSAGE: &str = r#"
 [keybindings]
@@ -34,6 +35,7 @@ base = [
 snapshot_config_parse_error!(
     (unknown_theme, UNKNOWN_THEME),
     (invalid_theme_ty, INVALID_THEME_TY),
+    (unknown_image_backdrop, UNKNOWN_IMAGE_BACKDROP),
     // FIXME: vv
     (fix_this_sucky_error_message, FIX_THIS_SUCKY_ERROR_MESSAGE),
 );
@@ -1,9 +1,16 @@
 use std::ffi::OsString;
 use std::path::PathBuf;
 
-use crate::color::{SyntaxTheme, Theme, ThemeDefaults};
-use crate::opts::config::{self, FontOptions, LinesToScroll};
-use crate::opts::{cli, Args, Opts, ResolvedTheme, ThemeType};
+use crate::color::{ImageBackdrop, SyntaxTheme, Theme, ThemeDefaults};
+use crate::keybindings::Keybindings;
+use crate::opts::config::{
+    self, AnimateImages, CodeBlockOptions, ColorSwatches, FontOptions, FrontMatter, ImageCaptions,
+    ImageFetchOptions, ImageLoupe, LinesToScroll, RowSeparators, ScrollOptions, SmartPunctuation,
+    StickyHeadings, TableOptions, TextureBudget, WindowTitle,
+};
+use crate::opts::{
+    cli, Args, BenchArgs, Cli, ExportArgs, Opts, PageSize, ResolvedTheme, ServeArgs, ThemeType,
+};
 use crate::test_utils::init_test_log;
 
 use pretty_assertions::assert_eq;
@@ -19,11 +26,38 @@ impl Opts {
     fn mostly_default(file_path: impl Into<PathBuf>) -> Self {
         Self {
             file_path: file_path.into(),
+            clipboard: false,
+            stdin: false,
+            follow: false,
             theme: ResolvedTheme::Light.as_theme(),
+            toggled_theme: ResolvedTheme::Dark.as_theme(),
             scale: None,
             page_width: None,
+            zoom: None,
+            line: None,
             font_opts: FontOptions::default(),
             lines_to_scroll: LinesToScroll::default().0,
+            color_swatches: ColorSwatches::default().0,
+            image_captions: ImageCaptions::default(),
+            cross_references: false,
+            smart_punctuation: SmartPunctuation::default().0,
+            front_matter: FrontMatter::default(),
+            window_title: WindowTitle::default().0,
+            status_bar: false,
+            sticky_headings: StickyHeadings::default().0,
+            minimap: false,
+            page_size: PageSize::default(),
+            slide_counter: true,
+            no_cache: false,
+            animate_images: AnimateImages::default().0,
+            image_loupe: ImageLoupe::default().0,
+            toggle_checkboxes: false,
+            vim_mode: false,
+            texture_budget: TextureBudget::default().0,
+            scroll: ScrollOptions::default(),
+            code_blocks: CodeBlockOptions::default(),
+            tables: TableOptions::default(),
+            image_fetch: ImageFetchOptions::default(),
             keybindings: Default::default(),
             color_scheme: None,
         }
@@ -79,6 +113,7 @@ fn config_overrides_default() {
         .unwrap(),
         Opts {
             theme: ResolvedTheme::Dark.as_theme(),
+            toggled_theme: ResolvedTheme::Light.as_theme(),
             color_scheme: Some(ResolvedTheme::Dark),
             ..Opts::mostly_default("file.md")
         }
@@ -134,6 +169,7 @@ fn from_cli() {
         .unwrap(),
         Opts {
             theme: ResolvedTheme::Dark.as_theme(),
+            toggled_theme: ResolvedTheme::Light.as_theme(),
             color_scheme: Some(ResolvedTheme::Dark),
             ..Opts::mostly_default("file.md")
         }
@@ -154,6 +190,7 @@ fn from_cli() {
         .unwrap(),
         Opts {
             theme: ResolvedTheme::Dark.as_theme(),
+            toggled_theme: ResolvedTheme::Light.as_theme(),
             scale: Some(1.5),
             color_scheme: Some(ResolvedTheme::Dark),
             ..Opts::mostly_default("file.md")
@@ -171,6 +208,7 @@ fn cli_kitchen_sink() {
         "--scale", "1.5",
         "--config", "/path/to/file.toml",
         "--page-width", "500",
+        "--line", "42",
         "file.md",
     ]);
     assert_eq!(
@@ -182,14 +220,54 @@ fn cli_kitchen_sink() {
         .unwrap(),
         Opts {
             page_width: Some(500.0),
+            line: Some(42),
             scale: Some(1.5),
             theme: ResolvedTheme::Dark.as_theme(),
+            toggled_theme: ResolvedTheme::Light.as_theme(),
             color_scheme: Some(ResolvedTheme::Dark),
             ..Opts::mostly_default("file.md")
         }
     );
 }
 
+#[test]
+fn named_theme() {
+    init_test_log();
+
+    let config = config::Config::load_from_str(
+        r#"
+        [dark-theme]
+        text-color = 0xeeeeee
+
+        [themes.midnight]
+        base = "Dark"
+        background-color = 0x000000
+        "#,
+    )
+    .unwrap();
+    let args = gen_args(vec!["--theme", "midnight", "file.md"]);
+    let opts = Opts::parse_and_load_with_system_theme(
+        Args::try_parse_from(args).unwrap(),
+        config,
+        Some(ResolvedTheme::Light),
+    )
+    .unwrap();
+    // Layered on top of the customized dark theme
+    assert_eq!(opts.theme.background_color, 0x000000);
+    assert_eq!(opts.theme.text_color, 0xeeeeee);
+    assert_eq!(opts.toggled_theme, ResolvedTheme::Light.as_theme());
+    assert_eq!(opts.color_scheme, Some(ResolvedTheme::Dark));
+
+    let args = gen_args(vec!["--theme", "sepia", "file.md"]);
+    let err = Opts::parse_and_load_with_system_theme(
+        Args::try_parse_from(args).unwrap(),
+        config::Config::default(),
+        Some(ResolvedTheme::Light),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("'sepia'"), "{err}");
+}
+
 #[test]
 fn builtin_syntax_theme() {
     init_test_log();
@@ -252,10 +330,388 @@ fn custom_syntax_theme() {
     );
 }
 
+#[test]
+fn image_backdrops() {
+    init_test_log();
+
+    let backdrop = |value: &str| {
+        let config =
+            config::Config::load_from_str(&format!("dark-theme.image-backdrop = {value}")).unwrap();
+        config.dark_theme.unwrap().image_backdrop.unwrap()
+    };
+    assert_eq!(backdrop(r#""none""#), ImageBackdrop::None);
+    assert_eq!(backdrop(r#""checkerboard""#), ImageBackdrop::Checkerboard);
+    assert_eq!(backdrop("0xf0f0f0"), ImageBackdrop::Solid(0xf0f0f0));
+}
+
+#[test]
+fn image_fetch_headers() {
+    init_test_log();
+
+    let config = config::Config::load_from_str(
+        r#"
+[image-fetch]
+netrc = true
+
+[image-fetch.headers."gitlab.example.com"]
+PRIVATE-TOKEN = "glpat-123"
+"#,
+    )
+    .unwrap();
+    let image_fetch = config.image_fetch;
+    assert!(image_fetch.netrc);
+    assert_eq!(image_fetch.proxy, None);
+    assert_eq!(
+        image_fetch.headers["gitlab.example.com"]["PRIVATE-TOKEN"],
+        "glpat-123"
+    );
+}
+
+#[test]
+fn table_options() {
+    init_test_log();
+
+    let config = config::Config::load_from_str(
+        r#"
+[tables]
+header-background = true
+row-separators = "lines"
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.tables,
+        TableOptions {
+            cell_padding: 10.0,
+            header_background: true,
+            row_separators: RowSeparators::Lines,
+        }
+    );
+    assert!(config.tables.row_separators.lines());
+    assert!(!config.tables.row_separators.stripes());
+}
+
 #[test]
 fn missing_file_arg() {
     init_test_log();
 
-    // A file arg should be required
-    assert!(Args::try_parse_from(gen_args(Vec::new())).is_err());
+    // The file arg can be left off to reopen the most recent file instead
+    let args = Args::try_parse_from(gen_args(Vec::new())).unwrap();
+    assert_eq!(args.file_path, None);
+    // but something still has to fill it in before it can be viewed
+    assert!(Opts::parse_and_load_with_system_theme(
+        args,
+        config::Config::default(),
+        Some(ResolvedTheme::Light),
+    )
+    .is_err());
+}
+
+#[test]
+fn clipboard_flag() {
+    init_test_log();
+
+    let args = Args::try_parse_from(gen_args(vec!["--clipboard"])).unwrap();
+    assert!(args.clipboard);
+    // There's no file, so relative links are resolved from the current dir
+    let clipboard_path = std::env::current_dir().unwrap().join("clipboard.md");
+    assert_eq!(
+        Opts::parse_and_load_with_system_theme(
+            args,
+            config::Config::default(),
+            Some(ResolvedTheme::Light),
+        )
+        .unwrap(),
+        Opts {
+            clipboard: true,
+            color_scheme: Some(ResolvedTheme::Light),
+            ..Opts::mostly_default(clipboard_path)
+        }
+    );
+
+    // It's either the clipboard or a file, not both
+    assert!(Args::try_parse_from(gen_args(vec!["--clipboard", "file.md"])).is_err());
+}
+
+#[test]
+fn reading_stdin() {
+    init_test_log();
+
+    let stdin_path = std::env::current_dir().unwrap().join("stdin.md");
+    let parse = |args| {
+        Opts::parse_and_load_with_system_theme(
+            Args::try_parse_from(gen_args(args)).unwrap(),
+            config::Config::default(),
+            Some(ResolvedTheme::Light),
+        )
+    };
+    assert_eq!(
+        parse(vec!["-"]).unwrap(),
+        Opts {
+            stdin: true,
+            color_scheme: Some(ResolvedTheme::Light),
+            ..Opts::mostly_default(&stdin_path)
+        }
+    );
+    assert_eq!(
+        parse(vec!["--follow", "-"]).unwrap(),
+        Opts {
+            stdin: true,
+            follow: true,
+            color_scheme: Some(ResolvedTheme::Light),
+            ..Opts::mostly_default(&stdin_path)
+        }
+    );
+    // There's nothing to follow in a file
+    assert!(parse(vec!["--follow", "file.md"]).is_err());
+}
+
+#[test]
+fn font_flags() {
+    init_test_log();
+
+    let config = config::Config::load_from_str(
+        r#"
+[font-options]
+regular-font = "Inter"
+font-size = 18.0
+heading-scales = [2.5, 2.0, 1.5, 1.25, 1.0, 0.9]
+fallback-fonts = ["Noto Sans CJK JP"]
+"#,
+    )
+    .unwrap();
+    let args = Args::try_parse_from(gen_args(vec![
+        "--monospace-font",
+        "Fira Code",
+        "--font-size",
+        "20",
+        "file.md",
+    ]))
+    .unwrap();
+    assert_eq!(
+        Opts::parse_and_load_with_system_theme(args, config, Some(ResolvedTheme::Light)).unwrap(),
+        Opts {
+            // Flags win over the config, but only for what they set
+            font_opts: FontOptions {
+                regular_font: Some("Inter".to_owned()),
+                monospace_font: Some("Fira Code".to_owned()),
+                font_size: Some(20.0),
+                heading_scales: Some([2.5, 2.0, 1.5, 1.25, 1.0, 0.9]),
+                fallback_fonts: vec!["Noto Sans CJK JP".to_owned()],
+            },
+            color_scheme: Some(ResolvedTheme::Light),
+            ..Opts::mostly_default("file.md")
+        }
+    );
+}
+
+#[test]
+fn no_cache_flag() {
+    init_test_log();
+
+    let args = Args::try_parse_from(gen_args(vec!["--no-cache", "file.md"])).unwrap();
+    assert!(args.no_cache);
+    assert_eq!(
+        Opts::parse_and_load_with_system_theme(
+            args,
+            config::Config::default(),
+            Some(ResolvedTheme::Light),
+        )
+        .unwrap(),
+        Opts {
+            no_cache: true,
+            color_scheme: Some(ResolvedTheme::Light),
+            ..Opts::mostly_default("file.md")
+        }
+    );
+}
+
+#[test]
+fn new_window_flag() {
+    init_test_log();
+
+    let args = Args::try_parse_from(gen_args(vec!["--new-window", "file.md"])).unwrap();
+    assert!(args.new_window);
+    let args = Args::try_parse_from(gen_args(vec!["file.md"])).unwrap();
+    assert!(!args.new_window);
+}
+
+#[test]
+fn vim_mode() {
+    init_test_log();
+
+    let with_vim_mode = Opts {
+        vim_mode: true,
+        keybindings: config::KeybindingsSection {
+            base: Keybindings::default().with_vim_mode(),
+            extra: None,
+        },
+        color_scheme: Some(ResolvedTheme::Light),
+        ..Opts::mostly_default("file.md")
+    };
+
+    let args = Args::try_parse_from(gen_args(vec!["--vim", "file.md"])).unwrap();
+    assert!(args.vim);
+    assert_eq!(
+        Opts::parse_and_load_with_system_theme(
+            args,
+            config::Config::default(),
+            Some(ResolvedTheme::Light),
+        )
+        .unwrap(),
+        with_vim_mode
+    );
+
+    let config = config::Config::load_from_str("vim-mode = true").unwrap();
+    assert_eq!(
+        Opts::parse_and_load_with_system_theme(
+            Args::try_parse_from(gen_args(vec!["file.md"])).unwrap(),
+            config,
+            Some(ResolvedTheme::Light),
+        )
+        .unwrap(),
+        with_vim_mode
+    );
+}
+
+#[test]
+fn serve_subcommand() {
+    init_test_log();
+
+    assert_eq!(
+        Cli::try_parse_from(gen_args(vec!["serve"])).unwrap(),
+        Cli::Serve(ServeArgs {
+            dir: PathBuf::from("."),
+            port: 8000,
+            theme: None,
+            config: None,
+        })
+    );
+    assert_eq!(
+        Cli::try_parse_from(gen_args(vec![
+            "serve", "docs", "--port", "3000", "--theme", "dark"
+        ]))
+        .unwrap(),
+        Cli::Serve(ServeArgs {
+            dir: PathBuf::from("docs"),
+            port: 3000,
+            theme: Some(ThemeType::Dark),
+            config: None,
+        })
+    );
+    // Viewing a file still works without the subcommand
+    assert!(matches!(
+        Cli::try_parse_from(gen_args(vec!["file.md"])).unwrap(),
+        Cli::View(_)
+    ));
+}
+
+#[test]
+fn bench_subcommand() {
+    init_test_log();
+
+    assert_eq!(
+        Cli::try_parse_from(gen_args(vec![
+            "bench", "file.md", "--frames", "10", "--json"
+        ]))
+        .unwrap(),
+        Cli::Bench(BenchArgs {
+            file_path: PathBuf::from("file.md"),
+            frames: 10,
+            json: true,
+            theme: None,
+            config: None,
+        })
+    );
+    // There's no point in benchmarking zero frames
+    assert!(Cli::try_parse_from(gen_args(vec!["bench", "file.md", "--frames", "0"])).is_err());
+}
+
+#[test]
+fn export_subcommand() {
+    init_test_log();
+
+    assert_eq!(
+        Cli::try_parse_from(gen_args(vec!["export", "--png", "out.png", "file.md"])).unwrap(),
+        Cli::Export(ExportArgs {
+            file_path: PathBuf::from("file.md"),
+            png: Some(PathBuf::from("out.png")),
+            pdf: None,
+            html: None,
+            slides: None,
+            width: 1280,
+            page_size: None,
+            slide_size: (1920, 1080),
+            theme: None,
+            config: None,
+        })
+    );
+    assert_eq!(
+        Cli::try_parse_from(gen_args(vec![
+            "export",
+            "--pdf",
+            "out.pdf",
+            "--page-size",
+            "letter",
+            "file.md"
+        ]))
+        .unwrap(),
+        Cli::Export(ExportArgs {
+            file_path: PathBuf::from("file.md"),
+            png: None,
+            pdf: Some(PathBuf::from("out.pdf")),
+            html: None,
+            slides: None,
+            width: 1280,
+            page_size: Some(PageSize::Letter),
+            slide_size: (1920, 1080),
+            theme: None,
+            config: None,
+        })
+    );
+    let args = Cli::try_parse_from(gen_args(vec!["export", "--html", "out.html", "file.md"]));
+    assert!(matches!(
+        args,
+        Ok(Cli::Export(ExportArgs { html: Some(_), .. }))
+    ));
+    let args = Cli::try_parse_from(gen_args(vec![
+        "export",
+        "--slides",
+        "deck",
+        "--slide-size",
+        "1280x720",
+        "file.md",
+    ]));
+    assert!(matches!(
+        args,
+        Ok(Cli::Export(ExportArgs {
+            slides: Some(_),
+            slide_size: (1280, 720),
+            ..
+        }))
+    ));
+    for bad_size in ["1280", "0x720", "widexhigh"] {
+        let args = gen_args(vec![
+            "export",
+            "--slides",
+            "deck",
+            "--slide-size",
+            bad_size,
+            "f.md",
+        ]);
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+    // Something has to get written out
+    assert!(Cli::try_parse_from(gen_args(vec!["export", "file.md"])).is_err());
+}
+
+#[test]
+fn max_content_width() {
+    init_test_log();
+
+    let config = config::Config::load_from_str("max-content-width = 900").unwrap();
+    assert_eq!(config.page_width, Some(900.0));
+
+    let args = Args::try_parse_from(gen_args(vec!["--max-content-width", "700", "file.md"]));
+    assert_eq!(args.unwrap().page_width, Some(700.0));
 }
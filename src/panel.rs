@@ -0,0 +1,183 @@
+//! Boxes of text that get drawn over or beside the document
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::diagnostics::Diagnostic;
+
+/// The most lines that a panel displays at once
+pub const MAX_LINES: usize = 12;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Panel {
+    pub title: String,
+    pub lines: Vec<String>,
+    /// The highlighted line for panels that can be picked from
+    pub selected: Option<usize>,
+}
+
+impl Panel {
+    pub fn new(title: impl Into<String>, lines: Vec<String>) -> Self {
+        Self {
+            title: title.into(),
+            lines,
+            selected: None,
+        }
+    }
+
+    pub fn with_selected(mut self, selected: usize) -> Self {
+        self.selected = (selected < self.lines.len()).then_some(selected);
+        self
+    }
+
+    pub fn diagnostics(diagnostics: &[Diagnostic]) -> Self {
+        if diagnostics.is_empty() {
+            Self::new("Diagnostics", vec!["No problems found".to_owned()])
+        } else {
+            let title = format!("Diagnostics ({})", diagnostics.len());
+            let lines = diagnostics.iter().map(ToString::to_string).collect();
+            Self::new(title, lines)
+        }
+    }
+
+    pub fn recent_files(files: &[PathBuf], selected: usize) -> Self {
+        if files.is_empty() {
+            Self::new("Recent Files", vec!["No recently opened files".to_owned()])
+        } else {
+            let lines = files
+                .iter()
+                .map(|file| file.display().to_string())
+                .collect();
+            Self::new("Recent Files", lines).with_selected(selected)
+        }
+    }
+
+    /// The lines that fit in the panel along with whether each one is selected
+    ///
+    /// Panels with a selection scroll to keep it in view while others note how many lines had to
+    /// be left off
+    pub fn visible_lines(&self) -> Vec<(String, bool)> {
+        if let Some(selected) = self.selected {
+            let start = (selected + 1).saturating_sub(MAX_LINES);
+            self.lines
+                .iter()
+                .enumerate()
+                .skip(start)
+                .take(MAX_LINES)
+                .map(|(i, line)| (line.to_owned(), i == selected))
+                .collect()
+        } else if self.lines.len() <= MAX_LINES {
+            self.lines
+                .iter()
+                .map(|line| (line.to_owned(), false))
+                .collect()
+        } else {
+            let shown = MAX_LINES - 1;
+            let mut lines: Vec<_> = self.lines[..shown]
+                .iter()
+                .map(|line| (line.to_owned(), false))
+                .collect();
+            lines.push((format!("...and {} more", self.lines.len() - shown), false));
+            lines
+        }
+    }
+}
+
+/// The markdown source of the document shown beside it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourcePane {
+    pub lines: Vec<String>,
+}
+
+impl SourcePane {
+    pub fn new(source: &str) -> Self {
+        let lines = source
+            .lines()
+            .map(|line| line.replace('\t', "    "))
+            .collect();
+        Self { lines }
+    }
+
+    /// The (fractional) zero-indexed line that lines up with the top of the document's view
+    ///
+    /// `source_lines` maps one-indexed source lines to where they start in the document. Anything
+    /// between two known lines gets interpolated so that scrolling stays smooth
+    pub fn top_line(&self, source_lines: &BTreeMap<usize, f32>, scroll_y: f32) -> f32 {
+        let mut before = None;
+        let mut after = None;
+        for (&line, &y) in source_lines {
+            if y <= scroll_y {
+                // Lines can map to the same spot (or earlier ones) e.g. within a table, so keep
+                // the furthest along
+                if before.map_or(true, |(_, before_y)| y >= before_y) {
+                    before = Some((line, y));
+                }
+            } else if before.is_some() {
+                after = Some((line, y));
+                break;
+            }
+        }
+
+        let line = match (before, after) {
+            (Some((line_a, y_a)), Some((line_b, y_b))) if line_b > line_a => {
+                let progress = (scroll_y - y_a) / (y_b - y_a);
+                line_a as f32 + progress * (line_b - line_a) as f32
+            }
+            (Some((line, _)), _) => line as f32,
+            (None, _) => 1.,
+        };
+        let last_line = self.lines.len().max(1) as f32;
+        (line - 1.).clamp(0., last_line - 1.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_lines_are_summarized() {
+        let lines: Vec<_> = (0..20).map(|i| i.to_string()).collect();
+        let panel = Panel::new("Title", lines);
+        let visible = panel.visible_lines();
+        assert_eq!(visible.len(), MAX_LINES);
+        assert_eq!(visible[MAX_LINES - 2].0, (MAX_LINES - 2).to_string());
+        assert_eq!(visible.last().unwrap().0, "...and 9 more");
+
+        let panel = Panel::new("Title", vec!["only".to_owned()]);
+        assert_eq!(panel.visible_lines(), [("only".to_owned(), false)]);
+    }
+
+    #[test]
+    fn selection_stays_in_view() {
+        let lines: Vec<_> = (0..20).map(|i| i.to_string()).collect();
+        let panel = Panel::new("Title", lines.clone()).with_selected(2);
+        let visible = panel.visible_lines();
+        assert_eq!(visible.len(), MAX_LINES);
+        assert_eq!(visible[0].0, "0");
+        assert_eq!(visible[2], ("2".to_owned(), true));
+
+        let panel = Panel::new("Title", lines.clone()).with_selected(19);
+        let visible = panel.visible_lines();
+        assert_eq!(visible.len(), MAX_LINES);
+        assert_eq!(visible.last().unwrap(), &("19".to_owned(), true));
+
+        // Out of range selections are dropped
+        assert_eq!(Panel::new("Title", lines).with_selected(20).selected, None);
+    }
+
+    #[test]
+    fn source_follows_scroll() {
+        let lines: Vec<_> = (1..=30).map(|i| format!("line {i}")).collect();
+        let pane = SourcePane::new(&lines.join("\n"));
+        let source_lines = BTreeMap::from([(1, 0.), (3, 100.), (10, 200.), (20, 400.)]);
+
+        assert_eq!(pane.top_line(&source_lines, 0.), 0.);
+        assert_eq!(pane.top_line(&source_lines, 50.), 1.);
+        assert_eq!(pane.top_line(&source_lines, 200.), 9.);
+        assert_eq!(pane.top_line(&source_lines, 300.), 14.);
+        // Past the last known line stays on it
+        assert_eq!(pane.top_line(&source_lines, 1_000.), 19.);
+        assert_eq!(pane.top_line(&BTreeMap::new(), 1_000.), 0.);
+    }
+}
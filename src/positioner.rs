@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 
 use anyhow::Context;
@@ -7,12 +7,18 @@ use taffy::Taffy;
 
 use crate::image::Image;
 use crate::text::TextSystem;
-use crate::utils::{Align, Point, Rect, Size};
-use crate::{debug_impls, Element};
+use crate::utils::{Align, Float, Point, Rect, Size};
+use crate::{debug_impls, toc, Element};
 
 pub const DEFAULT_PADDING: f32 = 5.;
 pub const DEFAULT_MARGIN: f32 = 100.;
 
+/// How far the page gets pushed in from both sides to center it when the document is wider than
+/// `page_width`. The usual [`DEFAULT_MARGIN`] goes on top of this
+pub fn centering(doc_width: f32, page_width: f32) -> f32 {
+    (doc_width - page_width).max(0.) / 2.
+}
+
 #[derive(Debug)]
 pub struct Positioned<T> {
     pub inner: T,
@@ -45,7 +51,27 @@ pub struct Positioner {
     pub hidpi_scale: f32,
     pub page_width: f32,
     pub anchors: HashMap<String, f32>,
+    /// Where each line of the markdown source starts in the document for lines that map to text
+    pub source_lines: BTreeMap<usize, f32>,
+    /// Gives the right half of the screen to a pane beside the document
+    pub side_pane: bool,
+    /// Gives the right edge of the screen to the table of contents
+    pub toc_pane: bool,
     pub taffy: Taffy,
+    /// The floating image that text is currently going beside
+    float: Option<ActiveFloat>,
+}
+
+/// Text goes beside a floating image, one block at a time, until it makes it past the image. Lines
+/// of a paragraph that started beside the image stay just as narrow below it
+#[derive(Debug)]
+struct ActiveFloat {
+    side: Float,
+    /// The width taken up by the image along with the gap next to it
+    width: f32,
+    bottom: f32,
+    /// Where the next block beside the image goes. `reserved_height` is already past the image
+    next_y: f32,
 }
 
 impl Positioner {
@@ -58,7 +84,74 @@ impl Positioner {
             page_width,
             screen_size,
             anchors: HashMap::new(),
+            source_lines: BTreeMap::new(),
+            side_pane: false,
+            toc_pane: false,
             taffy,
+            float: None,
+        }
+    }
+
+    /// Starts over from the top of the document
+    pub fn reset(&mut self, zoom: f32) {
+        self.reserved_height = DEFAULT_PADDING * self.hidpi_scale * zoom;
+        self.float = None;
+    }
+
+    /// Makes room for an element that was just positioned
+    pub fn reserve(&mut self, element: &Positioned<Element>, zoom: f32) -> anyhow::Result<()> {
+        let bounds = element
+            .bounds
+            .as_ref()
+            .context("Element didn't have bounds")?;
+        let bottom = bounds.max().1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
+        let is_float = matches!(&element.inner, Element::Image(image) if image.float.is_some());
+        match &mut self.float {
+            Some(float) if !is_float => {
+                float.next_y = bottom;
+                self.reserved_height = self.reserved_height.max(bottom);
+                if float.next_y >= float.bottom {
+                    self.float = None;
+                }
+            }
+            _ => self.reserved_height = bottom,
+        }
+        Ok(())
+    }
+
+    /// Moves everything that comes next below the floating image, if there is one
+    fn clear_float(&mut self) {
+        self.float = None;
+    }
+
+    /// Where the next element goes along with how much room a floating image takes on its left
+    /// and right
+    fn flow(&self) -> (f32, f32, f32) {
+        match &self.float {
+            Some(float) => match float.side {
+                Float::Left => (float.next_y, float.width, 0.),
+                Float::Right => (float.next_y, 0., float.width),
+            },
+            None => (self.reserved_height, 0., 0.),
+        }
+    }
+
+    /// The width available to the document
+    pub fn doc_width(&self) -> f32 {
+        let width = self.screen_size.0 - self.toc_width();
+        if self.side_pane {
+            width / 2.
+        } else {
+            width
+        }
+    }
+
+    /// The width taken up by the table of contents on the right edge of the screen
+    pub fn toc_width(&self) -> f32 {
+        if self.toc_pane {
+            (toc::WIDTH * self.hidpi_scale).min(self.screen_size.0 / 3.)
+        } else {
+            0.
         }
     }
 
@@ -69,64 +162,112 @@ impl Positioner {
         element: &mut Positioned<Element>,
         zoom: f32,
     ) -> anyhow::Result<()> {
-        let centering = (self.screen_size.0 - self.page_width).max(0.) / 2.;
+        let doc_width = self.doc_width();
+        let centering = centering(doc_width, self.page_width);
 
         let bounds = match &mut element.inner {
             Element::TextBox(text_box) => {
+                // Block backgrounds span the whole page, so they can't go beside anything
+                if text_box.is_code_block || text_box.is_quote_block.is_some() {
+                    self.clear_float();
+                }
+                let (y, left, right) = self.flow();
+                text_box.float_inset = right;
                 let indent = text_box.indent;
-                let pos = (DEFAULT_MARGIN + indent + centering, self.reserved_height);
+                let pos = (DEFAULT_MARGIN + indent + centering + left, y);
 
                 let size = text_box.size(
                     text_system,
                     (
-                        (self.screen_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
+                        (doc_width - pos.0 - DEFAULT_MARGIN - centering - right).max(0.),
                         f32::INFINITY,
                     ),
                     zoom,
                 );
 
-                if let Some(ref anchor_name) = text_box.is_anchor {
+                for anchor_name in text_box.is_anchor.iter().chain(&text_box.other_anchors) {
                     let _ = self.anchors.insert(anchor_name.clone(), pos.1);
                 }
+                if let Some(line) = text_box.source_line {
+                    let _ = self.source_lines.insert(line, pos.1);
+                }
 
                 Rect::new(pos, size)
             }
             Element::Spacer(spacer) => Rect::new(
-                (0., self.reserved_height),
+                (0., self.flow().0),
                 (0., spacer.space * self.hidpi_scale * zoom),
             ),
             Element::Image(image) => {
+                // Floats don't stack up beside each other
+                self.clear_float();
+                let screen_size = (doc_width.min(self.page_width), self.screen_size.1);
                 let size = image
-                    .size(
-                        (self.screen_size.0.min(self.page_width), self.screen_size.1),
-                        zoom,
-                    )
+                    .size(screen_size, zoom)
+                    .or_else(|| image.placeholder_size(text_system, screen_size, zoom))
                     .unwrap_or_default();
-                match image.is_aligned {
-                    Some(Align::Center) => Rect::new(
-                        (self.screen_size.0 / 2. - size.0 / 2., self.reserved_height),
-                        size,
-                    ),
-                    _ => Rect::new((DEFAULT_MARGIN + centering, self.reserved_height), size),
+                if let Some(ref anchor_name) = image.is_anchor {
+                    let _ = self
+                        .anchors
+                        .insert(anchor_name.clone(), self.reserved_height);
+                }
+                let y = self.reserved_height;
+                if let Some(line) = image.source_line {
+                    let _ = self.source_lines.insert(line, y);
+                }
+                if let Some(side) = image.float {
+                    let gap = 2. * DEFAULT_PADDING * self.hidpi_scale * zoom;
+                    self.float = Some(ActiveFloat {
+                        side,
+                        width: size.0 + gap,
+                        bottom: y + size.1,
+                        next_y: y,
+                    });
+                }
+                match (image.float, image.is_aligned) {
+                    (Some(Float::Right), _) => {
+                        Rect::new((doc_width - DEFAULT_MARGIN - centering - size.0, y), size)
+                    }
+                    (None, Some(Align::Center)) => {
+                        Rect::new((doc_width / 2. - size.0 / 2., y), size)
+                    }
+                    _ => Rect::new((DEFAULT_MARGIN + centering, y), size),
                 }
             }
             Element::Table(table) => {
+                self.clear_float();
                 let pos = (DEFAULT_MARGIN + centering, self.reserved_height);
                 let layout = table.layout(
                     text_system,
                     &mut self.taffy,
                     (
-                        self.screen_size.0 - pos.0 - DEFAULT_MARGIN - centering,
+                        doc_width - pos.0 - DEFAULT_MARGIN - centering,
                         f32::INFINITY,
                     ),
                     zoom,
                 )?;
+                // Each row is a line of its own in the source
+                let cells = table.headers.iter().zip(&layout.headers).chain(
+                    table
+                        .rows
+                        .iter()
+                        .zip(&layout.rows)
+                        .flat_map(|(row, layouts)| row.iter().zip(layouts)),
+                );
+                for (cell, cell_layout) in cells {
+                    if let Some(line) = cell.source_line {
+                        let _ = self
+                            .source_lines
+                            .insert(line, pos.1 + cell_layout.location.y);
+                    }
+                }
                 Rect::new(
                     (DEFAULT_MARGIN + centering, self.reserved_height),
                     layout.size,
                 )
             }
             Element::Row(row) => {
+                self.clear_float();
                 let mut reserved_width = DEFAULT_MARGIN + centering;
                 let mut inner_reserved_height: f32 = 0.;
                 let mut max_height: f32 = 0.;
@@ -142,7 +283,7 @@ impl Positioner {
                         + DEFAULT_PADDING * self.hidpi_scale * zoom
                         + element_bounds.size.0;
                     // Row would be too long with this element so add another line
-                    if target_width > self.screen_size.0 - DEFAULT_MARGIN - centering {
+                    if target_width > doc_width - DEFAULT_MARGIN - centering {
                         max_width = max_width.max(reserved_width);
                         reserved_width = DEFAULT_MARGIN
                             + centering
@@ -170,38 +311,31 @@ impl Positioner {
                 )
             }
             Element::Section(section) => {
-                let mut section_bounds =
-                    Rect::new((DEFAULT_MARGIN + centering, self.reserved_height), (0., 0.));
+                self.clear_float();
+                let top = self.reserved_height;
+                let mut width: f32 = 0.;
                 if let Some(ref mut summary) = *section.summary {
                     self.position(text_system, summary, zoom)?;
-                    let element_size = summary
-                        .bounds
-                        .as_mut()
-                        .context("Element didn't have bounds")?
-                        .size;
-                    self.reserved_height +=
-                        element_size.1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                    section_bounds.size.1 +=
-                        element_size.1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                    section_bounds.size.0 = section_bounds.size.0.max(element_size.0)
+                    self.reserve(summary, zoom)?;
+                    width = width.max(summary.bounds.as_ref().map_or(0., |b| b.size.0));
                 }
+                let summary_bottom = self.reserved_height;
+                let hidden = *section.hidden.borrow();
                 for element in &mut section.elements {
                     self.position(text_system, element, zoom)?;
-                    let element_size = element
-                        .bounds
-                        .as_mut()
-                        .context("Element didn't have bounds")?
-                        .size;
-                    self.reserved_height +=
-                        element_size.1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                    if !*section.hidden.borrow() {
-                        section_bounds.size.1 +=
-                            element_size.1 + DEFAULT_PADDING * self.hidpi_scale * zoom;
-                        section_bounds.size.0 = section_bounds.size.0.max(element_size.0)
+                    self.reserve(element, zoom)?;
+                    if !hidden {
+                        width = width.max(element.bounds.as_ref().map_or(0., |b| b.size.0));
                     }
                 }
-                self.reserved_height = section_bounds.pos.1;
-                section_bounds
+                self.clear_float();
+                let bottom = if hidden {
+                    summary_bottom
+                } else {
+                    self.reserved_height
+                };
+                self.reserved_height = top;
+                Rect::new((DEFAULT_MARGIN + centering, top), (width, bottom - top))
             }
         };
         element.bounds = Some(bounds);
@@ -215,17 +349,11 @@ impl Positioner {
         elements: &mut [Positioned<Element>],
         zoom: f32,
     ) -> anyhow::Result<()> {
-        self.reserved_height = DEFAULT_PADDING * self.hidpi_scale * zoom;
+        self.reset(zoom);
 
         for element in elements {
             self.position(text_system, element, zoom)?;
-            self.reserved_height += DEFAULT_PADDING * self.hidpi_scale * zoom
-                + element
-                    .bounds
-                    .as_ref()
-                    .context("Element didn't have bounds")?
-                    .size
-                    .1;
+            self.reserve(element, zoom)?;
         }
         Ok(())
     }
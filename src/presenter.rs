@@ -0,0 +1,243 @@
+//! A second window for whoever is giving a slideshow, with the current slide's speaker notes, a
+//! preview of the next slide, and how long it's been going for
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::color::{native_color, Theme};
+use crate::image::{Image, ImageData, ImageSize};
+use crate::opts::FontOptions;
+use crate::positioner::{Positioned, Spacer, DEFAULT_MARGIN};
+use crate::renderer::{Frame, Renderer};
+use crate::text::{Text, TextBox};
+use crate::utils::{Align, Size};
+use crate::Element;
+
+use anyhow::Context;
+use winit::event::VirtualKeyCode;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::{Window, WindowBuilder};
+
+const HEADER_FONT_SIZE: f32 = 28.;
+const NOTES_FONT_SIZE: f32 = 22.;
+/// How much of the window's height the preview can take up, leaving the rest for the notes
+const PREVIEW_HEIGHT: f32 = 0.5;
+
+/// What the presenter was last filled in for: the slide, how many slides there are, and the size
+/// and zoom that the main window draws them at
+pub type View = (usize, usize, Size, f32);
+
+pub struct Presenter {
+    pub window: Window,
+    renderer: Renderer,
+    elements: Vec<Positioned<Element>>,
+    started: Instant,
+    /// How many seconds the timer was last drawn with
+    shown_secs: u64,
+    view: Option<View>,
+    counter: String,
+    /// The size of the preview's image, which gets fit to the window
+    preview: Option<(u32, u32)>,
+}
+
+impl Presenter {
+    /// Opens on a different monitor from the main window when there is one
+    pub fn open<T>(
+        target: &EventLoopWindowTarget<T>,
+        main: &Window,
+        theme: Theme,
+        scale: Option<f32>,
+        font_opts: FontOptions,
+    ) -> anyhow::Result<Self> {
+        let current = main.current_monitor();
+        let other = target
+            .available_monitors()
+            .find(|monitor| Some(monitor) != current.as_ref());
+        let mut builder = WindowBuilder::new().with_title("Inlyne - Presenter");
+        if let Some(monitor) = other {
+            builder = builder
+                .with_position(monitor.position())
+                .with_maximized(true);
+        }
+        let window = builder
+            .build(target)
+            .context("Failed to open the presenter window")?;
+        let hidpi_scale = scale.unwrap_or(window.scale_factor() as f32);
+        let renderer = pollster::block_on(Renderer::new(
+            &window,
+            theme,
+            hidpi_scale,
+            f32::MAX,
+            font_opts,
+        ))?;
+        Ok(Self {
+            window,
+            renderer,
+            elements: Vec::new(),
+            started: Instant::now(),
+            shown_secs: 0,
+            view: None,
+            counter: String::new(),
+            preview: None,
+        })
+    }
+
+    /// Whether it's already showing the notes and preview for `view`
+    pub fn is_showing(&self, view: View) -> bool {
+        self.view == Some(view)
+    }
+
+    /// Makes the notes and preview get filled in again, like after an image loaded
+    pub fn invalidate(&mut self) {
+        self.view = None;
+    }
+
+    pub fn show(
+        &mut self,
+        view: View,
+        counter: String,
+        notes: &[String],
+        preview: Option<::image::RgbaImage>,
+    ) {
+        self.view = Some(view);
+        self.counter = counter;
+        self.preview = preview.as_ref().map(::image::RgbaImage::dimensions);
+        let color = native_color(
+            self.renderer.theme.text_color,
+            &self.renderer.surface_format,
+        );
+        let hidpi_scale = self.renderer.hidpi_scale;
+
+        let header = self.header();
+        self.elements.clear();
+        self.elements.push(Positioned::new(header));
+        self.elements.push(Positioned::new(Spacer::invisible()));
+        match preview {
+            Some(preview) => {
+                let image_data = ImageData::new(preview, false);
+                let image =
+                    Image::from_image_data(Arc::new(Mutex::new(Some(image_data))), hidpi_scale)
+                        .with_align(Align::Center);
+                self.elements.push(Positioned::new(image));
+            }
+            None => {
+                let text = Text::new("Last slide".to_owned(), hidpi_scale, color);
+                let mut text_box = TextBox::new(vec![text], hidpi_scale);
+                text_box.font_size = NOTES_FONT_SIZE;
+                text_box.set_align(Align::Center);
+                self.elements.push(Positioned::new(text_box));
+            }
+        }
+        self.elements.push(Positioned::new(Spacer::visible()));
+        for note in notes {
+            for paragraph in note.split("\n\n") {
+                let text = paragraph.split_whitespace().collect::<Vec<_>>().join(" ");
+                let mut text_box =
+                    TextBox::new(vec![Text::new(text, hidpi_scale, color)], hidpi_scale);
+                text_box.font_size = NOTES_FONT_SIZE;
+                self.elements.push(Positioned::new(text_box));
+                self.elements.push(Positioned::new(Spacer::invisible()));
+            }
+        }
+        self.renderer.set_scroll_y(0.);
+        self.window.request_redraw();
+    }
+
+    fn header(&mut self) -> TextBox {
+        let color = native_color(
+            self.renderer.theme.text_color,
+            &self.renderer.surface_format,
+        );
+        let hidpi_scale = self.renderer.hidpi_scale;
+        self.shown_secs = self.started.elapsed().as_secs();
+        let text = format!("{}    {}", self.counter, elapsed_text(self.shown_secs));
+        let mut text_box = TextBox::new(
+            vec![Text::new(text, hidpi_scale, color).make_bold(true)],
+            hidpi_scale,
+        );
+        text_box.font_size = HEADER_FONT_SIZE;
+        text_box.set_align(Align::Center);
+        text_box
+    }
+
+    /// When the timer is due to be drawn with the next second
+    pub fn next_tick(&self) -> Instant {
+        self.started + Duration::from_secs(self.shown_secs + 1)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.renderer.resize(width, height);
+            self.window.request_redraw();
+        }
+    }
+
+    pub fn scroll(&mut self, num_pixels: f32) {
+        self.renderer
+            .set_scroll_y(self.renderer.scroll_y - num_pixels);
+        self.window.request_redraw();
+    }
+
+    /// Draws it with the timer brought up to date
+    pub fn redraw(&mut self) -> anyhow::Result<()> {
+        let header = self.header();
+        if let Some(first) = self.elements.first_mut() {
+            first.inner = header.into();
+        }
+        let preview_size = self.preview.map(|dimensions| self.preview_size(dimensions));
+        for element in &mut self.elements {
+            if let Element::Image(image) = &mut element.inner {
+                image.size = preview_size;
+            }
+        }
+        self.renderer.reposition(&mut self.elements)?;
+        if self.renderer.redraw(&mut self.elements)? == Frame::Skipped {
+            self.window.request_redraw();
+        }
+        Ok(())
+    }
+
+    /// As wide as it can be while fitting in its share of the window's height
+    fn preview_size(&self, (width, height): (u32, u32)) -> ImageSize {
+        let (screen_width, screen_height) = self.renderer.screen_size();
+        let fit = ((screen_width - 2. * DEFAULT_MARGIN) / width as f32)
+            .min(screen_height * PREVIEW_HEIGHT / height as f32);
+        ImageSize::width((width as f32 * fit / self.renderer.hidpi_scale) as u32)
+    }
+}
+
+/// Which way the slideshow gets stepped by a key pressed in the presenter window
+pub fn step_for(key: VirtualKeyCode) -> Option<bool> {
+    match key {
+        VirtualKeyCode::Right
+        | VirtualKeyCode::Down
+        | VirtualKeyCode::PageDown
+        | VirtualKeyCode::Space => Some(true),
+        VirtualKeyCode::Left
+        | VirtualKeyCode::Up
+        | VirtualKeyCode::PageUp
+        | VirtualKeyCode::Back => Some(false),
+        _ => None,
+    }
+}
+
+fn elapsed_text(secs: u64) -> String {
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{mins:02}:{secs:02}")
+    } else {
+        format!("{mins:02}:{secs:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timer() {
+        assert_eq!(elapsed_text(59), "00:59");
+        assert_eq!(elapsed_text(61), "01:01");
+        assert_eq!(elapsed_text(3_723), "1:02:03");
+    }
+}
@@ -0,0 +1,47 @@
+//! Printing by way of a temporary PDF
+//!
+//! The document gets paginated just like `inlyne export --pdf` would do it, and the PDF is then
+//! handed off to the platform's own printing. The PDF is left in the temp dir since the program
+//! that prints it can outlive us
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use crate::dialog;
+use crate::export;
+use crate::opts::ExportOpts;
+
+use anyhow::Context;
+
+/// Prints `md` in the background since laying out every page can take a bit
+pub fn print(opts: ExportOpts, md: String) {
+    thread::spawn(move || {
+        if let Err(err) = print_blocking(&opts, &md) {
+            tracing::warn!("Failed printing: {err:#}");
+        }
+    });
+}
+
+fn print_blocking(opts: &ExportOpts, md: &str) -> anyhow::Result<()> {
+    let pdf = pdf_path(opts);
+    let dir = pdf.parent().expect("Path is within the temp dir");
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Couldn't create the directory '{}'", dir.display()))?;
+    export::pdf::write_markdown(opts, md, &pdf)?;
+    tracing::info!("Printing '{}'", pdf.display());
+    dialog::print_pdf(&pdf)
+}
+
+/// Named after the document so that it shows up sensibly in the print queue
+fn pdf_path(opts: &ExportOpts) -> PathBuf {
+    let stem = opts
+        .file_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "document".to_owned());
+    env::temp_dir()
+        .join("inlyne-print")
+        .join(format!("{stem}.pdf"))
+}
@@ -0,0 +1,455 @@
+//! A palette for fuzzy finding markdown files in the current workspace
+
+use std::collections::VecDeque;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::panel::Panel;
+use crate::utils::is_markdown;
+
+/// Stop looking for files after this many
+const MAX_FILES: usize = 10_000;
+/// Stop looking after visiting this many entries, so that opening the palette somewhere huge like a
+/// home directory doesn't keep churning through it
+const MAX_ENTRIES: usize = 100_000;
+/// How many directories deep to look below the workspace's root
+const MAX_DEPTH: usize = 10;
+/// Directories that are large and practically never contain docs worth opening
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "__pycache__", "venv"];
+/// How often the files found so far get handed over to the palette
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub struct QuickOpen {
+    root: PathBuf,
+    /// Paths relative to `root`
+    files: Vec<PathBuf>,
+    /// Batches of files from the search while it's still going. Dropping it stops the search
+    found: Option<Receiver<Vec<PathBuf>>>,
+    query: String,
+    /// Indices into `files` ordered from best to worst match
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl QuickOpen {
+    /// Searches the workspace of `current_file` for markdown files in the background, calling
+    /// `notify` whenever there are more for [`QuickOpen::receive()`] to pick up
+    pub fn new(current_file: &Path, notify: impl Fn() + Send + 'static) -> Self {
+        let root = workspace_root(current_file);
+        let (sender, receiver) = mpsc::channel();
+        let search_root = root.clone();
+        thread::spawn(move || {
+            let mut batch = Vec::new();
+            let mut count = 0;
+            let mut last_sent = Instant::now();
+            markdown_files(&search_root, |file| {
+                batch.push(file);
+                count += 1;
+                if last_sent.elapsed() >= BATCH_INTERVAL {
+                    last_sent = Instant::now();
+                    // The palette was closed
+                    if sender.send(mem::take(&mut batch)).is_err() {
+                        return false;
+                    }
+                    notify();
+                }
+                count < MAX_FILES
+            });
+            if count >= MAX_FILES {
+                tracing::info!("Stopped looking for files after finding {}", MAX_FILES);
+            }
+            if sender.send(batch).is_ok() {
+                drop(sender);
+                notify();
+            }
+        });
+
+        let mut quick_open = Self::with_files(root, Vec::new());
+        quick_open.found = Some(receiver);
+        quick_open
+    }
+
+    fn with_files(root: PathBuf, files: Vec<PathBuf>) -> Self {
+        let mut quick_open = Self {
+            root,
+            files,
+            found: None,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        quick_open.update_matches();
+        quick_open
+    }
+
+    /// Takes in the files that the search found since the last call, keeping the same file
+    /// selected
+    ///
+    /// Returns whether anything changed
+    pub fn receive(&mut self) -> bool {
+        let Some(found) = &self.found else {
+            return false;
+        };
+        let mut changed = false;
+        loop {
+            match found.try_recv() {
+                Ok(files) => {
+                    changed |= !files.is_empty();
+                    self.files.extend(files);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.found = None;
+                    changed = true;
+                    break;
+                }
+            }
+        }
+
+        if changed {
+            let selected = self.matches.get(self.selected).copied();
+            self.update_matches();
+            if let Some(selected) = selected {
+                self.selected = self
+                    .matches
+                    .iter()
+                    .position(|&index| index == selected)
+                    .unwrap_or(0);
+            }
+        }
+        changed
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.update_matches();
+    }
+
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.update_matches();
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1));
+    }
+
+    /// The full path of the currently selected file
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        let &index = self.matches.get(self.selected)?;
+        Some(self.root.join(&self.files[index]))
+    }
+
+    pub fn panel(&self) -> Panel {
+        let title = format!("Open file: {}_", self.query);
+        if self.matches.is_empty() {
+            let line = if self.found.is_some() {
+                "Looking for files..."
+            } else {
+                "No matching files"
+            };
+            Panel::new(title, vec![line.to_owned()])
+        } else {
+            let lines = self
+                .matches
+                .iter()
+                .map(|&index| self.files[index].display().to_string())
+                .collect();
+            Panel::new(title, lines).with_selected(self.selected)
+        }
+    }
+
+    fn update_matches(&mut self) {
+        let mut scored: Vec<_> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(index, file)| {
+                let score = fuzzy_score(&self.query, &file.to_string_lossy())?;
+                Some((score, index))
+            })
+            .collect();
+        // Best score first with shorter paths winning ties
+        scored.sort_by(|(score_a, a), (score_b, b)| {
+            let (file_a, file_b) = (&self.files[*a], &self.files[*b]);
+            score_b
+                .cmp(score_a)
+                .then_with(|| file_a.as_os_str().len().cmp(&file_b.as_os_str().len()))
+                .then_with(|| file_a.cmp(file_b))
+        });
+
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+}
+
+/// The root of the repo containing `file` or the file's directory when it isn't in one
+fn workspace_root(file: &Path) -> PathBuf {
+    let file = file.canonicalize().unwrap_or_else(|_| file.to_owned());
+    let dir = file.parent().unwrap_or(&file);
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists() || ancestor.join(".hg").exists())
+        .unwrap_or(dir)
+        .to_owned()
+}
+
+/// Passes along the markdown files under `root` relative to it, until `found` returns `false`
+///
+/// Hidden entries, well known dependency and build directories, and the directories that the
+/// root's `.gitignore` lists get skipped
+fn markdown_files(root: &Path, mut found: impl FnMut(PathBuf) -> bool) {
+    let ignored = ignored_dirs(root);
+    let mut visited = 0;
+    let mut dirs = VecDeque::from([(PathBuf::new(), 0)]);
+    while let Some((relative_dir, depth)) = dirs.pop_front() {
+        let Ok(entries) = fs::read_dir(root.join(&relative_dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            visited += 1;
+            if visited > MAX_ENTRIES {
+                tracing::info!("Stopped looking for files after visiting {}", MAX_ENTRIES);
+                return;
+            }
+
+            let name = entry.file_name();
+            let name_str = name.to_string_lossy();
+            if name_str.starts_with('.') {
+                continue;
+            }
+
+            let relative = relative_dir.join(&name);
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                let skipped = SKIPPED_DIRS.contains(&name_str.as_ref())
+                    || ignored.iter().any(|pattern| pattern.matches(&relative));
+                if depth < MAX_DEPTH && !skipped {
+                    dirs.push_back((relative, depth + 1));
+                }
+            } else if is_markdown(&relative) && !found(relative) {
+                return;
+            }
+        }
+    }
+}
+
+/// A plain directory pattern from a `.gitignore`
+#[derive(Debug, PartialEq)]
+enum IgnoredDir {
+    /// Matches directories with this name anywhere
+    Name(String),
+    /// Matches the one directory at this path relative to the root
+    Path(PathBuf),
+}
+
+impl IgnoredDir {
+    fn matches(&self, relative: &Path) -> bool {
+        match self {
+            Self::Name(name) => relative.file_name().map_or(false, |n| n == name.as_str()),
+            Self::Path(path) => relative == path,
+        }
+    }
+}
+
+/// The directories listed in the `.gitignore` at `root`. Patterns with wildcards or negations are
+/// left out since skipping a directory is only ever an optimization here
+fn ignored_dirs(root: &Path) -> Vec<IgnoredDir> {
+    let Ok(gitignore) = fs::read_to_string(root.join(".gitignore")) else {
+        return Vec::new();
+    };
+    gitignore
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(['#', '!']))
+        .filter(|line| !line.contains(['*', '?', '[', '\\']))
+        .filter_map(|line| {
+            let trimmed = line.trim_end_matches('/');
+            if trimmed.is_empty() {
+                None
+            } else if trimmed.contains('/') {
+                Some(IgnoredDir::Path(PathBuf::from(
+                    trimmed.trim_start_matches('/'),
+                )))
+            } else {
+                Some(IgnoredDir::Name(trimmed.to_owned()))
+            }
+        })
+        .collect()
+}
+
+/// Scores how well `query` matches `candidate` or `None` if it doesn't match at all
+///
+/// Every character of the query has to show up in order (ignoring case). Runs of consecutive
+/// characters, matches at the start of a word, and matches within the file name score higher
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let file_name_start = candidate.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    let mut score = 0;
+    let mut prev_match = None;
+    let mut chars = candidate.char_indices();
+    let mut prev_char = None;
+
+    for query_char in query.chars().filter(|c| !c.is_whitespace()) {
+        let query_char = query_char.to_ascii_lowercase();
+        loop {
+            let (index, c) = chars.next()?;
+            let before = prev_char.replace(c);
+            if c.to_ascii_lowercase() != query_char {
+                continue;
+            }
+
+            score += 1;
+            if prev_match.map_or(false, |prev| prev + 1 == index) {
+                score += 5;
+            }
+            if before.map_or(true, |b: char| !b.is_alphanumeric()) {
+                score += 8;
+            }
+            if index >= file_name_start {
+                score += 2;
+            }
+            prev_match = Some(index);
+            break;
+        }
+    }
+
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched_files(quick_open: &QuickOpen) -> Vec<String> {
+        quick_open
+            .matches
+            .iter()
+            .map(|&i| quick_open.files[i].to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        assert_eq!(fuzzy_score("", "anything.md"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "README.md"), None);
+        assert_eq!(fuzzy_score("mr", "README.md"), None);
+        assert!(fuzzy_score("rdme", "README.md").is_some());
+
+        let files = [
+            "docs/getting-started.md",
+            "README.md",
+            "src/readme_notes.md",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+        let mut quick_open = QuickOpen::with_files(PathBuf::from("/root"), files);
+        // No query lists everything, shortest first
+        assert_eq!(
+            matched_files(&quick_open),
+            [
+                "README.md",
+                "src/readme_notes.md",
+                "docs/getting-started.md"
+            ]
+        );
+
+        for c in "gs".chars() {
+            quick_open.push_char(c);
+        }
+        assert_eq!(matched_files(&quick_open), ["docs/getting-started.md"]);
+        assert_eq!(
+            quick_open.selected_path().unwrap(),
+            Path::new("/root/docs/getting-started.md")
+        );
+
+        quick_open.pop_char();
+        quick_open.pop_char();
+        for c in "readme".chars() {
+            quick_open.push_char(c);
+        }
+        assert_eq!(
+            matched_files(&quick_open),
+            ["README.md", "src/readme_notes.md"]
+        );
+        quick_open.select_next();
+        quick_open.select_next();
+        assert_eq!(
+            quick_open.selected_path().unwrap(),
+            Path::new("/root/src/readme_notes.md")
+        );
+    }
+
+    #[test]
+    fn finds_markdown_in_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let too_deep = "a/".repeat(MAX_DEPTH + 1);
+        for dir in [
+            ".git",
+            ".hidden",
+            "docs/nested",
+            "node_modules/pkg",
+            "out/docs",
+            "docs/build",
+            "site/build",
+            &too_deep,
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(
+            root.join(".gitignore"),
+            "# Generated\n/out/\nbuild\n*.log\n",
+        )
+        .unwrap();
+        for file in [
+            "README.md",
+            "main.rs",
+            ".hidden/secret.md",
+            "docs/nested/deep.markdown",
+            "node_modules/pkg/README.md",
+            "out/docs/index.md",
+            "docs/build/api.md",
+            "site/build/index.md",
+            &format!("{too_deep}lost.md"),
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let current = root.join("docs").join("nested").join("deep.markdown");
+        assert_eq!(workspace_root(&current), root);
+        let mut files = Vec::new();
+        markdown_files(&root, |file| {
+            files.push(file);
+            true
+        });
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                Path::new("README.md").to_owned(),
+                Path::new("docs").join("nested").join("deep.markdown"),
+            ]
+        );
+
+        let mut quick_open = QuickOpen::new(&current, || {});
+        while quick_open.found.is_some() {
+            quick_open.receive();
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            matched_files(&quick_open),
+            ["README.md", "docs/nested/deep.markdown"]
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Keeps track of recently opened files across runs, along with where each was left off and the
+//! window that they were viewed in
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// The most files that get remembered
+pub const MAX_FILES: usize = 10;
+
+/// Where the window was and how big it was, in physical pixels
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowGeometry {
+    /// Some platforms, like Wayland, don't let windows know or pick where they are
+    pub position: Option<(i32, i32)>,
+    pub size: (u32, u32),
+    #[serde(default)]
+    pub maximized: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct RecentFiles {
+    files: Vec<PathBuf>,
+    /// The zoom level that each of the files was last viewed at when it wasn't the default
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    zooms: BTreeMap<PathBuf, f32>,
+    /// How far down each of the files was scrolled when it was last viewed
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    scroll_ys: BTreeMap<PathBuf, f32>,
+    /// The window's last size and position, shared by all of the files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    window: Option<WindowGeometry>,
+    /// Where the list gets persisted to. `None` keeps everything in memory
+    #[serde(skip)]
+    state_path: Option<PathBuf>,
+}
+
+impl RecentFiles {
+    /// Loads the list from the user's state directory falling back to an empty list
+    pub fn load() -> Self {
+        let Some(state_path) = dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("inlyne").join("recent_files.json"))
+        else {
+            tracing::warn!("Failed to find a directory to store recent files in");
+            return Self::default();
+        };
+
+        Self::load_from(state_path.clone()).unwrap_or_else(|err| {
+            tracing::warn!(
+                "Failed loading recent files. Starting fresh. Error: {}",
+                err
+            );
+            Self {
+                state_path: Some(state_path),
+                ..Self::default()
+            }
+        })
+    }
+
+    fn load_from(state_path: PathBuf) -> anyhow::Result<Self> {
+        let mut recent = if state_path.exists() {
+            let contents = fs::read_to_string(&state_path)?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid recent files at {}", state_path.display()))?
+        } else {
+            Self::default()
+        };
+        recent.state_path = Some(state_path);
+
+        Ok(recent)
+    }
+
+    /// The remembered files from most to least recently opened
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// The most recently opened file that still exists
+    pub fn most_recent(&self) -> Option<&Path> {
+        self.files
+            .iter()
+            .map(PathBuf::as_path)
+            .find(|path| path.exists())
+    }
+
+    /// Moves `path` to the front of the list without persisting it
+    pub fn push(&mut self, path: &Path) {
+        let Ok(path) = path.canonicalize() else {
+            return;
+        };
+        // The state file is JSON, so it can only hold paths that are valid UTF-8
+        if path.to_str().is_none() {
+            return;
+        }
+
+        self.files.retain(|file| file != &path);
+        self.files.insert(0, path);
+        self.files.truncate(MAX_FILES);
+        self.zooms.retain(|path, _| self.files.contains(path));
+        self.scroll_ys.retain(|path, _| self.files.contains(path));
+    }
+
+    /// The zoom level that `path` was last viewed at
+    pub fn zoom(&self, path: &Path) -> Option<f32> {
+        let path = path.canonicalize().ok()?;
+        self.zooms.get(&path).copied()
+    }
+
+    /// How far down `path` was scrolled when it was last viewed
+    pub fn scroll_y(&self, path: &Path) -> Option<f32> {
+        let path = path.canonicalize().ok()?;
+        self.scroll_ys.get(&path).copied()
+    }
+
+    /// Remembers how far down `path` is scrolled along with its zoom level, unless the zoom is
+    /// `None`. Only files that are in the list get remembered
+    pub fn record_view(&mut self, path: &Path, zoom: Option<f32>, scroll_y: f32) {
+        let Ok(path) = path.canonicalize() else {
+            return;
+        };
+        let unchanged_zoom = zoom.map_or(true, |zoom| {
+            self.zooms.get(&path).copied().unwrap_or(1.) == zoom
+        });
+        if unchanged_zoom && self.scroll_ys.get(&path).copied().unwrap_or(0.) == scroll_y {
+            return;
+        }
+
+        self.reload();
+        if !self.files.contains(&path) {
+            return;
+        }
+        if let Some(zoom) = zoom {
+            if zoom == 1. {
+                self.zooms.remove(&path);
+            } else {
+                self.zooms.insert(path.clone(), zoom);
+            }
+        }
+        if scroll_y == 0. {
+            self.scroll_ys.remove(&path);
+        } else {
+            self.scroll_ys.insert(path, scroll_y);
+        }
+        if let Err(err) = self.save() {
+            tracing::warn!("Failed saving recent files: {}", err);
+        }
+    }
+
+    /// The window's size and position from the last time that it was closed
+    pub fn window(&self) -> Option<WindowGeometry> {
+        self.window
+    }
+
+    pub fn record_window(&mut self, window: WindowGeometry) {
+        if self.window == Some(window) {
+            return;
+        }
+
+        self.reload();
+        self.window = Some(window);
+        if let Err(err) = self.save() {
+            tracing::warn!("Failed saving recent files: {}", err);
+        }
+    }
+
+    /// Remembers `path` as the most recent file and persists the updated list
+    ///
+    /// The list is reloaded first so that other running instances don't have their files dropped
+    pub fn record(&mut self, path: &Path) {
+        self.reload();
+        self.push(path);
+        note_recent_document(path);
+        if let Err(err) = self.save() {
+            tracing::warn!("Failed saving recent files: {}", err);
+        }
+    }
+
+    /// Picks up any changes that other running instances have saved since we last looked
+    fn reload(&mut self) {
+        if let Some(state_path) = self.state_path.clone() {
+            if let Ok(on_disk) = Self::load_from(state_path) {
+                *self = on_disk;
+            }
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(state_path) = &self.state_path else {
+            return Ok(());
+        };
+        if let Some(parent) = state_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(state_path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+}
+
+/// Also tells the OS about the file so that it shows up in the platform's own recent items
+#[cfg(all(target_os = "windows", not(test)))]
+fn note_recent_document(path: &Path) {
+    use std::os::windows::ffi::OsStrExt;
+
+    use windows_sys::Win32::UI::Shell::{SHAddToRecentDocs, SHARD_PATHW};
+
+    let Ok(path) = path.canonicalize() else {
+        return;
+    };
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: `SHARD_PATHW` expects a pointer to a nul-terminated wide string which outlives the
+    // call
+    unsafe { SHAddToRecentDocs(SHARD_PATHW as u32, wide.as_ptr().cast()) };
+}
+
+/// Also tells the OS about the file so that it shows up in the platform's own recent items
+#[cfg(all(target_os = "macos", not(test)))]
+fn note_recent_document(path: &Path) {
+    use objc::runtime::Object;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    const NS_UTF8_STRING_ENCODING: usize = 4;
+
+    let Some(path) = path
+        .canonicalize()
+        .ok()
+        .and_then(|p| p.to_str().map(ToOwned::to_owned))
+    else {
+        return;
+    };
+    // SAFETY: These are all plain AppKit/Foundation calls on the main thread. The string we
+    // allocate is released once the URL (which copies it) has been created
+    unsafe {
+        let string: *mut Object = msg_send![class!(NSString), alloc];
+        let string: *mut Object = msg_send![
+            string,
+            initWithBytes: path.as_ptr()
+            length: path.len()
+            encoding: NS_UTF8_STRING_ENCODING
+        ];
+        let url: *mut Object = msg_send![class!(NSURL), fileURLWithPath: string];
+        let controller: *mut Object =
+            msg_send![class!(NSDocumentController), sharedDocumentController];
+        let _: () = msg_send![controller, noteNewRecentDocumentURL: url];
+        let _: () = msg_send![string, release];
+    }
+}
+
+// Tests shouldn't go cluttering up the recent items of whoever runs them
+#[cfg(any(test, not(any(target_os = "windows", target_os = "macos"))))]
+fn note_recent_document(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_recent_first_without_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let files: Vec<_> = (0..=MAX_FILES)
+            .map(|i| {
+                let path = root.join(format!("{i}.md"));
+                fs::write(&path, "").unwrap();
+                path
+            })
+            .collect();
+
+        let mut recent = RecentFiles::default();
+        for file in &files {
+            recent.push(file);
+        }
+        recent.push(&files[5]);
+        // Missing files are never remembered
+        recent.push(&root.join("missing.md"));
+
+        assert_eq!(recent.files().len(), MAX_FILES);
+        assert_eq!(recent.files()[0], files[5]);
+        assert_eq!(recent.files()[1], files[MAX_FILES]);
+        assert_eq!(recent.files().iter().filter(|&f| f == &files[5]).count(), 1);
+        // The oldest one fell off the end
+        assert!(!recent.files().contains(&files[0]));
+
+        fs::remove_file(&files[5]).unwrap();
+        assert_eq!(recent.most_recent().unwrap(), files[MAX_FILES]);
+    }
+
+    #[test]
+    fn persists_between_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let doc = dir.path().join("doc.md");
+        fs::write(&doc, "# Hi").unwrap();
+        let state_path = dir.path().join("state").join("recent_files.json");
+
+        let mut recent = RecentFiles::load_from(state_path.clone()).unwrap();
+        assert!(recent.files().is_empty());
+        recent.record(&doc);
+
+        let reloaded = RecentFiles::load_from(state_path).unwrap();
+        assert_eq!(reloaded, recent);
+        assert_eq!(reloaded.files(), [doc.canonicalize().unwrap()]);
+    }
+
+    #[test]
+    fn remembers_views_of_recent_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("recent_files.json");
+        let file = dir.path().join("file.md");
+        let other = dir.path().join("other.md");
+        fs::write(&file, "").unwrap();
+        fs::write(&other, "").unwrap();
+
+        let mut recent = RecentFiles::load_from(state_path.clone()).unwrap();
+        recent.record_view(&file, Some(1.5), 300.);
+        // Not in the list yet
+        assert_eq!(recent.zoom(&file), None);
+        assert_eq!(recent.scroll_y(&file), None);
+
+        recent.record(&file);
+        recent.record_view(&file, Some(1.5), 300.);
+        assert_eq!(recent.zoom(&file), Some(1.5));
+        assert_eq!(recent.scroll_y(&file), Some(300.));
+        let reloaded = RecentFiles::load_from(state_path.clone()).unwrap();
+        assert_eq!(reloaded.zoom(&file), Some(1.5));
+        assert_eq!(reloaded.scroll_y(&file), Some(300.));
+
+        // Leaving the zoom out keeps the one from before
+        recent.record_view(&file, None, 0.);
+        assert_eq!(recent.zoom(&file), Some(1.5));
+        assert_eq!(recent.scroll_y(&file), None);
+        recent.record_view(&file, Some(1.), 0.);
+        assert_eq!(recent.zoom(&file), None);
+        assert_eq!(recent.zoom(&other), None);
+    }
+
+    #[test]
+    fn remembers_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("recent_files.json");
+        let window = WindowGeometry {
+            position: Some((-20, 40)),
+            size: (800, 600),
+            maximized: false,
+        };
+
+        let mut recent = RecentFiles::load_from(state_path.clone()).unwrap();
+        assert_eq!(recent.window(), None);
+        recent.record_window(window);
+        let reloaded = RecentFiles::load_from(state_path).unwrap();
+        assert_eq!(reloaded.window(), Some(window));
+    }
+}
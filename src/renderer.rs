@@ -1,27 +1,43 @@
-use std::borrow::Cow;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use crate::color::{native_color, Theme};
-use crate::fonts::get_fonts;
-use crate::image::ImageRenderer;
-use crate::opts::FontOptions;
-use crate::positioner::{Positioned, Positioner, DEFAULT_MARGIN};
-use crate::table::TABLE_ROW_GAP;
-use crate::text::{CachedTextArea, TextCache, TextSystem};
+use crate::color::{mix, native_color, ImageBackdrop, Theme};
+use crate::diff::DiffLine;
+use crate::fonts::{get_fonts, FontFallbacks};
+use crate::gpu::{self, Gpu};
+use crate::image::pool::Priority;
+use crate::image::textures::TextureManager;
+use crate::image::{self, Image, ImageBatch, ImageRenderer, ImageTransform, ImageVertex};
+use crate::lightbox::Lightbox;
+use crate::loupe::{self, Loupe};
+use crate::minimap;
+use crate::opts::{FontOptions, ImageFetchOptions};
+use crate::panel::{Panel, SourcePane};
+use crate::positioner::{centering, Positioned, Positioner, DEFAULT_MARGIN};
+use crate::search::Search;
+use crate::slideshow::Slideshow;
+use crate::status_bar::StatusBar;
+use crate::text::{Backdrop, CachedTextArea, Text, TextBox, TextCache, TextSystem};
+use crate::toc::Toc;
 use crate::utils::{Point, Rect, Selection, Size};
 use crate::Element;
 
-use anyhow::{Context, Ok};
+use anyhow::Context;
 use bytemuck::{Pod, Zeroable};
-use glyphon::{Resolution, SwashCache, TextArea, TextAtlas, TextRenderer};
+use glyphon::{FamilyOwned, Resolution, SwashCache, TextArea, TextRenderer};
 use lyon::geom::euclid::Point2D;
 use lyon::geom::Box2D;
 use lyon::path::Polygon;
 use lyon::tessellation::*;
 use wgpu::util::DeviceExt;
-use wgpu::{BindGroup, Buffer, IndexFormat, MultisampleState, TextureFormat};
+use wgpu::{IndexFormat, TextureFormat};
 use winit::window::Window;
 
+/// How long the scrollbar sticks around after scrolling or hovering it
+const SCROLLBAR_HIDE_DELAY: Duration = Duration::from_millis(1500);
+/// How long a code block's copy button says that it copied
+const COPIED_FLASH: Duration = Duration::from_millis(1200);
+
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable, Debug)]
 pub struct Vertex {
@@ -29,9 +45,36 @@ pub struct Vertex {
     pub color: [f32; 4],
 }
 
+/// Where finished frames end up
+pub enum RenderTarget {
+    /// Presented to a window
+    Surface(wgpu::Surface),
+    /// An offscreen texture used when there's no window around
+    Texture(wgpu::Texture),
+}
+
+/// Whether a frame made it to the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame {
+    Drawn,
+    /// Nothing was drawn, but asking for another frame should work
+    Skipped,
+}
+
+/// The link under the cursor
+#[derive(Debug, PartialEq)]
+pub struct HoveredLink {
+    pub target: String,
+    /// Where the image is in the document when the link is an image
+    pub image_bounds: Option<Rect>,
+}
+
 pub struct Renderer {
+    instance: wgpu::Instance,
+    /// Set once the device is gone, so everything on the GPU gets recreated before the next frame
+    device_lost: bool,
     pub config: wgpu::SurfaceConfiguration,
-    pub surface: wgpu::Surface,
+    pub target: RenderTarget,
     pub surface_format: TextureFormat,
     pub device: wgpu::Device,
     pub render_pipeline: wgpu::RenderPipeline,
@@ -47,6 +90,52 @@ pub struct Renderer {
     pub selection_text: String,
     pub zoom: f32,
     pub positioner: Positioner,
+    /// Drawn on top of everything else when set
+    pub panel: Option<Panel>,
+    /// Where each of the panel's visible lines was last drawn, for clicking on them
+    pub panel_lines: Vec<Rect>,
+    /// Shown beside the document when the positioner leaves room for it
+    pub source_pane: Option<SourcePane>,
+    /// Shown on the right edge of the screen when the positioner leaves room for it
+    pub toc: Option<Toc>,
+    /// Gets outlined when it's an image and its target shown in the bottom corner
+    pub hovered_link: Option<HoveredLink>,
+    /// Shown along the bottom of the document, with the hovered link's target in it
+    pub status_bar: Option<StatusBar>,
+    /// Where the heading of the section at the top of the view gets found to pin it there
+    pub sticky_headings: Option<Toc>,
+    /// Draws a shrunken outline of the document in its right margin
+    pub minimap: bool,
+    /// Has its matches highlighted in the document
+    pub search: Option<Search>,
+    /// Drawn in its own pass on top of everything, overlays included
+    pub lightbox: Option<Lightbox>,
+    /// Magnifies the scaled down image under the cursor, drawn along with the lightbox
+    pub loupe: Option<Loupe>,
+    /// Keeps the view on the current slide, with only that slide's elements getting drawn
+    pub slideshow: Option<Slideshow>,
+    pub overlay_lyon_buffer: VertexBuffers<Vertex, u16>,
+    pub overlay_text_renderer: TextRenderer,
+    /// Animated images stay on their first frame when unset
+    pub animate_images: bool,
+    /// Where every animation is timed from
+    animation_start: Instant,
+    /// When the next frame of a visible animation is due to be drawn
+    pub next_animation_frame: Option<Instant>,
+    /// Where the copy buttons of the visible code blocks were last drawn in the document, along
+    /// with what they copy
+    pub code_copy_buttons: Vec<(Rect, String)>,
+    /// The copy button that was last clicked and when
+    pub copied_code: Option<(Rect, Instant)>,
+    /// When the document last scrolled or the scrollbar was last hovered
+    scrollbar_active: Instant,
+    /// Whether the last frame had the scrollbar in it
+    scrollbar_shown: bool,
+    /// The scrollbar is never drawn when unset, like for frames that get written to a file
+    pub scrollbar: bool,
+    pub textures: TextureManager,
+    /// How far ahead of the view deferred images start loading, in screens
+    pub preload_screens: f32,
 }
 
 impl Renderer {
@@ -58,6 +147,11 @@ impl Renderer {
         self.positioner.screen_size
     }
 
+    /// The part of the screen that the document gets drawn to
+    pub fn doc_size(&self) -> Size {
+        (self.positioner.doc_width(), self.screen_height())
+    }
+
     pub async fn new(
         window: &Window,
         theme: Theme,
@@ -66,45 +160,13 @@ impl Renderer {
         font_opts: FontOptions,
     ) -> anyhow::Result<Self> {
         let size = window.inner_size();
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
-        });
+        let instance = Self::instance();
         let surface = unsafe {
             instance
                 .create_surface(window)
                 .expect("Could not create surface")
         };
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .context("Failed to find an appropriate adapter")?;
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
-                },
-                None,
-            )
-            .await?;
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/shader.wgsl"))),
-        });
-
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[],
-            push_constant_ranges: &[],
-        });
+        let adapter = gpu::request_adapter(&instance, Some(&surface), false).await?;
 
         let caps = surface.get_capabilities(&adapter);
         let surface_format = caps
@@ -113,32 +175,6 @@ impl Renderer {
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(caps.formats[0]);
-
-        let vertex_buffers = [wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
-        }];
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &vertex_buffers,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(surface_format.into())],
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-        });
-
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -149,15 +185,112 @@ impl Renderer {
             view_formats: vec![],
         };
 
-        surface.configure(&device, &config);
-        let image_renderer = ImageRenderer::new(&device, &surface_format);
+        Self::with_target(
+            instance,
+            adapter,
+            config,
+            Some(surface),
+            theme,
+            hidpi_scale,
+            page_width,
+            font_opts,
+        )
+        .await
+    }
+
+    /// Creates a renderer that draws to an offscreen texture of the given size
+    pub async fn new_headless(
+        (width, height): (u32, u32),
+        theme: Theme,
+        hidpi_scale: f32,
+        page_width: f32,
+        font_opts: FontOptions,
+    ) -> anyhow::Result<Self> {
+        let instance = Self::instance();
+        let adapter = gpu::request_adapter(&instance, None, false).await?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+
+        Self::with_target(
+            instance,
+            adapter,
+            config,
+            None,
+            theme,
+            hidpi_scale,
+            page_width,
+            font_opts,
+        )
+        .await
+    }
+
+    fn instance() -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+        })
+    }
+
+    fn target_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+            view_formats: &[],
+        })
+    }
+
+    // Most of the arguments are just passed straight through from the constructors
+    #[allow(clippy::too_many_arguments)]
+    async fn with_target(
+        instance: wgpu::Instance,
+        adapter: wgpu::Adapter,
+        config: wgpu::SurfaceConfiguration,
+        surface: Option<wgpu::Surface>,
+        theme: Theme,
+        hidpi_scale: f32,
+        page_width: f32,
+        font_opts: FontOptions,
+    ) -> anyhow::Result<Self> {
+        let surface_format = config.format;
+        let Gpu {
+            device,
+            queue,
+            render_pipeline,
+            image_renderer,
+            text_atlas,
+            text_renderer,
+            overlay_text_renderer,
+        } = Gpu::new(&instance, adapter, surface.as_ref(), surface_format).await?;
+
+        let target = match surface {
+            Some(surface) => {
+                surface.configure(&device, &config);
+                RenderTarget::Surface(surface)
+            }
+            None => RenderTarget::Texture(Self::target_texture(&device, &config)),
+        };
 
         let font_system = Arc::new(Mutex::new(get_fonts(&font_opts)));
         let swash_cache = SwashCache::new();
-        let mut text_atlas = TextAtlas::new(&device, &queue, surface_format);
-        let text_renderer =
-            TextRenderer::new(&mut text_atlas, &device, MultisampleState::default(), None);
-        let text_cache = Arc::new(Mutex::new(TextCache::new()));
+        let mut text_cache = TextCache::new();
+        text_cache.fallbacks = FontFallbacks::new(font_opts.fallback_fonts.clone());
+        let text_cache = Arc::new(Mutex::new(text_cache));
         let text_system = TextSystem {
             font_system,
             swash_cache,
@@ -168,10 +301,13 @@ impl Renderer {
 
         let lyon_buffer: VertexBuffers<Vertex, u16> = VertexBuffers::new();
 
-        let positioner = Positioner::new(window.inner_size().into(), hidpi_scale, page_width);
+        let screen_size = (config.width as f32, config.height as f32);
+        let positioner = Positioner::new(screen_size, hidpi_scale, page_width);
         Ok(Self {
+            instance,
+            device_lost: false,
             config,
-            surface,
+            target,
             surface_format,
             device,
             render_pipeline,
@@ -187,11 +323,78 @@ impl Renderer {
             selection: None,
             selection_text: String::new(),
             positioner,
+            panel: None,
+            panel_lines: Vec::new(),
+            source_pane: None,
+            toc: None,
+            hovered_link: None,
+            status_bar: None,
+            sticky_headings: None,
+            minimap: false,
+            search: None,
+            lightbox: None,
+            slideshow: None,
+            loupe: None,
+            overlay_lyon_buffer: VertexBuffers::new(),
+            overlay_text_renderer,
+            animate_images: false,
+            animation_start: Instant::now(),
+            next_animation_frame: None,
+            scrollbar_active: Instant::now(),
+            scrollbar: true,
+            scrollbar_shown: false,
+            code_copy_buttons: Vec::new(),
+            copied_code: None,
+            textures: TextureManager::unlimited(),
+            preload_screens: ImageFetchOptions::default().preload_screens,
         })
     }
 
+    /// Keeps the scrollbar from hiding for a while longer
+    ///
+    /// Returns if it needs to be drawn again to show up
+    pub fn show_scrollbar(&mut self) -> bool {
+        self.scrollbar_active = Instant::now();
+        !self.scrollbar_shown
+    }
+
+    /// When the scrollbar needs to be drawn again to hide it
+    pub fn scrollbar_hide_deadline(&self) -> Option<Instant> {
+        self.scrollbar_shown
+            .then_some(self.scrollbar_active + SCROLLBAR_HIDE_DELAY)
+    }
+
+    /// The code that the copy button at `loc` in the document copies, if there's one there
+    pub fn code_copy_button_at(&self, loc: Point) -> Option<(Rect, &str)> {
+        self.code_copy_buttons
+            .iter()
+            .find(|(button, _)| button.contains(loc))
+            .map(|(button, code)| (button.clone(), code.as_str()))
+    }
+
+    /// Has the copy button say that it copied for a little while
+    pub fn flash_copied(&mut self, button: Rect) {
+        self.copied_code = Some((button, Instant::now()));
+    }
+
+    /// When the copy button needs to be drawn again to stop saying that it copied
+    pub fn copied_flash_deadline(&self) -> Option<Instant> {
+        self.copied_code
+            .as_ref()
+            .map(|(_, copied)| *copied + COPIED_FLASH)
+    }
+
     fn draw_scrollbar(&mut self) -> anyhow::Result<()> {
-        let (screen_width, screen_height) = self.screen_size();
+        let (screen_width, screen_height) = self.doc_size();
+        // Nothing to scroll when everything already fits
+        self.scrollbar_shown = self.scrollbar
+            && self.slideshow.is_none()
+            && self.positioner.reserved_height > screen_height
+            && self.scrollbar_active.elapsed() < SCROLLBAR_HIDE_DELAY;
+        if !self.scrollbar_shown {
+            return Ok(());
+        }
+
         let height = (screen_height / self.positioner.reserved_height) * screen_height;
         self.draw_rectangle(
             Rect::new(
@@ -201,7 +404,7 @@ impl Renderer {
                 ),
                 (DEFAULT_MARGIN / 4., height),
             ),
-            [0.3, 0.3, 0.3, 1.0],
+            native_color(self.theme.scrollbar_color, &self.surface_format),
         )?;
         Ok(())
     }
@@ -211,7 +414,7 @@ impl Renderer {
         elements: &[Positioned<Element>],
     ) -> anyhow::Result<Vec<CachedTextArea>> {
         let mut text_areas: Vec<CachedTextArea> = Vec::new();
-        let screen_size = self.screen_size();
+        let doc_size = self.doc_size();
         for element in elements.iter() {
             let Rect { mut pos, size } =
                 element.bounds.as_ref().context("Element not positioned")?;
@@ -219,11 +422,11 @@ impl Renderer {
             // Dont render off screen elements
             if scrolled_pos.1 + size.1 <= 0. {
                 continue;
-            } else if scrolled_pos.1 >= screen_size.1 {
+            } else if scrolled_pos.1 >= doc_size.1 {
                 break;
             }
 
-            let centering = (screen_size.0 - self.page_width).max(0.) / 2.;
+            let centering = centering(doc_size.0, self.page_width);
 
             match &element.inner {
                 Element::TextBox(text_box) => {
@@ -235,21 +438,36 @@ impl Renderer {
                     }
 
                     let bounds = (
-                        (screen_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
+                        (doc_size.0 - pos.0 - DEFAULT_MARGIN - centering - text_box.float_inset)
+                            .max(0.),
                         f32::INFINITY,
                     );
 
-                    let areas = text_box.text_areas(
+                    // Where the text starts after any scrolling sideways
+                    let text_pos = (pos.0 - text_box.scroll_x, pos.1);
+                    let text_scrolled_pos = (scrolled_pos.0 - text_box.scroll_x, scrolled_pos.1);
+                    let mut areas = text_box.text_areas(
                         &mut self.text_system,
-                        pos,
+                        text_pos,
                         bounds,
                         self.zoom,
                         self.scroll_y,
                     );
+                    if text_box.no_wrap {
+                        areas.clip_x(scrolled_pos.0, scrolled_pos.0 + bounds.0);
+                    }
                     text_areas.push(areas.clone());
                     if text_box.is_code_block || text_box.is_quote_block.is_some() {
                         let color = if let Some(bg_color) = text_box.background_color {
                             bg_color
+                        } else if let Some(callout) = text_box.innermost_callout() {
+                            // Tinted with the callout's color
+                            let tint = mix(
+                                self.theme.background_color,
+                                callout.color(&self.theme),
+                                0.12,
+                            );
+                            native_color(tint, &self.surface_format)
                         } else {
                             native_color(self.theme.quote_block_color, &self.surface_format)
                         };
@@ -269,8 +487,29 @@ impl Renderer {
                         if let Some(nest) = text_box.is_quote_block {
                             min.0 -= (nest - 1) as f32 * DEFAULT_MARGIN / 2.;
                         }
-                        if min.0 < screen_size.0 - DEFAULT_MARGIN - centering {
-                            self.draw_rectangle(Rect::from_min_max(min, max), color)?;
+                        if min.0 < doc_size.0 - DEFAULT_MARGIN - centering {
+                            let block = Rect::from_min_max(min, max);
+                            self.draw_rectangle(block.clone(), color)?;
+                            for (ys, kind) in text_box.render_diff_lines(
+                                &mut self.text_system,
+                                text_scrolled_pos,
+                                bounds,
+                                self.zoom,
+                            ) {
+                                let tint = match kind {
+                                    DiffLine::Added => self.theme.tip_color,
+                                    DiffLine::Removed => self.theme.caution_color,
+                                };
+                                let tint = mix(self.theme.background_color, tint, 0.2);
+                                self.draw_rectangle(
+                                    Rect::from_min_max((min.0, ys.start), (max.0, ys.end)),
+                                    native_color(tint, &self.surface_format),
+                                )?;
+                            }
+                            if text_box.is_code_block {
+                                text_areas
+                                    .push(self.draw_code_block_header(text_box, block, color)?);
+                            }
                         }
                     }
                     if let Some(nest) = text_box.is_quote_block {
@@ -281,17 +520,23 @@ impl Renderer {
                                     - 10.
                                     - 5. * self.hidpi_scale * self.zoom
                                     - nest_indent)
-                                    .min(screen_size.0 - DEFAULT_MARGIN - centering),
+                                    .min(doc_size.0 - DEFAULT_MARGIN - centering),
                                 scrolled_pos.1,
                             );
                             let max = (
                                 (scrolled_pos.0 - 10. - nest_indent)
-                                    .min(screen_size.0 - DEFAULT_MARGIN - centering),
+                                    .min(doc_size.0 - DEFAULT_MARGIN - centering),
                                 min.1 + size.1 + 5. * self.hidpi_scale * self.zoom,
                             );
+                            let bar_color = match text_box.callout {
+                                Some((callout, level)) if level == nest - n => {
+                                    callout.color(&self.theme)
+                                }
+                                _ => self.theme.quote_bar_color,
+                            };
                             self.draw_rectangle(
                                 Rect::from_min_max(min, max),
-                                native_color(self.theme.select_color, &self.surface_format),
+                                native_color(bar_color, &self.surface_format),
                             )?;
                         }
                     }
@@ -304,7 +549,7 @@ impl Renderer {
                             scrolled_pos.0 + box_size - box_size * 1.5,
                             scrolled_pos.1 + size.1 / 2. + box_size / 2.,
                         );
-                        if max.0 < screen_size.0 - DEFAULT_MARGIN - centering {
+                        if max.0 < doc_size.0 - DEFAULT_MARGIN - centering {
                             if is_checked {
                                 self.draw_rectangle(
                                     Rect::from_min_max(min, max),
@@ -324,12 +569,47 @@ impl Renderer {
                             )?;
                         }
                     }
+                    for (rect, backdrop) in text_box.render_backdrops(
+                        &mut self.text_system,
+                        text_scrolled_pos,
+                        bounds,
+                        self.zoom,
+                        &areas,
+                    ) {
+                        match backdrop {
+                            Backdrop::Mark => self.draw_rectangle(
+                                rect,
+                                native_color(self.theme.mark_color, &self.surface_format),
+                            )?,
+                            Backdrop::KeyCap => {
+                                let padding = 2. * self.hidpi_scale * self.zoom;
+                                let inset = rect.size.1 * 0.1;
+                                let key = Rect::new(
+                                    (rect.pos.0 - padding, rect.pos.1 + inset),
+                                    (rect.size.0 + 2. * padding, rect.size.1 - 2. * inset),
+                                );
+                                self.draw_rectangle(
+                                    key.clone(),
+                                    native_color(
+                                        self.theme.quote_block_color,
+                                        &self.surface_format,
+                                    ),
+                                )?;
+                                self.stroke_rectangle(
+                                    key,
+                                    native_color(self.theme.text_color, &self.surface_format),
+                                    1. * self.hidpi_scale * self.zoom,
+                                )?;
+                            }
+                        }
+                    }
                     for line in text_box.render_lines(
                         &mut self.text_system,
-                        scrolled_pos,
+                        text_scrolled_pos,
                         bounds,
                         self.zoom,
                         &areas,
+                        self.hovered_link.as_ref().map(|link| link.target.as_str()),
                     ) {
                         let min = (line.min.0, line.min.1);
                         let max = (line.max.0, line.max.1 + 2. * self.hidpi_scale * self.zoom);
@@ -338,7 +618,7 @@ impl Renderer {
                     if let Some(selection) = self.selection {
                         let (selection_rects, selection_text) = text_box.render_selection(
                             &mut self.text_system,
-                            pos,
+                            text_pos,
                             bounds,
                             self.zoom,
                             selection,
@@ -346,10 +626,15 @@ impl Renderer {
                         self.selection_text.push_str(&selection_text);
                         self.selection_text.push('\n');
                         for rect in selection_rects {
+                            let (mut left, mut right) = (rect.pos.0, rect.max().0);
+                            if text_box.no_wrap {
+                                left = left.max(pos.0);
+                                right = right.min(pos.0 + bounds.0);
+                            }
                             self.draw_rectangle(
                                 Rect::from_min_max(
-                                    (rect.pos.0, rect.pos.1 - self.scroll_y),
-                                    (rect.max().0, rect.max().1 - self.scroll_y),
+                                    (left, rect.pos.1 - self.scroll_y),
+                                    (right.max(left), rect.max().1 - self.scroll_y),
                                 ),
                                 native_color(self.theme.select_color, &self.surface_format),
                             )?;
@@ -358,7 +643,7 @@ impl Renderer {
                 }
                 Element::Table(table) => {
                     let bounds = (
-                        (screen_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
+                        (doc_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
                         f32::INFINITY,
                     );
                     let layout = table.layout(
@@ -368,15 +653,48 @@ impl Renderer {
                         self.zoom,
                     )?;
 
+                    let style = table.style;
+                    let header_color = mix(
+                        self.theme.background_color,
+                        self.theme.table_border_color,
+                        0.12,
+                    );
+                    let header = layout
+                        .header_row()
+                        .filter(|_| style.header_background)
+                        .map(|rect| (rect, header_color));
+                    let stripe_color = self.theme.quote_block_color;
+                    let stripes = layout
+                        .striped_rows()
+                        .filter(|_| style.row_separators.stripes())
+                        .map(|rect| (rect, stripe_color));
+                    for (rect, color) in header.into_iter().chain(stripes) {
+                        let min = (
+                            (scrolled_pos.0 + rect.pos.0).max(DEFAULT_MARGIN + centering),
+                            scrolled_pos.1 + rect.pos.1,
+                        );
+                        let max = (scrolled_pos.0 + rect.max().0, scrolled_pos.1 + rect.max().1);
+                        self.draw_rectangle(
+                            Rect::from_min_max(min, max),
+                            native_color(color, &self.surface_format),
+                        )?;
+                    }
+
                     for (col, node) in layout.headers.iter().enumerate() {
                         if let Some(text_box) = table.headers.get(col) {
-                            text_areas.push(text_box.text_areas(
+                            let mut areas = text_box.text_areas(
                                 &mut self.text_system,
                                 (pos.0 + node.location.x, pos.1 + node.location.y),
                                 (node.size.width, f32::MAX),
                                 self.zoom,
                                 self.scroll_y,
-                            ));
+                            );
+                            // Words too long for their column get cut off at its edge
+                            areas.clip_x(
+                                pos.0 + node.location.x,
+                                pos.0 + node.location.x + node.size.width,
+                            );
+                            text_areas.push(areas);
                             if let Some(selection) = self.selection {
                                 let (selection_rects, selection_text) = text_box.render_selection(
                                     &mut self.text_system,
@@ -402,7 +720,7 @@ impl Renderer {
                     let last_header_node = layout.headers.last().unwrap();
                     let y = last_header_node.location.y
                         + last_header_node.size.height
-                        + TABLE_ROW_GAP / 2.;
+                        + layout.cell_padding;
                     let x = layout
                         .headers
                         .last()
@@ -419,7 +737,7 @@ impl Renderer {
                         );
                         self.draw_rectangle(
                             Rect::from_min_max(min, max),
-                            native_color(self.theme.text_color, &self.surface_format),
+                            native_color(self.theme.table_border_color, &self.surface_format),
                         )?;
                     }
 
@@ -427,13 +745,18 @@ impl Renderer {
                         for (col, node) in node_row.iter().enumerate() {
                             if let Some(row) = table.rows.get(row) {
                                 if let Some(text_box) = row.get(col) {
-                                    text_areas.push(text_box.text_areas(
+                                    let mut areas = text_box.text_areas(
                                         &mut self.text_system,
                                         (pos.0 + node.location.x, pos.1 + node.location.y),
                                         (node.size.width, f32::MAX),
                                         self.zoom,
                                         self.scroll_y,
-                                    ));
+                                    );
+                                    areas.clip_x(
+                                        pos.0 + node.location.x,
+                                        pos.0 + node.location.x + node.size.width,
+                                    );
+                                    text_areas.push(areas);
 
                                     if let Some(selection) = self.selection {
                                         let (selection_rects, selection_text) = text_box
@@ -462,10 +785,13 @@ impl Renderer {
                                 }
                             }
                         }
+                        if !style.row_separators.lines() {
+                            continue;
+                        }
                         let last_row_node = node_row.last().unwrap();
                         let y = last_row_node.location.y
                             + last_row_node.size.height
-                            + TABLE_ROW_GAP / 2.;
+                            + layout.cell_padding;
                         let x = node_row
                             .last()
                             .map(|f| f.location.x + f.size.width)
@@ -481,12 +807,38 @@ impl Renderer {
                             );
                             self.draw_rectangle(
                                 Rect::from_min_max(min, max),
-                                native_color(self.theme.text_color, &self.surface_format),
+                                native_color(self.theme.table_border_color, &self.surface_format),
                             )?;
                         }
                     }
                 }
-                Element::Image(_) => {}
+                Element::Image(image) => {
+                    if image.is_loaded() {
+                        if image.has_alpha() {
+                            self.draw_image_backdrop(Rect::new(scrolled_pos, *size))?;
+                        }
+                        continue;
+                    }
+                    let text_color = native_color(self.theme.text_color, &self.surface_format);
+                    if let Some(placeholder) = image.placeholder(text_color) {
+                        self.stroke_rectangle(
+                            Rect::new(scrolled_pos, *size),
+                            native_color(self.theme.select_color, &self.surface_format),
+                            1. * self.hidpi_scale * self.zoom,
+                        )?;
+                        let padding = image.placeholder_padding(self.zoom);
+                        text_areas.push(placeholder.text_areas(
+                            &mut self.text_system,
+                            (pos.0 + padding, pos.1 + padding),
+                            (
+                                (size.0 - 2. * padding).max(0.),
+                                (size.1 - 2. * padding).max(0.),
+                            ),
+                            self.zoom,
+                            self.scroll_y,
+                        ));
+                    }
+                }
                 Element::Spacer(spacer) => {
                     if spacer.visible {
                         self.draw_rectangle(
@@ -497,7 +849,7 @@ impl Renderer {
                                         - 2. * self.hidpi_scale * self.zoom,
                                 ),
                                 (
-                                    screen_size.0 - 2. * (DEFAULT_MARGIN + centering),
+                                    doc_size.0 - 2. * (DEFAULT_MARGIN + centering),
                                     2. * self.hidpi_scale * self.zoom,
                                 ),
                             ),
@@ -531,197 +883,1381 @@ impl Renderer {
         Ok(text_areas)
     }
 
-    fn draw_hidden_marker(
-        &mut self,
-        pos: Point,
-        size: f32,
-        color: [f32; 4],
-        hidden: bool,
-    ) -> anyhow::Result<()> {
-        let points = if hidden {
-            [
-                point(pos.0, pos.1, self.screen_size()).into(),
-                point(pos.0 - size, pos.1 + size, self.screen_size()).into(),
-                point(pos.0 - size, pos.1 - size, self.screen_size()).into(),
-            ]
-        } else {
-            [
-                point(pos.0, pos.1 - size / 2., self.screen_size()).into(),
-                point(pos.0 - size * 2., pos.1 - size / 2., self.screen_size()).into(),
-                point(pos.0 - size, pos.1 + size / 2., self.screen_size()).into(),
-            ]
-        };
-        let triangle = Polygon {
-            points: &points,
-            closed: true,
+    // Every match is highlighted like a selection, with the current one outlined as well
+    fn draw_search_highlights(&mut self) -> anyhow::Result<()> {
+        let Some(search) = &self.search else {
+            return Ok(());
         };
-        let mut fill_tessellator = FillTessellator::new();
-        fill_tessellator.tessellate_polygon(
-            triangle,
-            &FillOptions::default(),
-            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: FillVertex| Vertex {
-                pos: [vertex.position().x, vertex.position().y, 0.0],
-                color,
-            }),
-        )?;
-        Ok(())
-    }
-
-    fn draw_rectangle(&mut self, rect: Rect, color: [f32; 4]) -> anyhow::Result<()> {
-        let min = point(rect.pos.0, rect.pos.1, self.screen_size());
-        let max = point(rect.max().0, rect.max().1, self.screen_size());
-        let mut fill_tessellator = FillTessellator::new();
-        fill_tessellator.tessellate_rectangle(
-            &Box2D::new(Point2D::from(min), Point2D::from(max)),
-            &FillOptions::default(),
-            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: FillVertex| Vertex {
-                pos: [vertex.position().x, vertex.position().y, 0.0],
-                color,
-            }),
-        )?;
-        Ok(())
-    }
+        let current = search.current_index();
+        let screen_height = self.screen_height();
+        let scroll_y = self.scroll_y;
+        let highlights: Vec<_> = search
+            .matches()
+            .iter()
+            .enumerate()
+            .flat_map(|(i, rects)| rects.iter().map(move |rect| (rect, Some(i) == current)))
+            .filter(|(rect, _)| rect.max().1 > scroll_y && rect.pos.1 < scroll_y + screen_height)
+            .map(|(rect, is_current)| {
+                let pos = (rect.pos.0, rect.pos.1 - scroll_y);
+                (Rect::new(pos, rect.size), is_current)
+            })
+            .collect();
 
-    fn stroke_rectangle(&mut self, rect: Rect, color: [f32; 4], width: f32) -> anyhow::Result<()> {
-        let mut stroke_tessellator = StrokeTessellator::new();
-        let screen_size = self.screen_size();
-        stroke_tessellator.tessellate_rectangle(
-            &Box2D::new(Point2D::from(rect.pos), Point2D::from(rect.max())),
-            &StrokeOptions::default().with_line_width(width),
-            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: StrokeVertex| {
-                let point = point(vertex.position().x, vertex.position().y, screen_size);
-                Vertex {
-                    pos: [point[0], point[1], 0.0],
-                    color,
-                }
-            }),
-        )?;
+        let select_color = native_color(self.theme.select_color, &self.surface_format);
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        for (rect, is_current) in highlights {
+            self.draw_rectangle(rect.clone(), select_color)?;
+            if is_current {
+                self.stroke_rectangle(rect, text_color, 2. * self.hidpi_scale)?;
+            }
+        }
         Ok(())
     }
 
-    fn draw_tick(
+    fn render_overlays(
         &mut self,
-        pos: Point,
-        box_size: f32,
-        color: [f32; 4],
-        width: f32,
-    ) -> anyhow::Result<()> {
-        let screen_size = self.screen_size();
-        let mut stroke_tessellator = StrokeTessellator::new();
-        let stroke_opts = StrokeOptions::default().with_line_width(width);
-        let mut vertex_builder =
-            BuffersBuilder::new(&mut self.lyon_buffer, |vertex: StrokeVertex| {
-                let point = point(vertex.position().x, vertex.position().y, screen_size);
-                Vertex {
-                    pos: [point[0], point[1], 0.0],
-                    color,
-                }
-            });
-        let mut builder = stroke_tessellator.builder(&stroke_opts, &mut vertex_builder);
+        elements: &[Positioned<Element>],
+    ) -> anyhow::Result<Vec<CachedTextArea>> {
+        self.overlay_lyon_buffer.indices.clear();
+        self.overlay_lyon_buffer.vertices.clear();
 
-        // Build a simple path.
-        builder.begin((pos.0 + box_size * 0.2, pos.1 + box_size * 0.5).into());
-        builder.line_to((pos.0 + box_size * 0.4, pos.1 + box_size * 0.7).into());
-        builder.line_to((pos.0 + box_size * 0.8, pos.1 + box_size * 0.2).into());
-        builder.end(false);
-        builder.build()?;
-        Ok(())
+        // Reuse the regular drawing helpers, but have them draw into the overlay's buffer instead
+        std::mem::swap(&mut self.lyon_buffer, &mut self.overlay_lyon_buffer);
+        let text_areas = self.draw_overlays(elements);
+        std::mem::swap(&mut self.lyon_buffer, &mut self.overlay_lyon_buffer);
+
+        text_areas
     }
 
-    fn image_bindgroups(
+    fn draw_overlays(
         &mut self,
-        elements: &mut [Positioned<Element>],
-    ) -> Vec<(Arc<BindGroup>, Buffer)> {
-        let screen_size = self.screen_size();
-        let mut bind_groups = Vec::new();
-        for element in elements.iter_mut() {
-            let Rect { pos, size } = element.bounds.as_ref().unwrap();
-            let pos = (pos.0, pos.1 - self.scroll_y);
-            if pos.1 + size.1 <= 0. {
-                continue;
-            } else if pos.1 >= screen_size.1 {
-                break;
+        elements: &[Positioned<Element>],
+    ) -> anyhow::Result<Vec<CachedTextArea>> {
+        let mut text_areas = Vec::new();
+        // Neither has anything to do with just the one slide
+        let presenting = self.slideshow.is_some();
+        match self.sticky_headings.take() {
+            Some(sticky_headings) if !presenting => {
+                let maybe_text_area = self.draw_sticky_heading(&sticky_headings);
+                self.sticky_headings = Some(sticky_headings);
+                text_areas.extend(maybe_text_area?);
             }
-            match &mut element.inner {
-                Element::Image(ref mut image) => {
-                    if let Some(bind_group) = image.bind_group.clone().or_else(|| {
-                        image.create_bind_group(
-                            &self.device,
-                            &self.queue,
-                            &self.image_renderer.sampler,
-                            &self.image_renderer.bindgroup_layout,
-                        )
-                    }) {
-                        let vertex_buf =
-                            ImageRenderer::vertex_buf(&self.device, pos, *size, screen_size);
-                        bind_groups.push((bind_group.clone(), vertex_buf));
-                    }
-                }
-                Element::Row(ref mut row) => {
-                    for element in row.elements.iter_mut() {
-                        let Rect { pos, size } = element.bounds.as_ref().unwrap();
-                        let pos = (pos.0, pos.1 - self.scroll_y);
-                        if let Element::Image(ref mut image) = &mut element.inner {
-                            if let Some(bind_group) = image.bind_group.clone().or_else(|| {
-                                image.create_bind_group(
-                                    &self.device,
-                                    &self.queue,
-                                    &self.image_renderer.sampler,
-                                    &self.image_renderer.bindgroup_layout,
-                                )
-                            }) {
-                                let vertex_buf = ImageRenderer::vertex_buf(
-                                    &self.device,
-                                    pos,
-                                    *size,
-                                    screen_size,
-                                );
-                                bind_groups.push((bind_group.clone(), vertex_buf));
-                            }
-                        }
-                    }
-                }
-                Element::Section(ref mut section) => {
-                    if *section.hidden.borrow() {
+            sticky_headings => self.sticky_headings = sticky_headings,
+        }
+        if self.minimap && !presenting {
+            self.draw_minimap(elements)?;
+        }
+        if let Some(source_pane) = self.source_pane.take() {
+            let maybe_text_area = self.draw_source_pane(&source_pane);
+            self.source_pane = Some(source_pane);
+            text_areas.push(maybe_text_area?);
+        }
+        if let Some(toc) = self.toc.take() {
+            let maybe_text_area = self.draw_toc(&toc);
+            self.toc = Some(toc);
+            text_areas.push(maybe_text_area?);
+        }
+        if let Some(status_bar) = self.status_bar.take() {
+            let maybe_text_areas = self.draw_status_bar(&status_bar);
+            self.status_bar = Some(status_bar);
+            text_areas.extend(maybe_text_areas?);
+        }
+        if let Some(counter) = self
+            .slideshow
+            .as_ref()
+            .filter(|slideshow| slideshow.counter)
+            .map(Slideshow::counter_text)
+        {
+            text_areas.push(self.draw_slide_counter(counter)?);
+        }
+        if let Some(hovered_link) = self.hovered_link.take() {
+            let maybe_text_area = self.draw_hovered_link(&hovered_link);
+            self.hovered_link = Some(hovered_link);
+            text_areas.extend(maybe_text_area?);
+        }
+        self.panel_lines.clear();
+        if let Some(panel) = self.panel.take() {
+            let maybe_text_areas = self.draw_panel(&panel);
+            self.panel = Some(panel);
+            text_areas.extend(maybe_text_areas?);
+        }
+
+        Ok(text_areas)
+    }
+
+    // Outlines linked images and shows where the link goes in the bottom corner like a browser.
+    // The link's box is hidden behind any panel, so it's skipped while one is open, and the status
+    // bar shows the link itself
+    fn draw_hovered_link(&mut self, link: &HoveredLink) -> anyhow::Result<Option<CachedTextArea>> {
+        let link_color = native_color(self.theme.link_color, &self.surface_format);
+        if let Some(bounds) = &link.image_bounds {
+            let pos = (bounds.pos.0, bounds.pos.1 - self.scroll_y);
+            self.stroke_rectangle(
+                Rect::new(pos, bounds.size),
+                link_color,
+                2. * self.hidpi_scale * self.zoom,
+            )?;
+        }
+        if self.panel.is_some() || self.status_bar.is_some() {
+            return Ok(None);
+        }
+
+        let (doc_width, screen_height) = self.doc_size();
+        let padding = 4. * self.hidpi_scale;
+        let max_width = (doc_width * 0.6 - padding * 2.).max(0.);
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let text = Text::new(link.target.clone(), self.hidpi_scale, text_color);
+        let text_box = self.single_line_box(text, max_width);
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let size = text_box.size(&mut self.text_system, unbounded, 1.);
+
+        let top = screen_height - size.1 - padding * 2.;
+        self.draw_rectangle(
+            Rect::new((0., top), (size.0 + padding * 2., size.1 + padding * 2.)),
+            native_color(self.theme.quote_block_color, &self.surface_format),
+        )?;
+        Ok(Some(text_box.text_areas(
+            &mut self.text_system,
+            (padding, top + padding),
+            unbounded,
+            1.,
+            0.,
+        )))
+    }
+
+    // Sits in the bottom right corner across from where hovered links get shown, above the status
+    // bar when it's open
+    fn draw_slide_counter(&mut self, counter: String) -> anyhow::Result<CachedTextArea> {
+        let (doc_width, screen_height) = self.doc_size();
+        let padding = 4. * self.hidpi_scale;
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let text_box = self.single_line_box(
+            Text::new(counter, self.hidpi_scale, text_color),
+            f32::INFINITY,
+        );
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let size = text_box.size(&mut self.text_system, unbounded, 1.);
+
+        let left = doc_width - size.0 - padding * 2.;
+        let top = screen_height - self.status_bar_height() - size.1 - padding * 2.;
+        self.draw_rectangle(
+            Rect::new((left, top), (size.0 + padding * 2., size.1 + padding * 2.)),
+            native_color(self.theme.quote_block_color, &self.surface_format),
+        )?;
+        Ok(text_box.text_areas(
+            &mut self.text_system,
+            (left + padding, top + padding),
+            unbounded,
+            1.,
+            0.,
+        ))
+    }
+
+    const BAR_FONT_SIZE: f32 = 14.;
+
+    /// How tall the bars along the top and bottom of the document are
+    fn bar_height(&self) -> f32 {
+        let mut text_box = TextBox::new(Vec::new(), self.hidpi_scale);
+        text_box.font_size = Self::BAR_FONT_SIZE;
+        text_box.line_height(1.) + 8. * self.hidpi_scale
+    }
+
+    /// `text` in a box for one of the bars that's cut short instead of wrapping when it's wider
+    /// than `max_width`
+    fn single_line_box(&mut self, text: Text, max_width: f32) -> TextBox {
+        let mut text_box = TextBox::new(vec![text], self.hidpi_scale);
+        text_box.font_size = Self::BAR_FONT_SIZE;
+        let width = text_box
+            .size(&mut self.text_system, (f32::INFINITY, f32::INFINITY), 1.)
+            .0;
+        if width > max_width {
+            let text = &mut text_box.texts[0];
+            let num_chars = text.text.chars().count();
+            let keep = (num_chars as f32 * max_width / width) as usize;
+            let truncated: String = text.text.chars().take(keep.saturating_sub(1)).collect();
+            text.text = format!("{truncated}…");
+        }
+        text_box
+    }
+
+    /// How much of the bottom of the document the status bar covers
+    pub fn status_bar_height(&self) -> f32 {
+        if self.status_bar.is_none() {
+            return 0.;
+        }
+        self.bar_height()
+    }
+
+    /// How far through the document the view is scrolled. Everything is in view when there's
+    /// nowhere to scroll to
+    pub fn scroll_percent(&self) -> u8 {
+        let max_scroll_y = self.max_scroll_y();
+        if max_scroll_y <= 0. {
+            return 100;
+        }
+        (self.scroll_y / max_scroll_y * 100.)
+            .round()
+            .clamp(0., 100.) as u8
+    }
+
+    // Sits under any panel, so it's left out while one is open. Only scales with the window
+    fn draw_status_bar(&mut self, status_bar: &StatusBar) -> anyhow::Result<Vec<CachedTextArea>> {
+        if self.panel.is_some() {
+            return Ok(Vec::new());
+        }
+
+        let (doc_width, screen_height) = self.doc_size();
+        let height = self.status_bar_height();
+        let padding = 4. * self.hidpi_scale;
+        let top = screen_height - height;
+        self.draw_rectangle(
+            Rect::new((0., top), (doc_width, height)),
+            native_color(self.theme.quote_block_color, &self.surface_format),
+        )?;
+        self.draw_rectangle(
+            Rect::new((0., top), (doc_width, self.hidpi_scale)),
+            native_color(self.theme.select_color, &self.surface_format),
+        )?;
+
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let link_color = native_color(self.theme.link_color, &self.surface_format);
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let summary = Text::new(
+            status_bar.summary(self.scroll_percent()),
+            self.hidpi_scale,
+            text_color,
+        );
+        let summary = self.single_line_box(summary, f32::INFINITY);
+        let summary_width = summary.size(&mut self.text_system, unbounded, 1.).0;
+        let summary_left = (doc_width - summary_width - padding * 2.).max(padding);
+        let mut text_areas = vec![summary.text_areas(
+            &mut self.text_system,
+            (summary_left, top + padding),
+            unbounded,
+            1.,
+            0.,
+        )];
+
+        // Long links get cut short before they run into the summary
+        if let Some(link) = &self.hovered_link {
+            let max_width = (summary_left - padding * 4.).max(0.);
+            let text = Text::new(link.target.clone(), self.hidpi_scale, link_color);
+            let link_box = self.single_line_box(text, max_width);
+            text_areas.push(link_box.text_areas(
+                &mut self.text_system,
+                (padding * 2., top + padding),
+                unbounded,
+                1.,
+                0.,
+            ));
+        }
+
+        Ok(text_areas)
+    }
+
+    // Pins the heading of the section at the top of the view above the document, along with the
+    // headings of the sections it's in. The next heading pushes it up and out of the way as it
+    // scrolls by
+    fn draw_sticky_heading(&mut self, toc: &Toc) -> anyhow::Result<Option<CachedTextArea>> {
+        let anchors = &self.positioner.anchors;
+        let Some(current) = toc.current(anchors, self.scroll_y) else {
+            return Ok(None);
+        };
+        let height = self.bar_height();
+        let heading_y = anchors.get(&toc.headings[current].anchor).copied();
+        // Nothing to pin while the heading itself is still in view
+        if heading_y.map_or(true, |y| y > self.scroll_y - height) {
+            return Ok(None);
+        }
+        let next_y = toc
+            .headings
+            .get(current + 1)
+            .and_then(|heading| anchors.get(&heading.anchor));
+        let top = next_y.map_or(0., |y| (y - self.scroll_y - height).min(0.));
+
+        let doc_width = self.doc_size().0;
+        self.draw_rectangle(
+            Rect::new((0., top), (doc_width, height)),
+            native_color(self.theme.quote_block_color, &self.surface_format),
+        )?;
+        self.draw_rectangle(
+            Rect::new(
+                (0., top + height - self.hidpi_scale),
+                (doc_width, self.hidpi_scale),
+            ),
+            native_color(self.theme.select_color, &self.surface_format),
+        )?;
+
+        let titles: Vec<_> = toc
+            .breadcrumbs(current)
+            .iter()
+            .map(|heading| heading.title.as_str())
+            .collect();
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let text = Text::new(titles.join(" › "), self.hidpi_scale, text_color).make_bold(true);
+        let left = DEFAULT_MARGIN + centering(doc_width, self.page_width);
+        let text_box = self.single_line_box(text, (doc_width - left * 2.).max(0.));
+        Ok(Some(text_box.text_areas(
+            &mut self.text_system,
+            (left, top + 4. * self.hidpi_scale),
+            (f32::INFINITY, f32::INFINITY),
+            1.,
+            0.,
+        )))
+    }
+
+    /// Where the minimap sits on the screen and how much it shrinks the document. It goes in the
+    /// document's right margin, just to the left of the scrollbar
+    fn minimap_layout(&self) -> (Rect, f32) {
+        let (doc_width, screen_height) = self.doc_size();
+        let padding = 4. * self.hidpi_scale;
+        let width = (minimap::WIDTH * self.hidpi_scale).min(DEFAULT_MARGIN * 0.6);
+        let left = doc_width - DEFAULT_MARGIN / 4. - padding - width;
+        let height = (screen_height - self.status_bar_height() - padding * 2.).max(0.);
+        let scale = minimap::scale(self.positioner.reserved_height, height);
+        let drawn_height = self.positioner.reserved_height * scale;
+        (Rect::new((left, padding), (width, drawn_height)), scale)
+    }
+
+    /// Where clicking on the minimap at `pos` on the screen scrolls to, centering that part of
+    /// the document
+    pub fn minimap_scroll_at(&self, pos: Point) -> Option<f32> {
+        if !self.minimap {
+            return None;
+        }
+        let (bounds, scale) = self.minimap_layout();
+        bounds
+            .contains(pos)
+            .then(|| (pos.1 - bounds.pos.1) / scale - self.screen_height() / 2.)
+    }
+
+    // Blocks keep where they are across the page, while the document's margins are left out
+    fn draw_minimap(&mut self, elements: &[Positioned<Element>]) -> anyhow::Result<()> {
+        let (bounds, scale) = self.minimap_layout();
+        let doc_width = self.doc_size().0;
+        let margin = DEFAULT_MARGIN + centering(doc_width, self.page_width);
+        let page_width = (doc_width - margin * 2.).max(1.);
+        let x_scale = bounds.size.0 / page_width;
+        let min_height = self.hidpi_scale;
+
+        let mut background = native_color(self.theme.quote_block_color, &self.surface_format);
+        background[3] *= 0.8;
+        self.draw_rectangle(bounds.clone(), background)?;
+
+        let color = |color, alpha: f32| {
+            let mut color = native_color(color, &self.surface_format);
+            color[3] *= alpha;
+            color
+        };
+        let heading_color = color(self.theme.heading_color, 1.);
+        let text_color = color(self.theme.text_color, 0.3);
+        let code_color = color(self.theme.code_color, 0.5);
+        let image_color = color(self.theme.link_color, 0.4);
+        let table_color = color(self.theme.table_border_color, 0.6);
+        for (rect, block) in minimap::blocks(elements) {
+            let left = ((rect.pos.0 - margin) * x_scale).max(0.);
+            let width = (rect.size.0 * x_scale).min(bounds.size.0 - left);
+            let (height, color) = match block {
+                // Headings are drawn as bars that are thicker the bigger they are
+                minimap::Block::Heading(level) => {
+                    let thickness = f32::from(7u8.saturating_sub(level)) * 0.5 + 1.;
+                    (thickness * self.hidpi_scale, heading_color)
+                }
+                minimap::Block::Text => (rect.size.1 * scale, text_color),
+                minimap::Block::Code => (rect.size.1 * scale, code_color),
+                minimap::Block::Image => (rect.size.1 * scale, image_color),
+                minimap::Block::Table => (rect.size.1 * scale, table_color),
+            };
+            self.draw_rectangle(
+                Rect::new(
+                    (bounds.pos.0 + left, bounds.pos.1 + rect.pos.1 * scale),
+                    (width.max(0.), height.max(min_height)),
+                ),
+                color,
+            )?;
+        }
+
+        // Marks the part of the document that's in view
+        let view = Rect::new(
+            (bounds.pos.0, bounds.pos.1 + self.scroll_y * scale),
+            (bounds.size.0, self.screen_height() * scale),
+        );
+        let select_color = native_color(self.theme.select_color, &self.surface_format);
+        let mut view_color = select_color;
+        view_color[3] *= 0.5;
+        self.draw_rectangle(view.clone(), view_color)?;
+        self.stroke_rectangle(view, select_color, self.hidpi_scale)?;
+        Ok(())
+    }
+
+    // Like panels, the source pane only scales with the window and not zoom
+    fn draw_source_pane(&mut self, pane: &SourcePane) -> anyhow::Result<CachedTextArea> {
+        const FONT_SIZE: f32 = 14.;
+
+        let (screen_width, screen_height) = self.screen_size();
+        let left = self.positioner.doc_width();
+        let right = screen_width - self.positioner.toc_width();
+        let padding = 8. * self.hidpi_scale;
+        self.draw_rectangle(
+            Rect::new((left, 0.), (right - left, screen_height)),
+            native_color(self.theme.quote_block_color, &self.surface_format),
+        )?;
+        self.draw_rectangle(
+            Rect::new((left, 0.), (2. * self.hidpi_scale, screen_height)),
+            native_color(self.theme.select_color, &self.surface_format),
+        )?;
+
+        let mut text_box = TextBox::new(Vec::new(), self.hidpi_scale);
+        text_box.font_size = FONT_SIZE;
+        let line_height = text_box.line_height(1.);
+        let top_line = pane.top_line(&self.positioner.source_lines, self.scroll_y);
+        let first_line = top_line.floor() as usize;
+        let num_lines = (screen_height / line_height).ceil() as usize + 1;
+
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let mut gutter_color = text_color;
+        gutter_color[3] *= 0.5;
+        let gutter_width = pane.lines.len().max(1).to_string().len();
+        for (i, line) in pane
+            .lines
+            .iter()
+            .enumerate()
+            .skip(first_line)
+            .take(num_lines)
+        {
+            let gutter = format!("{:>gutter_width$}  ", i + 1);
+            for (text, color) in [(gutter, gutter_color), (format!("{line}\n"), text_color)] {
+                let text =
+                    Text::new(text, self.hidpi_scale, color).with_family(FamilyOwned::Monospace);
+                text_box.texts.push(text);
+            }
+        }
+
+        // Lines run off the edge instead of wrapping so that each one stays a single row
+        let top = -(top_line - first_line as f32) * line_height;
+        Ok(text_box.text_areas(
+            &mut self.text_system,
+            (left + padding, top),
+            (f32::INFINITY, f32::INFINITY),
+            1.,
+            0.,
+        ))
+    }
+
+    const TOC_FONT_SIZE: f32 = 14.;
+
+    /// Where the table of contents starts on the screen, how far its rows are inset, and how tall
+    /// each row is. The first row is its title
+    fn toc_layout(&self) -> (f32, f32, f32) {
+        let left = self.screen_size().0 - self.positioner.toc_width();
+        let padding = 8. * self.hidpi_scale;
+        let mut text_box = TextBox::new(Vec::new(), self.hidpi_scale);
+        text_box.font_size = Self::TOC_FONT_SIZE;
+        let line_height = text_box.line_height(1.);
+        (left, padding, line_height)
+    }
+
+    /// How many headings fit in the table of contents below its title
+    fn toc_rows(&self) -> usize {
+        let (_, padding, line_height) = self.toc_layout();
+        let rows = ((self.screen_height() - padding * 2.) / line_height).floor() as usize;
+        rows.saturating_sub(1)
+    }
+
+    /// Whether `pos` on the screen is over the table of contents
+    pub fn is_over_toc(&self, pos: Point) -> bool {
+        self.toc.is_some() && pos.0 >= self.toc_layout().0
+    }
+
+    /// The heading listed in the table of contents at `pos` on the screen
+    pub fn toc_entry_at(&self, pos: Point) -> Option<usize> {
+        if !self.is_over_toc(pos) {
+            return None;
+        }
+        let toc = self.toc.as_ref()?;
+        let (_, padding, line_height) = self.toc_layout();
+        let row = ((pos.1 - padding) / line_height).floor();
+        let rows = self.toc_rows();
+        if row < 1. || row as usize > rows {
+            return None;
+        }
+        let current = toc.current(&self.positioner.anchors, self.scroll_y);
+        let index = toc.first_shown(current, rows) + row as usize - 1;
+        (index < toc.headings.len()).then_some(index)
+    }
+
+    // Like the source pane, the table of contents only scales with the window and not zoom
+    fn draw_toc(&mut self, toc: &Toc) -> anyhow::Result<CachedTextArea> {
+        let (screen_width, screen_height) = self.screen_size();
+        let (left, padding, line_height) = self.toc_layout();
+        self.draw_rectangle(
+            Rect::new((left, 0.), (screen_width - left, screen_height)),
+            native_color(self.theme.quote_block_color, &self.surface_format),
+        )?;
+        self.draw_rectangle(
+            Rect::new((left, 0.), (2. * self.hidpi_scale, screen_height)),
+            native_color(self.theme.select_color, &self.surface_format),
+        )?;
+
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let title =
+            Text::new("Contents\n".to_owned(), self.hidpi_scale, text_color).make_bold(true);
+        let mut text_box = TextBox::new(vec![title], self.hidpi_scale);
+        text_box.font_size = Self::TOC_FONT_SIZE;
+        if toc.headings.is_empty() {
+            let text = Text::new("No headings".to_owned(), self.hidpi_scale, text_color);
+            text_box.texts.push(text);
+        }
+
+        let rows = self.toc_rows();
+        let current = toc.current(&self.positioner.anchors, self.scroll_y);
+        let first = toc.first_shown(current, rows);
+        for (i, heading) in toc.headings.iter().enumerate().skip(first).take(rows) {
+            let is_current = Some(i) == current;
+            if is_current {
+                let row = (1 + i - first) as f32;
+                self.draw_rectangle(
+                    Rect::new(
+                        (left + 2. * self.hidpi_scale, padding + row * line_height),
+                        (screen_width - left, line_height),
+                    ),
+                    native_color(self.theme.select_color, &self.surface_format),
+                )?;
+            }
+            let indent = "  ".repeat(usize::from(heading.level.saturating_sub(1)));
+            let line = format!("{indent}{}\n", heading.title);
+            let text = Text::new(line, self.hidpi_scale, text_color).make_bold(is_current);
+            text_box.texts.push(text);
+        }
+
+        // Titles run off the edge instead of wrapping so that each one stays a single row
+        Ok(text_box.text_areas(
+            &mut self.text_system,
+            (left + padding, padding),
+            (f32::INFINITY, f32::INFINITY),
+            1.,
+            0.,
+        ))
+    }
+
+    // Panels are docked to the bottom of the screen and only scale with the window, not zoom
+    fn draw_panel(&mut self, panel: &Panel) -> anyhow::Result<Vec<CachedTextArea>> {
+        let (screen_width, screen_height) = self.screen_size();
+        let padding = 8. * self.hidpi_scale;
+        let text_color = native_color(self.theme.text_color, &self.surface_format);
+        let bounds = ((screen_width - padding * 2.).max(0.), f32::INFINITY);
+
+        let title = Text::new(panel.title.clone(), self.hidpi_scale, text_color).make_bold(true);
+        let mut text_boxes = vec![TextBox::new(vec![title], self.hidpi_scale)];
+        let mut selected_box = None;
+        for (line, is_selected) in panel.visible_lines() {
+            if is_selected {
+                selected_box = Some(text_boxes.len());
+            }
+            let text = Text::new(line, self.hidpi_scale, text_color);
+            text_boxes.push(TextBox::new(vec![text], self.hidpi_scale));
+        }
+        let heights: Vec<_> = text_boxes
+            .iter()
+            .map(|text_box| text_box.size(&mut self.text_system, bounds, 1.).1)
+            .collect();
+        let height = heights.iter().sum::<f32>() + padding * 2.;
+        let top = (screen_height - height).max(0.);
+
+        self.draw_rectangle(
+            Rect::new((0., top), (screen_width, height)),
+            native_color(self.theme.quote_block_color, &self.surface_format),
+        )?;
+        self.draw_rectangle(
+            Rect::new((0., top), (screen_width, 2. * self.hidpi_scale)),
+            native_color(self.theme.select_color, &self.surface_format),
+        )?;
+        if let Some(index) = selected_box {
+            let y = top + padding + heights[..index].iter().sum::<f32>();
+            self.draw_rectangle(
+                Rect::new((0., y), (screen_width, heights[index])),
+                native_color(self.theme.select_color, &self.surface_format),
+            )?;
+        }
+
+        let mut y = top + padding;
+        let mut text_areas = Vec::with_capacity(text_boxes.len());
+        for (i, (text_box, height)) in text_boxes.iter().zip(heights).enumerate() {
+            // The title isn't one of the lines
+            if i > 0 {
+                self.panel_lines
+                    .push(Rect::new((0., y), (screen_width, height)));
+            }
+            text_areas.push(text_box.text_areas(
+                &mut self.text_system,
+                (padding, y),
+                bounds,
+                1.,
+                0.,
+            ));
+            y += height;
+        }
+
+        Ok(text_areas)
+    }
+
+    // Checkers stay the same size on screen instead of shrinking along with scaled down images
+    /// Draws the language badge and copy button in the top right corner of a code block. `block`
+    /// is where the block was drawn on the screen
+    fn draw_code_block_header(
+        &mut self,
+        text_box: &TextBox,
+        block: Rect,
+        block_color: [f32; 4],
+    ) -> anyhow::Result<CachedTextArea> {
+        const FONT_SIZE: f32 = 12.;
+
+        let scale = self.hidpi_scale * self.zoom;
+        let padding = 6. * scale;
+        let icon_size = 12. * scale;
+        let icon_pos = (block.max().0 - padding - icon_size, block.pos.1 + padding);
+        let button = Rect::new(
+            (
+                icon_pos.0 - padding / 2.,
+                icon_pos.1 - padding / 2. + self.scroll_y,
+            ),
+            (icon_size + padding, icon_size + padding),
+        );
+        let copied = self
+            .copied_code
+            .as_ref()
+            .filter(|(copied, at)| {
+                // Scrolling can shift the button around by a rounding error
+                let moved =
+                    (copied.pos.0 - button.pos.0).abs() + (copied.pos.1 - button.pos.1).abs();
+                moved < 1. && at.elapsed() < COPIED_FLASH
+            })
+            .is_some();
+        self.code_copy_buttons
+            .push((button, text_box.code_source.clone().unwrap_or_default()));
+
+        let color = native_color(self.theme.text_color, &self.surface_format);
+        let faded = [color[0], color[1], color[2], color[3] * 0.6];
+        // Two overlapping pages
+        let page = icon_size * 0.7;
+        let offset = icon_size - page;
+        self.stroke_rectangle(Rect::new(icon_pos, (page, page)), faded, scale)?;
+        let front = Rect::new((icon_pos.0 + offset, icon_pos.1 + offset), (page, page));
+        self.draw_rectangle(front.clone(), block_color)?;
+        self.stroke_rectangle(front, faded, scale)?;
+
+        let label = if copied {
+            "Copied!".to_owned()
+        } else {
+            text_box.code_language.clone().unwrap_or_default()
+        };
+        let mut text_box = TextBox::new(
+            vec![Text::new(label, self.hidpi_scale, faded)],
+            self.hidpi_scale,
+        );
+        text_box.font_size = FONT_SIZE;
+        let unbounded = (f32::INFINITY, f32::INFINITY);
+        let size = text_box.size(&mut self.text_system, unbounded, self.zoom);
+        let pos = (
+            icon_pos.0 - padding - size.0,
+            icon_pos.1 + (icon_size - size.1) / 2.,
+        );
+        Ok(text_box.text_areas(&mut self.text_system, pos, unbounded, self.zoom, 0.))
+    }
+
+    fn draw_image_backdrop(&mut self, bounds: Rect) -> anyhow::Result<()> {
+        const CHECKER_SIZE: f32 = 8.;
+
+        match self.theme.image_backdrop {
+            ImageBackdrop::None => {}
+            ImageBackdrop::Solid(color) => {
+                self.draw_rectangle(bounds, native_color(color, &self.surface_format))?;
+            }
+            ImageBackdrop::Checkerboard => {
+                let light = native_color(ImageBackdrop::CHECKER_LIGHT, &self.surface_format);
+                let dark = native_color(ImageBackdrop::CHECKER_DARK, &self.surface_format);
+                let max = bounds.max();
+                let screen_height = self.screen_size().1;
+                let checker = CHECKER_SIZE * self.hidpi_scale * self.zoom;
+                self.draw_rectangle(bounds.clone(), light)?;
+
+                // Only the checkers that are on screen
+                let first_row = (-bounds.pos.1 / checker).floor().max(0.) as usize;
+                for row in first_row.. {
+                    let y = bounds.pos.1 + row as f32 * checker;
+                    if y >= max.1 || y >= screen_height {
+                        break;
+                    }
+                    for col in (row % 2..).step_by(2) {
+                        let x = bounds.pos.0 + col as f32 * checker;
+                        if x >= max.0 {
+                            break;
+                        }
+                        let size = (checker.min(max.0 - x), checker.min(max.1 - y));
+                        self.draw_rectangle(Rect::new((x, y), size), dark)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_hidden_marker(
+        &mut self,
+        pos: Point,
+        size: f32,
+        color: [f32; 4],
+        hidden: bool,
+    ) -> anyhow::Result<()> {
+        let points = if hidden {
+            [
+                point(pos.0, pos.1, self.screen_size()).into(),
+                point(pos.0 - size, pos.1 + size, self.screen_size()).into(),
+                point(pos.0 - size, pos.1 - size, self.screen_size()).into(),
+            ]
+        } else {
+            [
+                point(pos.0, pos.1 - size / 2., self.screen_size()).into(),
+                point(pos.0 - size * 2., pos.1 - size / 2., self.screen_size()).into(),
+                point(pos.0 - size, pos.1 + size / 2., self.screen_size()).into(),
+            ]
+        };
+        let triangle = Polygon {
+            points: &points,
+            closed: true,
+        };
+        let mut fill_tessellator = FillTessellator::new();
+        fill_tessellator.tessellate_polygon(
+            triangle,
+            &FillOptions::default(),
+            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: FillVertex| Vertex {
+                pos: [vertex.position().x, vertex.position().y, 0.0],
+                color,
+            }),
+        )?;
+        Ok(())
+    }
+
+    fn draw_rectangle(&mut self, rect: Rect, color: [f32; 4]) -> anyhow::Result<()> {
+        let min = point(rect.pos.0, rect.pos.1, self.screen_size());
+        let max = point(rect.max().0, rect.max().1, self.screen_size());
+        let mut fill_tessellator = FillTessellator::new();
+        fill_tessellator.tessellate_rectangle(
+            &Box2D::new(Point2D::from(min), Point2D::from(max)),
+            &FillOptions::default(),
+            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: FillVertex| Vertex {
+                pos: [vertex.position().x, vertex.position().y, 0.0],
+                color,
+            }),
+        )?;
+        Ok(())
+    }
+
+    fn stroke_rectangle(&mut self, rect: Rect, color: [f32; 4], width: f32) -> anyhow::Result<()> {
+        let mut stroke_tessellator = StrokeTessellator::new();
+        let screen_size = self.screen_size();
+        stroke_tessellator.tessellate_rectangle(
+            &Box2D::new(Point2D::from(rect.pos), Point2D::from(rect.max())),
+            &StrokeOptions::default().with_line_width(width),
+            &mut BuffersBuilder::new(&mut self.lyon_buffer, |vertex: StrokeVertex| {
+                let point = point(vertex.position().x, vertex.position().y, screen_size);
+                Vertex {
+                    pos: [point[0], point[1], 0.0],
+                    color,
+                }
+            }),
+        )?;
+        Ok(())
+    }
+
+    fn draw_tick(
+        &mut self,
+        pos: Point,
+        box_size: f32,
+        color: [f32; 4],
+        width: f32,
+    ) -> anyhow::Result<()> {
+        let screen_size = self.screen_size();
+        let mut stroke_tessellator = StrokeTessellator::new();
+        let stroke_opts = StrokeOptions::default().with_line_width(width);
+        let mut vertex_builder =
+            BuffersBuilder::new(&mut self.lyon_buffer, |vertex: StrokeVertex| {
+                let point = point(vertex.position().x, vertex.position().y, screen_size);
+                Vertex {
+                    pos: [point[0], point[1], 0.0],
+                    color,
+                }
+            });
+        let mut builder = stroke_tessellator.builder(&stroke_opts, &mut vertex_builder);
+
+        // Build a simple path.
+        builder.begin((pos.0 + box_size * 0.2, pos.1 + box_size * 0.5).into());
+        builder.line_to((pos.0 + box_size * 0.4, pos.1 + box_size * 0.7).into());
+        builder.line_to((pos.0 + box_size * 0.8, pos.1 + box_size * 0.2).into());
+        builder.end(false);
+        builder.build()?;
+        Ok(())
+    }
+
+    fn image_bindgroups(&mut self, elements: &mut [Positioned<Element>]) -> Vec<ImageBatch> {
+        let screen_size = self.screen_size();
+        let scroll_y = self.scroll_y;
+        let zoom = self.zoom;
+        let elapsed = self.animate_images.then(|| self.animation_start.elapsed());
+        let frame = self.textures.next_frame();
+        let max_side = self.device.limits().max_texture_dimension_2d;
+        let mut until_next_frame: Option<Duration> = None;
+        let Self {
+            device,
+            queue,
+            image_renderer,
+            ..
+        } = self;
+        image_renderer.atlas.next_frame();
+        let mut bind_groups = Vec::new();
+        let mut atlas_quads: Vec<[ImageVertex; 4]> = Vec::new();
+        let mut push_image = |image: &mut Image, pos: Point, size: Size| {
+            image.last_drawn = frame;
+            image.refresh_svg(zoom);
+            if let Some(slot) = image.atlas_slot(&mut image_renderer.atlas, queue) {
+                let transform = image.transform.unwrap_or_default();
+                atlas_quads.push(ImageRenderer::quad(
+                    pos,
+                    size,
+                    screen_size,
+                    slot.uv,
+                    transform,
+                ));
+                return;
+            }
+            image.refresh_resolution(size, max_side);
+            let Some(bind_group) = image.bind_group.clone().or_else(|| {
+                image.create_bind_group(
+                    device,
+                    queue,
+                    &image_renderer.sampler,
+                    &image_renderer.bindgroup_layout,
+                    size,
+                )
+            }) else {
+                return;
+            };
+            if let Some(until_next) = elapsed.and_then(|elapsed| image.animate(queue, elapsed)) {
+                until_next_frame =
+                    Some(until_next_frame.map_or(until_next, |soonest| soonest.min(until_next)));
+            }
+            bind_groups.push(ImageBatch {
+                bind_group,
+                vertex_buf: ImageRenderer::vertex_buf(
+                    device,
+                    pos,
+                    size,
+                    screen_size,
+                    image.transform.unwrap_or_default(),
+                ),
+                quads: 1,
+            });
+        };
+
+        for element in elements.iter_mut() {
+            let Rect { pos, size } = element.bounds.as_ref().unwrap();
+            let pos = (pos.0, pos.1 - scroll_y);
+            if pos.1 + size.1 <= 0. {
+                continue;
+            } else if pos.1 >= screen_size.1 {
+                break;
+            }
+            match &mut element.inner {
+                Element::Image(ref mut image) => push_image(image, pos, *size),
+                Element::Row(ref mut row) => {
+                    for element in row.elements.iter_mut() {
+                        let Rect { pos, size } = element.bounds.as_ref().unwrap();
+                        let pos = (pos.0, pos.1 - scroll_y);
+                        if let Element::Image(ref mut image) = &mut element.inner {
+                            push_image(image, pos, *size);
+                        }
+                    }
+                }
+                Element::Section(ref mut section) => {
+                    if *section.hidden.borrow() {
                         continue;
                     }
                     for element in section.elements.iter_mut() {
                         let Rect { pos, size } = element.bounds.as_ref().unwrap();
-                        let pos = (pos.0, pos.1 - self.scroll_y);
+                        let pos = (pos.0, pos.1 - scroll_y);
                         if let Element::Image(ref mut image) = &mut element.inner {
-                            if let Some(bind_group) = image.bind_group.clone().or_else(|| {
-                                image.create_bind_group(
-                                    &self.device,
-                                    &self.queue,
-                                    &self.image_renderer.sampler,
-                                    &self.image_renderer.bindgroup_layout,
-                                )
-                            }) {
-                                let vertex_buf = ImageRenderer::vertex_buf(
-                                    &self.device,
-                                    pos,
-                                    *size,
-                                    screen_size,
-                                );
-                                bind_groups.push((bind_group.clone(), vertex_buf));
-                            }
+                            push_image(image, pos, *size);
                         }
                     }
                 }
                 _ => {}
             }
         }
+        self.next_animation_frame = until_next_frame.map(|until_next| Instant::now() + until_next);
+
+        // Everything in the atlas gets drawn together instead of one at a time
+        for quads in atlas_quads.chunks(ImageRenderer::MAX_BATCH_QUADS) {
+            bind_groups.push(ImageBatch {
+                bind_group: Arc::clone(&self.image_renderer.atlas.bind_group),
+                vertex_buf: ImageRenderer::batch_vertex_buf(&self.device, quads),
+                quads: quads.len() as u32,
+            });
+        }
+
+        let mut images = Vec::new();
+        collect_images(elements, &mut images);
+        self.textures.evict(images);
+
+        bind_groups
+    }
+
+    /// Starts loading the deferred images that are getting close to the view, closest first
+    fn load_nearby_images(&mut self, elements: &mut [Positioned<Element>]) {
+        let mut images = Vec::new();
+        collect_positioned_images(elements, &mut images);
+        images.retain(|(_, image)| image.is_deferred());
+        if images.is_empty() {
+            return;
+        }
+
+        let bounds: Vec<_> = images.iter().map(|(bounds, _)| *bounds).collect();
+        let view_bottom = self.scroll_y + self.screen_height();
+        let order = image::load_order(&bounds, self.scroll_y, view_bottom, self.preload_screens);
+        tracing::debug!("Starting to load {} nearby images", order.len());
+        for index in order {
+            let (bounds, image) = &mut images[index];
+            let visible = bounds.pos.1 < view_bottom && bounds.max().1 > self.scroll_y;
+            let priority = if visible {
+                Priority::Visible
+            } else {
+                Priority::Normal
+            };
+            image.start_loading(priority);
+        }
+    }
+
+    // The dimmed backdrop followed by the image itself
+    fn lightbox_bindgroups(&mut self) -> Vec<ImageBatch> {
+        const DIM_COLOR: [u8; 4] = [0, 0, 0, 220];
+
+        let screen_size = self.screen_size();
+        let elapsed = self.animate_images.then(|| self.animation_start.elapsed());
+        let Some(lightbox) = &mut self.lightbox else {
+            return Vec::new();
+        };
+
+        let backdrop = self
+            .image_renderer
+            .color_bind_group(&self.device, &self.queue, DIM_COLOR);
+        let backdrop_buf = ImageRenderer::vertex_buf(
+            &self.device,
+            (0., 0.),
+            screen_size,
+            screen_size,
+            ImageTransform::default(),
+        );
+        let mut bind_groups = vec![ImageBatch {
+            bind_group: Arc::new(backdrop),
+            vertex_buf: backdrop_buf,
+            quads: 1,
+        }];
+
+        let Some(bounds) = lightbox.bounds(screen_size) else {
+            return bind_groups;
+        };
+        let image = &mut lightbox.image;
+        image.refresh_resolution(bounds.size, self.device.limits().max_texture_dimension_2d);
+        let bind_group = image.bind_group.clone().or_else(|| {
+            image.create_bind_group(
+                &self.device,
+                &self.queue,
+                &self.image_renderer.sampler,
+                &self.image_renderer.bindgroup_layout,
+                bounds.size,
+            )
+        });
+        if let Some(bind_group) = bind_group {
+            if let Some(until_next) =
+                elapsed.and_then(|elapsed| image.animate(&self.queue, elapsed))
+            {
+                let next_frame = Instant::now() + until_next;
+                self.next_animation_frame = Some(
+                    self.next_animation_frame
+                        .map_or(next_frame, |soonest| soonest.min(next_frame)),
+                );
+            }
+            bind_groups.push(ImageBatch {
+                bind_group,
+                vertex_buf: ImageRenderer::vertex_buf(
+                    &self.device,
+                    bounds.pos,
+                    bounds.size,
+                    screen_size,
+                    image.transform.unwrap_or_default(),
+                ),
+                quads: 1,
+            });
+        }
+
         bind_groups
     }
 
-    pub fn redraw(&mut self, elements: &mut [Positioned<Element>]) -> anyhow::Result<()> {
+    // A frame around the crop followed by the crop itself
+    fn loupe_bindgroups(&mut self) -> Vec<ImageBatch> {
+        const BORDER_COLOR: [u8; 4] = [0, 0, 0, 160];
+
+        let screen_size = self.screen_size();
+        let Some(loupe) = &mut self.loupe else {
+            return Vec::new();
+        };
+        let Some((bounds, uv)) = loupe.view(self.scroll_y, screen_size) else {
+            return Vec::new();
+        };
+        let image = &mut loupe.image;
+        // Sized to the whole image so that it gets uploaded at full resolution
+        let Some(full_size) = image
+            .shown_dimensions()
+            .map(|(width, height)| (width as f32, height as f32))
+        else {
+            return Vec::new();
+        };
+        let Some(bind_group) = image.bind_group.clone().or_else(|| {
+            image.create_bind_group(
+                &self.device,
+                &self.queue,
+                &self.image_renderer.sampler,
+                &self.image_renderer.bindgroup_layout,
+                full_size,
+            )
+        }) else {
+            return Vec::new();
+        };
+
+        let border = loupe::BORDER * self.hidpi_scale;
         let frame = self
-            .surface
-            .get_current_texture()
-            .context("Failed to acquire next swap chain texture")?;
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+            .image_renderer
+            .color_bind_group(&self.device, &self.queue, BORDER_COLOR);
+        let frame_buf = ImageRenderer::vertex_buf(
+            &self.device,
+            (bounds.pos.0 - border, bounds.pos.1 - border),
+            (bounds.size.0 + 2. * border, bounds.size.1 + 2. * border),
+            screen_size,
+            ImageTransform::default(),
+        );
+        let crop = ImageRenderer::quad(
+            bounds.pos,
+            bounds.size,
+            screen_size,
+            uv,
+            image.transform.unwrap_or_default(),
+        );
+        vec![
+            ImageBatch {
+                bind_group: Arc::new(frame),
+                vertex_buf: frame_buf,
+                quads: 1,
+            },
+            ImageBatch {
+                bind_group,
+                vertex_buf: ImageRenderer::batch_vertex_buf(&self.device, &[crop]),
+                quads: 1,
+            },
+        ]
+    }
+
+    /// Switches to other fonts, which leaves all of the text that was laid out with the old ones
+    /// to be laid out again
+    pub fn set_font_options(&mut self, font_opts: &FontOptions) {
+        *self.text_system.font_system.lock().unwrap() = get_fonts(font_opts);
+        let mut text_cache = TextCache::new();
+        text_cache.fallbacks = FontFallbacks::new(font_opts.fallback_fonts.clone());
+        *self.text_system.text_cache.lock().unwrap() = text_cache;
+        self.text_system.swash_cache = SwashCache::new();
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.config.width = width;
+        self.config.height = height;
+        self.positioner.screen_size = (width as f32, height as f32);
+        match &mut self.target {
+            RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+            RenderTarget::Texture(texture) => {
+                *texture = Self::target_texture(&self.device, &self.config)
+            }
+        }
+    }
+
+    /// Tessellates the document in view into [`Renderer::lyon_buffer`] and lays out its text
+    /// without drawing anything, for turning it into something other than a frame
+    pub fn tessellate(
+        &mut self,
+        elements: &[Positioned<Element>],
+    ) -> anyhow::Result<Vec<CachedTextArea>> {
+        self.lyon_buffer.indices.clear();
+        self.lyon_buffer.vertices.clear();
+        self.selection_text = String::new();
+        self.code_copy_buttons.clear();
+        self.render_elements(elements)
+    }
+
+    /// Copies the last frame drawn to the offscreen texture back from the GPU
+    pub fn read_frame(&self) -> anyhow::Result<::image::RgbaImage> {
+        let RenderTarget::Texture(texture) = &self.target else {
+            anyhow::bail!("Only frames drawn offscreen can be read back");
+        };
+        let (width, height) = (self.config.width, self.config.height);
+        let row_bytes = width * 4;
+        // Rows of the copy have to line up with the alignment wgpu wants
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_bytes = (row_bytes + align - 1) / align * align;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Readback Buffer"),
+            size: u64::from(padded_row_bytes) * u64::from(height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |mapped| {
+            let _ = sender.send(mapped);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .context("The frame was never copied back")?
+            .context("Failed to read the frame back")?;
+
+        let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        buffer.unmap();
+        ::image::RgbaImage::from_raw(width, height, pixels)
+            .context("The frame didn't match its own size")
+    }
+
+    /// Draws the elements to a texture instead of the window and reads them back, leaving out
+    /// everything that's only there to interact with the document
+    pub fn draw_offscreen(
+        &mut self,
+        elements: &mut [Positioned<Element>],
+    ) -> anyhow::Result<::image::RgbaImage> {
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            ..self.config.clone()
+        };
+        let texture = RenderTarget::Texture(Self::target_texture(&self.device, &config));
+        let target = std::mem::replace(&mut self.target, texture);
+        let panel = self.panel.take();
+        let hovered_link = self.hovered_link.take();
+        let selection = self.selection.take();
+        let lightbox = self.lightbox.take();
+        let loupe = self.loupe.take();
+        let scrollbar = std::mem::replace(&mut self.scrollbar, false);
+        let selection_text = std::mem::take(&mut self.selection_text);
+        let code_copy_buttons = std::mem::take(&mut self.code_copy_buttons);
+
+        let drawn = match self.redraw(elements) {
+            Result::Ok(Frame::Drawn) => self.read_frame(),
+            Result::Ok(Frame::Skipped) => Err(anyhow::anyhow!("The frame was skipped")),
+            Err(err) => Err(err),
+        };
+
+        self.target = target;
+        self.panel = panel;
+        self.hovered_link = hovered_link;
+        self.selection = selection;
+        self.lightbox = lightbox;
+        self.loupe = loupe;
+        self.scrollbar = scrollbar;
+        self.selection_text = selection_text;
+        self.code_copy_buttons = code_copy_buttons;
+
+        let mut frame = drawn?;
+        if matches!(
+            self.surface_format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in frame.pixels_mut() {
+                pixel.0.swap(0, 2);
+            }
+        }
+        Ok(frame)
+    }
+
+    /// Draws a frame, first recreating everything on the GPU if the device was lost
+    ///
+    /// Everything the frame does on the GPU is checked for errors before it gets submitted, since
+    /// wgpu panics when submitting work that a lost device rejected. Those errors are taken as the
+    /// device being lost, because a working device doesn't produce any
+    pub fn redraw(&mut self, elements: &mut [Positioned<Element>]) -> anyhow::Result<Frame> {
+        let recovered = self.device_lost;
+        if recovered {
+            self.recover_device(elements)?;
+        }
+
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let encoded = self.encode_frame(elements);
+        let validation = pollster::block_on(self.device.pop_error_scope());
+        let out_of_memory = pollster::block_on(self.device.pop_error_scope());
+        let Some((commands, frame)) = encoded? else {
+            return Ok(Frame::Skipped);
+        };
+        if let Some(err) = validation.or(out_of_memory) {
+            // A new device failing the same way means it's not going to get any better
+            anyhow::ensure!(
+                !recovered,
+                "Failed to draw on a recreated GPU device: {err}"
+            );
+            tracing::warn!("GPU error while drawing: {err}");
+            self.device_lost = true;
+            return Ok(Frame::Skipped);
+        }
+
+        self.queue.submit(Some(commands));
+        if let Some(frame) = frame {
+            frame.present();
+        }
+        self.text_system.text_atlas.trim();
+
+        Ok(Frame::Drawn)
+    }
+
+    /// Swaps in a new device after the old one went away, like from a driver update or GPU reset
+    fn recover_device(&mut self, elements: &mut [Positioned<Element>]) -> anyhow::Result<()> {
+        tracing::warn!("Lost the GPU device. Recreating it");
+        self.device_lost = false;
+        let surface = match &self.target {
+            RenderTarget::Surface(surface) => Some(surface),
+            RenderTarget::Texture(_) => None,
+        };
+        let Gpu {
+            device,
+            queue,
+            render_pipeline,
+            image_renderer,
+            text_atlas,
+            text_renderer,
+            overlay_text_renderer,
+        } = pollster::block_on(async {
+            let adapter = gpu::request_adapter(&self.instance, surface, false).await?;
+            Gpu::new(&self.instance, adapter, surface, self.surface_format).await
+        })
+        .context("Failed to recreate the GPU device")?;
+        self.device = device;
+        self.queue = queue;
+        self.render_pipeline = render_pipeline;
+        self.image_renderer = image_renderer;
+        self.text_system.text_atlas = text_atlas;
+        self.text_system.text_renderer = text_renderer;
+        self.overlay_text_renderer = overlay_text_renderer;
+        // Sets up the swap chain or offscreen texture again on the new device
+        self.resize(self.config.width, self.config.height);
+        // Image textures lived on the old device, so they get uploaded again from the decoded
+        // image data that's still around
+        forget_image_textures(elements);
+        if let Some(lightbox) = &mut self.lightbox {
+            lightbox.image.forget_texture();
+        }
+        if let Some(loupe) = &mut self.loupe {
+            loupe.image.forget_texture();
+        }
+
+        Ok(())
+    }
+
+    /// Gets the next texture to draw to from the swap chain. `None` means the frame should be
+    /// skipped, but trying again later should work
+    ///
+    /// A surface that's still lost after making a new swap chain means that the device went away,
+    /// so `device_lost` gets set
+    fn acquire_frame(
+        surface: &wgpu::Surface,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        device_lost: &mut bool,
+    ) -> anyhow::Result<Option<wgpu::SurfaceTexture>> {
+        match surface.get_current_texture() {
+            Result::Ok(frame) => return Ok(Some(frame)),
+            // Things changed out from under the swap chain, so a new one should sort it out
+            Err(err @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                tracing::debug!("Recreating the swap chain: {err}");
+                surface.configure(device, config);
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                tracing::warn!("Timed out acquiring the next swap chain texture. Skipping a frame");
+                return Ok(None);
+            }
+            Err(err @ wgpu::SurfaceError::OutOfMemory) => {
+                return Err(err).context("Failed to acquire next swap chain texture");
+            }
+        }
+
+        match surface.get_current_texture() {
+            Result::Ok(frame) => Ok(Some(frame)),
+            Err(err @ wgpu::SurfaceError::OutOfMemory) => {
+                Err(err).context("Failed to acquire next swap chain texture")
+            }
+            Err(wgpu::SurfaceError::Lost) => {
+                tracing::warn!("The surface is still lost after recreating the swap chain");
+                *device_lost = true;
+                Ok(None)
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Failed to acquire next swap chain texture: {err}. Skipping a frame"
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Records everything for a frame without submitting it. `None` means the frame should be
+    /// skipped
+    fn encode_frame(
+        &mut self,
+        elements: &mut [Positioned<Element>],
+    ) -> anyhow::Result<Option<(wgpu::CommandBuffer, Option<wgpu::SurfaceTexture>)>> {
+        let frame = match &self.target {
+            RenderTarget::Surface(surface) => {
+                let acquired = Self::acquire_frame(
+                    surface,
+                    &self.device,
+                    &self.config,
+                    &mut self.device_lost,
+                )?;
+                match acquired {
+                    Some(frame) => Some(frame),
+                    None => return Ok(None),
+                }
+            }
+            RenderTarget::Texture(_) => None,
+        };
+        let view = match (&frame, &self.target) {
+            (Some(frame), _) => &frame.texture,
+            (None, RenderTarget::Texture(texture)) => texture,
+            (None, RenderTarget::Surface(_)) => unreachable!("Surfaces always have a frame"),
+        }
+        .create_view(&wgpu::TextureViewDescriptor::default());
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
@@ -730,7 +2266,9 @@ impl Renderer {
         self.lyon_buffer.indices.clear();
         self.lyon_buffer.vertices.clear();
         self.selection_text = String::new();
+        self.code_copy_buttons.clear();
         let cached_text_areas = self.render_elements(elements)?;
+        self.draw_search_highlights()?;
         let vertex_buf = self
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -746,14 +2284,33 @@ impl Renderer {
                 usage: wgpu::BufferUsages::INDEX,
             });
 
+        let overlay_text_areas = self.render_overlays(elements)?;
+        let overlay_vertex_buf =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Overlay Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&self.overlay_lyon_buffer.vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+        let overlay_index_buffer =
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Overlay Index Buffer"),
+                    contents: bytemuck::cast_slice(&self.overlay_lyon_buffer.indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+
+        self.load_nearby_images(elements);
         // Prepare image bind groups for drawing
         let image_bindgroups = self.image_bindgroups(elements);
+        let mut lightbox_bindgroups = self.lightbox_bindgroups();
+        lightbox_bindgroups.extend(self.loupe_bindgroups());
 
         {
             let mut text_cache = self.text_system.text_cache.lock().unwrap();
             let text_areas: Vec<TextArea> = cached_text_areas
                 .iter()
-                .map(|c| c.text_area(&text_cache))
+                .flat_map(|c| c.text_areas(&text_cache))
                 .collect();
 
             self.text_system.text_renderer.prepare(
@@ -768,6 +2325,23 @@ impl Renderer {
                 text_areas,
                 &mut self.text_system.swash_cache,
             )?;
+
+            let overlay_text_areas: Vec<TextArea> = overlay_text_areas
+                .iter()
+                .flat_map(|c| c.text_areas(&text_cache))
+                .collect();
+            self.overlay_text_renderer.prepare(
+                &self.device,
+                &self.queue,
+                &mut self.text_system.font_system.lock().unwrap(),
+                &mut self.text_system.text_atlas,
+                Resolution {
+                    width: self.config.width,
+                    height: self.config.height,
+                },
+                overlay_text_areas,
+                &mut self.text_system.swash_cache,
+            )?;
             text_cache.trim();
         }
 
@@ -803,23 +2377,53 @@ impl Renderer {
             // Draw images
             rpass.set_pipeline(&self.image_renderer.render_pipeline);
             rpass.set_index_buffer(self.image_renderer.index_buf.slice(..), IndexFormat::Uint16);
-            for (bindgroup, vertex_buf) in image_bindgroups.iter() {
-                rpass.set_bind_group(0, bindgroup, &[]);
-                rpass.set_vertex_buffer(0, vertex_buf.slice(..));
-                rpass.draw_indexed(0..6, 0, 0..1);
+            for batch in image_bindgroups.iter() {
+                rpass.set_bind_group(0, &batch.bind_group, &[]);
+                rpass.set_vertex_buffer(0, batch.vertex_buf.slice(..));
+                rpass.draw_indexed(0..batch.quads * 6, 0, 0..1);
             }
 
             self.text_system
                 .text_renderer
                 .render(&self.text_system.text_atlas, &mut rpass)
                 .unwrap();
+
+            // Draw any overlays on top of the document
+            if !self.overlay_lyon_buffer.indices.is_empty() {
+                rpass.set_pipeline(&self.render_pipeline);
+                rpass.set_vertex_buffer(0, overlay_vertex_buf.slice(..));
+                rpass.set_index_buffer(overlay_index_buffer.slice(..), IndexFormat::Uint16);
+                rpass.draw_indexed(0..self.overlay_lyon_buffer.indices.len() as u32, 0, 0..1);
+            }
+            self.overlay_text_renderer
+                .render(&self.text_system.text_atlas, &mut rpass)
+                .unwrap();
         }
 
-        self.queue.submit(Some(encoder.finish()));
-        frame.present();
-        self.text_system.text_atlas.trim();
+        if !lightbox_bindgroups.is_empty() {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Lightbox Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
 
-        Ok(())
+            rpass.set_pipeline(&self.image_renderer.render_pipeline);
+            rpass.set_index_buffer(self.image_renderer.index_buf.slice(..), IndexFormat::Uint16);
+            for batch in lightbox_bindgroups.iter() {
+                rpass.set_bind_group(0, &batch.bind_group, &[]);
+                rpass.set_vertex_buffer(0, batch.vertex_buf.slice(..));
+                rpass.draw_indexed(0..batch.quads * 6, 0, 0..1);
+            }
+        }
+
+        Ok(Some((encoder.finish(), frame)))
     }
 
     pub fn reposition(&mut self, elements: &mut [Positioned<Element>]) -> anyhow::Result<()> {
@@ -827,11 +2431,275 @@ impl Renderer {
             .reposition(&mut self.text_system, elements, self.zoom)
     }
 
+    /// Every match of `query` in `elements` in the order they're read
+    pub fn find_matches(
+        &mut self,
+        elements: &[Positioned<Element>],
+        query: &str,
+    ) -> Vec<Vec<Rect>> {
+        let doc_size = self.doc_size();
+        let centering = centering(doc_size.0, self.page_width);
+        let mut matches = Vec::new();
+        for element in elements {
+            let Some(Rect { pos, .. }) = element.bounds.as_ref() else {
+                continue;
+            };
+            match &element.inner {
+                Element::TextBox(text_box) => {
+                    let (pos, bounds) = self.text_box_bounds(text_box, *pos);
+                    matches.extend(text_box.find_matches(
+                        &mut self.text_system,
+                        query,
+                        pos,
+                        bounds,
+                        self.zoom,
+                    ));
+                }
+                Element::Table(table) => {
+                    let bounds = (
+                        (doc_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
+                        f32::INFINITY,
+                    );
+                    let Ok(layout) = table.layout(
+                        &mut self.text_system,
+                        &mut self.positioner.taffy,
+                        bounds,
+                        self.zoom,
+                    ) else {
+                        continue;
+                    };
+                    let headers = table.headers.iter().zip(&layout.headers);
+                    let cells = table
+                        .rows
+                        .iter()
+                        .zip(&layout.rows)
+                        .flat_map(|(row, node_row)| row.iter().zip(node_row));
+                    for (text_box, node) in headers.chain(cells) {
+                        matches.extend(text_box.find_matches(
+                            &mut self.text_system,
+                            query,
+                            (pos.0 + node.location.x, pos.1 + node.location.y),
+                            (node.size.width, f32::MAX),
+                            self.zoom,
+                        ));
+                    }
+                }
+                Element::Row(row) => matches.extend(self.find_matches(&row.elements, query)),
+                Element::Section(section) => {
+                    if let Some(ref summary) = *section.summary {
+                        matches.extend(self.find_matches(std::slice::from_ref(summary), query));
+                    }
+                    if !*section.hidden.borrow() {
+                        matches.extend(self.find_matches(&section.elements, query));
+                    }
+                }
+                Element::Image(_) | Element::Spacer(_) => {}
+            }
+        }
+        matches
+    }
+
+    /// Where every link in the document is along with where it goes, for places where they can't
+    /// get found by hovering over them
+    pub fn find_links(&mut self, elements: &[Positioned<Element>]) -> Vec<(Rect, String)> {
+        let doc_size = self.doc_size();
+        let centering = centering(doc_size.0, self.page_width);
+        let mut links = Vec::new();
+        for element in elements {
+            let Some(bounds) = element.bounds.as_ref() else {
+                continue;
+            };
+            let to_owned = |(rect, link): (Rect, &str)| (rect, link.to_owned());
+            match &element.inner {
+                Element::TextBox(text_box) => {
+                    let (pos, wrap) = self.text_box_bounds(text_box, bounds.pos);
+                    let area = text_box.text_areas(&mut self.text_system, pos, wrap, self.zoom, 0.);
+                    let spans =
+                        text_box.link_spans(&mut self.text_system, pos, wrap, self.zoom, &area);
+                    links.extend(spans.into_iter().map(to_owned));
+                }
+                Element::Table(table) => {
+                    let pos = bounds.pos;
+                    let wrap = (
+                        (doc_size.0 - pos.0 - DEFAULT_MARGIN - centering).max(0.),
+                        f32::INFINITY,
+                    );
+                    let Ok(layout) = table.layout(
+                        &mut self.text_system,
+                        &mut self.positioner.taffy,
+                        wrap,
+                        self.zoom,
+                    ) else {
+                        continue;
+                    };
+                    let headers = table.headers.iter().zip(&layout.headers);
+                    let cells = table
+                        .rows
+                        .iter()
+                        .zip(&layout.rows)
+                        .flat_map(|(row, node_row)| row.iter().zip(node_row));
+                    for (text_box, node) in headers.chain(cells) {
+                        let cell_pos = (pos.0 + node.location.x, pos.1 + node.location.y);
+                        let cell_wrap = (node.size.width, f32::MAX);
+                        let area = text_box.text_areas(
+                            &mut self.text_system,
+                            cell_pos,
+                            cell_wrap,
+                            self.zoom,
+                            0.,
+                        );
+                        let spans = text_box.link_spans(
+                            &mut self.text_system,
+                            cell_pos,
+                            cell_wrap,
+                            self.zoom,
+                            &area,
+                        );
+                        links.extend(spans.into_iter().map(to_owned));
+                    }
+                }
+                Element::Image(image) => {
+                    if let Some(link) = &image.is_link {
+                        links.push((bounds.clone(), link.clone()));
+                    }
+                }
+                Element::Row(row) => links.extend(self.find_links(&row.elements)),
+                Element::Section(section) => {
+                    if let Some(ref summary) = *section.summary {
+                        links.extend(self.find_links(std::slice::from_ref(summary)));
+                    }
+                    if !*section.hidden.borrow() {
+                        links.extend(self.find_links(&section.elements));
+                    }
+                }
+                Element::Spacer(_) => {}
+            }
+        }
+        links
+    }
+
+    /// The source line of the task list item whose checkbox is at `loc` in the document
+    pub fn checkbox_at(&self, elements: &[Positioned<Element>], loc: Point) -> Option<usize> {
+        elements.iter().find_map(|element| {
+            let Rect { pos, size } = element.bounds.as_ref()?;
+            match &element.inner {
+                Element::TextBox(text_box) => {
+                    text_box.is_checkbox?;
+                    let box_size = text_box.font_size * self.hidpi_scale * self.zoom * 0.75;
+                    let min = (pos.0, pos.1 + size.1 / 2. - box_size / 2.);
+                    let checkbox = Rect::new(min, (box_size, box_size));
+                    checkbox
+                        .contains(loc)
+                        .then_some(text_box.source_line)
+                        .flatten()
+                }
+                Element::Row(row) => self.checkbox_at(&row.elements, loc),
+                Element::Section(section) if !*section.hidden.borrow() => {
+                    self.checkbox_at(&section.elements, loc)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Where the text of `text_box` starts when it's positioned at `pos`, along with the bounds
+    /// that it wraps within. These match how it gets drawn
+    pub fn text_box_bounds(&self, text_box: &TextBox, mut pos: Point) -> (Point, Size) {
+        let doc_size = self.doc_size();
+        let centering = centering(doc_size.0, self.page_width);
+        if text_box.is_checkbox.is_some() {
+            let box_size = text_box.font_size * self.hidpi_scale * self.zoom * 0.75;
+            pos.0 += box_size * 1.5;
+        }
+        let bounds = (
+            (doc_size.0 - pos.0 - DEFAULT_MARGIN - centering - text_box.float_inset).max(0.),
+            f32::INFINITY,
+        );
+        pos.0 -= text_box.scroll_x;
+        (pos, bounds)
+    }
+
+    /// How far down the document can be scrolled
+    pub fn max_scroll_y(&self) -> f32 {
+        // The end of the document can be scrolled out from under the status bar
+        (self.positioner.reserved_height + self.status_bar_height() - self.screen_height()).max(0.)
+    }
+
     pub fn set_scroll_y(&mut self, scroll_y: f32) {
-        self.scroll_y = scroll_y.clamp(
-            0.,
-            (self.positioner.reserved_height - self.screen_height()).max(0.),
-        )
+        let scroll_y = match &self.slideshow {
+            Some(slideshow) => slideshow.scroll_y(scroll_y, self.screen_height()),
+            None => scroll_y.clamp(0., self.max_scroll_y()),
+        };
+        if scroll_y != self.scroll_y {
+            // Whatever was under the cursor has moved
+            self.hovered_link = None;
+            self.loupe = None;
+            self.scrollbar_active = Instant::now();
+        }
+        self.scroll_y = scroll_y;
+    }
+}
+
+/// Like [`collect_images()`], but only the images that have been positioned and aren't tucked away
+/// in a collapsed section
+pub fn collect_positioned_images<'a>(
+    elements: &'a mut [Positioned<Element>],
+    images: &mut Vec<(&'a Rect, &'a mut Image)>,
+) {
+    for Positioned { inner, bounds } in elements {
+        match inner {
+            Element::Image(image) => {
+                if let Some(bounds) = bounds {
+                    images.push((bounds, image));
+                }
+            }
+            Element::Row(row) => collect_positioned_images(&mut row.elements, images),
+            Element::Section(section) => {
+                if let Some(summary) = section.summary.as_mut() {
+                    collect_positioned_images(std::slice::from_mut(summary), images);
+                }
+                if !*section.hidden.borrow() {
+                    collect_positioned_images(&mut section.elements, images);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn collect_images<'a>(
+    elements: &'a mut [Positioned<Element>],
+    images: &mut Vec<&'a mut Image>,
+) {
+    for element in elements {
+        match &mut element.inner {
+            Element::Image(image) => images.push(image),
+            Element::Row(row) => collect_images(&mut row.elements, images),
+            Element::Section(section) => {
+                collect_images(&mut section.elements, images);
+                if let Some(summary) = section.summary.as_mut() {
+                    collect_images(std::slice::from_mut(summary), images);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Drops the textures of every image, so that they get uploaded again next time they're drawn
+fn forget_image_textures(elements: &mut [Positioned<Element>]) {
+    for element in elements {
+        match &mut element.inner {
+            Element::Image(image) => image.forget_texture(),
+            Element::Row(row) => forget_image_textures(&mut row.elements),
+            Element::Section(section) => {
+                forget_image_textures(&mut section.elements);
+                if let Some(summary) = section.summary.as_mut() {
+                    forget_image_textures(std::slice::from_mut(summary));
+                }
+            }
+            _ => {}
+        }
     }
 }
 
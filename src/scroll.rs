@@ -0,0 +1,206 @@
+//! Kinetic scrolling for touchpads and touchscreens that don't provide any momentum themselves,
+//! along with easing into place for everything that scrolls in steps
+
+use std::time::{Duration, Instant};
+
+/// How quickly the glide slows down. Roughly matches the feel of other platforms
+const TIME_CONSTANT: f32 = 0.325;
+/// Coasting stops once it's moving slower than this (in pixels per second)
+const MIN_VELOCITY: f32 = 20.0;
+/// Flicks need to be at least this fast to start coasting (in pixels per second)
+const MIN_RELEASE_VELOCITY: f32 = 200.0;
+/// Lifting your fingers after pausing shouldn't send the document flying
+const MAX_RELEASE_DELAY: Duration = Duration::from_millis(100);
+/// How quickly smooth scrolling closes in on its target. Short enough to still feel snappy
+const EASE_TIME_CONSTANT: f32 = 0.06;
+/// Smooth scrolling snaps to its target once it's within this many pixels
+const EASE_SNAP_DISTANCE: f32 = 0.5;
+
+#[derive(Debug, Default)]
+pub struct Momentum {
+    /// In pixels per second
+    velocity: f32,
+    last_event: Option<Instant>,
+    /// When the last coasting frame happened. `None` when not coasting
+    last_tick: Option<Instant>,
+}
+
+impl Momentum {
+    /// Keeps track of how fast the user is scrolling
+    pub fn track(&mut self, num_pixels: f32, now: Instant) {
+        self.last_tick = None;
+        let instant_velocity = match self.last_event {
+            Some(last) if now - last < MAX_RELEASE_DELAY => {
+                let elapsed = (now - last).as_secs_f32().max(0.001);
+                num_pixels / elapsed
+            }
+            _ => 0.0,
+        };
+        // Smooth things out a bit since the deltas that come in can be pretty noisy
+        self.velocity = 0.6 * instant_velocity + 0.4 * self.velocity;
+        self.last_event = Some(now);
+    }
+
+    /// The user let go, so start coasting if they were moving fast enough
+    ///
+    /// Returns if coasting started
+    pub fn release(&mut self, now: Instant) -> bool {
+        let recent = self
+            .last_event
+            .map_or(false, |last| now - last < MAX_RELEASE_DELAY);
+        if recent && self.velocity.abs() >= MIN_RELEASE_VELOCITY {
+            self.last_event = None;
+            self.last_tick = Some(now);
+            true
+        } else {
+            self.stop();
+            false
+        }
+    }
+
+    pub fn stop(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_coasting(&self) -> bool {
+        self.last_tick.is_some()
+    }
+
+    /// How many pixels to scroll by since the last tick or `None` when done coasting
+    pub fn tick(&mut self, now: Instant) -> Option<f32> {
+        let last_tick = self.last_tick?;
+        let elapsed = (now - last_tick).as_secs_f32();
+        let decay = (-elapsed / TIME_CONSTANT).exp();
+        // The distance covered while exponentially slowing down over `elapsed`
+        let num_pixels = self.velocity * TIME_CONSTANT * (1.0 - decay);
+        self.velocity *= decay;
+        self.last_tick = Some(now);
+
+        if self.velocity.abs() < MIN_VELOCITY {
+            self.stop();
+        }
+        Some(num_pixels)
+    }
+}
+
+/// Eases towards a target scroll position instead of jumping straight to it
+#[derive(Debug, Default)]
+pub struct SmoothScroll {
+    target: Option<f32>,
+    last_tick: Option<Instant>,
+}
+
+impl SmoothScroll {
+    /// Starts (or redirects) the animation towards `target`
+    pub fn scroll_to(&mut self, target: f32, now: Instant) {
+        if self.target.is_none() {
+            self.last_tick = Some(now);
+        }
+        self.target = Some(target);
+    }
+
+    /// Where the animation is headed. Lets steps that come in quick succession build on each
+    /// other instead of on wherever the animation happens to be
+    pub fn target(&self) -> Option<f32> {
+        self.target
+    }
+
+    pub fn stop(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// The scroll position to move to from `current` or `None` when not animating
+    pub fn tick(&mut self, current: f32, now: Instant) -> Option<f32> {
+        let target = self.target?;
+        let elapsed = self
+            .last_tick
+            .map_or(0.0, |last| (now - last).as_secs_f32());
+        self.last_tick = Some(now);
+        let decay = (-elapsed / EASE_TIME_CONSTANT).exp();
+        let next = target + (current - target) * decay;
+
+        if (target - next).abs() < EASE_SNAP_DISTANCE {
+            self.stop();
+            Some(target)
+        } else {
+            Some(next)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flick(momentum: &mut Momentum, start: Instant, pixels_per_frame: f32) -> Instant {
+        let frame = Duration::from_millis(16);
+        let mut now = start;
+        for _ in 0..5 {
+            now += frame;
+            momentum.track(pixels_per_frame, now);
+        }
+        now
+    }
+
+    #[test]
+    fn coasts_then_stops() {
+        let start = Instant::now();
+        let mut momentum = Momentum::default();
+        let now = flick(&mut momentum, start, -20.0);
+        assert!(momentum.release(now));
+
+        let mut total = 0.0;
+        let mut now = now + Duration::from_millis(16);
+        let mut prev_step = f32::NEG_INFINITY;
+        while let Some(step) = momentum.tick(now) {
+            // Keeps going the same direction while slowing down
+            assert!(step < 0.0);
+            assert!(step >= prev_step);
+            prev_step = step;
+            total += step;
+            now += Duration::from_millis(16);
+        }
+        assert!(!momentum.is_coasting());
+        assert!(total < -100.0, "{total}");
+        assert!(now - start < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn slow_or_paused_release_doesnt_coast() {
+        let start = Instant::now();
+        let mut momentum = Momentum::default();
+        let now = flick(&mut momentum, start, -1.0);
+        assert!(!momentum.release(now));
+
+        let now = flick(&mut momentum, now, 30.0);
+        assert!(!momentum.release(now + Duration::from_millis(300)));
+        assert_eq!(momentum.tick(now), None);
+    }
+
+    #[test]
+    fn eases_into_place() {
+        let start = Instant::now();
+        let mut smooth = SmoothScroll::default();
+        smooth.scroll_to(300.0, start);
+        // Another step before the first one finished stacks onto it
+        let target = smooth.target().unwrap() + 100.0;
+        smooth.scroll_to(target, start);
+
+        let mut current = 0.0;
+        let mut now = start;
+        while let Some(next) = smooth.tick(current, now) {
+            // Always heading towards the target without overshooting it
+            assert!(next >= current);
+            assert!(next <= 400.0);
+            current = next;
+            now += Duration::from_millis(16);
+        }
+        assert_eq!(current, 400.0);
+        assert!(!smooth.is_animating());
+        assert!(now - start < Duration::from_secs(1));
+    }
+}
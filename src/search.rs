@@ -0,0 +1,171 @@
+//! Finding text in the document and stepping through the matches
+
+use std::ops::Range;
+
+use crate::panel::Panel;
+use crate::utils::Rect;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Search {
+    pub query: String,
+    /// Where each match is in the document in reading order. Matches that wrap onto another line
+    /// take up a rect on each
+    matches: Vec<Vec<Rect>>,
+    /// The match that was last jumped to
+    current: usize,
+    /// Done typing the query, which leaves the keys free for stepping through the matches
+    pub confirmed: bool,
+}
+
+impl Search {
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+    }
+
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+    }
+
+    /// Swaps in the matches for a new query, starting from the first one at or below `from_y`
+    pub fn set_matches(&mut self, matches: Vec<Vec<Rect>>, from_y: f32) {
+        self.current = matches
+            .iter()
+            .position(|rects| rects.first().map_or(false, |rect| rect.pos.1 >= from_y))
+            .unwrap_or(0);
+        self.matches = matches;
+    }
+
+    /// Swaps in the matches found again after the document moved around, staying on the same one
+    pub fn relocate(&mut self, matches: Vec<Vec<Rect>>) {
+        self.current = self.current.min(matches.len().saturating_sub(1));
+        self.matches = matches;
+    }
+
+    /// Moves on to the next match, wrapping back around to the first one
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + 1) % self.matches.len();
+        }
+    }
+
+    /// Moves back to the previous match, wrapping back around to the last one
+    pub fn select_prev(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    pub fn matches(&self) -> &[Vec<Rect>] {
+        &self.matches
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        (self.current < self.matches.len()).then_some(self.current)
+    }
+
+    pub fn current(&self) -> Option<&[Rect]> {
+        self.matches.get(self.current).map(Vec::as_slice)
+    }
+
+    pub fn panel(&self) -> Panel {
+        let cursor = if self.confirmed { "" } else { "_" };
+        let title = format!("Find: {}{cursor}", self.query);
+        let status = if self.query.is_empty() {
+            "Type to search the document".to_owned()
+        } else if self.matches.is_empty() {
+            "No matches".to_owned()
+        } else {
+            format!("{} of {} matches", self.current + 1, self.matches.len())
+        };
+        Panel::new(title, vec![status])
+    }
+}
+
+/// The byte ranges of `text` that match `query` ignoring case, without any overlapping
+pub fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    let mut next_start = 0;
+    for (start, _) in text.char_indices() {
+        if start < next_start {
+            continue;
+        }
+        let mut lowered = text[start..].char_indices().flat_map(|(i, c)| {
+            c.to_lowercase()
+                .map(move |lower| (start + i + c.len_utf8(), lower))
+        });
+        let mut end = start;
+        let is_match = query.iter().all(|&wanted| match lowered.next() {
+            Some((char_end, lower)) if lower == wanted => {
+                end = char_end;
+                true
+            }
+            _ => false,
+        });
+        if is_match {
+            matches.push(start..end);
+            next_start = end;
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_with(num_matches: usize) -> Search {
+        let mut search = Search {
+            query: "match".to_owned(),
+            ..Search::default()
+        };
+        let matches = (0..num_matches)
+            .map(|i| vec![Rect::new((0., i as f32 * 100.), (10., 10.))])
+            .collect();
+        search.set_matches(matches, 150.);
+        search
+    }
+
+    #[test]
+    fn matching_ignores_case() {
+        assert_eq!(find_matches("Foo foo FOO", "foo"), [0..3, 4..7, 8..11]);
+        assert_eq!(find_matches("aaaa", "aa"), [0..2, 2..4]);
+        assert_eq!(
+            find_matches("Straße STRASSE straße", "straße"),
+            [0..7, 16..23]
+        );
+        assert_eq!(find_matches("ÄB äb", "äb"), [0..3, 4..7]);
+        assert!(find_matches("anything", "").is_empty());
+    }
+
+    #[test]
+    fn starts_from_the_view() {
+        let search = search_with(4);
+        assert_eq!(search.current_index(), Some(2));
+
+        // Everything is above the view
+        let mut search = Search::default();
+        search.set_matches(vec![vec![Rect::new((0., 0.), (10., 10.))]], 500.);
+        assert_eq!(search.current_index(), Some(0));
+    }
+
+    #[test]
+    fn stepping_wraps_around() {
+        let mut search = search_with(3);
+        assert_eq!(search.current_index(), Some(2));
+        search.select_next();
+        assert_eq!(search.current_index(), Some(0));
+        search.select_prev();
+        assert_eq!(search.current_index(), Some(2));
+        assert_eq!(search.panel().lines, ["3 of 3 matches"]);
+
+        search.relocate(Vec::new());
+        assert_eq!(search.current_index(), None);
+        search.select_next();
+        assert_eq!(search.current(), None);
+    }
+}
@@ -0,0 +1,389 @@
+//! A small HTTP server that previews a directory of markdown files in the browser
+//!
+//! Markdown files are rendered with the same theme as the viewer and every page listens for
+//! changes under the served directory so that edits show up without a manual refresh.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::color::Theme;
+use crate::export::html;
+use crate::opts::ServeOpts;
+use crate::utils::{is_markdown, MarkdownOptions};
+
+use anyhow::Context;
+use notify::{RecursiveMode, Watcher as _};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+
+const EVENTS_PATH: &str = "/__inlyne/events";
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>
+new EventSource("/__inlyne/events").onmessage = () => location.reload();
+</script>
+"#;
+
+/// Characters that need escaping within a single path segment of a URL
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+type Clients = Arc<Mutex<Vec<mpsc::Sender<()>>>>;
+
+struct Server {
+    root: PathBuf,
+    theme: Theme,
+    markdown: MarkdownOptions,
+    clients: Clients,
+}
+
+pub fn run(opts: ServeOpts) -> anyhow::Result<()> {
+    let ServeOpts {
+        dir,
+        port,
+        theme,
+        markdown,
+    } = opts;
+    let root = dir
+        .canonicalize()
+        .with_context(|| format!("Failed to find directory: {}", dir.display()))?;
+    anyhow::ensure!(root.is_dir(), "Not a directory: {}", root.display());
+
+    let clients = Clients::default();
+    let broadcast = Arc::clone(&clients);
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(10),
+        None,
+        move |res: DebounceEventResult| {
+            if res.map_or(false, |events| !events.is_empty()) {
+                tracing::debug!("Change detected. Reloading connected pages");
+                broadcast
+                    .lock()
+                    .unwrap()
+                    .retain(|client| client.send(()).is_ok());
+            }
+        },
+    )?;
+    debouncer
+        .watcher()
+        .watch(&root, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch directory: {}", root.display()))?;
+
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Failed to bind to port {port}"))?;
+    tracing::info!(
+        "Serving {} at http://{}",
+        root.display(),
+        listener.local_addr()?
+    );
+
+    let server = Arc::new(Server {
+        root,
+        theme,
+        markdown,
+        clients,
+    });
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = Arc::clone(&server);
+                thread::spawn(move || {
+                    if let Err(err) = server.handle(stream) {
+                        tracing::debug!("Failed handling connection: {:?}", err);
+                    }
+                });
+            }
+            Err(err) => tracing::warn!("Failed accepting connection: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Self {
+            status,
+            ..Self::new("text/plain; charset=utf-8", status)
+        }
+    }
+
+    fn redirect(location: String) -> Self {
+        Self {
+            status: "301 Moved Permanently",
+            headers: vec![("Location", location)],
+            ..Self::new("text/plain; charset=utf-8", "")
+        }
+    }
+
+    fn write_to(&self, stream: &mut TcpStream, include_body: bool) -> std::io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            self.content_type,
+            self.body.len()
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+
+        stream.write_all(head.as_bytes())?;
+        if include_body {
+            stream.write_all(&self.body)?;
+        }
+        stream.flush()
+    }
+}
+
+impl Server {
+    fn handle(&self, mut stream: TcpStream) -> anyhow::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Drain the headers. Nothing in them changes how we respond
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Ok(Response::error("400 Bad Request").write_to(&mut stream, true)?);
+        };
+        tracing::debug!("{} {}", method, target);
+        let include_body = match method {
+            "GET" => true,
+            "HEAD" => false,
+            _ => return Ok(Response::error("405 Method Not Allowed").write_to(&mut stream, true)?),
+        };
+
+        let path = target.split(['?', '#']).next().unwrap_or_default();
+        if path == EVENTS_PATH {
+            return self.stream_events(stream);
+        }
+
+        let response = self.respond(path);
+        response.write_to(&mut stream, include_body)?;
+        Ok(())
+    }
+
+    fn respond(&self, url_path: &str) -> Response {
+        let Some(path) = resolve_path(&self.root, url_path) else {
+            return Response::error("404 Not Found");
+        };
+
+        if path.is_dir() {
+            if !url_path.ends_with('/') {
+                return Response::redirect(format!("{url_path}/"));
+            }
+            return match self.listing(&path, url_path) {
+                Ok(page) => Response::new("text/html; charset=utf-8", page),
+                Err(err) => {
+                    tracing::warn!("Failed listing {}: {}", path.display(), err);
+                    Response::error("500 Internal Server Error")
+                }
+            };
+        }
+
+        if is_markdown(&path) {
+            return match fs::read_to_string(&path) {
+                Ok(md) => {
+                    let title = path
+                        .file_name()
+                        .map(|name| name.to_string_lossy())
+                        .unwrap_or_default();
+                    let page = html::page_with_head(
+                        &md,
+                        &self.theme,
+                        &title,
+                        LIVE_RELOAD_SCRIPT,
+                        self.markdown,
+                    );
+                    Response::new("text/html; charset=utf-8", page)
+                }
+                Err(err) => {
+                    tracing::warn!("Failed reading {}: {}", path.display(), err);
+                    Response::error("500 Internal Server Error")
+                }
+            };
+        }
+
+        match fs::read(&path) {
+            Ok(contents) => Response::new(content_type(&path), contents),
+            Err(err) => {
+                tracing::warn!("Failed reading {}: {}", path.display(), err);
+                Response::error("500 Internal Server Error")
+            }
+        }
+    }
+
+    fn listing(&self, dir: &Path, url_path: &str) -> std::io::Result<String> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let is_dir = entry.file_type()?.is_dir();
+            entries.push((!is_dir, name));
+        }
+        // Directories first, then everything else alphabetically
+        entries.sort();
+
+        let mut body = String::from("<h1>");
+        html_escape::encode_text_to_string(url_path, &mut body);
+        body.push_str("</h1>\n<ul>\n");
+        if url_path != "/" {
+            body.push_str("<li><a href=\"../\">../</a></li>\n");
+        }
+        for (is_file, name) in entries {
+            let suffix = if is_file { "" } else { "/" };
+            let href = utf8_percent_encode(&name, SEGMENT).to_string();
+            body.push_str(&format!("<li><a href=\"{href}{suffix}\">"));
+            html_escape::encode_text_to_string(&name, &mut body);
+            body.push_str(suffix);
+            body.push_str("</a></li>\n");
+        }
+        body.push_str("</ul>\n");
+
+        Ok(html::wrap_body(
+            &body,
+            &self.theme,
+            url_path,
+            LIVE_RELOAD_SCRIPT,
+        ))
+    }
+
+    /// Holds the connection open as a server-sent event stream that notifies about changes
+    fn stream_events(&self, mut stream: TcpStream) -> anyhow::Result<()> {
+        let (tx, rx) = mpsc::channel();
+        self.clients.lock().unwrap().push(tx);
+
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+            Cache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n",
+        )?;
+        stream.flush()?;
+
+        loop {
+            let msg: &[u8] = match rx.recv_timeout(KEEP_ALIVE_INTERVAL) {
+                Ok(()) => b"data: reload\n\n",
+                // Periodically poke the connection so that we notice when the page goes away
+                Err(mpsc::RecvTimeoutError::Timeout) => b": keep-alive\n\n",
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            stream.write_all(msg)?;
+            stream.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps a requested URL path to a file within `root`
+///
+/// Returns `None` for anything that doesn't exist or would escape the root directory
+fn resolve_path(root: &Path, url_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(url_path).decode_utf8().ok()?;
+    let relative = Path::new(decoded.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+
+    // Canonicalizing also resolves symlinks that may point outside of the root
+    let path = root.join(relative).canonicalize().ok()?;
+    path.starts_with(root).then_some(path)
+}
+
+pub fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "json" => "application/json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "ico" => "image/x-icon",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_path_stays_within_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("sub dir")).unwrap();
+        fs::write(root.join("sub dir").join("file.md"), "# Hi").unwrap();
+
+        assert_eq!(resolve_path(&root, "/").unwrap(), root);
+        assert_eq!(
+            resolve_path(&root, "/sub%20dir/file.md").unwrap(),
+            root.join("sub dir").join("file.md")
+        );
+        assert_eq!(resolve_path(&root, "/missing.md"), None);
+        assert_eq!(resolve_path(&root, "/sub%20dir/../../etc/passwd"), None);
+        assert_eq!(resolve_path(&root, "/%2e%2e/"), None);
+    }
+
+    #[test]
+    fn markdown_detection() {
+        assert!(is_markdown(Path::new("README.md")));
+        assert!(is_markdown(Path::new("notes.MARKDOWN")));
+        assert!(!is_markdown(Path::new("image.png")));
+        assert_eq!(content_type(Path::new("image.PNG")), "image/png");
+    }
+}
@@ -0,0 +1,238 @@
+//! Presenting a document as slides, split up by its horizontal rules
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::positioner::{Positioned, Spacer};
+use crate::Element;
+
+/// Speaker notes written as `<!-- notes: ... -->` comments
+pub type SpeakerNotes = Arc<Mutex<Vec<Note>>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    /// How many top level elements came before the note
+    pub position: usize,
+    pub text: String,
+}
+
+/// Gives the text of a speaker notes comment
+pub fn parse_note(comment: &str) -> Option<String> {
+    let comment = comment.trim_start();
+    let (prefix, text) = comment.split_at(comment.find(':')?);
+    prefix
+        .eq_ignore_ascii_case("notes")
+        .then(|| text[1..].trim().to_owned())
+        .filter(|text| !text.is_empty())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slide {
+    /// Which of the document's top level elements are on this slide
+    pub elements: Range<usize>,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl Slide {
+    fn height(&self) -> f32 {
+        self.bottom - self.top
+    }
+}
+
+#[derive(Debug)]
+pub struct Slideshow {
+    pub slides: Vec<Slide>,
+    pub current: usize,
+    /// Show which slide this is out of how many in the bottom corner
+    pub counter: bool,
+}
+
+impl Slideshow {
+    pub fn new(elements: &[Positioned<Element>], counter: bool) -> Self {
+        Self {
+            slides: slides(elements),
+            current: 0,
+            counter,
+        }
+    }
+
+    /// Splits the document up again after it's been repositioned or reloaded, staying on the same
+    /// slide if it's still around
+    pub fn update(&mut self, elements: &[Positioned<Element>]) {
+        self.slides = slides(elements);
+        self.current = self.current.min(self.slides.len().saturating_sub(1));
+    }
+
+    pub fn slide(&self) -> Option<&Slide> {
+        self.slides.get(self.current)
+    }
+
+    /// Moves to the next or previous slide. Gives whether there was one to move to
+    pub fn step(&mut self, forward: bool) -> bool {
+        let next = if forward {
+            self.current + 1
+        } else {
+            self.current.wrapping_sub(1)
+        };
+        if next >= self.slides.len() {
+            return false;
+        }
+        self.current = next;
+        true
+    }
+
+    /// Slides that fit on screen sit in the middle of it, while taller ones can be scrolled
+    /// through without running into their neighbors
+    pub fn scroll_y(&self, scroll_y: f32, screen_height: f32) -> f32 {
+        let Some(slide) = self.slide() else {
+            return 0.;
+        };
+        if slide.height() <= screen_height {
+            slide.top - (screen_height - slide.height()) / 2.
+        } else {
+            scroll_y.clamp(slide.top, slide.bottom - screen_height)
+        }
+    }
+
+    pub fn counter_text(&self) -> String {
+        format!("{} / {}", self.current + 1, self.slides.len())
+    }
+
+    /// The notes that go with the current slide. Notes before the first slide go with it
+    pub fn notes<'notes>(&self, notes: &'notes [Note]) -> Vec<&'notes str> {
+        notes
+            .iter()
+            .filter(|note| {
+                let on = self
+                    .slides
+                    .iter()
+                    .rposition(|slide| slide.elements.start <= note.position);
+                on.unwrap_or(0) == self.current
+            })
+            .map(|note| note.text.as_str())
+            .collect()
+    }
+}
+
+/// Which of the `len` elements are on screen, which is only the current slide's while presenting
+pub fn shown(slideshow: Option<&Slideshow>, len: usize) -> Range<usize> {
+    match slideshow.and_then(Slideshow::slide) {
+        Some(slide) if slide.elements.end <= len => slide.elements.clone(),
+        _ => 0..len,
+    }
+}
+
+fn is_rule(element: &Positioned<Element>) -> bool {
+    matches!(element.inner, Element::Spacer(Spacer { visible: true, .. }))
+}
+
+/// Splits the top level of the document on its horizontal rules. Slides that would be empty, like
+/// from a rule at the very start, get left out
+pub fn slides(elements: &[Positioned<Element>]) -> Vec<Slide> {
+    let mut slides = Vec::new();
+    let mut start = 0;
+    for end in (0..=elements.len()).filter(|&i| i == elements.len() || is_rule(&elements[i])) {
+        let content = elements[start..end].iter().filter_map(|element| {
+            match (&element.inner, &element.bounds) {
+                (Element::Spacer(_), _) | (_, None) => None,
+                (_, Some(bounds)) => Some(bounds),
+            }
+        });
+        let extent = content.fold(None, |extent: Option<(f32, f32)>, bounds| {
+            let (top, bottom) = (bounds.pos.1, bounds.pos.1 + bounds.size.1);
+            Some(extent.map_or((top, bottom), |(t, b)| (t.min(top), b.max(bottom))))
+        });
+        if let Some((top, bottom)) = extent {
+            slides.push(Slide {
+                elements: start..end,
+                top,
+                bottom,
+            });
+        }
+        start = end + 1;
+    }
+    slides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{Text, TextBox};
+    use crate::utils::Rect;
+
+    fn text_box(y: f32) -> Positioned<Element> {
+        let text_box = TextBox::new(vec![Text::new("Text".to_owned(), 1., [0.; 4])], 1.);
+        let mut element = Positioned::new(text_box);
+        element.bounds = Some(Rect::new((100., y), (500., 20.)));
+        element
+    }
+
+    fn rule(y: f32) -> Positioned<Element> {
+        let mut element = Positioned::new(Spacer::visible());
+        element.bounds = Some(Rect::new((0., y), (600., 5.)));
+        element
+    }
+
+    #[test]
+    fn splitting_on_rules() {
+        let elements = [
+            rule(0.),
+            text_box(10.),
+            text_box(40.),
+            rule(70.),
+            rule(80.),
+            text_box(90.),
+        ];
+        assert_eq!(
+            slides(&elements),
+            [
+                Slide {
+                    elements: 1..3,
+                    top: 10.,
+                    bottom: 60.,
+                },
+                Slide {
+                    elements: 5..6,
+                    top: 90.,
+                    bottom: 110.,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stepping_and_scrolling() {
+        let elements = [text_box(0.), rule(30.), text_box(40.), text_box(1_000.)];
+        let mut slideshow = Slideshow::new(&elements, true);
+        assert!(!slideshow.step(false));
+        assert_eq!(slideshow.counter_text(), "1 / 2");
+        // Short slides get centered
+        assert_eq!(slideshow.scroll_y(500., 600.), -290.);
+
+        assert!(slideshow.step(true));
+        assert!(!slideshow.step(true));
+        assert_eq!(slideshow.counter_text(), "2 / 2");
+        // Tall ones are kept to their own bounds
+        assert_eq!(slideshow.scroll_y(0., 600.), 40.);
+        assert_eq!(slideshow.scroll_y(10_000., 600.), 420.);
+    }
+
+    #[test]
+    fn notes_per_slide() {
+        assert_eq!(parse_note(" Notes: Say hi\n"), Some("Say hi".to_owned()));
+        assert_eq!(parse_note("notes:"), None);
+        assert_eq!(parse_note("TODO: notes"), None);
+
+        let elements = [rule(0.), text_box(10.), rule(40.), text_box(50.)];
+        let note = |position, text: &str| Note {
+            position,
+            text: text.to_owned(),
+        };
+        let notes = [note(0, "Intro"), note(2, "First"), note(4, "Second")];
+        let mut slideshow = Slideshow::new(&elements, true);
+        assert_eq!(slideshow.notes(&notes), ["Intro", "First"]);
+        slideshow.step(true);
+        assert_eq!(slideshow.notes(&notes), ["Second"]);
+    }
+}
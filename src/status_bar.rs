@@ -0,0 +1,83 @@
+//! The bar along the bottom of the document with how far through it you are and how long it is
+
+use crate::utils;
+
+/// How many words a minute of reading time is worth
+const WORDS_PER_MINUTE: usize = 200;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct StatusBar {
+    pub words: usize,
+}
+
+impl StatusBar {
+    pub fn new(md: &str) -> Self {
+        Self {
+            words: word_count(md),
+        }
+    }
+
+    /// Rounded up so that anything with words in it takes at least a minute
+    pub fn reading_minutes(&self) -> usize {
+        (self.words + WORDS_PER_MINUTE - 1) / WORDS_PER_MINUTE
+    }
+
+    /// What's shown on the right side of the bar
+    pub fn summary(&self, scroll_percent: u8) -> String {
+        let words = thousands(self.words);
+        let plural = if self.words == 1 { "" } else { "s" };
+        let minutes = self.reading_minutes();
+        format!("{scroll_percent}% · {words} word{plural} · {minutes} min read")
+    }
+}
+
+/// Only counts runs of text with a letter or number in them, so markup like `#`, `-`, and `|` gets
+/// left out. The front matter isn't part of what gets read either
+fn word_count(md: &str) -> usize {
+    let body = match utils::front_matter(md) {
+        Some(front_matter) => md.get(front_matter.len() + 6..).unwrap_or_default(),
+        None => md,
+    };
+    body.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+fn thousands(num: usize) -> String {
+    let digits = num.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counting_words() {
+        let md = "---\ntitle: Not counted\n---\n# Some heading\n\n- one\n- `two`\n\n| a | b |\n| - | - |\n";
+        assert_eq!(word_count(md), 6);
+        assert_eq!(word_count(""), 0);
+    }
+
+    #[test]
+    fn summaries() {
+        assert_eq!(
+            StatusBar { words: 1 }.summary(0),
+            "0% · 1 word · 1 min read"
+        );
+        assert_eq!(
+            StatusBar { words: 1_234_567 }.summary(42),
+            "42% · 1,234,567 words · 6173 min read"
+        );
+        assert_eq!(StatusBar { words: 0 }.reading_minutes(), 0);
+        assert_eq!(StatusBar { words: 400 }.reading_minutes(), 2);
+        assert_eq!(StatusBar { words: 401 }.reading_minutes(), 3);
+    }
+}
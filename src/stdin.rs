@@ -0,0 +1,84 @@
+//! Reading markdown that gets piped in, like `cat notes.md | inlyne -`
+//!
+//! With `--follow` stdin keeps getting read after the window opens, so tools that generate
+//! markdown on the fly can keep adding onto the document. A form feed (`\x0c`) throws out
+//! everything before it, which lets them swap in a whole new document instead
+
+use std::io::{self, Read};
+
+use crate::InlyneEvent;
+
+use winit::event_loop::EventLoopProxy;
+
+const NEW_DOCUMENT: u8 = b'\x0c';
+
+/// Reads everything that gets piped in until stdin closes
+pub fn read_all() -> anyhow::Result<String> {
+    let mut bytes = Vec::new();
+    io::stdin().lock().read_to_end(&mut bytes)?;
+    let mut document = Vec::new();
+    extend(&mut document, &bytes);
+    Ok(String::from_utf8_lossy(&document).into_owned())
+}
+
+/// Keeps reading stdin on another thread, sending the whole document along whenever more of it
+/// comes in
+pub fn follow(event_proxy: EventLoopProxy<InlyneEvent>) {
+    std::thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut document = Vec::new();
+        let mut buf = [0; 8 * 1024];
+        loop {
+            match stdin.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => {
+                    extend(&mut document, &buf[..len]);
+                    // Lossy since a read can end in the middle of a character, which gets fixed
+                    // up once the rest of it comes in
+                    let contents = String::from_utf8_lossy(&document).into_owned();
+                    if event_proxy
+                        .send_event(InlyneEvent::StdinChange { contents })
+                        .is_err()
+                    {
+                        // The window is gone
+                        break;
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    tracing::warn!("Stopped following stdin\nError: {err}");
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Adds a chunk that was read onto the document, starting over after any form feeds
+fn extend(document: &mut Vec<u8>, chunk: &[u8]) {
+    match chunk.iter().rposition(|&b| b == NEW_DOCUMENT) {
+        Some(pos) => {
+            document.clear();
+            document.extend_from_slice(&chunk[pos + 1..]);
+        }
+        None => document.extend_from_slice(chunk),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn form_feeds_start_a_new_document() {
+        let mut document = Vec::new();
+        extend(&mut document, b"# Title\n");
+        extend(&mut document, b"more\n");
+        assert_eq!(document, b"# Title\nmore\n");
+
+        extend(&mut document, b"ignored\x0c# Second\x0c# Third\n");
+        assert_eq!(document, b"# Third\n");
+        extend(&mut document, b"\x0c");
+        assert!(document.is_empty());
+    }
+}
@@ -1,33 +1,74 @@
+use std::fmt;
 use std::sync::Arc;
 
+use crate::opts::TableOptions;
 use crate::text::{Text, TextBox, TextBoxMeasure, TextSystem};
 use crate::utils::{default, Point, Rect, Size};
 
 use taffy::node::MeasureFunc;
 use taffy::prelude::{
-    auto, line, points, AvailableSpace, Display, Layout, Size as TaffySize, Style, Taffy,
+    auto, line, minmax, points, AvailableSpace, Display, Layout, Size as TaffySize, Style, Taffy,
 };
 use taffy::style::JustifyContent;
 
-pub const TABLE_ROW_GAP: f32 = 20.;
-pub const TABLE_COL_GAP: f32 = 20.;
-
 #[derive(Debug)]
 pub struct TableLayout {
     pub headers: Vec<Layout>,
     pub rows: Vec<Vec<Layout>>,
     pub size: Size,
+    /// Half of the gap between cells
+    pub cell_padding: f32,
 }
 
-#[derive(Default, Debug)]
+impl TableLayout {
+    /// The area taken up by the header row relative to where the table is
+    pub fn header_row(&self) -> Option<Rect> {
+        self.row_rect(&self.headers)
+    }
+
+    /// The area taken up by every other row of the body, starting with the second, relative to
+    /// where the table is. These get a faint background to make rows easier to follow across
+    pub fn striped_rows(&self) -> impl Iterator<Item = Rect> + '_ {
+        self.rows
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter_map(|row| self.row_rect(row))
+    }
+
+    fn row_rect(&self, row: &[Layout]) -> Option<Rect> {
+        let first = row.first()?;
+        let last = row.last()?;
+        let top = first.location.y - self.cell_padding;
+        let bottom = last.location.y + last.size.height + self.cell_padding;
+        let right = last.location.x + last.size.width;
+        Some(Rect::from_min_max((0., top), (right, bottom)))
+    }
+}
+
+#[derive(Default)]
 pub struct Table {
     pub headers: Vec<TextBox>,
     pub rows: Vec<Vec<TextBox>>,
+    pub style: TableOptions,
+}
+
+// The style comes from the config rather than the document, so it's left out
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("headers", &self.headers)
+            .field("rows", &self.rows)
+            .finish()
+    }
 }
 
 impl Table {
-    pub fn new() -> Table {
-        Table::default()
+    pub fn new(style: TableOptions) -> Table {
+        Table {
+            style,
+            ..Table::default()
+        }
     }
 
     pub fn find_hoverable<'a>(
@@ -100,13 +141,16 @@ impl Table {
             ..default()
         };
 
+        // Columns can shrink past their longest word, so that a wide table wraps its cells
+        // instead of running off the page
+        let gap = 2. * self.style.cell_padding;
         let grid_style = Style {
             display: Display::Grid,
             gap: TaffySize {
-                width: points(TABLE_COL_GAP),
-                height: points(TABLE_ROW_GAP),
+                width: points(gap),
+                height: points(gap),
             },
-            grid_template_columns: vec![auto(); max_columns],
+            grid_template_columns: vec![minmax(points(0.), auto()); max_columns],
             ..default()
         };
 
@@ -191,6 +235,7 @@ impl Table {
             headers: header_layout,
             rows: rows_layout,
             size: (size.width, size.height),
+            cell_padding: self.style.cell_padding,
         })
     }
 
@@ -5,7 +5,11 @@ use std::hash::{BuildHasher, Hash, Hasher};
 use std::ops::Range;
 use std::sync::{Arc, Mutex};
 
+use crate::callout::Callout;
 use crate::debug_impls::{self, DebugInline, DebugInlineMaybeF32Color};
+use crate::diff::DiffLine;
+use crate::fonts::FontFallbacks;
+use crate::search;
 use crate::utils::{Align, Line, Point, Rect, Selection, Size};
 
 use fxhash::{FxHashMap, FxHashSet};
@@ -19,6 +23,9 @@ use taffy::prelude::{AvailableSpace, Size as TaffySize};
 type KeyHash = u64;
 type HashBuilder = twox_hash::RandomXxHashBuilder64;
 
+/// How large superscripts and subscripts are compared to the text around them
+const SCRIPT_SCALE: f32 = 0.7;
+
 pub struct TextBoxMeasure {
     pub textbox: Arc<TextBox>,
     pub text_cache: Arc<Mutex<TextCache>>,
@@ -62,12 +69,45 @@ pub struct TextBox {
     #[debug(wrapper = DebugInlineMaybeF32Color)]
     pub background_color: Option<[f32; 4]>,
     pub is_code_block: bool,
+    /// The language that a fenced code block was marked with
+    #[debug(skip)]
+    pub code_language: Option<String>,
+    /// What a code block's copy button copies, from before any color swatches got added in
+    #[debug(skip)]
+    pub code_source: Option<String>,
+    /// Which lines of a ```` ```diff ```` block were added or removed
+    #[debug(skip)]
+    pub diff_lines: Vec<Option<DiffLine>>,
+    /// Long lines run past the edge to get scrolled to sideways instead of wrapping
+    #[debug(skip)]
+    pub no_wrap: bool,
+    /// How far a box that doesn't wrap is scrolled sideways
+    #[debug(skip)]
+    pub scroll_x: f32,
     #[debug(wrapper = DebugInline)]
     pub is_quote_block: Option<usize>,
+    /// The callout that the quote block at that nesting level is
+    #[debug(wrapper = DebugInline)]
+    pub callout: Option<(Callout, usize)>,
     #[debug(wrapper = DebugInline)]
     pub is_checkbox: Option<bool>,
     #[debug(wrapper = DebugInline)]
     pub is_anchor: Option<String>,
+    /// Anchors that were set before `is_anchor`, which all lead to the start of this too
+    #[debug(skip)]
+    pub other_anchors: Vec<String>,
+    /// 1 for `<h1>` through 6 for `<h6>` when this is a heading
+    #[debug(skip)]
+    pub heading_level: Option<u8>,
+    /// The line in the markdown source that this came from
+    #[debug(skip)]
+    pub source_line: Option<usize>,
+    /// How much room a floating image to the right takes from the end of each line
+    #[debug(skip)]
+    pub float_inset: f32,
+    /// Scales how far apart lines are from the usual
+    #[debug(skip)]
+    pub line_spacing: f32,
     #[debug(no_skip)]
     pub texts: Vec<Text>,
     #[debug(skip)]
@@ -81,9 +121,20 @@ impl Default for TextBox {
             font_size: 16.0,
             texts: Vec::new(),
             is_code_block: false,
+            code_language: None,
+            code_source: None,
+            diff_lines: Vec::new(),
+            no_wrap: false,
+            scroll_x: 0.0,
             is_quote_block: None,
+            callout: None,
             is_checkbox: None,
             is_anchor: None,
+            other_anchors: Vec::new(),
+            heading_level: None,
+            source_line: None,
+            float_inset: 0.0,
+            line_spacing: 1.0,
             align: Align::default(),
             hidpi_scale: 1.0,
             padding_height: 0.0,
@@ -99,9 +150,18 @@ pub struct CachedTextArea {
     top: f32,
     bounds: TextBounds,
     default_color: Color,
+    /// Superscripts and subscripts, which get drawn smaller over where they hold their place in
+    /// the text
+    scripts: Vec<(KeyHash, Point)>,
 }
 
 impl CachedTextArea {
+    /// Hides anything outside of `left..right` on the screen
+    pub fn clip_x(&mut self, left: f32, right: f32) {
+        self.bounds.left = left.floor() as i32;
+        self.bounds.right = right.ceil() as i32;
+    }
+
     pub fn text_area<'a>(&self, cache: &'a TextCache) -> TextArea<'a> {
         TextArea {
             buffer: cache.get(&self.key).expect("Get cached buffer"),
@@ -112,6 +172,19 @@ impl CachedTextArea {
             scale: 1.,
         }
     }
+
+    /// The text along with any superscripts and subscripts drawn over it
+    pub fn text_areas<'a>(&'a self, cache: &'a TextCache) -> impl Iterator<Item = TextArea<'a>> {
+        let scripts = self.scripts.iter().map(|(key, (left, top))| TextArea {
+            buffer: cache.get(key).expect("Get cached buffer"),
+            left: *left,
+            top: *top,
+            bounds: self.bounds,
+            default_color: self.default_color,
+            scale: 1.,
+        });
+        std::iter::once(self.text_area(cache)).chain(scripts)
+    }
 }
 
 impl TextBox {
@@ -131,8 +204,17 @@ impl TextBox {
         self.is_quote_block = Some(nest);
     }
 
+    /// The callout that this is directly in, unlike one further out than a nested quote
+    pub fn innermost_callout(&self) -> Option<Callout> {
+        match (self.callout, self.is_quote_block) {
+            (Some((callout, level)), Some(nest)) if level == nest => Some(callout),
+            _ => None,
+        }
+    }
+
     pub fn clear_quote_block(&mut self) {
         self.is_quote_block = None;
+        self.callout = None;
     }
 
     pub fn set_checkbox(&mut self, is_checked: bool) {
@@ -140,7 +222,9 @@ impl TextBox {
     }
 
     pub fn set_anchor(&mut self, anchor: String) {
-        self.is_anchor = Some(anchor);
+        if let Some(previous) = self.is_anchor.replace(anchor) {
+            self.other_anchors.push(previous);
+        }
     }
 
     pub fn set_background_color(&mut self, color: [f32; 4]) {
@@ -161,7 +245,7 @@ impl TextBox {
     }
 
     pub fn line_height(&self, zoom: f32) -> f32 {
-        self.font_size * 1.1 * self.hidpi_scale * zoom
+        self.font_size * 1.1 * self.line_spacing * self.hidpi_scale * zoom
     }
 
     pub fn key(&self, bounds: Size, zoom: f32) -> Key<'_> {
@@ -179,6 +263,11 @@ impl TextBox {
             sections.clear();
         }
 
+        let bounds = if self.no_wrap {
+            (f32::INFINITY, bounds.1)
+        } else {
+            bounds
+        };
         Key {
             lines,
             size: self.font_size * self.hidpi_scale * zoom,
@@ -255,6 +344,12 @@ impl TextBox {
             .fold((0, 0.0), |(_, max), (i, buffer)| {
                 (i + 1, buffer.line_w.max(max))
             });
+        // Anything past the bounds gets scrolled to instead
+        let max_width = if self.no_wrap {
+            max_width.min(bounds.0)
+        } else {
+            max_width
+        };
 
         (
             max_width,
@@ -262,6 +357,36 @@ impl TextBox {
         )
     }
 
+    /// Scrolls a box that doesn't wrap sideways by `delta`, keeping its lines in view
+    ///
+    /// Returns if it moved
+    pub fn scroll_x_by(
+        &mut self,
+        text_system: &mut TextSystem,
+        bounds: Size,
+        zoom: f32,
+        delta: f32,
+    ) -> bool {
+        if !self.no_wrap {
+            return false;
+        }
+
+        let line_width = {
+            let mut cache = text_system.text_cache.lock().unwrap();
+            let (_, paragraph) = cache.allocate(
+                text_system.font_system.lock().unwrap().borrow_mut(),
+                self.key(bounds, zoom),
+            );
+            paragraph
+                .layout_runs()
+                .fold(0., |max: f32, run| run.line_w.max(max))
+        };
+        let scroll_x = (self.scroll_x + delta).clamp(0., (line_width - bounds.0).max(0.));
+        let moved = scroll_x != self.scroll_x;
+        self.scroll_x = scroll_x;
+        moved
+    }
+
     pub fn text_areas(
         &self,
         text_system: &mut TextSystem,
@@ -272,34 +397,94 @@ impl TextBox {
     ) -> CachedTextArea {
         let cache = text_system.text_cache.borrow_mut();
 
-        let (key, max_width) = {
+        let (key, (min_x, max_x), scripts) = {
             let mut cache = cache.lock().unwrap();
-            let (key, paragraph) = cache.allocate(
-                text_system.font_system.lock().unwrap().borrow_mut(),
-                self.key(bounds, zoom),
-            );
+            let mut font_system = text_system.font_system.lock().unwrap();
+            let (key, paragraph) = cache.allocate(font_system.borrow_mut(), self.key(bounds, zoom));
 
-            let max_width = paragraph
+            // Right-to-left lines get laid out from the right edge instead of the left, so
+            // align by where the glyphs actually ended up
+            let extents = paragraph
                 .layout_runs()
-                .fold(0., |max, buffer| buffer.line_w.max(max));
-            (key, max_width)
+                .flat_map(|run| run.glyphs.iter())
+                .fold(None, |extents, glyph| {
+                    let (min, max) = extents.unwrap_or((glyph.x, glyph.x + glyph.w));
+                    Some((glyph.x.min(min), (glyph.x + glyph.w).max(max)))
+                });
+
+            // Scripts hold their place in the paragraph and get laid out again on their own
+            let mut spans = Vec::new();
+            if self.texts.iter().any(|text| text.script.is_some()) {
+                for run in paragraph.layout_runs() {
+                    let mut run_spans: Vec<(usize, Range<usize>, f32)> = Vec::new();
+                    for glyph in run.glyphs {
+                        if self.texts[glyph.metadata].script.is_none() {
+                            continue;
+                        }
+                        match run_spans.last_mut() {
+                            Some((text_i, range, x))
+                                if *text_i == glyph.metadata && range.end == glyph.start =>
+                            {
+                                range.end = glyph.end;
+                                *x = x.min(glyph.x);
+                            }
+                            _ => run_spans.push((glyph.metadata, glyph.start..glyph.end, glyph.x)),
+                        }
+                    }
+                    spans.extend(run_spans.into_iter().map(|(text_i, range, x)| {
+                        (text_i, run.text[range].to_owned(), x, run.line_y)
+                    }));
+                }
+            }
+
+            let size = self.font_size * self.hidpi_scale * zoom;
+            let scripts: Vec<_> = spans
+                .into_iter()
+                .map(|(text_i, content, x, line_y)| {
+                    let text = &self.texts[text_i];
+                    let script = text.script.expect("Only scripts get spans");
+                    let script_size = size * SCRIPT_SCALE;
+                    let key = Key {
+                        lines: vec![vec![SectionKey {
+                            content: &content,
+                            font: text.font(),
+                            color: text.glyph_color(),
+                            index: text_i,
+                        }]],
+                        size: script_size,
+                        line_height: script_size * 1.1,
+                        bounds: (f32::INFINITY, f32::INFINITY),
+                    };
+                    let (key, buffer) = cache.allocate(font_system.borrow_mut(), key);
+                    let baseline = buffer.layout_runs().next().map_or(0., |run| run.line_y);
+                    (key, (x, line_y - script.rise() * size - baseline))
+                })
+                .collect();
+            (key, extents.unwrap_or_default(), scripts)
         };
 
         let left = match self.align {
-            Align::Left => screen_position.0,
-            Align::Center => screen_position.0 + (bounds.0 - max_width) / 2.,
-            Align::Right => screen_position.0 + bounds.0 - max_width,
+            Align::Left => screen_position.0 - min_x,
+            Align::Center => screen_position.0 + (bounds.0 - (max_x - min_x)) / 2. - min_x,
+            Align::Right => screen_position.0 + bounds.0 - max_x,
         };
 
+        let top = screen_position.1 - scroll_y;
         CachedTextArea {
             key,
             left,
-            top: (screen_position.1 - scroll_y),
+            top,
             bounds: TextBounds::default(),
             default_color: Color::rgb(255, 255, 255),
+            scripts: scripts
+                .into_iter()
+                .map(|(key, (x, y))| (key, (left + x, top + y)))
+                .collect(),
         }
     }
 
+    /// The underlines and strikethroughs of the text. Links going to `hovered_link` get underlined
+    /// too, like they would in a browser
     pub fn render_lines(
         &self,
         text_system: &mut TextSystem,
@@ -307,6 +492,7 @@ impl TextBox {
         bounds: Size,
         zoom: f32,
         text_area: &CachedTextArea,
+        hovered_link: Option<&str>,
     ) -> Vec<Line> {
         fn push_line_segment(
             lines: &mut Vec<ThinLine>,
@@ -329,10 +515,13 @@ impl TextBox {
             ThinLine { range, color }
         }
 
+        let is_underlined = |text: &Text| {
+            text.is_underlined || (text.link.is_some() && text.link.as_deref() == hovered_link)
+        };
         let has_lines = self
             .texts
             .iter()
-            .any(|text| text.is_striked || text.is_underlined);
+            .any(|text| text.is_striked || is_underlined(text));
         if !has_lines {
             return Vec::new();
         }
@@ -358,7 +547,7 @@ impl TextBox {
             for glyph in line.glyphs {
                 let text = &self.texts[glyph.metadata];
                 let color = text.color.unwrap_or(text.default_color);
-                if text.is_underlined {
+                if is_underlined(text) {
                     let underline =
                         push_line_segment(&mut underlines, current_underline, glyph, color);
                     current_underline = Some(underline);
@@ -407,6 +596,152 @@ impl TextBox {
         lines
     }
 
+    /// Where the `<mark>`ed and `<kbd>` parts of the text are, so that they can get something drawn
+    /// behind them
+    pub fn render_backdrops(
+        &self,
+        text_system: &mut TextSystem,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+        text_area: &CachedTextArea,
+    ) -> Vec<(Rect, Backdrop)> {
+        if !self.texts.iter().any(|text| text.backdrop().is_some()) {
+            return Vec::new();
+        }
+
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock().unwrap();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let mut backdrops = Vec::new();
+        let mut y = screen_position.1;
+        for run in buffer.layout_runs() {
+            // Marks carry on across texts while each key gets a cap of its own
+            let mut spans: Vec<(Range<usize>, Backdrop, usize)> = Vec::new();
+            for glyph in run.glyphs {
+                let Some(backdrop) = self.texts[glyph.metadata].backdrop() else {
+                    continue;
+                };
+                match spans.last_mut() {
+                    Some((range, last, text_i))
+                        if range.end == glyph.start
+                            && *last == backdrop
+                            && (backdrop == Backdrop::Mark || *text_i == glyph.metadata) =>
+                    {
+                        range.end = glyph.end;
+                    }
+                    _ => spans.push((glyph.start..glyph.end, backdrop, glyph.metadata)),
+                }
+            }
+            for (range, backdrop, _) in spans {
+                let start_cursor = Cursor::new(run.line_i, range.start);
+                let end_cursor = Cursor::new(run.line_i, range.end);
+                if let Some((highlight_x, highlight_w)) = run.highlight(start_cursor, end_cursor) {
+                    let x = text_area.left + highlight_x;
+                    let rect = Rect::new((x, y), (highlight_w, line_height));
+                    backdrops.push((rect, backdrop));
+                }
+            }
+            y += line_height;
+        }
+
+        backdrops
+    }
+
+    /// Where each stretch of linked text is on each line, along with where it links to
+    pub fn link_spans(
+        &self,
+        text_system: &mut TextSystem,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+        text_area: &CachedTextArea,
+    ) -> Vec<(Rect, &str)> {
+        if !self.texts.iter().any(|text| text.link.is_some()) {
+            return Vec::new();
+        }
+
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock().unwrap();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let mut links = Vec::new();
+        let mut y = screen_position.1;
+        for run in buffer.layout_runs() {
+            let mut spans: Vec<(Range<usize>, &str)> = Vec::new();
+            for glyph in run.glyphs {
+                let Some(link) = self.texts[glyph.metadata].link.as_deref() else {
+                    continue;
+                };
+                match spans.last_mut() {
+                    Some((range, last)) if range.end == glyph.start && *last == link => {
+                        range.end = glyph.end;
+                    }
+                    _ => spans.push((glyph.start..glyph.end, link)),
+                }
+            }
+            for (range, link) in spans {
+                let start_cursor = Cursor::new(run.line_i, range.start);
+                let end_cursor = Cursor::new(run.line_i, range.end);
+                if let Some((highlight_x, highlight_w)) = run.highlight(start_cursor, end_cursor) {
+                    let x = text_area.left + highlight_x;
+                    links.push((Rect::new((x, y), (highlight_w, line_height)), link));
+                }
+            }
+            y += line_height;
+        }
+
+        links
+    }
+
+    /// The stretches of height taken up by the added and removed lines of a diff, which can each
+    /// wrap onto more than one line
+    pub fn render_diff_lines(
+        &self,
+        text_system: &mut TextSystem,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+    ) -> Vec<(Range<f32>, DiffLine)> {
+        if self.diff_lines.iter().all(Option::is_none) {
+            return Vec::new();
+        }
+
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock().unwrap();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let mut stretches: Vec<(Range<f32>, DiffLine, usize)> = Vec::new();
+        let mut y = screen_position.1;
+        for run in buffer.layout_runs() {
+            if let Some(&Some(kind)) = self.diff_lines.get(run.line_i) {
+                match stretches.last_mut() {
+                    Some((range, _, line_i)) if *line_i == run.line_i => range.end += line_height,
+                    _ => stretches.push((y..y + line_height, kind, run.line_i)),
+                }
+            }
+            y += line_height;
+        }
+
+        stretches
+            .into_iter()
+            .map(|(range, kind, _)| (range, kind))
+            .collect()
+    }
+
     pub fn render_selection(
         &self,
         text_system: &mut TextSystem,
@@ -490,6 +825,163 @@ impl TextBox {
 
         (rects, selected_text)
     }
+
+    /// A selection covering the word at `loc`, or just the character there if it isn't part of
+    /// a word
+    pub fn word_selection(
+        &self,
+        text_system: &mut TextSystem,
+        loc: Point,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+    ) -> Option<Selection> {
+        if screen_position.1 > loc.1 || screen_position.1 + bounds.1 < loc.1 {
+            return None;
+        }
+
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock().unwrap();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let cursor = buffer.hit(loc.0 - screen_position.0, loc.1 - screen_position.1)?;
+        let range = word_range(buffer.lines[cursor.line].text(), cursor.index);
+        let start_cursor = Cursor::new(cursor.line, range.start);
+        let end_cursor = Cursor::new(cursor.line, range.end);
+        let mut y = screen_position.1;
+        for line in buffer.layout_runs() {
+            let on_line = loc.1 >= y && loc.1 <= y + line_height;
+            if line.line_i == cursor.line && on_line {
+                let (highlight_x, highlight_w) = line.highlight(start_cursor, end_cursor)?;
+                let x = screen_position.0 + highlight_x;
+                let y = y + line_height / 2.;
+                return Some(((x, y), (x + highlight_w, y)));
+            }
+            y += line_height;
+        }
+        None
+    }
+
+    /// Where each match of `query` shows up. A match gets a rect for every line that it's on
+    pub fn find_matches(
+        &self,
+        text_system: &mut TextSystem,
+        query: &str,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+    ) -> Vec<Vec<Rect>> {
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock().unwrap();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let mut matches = Vec::new();
+        for (line_i, line) in buffer.lines.iter().enumerate() {
+            for range in search::find_matches(line.text(), query) {
+                let start_cursor = Cursor::new(line_i, range.start);
+                let end_cursor = Cursor::new(line_i, range.end);
+                let mut rects = Vec::new();
+                let mut y = screen_position.1;
+                for run in buffer.layout_runs() {
+                    if run.line_i == line_i {
+                        if let Some((highlight_x, highlight_w)) =
+                            run.highlight(start_cursor, end_cursor)
+                        {
+                            let x = screen_position.0 + highlight_x;
+                            rects.push(Rect::new((x, y), (highlight_w, line_height)));
+                        }
+                    }
+                    y += line_height;
+                }
+                if !rects.is_empty() {
+                    matches.push(rects);
+                }
+            }
+        }
+        matches
+    }
+
+    /// A selection covering all of the text
+    pub fn paragraph_selection(
+        &self,
+        text_system: &mut TextSystem,
+        screen_position: Point,
+        bounds: Size,
+        zoom: f32,
+    ) -> Selection {
+        let line_height = self.line_height(zoom);
+        let mut cache = text_system.text_cache.lock().unwrap();
+
+        let (_, buffer) = cache.allocate(
+            text_system.font_system.lock().unwrap().borrow_mut(),
+            self.key(bounds, zoom),
+        );
+
+        let lines = buffer.layout_runs().count().max(1) as f32;
+        let (x, y) = screen_position;
+        (
+            (x, y + line_height / 2.),
+            (x + bounds.0, y + (lines - 0.5) * line_height),
+        )
+    }
+}
+
+/// The word around the byte at `index` in `text`, or the character there when it's not part of one
+fn word_range(text: &str, index: usize) -> Range<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let index = index.min(text.len());
+    let start = text[..index]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word(c))
+        .last()
+        .map_or(index, |(i, _)| i);
+    let end = text[index..]
+        .char_indices()
+        .take_while(|&(_, c)| is_word(c))
+        .last()
+        .map_or(index, |(i, c)| index + i + c.len_utf8());
+    if start == end {
+        let len = text[index..].chars().next().map_or(0, char::len_utf8);
+        return index..index + len;
+    }
+    start..end
+}
+
+/// What gets drawn behind a stretch of text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backdrop {
+    /// Highlighted like it was gone over with a marker
+    Mark,
+    /// Framed like a key on a keyboard
+    KeyCap,
+}
+
+/// Text that gets drawn smaller and off of the baseline
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Script {
+    /// Raised like `<sup>`
+    Super,
+    /// Lowered like `<sub>`
+    Sub,
+}
+
+impl Script {
+    /// How far the baseline moves up, in ems of the text around it
+    fn rise(self) -> f32 {
+        match self {
+            Self::Super => 0.35,
+            Self::Sub => -0.2,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -507,6 +999,9 @@ pub struct Text {
     pub is_italic: bool,
     pub is_underlined: bool,
     pub is_striked: bool,
+    pub is_marked: bool,
+    pub is_key_cap: bool,
+    pub script: Option<Script>,
     pub font_family: FamilyOwned,
     pub hidpi_scale: f32,
     pub default_color: [f32; 4],
@@ -530,6 +1025,9 @@ impl Text {
             is_italic: false,
             is_underlined: false,
             is_striked: false,
+            is_marked: false,
+            is_key_cap: false,
+            script: None,
             font_family: FamilyOwned::SansSerif,
         }
     }
@@ -564,6 +1062,21 @@ impl Text {
         self
     }
 
+    pub fn make_marked(mut self, marked: bool) -> Self {
+        self.is_marked = marked;
+        self
+    }
+
+    pub fn make_key_cap(mut self, key_cap: bool) -> Self {
+        self.is_key_cap = key_cap;
+        self
+    }
+
+    pub fn with_script(mut self, script: Script) -> Self {
+        self.script = Some(script);
+        self
+    }
+
     pub fn with_family(mut self, family: FamilyOwned) -> Self {
         self.font_family = family;
         self
@@ -573,6 +1086,16 @@ impl Text {
         self.color.unwrap_or(self.default_color)
     }
 
+    fn backdrop(&self) -> Option<Backdrop> {
+        if self.is_key_cap {
+            Some(Backdrop::KeyCap)
+        } else if self.is_marked {
+            Some(Backdrop::Mark)
+        } else {
+            None
+        }
+    }
+
     fn style(&self) -> Style {
         if self.is_italic {
             Style::Italic
@@ -589,19 +1112,32 @@ impl Text {
         }
     }
 
-    pub fn section_keys(&self, index: usize) -> Vec<SectionKey<'_>> {
+    fn glyph_color(&self) -> Color {
         let color = self.color();
-        let color = Color::rgba(
+        Color::rgba(
             (color[0] * 255.) as u8,
             (color[1] * 255.) as u8,
             (color[2] * 255.) as u8,
             (color[3] * 255.) as u8,
-        );
-        let font = Font {
+        )
+    }
+
+    fn font(&self) -> Font<'_> {
+        Font {
             family: self.font_family.as_family(),
             weight: self.weight(),
             style: self.style(),
+        }
+    }
+
+    pub fn section_keys(&self, index: usize) -> Vec<SectionKey<'_>> {
+        // Scripts get drawn over the room they take up here instead
+        let color = if self.script.is_some() {
+            Color::rgba(0, 0, 0, 0)
+        } else {
+            self.glyph_color()
         };
+        let font = self.font();
         self.text
             .lines()
             .map(|line| SectionKey {
@@ -642,6 +1178,7 @@ pub struct TextCache {
     entries: FxHashMap<KeyHash, glyphon::Buffer>,
     recently_used: FxHashSet<KeyHash>,
     hasher: HashBuilder,
+    pub fallbacks: FontFallbacks,
 }
 
 impl TextCache {
@@ -684,16 +1221,24 @@ impl TextCache {
                 for section in line {
                     let start = line_str.len();
                     line_str.push_str(section.content);
-                    let end = line_str.len();
-                    attrs_list.add_span(
-                        start..end,
-                        Attrs::new()
-                            .family(section.font.family)
-                            .weight(section.font.weight)
-                            .style(section.font.style)
-                            .color(section.color)
-                            .metadata(section.index),
-                    )
+                    let runs =
+                        self.fallbacks
+                            .runs(font_system, section.font.family, section.content);
+                    for (range, fallback) in runs {
+                        let family = match fallback {
+                            Some(i) => glyphon::Family::Name(&self.fallbacks.families[i]),
+                            None => section.font.family,
+                        };
+                        attrs_list.add_span(
+                            start + range.start..start + range.end,
+                            Attrs::new()
+                                .family(family)
+                                .weight(section.font.weight)
+                                .style(section.font.style)
+                                .color(section.color)
+                                .metadata(section.index),
+                        )
+                    }
                 }
                 let buffer_line = BufferLine::new(line_str, attrs_list, Shaping::Advanced);
                 buffer.lines.push(buffer_line);
@@ -724,3 +1269,22 @@ pub struct TextSystem {
     pub text_cache: Arc<Mutex<TextCache>>,
     pub swash_cache: SwashCache,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_ranges() {
+        let text = "Hello, wörld_1 !";
+        assert_eq!(word_range(text, 0), 0..5);
+        assert_eq!(word_range(text, 3), 0..5);
+        // Right after the word still counts as the word
+        assert_eq!(word_range(text, 5), 0..5);
+        assert_eq!(word_range(text, 8), 7..15);
+        assert_eq!(word_range(text, 15), 7..15);
+        // Anything else is just the one character
+        assert_eq!(word_range(text, 16), 16..17);
+        assert_eq!(word_range(text, text.len()), text.len()..text.len());
+    }
+}
@@ -0,0 +1,131 @@
+//! The window's title
+//!
+//! The title comes from a format string in the config where `{heading}` is the document's title
+//! (the front matter's `title:` or else its first top-level heading), `{file}` is the file's name,
+//! and `{path}` is where the file is relative to the root of its repo
+
+use comrak::nodes::{NodeCode, NodeHeading, NodeValue};
+use comrak::{parse_document, Arena, ComrakOptions};
+
+pub const DEFAULT_FORMAT: &str = "{heading} — {file} - Inlyne";
+
+/// The text of the first `# Heading` in `md`, skipping over any front matter
+pub fn first_heading(md: &str) -> Option<String> {
+    let arena = Arena::new();
+    let mut options = ComrakOptions::default();
+    options.extension.front_matter_delimiter = Some("---".to_owned());
+    let root = parse_document(&arena, md, &options);
+
+    let heading = root.descendants().find(|node| {
+        matches!(
+            node.data.borrow().value,
+            NodeValue::Heading(NodeHeading { level: 1, .. })
+        )
+    })?;
+    let mut text = String::new();
+    for node in heading.descendants() {
+        match &node.data.borrow().value {
+            NodeValue::Text(literal) | NodeValue::Code(NodeCode { literal, .. }) => {
+                text.push_str(literal)
+            }
+            NodeValue::SoftBreak | NodeValue::LineBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+enum Piece<'a> {
+    Literal(&'a str),
+    Value(Option<&'a str>),
+}
+
+/// Fills in the placeholders in `format`. A placeholder without a value gets dropped along with
+/// the text between it and the placeholder next to it, so that `{heading} — {file}` is just the
+/// file's name for a document without a heading
+pub fn format(format: &str, heading: Option<&str>, file: &str, path: &str) -> String {
+    let mut pieces = Vec::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        let value = rest[start..].find('}').and_then(|len| {
+            let value = match &rest[start + 1..start + len] {
+                "heading" => heading,
+                "file" => Some(file),
+                "path" => Some(path),
+                _ => return None,
+            };
+            Some((value, start + len + 1))
+        });
+        match value {
+            Some((value, end)) => {
+                pieces.push(Piece::Literal(&rest[..start]));
+                pieces.push(Piece::Value(value));
+                rest = &rest[end..];
+            }
+            // Not a placeholder, so it's kept as it was written
+            None => {
+                pieces.push(Piece::Literal(&rest[..=start]));
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    pieces.push(Piece::Literal(rest));
+
+    let is_value = |piece: &Piece| matches!(piece, Piece::Value(_));
+    let mut dropped = vec![false; pieces.len()];
+    for (i, piece) in pieces.iter().enumerate() {
+        if !matches!(piece, Piece::Value(None)) {
+            continue;
+        }
+        dropped[i] = true;
+        let after = pieces[i + 1..].iter().position(is_value).map(|j| i + 1 + j);
+        let before = pieces[..i].iter().rposition(is_value);
+        match (after, before) {
+            (Some(next), _) => dropped[i + 1..next].fill(true),
+            (None, Some(prev)) => dropped[prev + 1..i].fill(true),
+            (None, None) => {}
+        }
+    }
+
+    pieces
+        .iter()
+        .zip(dropped)
+        .filter(|(_, dropped)| !dropped)
+        .filter_map(|(piece, _)| match piece {
+            Piece::Literal(s) => Some(*s),
+            Piece::Value(value) => *value,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headings() {
+        let md = "---\ntitle: Notes\n---\nIntro\n\n## Not this\n\n# The `inlyne` *book*\n\n# Later";
+        assert_eq!(first_heading(md).as_deref(), Some("The inlyne book"));
+        assert_eq!(first_heading("Setext\n===\n").as_deref(), Some("Setext"));
+        assert_eq!(first_heading("## Only h2\n"), None);
+        assert_eq!(first_heading("#\n"), None);
+    }
+
+    #[test]
+    fn formatting() {
+        let title = |heading| format(DEFAULT_FORMAT, heading, "README.md", "inlyne/README.md");
+        assert_eq!(title(Some("Inlyne")), "Inlyne — README.md - Inlyne");
+        assert_eq!(title(None), "README.md - Inlyne");
+
+        assert_eq!(
+            format("Inlyne - {path} — {heading}", None, "a.md", "docs/a.md"),
+            "Inlyne - docs/a.md"
+        );
+        assert_eq!(format("{heading}", None, "a.md", "a.md"), "");
+        assert_eq!(
+            format("{nope} {file} {", Some("A"), "a.md", "a.md"),
+            "{nope} a.md {"
+        );
+    }
+}
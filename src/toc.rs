@@ -0,0 +1,162 @@
+//! The document's headings listed in a sidebar for jumping between them
+
+use std::collections::HashMap;
+
+use crate::positioner::Positioned;
+use crate::Element;
+
+/// How wide the sidebar gets before it's capped to a third of the window
+pub const WIDTH: f32 = 280.;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// 1 for `<h1>` through 6 for `<h6>`
+    pub level: u8,
+    pub title: String,
+    /// Where the heading is found in [`Positioner::anchors`](crate::positioner::Positioner)
+    pub anchor: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Toc {
+    pub headings: Vec<Heading>,
+}
+
+impl Toc {
+    /// Collects every heading in `elements` that isn't tucked away in a collapsed section
+    pub fn new(elements: &[Positioned<Element>]) -> Self {
+        let mut headings = Vec::new();
+        collect_headings(elements, &mut headings);
+        Self { headings }
+    }
+
+    /// The heading that the top of the view is currently in, if it's made it past one yet
+    pub fn current(&self, anchors: &HashMap<String, f32>, scroll_y: f32) -> Option<usize> {
+        // A little leeway so that jumping to a heading makes it the current one
+        let top = scroll_y + 1.;
+        self.headings
+            .iter()
+            .rposition(|heading| anchors.get(&heading.anchor).map_or(false, |&y| y <= top))
+    }
+
+    /// The heading at `index` along with the headings of the sections that it's nested in,
+    /// outermost first
+    pub fn breadcrumbs(&self, index: usize) -> Vec<&Heading> {
+        let mut crumbs = Vec::new();
+        let mut level = u8::MAX;
+        for heading in self.headings[..=index].iter().rev() {
+            if heading.level < level {
+                level = heading.level;
+                crumbs.push(heading);
+            }
+        }
+        crumbs.reverse();
+        crumbs
+    }
+
+    /// The first heading to list when only `rows` of them fit, keeping `current` in view
+    pub fn first_shown(&self, current: Option<usize>, rows: usize) -> usize {
+        let max_first = self.headings.len().saturating_sub(rows);
+        match current {
+            Some(current) => (current + 1).saturating_sub(rows).min(max_first),
+            None => 0,
+        }
+    }
+}
+
+fn collect_headings(elements: &[Positioned<Element>], headings: &mut Vec<Heading>) {
+    for element in elements {
+        match &element.inner {
+            Element::TextBox(text_box) => {
+                if let (Some(level), Some(anchor)) = (text_box.heading_level, &text_box.is_anchor) {
+                    let title: String = text_box.texts.iter().map(|t| t.text.as_str()).collect();
+                    headings.push(Heading {
+                        level,
+                        title: title.trim().to_owned(),
+                        anchor: anchor.to_owned(),
+                    });
+                }
+            }
+            Element::Row(row) => collect_headings(&row.elements, headings),
+            Element::Section(section) => {
+                if let Some(summary) = &*section.summary {
+                    collect_headings(std::slice::from_ref(summary), headings);
+                }
+                if !*section.hidden.borrow() {
+                    collect_headings(&section.elements, headings);
+                }
+            }
+            Element::Spacer(_) | Element::Image(_) | Element::Table(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{Text, TextBox};
+
+    fn heading(level: u8, title: &str) -> Positioned<Element> {
+        let mut text_box = TextBox::new(vec![Text::new(title.to_owned(), 1., [0.; 4])], 1.);
+        text_box.heading_level = Some(level);
+        text_box.set_anchor(format!("#{}", title.to_lowercase()));
+        Positioned::new(text_box)
+    }
+
+    fn toc() -> Toc {
+        let paragraph = TextBox::new(vec![Text::new("Text".to_owned(), 1., [0.; 4])], 1.);
+        Toc::new(&[
+            heading(1, "Title"),
+            Positioned::new(paragraph),
+            heading(2, "Usage"),
+            heading(2, "Install"),
+        ])
+    }
+
+    #[test]
+    fn lists_headings() {
+        let toc = toc();
+        let titles: Vec<_> = toc
+            .headings
+            .iter()
+            .map(|heading| (heading.level, heading.title.as_str()))
+            .collect();
+        assert_eq!(titles, [(1, "Title"), (2, "Usage"), (2, "Install")]);
+    }
+
+    #[test]
+    fn tracks_the_top_of_the_view() {
+        let toc = toc();
+        let anchors = HashMap::from([
+            ("#title".to_owned(), 10.),
+            ("#usage".to_owned(), 300.),
+            ("#install".to_owned(), 600.),
+        ]);
+        assert_eq!(toc.current(&anchors, 0.), None);
+        assert_eq!(toc.current(&anchors, 10.), Some(0));
+        assert_eq!(toc.current(&anchors, 450.), Some(1));
+        assert_eq!(toc.current(&anchors, 2_000.), Some(2));
+    }
+
+    #[test]
+    fn nested_sections() {
+        let toc = toc();
+        let titles = |index| -> Vec<_> {
+            toc.breadcrumbs(index)
+                .iter()
+                .map(|heading| heading.title.as_str())
+                .collect()
+        };
+        assert_eq!(titles(0), ["Title"]);
+        assert_eq!(titles(2), ["Title", "Install"]);
+    }
+
+    #[test]
+    fn current_stays_listed() {
+        let toc = toc();
+        assert_eq!(toc.first_shown(None, 2), 0);
+        assert_eq!(toc.first_shown(Some(1), 2), 0);
+        assert_eq!(toc.first_shown(Some(2), 2), 1);
+        assert_eq!(toc.first_shown(Some(2), 10), 0);
+    }
+}
@@ -0,0 +1,324 @@
+//! Turns raw touch events into gestures
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::utils::Point;
+
+use winit::event::TouchPhase;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PinchUpdate {
+    /// How much the zoom should be multiplied by
+    pub scale: f32,
+    /// Where the gesture is centered in screen coordinates
+    pub focal_point: Point,
+}
+
+/// How long a finger has to be held down before it counts as a long press
+const LONG_PRESS: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// A single finger dragging the document by this many pixels vertically
+    Scroll(f32),
+    /// The finger was lifted while scrolling
+    Fling,
+    Tap(Point),
+    LongPress(Point),
+    /// The finger is dragging after a long press
+    Select(Point),
+    Pinch(PinchUpdate),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FingerState {
+    /// Down, but it's not clear what it's doing yet
+    Pressed,
+    Scrolling,
+    Selecting,
+    /// Turned into a pinch, so it's ignored until all fingers are lifted
+    Cancelled,
+}
+
+#[derive(Debug)]
+struct Finger {
+    id: u64,
+    start: Point,
+    pressed_at: Instant,
+    last: Point,
+    state: FingerState,
+}
+
+#[derive(Debug, Default)]
+pub struct Gestures {
+    pinch: Pinch,
+    finger: Option<Finger>,
+}
+
+impl Gestures {
+    /// `slop` is how far a finger can wander before a press turns into a drag
+    pub fn touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        location: Point,
+        now: Instant,
+        slop: f32,
+    ) -> Option<Gesture> {
+        let pinch = self.pinch.touch(id, phase, location);
+        if self.pinch.touches.len() >= 2 {
+            if let Some(finger) = &mut self.finger {
+                finger.state = FingerState::Cancelled;
+            }
+            return pinch.map(Gesture::Pinch);
+        }
+
+        match phase {
+            TouchPhase::Started => {
+                self.finger = Some(Finger {
+                    id,
+                    start: location,
+                    pressed_at: now,
+                    last: location,
+                    state: FingerState::Pressed,
+                });
+                None
+            }
+            TouchPhase::Moved => {
+                let finger = self.finger.as_mut().filter(|finger| finger.id == id)?;
+                let delta_y = location.1 - finger.last.1;
+                finger.last = location;
+                match finger.state {
+                    FingerState::Pressed => {
+                        let (dx, dy) = (location.0 - finger.start.0, location.1 - finger.start.1);
+                        if (dx * dx + dy * dy).sqrt() > slop {
+                            finger.state = FingerState::Scrolling;
+                            // Catch up on everything within the slop too, so that the document
+                            // stays under the finger
+                            Some(Gesture::Scroll(dy))
+                        } else {
+                            None
+                        }
+                    }
+                    FingerState::Scrolling => Some(Gesture::Scroll(delta_y)),
+                    FingerState::Selecting => Some(Gesture::Select(location)),
+                    FingerState::Cancelled => None,
+                }
+            }
+            TouchPhase::Ended => {
+                if self.finger.as_ref()?.id != id {
+                    return None;
+                }
+                let finger = self.finger.take()?;
+                match finger.state {
+                    FingerState::Pressed => Some(Gesture::Tap(finger.start)),
+                    FingerState::Scrolling => Some(Gesture::Fling),
+                    FingerState::Selecting | FingerState::Cancelled => None,
+                }
+            }
+            TouchPhase::Cancelled => {
+                self.finger = None;
+                None
+            }
+        }
+    }
+
+    /// Checks if a finger has been held down long enough to be a long press
+    pub fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        let finger = self.finger.as_mut()?;
+        if finger.state == FingerState::Pressed && now >= finger.pressed_at + LONG_PRESS {
+            finger.state = FingerState::Selecting;
+            Some(Gesture::LongPress(finger.start))
+        } else {
+            None
+        }
+    }
+
+    /// When [`Gestures::poll()`] should next be called
+    pub fn long_press_deadline(&self) -> Option<Instant> {
+        let finger = self.finger.as_ref()?;
+        (finger.state == FingerState::Pressed).then_some(finger.pressed_at + LONG_PRESS)
+    }
+
+    pub fn is_selecting(&self) -> bool {
+        self.finger
+            .as_ref()
+            .map_or(false, |finger| finger.state == FingerState::Selecting)
+    }
+}
+
+/// Points to probe for something under a finger, starting from the center and working outwards
+///
+/// Fingers are a lot less precise than a cursor, so small targets like links get some extra room
+pub fn probe_points(center: Point, radius: f32) -> impl Iterator<Item = Point> {
+    const DIRECTIONS: [(f32, f32); 8] = [
+        (0.0, -1.0),
+        (0.0, 1.0),
+        (-1.0, 0.0),
+        (1.0, 0.0),
+        (-0.707, -0.707),
+        (0.707, -0.707),
+        (-0.707, 0.707),
+        (0.707, 0.707),
+    ];
+
+    let rings = [radius / 2.0, radius].into_iter().filter(|&r| r > 0.0);
+    std::iter::once(center).chain(rings.flat_map(move |r| {
+        DIRECTIONS
+            .iter()
+            .map(move |(dx, dy)| (center.0 + dx * r, center.1 + dy * r))
+    }))
+}
+
+/// Tracks two fingers pinching in or out
+#[derive(Debug, Default)]
+struct Pinch {
+    touches: BTreeMap<u64, Point>,
+    last_distance: Option<f32>,
+}
+
+impl Pinch {
+    fn touch(&mut self, id: u64, phase: TouchPhase, location: Point) -> Option<PinchUpdate> {
+        match phase {
+            TouchPhase::Started | TouchPhase::Moved => {
+                self.touches.insert(id, location);
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+            }
+        }
+
+        let mut fingers = self.touches.values();
+        let (Some(&a), Some(&b), None) = (fingers.next(), fingers.next(), fingers.next()) else {
+            self.last_distance = None;
+            return None;
+        };
+        let distance = ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+        let last_distance = self.last_distance.replace(distance);
+        if phase != TouchPhase::Moved {
+            return None;
+        }
+
+        let last_distance = last_distance.filter(|&d| d > 0.0)?;
+        Some(PinchUpdate {
+            scale: distance / last_distance,
+            focal_point: ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOP: f32 = 10.0;
+
+    fn at(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn tap_scroll_and_long_press() {
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        // Wiggling within the slop is still a tap
+        gestures.touch(0, TouchPhase::Started, (50.0, 50.0), start, SLOP);
+        assert_eq!(gestures.long_press_deadline(), Some(at(start, 500)));
+        let wiggle = gestures.touch(0, TouchPhase::Moved, (53.0, 54.0), at(start, 50), SLOP);
+        assert_eq!(wiggle, None);
+        let lifted = gestures.touch(0, TouchPhase::Ended, (53.0, 54.0), at(start, 100), SLOP);
+        assert_eq!(lifted, Some(Gesture::Tap((50.0, 50.0))));
+        assert_eq!(gestures.long_press_deadline(), None);
+
+        gestures.touch(1, TouchPhase::Started, (50.0, 50.0), start, SLOP);
+        let moved = gestures.touch(1, TouchPhase::Moved, (50.0, 70.0), at(start, 50), SLOP);
+        assert_eq!(moved, Some(Gesture::Scroll(20.0)));
+        let moved = gestures.touch(1, TouchPhase::Moved, (52.0, 65.0), at(start, 60), SLOP);
+        assert_eq!(moved, Some(Gesture::Scroll(-5.0)));
+        // Scrolling never turns into a long press
+        assert_eq!(gestures.poll(at(start, 1_000)), None);
+        let lifted = gestures.touch(1, TouchPhase::Ended, (52.0, 65.0), at(start, 1_000), SLOP);
+        assert_eq!(lifted, Some(Gesture::Fling));
+
+        gestures.touch(2, TouchPhase::Started, (10.0, 10.0), start, SLOP);
+        assert_eq!(gestures.poll(at(start, 499)), None);
+        assert_eq!(
+            gestures.poll(at(start, 500)),
+            Some(Gesture::LongPress((10.0, 10.0)))
+        );
+        assert!(gestures.is_selecting());
+        let moved = gestures.touch(2, TouchPhase::Moved, (80.0, 10.0), at(start, 600), SLOP);
+        assert_eq!(moved, Some(Gesture::Select((80.0, 10.0))));
+        let lifted = gestures.touch(2, TouchPhase::Ended, (80.0, 10.0), at(start, 700), SLOP);
+        assert_eq!(lifted, None);
+    }
+
+    #[test]
+    fn second_finger_cancels_tap() {
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+        gestures.touch(0, TouchPhase::Started, (0.0, 0.0), start, SLOP);
+        gestures.touch(1, TouchPhase::Started, (100.0, 0.0), start, SLOP);
+        let pinched = gestures.touch(1, TouchPhase::Moved, (200.0, 0.0), start, SLOP);
+        assert!(matches!(pinched, Some(Gesture::Pinch(_))));
+
+        assert_eq!(
+            gestures.touch(1, TouchPhase::Ended, (200.0, 0.0), start, SLOP),
+            None
+        );
+        assert_eq!(gestures.poll(at(start, 1_000)), None);
+        assert_eq!(
+            gestures.touch(0, TouchPhase::Moved, (0.0, 50.0), start, SLOP),
+            None
+        );
+        assert_eq!(
+            gestures.touch(0, TouchPhase::Ended, (0.0, 50.0), start, SLOP),
+            None
+        );
+    }
+
+    #[test]
+    fn probes_outwards() {
+        let points: Vec<_> = probe_points((10.0, 10.0), 8.0).collect();
+        assert_eq!(points.len(), 17);
+        assert_eq!(points[0], (10.0, 10.0));
+        assert_eq!(points[1], (10.0, 6.0));
+        assert_eq!(points[16], (10.0 + 0.707 * 8.0, 10.0 + 0.707 * 8.0));
+
+        assert_eq!(
+            probe_points((1.0, 2.0), 0.0).collect::<Vec<_>>(),
+            [(1.0, 2.0)]
+        );
+    }
+
+    #[test]
+    fn two_finger_pinch() {
+        let mut pinch = Pinch::default();
+        assert_eq!(pinch.touch(0, TouchPhase::Started, (100.0, 100.0)), None);
+        assert_eq!(pinch.touch(1, TouchPhase::Started, (200.0, 100.0)), None);
+
+        // Spreading the fingers apart zooms in around the middle of them
+        assert_eq!(
+            pinch.touch(1, TouchPhase::Moved, (300.0, 100.0)),
+            Some(PinchUpdate {
+                scale: 2.0,
+                focal_point: (200.0, 100.0),
+            })
+        );
+        let update = pinch.touch(0, TouchPhase::Moved, (200.0, 100.0)).unwrap();
+        assert_eq!(update.scale, 0.5);
+
+        // A third finger isn't a pinch anymore
+        assert_eq!(pinch.touch(2, TouchPhase::Started, (0.0, 0.0)), None);
+        assert_eq!(pinch.touch(2, TouchPhase::Moved, (10.0, 0.0)), None);
+        assert_eq!(pinch.touch(2, TouchPhase::Ended, (10.0, 0.0)), None);
+        // and picking back up doesn't jump based on the old distance
+        let update = pinch.touch(0, TouchPhase::Moved, (250.0, 100.0)).unwrap();
+        assert_eq!(update.scale, 0.5);
+
+        pinch.touch(0, TouchPhase::Ended, (250.0, 100.0));
+        assert_eq!(pinch.touch(1, TouchPhase::Moved, (400.0, 100.0)), None);
+    }
+}
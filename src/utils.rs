@@ -1,26 +1,40 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io;
+use std::path::Path;
 use std::sync::{Arc, Mutex, OnceLock};
 
+use crate::callout;
+use crate::chart;
+use crate::crossref;
+use crate::diagram;
+use crate::diff;
 use crate::image::ImageData;
+use crate::math;
 
 use comrak::adapters::SyntaxHighlighterAdapter;
+use comrak::arena_tree::Node;
+use comrak::nodes::{Ast, AstNode, NodeCodeBlock, NodeDescriptionItem, NodeHtmlBlock, NodeValue};
 use comrak::plugins::syntect::{SyntectAdapter, SyntectAdapterBuilder};
-use comrak::{markdown_to_html_with_plugins, ComrakOptions};
+use comrak::{format_html, format_html_with_plugins, parse_document, Arena, ComrakOptions};
 use indexmap::IndexMap;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, ClientBuilder};
 use serde::Deserialize;
 use syntect::highlighting::{Theme as SyntectTheme, ThemeSet as SyntectThemeSet};
 use syntect::parsing::SyntaxSet;
 use winit::window::CursorIcon;
 
 pub fn client() -> Client {
+    client_builder().build().unwrap()
+}
+
+pub fn client_builder() -> ClientBuilder {
     const USER_AGENT: &str = concat!(
         "inlyne ",
         env!("CARGO_PKG_VERSION"),
         " https://github.com/trimental/inlyne"
     );
-    Client::builder().user_agent(USER_AGENT).build().unwrap()
+    Client::builder().user_agent(USER_AGENT)
 }
 
 pub(crate) fn default<T: Default>() -> T {
@@ -31,6 +45,14 @@ pub fn usize_in_mib(num: usize) -> f32 {
     num as f32 / 1_024.0 / 1_024.0
 }
 
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| {
+            ["md", "markdown"].contains(&ext.to_ascii_lowercase().as_str())
+        })
+}
+
 pub type Selection = ((f32, f32), (f32, f32));
 pub type Point = (f32, f32);
 pub type Size = (f32, f32);
@@ -49,7 +71,7 @@ impl Line {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Rect {
     pub pos: Point,
     pub size: Point,
@@ -97,6 +119,23 @@ impl Align {
     }
 }
 
+/// The side of the page that an image floats to, letting what follows go beside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Float {
+    Left,
+    Right,
+}
+
+impl Float {
+    pub fn from_align(align: Align) -> Option<Self> {
+        match align {
+            Align::Left => Some(Self::Left),
+            Align::Right => Some(Self::Right),
+            Align::Center => None,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct HoverInfo {
     pub cursor_icon: CursorIcon,
@@ -125,6 +164,15 @@ impl SyntaxHighlighterAdapter for CustomSyntectAdapter {
         code: &str,
     ) -> io::Result<()> {
         let norm_lang = lang.map(|l| l.split_once(',').map(|(lang, _)| lang).unwrap_or(l));
+        if let Some(inner) = norm_lang.and_then(diff::inner_language) {
+            // Highlighted as the language it's a diff of, which the markers would throw off
+            let (markers, stripped) = diff::strip_markers(code);
+            let mut highlighted = Vec::new();
+            self.0
+                .write_highlighted(&mut highlighted, Some(inner), &stripped)?;
+            let html = String::from_utf8_lossy(&highlighted);
+            return output.write_all(diff::restore_markers(&html, &markers).as_bytes());
+        }
         self.0.write_highlighted(output, norm_lang, code)
     }
 
@@ -145,17 +193,314 @@ impl SyntaxHighlighterAdapter for CustomSyntectAdapter {
     }
 }
 
-pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme) -> String {
+const SOURCE_LINE_MARKER: &str = "inlyne-sourcepos:";
+
+fn source_line_marker(line: usize) -> String {
+    format!("<!--{SOURCE_LINE_MARKER}{line}-->")
+}
+
+/// Reads the line number back out of the contents of a comment inserted by [`markdown_to_html`]
+pub fn parse_source_line_marker(comment: &str) -> Option<usize> {
+    comment.strip_prefix(SOURCE_LINE_MARKER)?.parse().ok()
+}
+
+fn line_start(source: &str, line: usize) -> Option<usize> {
+    match line.checked_sub(2) {
+        Some(newlines) => Some(source.match_indices('\n').nth(newlines)?.0 + 1),
+        None => Some(0),
+    }
+}
+
+/// The one-indexed `line` of `source`, if it has that many
+pub fn source_line(source: &str, line: usize) -> Option<&str> {
+    source[line_start(source, line)?..].lines().next()
+}
+
+/// Flips the task list checkbox on the one-indexed `line` of the markdown `source`
+///
+/// Gives `None` when that line isn't a task list item
+pub fn toggle_task(source: &str, line: usize) -> Option<String> {
+    let line_start = line_start(source, line)?;
+    let text = source[line_start..].lines().next()?;
+    let item = text.trim_start_matches(|c: char| c.is_whitespace() || c == '>');
+    let after_marker = match item.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let after_digits = item.trim_start_matches(|c: char| c.is_ascii_digit());
+            if after_digits.len() == item.len() {
+                return None;
+            }
+            after_digits.strip_prefix(['.', ')'])?
+        }
+    };
+    let task = after_marker.trim_start_matches([' ', '\t']);
+    if task.len() == after_marker.len() {
+        return None;
+    }
+    let mark = match task.get(..3)? {
+        "[ ]" => "x",
+        "[x]" | "[X]" => " ",
+        _ => return None,
+    };
+
+    let mark_start = line_start + text.len() - task.len() + 1;
+    let mut toggled = source.to_owned();
+    toggled.replace_range(mark_start..mark_start + 1, mark);
+    Some(toggled)
+}
+
+/// Swaps out chart code blocks for an image of the rendered chart
+///
+/// Blocks that fail to render are left as regular code blocks
+fn chart_html(block: &NodeCodeBlock, text_color: u32) -> Option<String> {
+    let lang = block.info.split([',', ' ']).next()?;
+    if !block.fenced || !chart::is_chart_lang(lang) {
+        return None;
+    }
+
+    match chart::render_cached(lang, &block.literal, text_color) {
+        Ok(path) => Some(centered_image_html(&path)),
+        Err(err) => {
+            tracing::warn!("Failed rendering {} chart: {:#}", lang, err);
+            None
+        }
+    }
+}
+
+/// Swaps out diagram code blocks like ```` ```mermaid ```` for an image of the drawn diagram
+///
+/// Blocks that fail to render, like when the program that draws them isn't installed, are left as
+/// regular code blocks
+fn diagram_html(block: &NodeCodeBlock, text_color: u32) -> Option<String> {
+    let lang = block.info.split([',', ' ']).next()?;
+    if !block.fenced || !diagram::is_diagram_lang(lang) {
+        return None;
+    }
+
+    match diagram::render_cached(lang, &block.literal, text_color) {
+        Ok(path) => Some(centered_image_html(&path)),
+        Err(err) => {
+            tracing::warn!("Failed rendering {} diagram: {:#}", lang, err);
+            None
+        }
+    }
+}
+
+fn centered_image_html(path: &Path) -> String {
+    let mut html = String::from("<p align=\"center\"><img src=\"");
+    html_escape::encode_double_quoted_attribute_to_string(path.to_string_lossy(), &mut html);
+    html.push_str("\"></p>\n");
+    html
+}
+
+/// Swaps out a node for some HTML that was already rendered from it
+pub fn replace_with_html<'a>(node: &'a AstNode<'a>, literal: String) {
+    node.data.borrow_mut().value = NodeValue::HtmlBlock(NodeHtmlBlock {
+        block_type: 6,
+        literal,
+    });
+    let children: Vec<_> = node.children().collect();
+    for child in children {
+        child.detach();
+    }
+}
+
+/// The character and length of the code fence that `line` opens, if it opens one
+pub fn opens_fence(line: &str) -> Option<(char, usize)> {
+    let fence_char = line.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let fence_len = line.len() - line.trim_start_matches(fence_char).len();
+    let info = &line[fence_len..];
+    (fence_len >= 3 && !(fence_char == '`' && info.contains('`')))
+        .then_some((fence_char, fence_len))
+}
+
+pub fn closes_fence(line: &str, fence_char: char, fence_len: usize) -> bool {
+    let rest = line.trim_start_matches(fence_char);
+    line.len() - rest.len() >= fence_len && rest.trim().is_empty()
+}
+
+/// Turns lines of terms followed by lines starting with `:` into a definition list. Comrak only
+/// does that when there's a blank line after the terms
+///
+/// ```markdown
+/// Apple
+/// : A red fruit
+/// ```
+fn compact_definition_list<'a>(arena: &'a Arena<AstNode<'a>>, paragraph: &'a AstNode<'a>) {
+    if !matches!(paragraph.data.borrow().value, NodeValue::Paragraph) {
+        return;
+    }
+    let mut lines = vec![Vec::new()];
+    for child in paragraph.children() {
+        match child.data.borrow().value {
+            NodeValue::SoftBreak => lines.push(Vec::new()),
+            _ => lines.last_mut().unwrap().push(child),
+        }
+    }
+    let is_definition = |line: &[&'a AstNode<'a>]| {
+        line.first().map_or(false, |first| {
+            matches!(&first.data.borrow().value, NodeValue::Text(text) if text.starts_with(':'))
+        })
+    };
+    let Some(first_definition) = lines.iter().position(|line| is_definition(line)) else {
+        return;
+    };
+    if first_definition == 0 {
+        return;
+    }
+
+    let node = |value, start| -> &'a AstNode<'a> {
+        arena.alloc(Node::new(RefCell::new(Ast::new(value, start))))
+    };
+    let start = paragraph.data.borrow().sourcepos.start;
+    let list = node(NodeValue::DescriptionList, start);
+    let item = node(
+        NodeValue::DescriptionItem(NodeDescriptionItem {
+            marker_offset: 0,
+            padding: 2,
+        }),
+        start,
+    );
+    list.append(item);
+    let mut current = item;
+    for (i, line) in lines.into_iter().enumerate() {
+        let line_start = line
+            .first()
+            .map_or(start, |first| first.data.borrow().sourcepos.start);
+        if i < first_definition {
+            // Each of the lines before the definitions is a term of its own
+            let term = node(NodeValue::DescriptionTerm, line_start);
+            current = node(NodeValue::Paragraph, line_start);
+            item.append(term);
+            term.append(current);
+        } else if is_definition(&line) {
+            if let NodeValue::Text(text) = &mut line[0].data.borrow_mut().value {
+                *text = text[1..].trim_start().to_owned();
+            }
+            let details = node(NodeValue::DescriptionDetails, line_start);
+            current = node(NodeValue::Paragraph, line_start);
+            item.append(details);
+            details.append(current);
+        } else {
+            // A line that carries on from the definition before it
+            current.append(node(NodeValue::SoftBreak, line_start));
+        }
+        for inline in line {
+            current.append(inline);
+        }
+    }
+    paragraph.insert_before(list);
+    paragraph.detach();
+}
+
+/// Turns an image followed by a line of emphasized text into a figure with the text as its caption
+///
+/// ```markdown
+/// ![A cat](cat.png)
+/// *Figure 1: Some cat*
+/// ```
+fn figure_html<'a>(paragraph: &'a AstNode<'a>, options: &ComrakOptions) -> Option<String> {
+    if !matches!(paragraph.data.borrow().value, NodeValue::Paragraph) {
+        return None;
+    }
+
+    let children: Vec<_> = paragraph
+        .children()
+        .filter(|child| {
+            !matches!(&child.data.borrow().value, NodeValue::Text(text) if text.trim().is_empty())
+        })
+        .collect();
+    let [image, line_break, caption] = children.as_slice() else {
+        return None;
+    };
+    let is_figure = matches!(image.data.borrow().value, NodeValue::Image(_))
+        && matches!(
+            line_break.data.borrow().value,
+            NodeValue::SoftBreak | NodeValue::LineBreak
+        )
+        && matches!(caption.data.borrow().value, NodeValue::Emph);
+    if !is_figure {
+        return None;
+    }
+
+    let mut html = Vec::from("<figure>");
+    format_html(image, options, &mut html).ok()?;
+    html.extend_from_slice(b"<figcaption>");
+    for child in caption.children() {
+        format_html(child, options, &mut html).ok()?;
+    }
+    html.extend_from_slice(b"</figcaption></figure>\n");
+    String::from_utf8(html).ok()
+}
+
+fn comrak_options() -> ComrakOptions {
     let mut options = ComrakOptions::default();
     options.extension.autolink = true;
     options.extension.table = true;
     options.extension.strikethrough = true;
     options.extension.tasklist = true;
-    // options.extension.footnotes = true;
+    options.extension.footnotes = true;
+    options.extension.description_lists = true;
     options.extension.front_matter_delimiter = Some("---".to_owned());
     options.extension.shortcodes = true;
     options.parse.smart = true;
     options.render.unsafe_ = true;
+    options.render.sourcepos = true;
+
+    options
+}
+
+/// How many top-level blocks go in the first chunk of HTML. Plenty to fill the first screen
+const FIRST_CHUNK_BLOCKS: usize = 32;
+/// Later chunks can be bigger since nothing is waiting on them to show up
+const CHUNK_BLOCKS: usize = 256;
+
+/// Which of the optional markdown extensions get applied
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarkdownOptions {
+    /// Number pandoc-crossref style labels and resolve references to them
+    pub cross_references: bool,
+    /// Turn straight quotes into curly ones, `--` and `---` into en and em dashes, and `...` into
+    /// an ellipsis
+    pub smart_punctuation: bool,
+    /// Show the YAML front matter as a table at the top instead of leaving it out
+    pub front_matter_table: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            cross_references: false,
+            smart_punctuation: true,
+            front_matter_table: false,
+        }
+    }
+}
+
+pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme, markdown: MarkdownOptions) -> String {
+    let mut htmlified = String::new();
+    markdown_to_html_chunked(md, syntax_theme, markdown, |chunk| {
+        htmlified.push_str(chunk)
+    });
+    htmlified
+}
+
+/// Like [`markdown_to_html()`], but the HTML is handed over a chunk of top-level blocks at a time,
+/// so the start of a large document can be shown before the rest is highlighted and rendered
+pub fn markdown_to_html_chunked(
+    md: &str,
+    syntax_theme: SyntectTheme,
+    markdown: MarkdownOptions,
+    mut on_chunk: impl FnMut(&str),
+) {
+    let mut options = comrak_options();
+    options.parse.smart = markdown.smart_punctuation;
+
+    // Charts and diagrams get drawn in the same color as code
+    let drawing_text_color = syntax_theme
+        .settings
+        .foreground
+        .map_or(0x333333, |c| u32::from_be_bytes([0, c.r, c.g, c.b]));
 
     // TODO(cosmic): gonna send a PR so that a plugin can pass in a single theme too
     let dummy_name = "theme";
@@ -178,49 +523,145 @@ pub fn markdown_to_html(md: &str, syntax_theme: SyntectTheme) -> String {
     let custom = CustomSyntectAdapter(adapter);
     plugins.render.codefence_syntax_highlighter = Some(&custom);
 
-    let htmlified = markdown_to_html_with_plugins(md, &options, &plugins);
-
-    // Comrak doesn't support converting the front matter to HTML, so we have to convert it to an
-    // HTML table ourselves. Front matter is found like so
+    let arena = Arena::new();
+    let marked = math::mark_math(md);
+    let marked = callout::mark_containers(&marked);
+    let root = parse_document(&arena, &marked, &options);
+    if markdown.cross_references {
+        crossref::resolve(&arena, root, md, &options);
+    }
+    let nodes: Vec<_> = root.descendants().collect();
+    for node in nodes {
+        compact_definition_list(&arena, node);
+    }
+    // Raw HTML blocks are the only blocks that don't get a `data-sourcepos` attribute, so we mark
+    // where they start with a comment instead
+    for node in root.descendants() {
+        callout::render(&arena, node);
+        math::render(node);
+        if let Some(literal) = figure_html(node, &options) {
+            replace_with_html(node, literal);
+        }
+        let mut ast = node.data.borrow_mut();
+        let line = ast.sourcepos.start.line;
+        if let NodeValue::CodeBlock(block) = &ast.value {
+            let drawing = chart_html(block, drawing_text_color)
+                .or_else(|| diagram_html(block, drawing_text_color));
+            if let Some(literal) = drawing {
+                ast.value = NodeValue::HtmlBlock(NodeHtmlBlock {
+                    block_type: 6,
+                    literal,
+                });
+            }
+        }
+        if let NodeValue::HtmlBlock(block) = &mut ast.value {
+            block.literal.insert_str(0, &source_line_marker(line));
+        }
+    }
+    // Comrak leaves the front matter out of the HTML, so we have to convert it to an HTML table
+    // ourselves when it's wanted. Front matter is found like so
     // ---
     // {YAML value}
     // ---
     // {Markdown}
-    let html_front_matter = if md.starts_with("---") {
-        let mut parts = md.split("---");
-        let _ = parts.next();
-        parts
-            .next()
-            .and_then(
-                |front_matter| match serde_yaml::from_str::<FrontMatter>(front_matter) {
-                    Ok(front_matter) => Some(front_matter.to_table()),
-                    Err(err) => {
-                        tracing::warn!(
-                            "Failed parsing front matter. Error: {}\n{}",
-                            err,
-                            front_matter
-                        );
-                        None
-                    }
-                },
-            )
-            .unwrap_or_default()
-    } else {
-        String::new()
-    };
+    let html_front_matter = front_matter(md)
+        .filter(|_| markdown.front_matter_table)
+        .and_then(
+            |front_matter| match serde_yaml::from_str::<FrontMatter>(front_matter) {
+                Ok(front_matter) => Some(front_matter.to_table()),
+                Err(err) => {
+                    tracing::warn!(
+                        "Failed parsing front matter. Error: {}\n{}",
+                        err,
+                        front_matter
+                    );
+                    None
+                }
+            },
+        )
+        .unwrap_or_default();
+    if !html_front_matter.is_empty() {
+        on_chunk(&html_front_matter);
+    }
+
+    // Footnote definitions get numbered and wrapped in one section as they're written, so they're
+    // moved under a document of their own to be written together at the end
+    let (footnotes, blocks): (Vec<_>, Vec<_>) = root
+        .children()
+        .partition(|node| matches!(node.data.borrow().value, NodeValue::FootnoteDefinition(_)));
+    let mut rest = blocks.as_slice();
+    let mut chunk_len = FIRST_CHUNK_BLOCKS;
+    let mut htmlified = Vec::new();
+    while !rest.is_empty() {
+        let (chunk, remaining) = rest.split_at(chunk_len.min(rest.len()));
+        htmlified.clear();
+        // Each block renders the same on its own as it would as part of the whole document
+        for block in chunk {
+            format_html_with_plugins(block, &options, &mut htmlified, &plugins)
+                .expect("Writing to a `Vec` is infallible");
+        }
+        on_chunk(std::str::from_utf8(&htmlified).expect("Comrak always emits valid UTF-8"));
+        rest = remaining;
+        chunk_len = CHUNK_BLOCKS;
+    }
+    if let Some(first) = footnotes.first() {
+        let start = first.data.borrow().sourcepos.start;
+        let section = arena.alloc(Node::new(RefCell::new(Ast::new(
+            NodeValue::Document,
+            start,
+        ))));
+        for footnote in footnotes {
+            section.append(footnote);
+        }
+        htmlified.clear();
+        format_html_with_plugins(section, &options, &mut htmlified, &plugins)
+            .expect("Writing to a `Vec` is infallible");
+        on_chunk(std::str::from_utf8(&htmlified).expect("Comrak always emits valid UTF-8"));
+    }
+}
+
+/// The YAML between the `---` lines at the very start of a document
+pub fn front_matter(md: &str) -> Option<&str> {
+    if !md.starts_with("---") {
+        return None;
+    }
+    md.split("---").nth(1)
+}
+
+/// The `title:` from the front matter, which the window gets named after
+pub fn front_matter_title(md: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Titled {
+        title: Option<String>,
+    }
 
-    format!("{}{}", html_front_matter, htmlified)
+    let front_matter = front_matter(md)?;
+    let title = serde_yaml::from_str::<Titled>(front_matter).ok()?.title?;
+    let title = title.trim();
+    (!title.is_empty()).then(|| title.to_owned())
 }
 
+/// Keys that pick settings for the document instead of being shown in the front matter's table
+const FRONT_MATTER_SETTINGS: &[&str] = &["line-numbers", "code-wrap"];
+
 #[derive(Deserialize, Debug)]
 struct FrontMatter(IndexMap<String, Cell>);
 
 impl FrontMatter {
     fn to_table(&self) -> String {
+        let cells: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(key, _)| !FRONT_MATTER_SETTINGS.contains(&key.as_str()))
+            .collect();
+        if cells.is_empty() {
+            return String::new();
+        }
+
         let mut table = String::from("<table>\n");
 
         table.push_str("<thead>\n<tr>\n");
-        for key in self.0.keys() {
+        for (key, _) in &cells {
             table.push_str("<th align=\"center\">");
             html_escape::encode_safe_to_string(key, &mut table);
             table.push_str("</th>\n");
@@ -228,7 +669,7 @@ impl FrontMatter {
         table.push_str("</tr>\n</thead>\n");
 
         table.push_str("<tbody>\n<tr>\n");
-        for cell in self.0.values() {
+        for (_, cell) in &cells {
             table.push_str("<td align=\"center\">");
             cell.render_into(&mut table);
             table.push_str("</td>\n");
@@ -244,6 +685,7 @@ impl FrontMatter {
 #[serde(untagged)]
 enum Cell {
     Str(String),
+    Bool(bool),
     Table(Vec<String>),
 }
 
@@ -253,6 +695,7 @@ impl Cell {
             Self::Str(s) => {
                 html_escape::encode_safe_to_string(s, buf);
             }
+            Self::Bool(b) => buf.push_str(if *b { "true" } else { "false" }),
             Self::Table(_v) => {
                 tracing::warn!("Nested tables aren't supported yet. Skipping");
                 buf.push_str("{Skipped nested table}");
@@ -260,3 +703,61 @@ impl Cell {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::color::Theme;
+
+    #[test]
+    fn front_matter_titles() {
+        let md = "---\ntitle: \"  Release notes \"\ndate: 2018-05-01\n---\n# Changes\n";
+        assert_eq!(front_matter_title(md).as_deref(), Some("Release notes"));
+        assert_eq!(front_matter_title("---\ntitle:\n---\n"), None);
+        assert_eq!(front_matter_title("# title: not front matter\n"), None);
+    }
+
+    #[test]
+    fn toggling_tasks() {
+        let md = "# Todo\n\n- [ ] first\n  * [x] nested\n> 2. [X] quoted\n- [] not a task\n-[ ] nor this";
+        let toggled = toggle_task(md, 3).unwrap();
+        assert_eq!(toggled.lines().nth(2), Some("- [x] first"));
+        let toggled = toggle_task(&toggled, 4).unwrap();
+        assert_eq!(toggled.lines().nth(3), Some("  * [ ] nested"));
+        let toggled = toggle_task(md, 5).unwrap();
+        assert_eq!(toggled.lines().nth(4), Some("> 2. [ ] quoted"));
+
+        for line in [0, 1, 2, 6, 7, 8] {
+            assert_eq!(toggle_task(md, line), None, "line {line}");
+        }
+
+        assert_eq!(source_line(md, 1), Some("# Todo"));
+        assert_eq!(source_line(md, 5), Some("> 2. [X] quoted"));
+        assert_eq!(source_line(md, 8), None);
+    }
+
+    #[test]
+    fn chunks_match_whole_document() {
+        let md: String = (0..FIRST_CHUNK_BLOCKS + CHUNK_BLOCKS + 10)
+            .map(|i| match i % 5 {
+                0 => format!("# Header {i}\n\n"),
+                1 => format!("Some *text* {i}\nacross lines\n\n"),
+                2 => format!("- item {i}\n  - nested\n\n"),
+                3 => format!("> quote {i}\n\n"),
+                _ => format!("| a | b |\n| - | - |\n| {i} | x |\n\n"),
+            })
+            .collect();
+        let theme = Theme::light_default().code_highlighter;
+
+        let mut chunks = Vec::new();
+        markdown_to_html_chunked(&md, theme, MarkdownOptions::default(), |chunk| {
+            chunks.push(chunk.to_owned())
+        });
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(
+            chunks.concat(),
+            comrak::markdown_to_html(&md, &comrak_options())
+        );
+    }
+}
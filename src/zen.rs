@@ -0,0 +1,21 @@
+//! Distraction-free reading, which clears everything but the document off of the screen
+
+/// How much further apart lines get spread
+pub const LINE_SPACING: f32 = 1.2;
+/// The widest that the document gets before the rest is left as margins, in logical pixels
+const PAGE_WIDTH: f32 = 720.;
+
+/// How things were before zen mode, so that they can be put back after
+#[derive(Debug)]
+pub struct Zen {
+    pub fullscreen: bool,
+    pub status_bar: bool,
+    pub minimap: bool,
+    pub scrollbar: bool,
+    pub page_width: f32,
+}
+
+/// Narrows `page_width` down to zen mode's, unless it's already narrower
+pub fn page_width(page_width: f32, hidpi_scale: f32) -> f32 {
+    page_width.min(PAGE_WIDTH * hidpi_scale)
+}